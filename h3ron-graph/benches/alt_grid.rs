@@ -0,0 +1,66 @@
+use std::convert::TryInto;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use geo_types::Coordinate;
+
+use h3ron::{H3Cell, Index};
+use h3ron_graph::algorithm::landmarks::{
+    compute_landmarks, LandmarkSet, LandmarkStrategy, ShortestPathAlt,
+};
+use h3ron_graph::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+fn build_grid_graph() -> (H3Cell, H3Cell, PreparedH3EdgeGraph<u32>) {
+    let center = H3Cell::from_coordinate(Coordinate::from((10.0, 50.0)), 8).unwrap();
+    let cells = center.grid_disk(80).unwrap();
+
+    let mut graph = H3EdgeGraph::new(8);
+    for cell in cells.iter() {
+        for edge in cell.directed_edges().unwrap().iter() {
+            // non-uniform weights to make the geographic distance a bad predictor of the cost
+            let weight = 1 + (edge.destination_cell().unwrap().h3index() % 7) as u32;
+            graph.add_edge(edge, weight).unwrap();
+        }
+    }
+
+    let ring = center.grid_ring_unsafe(75).unwrap();
+    let origin = ring.first().unwrap();
+    let destination = ring.iter().nth(ring.iter().count() / 2).unwrap();
+    (origin, destination, graph.try_into().unwrap())
+}
+
+fn route(
+    graph: &PreparedH3EdgeGraph<u32>,
+    origin: H3Cell,
+    destination: H3Cell,
+    landmarks: &LandmarkSet,
+) -> usize {
+    let (path, stats) = graph
+        .shortest_path_alt_with_stats(origin, destination, landmarks)
+        .unwrap();
+    assert!(path.is_some());
+    stats.num_settled_nodes
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let (origin, destination, graph) = build_grid_graph();
+
+    let mut group = c.benchmark_group("alt_grid");
+    group.sample_size(10);
+    for n_landmarks in [0, 8] {
+        let landmarks =
+            compute_landmarks(&graph, n_landmarks, LandmarkStrategy::FarthestCell).unwrap();
+        println!(
+            "{} landmarks: {} settled nodes",
+            n_landmarks,
+            route(&graph, origin, destination, &landmarks)
+        );
+
+        group.bench_function(format!("{} landmarks", n_landmarks), |b| {
+            b.iter(|| route(black_box(&graph), origin, destination, &landmarks))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -132,12 +132,87 @@ where
     Ok(parents.drain(..).collect())
 }
 
+/// follow the edges of the graph from `origin_cell` until all reachable cells are settled or
+/// the aggregated weights exceed `max_weight`.
+///
+/// Returns all traversed cells except the origin together with their weight and the
+/// edge leading to them.
+///
+/// This function does not make usage of longedges as these would skip the cells they contain.
+pub fn edge_dijkstra_predecessors<G, W>(
+    graph: &G,
+    origin_cell: &H3Cell,
+    max_weight: Option<W>,
+) -> Result<H3CellMap<(W, H3DirectedEdge)>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: Zero + Ord + Copy + Add,
+{
+    let mut to_see = BinaryHeap::new();
+    let mut parents: IndexMap<H3Cell, (W, Option<H3DirectedEdge>), RandomState> =
+        IndexMap::default();
+
+    to_see.push(SmallestHolder {
+        weight: W::zero(),
+        index: 0,
+    });
+    parents.insert(*origin_cell, (W::zero(), None));
+
+    while let Some(SmallestHolder { weight, index }) = to_see.pop() {
+        let (cell, (weight_from_parents, _)) = parents.get_index(index).unwrap();
+
+        // skip outdated entries of the binary heap.
+        if weight > *weight_from_parents {
+            continue;
+        }
+
+        for (succeeding_edge, succeeding_edge_value) in graph.get_edges_originating_from(cell)? {
+            let new_weight = weight + succeeding_edge_value.weight;
+            if let Some(max_weight) = max_weight {
+                if new_weight > max_weight {
+                    continue;
+                }
+            }
+
+            let n;
+            match parents.entry(succeeding_edge.destination_cell()?) {
+                Vacant(e) => {
+                    n = e.index();
+                    e.insert((new_weight, Some(succeeding_edge)));
+                }
+                Occupied(mut e) => {
+                    if e.get().0 > new_weight {
+                        n = e.index();
+                        e.insert((new_weight, Some(succeeding_edge)));
+                    } else {
+                        continue;
+                    }
+                }
+            }
+            to_see.push(SmallestHolder {
+                weight: new_weight,
+                index: n,
+            });
+        }
+    }
+    Ok(parents
+        .drain(..)
+        .filter_map(|(cell, (weight, edge))| edge.map(|edge| (cell, (weight, edge))))
+        .collect())
+}
+
 /// Dijkstra shortest path using h3 edges
 ///
+/// The search starts in the state of having reached `origin_cell` using the `arrival_edge`,
+/// so it does not leave the `origin_cell` using the reverse of that edge. All other uses of
+/// the reversed edge are unrestricted. An `arrival_edge` not leading to the `origin_cell`
+/// is ignored.
+///
 /// Adapted from the `run_dijkstra` function of the `pathfinding` crate.
 pub fn edge_dijkstra<'a, G, W>(
     graph: &'a G,
     origin_cell: &H3Cell,
+    arrival_edge: Option<H3DirectedEdge>,
     destinations: &H3Treemap<H3Cell>,
     num_destinations_to_reach: Option<usize>,
 ) -> Result<Vec<Path<W>>, Error>
@@ -145,6 +220,11 @@ where
     G: GetCellEdges<EdgeWeightType = W>,
     W: Zero + Ord + Copy + Add,
 {
+    let u_turn_edge = match arrival_edge {
+        Some(edge) if edge.destination_cell()? == *origin_cell => Some(edge.reversed()?),
+        _ => None,
+    };
+
     // this is the main exit condition. Stop after this many destinations have been reached or
     // the complete graph has been traversed.
     let num_destinations_to_reach = num_destinations_to_reach
@@ -184,6 +264,11 @@ where
         }
 
         for (succeeding_edge, succeeding_edge_value) in graph.get_edges_originating_from(cell)? {
+            // a longedge starts with the edge it is attached to, so it is skipped as well
+            if index == 0 && u_turn_edge == Some(succeeding_edge) {
+                continue;
+            }
+
             // use the longedge if it does not contain any destination. If it would
             // contain a destination we would "jump over" it when we would use the longedge.
             let (dijkstra_edge, new_weight) =
@@ -0,0 +1,691 @@
+//! Disjoint paths between two cells.
+//!
+//! Used to analyze the resilience of a network: Do `k` routes exist between two locations
+//! which do not share any edge - or stricter - any intermediate cell?
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::ops::Add;
+
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+
+use h3ron::collections::{H3CellMap, H3Treemap, HashSet};
+use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution};
+
+use crate::algorithm::path::{DirectedEdgePath, Path};
+use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+use crate::algorithm::NearestGraphNodes;
+use crate::error::Error;
+use crate::graph::node::NodeType;
+use crate::graph::{EdgeWeight, GetCellEdges, GetCellNode};
+
+/// What the paths must not share.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Disjointness {
+    /// The paths do not share any directed edge.
+    #[default]
+    EdgeDisjoint,
+
+    /// The paths do not share any cell except the origin and the destination.
+    CellDisjoint,
+}
+
+/// How the paths are found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DisjointPathsMode {
+    /// Route the shortest path, remove its edges or cells from the graph and repeat.
+    ///
+    /// This is fast as it only requires `k` shortest path queries, but an early path may
+    /// block all remaining paths even when a set of `k` disjoint paths exists.
+    #[default]
+    GreedyRemoval,
+
+    /// Successive shortest augmenting paths in a flow network with unit capacities.
+    ///
+    /// Finds the largest set of disjoint paths up to `k` and minimizes the sum of their
+    /// costs. The part of the graph reachable from the origin gets explored completely
+    /// and each augmentation is a Bellman-Ford search, so this is much more expensive
+    /// than [`DisjointPathsMode::GreedyRemoval`].
+    Flow,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DisjointPathsOptions {
+    pub disjointness: Disjointness,
+    pub mode: DisjointPathsMode,
+}
+
+/// Disjoint paths between an origin and a destination.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisjointPathSet<W> {
+    /// the paths ordered by their cost
+    pub paths: Vec<Path<W>>,
+
+    /// the number of requested paths
+    pub k: usize,
+}
+
+impl<W> DisjointPathSet<W> {
+    /// `true` when `k` disjoint paths have been found.
+    pub fn is_complete(&self) -> bool {
+        self.paths.len() >= self.k
+    }
+}
+
+pub trait DisjointPaths<W> {
+    /// Find up to `k` disjoint paths from `origin_cell` to `destination_cell`.
+    ///
+    /// Finding fewer than `k` paths is not an error, see [`DisjointPathSet::is_complete`].
+    /// With [`DisjointPathsMode::GreedyRemoval`] fewer paths may be found than exist.
+    ///
+    /// Longedges are expanded into their edges, so paths do not share an edge or cell
+    /// contained in different longedges.
+    fn disjoint_paths(
+        &self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        k: usize,
+        options: &DisjointPathsOptions,
+    ) -> Result<DisjointPathSet<W>, Error>;
+}
+
+impl<W, G> DisjointPaths<W> for G
+where
+    G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes,
+    W: PartialOrd + PartialEq + Add + Copy + Ord + Zero,
+{
+    fn disjoint_paths(
+        &self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        k: usize,
+        options: &DisjointPathsOptions,
+    ) -> Result<DisjointPathSet<W>, Error> {
+        if origin_cell == destination_cell {
+            return Err(Error::OriginIsDestination);
+        }
+        let mut paths = match options.mode {
+            DisjointPathsMode::GreedyRemoval => {
+                greedy_removal(self, origin_cell, destination_cell, k, options.disjointness)?
+            }
+            DisjointPathsMode::Flow => {
+                FlowNetwork::build(self, origin_cell, destination_cell, k, options.disjointness)?
+                    .disjoint_paths(k)?
+            }
+        };
+        paths.sort_by_key(|path| path.cost);
+        Ok(DisjointPathSet { paths, k })
+    }
+}
+
+fn greedy_removal<G, W>(
+    graph: &G,
+    origin_cell: H3Cell,
+    destination_cell: H3Cell,
+    k: usize,
+    disjointness: Disjointness,
+) -> Result<Vec<Path<W>>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes,
+    W: PartialOrd + PartialEq + Add + Copy + Ord + Zero,
+{
+    let mut overlay = WithoutElements {
+        graph,
+        edges: Default::default(),
+        cells: Default::default(),
+    };
+    let mut paths = Vec::with_capacity(k);
+    while paths.len() < k {
+        let path = match overlay.shortest_path(
+            origin_cell,
+            [destination_cell],
+            &DefaultShortestPathOptions::default(),
+        ) {
+            Ok(mut found) => match found.pop() {
+                Some(path) => path,
+                None => break,
+            },
+            Err(Error::DestinationsNotInGraph) => break,
+            Err(e) => return Err(e),
+        };
+
+        for edge in path.directed_edge_path.edges() {
+            overlay.edges.insert(*edge);
+            if disjointness == Disjointness::CellDisjoint {
+                let cell = edge.destination_cell()?;
+                if cell != destination_cell {
+                    overlay.cells.insert(cell);
+                }
+            }
+        }
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// view on a graph without a set of `edges` and `cells`.
+struct WithoutElements<'a, G> {
+    graph: &'a G,
+    edges: HashSet<H3DirectedEdge>,
+    cells: H3Treemap<H3Cell>,
+}
+
+impl<'a, G> WithoutElements<'a, G> {
+    fn contains_removed_edge<I>(&self, edges: I) -> bool
+    where
+        I: IntoIterator<Item = H3DirectedEdge>,
+    {
+        edges.into_iter().any(|edge| self.edges.contains(&edge))
+    }
+}
+
+impl<'a, G> GetCellEdges for WithoutElements<'a, G>
+where
+    G: GetCellEdges,
+{
+    type EdgeWeightType = G::EdgeWeightType;
+
+    fn get_edges_originating_from(
+        &self,
+        cell: &H3Cell,
+    ) -> Result<Vec<(H3DirectedEdge, EdgeWeight<Self::EdgeWeightType>)>, Error> {
+        if self.cells.contains(cell) {
+            return Ok(vec![]);
+        }
+        let found = self.graph.get_edges_originating_from(cell)?;
+        let mut remaining = Vec::with_capacity(found.len());
+        for (edge, edge_value) in found {
+            if self.edges.contains(&edge) || self.cells.contains(&edge.destination_cell()?) {
+                continue;
+            }
+
+            // remove the longedge when it contains any removed edge or cell
+            let longedge = match edge_value.longedge {
+                Some((longedge, longedge_weight))
+                    if longedge.is_disjoint(&self.cells)
+                        && !self.contains_removed_edge(longedge.h3edge_path()?) =>
+                {
+                    Some((longedge, longedge_weight))
+                }
+                _ => None,
+            };
+            remaining.push((
+                edge,
+                EdgeWeight {
+                    weight: edge_value.weight,
+                    longedge,
+                },
+            ));
+        }
+        Ok(remaining)
+    }
+}
+
+impl<'a, G> GetCellNode for WithoutElements<'a, G>
+where
+    G: GetCellNode,
+{
+    fn get_cell_node(&self, cell: &H3Cell) -> Option<NodeType> {
+        if self.cells.contains(cell) {
+            None
+        } else {
+            self.graph.get_cell_node(cell)
+        }
+    }
+}
+
+impl<'a, G> HasH3Resolution for WithoutElements<'a, G>
+where
+    G: HasH3Resolution,
+{
+    fn h3_resolution(&self) -> u8 {
+        self.graph.h3_resolution()
+    }
+}
+
+/// A cost which may become negative in the residual network, without requiring
+/// `W` to be signed. The value is `positive - negative`.
+#[derive(Clone, Copy)]
+struct SignedCost<W> {
+    positive: W,
+    negative: W,
+}
+
+impl<W> SignedCost<W>
+where
+    W: Add<Output = W> + Copy + Ord + Zero,
+{
+    fn zero() -> Self {
+        Self {
+            positive: W::zero(),
+            negative: W::zero(),
+        }
+    }
+
+    fn add_arc(&self, arc: &Arc<W>) -> Self {
+        if arc.is_reverse {
+            Self {
+                positive: self.positive,
+                negative: self.negative + arc.weight,
+            }
+        } else {
+            Self {
+                positive: self.positive + arc.weight,
+                negative: self.negative,
+            }
+        }
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.positive + other.negative).cmp(&(other.positive + self.negative))
+    }
+}
+
+struct Arc<W> {
+    to: usize,
+
+    /// index of the opposite arc in the arcs of `to`
+    opposite: usize,
+    capacity: usize,
+    weight: W,
+
+    /// arcs of the residual network which cancel the flow of their opposite arc
+    is_reverse: bool,
+
+    /// the edge of the graph. `None` for the arcs connecting the two nodes of a cell
+    edge: Option<H3DirectedEdge>,
+}
+
+/// Flow network with unit capacities on the edges of the graph.
+///
+/// Each cell is split into an incoming and an outgoing node connected by an arc. The
+/// capacity of that arc limits the number of paths passing through the cell.
+struct FlowNetwork<W> {
+    cells: Vec<H3Cell>,
+    arcs: Vec<Vec<Arc<W>>>,
+    source: usize,
+    sink: usize,
+}
+
+const fn in_node(cell_index: usize) -> usize {
+    cell_index * 2
+}
+
+const fn out_node(cell_index: usize) -> usize {
+    cell_index * 2 + 1
+}
+
+impl<W> FlowNetwork<W>
+where
+    W: Add<Output = W> + Copy + Ord + Zero,
+{
+    /// explore the graph reachable from `origin_cell`. Longedges are not used as they
+    /// consist of the same edges.
+    fn build<G>(
+        graph: &G,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        k: usize,
+        disjointness: Disjointness,
+    ) -> Result<Self, Error>
+    where
+        G: GetCellEdges<EdgeWeightType = W>,
+    {
+        let mut network = Self {
+            cells: vec![],
+            arcs: vec![],
+            source: 0,
+            sink: 0,
+        };
+        let mut cell_indexes = H3CellMap::default();
+        let mut to_visit = VecDeque::new();
+        let origin_index = network.add_cell(&mut cell_indexes, origin_cell);
+        to_visit.push_back(origin_cell);
+
+        while let Some(cell) = to_visit.pop_front() {
+            let cell_index = cell_indexes[&cell];
+            if cell == destination_cell {
+                continue;
+            }
+            for (edge, edge_value) in graph.get_edges_originating_from(&cell)? {
+                let next_cell = edge.destination_cell()?;
+                let next_index = match cell_indexes.get(&next_cell) {
+                    Some(next_index) => *next_index,
+                    None => {
+                        to_visit.push_back(next_cell);
+                        network.add_cell(&mut cell_indexes, next_cell)
+                    }
+                };
+                network.add_arc(
+                    out_node(cell_index),
+                    in_node(next_index),
+                    1,
+                    edge_value.weight,
+                    Some(edge),
+                );
+            }
+        }
+
+        for (cell_index, cell) in network.cells.clone().into_iter().enumerate() {
+            let capacity = if disjointness == Disjointness::CellDisjoint
+                && cell != origin_cell
+                && cell != destination_cell
+            {
+                1
+            } else {
+                k
+            };
+            network.add_arc(
+                in_node(cell_index),
+                out_node(cell_index),
+                capacity,
+                W::zero(),
+                None,
+            );
+        }
+
+        network.source = in_node(origin_index);
+        network.sink = cell_indexes
+            .get(&destination_cell)
+            .map_or(usize::MAX, |index| out_node(*index));
+        Ok(network)
+    }
+
+    fn add_cell(&mut self, cell_indexes: &mut H3CellMap<usize>, cell: H3Cell) -> usize {
+        let index = self.cells.len();
+        self.cells.push(cell);
+        self.arcs.push(vec![]);
+        self.arcs.push(vec![]);
+        cell_indexes.insert(cell, index);
+        index
+    }
+
+    fn add_arc(
+        &mut self,
+        from: usize,
+        to: usize,
+        capacity: usize,
+        weight: W,
+        edge: Option<H3DirectedEdge>,
+    ) {
+        let opposite_of_forward = self.arcs[to].len();
+        let opposite_of_reverse = self.arcs[from].len();
+        self.arcs[from].push(Arc {
+            to,
+            opposite: opposite_of_forward,
+            capacity,
+            weight,
+            is_reverse: false,
+            edge,
+        });
+        self.arcs[to].push(Arc {
+            to: from,
+            opposite: opposite_of_reverse,
+            capacity: 0,
+            weight,
+            is_reverse: true,
+            edge,
+        });
+    }
+
+    /// augment the flow along the shortest path in the residual network using
+    /// Bellman-Ford. Returns `false` when the sink is not reachable.
+    fn augment(&mut self) -> bool {
+        let num_nodes = self.arcs.len();
+        let mut costs: Vec<Option<SignedCost<W>>> = vec![None; num_nodes];
+        let mut predecessors: Vec<Option<(usize, usize)>> = vec![None; num_nodes];
+        let mut in_queue = vec![false; num_nodes];
+        let mut queue = VecDeque::new();
+
+        costs[self.source] = Some(SignedCost::zero());
+        queue.push_back(self.source);
+        while let Some(node) = queue.pop_front() {
+            in_queue[node] = false;
+            let cost = match costs[node] {
+                Some(cost) => cost,
+                None => continue,
+            };
+            for (arc_index, arc) in self.arcs[node].iter().enumerate() {
+                if arc.capacity == 0 {
+                    continue;
+                }
+                let new_cost = cost.add_arc(arc);
+                let improves =
+                    costs[arc.to].is_none_or(|old_cost| new_cost.cmp(&old_cost) == Ordering::Less);
+                if improves {
+                    costs[arc.to] = Some(new_cost);
+                    predecessors[arc.to] = Some((node, arc_index));
+                    if !in_queue[arc.to] {
+                        in_queue[arc.to] = true;
+                        queue.push_back(arc.to);
+                    }
+                }
+            }
+        }
+
+        if costs[self.sink].is_none() {
+            return false;
+        }
+        let mut node = self.sink;
+        while let Some((predecessor, arc_index)) = predecessors[node] {
+            let arc = &mut self.arcs[predecessor][arc_index];
+            arc.capacity -= 1;
+            let (to, opposite) = (arc.to, arc.opposite);
+            self.arcs[to][opposite].capacity += 1;
+            node = predecessor;
+        }
+        true
+    }
+
+    fn disjoint_paths(mut self, k: usize) -> Result<Vec<Path<W>>, Error> {
+        if self.sink == usize::MAX {
+            return Ok(vec![]);
+        }
+        let mut num_paths = 0;
+        while num_paths < k && self.augment() {
+            num_paths += 1;
+        }
+
+        // decompose the flow into paths. The edges carrying flow are the saturated
+        // forward arcs between different cells.
+        let mut used: HashSet<(usize, usize)> = Default::default();
+        let origin_cell = self.cells[self.source / 2];
+        let destination_index = self.sink / 2;
+        let mut paths = Vec::with_capacity(num_paths);
+        for _ in 0..num_paths {
+            let mut cell_index = self.source / 2;
+            let mut edges: Vec<H3DirectedEdge> = vec![];
+            let mut weights: Vec<W> = vec![];
+            let mut visited = vec![cell_index];
+            while cell_index != destination_index {
+                let node = out_node(cell_index);
+                let (arc_index, arc) = self.arcs[node]
+                    .iter()
+                    .enumerate()
+                    .find(|(arc_index, arc)| {
+                        !arc.is_reverse
+                            && arc.edge.is_some()
+                            && arc.capacity == 0
+                            && !used.contains(&(node, *arc_index))
+                    })
+                    .ok_or_else(|| {
+                        Error::UnknownWithMessage("inconsistent flow network".to_string())
+                    })?;
+                used.insert((node, arc_index));
+                cell_index = arc.to / 2;
+                edges.push(arc.edge.unwrap());
+                weights.push(arc.weight);
+
+                // remove cycles which may occur with edges having a weight of zero
+                if let Some(position) = visited.iter().position(|i| *i == cell_index) {
+                    visited.truncate(position + 1);
+                    edges.truncate(position);
+                    weights.truncate(position);
+                } else {
+                    visited.push(cell_index);
+                }
+            }
+
+            paths.push(Path {
+                origin_cell,
+                destination_cell: self.cells[destination_index],
+                cost: weights
+                    .into_iter()
+                    .fold(W::zero(), |cost, weight| cost + weight),
+                directed_edge_path: DirectedEdgePath::DirectedEdgeSequence(edges),
+            });
+        }
+        Ok(paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use h3ron::{H3Cell, HasH3Resolution};
+
+    use crate::algorithm::disjoint_paths::{
+        DisjointPaths, DisjointPathsMode, DisjointPathsOptions, Disjointness,
+    };
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    fn graph_from_edges(
+        cells: &[H3Cell],
+        edges: &[(usize, usize, u32)],
+    ) -> PreparedH3EdgeGraph<u32> {
+        let mut graph = H3EdgeGraph::new(cells[0].h3_resolution());
+        for (from, to, weight) in edges {
+            graph
+                .add_edge(cells[*from].directed_edge_to(cells[*to]).unwrap(), *weight)
+                .unwrap();
+        }
+        graph.try_into().unwrap()
+    }
+
+    fn options(disjointness: Disjointness, mode: DisjointPathsMode) -> DisjointPathsOptions {
+        DisjointPathsOptions { disjointness, mode }
+    }
+
+    /// a center cell and its neighbors ordered along the ring
+    fn cells_fixture() -> (H3Cell, Vec<H3Cell>) {
+        let center = H3Cell::from_coordinate((10.2, 45.6).into(), 8).unwrap();
+        let ring: Vec<_> = center.grid_ring_unsafe(1).unwrap().iter().collect();
+        for (i, cell) in ring.iter().enumerate() {
+            assert!(cell.directed_edge_to(ring[(i + 1) % ring.len()]).is_ok());
+        }
+        (center, ring)
+    }
+
+    #[test]
+    fn two_corridors() {
+        let (_, ring) = cells_fixture();
+        // two corridors around the unused center from ring[0] to ring[3]
+        let graph = graph_from_edges(
+            &ring,
+            &[
+                (0, 1, 1),
+                (1, 2, 1),
+                (2, 3, 1),
+                (0, 5, 2),
+                (5, 4, 2),
+                (4, 3, 2),
+            ],
+        );
+        for mode in [DisjointPathsMode::GreedyRemoval, DisjointPathsMode::Flow] {
+            let path_set = graph
+                .disjoint_paths(
+                    ring[0],
+                    ring[3],
+                    3,
+                    &options(Disjointness::CellDisjoint, mode),
+                )
+                .unwrap();
+            assert!(!path_set.is_complete());
+            assert_eq!(path_set.paths.len(), 2);
+            assert_eq!(path_set.paths[0].cost, 3);
+            assert_eq!(path_set.paths[1].cost, 6);
+            assert_eq!(
+                path_set.paths[1].directed_edge_path.cells().unwrap(),
+                vec![ring[0], ring[5], ring[4], ring[3]]
+            );
+        }
+    }
+
+    #[test]
+    fn greedy_trap() {
+        let (center, ring) = cells_fixture();
+        let cells = [ring[0], center, ring[1], ring[2]];
+        // the shortest path 0 -> 1 -> 2 -> 3 blocks both of the disjoint paths
+        // 0 -> 1 -> 3 and 0 -> 2 -> 3.
+        let graph = graph_from_edges(
+            &cells,
+            &[(0, 1, 1), (1, 2, 1), (2, 3, 1), (0, 2, 5), (1, 3, 5)],
+        );
+
+        let greedy = graph
+            .disjoint_paths(
+                cells[0],
+                cells[3],
+                2,
+                &options(Disjointness::EdgeDisjoint, DisjointPathsMode::GreedyRemoval),
+            )
+            .unwrap();
+        assert_eq!(greedy.paths.len(), 1);
+        assert_eq!(greedy.paths[0].cost, 3);
+
+        let flow = graph
+            .disjoint_paths(
+                cells[0],
+                cells[3],
+                2,
+                &options(Disjointness::EdgeDisjoint, DisjointPathsMode::Flow),
+            )
+            .unwrap();
+        assert!(flow.is_complete());
+        assert_eq!(flow.paths.len(), 2);
+        assert_eq!(flow.paths[0].cost, 6);
+        assert_eq!(flow.paths[1].cost, 6);
+    }
+
+    #[test]
+    fn edge_and_cell_disjointness_differ() {
+        let (center, ring) = cells_fixture();
+        let cells = [ring[0], ring[1], center, ring[3], ring[4]];
+        // both routes from 0 to 3 pass through the junction cell 2
+        let graph = graph_from_edges(
+            &cells,
+            &[
+                (0, 2, 1),
+                (0, 1, 1),
+                (1, 2, 1),
+                (2, 3, 1),
+                (2, 4, 1),
+                (4, 3, 1),
+            ],
+        );
+        for mode in [DisjointPathsMode::GreedyRemoval, DisjointPathsMode::Flow] {
+            let edge_disjoint = graph
+                .disjoint_paths(
+                    cells[0],
+                    cells[3],
+                    2,
+                    &options(Disjointness::EdgeDisjoint, mode),
+                )
+                .unwrap();
+            assert!(edge_disjoint.is_complete());
+
+            let cell_disjoint = graph
+                .disjoint_paths(
+                    cells[0],
+                    cells[3],
+                    2,
+                    &options(Disjointness::CellDisjoint, mode),
+                )
+                .unwrap();
+            assert!(!cell_disjoint.is_complete());
+            assert_eq!(cell_disjoint.paths.len(), 1);
+            assert_eq!(cell_disjoint.paths[0].cost, 2);
+        }
+    }
+}
@@ -0,0 +1,699 @@
+//! A* routing using the ALT (A*, Landmarks, Triangle inequality) heuristic.
+//!
+//! ALT precomputes the distances from and to a small set of landmark cells. During routing the
+//! triangle inequality provides a lower bound for the remaining distance to the destination, which
+//! is much tighter than a purely geometric heuristic - especially when the weights of the graph do not
+//! correlate well with the geographic distance, like travel times on road networks.
+//!
+//! See [Computing the Shortest Path: A* Search Meets Graph Theory](https://www.microsoft.com/en-us/research/publication/computing-the-shortest-path-a-search-meets-graph-theory/)
+//! by Goldberg and Harrelson.
+//!
+//! # Quantization
+//!
+//! To keep the memory requirements bounded, the distances are stored as `u32` values. A distance
+//! `d` is stored as `floor(d / scale)` where `scale` is chosen to make the largest finite
+//! distance found fit into the value range of `u32`. The heuristic accounts for the rounding, so
+//! it stays admissible and the found paths are exact shortest paths.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::ops::Add;
+
+use num_traits::{ToPrimitive, Zero};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use h3ron::collections::H3CellMap;
+use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution, Index};
+
+use crate::algorithm::path::{DirectedEdgePath, Path};
+use crate::error::Error;
+use crate::graph::{GetCellEdges, IterateCellNodes, PreparedH3EdgeGraph};
+
+/// value used for cells which are not reachable from/to a landmark
+const UNREACHABLE: u32 = u32::MAX;
+
+/// Strategy to select the landmarks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LandmarkStrategy {
+    /// Iteratively select the cell farthest away from all already selected landmarks. This
+    /// usually results in landmarks at the periphery of the graph which give the best bounds.
+    FarthestCell,
+
+    /// Select random cells using the given seed.
+    Random { seed: u64 },
+}
+
+/// Identifies the graph a [`LandmarkSet`] was built for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GraphFingerprint {
+    pub h3_resolution: u8,
+    pub num_nodes: usize,
+    pub num_edges: usize,
+
+    /// order-independent hash over all edges of the graph and their weights
+    pub weights_hash: u64,
+}
+
+impl GraphFingerprint {
+    fn from_graph<W>(graph: &PreparedH3EdgeGraph<W>) -> Self
+    where
+        W: Copy + ToPrimitive,
+    {
+        let mut num_edges = 0;
+        let mut weights_hash = 0_u64;
+        for (edge, edge_weight) in graph.iter_edges() {
+            let weight_bits = edge_weight.weight.to_f64().map_or(u64::MAX, f64::to_bits);
+            let mut state = edge.h3index() ^ weight_bits.rotate_left(32);
+            weights_hash = weights_hash.wrapping_add(splitmix64(&mut state));
+            num_edges += 1;
+        }
+        Self {
+            h3_resolution: graph.h3_resolution(),
+            num_nodes: graph.iter_cell_nodes().len(),
+            num_edges,
+            weights_hash,
+        }
+    }
+
+    /// the fingerprint of the `graph`, only computed once per graph.
+    fn of_graph<W>(graph: &PreparedH3EdgeGraph<W>) -> &Self
+    where
+        W: Copy + ToPrimitive,
+    {
+        graph
+            .landmark_fingerprint
+            .get_or_init(|| Self::from_graph(graph))
+    }
+}
+
+/// Precomputed distances from and to a set of landmark cells.
+///
+/// Created using [`compute_landmarks`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LandmarkSet {
+    fingerprint: GraphFingerprint,
+    landmarks: Vec<H3Cell>,
+
+    /// sorted cells of the graph. The position of a cell is used to index the distance vectors.
+    cells: Vec<H3Cell>,
+
+    /// the scale used to quantize the distances.
+    scale: f64,
+
+    /// quantized distances from each landmark to all `cells`.
+    distances_from_landmark: Vec<Vec<u32>>,
+
+    /// quantized distances from all `cells` to each landmark.
+    distances_to_landmark: Vec<Vec<u32>>,
+}
+
+impl LandmarkSet {
+    /// the selected landmark cells
+    pub fn landmarks(&self) -> &[H3Cell] {
+        &self.landmarks
+    }
+
+    pub const fn fingerprint(&self) -> &GraphFingerprint {
+        &self.fingerprint
+    }
+
+    /// the scale used to quantize the stored distances
+    pub const fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Validate this set has been built for the given `graph`.
+    ///
+    /// Besides the resolution and the number of nodes and edges, the fingerprint includes a hash
+    /// over all edges and their weights. It is computed once per graph when it is used the first
+    /// time, so [`ShortestPathAlt::shortest_path_alt`] can validate the set on each call.
+    pub fn validate<W>(&self, graph: &PreparedH3EdgeGraph<W>) -> Result<(), Error>
+    where
+        W: Copy + ToPrimitive,
+    {
+        if GraphFingerprint::of_graph(graph) == &self.fingerprint {
+            Ok(())
+        } else {
+            Err(Error::LandmarksGraphMismatch)
+        }
+    }
+
+    fn position(&self, cell: &H3Cell) -> Option<usize> {
+        self.cells.binary_search(cell).ok()
+    }
+
+    /// lower bound of the distance between the cells at the positions `from` and `to`.
+    fn lower_bound(&self, from: Option<usize>, to: Option<usize>) -> f64 {
+        let (from, to) = match (from, to) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return 0.0,
+        };
+
+        let mut bound = 0u32;
+        for (distances_from, distances_to) in self
+            .distances_from_landmark
+            .iter()
+            .zip(self.distances_to_landmark.iter())
+        {
+            // d(L, to) - d(L, from) <= d(from, to)
+            bound = bound.max(quantized_difference(
+                distances_from[to],
+                distances_from[from],
+            ));
+            // d(from, L) - d(to, L) <= d(from, to)
+            bound = bound.max(quantized_difference(distances_to[from], distances_to[to]));
+        }
+        f64::from(bound) * self.scale
+    }
+}
+
+/// lower bound of `a - b` in quantized units, accounting for the rounding of both values.
+fn quantized_difference(a: u32, b: u32) -> u32 {
+    if a == UNREACHABLE || b == UNREACHABLE {
+        0
+    } else {
+        a.saturating_sub(b).saturating_sub(1)
+    }
+}
+
+/// Select `n_landmarks` landmarks and compute the distances from and to them.
+///
+/// The distances for the landmarks are computed in parallel.
+pub fn compute_landmarks<W>(
+    graph: &PreparedH3EdgeGraph<W>,
+    n_landmarks: usize,
+    strategy: LandmarkStrategy,
+) -> Result<LandmarkSet, Error>
+where
+    W: Copy + ToPrimitive + Send + Sync,
+{
+    let fingerprint = GraphFingerprint::of_graph(graph).clone();
+    let adjacency = Adjacency::from_graph(graph)?;
+
+    let landmark_positions = match strategy {
+        LandmarkStrategy::FarthestCell => adjacency.select_farthest(n_landmarks),
+        LandmarkStrategy::Random { seed } => adjacency.select_random(n_landmarks, seed),
+    };
+
+    let distances: Vec<_> = landmark_positions
+        .par_iter()
+        .map(|position| {
+            (
+                dijkstra(&adjacency.forward, *position),
+                dijkstra(&adjacency.reverse, *position),
+            )
+        })
+        .collect();
+
+    let max_distance = distances
+        .iter()
+        .flat_map(|(from, to)| from.iter().chain(to.iter()))
+        .filter(|d| d.is_finite())
+        .fold(0.0f64, |acc, d| acc.max(*d));
+    let scale = if max_distance > 0.0 {
+        max_distance / f64::from(UNREACHABLE - 1)
+    } else {
+        1.0
+    };
+    let quantize = |values: Vec<f64>| -> Vec<u32> {
+        values
+            .into_iter()
+            .map(|d| {
+                if d.is_finite() {
+                    ((d / scale).floor() as u32).min(UNREACHABLE - 1)
+                } else {
+                    UNREACHABLE
+                }
+            })
+            .collect()
+    };
+
+    let mut distances_from_landmark = Vec::with_capacity(distances.len());
+    let mut distances_to_landmark = Vec::with_capacity(distances.len());
+    for (from, to) in distances {
+        distances_from_landmark.push(quantize(from));
+        distances_to_landmark.push(quantize(to));
+    }
+
+    Ok(LandmarkSet {
+        fingerprint,
+        landmarks: landmark_positions
+            .iter()
+            .map(|position| adjacency.cells[*position])
+            .collect(),
+        cells: adjacency.cells,
+        scale,
+        distances_from_landmark,
+        distances_to_landmark,
+    })
+}
+
+/// Statistics of a single A* search.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// number of cells taken from the priority queue and expanded.
+    pub num_settled_nodes: usize,
+}
+
+/// A* shortest path routing using a [`LandmarkSet`].
+pub trait ShortestPathAlt<W> {
+    /// Find the shortest path from `origin_cell` to `destination_cell`.
+    ///
+    /// Returns `None` when the destination can not be reached.
+    fn shortest_path_alt(
+        &self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        landmarks: &LandmarkSet,
+    ) -> Result<Option<Path<W>>, Error> {
+        self.shortest_path_alt_with_stats(origin_cell, destination_cell, landmarks)
+            .map(|(path, _)| path)
+    }
+
+    /// Same as [`ShortestPathAlt::shortest_path_alt`], but additionally returns statistics
+    /// of the search.
+    fn shortest_path_alt_with_stats(
+        &self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        landmarks: &LandmarkSet,
+    ) -> Result<(Option<Path<W>>, SearchStats), Error>;
+}
+
+impl<W> ShortestPathAlt<W> for PreparedH3EdgeGraph<W>
+where
+    W: PartialOrd + Add<Output = W> + Copy + Ord + Zero + ToPrimitive,
+{
+    fn shortest_path_alt_with_stats(
+        &self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        landmarks: &LandmarkSet,
+    ) -> Result<(Option<Path<W>>, SearchStats), Error> {
+        landmarks.validate(self)?;
+        astar(self, origin_cell, destination_cell, landmarks)
+    }
+}
+
+fn astar<G, W>(
+    graph: &G,
+    origin_cell: H3Cell,
+    destination_cell: H3Cell,
+    landmarks: &LandmarkSet,
+) -> Result<(Option<Path<W>>, SearchStats), Error>
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: PartialOrd + Add<Output = W> + Copy + Ord + Zero + ToPrimitive,
+{
+    let destination_position = landmarks.position(&destination_cell);
+    let mut stats = SearchStats::default();
+
+    // best known cost and the edge leading to each cell
+    let mut best: H3CellMap<(W, Option<H3DirectedEdge>)> = Default::default();
+    let mut queue = BinaryHeap::new();
+
+    best.insert(origin_cell, (W::zero(), None));
+    queue.push(QueueItem {
+        priority: landmarks.lower_bound(landmarks.position(&origin_cell), destination_position),
+        payload: (W::zero(), origin_cell),
+    });
+
+    while let Some(QueueItem {
+        payload: (cost, cell),
+        ..
+    }) = queue.pop()
+    {
+        if let Some((best_cost, _)) = best.get(&cell) {
+            if cost > *best_cost {
+                // outdated queue entry
+                continue;
+            }
+        }
+        stats.num_settled_nodes += 1;
+
+        if cell == destination_cell {
+            let path = build_path(&best, origin_cell, destination_cell, cost)?;
+            return Ok((Some(path), stats));
+        }
+
+        for (edge, edge_weight) in graph.get_edges_originating_from(&cell)? {
+            let next_cell = edge.destination_cell()?;
+            let next_cost = cost + edge_weight.weight;
+            let improves = best
+                .get(&next_cell)
+                .is_none_or(|(known_cost, _)| next_cost < *known_cost);
+            if improves {
+                best.insert(next_cell, (next_cost, Some(edge)));
+                queue.push(QueueItem {
+                    priority: next_cost.to_f64().unwrap_or(f64::INFINITY)
+                        + landmarks
+                            .lower_bound(landmarks.position(&next_cell), destination_position),
+                    payload: (next_cost, next_cell),
+                });
+            }
+        }
+    }
+    Ok((None, stats))
+}
+
+fn build_path<W: Copy>(
+    best: &H3CellMap<(W, Option<H3DirectedEdge>)>,
+    origin_cell: H3Cell,
+    destination_cell: H3Cell,
+    cost: W,
+) -> Result<Path<W>, Error> {
+    let mut edges = Vec::new();
+    let mut current = destination_cell;
+    while let Some((_, Some(edge))) = best.get(&current) {
+        edges.push(*edge);
+        current = edge.origin_cell()?;
+    }
+    edges.reverse();
+
+    Ok(Path {
+        origin_cell,
+        destination_cell,
+        cost,
+        directed_edge_path: if edges.is_empty() {
+            DirectedEdgePath::OriginIsDestination(origin_cell)
+        } else {
+            DirectedEdgePath::DirectedEdgeSequence(edges)
+        },
+    })
+}
+
+/// Item of a priority queue, ordered by the smallest `priority` first.
+pub(crate) struct QueueItem<T> {
+    pub(crate) priority: f64,
+    pub(crate) payload: T,
+}
+
+impl<T> PartialEq for QueueItem<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for QueueItem<T> {}
+
+impl<T> PartialOrd for QueueItem<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for QueueItem<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed to turn the BinaryHeap into a min-heap
+        other.priority.total_cmp(&self.priority)
+    }
+}
+
+/// The graph as adjacency lists using the positions in `cells`.
+struct Adjacency {
+    cells: Vec<H3Cell>,
+    forward: Vec<Vec<(usize, f64)>>,
+    reverse: Vec<Vec<(usize, f64)>>,
+}
+
+impl Adjacency {
+    fn from_graph<W>(graph: &PreparedH3EdgeGraph<W>) -> Result<Self, Error>
+    where
+        W: Copy + ToPrimitive,
+    {
+        let mut cells: Vec<_> = graph.iter_cell_nodes().map(|(cell, _)| *cell).collect();
+        cells.sort_unstable();
+
+        let mut forward = vec![Vec::new(); cells.len()];
+        let mut reverse = vec![Vec::new(); cells.len()];
+        for (position, cell) in cells.iter().enumerate() {
+            for (edge, edge_weight) in graph.get_edges_originating_from(cell)? {
+                let weight = edge_weight.weight.to_f64().ok_or_else(|| {
+                    Error::Other("edge weight is not representable as f64".to_string())
+                })?;
+                if let Ok(destination_position) = cells.binary_search(&edge.destination_cell()?) {
+                    forward[position].push((destination_position, weight));
+                    reverse[destination_position].push((position, weight));
+                }
+            }
+        }
+        Ok(Self {
+            cells,
+            forward,
+            reverse,
+        })
+    }
+
+    fn select_farthest(&self, n_landmarks: usize) -> Vec<usize> {
+        let n_landmarks = n_landmarks.min(self.cells.len());
+        let mut landmarks = Vec::with_capacity(n_landmarks);
+        if n_landmarks == 0 {
+            return landmarks;
+        }
+
+        // start with the cell farthest away from an arbitrary cell
+        let mut min_distances = dijkstra(&self.forward, 0);
+        while landmarks.len() < n_landmarks {
+            let next = min_distances
+                .iter()
+                .enumerate()
+                .filter(|(position, d)| d.is_finite() && !landmarks.contains(position))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(position, _)| position)
+                // unreachable parts of the graph
+                .or_else(|| (0..self.cells.len()).find(|position| !landmarks.contains(position)));
+
+            let next = match next {
+                Some(next) => next,
+                None => break,
+            };
+            if landmarks.is_empty() {
+                min_distances = dijkstra(&self.forward, next);
+            } else {
+                for (min_distance, d) in min_distances.iter_mut().zip(dijkstra(&self.forward, next))
+                {
+                    *min_distance = min_distance.min(d);
+                }
+            }
+            landmarks.push(next);
+        }
+        landmarks
+    }
+
+    fn select_random(&self, n_landmarks: usize, seed: u64) -> Vec<usize> {
+        let n_landmarks = n_landmarks.min(self.cells.len());
+        let mut landmarks = Vec::with_capacity(n_landmarks);
+        let mut state = seed;
+        while landmarks.len() < n_landmarks {
+            let position = (splitmix64(&mut state) % self.cells.len() as u64) as usize;
+            if !landmarks.contains(&position) {
+                landmarks.push(position);
+            }
+        }
+        landmarks
+    }
+}
+
+/// distances from the cell at `origin` to all other cells. Unreachable cells
+/// have a distance of infinity.
+fn dijkstra(adjacency: &[Vec<(usize, f64)>], origin: usize) -> Vec<f64> {
+    let mut distances = vec![f64::INFINITY; adjacency.len()];
+    let mut queue = BinaryHeap::new();
+    distances[origin] = 0.0;
+    queue.push(QueueItem {
+        priority: 0.0,
+        payload: origin,
+    });
+
+    while let Some(QueueItem {
+        priority: distance,
+        payload: position,
+    }) = queue.pop()
+    {
+        if distance > distances[position] {
+            continue;
+        }
+        for (next, weight) in adjacency[position].iter() {
+            let next_distance = distance + weight;
+            if next_distance < distances[*next] {
+                distances[*next] = next_distance;
+                queue.push(QueueItem {
+                    priority: next_distance,
+                    payload: *next,
+                });
+            }
+        }
+    }
+    distances
+}
+
+/// [SplitMix64](https://prng.di.unimi.it/splitmix64.c) pseudo random number generator
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use geo_types::Coordinate;
+
+    use h3ron::H3Cell;
+
+    use crate::algorithm::landmarks::{
+        compute_landmarks, splitmix64, LandmarkStrategy, ShortestPathAlt,
+    };
+    use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+    use crate::error::Error;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    /// random graph on a grid disk with random weights and some edges missing
+    fn random_graph(seed: u64) -> (Vec<H3Cell>, PreparedH3EdgeGraph<u32>) {
+        let mut state = seed;
+        let cells: Vec<_> = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8)
+            .unwrap()
+            .grid_disk(6)
+            .unwrap()
+            .iter()
+            .collect();
+
+        let mut graph = H3EdgeGraph::new(8);
+        for cell in cells.iter() {
+            for edge in cell.directed_edges().unwrap().iter() {
+                if splitmix64(&mut state) % 10 < 8 {
+                    let weight = 1 + (splitmix64(&mut state) % 20) as u32;
+                    graph.add_edge(edge, weight).unwrap();
+                }
+            }
+        }
+        (cells, graph.try_into().unwrap())
+    }
+
+    #[test]
+    fn alt_equals_dijkstra() {
+        for seed in 0..3 {
+            let (cells, graph) = random_graph(seed);
+            for strategy in [
+                LandmarkStrategy::FarthestCell,
+                LandmarkStrategy::Random { seed },
+            ] {
+                let landmarks = compute_landmarks(&graph, 4, strategy).unwrap();
+                assert_eq!(landmarks.landmarks().len(), 4);
+                landmarks.validate(&graph).unwrap();
+
+                let mut state = seed;
+                for _ in 0..20 {
+                    let origin = cells[(splitmix64(&mut state) % cells.len() as u64) as usize];
+                    let destination = cells[(splitmix64(&mut state) % cells.len() as u64) as usize];
+                    if origin == destination {
+                        continue;
+                    }
+
+                    let dijkstra_cost = match graph.shortest_path(
+                        origin,
+                        [destination],
+                        &DefaultShortestPathOptions::default(),
+                    ) {
+                        Ok(paths) => paths.first().map(|path| path.cost),
+                        Err(Error::DestinationsNotInGraph) => None,
+                        Err(e) => panic!("routing failed: {:?}", e),
+                    };
+                    let alt_path = graph
+                        .shortest_path_alt(origin, destination, &landmarks)
+                        .unwrap();
+                    if let Some(alt_path) = alt_path.as_ref() {
+                        assert_eq!(alt_path.directed_edge_path.origin_cell().unwrap(), origin);
+                        assert_eq!(
+                            alt_path.directed_edge_path.destination_cell().unwrap(),
+                            destination
+                        );
+                    }
+                    assert_eq!(alt_path.map(|path| path.cost), dijkstra_cost);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn alt_settles_fewer_nodes() {
+        let (cells, graph) = random_graph(42);
+        let no_landmarks = compute_landmarks(&graph, 0, LandmarkStrategy::FarthestCell).unwrap();
+        let landmarks = compute_landmarks(&graph, 8, LandmarkStrategy::FarthestCell).unwrap();
+
+        let origin = cells[0];
+        let destination = cells[cells.len() - 1];
+        let (path_plain, stats_plain) = graph
+            .shortest_path_alt_with_stats(origin, destination, &no_landmarks)
+            .unwrap();
+        let (path_alt, stats_alt) = graph
+            .shortest_path_alt_with_stats(origin, destination, &landmarks)
+            .unwrap();
+        assert_eq!(path_plain.map(|p| p.cost), path_alt.map(|p| p.cost));
+        assert!(stats_alt.num_settled_nodes <= stats_plain.num_settled_nodes);
+    }
+
+    #[test]
+    fn landmarks_of_other_graph_are_rejected() {
+        let (_, graph) = random_graph(1);
+        let (cells, other_graph) = random_graph(2);
+        let landmarks = compute_landmarks(&graph, 2, LandmarkStrategy::FarthestCell).unwrap();
+
+        assert!(matches!(
+            landmarks.validate(&other_graph),
+            Err(Error::LandmarksGraphMismatch)
+        ));
+
+        let mut smaller_graph = H3EdgeGraph::new(8);
+        smaller_graph
+            .add_edge(cells[0].directed_edges().unwrap().first().unwrap(), 1_u32)
+            .unwrap();
+        let smaller_graph: PreparedH3EdgeGraph<u32> = smaller_graph.try_into().unwrap();
+        assert!(matches!(
+            smaller_graph.shortest_path_alt(cells[0], cells[1], &landmarks),
+            Err(Error::LandmarksGraphMismatch)
+        ));
+    }
+
+    #[test]
+    fn landmarks_of_reweighted_graph_are_rejected() {
+        let (cells, graph) = random_graph(1);
+        let landmarks = compute_landmarks(&graph, 2, LandmarkStrategy::FarthestCell).unwrap();
+
+        // same edges, only a single weight differs
+        let mut reweighted = H3EdgeGraph::new(8);
+        for (i, (edge, edge_weight)) in graph.iter_edges().enumerate() {
+            let weight = if i == 0 {
+                edge_weight.weight + 1
+            } else {
+                edge_weight.weight
+            };
+            reweighted.add_edge(edge, weight).unwrap();
+        }
+        let reweighted: PreparedH3EdgeGraph<u32> = reweighted.try_into().unwrap();
+        assert_eq!(reweighted.count_edges(), graph.count_edges());
+
+        assert!(matches!(
+            reweighted.shortest_path_alt(cells[0], cells[1], &landmarks),
+            Err(Error::LandmarksGraphMismatch)
+        ));
+        assert!(graph
+            .shortest_path_alt(cells[0], cells[1], &landmarks)
+            .is_ok());
+    }
+
+    #[cfg(feature = "io_serde_util")]
+    #[test]
+    fn landmarkset_serde_roundtrip() {
+        use crate::algorithm::landmarks::LandmarkSet;
+
+        let (_, graph) = random_graph(3);
+        let landmarks = compute_landmarks(&graph, 3, LandmarkStrategy::Random { seed: 3 }).unwrap();
+        let serialized = bincode::serialize(&landmarks).unwrap();
+        let deserialized: LandmarkSet = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(landmarks, deserialized);
+    }
+}
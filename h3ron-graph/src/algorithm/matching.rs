@@ -0,0 +1,521 @@
+//! HMM-based map matching of traces of observed cells to the graph.
+//!
+//! The implementation follows the approach described in
+//! [Hidden Markov Map Matching Through Noise and Sparseness](https://www.microsoft.com/en-us/research/publication/hidden-markov-map-matching-noise-sparseness/)
+//! by Newson and Krumm, using grid distances instead of geodesic distances:
+//!
+//! * The candidate states of an observation are the cells of the graph within a k-ring around the
+//!   observed cell.
+//! * Emission probabilities decay with the grid distance between the observed cell and the candidate.
+//! * Transition probabilities decay with the difference between the routed distance of two consecutive
+//!   candidates and the grid distance between the two observed cells. Transitions turning back on
+//!   the edge the previous candidate was reached by are penalized, so noise around the trace does
+//!   not result in the matched path going back and forth.
+//!
+//! The most likely sequence of candidates is found using Viterbi decoding.
+use std::ops::Add;
+
+use num_traits::Zero;
+
+use h3ron::collections::HashMap;
+use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution, Index};
+
+use crate::algorithm::path::DirectedEdgePath;
+use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPathManyToMany};
+use crate::algorithm::NearestGraphNodes;
+use crate::error::Error;
+use crate::graph::{GetCellEdges, GetCellNode};
+
+/// Parameters of the hidden markov model used by [`match_trace`].
+#[derive(Debug, Clone)]
+pub struct MatchOptions {
+    /// Maximum grid distance between an observed cell and the graph cells
+    /// considered as candidates for that observation.
+    pub max_candidate_k: u32,
+
+    /// Scale of the exponential distribution of the transition probabilities. Larger values
+    /// are more tolerant to differences between the routed distance and the observed distance.
+    pub transition_beta: f64,
+
+    /// Standard deviation of the gaussian distribution of the emission probabilities, measured
+    /// in grid distance.
+    pub emission_sigma: f64,
+
+    /// Log-probability subtracted from transitions starting with the reverse of the edge the
+    /// previous candidate was reached by. `0.0` disables the penalty.
+    ///
+    /// The penalty uses the unit of the emission and transition log-probabilities: it weighs as
+    /// much as a difference of `reversal_penalty * transition_beta` cells between the routed and
+    /// the observed distance. The default of `2.0` equals the emission log-probability of a
+    /// candidate two cells away from its observation with an `emission_sigma` of `1.0`. With the
+    /// default options, a single observation behind the previous match is attributed to noise,
+    /// while turning back is matched as soon as the following observation supports it.
+    pub reversal_penalty: f64,
+}
+
+impl Default for MatchOptions {
+    fn default() -> Self {
+        Self {
+            max_candidate_k: 1,
+            transition_beta: 1.0,
+            emission_sigma: 1.0,
+            reversal_penalty: 2.0,
+        }
+    }
+}
+
+/// A continuous part of a trace matched to the graph.
+#[derive(Debug, Clone)]
+pub struct MatchedSegment {
+    /// Position of the first observation of this segment in the `observed_cells` passed to [`match_trace`].
+    pub observation_offset: usize,
+
+    /// The graph cell matched for each observation of this segment.
+    pub matched_cells: Vec<H3Cell>,
+
+    /// The confidence of each match. This is the share of the Viterbi probability of the
+    /// matched candidate of all candidates of the observation, so it is within `0.0..=1.0`.
+    pub confidences: Vec<f64>,
+
+    /// The path through the graph connecting the `matched_cells`.
+    pub path: DirectedEdgePath,
+}
+
+impl MatchedSegment {
+    /// number of observations in this segment
+    pub fn len(&self) -> usize {
+        self.matched_cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matched_cells.is_empty()
+    }
+}
+
+/// Match a trace of `observed_cells` to the graph.
+///
+/// The trace is split into multiple [`MatchedSegment`]s when an observation has no candidates
+/// in the graph or none of its candidates can be reached from the candidates of the
+/// preceding observation.
+///
+/// All `observed_cells` must be in the h3 resolution of the graph.
+pub fn match_trace<G, W>(
+    graph: &G,
+    observed_cells: &[H3Cell],
+    options: &MatchOptions,
+) -> Result<Vec<MatchedSegment>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes + Sync,
+    W: PartialOrd + PartialEq + Add + Copy + Send + Ord + Zero + Sync,
+{
+    let mut segments = Vec::new();
+    let mut steps: Vec<ViterbiStep> = Vec::new();
+
+    for (observation, observed_cell) in observed_cells.iter().enumerate() {
+        if observed_cell.resolution() != graph.h3_resolution() {
+            return Err(Error::MixedH3Resolutions(
+                graph.h3_resolution(),
+                observed_cell.resolution(),
+            ));
+        }
+
+        let candidates = find_candidates(graph, observed_cell, options)?;
+        if candidates.is_empty() {
+            finish_segment(&mut steps, &mut segments)?;
+            continue;
+        }
+
+        let emissions: Vec<_> = candidates
+            .iter()
+            .map(|(_, k)| emission_log_probability(*k, options.emission_sigma))
+            .collect();
+        let candidate_cells: Vec<_> = candidates.into_iter().map(|(cell, _)| cell).collect();
+
+        let step = match steps.last() {
+            Some(previous) => {
+                let step = transition_step(
+                    graph,
+                    previous,
+                    observed_cells[previous.observation],
+                    observation,
+                    *observed_cell,
+                    candidate_cells.clone(),
+                    &emissions,
+                    options,
+                )?;
+                if step.is_none() {
+                    // no candidate is reachable from the previous observation
+                    finish_segment(&mut steps, &mut segments)?;
+                }
+                step
+            }
+            None => None,
+        };
+
+        steps.push(step.unwrap_or_else(|| ViterbiStep {
+            observation,
+            incoming_edges: vec![None; candidate_cells.len()],
+            candidates: candidate_cells,
+            scores: emissions,
+            backpointers: vec![],
+            transition_paths: Default::default(),
+        }));
+    }
+    finish_segment(&mut steps, &mut segments)?;
+    Ok(segments)
+}
+
+struct ViterbiStep {
+    /// position of the observation in the trace
+    observation: usize,
+
+    candidates: Vec<H3Cell>,
+
+    /// log-probability of the most likely sequence ending in each candidate
+    scores: Vec<f64>,
+
+    /// position of the preceding candidate in the previous step for each candidate. Empty
+    /// for the first step of a segment.
+    backpointers: Vec<usize>,
+
+    /// paths from the candidates of the previous step keyed by (origin, destination)
+    transition_paths: HashMap<(H3Cell, H3Cell), DirectedEdgePath>,
+
+    /// the last edge of the most likely sequence ending in each candidate. `None` when the
+    /// sequence contains no edges.
+    incoming_edges: Vec<Option<H3DirectedEdge>>,
+}
+
+/// graph cells within `max_candidate_k` of the `observed_cell` together with their grid distance
+fn find_candidates<G>(
+    graph: &G,
+    observed_cell: &H3Cell,
+    options: &MatchOptions,
+) -> Result<Vec<(H3Cell, u32)>, Error>
+where
+    G: GetCellNode,
+{
+    let mut candidates: Vec<_> = observed_cell
+        .grid_disk_distances(0, options.max_candidate_k)?
+        .into_iter()
+        .filter(|(_, cell)| graph.get_cell_node(cell).is_some())
+        .map(|(k, cell)| (cell, k))
+        .collect();
+    candidates.sort_unstable();
+    Ok(candidates)
+}
+
+fn emission_log_probability(grid_distance: u32, emission_sigma: f64) -> f64 {
+    let z = f64::from(grid_distance) / emission_sigma;
+    -0.5 * z * z
+}
+
+fn transition_log_probability(
+    routed_distance: usize,
+    observed_distance: usize,
+    transition_beta: f64,
+) -> f64 {
+    -((routed_distance as f64) - (observed_distance as f64)).abs() / transition_beta
+}
+
+/// Build the Viterbi step for an observation from the previous step.
+///
+/// Returns `None` when none of the candidates can be reached.
+#[allow(clippy::too_many_arguments)]
+fn transition_step<G, W>(
+    graph: &G,
+    previous: &ViterbiStep,
+    previous_observed_cell: H3Cell,
+    observation: usize,
+    observed_cell: H3Cell,
+    candidates: Vec<H3Cell>,
+    emissions: &[f64],
+    options: &MatchOptions,
+) -> Result<Option<ViterbiStep>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes + Sync,
+    W: PartialOrd + PartialEq + Add + Copy + Send + Ord + Zero + Sync,
+{
+    let observed_distance = match previous_observed_cell.grid_distance_to(observed_cell) {
+        Ok(distance) => distance,
+        // the grid distance can not be computed across pentagons or over very long distances.
+        Err(_) => return Ok(None),
+    };
+
+    // route between all candidates of both observations using a single batched call.
+    let paths = match graph.shortest_path_many_to_many_map(
+        previous.candidates.iter(),
+        candidates.iter(),
+        &DefaultShortestPathOptions::default(),
+        |path| Ok((path.destination_cell, path.directed_edge_path)),
+    ) {
+        Ok(paths) => paths,
+        Err(Error::DestinationsNotInGraph) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut transition_paths: HashMap<(H3Cell, H3Cell), DirectedEdgePath> = Default::default();
+    for (origin_cell, destinations) in paths {
+        for (destination_cell, directed_edge_path) in destinations {
+            transition_paths.insert((origin_cell, destination_cell), directed_edge_path);
+        }
+    }
+
+    let mut scores = Vec::with_capacity(candidates.len());
+    let mut backpointers = Vec::with_capacity(candidates.len());
+    let mut incoming_edges = Vec::with_capacity(candidates.len());
+    for (candidate, emission) in candidates.iter().zip(emissions.iter()) {
+        let mut best: Option<(usize, f64, &DirectedEdgePath)> = None;
+        for (previous_pos, (previous_candidate, previous_score)) in previous
+            .candidates
+            .iter()
+            .zip(previous.scores.iter())
+            .enumerate()
+        {
+            if !previous_score.is_finite() {
+                continue;
+            }
+            if let Some(path) = transition_paths.get(&(*previous_candidate, *candidate)) {
+                let mut score = previous_score
+                    + transition_log_probability(
+                        path.len(),
+                        observed_distance,
+                        options.transition_beta,
+                    );
+                if let (Some(incoming_edge), Some(first_edge)) =
+                    (previous.incoming_edges[previous_pos], path.edges().first())
+                {
+                    if first_edge.reversed()? == incoming_edge {
+                        score -= options.reversal_penalty;
+                    }
+                }
+                if best.is_none_or(|(_, best_score, _)| score > best_score) {
+                    best = Some((previous_pos, score, path));
+                }
+            }
+        }
+        match best {
+            Some((previous_pos, score, path)) => {
+                scores.push(score + emission);
+                backpointers.push(previous_pos);
+                incoming_edges.push(
+                    path.edges()
+                        .last()
+                        .copied()
+                        .or(previous.incoming_edges[previous_pos]),
+                );
+            }
+            None => {
+                scores.push(f64::NEG_INFINITY);
+                backpointers.push(0);
+                incoming_edges.push(None);
+            }
+        }
+    }
+
+    if scores.iter().all(|score| !score.is_finite()) {
+        return Ok(None);
+    }
+
+    Ok(Some(ViterbiStep {
+        observation,
+        candidates,
+        scores,
+        backpointers,
+        transition_paths,
+        incoming_edges,
+    }))
+}
+
+/// position and score of the most likely candidate of a step
+fn best_candidate(step: &ViterbiStep) -> Option<(usize, f64)> {
+    step.scores
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, score)| score.is_finite())
+        .fold(None, |best, (pos, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((pos, score)),
+        })
+}
+
+/// share of the probability of the candidate at `pos` of the probabilities of all candidates
+fn confidence(step: &ViterbiStep, pos: usize, max_score: f64) -> f64 {
+    let sum: f64 = step
+        .scores
+        .iter()
+        .filter(|score| score.is_finite())
+        .map(|score| (score - max_score).exp())
+        .sum();
+    (step.scores[pos] - max_score).exp() / sum
+}
+
+/// backtrack the steps of the current segment and append the result to `segments`
+fn finish_segment(
+    steps: &mut Vec<ViterbiStep>,
+    segments: &mut Vec<MatchedSegment>,
+) -> Result<(), Error> {
+    let steps = std::mem::take(steps);
+    let (mut pos, _) = match steps.last().and_then(best_candidate) {
+        Some(best) => best,
+        None => return Ok(()),
+    };
+
+    let mut matched_cells = Vec::with_capacity(steps.len());
+    let mut confidences = Vec::with_capacity(steps.len());
+    let mut edges: Vec<H3DirectedEdge> = Vec::new();
+    for (step_i, step) in steps.iter().enumerate().rev() {
+        let cell = step.candidates[pos];
+        let max_score = step
+            .scores
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        matched_cells.push(cell);
+        confidences.push(confidence(step, pos, max_score));
+
+        if step_i > 0 {
+            let previous_pos = step.backpointers[pos];
+            let previous_cell = steps[step_i - 1].candidates[previous_pos];
+            let path = step
+                .transition_paths
+                .get(&(previous_cell, cell))
+                .ok_or_else(|| Error::Other("missing transition path".to_string()))?;
+            edges.extend(path.edges().iter().rev());
+            pos = previous_pos;
+        }
+    }
+    matched_cells.reverse();
+    confidences.reverse();
+    edges.reverse();
+
+    let path = if edges.is_empty() {
+        DirectedEdgePath::OriginIsDestination(matched_cells[0])
+    } else {
+        DirectedEdgePath::DirectedEdgeSequence(edges)
+    };
+
+    segments.push(MatchedSegment {
+        observation_offset: steps[0].observation,
+        matched_cells,
+        confidences,
+        path,
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use geo_types::Coordinate;
+
+    use h3ron::collections::H3CellSet;
+    use h3ron::{grid_path_cells, H3Cell, HasH3Resolution};
+
+    use crate::algorithm::matching::{match_trace, MatchOptions};
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    fn build_route_and_graph() -> (Vec<H3Cell>, PreparedH3EdgeGraph<u32>) {
+        let res = 8;
+        let route: Vec<_> = grid_path_cells(
+            H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), res).unwrap(),
+            H3Cell::from_coordinate(Coordinate::from((10.1, 20.05)), res).unwrap(),
+        )
+        .unwrap()
+        .iter()
+        .collect();
+
+        let mut graph = H3EdgeGraph::new(res);
+        for window in route.windows(2) {
+            graph
+                .add_edge_using_cells_bidirectional(window[0], window[1], 1_u32)
+                .unwrap();
+        }
+        (route, graph.try_into().unwrap())
+    }
+
+    #[test]
+    fn match_noisy_trace() {
+        let (route, graph) = build_route_and_graph();
+        assert!(route.len() > 10);
+        let route_set: H3CellSet = route.iter().copied().collect();
+
+        // replace every second cell by a neighbor not being part of the route
+        let trace: Vec<_> = route
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| {
+                if i % 2 == 1 && i + 1 < route.len() {
+                    cell.grid_disk(1)
+                        .unwrap()
+                        .iter()
+                        .find(|neighbor| !route_set.contains(neighbor))
+                        .unwrap()
+                } else {
+                    *cell
+                }
+            })
+            .collect();
+
+        let segments = match_trace(&graph, &trace, &MatchOptions::default()).unwrap();
+        assert_eq!(segments.len(), 1);
+        let segment = &segments[0];
+        assert_eq!(segment.observation_offset, 0);
+        assert_eq!(segment.len(), trace.len());
+        assert_eq!(segment.confidences.len(), trace.len());
+        for confidence in segment.confidences.iter() {
+            assert!(*confidence > 0.0 && *confidence <= 1.0);
+        }
+        assert_eq!(segment.path.cells().unwrap(), route);
+    }
+
+    #[test]
+    fn reversal_penalty() {
+        let (route, graph) = build_route_and_graph();
+        let matched_cells = |trace: &[H3Cell], reversal_penalty: f64| {
+            let options = MatchOptions {
+                reversal_penalty,
+                ..Default::default()
+            };
+            let segments = match_trace(&graph, trace, &options).unwrap();
+            assert_eq!(segments.len(), 1);
+            segments[0].matched_cells.clone()
+        };
+
+        // a single observation behind the previous match is attributed to noise
+        let trace = [route[0], route[1], route[2], route[1]];
+        assert_eq!(matched_cells(&trace, 0.0), trace.to_vec());
+        assert_eq!(
+            matched_cells(&trace, MatchOptions::default().reversal_penalty),
+            vec![route[0], route[1], route[2], route[2]]
+        );
+
+        // turning back is matched when the following observation supports it
+        let trace = [route[0], route[1], route[2], route[1], route[0]];
+        assert_eq!(
+            matched_cells(&trace, MatchOptions::default().reversal_penalty),
+            trace.to_vec()
+        );
+    }
+
+    #[test]
+    fn match_off_graph_trace_splits() {
+        let (route, graph) = build_route_and_graph();
+        let split_at = route.len() / 2;
+        let off_graph_cell =
+            H3Cell::from_coordinate(Coordinate::from((30.0, 30.0)), graph.h3_resolution()).unwrap();
+
+        let mut trace = route[..split_at].to_vec();
+        trace.extend([off_graph_cell; 3]);
+        trace.extend_from_slice(&route[split_at..]);
+
+        let segments = match_trace(&graph, &trace, &MatchOptions::default()).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].observation_offset, 0);
+        assert_eq!(segments[0].matched_cells, route[..split_at].to_vec());
+        assert_eq!(segments[1].observation_offset, split_at + 3);
+        assert_eq!(segments[1].matched_cells, route[split_at..].to_vec());
+    }
+}
@@ -1,14 +1,26 @@
 pub mod covered_area;
 pub mod differential_shortest_path;
 mod dijkstra;
+pub mod disjoint_paths;
+pub mod landmarks;
+pub mod matching;
 pub mod nearest_graph_nodes;
+pub mod node_features;
 pub mod path;
+pub mod route_sensitivity;
+pub mod route_via;
 pub mod shortest_path;
+pub mod shortest_path_tree;
 pub mod within_weight_threshold;
 
 // re-export all algorithm traits
 pub use covered_area::CoveredArea;
 pub use differential_shortest_path::DifferentialShortestPath;
+pub use disjoint_paths::DisjointPaths;
+pub use landmarks::ShortestPathAlt;
 pub use nearest_graph_nodes::NearestGraphNodes;
+pub use route_sensitivity::RouteSensitivity;
+pub use route_via::RouteVia;
 pub use shortest_path::{ShortestPath, ShortestPathManyToMany};
+pub use shortest_path_tree::BuildShortestPathTree;
 pub use within_weight_threshold::{WithinWeightThreshold, WithinWeightThresholdMany};
@@ -0,0 +1,408 @@
+//! Structural descriptors of the cells of a graph for use as features in machine learning
+//! or statistical models.
+//!
+//! The features are computed for all cells of the graph and are returned keyed by the cell, so
+//! they can be directly joined to other tables of H3 cells.
+use std::collections::VecDeque;
+
+use num_traits::ToPrimitive;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use h3ron::H3Cell;
+
+use crate::algorithm::landmarks::splitmix64;
+use crate::error::Error;
+use crate::graph::{GetCellEdges, IterateCellNodes, PreparedH3EdgeGraph};
+
+/// number of betweenness source cells processed by a single task
+const BETWEENNESS_CHUNK_SIZE: usize = 16;
+
+/// Options for [`node_features`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NodeFeatureOptions {
+    /// number of source cells to approximate the betweenness centrality from. When this
+    /// number is not lower than the number of cells of the graph, the betweenness is exact.
+    pub betweenness_samples: usize,
+
+    /// seed for the selection of the betweenness source cells.
+    pub seed: u64,
+}
+
+impl Default for NodeFeatureOptions {
+    fn default() -> Self {
+        Self {
+            betweenness_samples: 256,
+            seed: 0,
+        }
+    }
+}
+
+/// Structural descriptors of a single cell of a graph.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeFeatures {
+    /// number of edges ending in the cell
+    pub in_degree: usize,
+
+    /// number of edges starting at the cell
+    pub out_degree: usize,
+
+    /// sum of the weights of all incoming and outgoing edges
+    pub weight_sum: f64,
+
+    /// mean of the weights of all incoming and outgoing edges
+    pub weight_mean: f64,
+
+    /// local clustering coefficient of the cell in the undirected projection of the graph.
+    ///
+    /// The fraction of pairs of neighbors which are neighbors of each other as well. `0.0`
+    /// for cells with less than two neighbors.
+    pub clustering_coefficient: f64,
+
+    /// betweenness centrality based on the number of edges of the shortest paths.
+    ///
+    /// The number of shortest paths between ordered pairs of other cells passing through
+    /// the cell. When approximated from a sample of source cells, the value is extrapolated
+    /// to all source cells.
+    pub betweenness: f64,
+
+    /// number of edges to the nearest dead end in the undirected projection of the graph.
+    ///
+    /// Dead ends are cells with a single neighbor, for these the distance is `0`. `None`
+    /// when no dead end is reachable.
+    pub dead_end_distance: Option<u32>,
+}
+
+/// Compute the [`NodeFeatures`] of all cells of the `graph`.
+///
+/// Degrees and weights are aggregated in a single pass over the edges. The remaining
+/// features are computed in parallel.
+///
+/// The returned vector is sorted by cell.
+pub fn node_features<W>(
+    graph: &PreparedH3EdgeGraph<W>,
+    options: &NodeFeatureOptions,
+) -> Result<Vec<(H3Cell, NodeFeatures)>, Error>
+where
+    W: Copy + ToPrimitive + Send + Sync,
+{
+    let mut cells: Vec<_> = graph.iter_cell_nodes().map(|(cell, _)| *cell).collect();
+    cells.sort_unstable();
+
+    let mut features: Vec<_> = (0..cells.len())
+        .map(|_| NodeFeatures {
+            in_degree: 0,
+            out_degree: 0,
+            weight_sum: 0.0,
+            weight_mean: 0.0,
+            clustering_coefficient: 0.0,
+            betweenness: 0.0,
+            dead_end_distance: None,
+        })
+        .collect();
+    let mut forward = vec![Vec::new(); cells.len()];
+    let mut undirected = vec![Vec::new(); cells.len()];
+
+    for (position, cell) in cells.iter().enumerate() {
+        for (edge, edge_weight) in graph.get_edges_originating_from(cell)? {
+            let weight = edge_weight.weight.to_f64().ok_or_else(|| {
+                Error::Other("edge weight is not representable as f64".to_string())
+            })?;
+            if let Ok(destination_position) = cells.binary_search(&edge.destination_cell()?) {
+                forward[position].push(destination_position);
+                undirected[position].push(destination_position);
+                undirected[destination_position].push(position);
+
+                features[position].out_degree += 1;
+                features[position].weight_sum += weight;
+                features[destination_position].in_degree += 1;
+                features[destination_position].weight_sum += weight;
+            }
+        }
+    }
+    for neighbors in undirected.iter_mut() {
+        neighbors.sort_unstable();
+        neighbors.dedup();
+    }
+
+    let clustering_coefficients: Vec<_> = (0..cells.len())
+        .into_par_iter()
+        .map(|position| clustering_coefficient(&undirected, position))
+        .collect();
+    let betweenness = approximate_betweenness(&forward, options);
+    let dead_end_distances = dead_end_distances(&undirected);
+
+    Ok(cells
+        .into_iter()
+        .zip(features)
+        .enumerate()
+        .map(|(position, (cell, mut nf))| {
+            let degree = nf.in_degree + nf.out_degree;
+            if degree > 0 {
+                nf.weight_mean = nf.weight_sum / degree as f64;
+            }
+            nf.clustering_coefficient = clustering_coefficients[position];
+            nf.betweenness = betweenness[position];
+            nf.dead_end_distance = dead_end_distances[position];
+            (cell, nf)
+        })
+        .collect())
+}
+
+fn clustering_coefficient(undirected: &[Vec<usize>], position: usize) -> f64 {
+    let neighbors = &undirected[position];
+    if neighbors.len() < 2 {
+        return 0.0;
+    }
+    let mut links = 0_usize;
+    for (i, a) in neighbors.iter().enumerate() {
+        for b in neighbors[(i + 1)..].iter() {
+            if undirected[*a].binary_search(b).is_ok() {
+                links += 1;
+            }
+        }
+    }
+    (2 * links) as f64 / (neighbors.len() * (neighbors.len() - 1)) as f64
+}
+
+/// Brandes' algorithm on unweighted directed edges, using a sample of the cells as sources.
+fn approximate_betweenness(forward: &[Vec<usize>], options: &NodeFeatureOptions) -> Vec<f64> {
+    let num_cells = forward.len();
+    let sources: Vec<usize> = if options.betweenness_samples >= num_cells {
+        (0..num_cells).collect()
+    } else {
+        let mut sources = Vec::with_capacity(options.betweenness_samples);
+        let mut state = options.seed;
+        while sources.len() < options.betweenness_samples {
+            let position = (splitmix64(&mut state) % num_cells as u64) as usize;
+            if !sources.contains(&position) {
+                sources.push(position);
+            }
+        }
+        sources
+    };
+    if sources.is_empty() {
+        return vec![0.0; num_cells];
+    }
+
+    // Summing up in fixed chunks of sources keeps the order of the floating point additions
+    // and by this the results independent of the scheduling of the threads.
+    let scale = num_cells as f64 / sources.len() as f64;
+    let chunk_sums: Vec<_> = sources
+        .par_chunks(BETWEENNESS_CHUNK_SIZE)
+        .map(|chunk| {
+            let mut sums = vec![0.0; num_cells];
+            for source in chunk {
+                add_assign(&mut sums, &single_source_dependencies(forward, *source));
+            }
+            sums
+        })
+        .collect();
+
+    let mut betweenness = vec![0.0; num_cells];
+    for chunk_sum in chunk_sums.iter() {
+        add_assign(&mut betweenness, chunk_sum);
+    }
+    betweenness.iter_mut().for_each(|b| *b *= scale);
+    betweenness
+}
+
+fn add_assign(values: &mut [f64], other: &[f64]) {
+    values
+        .iter_mut()
+        .zip(other.iter())
+        .for_each(|(v, o)| *v += o);
+}
+
+/// dependencies of the `source` cell on all other cells
+fn single_source_dependencies(forward: &[Vec<usize>], source: usize) -> Vec<f64> {
+    let num_cells = forward.len();
+    let mut distance = vec![u32::MAX; num_cells];
+    let mut num_paths = vec![0.0_f64; num_cells];
+    let mut predecessors = vec![Vec::new(); num_cells];
+    let mut settled = Vec::new();
+    let mut queue = VecDeque::new();
+
+    distance[source] = 0;
+    num_paths[source] = 1.0;
+    queue.push_back(source);
+    while let Some(position) = queue.pop_front() {
+        settled.push(position);
+        for next in forward[position].iter().copied() {
+            if distance[next] == u32::MAX {
+                distance[next] = distance[position] + 1;
+                queue.push_back(next);
+            }
+            if distance[next] == distance[position] + 1 {
+                num_paths[next] += num_paths[position];
+                predecessors[next].push(position);
+            }
+        }
+    }
+
+    let mut dependencies = vec![0.0_f64; num_cells];
+    while let Some(position) = settled.pop() {
+        for predecessor in predecessors[position].iter().copied() {
+            dependencies[predecessor] +=
+                num_paths[predecessor] / num_paths[position] * (1.0 + dependencies[position]);
+        }
+    }
+    dependencies[source] = 0.0;
+    dependencies
+}
+
+/// multi-source breadth first search starting at all cells with a single neighbor
+fn dead_end_distances(undirected: &[Vec<usize>]) -> Vec<Option<u32>> {
+    let mut distances = vec![None; undirected.len()];
+    let mut queue = VecDeque::new();
+    for (position, neighbors) in undirected.iter().enumerate() {
+        if neighbors.len() == 1 {
+            distances[position] = Some(0);
+            queue.push_back(position);
+        }
+    }
+    while let Some(position) = queue.pop_front() {
+        let next_distance = distances[position].map(|d: u32| d + 1);
+        for next in undirected[position].iter().copied() {
+            if distances[next].is_none() {
+                distances[next] = next_distance;
+                queue.push_back(next);
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use std::time::{Duration, Instant};
+
+    use geo_types::Coordinate;
+
+    use h3ron::H3Cell;
+
+    use crate::algorithm::node_features::{node_features, NodeFeatureOptions, NodeFeatures};
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    /// triangle `a`, `b`, `c` with the dead end `d` attached to `c`.
+    fn tiny_graph() -> ([H3Cell; 4], PreparedH3EdgeGraph<u32>) {
+        let a = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8).unwrap();
+        let ring: Vec<_> = a.grid_ring_unsafe(1).unwrap().iter().collect();
+        let b = ring[0];
+        let c = *ring
+            .iter()
+            .find(|cell| cell.grid_distance_to(b).unwrap() == 1)
+            .unwrap();
+        let d = c
+            .grid_ring_unsafe(1)
+            .unwrap()
+            .iter()
+            .find(|cell| {
+                cell.grid_distance_to(a).unwrap() == 2 && cell.grid_distance_to(b).unwrap() == 2
+            })
+            .unwrap();
+
+        let mut graph = H3EdgeGraph::new(8);
+        for (from, to, weight) in [(a, b, 1), (b, c, 2), (a, c, 3), (c, d, 4)] {
+            graph
+                .add_edge_using_cells_bidirectional(from, to, weight)
+                .unwrap();
+        }
+        ([a, b, c, d], graph.try_into().unwrap())
+    }
+
+    fn features_of(features: &[(H3Cell, NodeFeatures)], cell: H3Cell) -> &NodeFeatures {
+        &features.iter().find(|(c, _)| *c == cell).unwrap().1
+    }
+
+    #[test]
+    fn tiny_graph_features() {
+        let ([a, b, c, d], graph) = tiny_graph();
+        let features = node_features(&graph, &NodeFeatureOptions::default()).unwrap();
+        assert_eq!(features.len(), 4);
+
+        let fa = features_of(&features, a);
+        assert_eq!((fa.in_degree, fa.out_degree), (2, 2));
+        assert!((fa.weight_sum - 8.0).abs() < 1e-9);
+        assert!((fa.weight_mean - 2.0).abs() < 1e-9);
+        assert!((fa.clustering_coefficient - 1.0).abs() < 1e-9);
+        assert!(fa.betweenness.abs() < 1e-9);
+        assert_eq!(fa.dead_end_distance, Some(2));
+
+        let fc = features_of(&features, c);
+        assert_eq!((fc.in_degree, fc.out_degree), (3, 3));
+        assert!((fc.weight_sum - 18.0).abs() < 1e-9);
+        assert!((fc.weight_mean - 3.0).abs() < 1e-9);
+        assert!((fc.clustering_coefficient - 1.0 / 3.0).abs() < 1e-9);
+        // a->d, b->d, d->a, d->b
+        assert!((fc.betweenness - 4.0).abs() < 1e-9);
+        assert_eq!(fc.dead_end_distance, Some(1));
+
+        let fd = features_of(&features, d);
+        assert_eq!((fd.in_degree, fd.out_degree), (1, 1));
+        assert!((fd.weight_mean - 4.0).abs() < 1e-9);
+        assert!(fd.clustering_coefficient.abs() < 1e-9);
+        assert_eq!(fd.dead_end_distance, Some(0));
+
+        assert_eq!(features_of(&features, b).dead_end_distance, Some(2));
+    }
+
+    fn disk_graph(k: u32) -> PreparedH3EdgeGraph<u32> {
+        let mut graph = H3EdgeGraph::new(8);
+        let disk = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8)
+            .unwrap()
+            .grid_disk(k)
+            .unwrap();
+        for (i, cell) in disk.iter().enumerate() {
+            for (j, edge) in cell.directed_edges().unwrap().iter().enumerate() {
+                if (i + j) % 4 != 0 {
+                    graph.add_edge(edge, 1 + ((i + j) % 7) as u32).unwrap();
+                }
+            }
+        }
+        graph.try_into().unwrap()
+    }
+
+    #[test]
+    fn betweenness_sampling_is_deterministic() {
+        let graph = disk_graph(8);
+        let options = NodeFeatureOptions {
+            betweenness_samples: 20,
+            seed: 42,
+        };
+        let first = node_features(&graph, &options).unwrap();
+        let second = node_features(&graph, &options).unwrap();
+        assert_eq!(first, second);
+        assert!(first.iter().any(|(_, nf)| nf.betweenness > 0.0));
+
+        let other_seed = node_features(
+            &graph,
+            &NodeFeatureOptions {
+                seed: 43,
+                ..options
+            },
+        )
+        .unwrap();
+        assert_ne!(first, other_seed);
+    }
+
+    #[test]
+    #[ignore]
+    fn scales_to_100k_edges() {
+        let graph = disk_graph(95);
+        assert!(graph.count_edges().0 > 100_000);
+
+        let start = Instant::now();
+        let features = node_features(
+            &graph,
+            &NodeFeatureOptions {
+                betweenness_samples: 32,
+                seed: 1,
+            },
+        )
+        .unwrap();
+        assert!(!features.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(120));
+    }
+}
@@ -3,10 +3,12 @@ use std::cmp::Ordering;
 use geo_types::LineString;
 use serde::{Deserialize, Serialize};
 
+use h3ron::collections::{H3CellSet, HashSet};
 use h3ron::to_geo::{ToLineString, ToMultiLineString};
 use h3ron::{H3Cell, H3DirectedEdge, Index};
 
 use crate::error::Error;
+use crate::graph::longedge::{join_linestrings, DEFAULT_SEGMENT_EPSILON};
 
 /// [DirectedEdgePath] describes a path between a cell and another.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -70,14 +72,7 @@ impl DirectedEdgePath {
             Self::DirectedEdgeSequence(edges) => match edges.len() {
                 0 => Err(Error::InsufficientNumberOfEdges),
                 1 => Ok(edges[0].to_linestring()?),
-                _ => {
-                    let mut multilinesstring = edges.to_multilinestring()?;
-                    match multilinesstring.0.len() {
-                        0 => Err(Error::InsufficientNumberOfEdges),
-                        1 => Ok(multilinesstring.0.remove(0)),
-                        _ => Err(Error::SegmentedPath),
-                    }
-                }
+                _ => join_linestrings(edges.to_multilinestring()?, DEFAULT_SEGMENT_EPSILON),
             },
         }
     }
@@ -111,13 +106,7 @@ impl DirectedEdgePath {
     pub fn length_m(&self) -> Result<f64, Error> {
         match self {
             Self::OriginIsDestination(_) => Ok(0.0),
-            Self::DirectedEdgeSequence(edges) => {
-                let mut length_m = 0.0;
-                for edge in edges {
-                    length_m += edge.length_m()?;
-                }
-                Ok(length_m)
-            }
+            Self::DirectedEdgeSequence(edges) => edges_length_m(edges),
         }
     }
 }
@@ -153,6 +142,204 @@ impl<W> Path<W> {
     pub fn len(&self) -> usize {
         self.directed_edge_path.len()
     }
+
+    /// Compare this path to `other` - typically a path between the same cells created
+    /// using a different weight model.
+    ///
+    /// As the path does not store the weights of its individual edges, the shared
+    /// fractions are measured using the length of the edges. Paths created from longedges
+    /// are already expanded to their individual edges.
+    ///
+    /// Paths of different graphs can not be detected, only paths of different
+    /// resolutions result in an error.
+    pub fn compare<W2>(&self, other: &Path<W2>) -> Result<PathComparison, Error> {
+        let resolution = self.origin_cell.resolution();
+        let other_resolution = other.origin_cell.resolution();
+        if resolution != other_resolution {
+            return Err(Error::MixedH3Resolutions(resolution, other_resolution));
+        }
+
+        let edges = self.directed_edge_path.edges();
+        let other_edges = other.directed_edge_path.edges();
+        let other_edge_set: HashSet<_> = other_edges.iter().copied().collect();
+        let shared_edges: Vec<_> = edges
+            .iter()
+            .filter(|edge| other_edge_set.contains(*edge))
+            .copied()
+            .collect();
+        let shared_edge_set: HashSet<_> = shared_edges.iter().copied().collect();
+
+        let shared_length_m = edges_length_m(&shared_edges)?;
+        let shared_fraction = |path: &DirectedEdgePath| -> Result<f64, Error> {
+            let length_m = path.length_m()?;
+            Ok(if length_m > 0.0 {
+                shared_length_m / length_m
+            } else if self.directed_edge_path == other.directed_edge_path {
+                1.0
+            } else {
+                0.0
+            })
+        };
+
+        let divergence_segments = pair_divergent_runs(
+            divergent_runs(edges, &shared_edge_set)?,
+            divergent_runs(other_edges, &shared_edge_set)?,
+        );
+
+        let cells = self.directed_edge_path.cells()?;
+        let other_cells = other.directed_edge_path.cells()?;
+        let max_grid_distance = max_min_grid_distance(&cells, &other_cells)?
+            .max(max_min_grid_distance(&other_cells, &cells)?);
+
+        Ok(PathComparison {
+            shared_fraction: shared_fraction(&self.directed_edge_path)?,
+            other_shared_fraction: shared_fraction(&other.directed_edge_path)?,
+            shared_edges,
+            divergence_segments,
+            max_grid_distance,
+        })
+    }
+}
+
+/// Limit for the grid distance computed in [`Path::compare`].
+pub const PATH_COMPARISON_MAX_GRID_DISTANCE: u32 = 16;
+
+/// The result of [`Path::compare`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PathComparison {
+    /// the edges contained in both paths, in the order of the first path.
+    pub shared_edges: Vec<H3DirectedEdge>,
+
+    /// fraction of the length of the first path covered by the shared edges.
+    pub shared_fraction: f64,
+
+    /// fraction of the length of the other path covered by the shared edges.
+    pub other_shared_fraction: f64,
+
+    /// the sections in which the paths take different routes.
+    pub divergence_segments: Vec<DivergenceSegment>,
+
+    /// The maximum distance in grid cells from any cell of one path to the nearest cell
+    /// of the other path - a Hausdorff distance in grid units.
+    ///
+    /// Distances are capped at [`PATH_COMPARISON_MAX_GRID_DISTANCE`].
+    pub max_grid_distance: u32,
+}
+
+/// A maximal run of non-shared edges in one or both of the compared paths.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DivergenceSegment {
+    /// The cell at which the paths diverge - the last shared cell or the origin.
+    pub from_cell: H3Cell,
+
+    /// The cell at which the paths rejoin - the next shared cell or the destination.
+    pub to_cell: H3Cell,
+
+    /// the edges of the first path between `from_cell` and `to_cell`.
+    ///
+    /// Empty when only the other path has a divergent run between these cells.
+    pub edges: Vec<H3DirectedEdge>,
+
+    /// the edges of the other path between `from_cell` and `to_cell`.
+    ///
+    /// Empty when only the first path has a divergent run between these cells.
+    pub other_edges: Vec<H3DirectedEdge>,
+}
+
+fn edges_length_m(edges: &[H3DirectedEdge]) -> Result<f64, Error> {
+    let mut length_m = 0.0;
+    for edge in edges {
+        length_m += edge.length_m()?;
+    }
+    Ok(length_m)
+}
+
+/// collect the maximal runs of edges not contained in `shared_edges` as
+/// `(from_cell, to_cell, edges)`.
+fn divergent_runs(
+    edges: &[H3DirectedEdge],
+    shared_edges: &HashSet<H3DirectedEdge>,
+) -> Result<Vec<(H3Cell, H3Cell, Vec<H3DirectedEdge>)>, Error> {
+    let to_run = |run: &[H3DirectedEdge]| -> Result<_, Error> {
+        Ok((
+            run[0].origin_cell()?,
+            run[run.len() - 1].destination_cell()?,
+            run.to_vec(),
+        ))
+    };
+
+    let mut runs = Vec::new();
+    let mut run_start = None;
+    for (pos, edge) in edges.iter().enumerate() {
+        if shared_edges.contains(edge) {
+            if let Some(start) = run_start.take() {
+                runs.push(to_run(&edges[start..pos])?);
+            }
+        } else if run_start.is_none() {
+            run_start = Some(pos);
+        }
+    }
+    if let Some(start) = run_start {
+        runs.push(to_run(&edges[start..])?);
+    }
+    Ok(runs)
+}
+
+/// pair the runs of both paths by their bracketing cells.
+fn pair_divergent_runs(
+    runs: Vec<(H3Cell, H3Cell, Vec<H3DirectedEdge>)>,
+    mut other_runs: Vec<(H3Cell, H3Cell, Vec<H3DirectedEdge>)>,
+) -> Vec<DivergenceSegment> {
+    let mut segments: Vec<_> = runs
+        .into_iter()
+        .map(|(from_cell, to_cell, edges)| {
+            let other_edges = other_runs
+                .iter()
+                .position(|(other_from, other_to, _)| {
+                    *other_from == from_cell && *other_to == to_cell
+                })
+                .map(|pos| other_runs.remove(pos).2)
+                .unwrap_or_default();
+            DivergenceSegment {
+                from_cell,
+                to_cell,
+                edges,
+                other_edges,
+            }
+        })
+        .collect();
+
+    segments.extend(
+        other_runs
+            .into_iter()
+            .map(|(from_cell, to_cell, other_edges)| DivergenceSegment {
+                from_cell,
+                to_cell,
+                edges: vec![],
+                other_edges,
+            }),
+    );
+    segments
+}
+
+/// the maximum over all `cells` of the grid distance to the nearest cell of `other_cells`.
+fn max_min_grid_distance(cells: &[H3Cell], other_cells: &[H3Cell]) -> Result<u32, Error> {
+    let other_cells: H3CellSet = other_cells.iter().copied().collect();
+    let mut max_distance = 0;
+    for cell in cells {
+        if other_cells.contains(cell) {
+            continue;
+        }
+        let distance = cell
+            .grid_disk_distances(1, PATH_COMPARISON_MAX_GRID_DISTANCE)?
+            .into_iter()
+            .filter(|(_, disk_cell)| other_cells.contains(disk_cell))
+            .map(|(distance, _)| distance)
+            .min()
+            .unwrap_or(PATH_COMPARISON_MAX_GRID_DISTANCE);
+        max_distance = max_distance.max(distance);
+    }
+    Ok(max_distance)
 }
 
 impl<W> TryFrom<(DirectedEdgePath, W)> for Path<W> {
@@ -221,10 +408,34 @@ fn index_or_zero(cell: Result<H3Cell, Error>) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use h3ron::{H3DirectedEdge, Index};
+    use h3ron::{H3Cell, H3DirectedEdge, Index};
+
+    use crate::error::Error;
 
     use super::{DirectedEdgePath, Path};
 
+    fn path_from_cells(cells: &[H3Cell]) -> Path<u32> {
+        let edges = cells
+            .windows(2)
+            .map(|pair| pair[0].directed_edge_to(pair[1]).unwrap())
+            .collect::<Vec<_>>();
+        (
+            DirectedEdgePath::DirectedEdgeSequence(edges),
+            cells.len() as u32 - 1,
+        )
+            .try_into()
+            .unwrap()
+    }
+
+    /// a neighbor of `cell` two steps away from `center`.
+    fn outer_neighbor(cell: H3Cell, center: H3Cell) -> H3Cell {
+        cell.grid_ring_unsafe(1)
+            .unwrap()
+            .iter()
+            .find(|neighbor| neighbor.grid_distance_to(center).unwrap() == 2)
+            .unwrap()
+    }
+
     #[test]
     fn pathdirectededges_deterministic_ordering() {
         let r1 =
@@ -263,4 +474,89 @@ mod tests {
         assert_eq!(paths[1], r2);
         assert_eq!(paths[2], r3);
     }
+
+    #[test]
+    fn compare_identical_paths() {
+        let center = H3Cell::from_coordinate((10.0, 20.0).into(), 8).unwrap();
+        let ring: Vec<_> = center.grid_ring_unsafe(1).unwrap().iter().collect();
+        let path = path_from_cells(&ring[0..4]);
+
+        let comparison = path.compare(&path).unwrap();
+        assert_eq!(comparison.shared_edges.len(), 3);
+        assert!((comparison.shared_fraction - 1.0).abs() < 1e-9);
+        assert!((comparison.other_shared_fraction - 1.0).abs() < 1e-9);
+        assert!(comparison.divergence_segments.is_empty());
+        assert_eq!(comparison.max_grid_distance, 0);
+    }
+
+    #[test]
+    fn compare_paths_around_obstacle() {
+        // both paths lead around `center` on opposite sides
+        let center = H3Cell::from_coordinate((10.0, 20.0).into(), 8).unwrap();
+        let ring: Vec<_> = center.grid_ring_unsafe(1).unwrap().iter().collect();
+        assert_eq!(ring.len(), 6);
+        let before = outer_neighbor(ring[0], center);
+        let after = outer_neighbor(ring[3], center);
+
+        let path = path_from_cells(&[before, ring[0], ring[1], ring[2], ring[3], after]);
+        let other = path_from_cells(&[before, ring[0], ring[5], ring[4], ring[3], after]);
+
+        let comparison = path.compare(&other).unwrap();
+        assert_eq!(
+            comparison.shared_edges,
+            vec![
+                before.directed_edge_to(ring[0]).unwrap(),
+                ring[3].directed_edge_to(after).unwrap()
+            ]
+        );
+        assert!(comparison.shared_fraction > 0.2);
+        assert!(comparison.shared_fraction < 0.6);
+
+        assert_eq!(comparison.divergence_segments.len(), 1);
+        let segment = &comparison.divergence_segments[0];
+        assert_eq!(segment.from_cell, ring[0]);
+        assert_eq!(segment.to_cell, ring[3]);
+        assert_eq!(segment.edges.len(), 3);
+        assert_eq!(segment.other_edges.len(), 3);
+        assert_eq!(segment.edges[0].destination_cell().unwrap(), ring[1]);
+        assert_eq!(segment.other_edges[0].destination_cell().unwrap(), ring[5]);
+
+        // ring[1] and ring[2] have neighbors in the other path: ring[0] and ring[3]
+        assert_eq!(comparison.max_grid_distance, 1);
+    }
+
+    #[test]
+    fn compare_grid_distance() {
+        let origin = H3Cell::from_coordinate((10.0, 20.0).into(), 8).unwrap();
+        let far_cell = origin.grid_disk_distances(3, 3).unwrap().first().unwrap().1;
+        let path: Path<u32> = (DirectedEdgePath::OriginIsDestination(origin), 0)
+            .try_into()
+            .unwrap();
+        let other: Path<u32> = (DirectedEdgePath::OriginIsDestination(far_cell), 0)
+            .try_into()
+            .unwrap();
+
+        let comparison = path.compare(&other).unwrap();
+        assert_eq!(comparison.max_grid_distance, 3);
+        assert!(comparison.shared_edges.is_empty());
+        assert!(comparison.shared_fraction.abs() < 1e-9);
+    }
+
+    #[test]
+    fn compare_different_resolutions() {
+        let cell = H3Cell::from_coordinate((10.0, 20.0).into(), 8).unwrap();
+        let path: Path<u32> = (DirectedEdgePath::OriginIsDestination(cell), 0)
+            .try_into()
+            .unwrap();
+        let other: Path<u32> = (
+            DirectedEdgePath::OriginIsDestination(cell.get_parent(7).unwrap()),
+            0,
+        )
+            .try_into()
+            .unwrap();
+        assert!(matches!(
+            path.compare(&other),
+            Err(Error::MixedH3Resolutions(8, 7))
+        ));
+    }
 }
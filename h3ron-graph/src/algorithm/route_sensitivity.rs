@@ -0,0 +1,529 @@
+//! Sensitivity of a route to changes of the weights of its edges.
+//!
+//! Answers questions like "which edges of this route would change the route or its total weight
+//! the most when they get slowed down by 20%?". Instead of routing once per edge of the route,
+//! the detours around all edges of the route are derived from a single pair of shortest path
+//! trees - one from the origin and one towards the destination - so the analysis costs about as
+//! much as two shortest path computations regardless of the length of the route.
+//!
+//! The analysis works on the single edges of the graph. Longedges are expanded, so the
+//! sensitivity is always attributed to real edges.
+use std::collections::BinaryHeap;
+
+use num_traits::ToPrimitive;
+use serde::{Deserialize, Serialize};
+
+use h3ron::collections::{H3CellMap, HashMap};
+use h3ron::{H3Cell, H3DirectedEdge};
+
+use crate::algorithm::landmarks::QueueItem;
+use crate::error::Error;
+use crate::graph::{GetCellEdges, GetEdge};
+
+/// The effect of perturbing the weight of a single edge of a route.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EdgeSensitivity {
+    /// the perturbed edge of the route
+    pub edge: H3DirectedEdge,
+
+    /// position of the edge within the route, starting at `0`
+    pub position: usize,
+
+    /// the unperturbed weight of the edge
+    pub edge_weight: f64,
+
+    /// the weight of the optimal route after the weight of the edge has been perturbed. This is
+    /// either the weight of the original route including the perturbed edge or the weight of the
+    /// detour around the edge.
+    pub perturbed_route_weight: f64,
+
+    /// the difference between `perturbed_route_weight` and the weight of the unperturbed route
+    pub weight_change: f64,
+
+    /// `true` when the optimal route avoids the edge after the perturbation
+    pub route_changes: bool,
+
+    /// the weight of the best route avoiding the edge. `None` when no such route exists.
+    pub detour_weight: Option<f64>,
+}
+
+pub trait RouteSensitivity {
+    /// Analyze how perturbing the weight of each edge of the shortest route from `origin_cell`
+    /// to `destination_cell` affects the optimal route.
+    ///
+    /// The weight of each edge gets multiplied with `1.0 + perturbation` - one edge at a time - so
+    /// `0.2` models an edge being slowed down by 20%. The returned [`EdgeSensitivity`] values
+    /// of the `top_n` edges with the largest `weight_change` are ordered by their `weight_change`,
+    /// starting with the largest.
+    ///
+    /// The detours are exact for graphs where each edge has a reverse edge of the same weight
+    /// and the shortest routes are unique. Otherwise the `detour_weight` is the weight of an
+    /// existing route avoiding the edge, but a better detour may exist.
+    ///
+    /// Fails with [`Error::OriginIsDestination`] when both cells are the same and with
+    /// [`Error::DestinationsNotInGraph`] when the destination is not reachable.
+    fn route_sensitivity(
+        &self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        perturbation: f64,
+        top_n: usize,
+    ) -> Result<Vec<EdgeSensitivity>, Error>;
+}
+
+impl<G, W> RouteSensitivity for G
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: Copy + ToPrimitive,
+{
+    fn route_sensitivity(
+        &self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        perturbation: f64,
+        top_n: usize,
+    ) -> Result<Vec<EdgeSensitivity>, Error> {
+        if !perturbation.is_finite() {
+            return Err(Error::Other(
+                "the perturbation must be a finite number".to_string(),
+            ));
+        }
+        if origin_cell == destination_cell {
+            return Err(Error::OriginIsDestination);
+        }
+
+        let forward = search_tree(self, origin_cell, Direction::Forward)?;
+        let route_weight = *forward
+            .weights
+            .get(&destination_cell)
+            .ok_or(Error::DestinationsNotInGraph)?;
+        let route = forward.route_to(destination_cell);
+        let backward = search_tree(self, destination_cell, Direction::Backward)?;
+
+        // positions of the edges within the route, starting at 1
+        let route_positions: HashMap<H3DirectedEdge, usize> = route
+            .iter()
+            .enumerate()
+            .map(|(i, (edge, _))| (*edge, i + 1))
+            .collect();
+
+        // The tree path to a cell shares a prefix with the route, so the route edges on it
+        // are identified by the position of the last one. Likewise the tree path from a cell to
+        // the destination contains a suffix of the route, identified by its first position.
+        let mut last_route_position: H3CellMap<usize> = Default::default();
+        for cell in forward.settled.iter() {
+            let position = match forward.links.get(cell) {
+                Some((edge, _)) => last_route_position
+                    .get(&edge.origin_cell()?)
+                    .copied()
+                    .unwrap_or(0)
+                    .max(route_positions.get(edge).copied().unwrap_or(0)),
+                None => 0,
+            };
+            last_route_position.insert(*cell, position);
+        }
+        let mut first_route_position: H3CellMap<usize> = Default::default();
+        for cell in backward.settled.iter() {
+            let position = match backward.links.get(cell) {
+                Some((edge, _)) => first_route_position
+                    .get(&edge.destination_cell()?)
+                    .copied()
+                    .unwrap_or(route.len() + 1)
+                    .min(
+                        route_positions
+                            .get(edge)
+                            .copied()
+                            .unwrap_or(route.len() + 1),
+                    ),
+                None => route.len() + 1,
+            };
+            first_route_position.insert(*cell, position);
+        }
+
+        // Each edge outside of the route connecting both trees is a detour around all route edges
+        // which are neither part of the tree path to its origin nor of the tree path from
+        // its destination.
+        let mut detour_candidates = Vec::new();
+        for cell in forward.settled.iter() {
+            let weight_to_cell = forward.weights[cell];
+            let first_avoided = last_route_position[cell] + 1;
+            for (edge, edge_weight) in self.get_edges_originating_from(cell)? {
+                if route_positions.contains_key(&edge) {
+                    continue;
+                }
+                let next_cell = edge.destination_cell()?;
+                let weight_from_next_cell = match backward.weights.get(&next_cell) {
+                    Some(weight) => *weight,
+                    None => continue,
+                };
+                let last_avoided = first_route_position[&next_cell] - 1;
+                if first_avoided <= last_avoided {
+                    detour_candidates.push((
+                        weight_to_cell
+                            + weight_to_f64(&edge_weight.weight)?
+                            + weight_from_next_cell,
+                        first_avoided,
+                        last_avoided,
+                    ));
+                }
+            }
+        }
+        let detours = best_detours(detour_candidates, route.len());
+
+        let mut sensitivities: Vec<_> = route
+            .iter()
+            .enumerate()
+            .map(|(i, (edge, edge_weight))| {
+                let detour_weight = detours[i + 1];
+                let perturbed_route_weight = route_weight + edge_weight * perturbation;
+                let route_changes = detour_weight
+                    .is_some_and(|detour_weight| detour_weight < perturbed_route_weight);
+                let perturbed_route_weight = if route_changes {
+                    detour_weight.unwrap_or(perturbed_route_weight)
+                } else {
+                    perturbed_route_weight
+                };
+                EdgeSensitivity {
+                    edge: *edge,
+                    position: i,
+                    edge_weight: *edge_weight,
+                    perturbed_route_weight,
+                    weight_change: perturbed_route_weight - route_weight,
+                    route_changes,
+                    detour_weight,
+                }
+            })
+            .collect();
+
+        // stable sort to keep the order of the route for equal changes
+        sensitivities.sort_by(|a, b| b.weight_change.total_cmp(&a.weight_change));
+        sensitivities.truncate(top_n);
+        Ok(sensitivities)
+    }
+}
+
+fn weight_to_f64<W: ToPrimitive>(weight: &W) -> Result<f64, Error> {
+    weight
+        .to_f64()
+        .ok_or_else(|| Error::Other("edge weight is not representable as f64".to_string()))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// follow the edges starting at the root
+    Forward,
+
+    /// follow the edges ending at the root in reverse
+    Backward,
+}
+
+/// Shortest path tree over the single edges of a graph.
+struct SearchTree {
+    /// the cells in the order they have been settled, starting with the root
+    settled: Vec<H3Cell>,
+
+    /// weight of the shortest route between the root and the cell
+    weights: H3CellMap<f64>,
+
+    /// the edge connecting the cell to its parent within the tree and its weight
+    links: H3CellMap<(H3DirectedEdge, f64)>,
+}
+
+impl SearchTree {
+    /// the edges of a forward tree leading from the root to `cell`, starting at the root
+    fn route_to(&self, cell: H3Cell) -> Vec<(H3DirectedEdge, f64)> {
+        let mut route = Vec::new();
+        let mut current = cell;
+        while let Some((edge, weight)) = self.links.get(&current) {
+            route.push((*edge, *weight));
+            current = match edge.origin_cell() {
+                Ok(origin_cell) => origin_cell,
+                Err(_) => break,
+            };
+        }
+        route.reverse();
+        route
+    }
+}
+
+fn search_tree<G, W>(graph: &G, root: H3Cell, direction: Direction) -> Result<SearchTree, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: Copy + ToPrimitive,
+{
+    let mut tree = SearchTree {
+        settled: Vec::new(),
+        weights: Default::default(),
+        links: Default::default(),
+    };
+    let mut queue = BinaryHeap::new();
+    tree.weights.insert(root, 0.0);
+    queue.push(QueueItem {
+        priority: 0.0,
+        payload: root,
+    });
+
+    let mut neighbors = Vec::new();
+    while let Some(QueueItem {
+        priority: weight,
+        payload: cell,
+    }) = queue.pop()
+    {
+        if tree.weights.get(&cell).is_some_and(|best| weight > *best) {
+            // outdated queue entry
+            continue;
+        }
+        tree.settled.push(cell);
+
+        neighbors.clear();
+        match direction {
+            Direction::Forward => {
+                for (edge, edge_weight) in graph.get_edges_originating_from(&cell)? {
+                    neighbors.push((edge, edge.destination_cell()?, edge_weight.weight));
+                }
+            }
+            Direction::Backward => {
+                for outgoing_edge in cell.directed_edges()?.iter() {
+                    let edge = outgoing_edge.reversed()?;
+                    if let Some(edge_weight) = graph.get_edge(&edge)? {
+                        neighbors.push((
+                            edge,
+                            outgoing_edge.destination_cell()?,
+                            edge_weight.weight,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (edge, next_cell, edge_weight) in neighbors.iter() {
+            let edge_weight = weight_to_f64(edge_weight)?;
+            let next_weight = weight + edge_weight;
+            if tree
+                .weights
+                .get(next_cell)
+                .is_none_or(|known| next_weight < *known)
+            {
+                tree.weights.insert(*next_cell, next_weight);
+                tree.links.insert(*next_cell, (*edge, edge_weight));
+                queue.push(QueueItem {
+                    priority: next_weight,
+                    payload: *next_cell,
+                });
+            }
+        }
+    }
+    Ok(tree)
+}
+
+/// The weight of the best detour for each route position `1..=num_positions`.
+///
+/// Each candidate is a `(weight, first_position, last_position)` tuple. The candidates are
+/// assigned in the order of their weight, skipping positions which already received a detour.
+fn best_detours(
+    mut candidates: Vec<(f64, usize, usize)>,
+    num_positions: usize,
+) -> Vec<Option<f64>> {
+    candidates.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut detours = vec![None; num_positions + 2];
+    // the next position without a detour for each position. The last one is a sentinel.
+    let mut next_unassigned: Vec<usize> = (0..(num_positions + 2)).collect();
+    for (weight, first_position, last_position) in candidates {
+        let mut position = find_unassigned(&mut next_unassigned, first_position);
+        while position <= last_position {
+            detours[position] = Some(weight);
+            next_unassigned[position] = position + 1;
+            position = find_unassigned(&mut next_unassigned, position + 1);
+        }
+    }
+    detours
+}
+
+fn find_unassigned(next_unassigned: &mut [usize], position: usize) -> usize {
+    let mut unassigned = position;
+    while next_unassigned[unassigned] != unassigned {
+        unassigned = next_unassigned[unassigned];
+    }
+
+    // path compression
+    let mut current = position;
+    while next_unassigned[current] != unassigned {
+        let next = next_unassigned[current];
+        next_unassigned[current] = unassigned;
+        current = next;
+    }
+    unassigned
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use geo_types::Coordinate;
+
+    use h3ron::{H3Cell, H3DirectedEdge};
+
+    use crate::algorithm::landmarks::splitmix64;
+    use crate::algorithm::route_sensitivity::RouteSensitivity;
+    use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+    use crate::error::Error;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    /// grid disk where each edge has a reverse edge of the same random weight. Some
+    /// neighbors are not connected to create longedges.
+    fn symmetric_edges(seed: u64) -> (Vec<H3Cell>, Vec<(H3DirectedEdge, u32)>) {
+        let mut state = seed;
+        let cells: Vec<_> = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8)
+            .unwrap()
+            .grid_disk(4)
+            .unwrap()
+            .iter()
+            .collect();
+
+        let mut edges = Vec::new();
+        for cell in cells.iter() {
+            for edge in cell.directed_edges().unwrap().iter() {
+                let neighbor = edge.destination_cell().unwrap();
+                if *cell < neighbor && cells.contains(&neighbor) && splitmix64(&mut state) % 10 < 8
+                {
+                    let weight = 1 + (splitmix64(&mut state) % 1_000_000) as u32;
+                    edges.push((edge, weight));
+                    edges.push((edge.reversed().unwrap(), weight));
+                }
+            }
+        }
+        (cells, edges)
+    }
+
+    /// build a graph from `edges`, changing the weights using `weight_fn`. Edges
+    /// for which `weight_fn` returns `None` are left out.
+    fn build_graph<F>(edges: &[(H3DirectedEdge, u32)], weight_fn: F) -> PreparedH3EdgeGraph<u32>
+    where
+        F: Fn(&H3DirectedEdge, u32) -> Option<u32>,
+    {
+        let mut graph = H3EdgeGraph::new(8);
+        for (edge, weight) in edges {
+            if let Some(weight) = weight_fn(edge, *weight) {
+                graph.add_edge(*edge, weight).unwrap();
+            }
+        }
+        graph.try_into().unwrap()
+    }
+
+    fn route_weight(
+        graph: &PreparedH3EdgeGraph<u32>,
+        origin: H3Cell,
+        destination: H3Cell,
+    ) -> Option<f64> {
+        match graph.shortest_path(
+            origin,
+            [destination],
+            &DefaultShortestPathOptions::default(),
+        ) {
+            Ok(paths) => paths.first().map(|path| f64::from(path.cost)),
+            Err(Error::DestinationsNotInGraph) => None,
+            Err(e) => panic!("routing failed: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn matches_per_edge_rerouting() {
+        let mut num_checked_edges = 0;
+        let mut num_route_changes = 0;
+        for seed in 0..3 {
+            let (cells, edges) = symmetric_edges(seed);
+            let graph = build_graph(&edges, |_, weight| Some(weight));
+
+            let mut state = seed;
+            for _ in 0..5 {
+                let origin = cells[(splitmix64(&mut state) % cells.len() as u64) as usize];
+                let destination = cells[(splitmix64(&mut state) % cells.len() as u64) as usize];
+                let route_weight_before = match route_weight(&graph, origin, destination) {
+                    Some(weight) if origin != destination => weight,
+                    _ => continue,
+                };
+
+                // doubling the weight of the edges keeps the weights integers
+                let sensitivities = graph
+                    .route_sensitivity(origin, destination, 1.0, usize::MAX)
+                    .unwrap();
+                assert!(!sensitivities.is_empty());
+
+                for sensitivity in sensitivities.iter() {
+                    let perturbed_graph = build_graph(&edges, |edge, weight| {
+                        Some(if *edge == sensitivity.edge {
+                            weight * 2
+                        } else {
+                            weight
+                        })
+                    });
+                    assert_eq!(
+                        route_weight(&perturbed_graph, origin, destination),
+                        Some(sensitivity.perturbed_route_weight)
+                    );
+
+                    let graph_without_edge = build_graph(&edges, |edge, weight| {
+                        (*edge != sensitivity.edge).then_some(weight)
+                    });
+                    let detour_weight = route_weight(&graph_without_edge, origin, destination);
+                    assert_eq!(detour_weight, sensitivity.detour_weight);
+                    assert_eq!(
+                        sensitivity.route_changes,
+                        detour_weight.is_some_and(|detour_weight| detour_weight
+                            < route_weight_before + sensitivity.edge_weight)
+                    );
+
+                    num_checked_edges += 1;
+                    if sensitivity.route_changes {
+                        num_route_changes += 1;
+                    }
+                }
+            }
+        }
+        assert!(num_checked_edges > 20);
+        assert!(num_route_changes > 0);
+    }
+
+    #[test]
+    fn top_n_ordering() {
+        let (cells, edges) = symmetric_edges(7);
+        let graph = build_graph(&edges, |_, weight| Some(weight));
+        let origin = cells[0];
+        let destination = *cells.last().unwrap();
+
+        let all = graph
+            .route_sensitivity(origin, destination, 0.2, usize::MAX)
+            .unwrap();
+        assert!(all.len() > 3);
+        for window in all.windows(2) {
+            assert!(window[0].weight_change >= window[1].weight_change);
+        }
+        let mut positions: Vec<_> = all.iter().map(|s| s.position).collect();
+        positions.sort_unstable();
+        assert_eq!(positions, (0..all.len()).collect::<Vec<_>>());
+
+        let top = graph
+            .route_sensitivity(origin, destination, 0.2, 3)
+            .unwrap();
+        assert_eq!(top.as_slice(), &all[..3]);
+    }
+
+    #[test]
+    fn invalid_queries() {
+        let (cells, edges) = symmetric_edges(3);
+        let graph = build_graph(&edges, |_, weight| Some(weight));
+        assert!(matches!(
+            graph.route_sensitivity(cells[0], cells[0], 0.2, 10),
+            Err(Error::OriginIsDestination)
+        ));
+        let outside = H3Cell::from_coordinate(Coordinate::from((30.0, 20.0)), 8).unwrap();
+        assert!(matches!(
+            graph.route_sensitivity(cells[0], outside, 0.2, 10),
+            Err(Error::DestinationsNotInGraph)
+        ));
+        assert!(graph
+            .route_sensitivity(cells[0], cells[1], f64::NAN, 10)
+            .is_err());
+    }
+}
@@ -0,0 +1,338 @@
+//! Routing through a sequence of via points.
+//!
+//! A route from `A` through `B` and `C` to `D` is computed as the consecutive legs
+//! `A -> B`, `B -> C` and `C -> D`. The legs are returned individually in a [`MultiLegPath`].
+use std::ops::Add;
+
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+
+use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution};
+
+use crate::algorithm::path::{DirectedEdgePath, Path};
+use crate::algorithm::shortest_path::{shortest_path_after_arrival, ShortestPathOptions};
+use crate::algorithm::NearestGraphNodes;
+use crate::error::Error;
+use crate::graph::{GetCellEdges, GetCellNode};
+
+/// Behaviour at the via points.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ViaTurnPolicy {
+    /// Allow leaving a via point using the reverse of the edge the via point was reached by.
+    #[default]
+    AllowBacktrack,
+
+    /// Forbid leaving a via point using the reverse of the edge the via point was reached by.
+    ForbidBacktrack,
+}
+
+/// Behaviour when no path is found for a leg.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LegFailurePolicy {
+    /// fail with [`Error::LegNotRoutable`].
+    #[default]
+    FailRequest,
+
+    /// return the legs found before the failed leg. The index of the failed leg is
+    /// available as [`MultiLegPath::failed_leg`].
+    ReturnPartial,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteViaOptions {
+    /// see [`ShortestPathOptions::max_distance_to_graph`]
+    pub max_distance_to_graph: u32,
+    pub turn_policy: ViaTurnPolicy,
+    pub failure_policy: LegFailurePolicy,
+}
+
+impl ShortestPathOptions for RouteViaOptions {
+    fn max_distance_to_graph(&self) -> u32 {
+        self.max_distance_to_graph
+    }
+}
+
+/// The legs of a route through via points.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiLegPath<W> {
+    /// one path per pair of consecutive points
+    pub legs: Vec<Path<W>>,
+
+    /// index of the leg no path could be found for. Only set when using
+    /// [`LegFailurePolicy::ReturnPartial`].
+    pub failed_leg: Option<usize>,
+}
+
+impl<W> MultiLegPath<W>
+where
+    W: Zero + Copy,
+{
+    /// `true` when paths for all legs have been found.
+    pub fn is_complete(&self) -> bool {
+        self.failed_leg.is_none()
+    }
+
+    pub fn leg_weights(&self) -> Vec<W> {
+        self.legs.iter().map(|leg| leg.cost).collect()
+    }
+
+    /// The sum of the weights of all legs.
+    pub fn cost(&self) -> W {
+        self.legs.iter().fold(W::zero(), |acc, leg| acc + leg.cost)
+    }
+
+    /// A single path from the origin of the first leg to the destination of the last leg.
+    ///
+    /// The edges are the concatenation of the edges of all legs, so the cells connecting
+    /// the legs are only contained once. Using a `max_distance_to_graph` larger than zero
+    /// may result in gaps between the legs.
+    pub fn combined_path(&self) -> Result<Path<W>, Error> {
+        let (first, last) = match (self.legs.first(), self.legs.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Err(Error::EmptyPath),
+        };
+        let edges: Vec<_> = self
+            .legs
+            .iter()
+            .flat_map(|leg| leg.directed_edge_path.edges().iter().copied())
+            .collect();
+        let directed_edge_path = if edges.is_empty() {
+            DirectedEdgePath::OriginIsDestination(first.directed_edge_path.origin_cell()?)
+        } else {
+            DirectedEdgePath::DirectedEdgeSequence(edges)
+        };
+        Ok(Path {
+            origin_cell: first.origin_cell,
+            destination_cell: last.destination_cell,
+            cost: self.cost(),
+            directed_edge_path,
+        })
+    }
+}
+
+pub trait RouteVia<W> {
+    /// Route through all `points` in the given order.
+    ///
+    /// At least two points are required.
+    fn route_via(
+        &self,
+        points: &[H3Cell],
+        options: &RouteViaOptions,
+    ) -> Result<MultiLegPath<W>, Error>;
+}
+
+impl<W, G> RouteVia<W> for G
+where
+    G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes,
+    W: PartialOrd + PartialEq + Add + Copy + Ord + Zero,
+{
+    fn route_via(
+        &self,
+        points: &[H3Cell],
+        options: &RouteViaOptions,
+    ) -> Result<MultiLegPath<W>, Error> {
+        if points.len() < 2 {
+            return Err(Error::InsufficientNumberOfPoints);
+        }
+
+        let mut multi_leg_path = MultiLegPath {
+            legs: Vec::with_capacity(points.len() - 1),
+            failed_leg: None,
+        };
+
+        // the edge the current origin was reached by
+        let mut arrival_edge: Option<H3DirectedEdge> = None;
+
+        for (leg_i, leg_points) in points.windows(2).enumerate() {
+            let leg_arrival_edge = match options.turn_policy {
+                ViaTurnPolicy::ForbidBacktrack => arrival_edge,
+                ViaTurnPolicy::AllowBacktrack => None,
+            };
+            let leg = route_leg(self, leg_points, leg_arrival_edge, options)?;
+
+            match leg {
+                Some(path) => {
+                    if let Some(last_edge) = path.directed_edge_path.edges().last() {
+                        arrival_edge = Some(*last_edge);
+                    }
+                    multi_leg_path.legs.push(path);
+                }
+                None => match options.failure_policy {
+                    LegFailurePolicy::FailRequest => return Err(Error::LegNotRoutable(leg_i)),
+                    LegFailurePolicy::ReturnPartial => {
+                        multi_leg_path.failed_leg = Some(leg_i);
+                        break;
+                    }
+                },
+            }
+        }
+        Ok(multi_leg_path)
+    }
+}
+
+/// the shortest path between the two `leg_points`, `None` when there is none.
+///
+/// The path does not start with the reverse of the `arrival_edge`.
+fn route_leg<G, W>(
+    graph: &G,
+    leg_points: &[H3Cell],
+    arrival_edge: Option<H3DirectedEdge>,
+    options: &RouteViaOptions,
+) -> Result<Option<Path<W>>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes,
+    W: PartialOrd + PartialEq + Add + Copy + Ord + Zero,
+{
+    match shortest_path_after_arrival(graph, leg_points[0], arrival_edge, [leg_points[1]], options)
+    {
+        Ok(mut paths) => Ok(paths.pop()),
+        Err(Error::DestinationsNotInGraph) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use h3ron::{H3Cell, HasH3Resolution};
+
+    use crate::algorithm::route_via::{LegFailurePolicy, RouteVia, RouteViaOptions, ViaTurnPolicy};
+    use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+    use crate::error::Error;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    /// a hexagon of cells around a center cell. Edges between neighboring cells of the ring
+    /// have a weight of 3, the spokes from and to the center cell a weight of 2.
+    fn wheel_graph() -> (PreparedH3EdgeGraph<u32>, H3Cell, Vec<H3Cell>) {
+        let center = H3Cell::from_coordinate((10.2, 45.6).into(), 8).unwrap();
+        let ring: Vec<_> = center.grid_ring_unsafe(1).unwrap().iter().collect();
+        let mut graph = H3EdgeGraph::new(center.h3_resolution());
+        for cell in ring.iter() {
+            graph
+                .add_edge(center.directed_edge_to(*cell).unwrap(), 2)
+                .unwrap();
+            graph
+                .add_edge(cell.directed_edge_to(center).unwrap(), 2)
+                .unwrap();
+            for other in ring.iter() {
+                if let Ok(edge) = cell.directed_edge_to(*other) {
+                    graph.add_edge(edge, 3).unwrap();
+                }
+            }
+        }
+        (graph.try_into().unwrap(), center, ring)
+    }
+
+    #[test]
+    fn permissive_equals_pairwise_queries() {
+        let (graph, center, ring) = wheel_graph();
+        let points = [ring[0], center, ring[3], ring[5]];
+        let multi_leg_path = graph
+            .route_via(&points, &RouteViaOptions::default())
+            .unwrap();
+        assert!(multi_leg_path.is_complete());
+        assert_eq!(multi_leg_path.legs.len(), 3);
+
+        let mut expected_edges = vec![];
+        for (leg, leg_points) in multi_leg_path.legs.iter().zip(points.windows(2)) {
+            let mut pairwise = graph
+                .shortest_path(
+                    leg_points[0],
+                    [leg_points[1]],
+                    &DefaultShortestPathOptions::default(),
+                )
+                .unwrap();
+            assert_eq!(pairwise.len(), 1);
+            let pairwise = pairwise.remove(0);
+            assert_eq!(leg, &pairwise);
+            expected_edges.extend_from_slice(pairwise.directed_edge_path.edges());
+        }
+        assert_eq!(multi_leg_path.leg_weights(), vec![2, 2, 4]);
+
+        let combined = multi_leg_path.combined_path().unwrap();
+        assert_eq!(combined.cost, 8);
+        assert_eq!(combined.origin_cell, ring[0]);
+        assert_eq!(combined.destination_cell, ring[5]);
+        assert_eq!(
+            combined.directed_edge_path.edges(),
+            expected_edges.as_slice()
+        );
+
+        // the junction cells are contained only once
+        let cells = combined.directed_edge_path.cells().unwrap();
+        assert_eq!(cells.len(), expected_edges.len() + 1);
+    }
+
+    #[test]
+    fn forbid_backtrack() {
+        let (graph, center, ring) = wheel_graph();
+
+        // returning to the previous cell is the shortest path for the middle leg
+        let points = [ring[3], center, ring[3], ring[0]];
+        let permissive = graph
+            .route_via(&points, &RouteViaOptions::default())
+            .unwrap();
+        let forbidding = graph
+            .route_via(
+                &points,
+                &RouteViaOptions {
+                    turn_policy: ViaTurnPolicy::ForbidBacktrack,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(permissive.leg_weights(), vec![2, 2, 4]);
+        assert_eq!(forbidding.leg_weights()[0], 2);
+        assert_eq!(forbidding.leg_weights()[1], 5);
+        assert_ne!(forbidding.legs[1], permissive.legs[1]);
+
+        // the middle leg does not start with the reverse of the arriving edge
+        let arrival_edge = forbidding.legs[0]
+            .directed_edge_path
+            .edges()
+            .last()
+            .unwrap();
+        let departure_edge = forbidding.legs[1]
+            .directed_edge_path
+            .edges()
+            .first()
+            .unwrap();
+        assert_ne!(*departure_edge, arrival_edge.reversed().unwrap());
+    }
+
+    #[test]
+    fn failing_leg() {
+        let (graph, center, ring) = wheel_graph();
+        let unreachable = H3Cell::from_coordinate((-40.0, -20.0).into(), 8).unwrap();
+        let points = [ring[0], center, unreachable, ring[3]];
+
+        assert!(matches!(
+            graph.route_via(&points, &RouteViaOptions::default()),
+            Err(Error::LegNotRoutable(1))
+        ));
+
+        let partial = graph
+            .route_via(
+                &points,
+                &RouteViaOptions {
+                    failure_policy: LegFailurePolicy::ReturnPartial,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        assert!(!partial.is_complete());
+        assert_eq!(partial.failed_leg, Some(1));
+        assert_eq!(partial.legs.len(), 1);
+        assert_eq!(partial.cost(), 2);
+    }
+
+    #[test]
+    fn insufficient_points() {
+        let (graph, center, _) = wheel_graph();
+        assert!(graph
+            .route_via(&[center], &RouteViaOptions::default())
+            .is_err());
+    }
+}
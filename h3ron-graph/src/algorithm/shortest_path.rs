@@ -9,7 +9,7 @@ use rayon::prelude::*;
 use h3ron::collections::hashbrown::hash_map::Entry;
 use h3ron::collections::{H3CellMap, H3Treemap, HashMap};
 use h3ron::iter::change_resolution;
-use h3ron::{H3Cell, HasH3Resolution};
+use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution};
 
 use crate::algorithm::dijkstra::edge_dijkstra;
 use crate::algorithm::path::Path;
@@ -178,6 +178,7 @@ where
                 shortest_path_many_worker(
                     self,
                     graph_connected_origin_cell,
+                    None,
                     output_origin_cells.as_slice(),
                     &destination_treemap,
                     &destination_substmap,
@@ -219,53 +220,76 @@ where
         I::Item: Borrow<H3Cell>,
         OPT: ShortestPathOptions,
     {
-        let (graph_connected_origin_cell, requested_origin_cells) = {
-            let mut filtered_origin_cells = substitute_origin_cells(
-                self,
-                options.max_distance_to_graph(),
-                std::iter::once(origin_cell),
-                false, // not necessary
-            )?;
-            if filtered_origin_cells.is_empty() {
-                return Ok(Default::default());
-            } else {
-                filtered_origin_cells.remove(0)
-            }
-        };
-
-        let destination_substmap = {
-            let mut origins_treemap: H3Treemap<H3Cell> = Default::default();
-            origins_treemap.insert(graph_connected_origin_cell);
-            substitute_destination_cells(
-                self,
-                options.max_distance_to_graph(),
-                destination_cells,
-                &origins_treemap,
-            )?
-        };
+        shortest_path_after_arrival(self, origin_cell, None, destination_cells, options)
+    }
+}
 
-        if destination_substmap.0.is_empty() {
+/// Like [`ShortestPath::shortest_path`], but starting in the state of having reached the
+/// `origin_cell` using the `arrival_edge`, which forbids leaving the origin using the reverse
+/// of that edge.
+pub(crate) fn shortest_path_after_arrival<G, W, I, OPT>(
+    graph: &G,
+    origin_cell: H3Cell,
+    arrival_edge: Option<H3DirectedEdge>,
+    destination_cells: I,
+    options: &OPT,
+) -> Result<Vec<Path<W>>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes,
+    W: PartialOrd + PartialEq + Add + Copy + Ord + Zero,
+    I: IntoIterator,
+    I::Item: Borrow<H3Cell>,
+    OPT: ShortestPathOptions,
+{
+    let (graph_connected_origin_cell, requested_origin_cells) = {
+        let mut filtered_origin_cells = substitute_origin_cells(
+            graph,
+            options.max_distance_to_graph(),
+            std::iter::once(origin_cell),
+            false, // not necessary
+        )?;
+        if filtered_origin_cells.is_empty() {
             return Ok(Default::default());
+        } else {
+            filtered_origin_cells.remove(0)
         }
+    };
 
-        let destination_treemap =
-            H3Treemap::from_iter_with_sort(destination_substmap.0.keys().copied());
+    let destination_substmap = {
+        let mut origins_treemap: H3Treemap<H3Cell> = Default::default();
+        origins_treemap.insert(graph_connected_origin_cell);
+        substitute_destination_cells(
+            graph,
+            options.max_distance_to_graph(),
+            destination_cells,
+            &origins_treemap,
+        )?
+    };
 
-        shortest_path_many_worker(
-            self,
-            &graph_connected_origin_cell,
-            requested_origin_cells.as_slice(),
-            &destination_treemap,
-            &destination_substmap,
-            options,
-            Ok,
-        )
+    if destination_substmap.0.is_empty() {
+        return Ok(Default::default());
     }
+
+    let destination_treemap =
+        H3Treemap::from_iter_with_sort(destination_substmap.0.keys().copied());
+
+    shortest_path_many_worker(
+        graph,
+        &graph_connected_origin_cell,
+        arrival_edge,
+        requested_origin_cells.as_slice(),
+        &destination_treemap,
+        &destination_substmap,
+        options,
+        Ok,
+    )
 }
 
+#[allow(clippy::too_many_arguments)]
 fn shortest_path_many_worker<G, W, OPT, PM, O>(
     graph: &G,
     origin_cell: &H3Cell,
+    arrival_edge: Option<H3DirectedEdge>,
     requested_origin_cells: &[H3Cell],
     destination_cells: &H3Treemap<H3Cell>,
     destination_substmap: &SubstituteMap,
@@ -282,6 +306,7 @@ where
     let found_paths = edge_dijkstra(
         graph,
         origin_cell,
+        arrival_edge,
         destination_cells,
         options.num_destinations_to_reach(),
     )?;
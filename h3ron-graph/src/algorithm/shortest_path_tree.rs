@@ -0,0 +1,265 @@
+//! Shortest path trees to answer many routing queries sharing the same origin.
+use std::mem::size_of;
+use std::ops::Add;
+
+use num_traits::Zero;
+use serde::{Deserialize, Serialize};
+
+use h3ron::{H3Cell, H3DirectedEdge};
+
+use crate::algorithm::dijkstra::edge_dijkstra_predecessors;
+use crate::algorithm::path::{DirectedEdgePath, Path};
+use crate::error::Error;
+use crate::graph::GetCellEdges;
+
+/// The result of a single Dijkstra run from an origin cell to all cells of the graph
+/// reachable within an optional maximum weight.
+///
+/// For each settled cell the tree stores the accumulated weight and the edge leading to
+/// that cell. To keep the memory requirements low for large trees, this data is stored in
+/// parallel arrays sorted by the cell.
+///
+/// Created by [`BuildShortestPathTree::shortest_path_tree`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShortestPathTree<W> {
+    origin_cell: H3Cell,
+
+    /// sorted settled cells, not including the `origin_cell`.
+    cells: Vec<H3Cell>,
+
+    /// accumulated weight of the cells at the same position in `cells`.
+    weights: Vec<W>,
+
+    /// the edge leading to the cells at the same position in `cells`.
+    incoming_edges: Vec<H3DirectedEdge>,
+}
+
+impl<W> ShortestPathTree<W>
+where
+    W: Zero + Copy,
+{
+    pub const fn origin_cell(&self) -> H3Cell {
+        self.origin_cell
+    }
+
+    /// number of settled cells including the origin cell.
+    pub fn num_settled_cells(&self) -> usize {
+        self.cells.len() + 1
+    }
+
+    /// iterate over all settled cells, starting with the origin cell.
+    pub fn settled_cells(&self) -> impl Iterator<Item = H3Cell> + '_ {
+        std::iter::once(self.origin_cell).chain(self.cells.iter().copied())
+    }
+
+    /// The accumulated weight from the origin cell to `cell`.
+    ///
+    /// Returns `None` when the cell has not been reached.
+    pub fn weight_to(&self, cell: &H3Cell) -> Option<W> {
+        if cell == &self.origin_cell {
+            Some(W::zero())
+        } else {
+            self.position(cell).map(|position| self.weights[position])
+        }
+    }
+
+    /// The path from the origin cell to `cell`.
+    ///
+    /// Returns `None` when the cell has not been reached. For the origin cell itself - which is
+    /// part of the [`ShortestPathTree::settled_cells`] - the path is a
+    /// [`DirectedEdgePath::OriginIsDestination`] without any edges.
+    pub fn path_to(&self, cell: &H3Cell) -> Result<Option<Path<W>>, Error> {
+        let cost = match self.weight_to(cell) {
+            Some(cost) => cost,
+            None => return Ok(None),
+        };
+
+        let mut edges = Vec::new();
+        let mut current = *cell;
+        while let Some(position) = self.position(&current) {
+            let edge = self.incoming_edges[position];
+            edges.push(edge);
+            current = edge.origin_cell()?;
+        }
+        edges.reverse();
+
+        Ok(Some(Path {
+            origin_cell: self.origin_cell,
+            destination_cell: *cell,
+            cost,
+            directed_edge_path: if edges.is_empty() {
+                DirectedEdgePath::OriginIsDestination(self.origin_cell)
+            } else {
+                DirectedEdgePath::DirectedEdgeSequence(edges)
+            },
+        }))
+    }
+
+    /// approximate size of the tree in memory in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        size_of::<Self>()
+            + self.cells.capacity() * size_of::<H3Cell>()
+            + self.weights.capacity() * size_of::<W>()
+            + self.incoming_edges.capacity() * size_of::<H3DirectedEdge>()
+    }
+
+    fn position(&self, cell: &H3Cell) -> Option<usize> {
+        self.cells.binary_search(cell).ok()
+    }
+}
+
+pub trait BuildShortestPathTree<W> {
+    /// Build the [`ShortestPathTree`] from `origin_cell` to all reachable cells of the graph.
+    ///
+    /// When `max_weight` is set, cells with a higher accumulated weight are not
+    /// part of the tree.
+    ///
+    /// In contrast to the [`ShortestPath`](crate::algorithm::ShortestPath) trait, the
+    /// longedges of prepared graphs are not used as the tree needs to contain all cells
+    /// the longedges would skip.
+    fn shortest_path_tree(
+        &self,
+        origin_cell: H3Cell,
+        max_weight: Option<W>,
+    ) -> Result<ShortestPathTree<W>, Error>;
+}
+
+impl<W, G> BuildShortestPathTree<W> for G
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: Zero + Ord + Copy + Add,
+{
+    fn shortest_path_tree(
+        &self,
+        origin_cell: H3Cell,
+        max_weight: Option<W>,
+    ) -> Result<ShortestPathTree<W>, Error> {
+        let mut settled: Vec<_> = edge_dijkstra_predecessors(self, &origin_cell, max_weight)?
+            .into_iter()
+            .collect();
+        settled.sort_unstable_by_key(|(cell, _)| *cell);
+
+        let mut cells = Vec::with_capacity(settled.len());
+        let mut weights = Vec::with_capacity(settled.len());
+        let mut incoming_edges = Vec::with_capacity(settled.len());
+        for (cell, (weight, edge)) in settled {
+            cells.push(cell);
+            weights.push(weight);
+            incoming_edges.push(edge);
+        }
+
+        Ok(ShortestPathTree {
+            origin_cell,
+            cells,
+            weights,
+            incoming_edges,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use geo_types::Coordinate;
+
+    use h3ron::H3Cell;
+
+    use crate::algorithm::path::DirectedEdgePath;
+    use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+    use crate::algorithm::shortest_path_tree::BuildShortestPathTree;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    fn build_graph() -> (H3Cell, PreparedH3EdgeGraph<u32>) {
+        let origin = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8).unwrap();
+        let mut graph = H3EdgeGraph::new(8);
+        for (i, cell) in origin.grid_disk(8).unwrap().iter().enumerate() {
+            for (j, edge) in cell.directed_edges().unwrap().iter().enumerate() {
+                // leave out some edges to create longedges
+                if (i + j) % 5 != 0 {
+                    graph.add_edge(edge, 1 + ((i * 7 + j) % 4) as u32).unwrap();
+                }
+            }
+        }
+        (origin, graph.try_into().unwrap())
+    }
+
+    #[test]
+    fn tree_equals_shortest_path() {
+        let (origin, graph) = build_graph();
+        let tree = graph.shortest_path_tree(origin, None).unwrap();
+        assert_eq!(tree.weight_to(&origin), Some(0));
+
+        let destinations: Vec<_> = tree.settled_cells().step_by(13).collect();
+        assert!(destinations.len() > 10);
+        for destination in destinations {
+            let expected = graph
+                .shortest_path(
+                    origin,
+                    [destination],
+                    &DefaultShortestPathOptions::default(),
+                )
+                .unwrap()
+                .remove(0);
+
+            let path = tree.path_to(&destination).unwrap().unwrap();
+            assert_eq!(tree.weight_to(&destination), Some(expected.cost));
+            assert_eq!(path.cost, expected.cost);
+            assert_eq!(path.origin_cell, origin);
+            assert_eq!(path.destination_cell, destination);
+            assert_eq!(path.directed_edge_path.origin_cell().unwrap(), origin);
+            assert_eq!(
+                path.directed_edge_path.destination_cell().unwrap(),
+                destination
+            );
+            if destination == origin {
+                assert_eq!(
+                    path.directed_edge_path,
+                    DirectedEdgePath::OriginIsDestination(origin)
+                );
+            } else {
+                // paths may differ for equal costs, but have to be consecutive
+                assert!(path.directed_edge_path.to_linestring().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn max_weight_cutoff() {
+        let (origin, graph) = build_graph();
+        let full_tree = graph.shortest_path_tree(origin, None).unwrap();
+        let tree = graph.shortest_path_tree(origin, Some(5)).unwrap();
+        assert!(tree.num_settled_cells() < full_tree.num_settled_cells());
+
+        for cell in full_tree.settled_cells() {
+            let weight = full_tree.weight_to(&cell).unwrap();
+            if weight <= 5 {
+                assert_eq!(tree.weight_to(&cell), Some(weight));
+            } else {
+                assert_eq!(tree.weight_to(&cell), None);
+                assert!(tree.path_to(&cell).unwrap().is_none());
+            }
+        }
+        assert!(tree.size_in_bytes() < full_tree.size_in_bytes());
+    }
+
+    #[cfg(feature = "io_serde_util")]
+    #[test]
+    fn serde_roundtrip() {
+        use crate::algorithm::shortest_path_tree::ShortestPathTree;
+
+        let (origin, graph) = build_graph();
+        let tree = graph.shortest_path_tree(origin, Some(10)).unwrap();
+        let serialized = bincode::serialize(&tree).unwrap();
+        let deserialized: ShortestPathTree<u32> = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(tree, deserialized);
+
+        for cell in tree.settled_cells() {
+            assert_eq!(tree.weight_to(&cell), deserialized.weight_to(&cell));
+            assert_eq!(
+                tree.path_to(&cell).unwrap(),
+                deserialized.path_to(&cell).unwrap()
+            );
+        }
+    }
+}
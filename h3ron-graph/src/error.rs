@@ -1,3 +1,5 @@
+use geo_types::Coordinate;
+use h3ron::H3Cell;
 use thiserror::Error as ThisError;
 
 #[derive(ThisError, Debug)]
@@ -26,8 +28,52 @@ pub enum Error {
     #[error("insufficient number of edges")]
     InsufficientNumberOfEdges,
 
-    #[error("path is segmented into multiple parts")]
-    SegmentedPath,
+    #[error("path is segmented into multiple parts: gap between {0:?} and {1:?}")]
+    SegmentedPath(Coordinate<f64>, Coordinate<f64>),
+
+    #[error(
+        "the edge at position {0} does not start at the destination cell of the preceding edge"
+    )]
+    DiscontinuousPath(usize),
+
+    #[error("the cells of the consecutive pair at index {0} are no neighbors: {1:?} <> {2:?}")]
+    NonNeighborCells(usize, H3Cell, H3Cell),
+
+    #[error("the cell is not part of the longedge")]
+    CellNotInLongEdge,
+
+    #[error("the longedges are not connected: destination cell {0:?} <> origin cell {1:?}")]
+    LongEdgesNotConnected(H3Cell, H3Cell),
+
+    #[error("the landmarks have been computed for a different graph")]
+    LandmarksGraphMismatch,
+
+    #[error("at least two points are required")]
+    InsufficientNumberOfPoints,
+
+    #[error("no path found for leg {0}")]
+    LegNotRoutable(usize),
+
+    #[error("the cells are no neighbors: {0:?} <> {1:?}")]
+    NotNeighbors(H3Cell, H3Cell),
+
+    #[error("origin and destination are the same cell")]
+    OriginIsDestination,
+
+    #[error("the graph does not match the topology")]
+    TopologyMismatch,
+
+    #[error("invalid checkpoint: {0}")]
+    InvalidCheckpoint(String),
+
+    #[error("the checkpoint has been created for a different input graph")]
+    StaleCheckpoint,
+
+    #[error("invalid weight expression at position {0}: {1}")]
+    InvalidWeightExpression(usize, String),
+
+    #[error("missing or mistyped value for field {0}")]
+    InvalidFieldValue(String),
 
     #[error("unknown error: {0}")]
     UnknownWithMessage(String),
@@ -0,0 +1,362 @@
+use std::ops::Add;
+use std::sync::OnceLock;
+
+use geo_types::LineString;
+use num_traits::{ToPrimitive, Zero};
+use rayon::prelude::*;
+use serde::Serialize;
+
+use h3ron::collections::HashMap;
+use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution, Index, ToCoordinate};
+
+use crate::error::Error;
+use crate::graph::longedge::LongEdge;
+use crate::graph::prepared::{
+    group_outgoing_edges, validate_min_longedge_length, OwnedEdgeTuple, OwnedEdgeValue,
+    MIN_LONGEDGE_LENGTH,
+};
+use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+/// Statistics of a graph built using [`GraphBuilder`].
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GraphBuilderStats {
+    pub h3_resolution: u8,
+    pub num_nodes: usize,
+    pub num_edges: usize,
+
+    /// number of input cell pairs which were no neighbors and have been bridged by
+    /// a path of cells.
+    pub num_bridged_pairs: usize,
+
+    /// number of longedges created by the simplification pass.
+    pub num_long_edges: usize,
+
+    /// number of edges absorbed into the created longedges.
+    pub num_absorbed_edges: usize,
+}
+
+/// Builds a [`PreparedH3EdgeGraph`] from a list of edges given by their origin and
+/// destination cells.
+///
+/// After all edges have been added, [`GraphBuilder::build`] replaces chains of nodes having
+/// exactly one incoming and one outgoing edge with `LongEdge`s carrying the summed weight
+/// of the edges of the chain. The individual edges stay part of the graph.
+///
+/// Duplicate edges are merged, the lower weight takes precedence.
+pub struct GraphBuilder<W> {
+    graph: H3EdgeGraph<W>,
+    bridge_non_neighbors: bool,
+    min_longedge_length: usize,
+    num_bridged_pairs: usize,
+}
+
+impl<W> GraphBuilder<W>
+where
+    W: PartialOrd + PartialEq + Add<Output = W> + Copy + Ord + Zero + ToPrimitive + Send + Sync,
+{
+    pub fn new(h3_resolution: u8) -> Self {
+        Self {
+            graph: H3EdgeGraph::new(h3_resolution),
+            bridge_non_neighbors: false,
+            min_longedge_length: MIN_LONGEDGE_LENGTH,
+            num_bridged_pairs: 0,
+        }
+    }
+
+    /// Connect cells which are no neighbors using the cells of a line between their centroids
+    /// instead of failing with [`Error::NotNeighbors`].
+    ///
+    /// The weight is assigned to the first edge of the line, all further edges get a
+    /// weight of zero. This way the summed weight of the line equals the given weight.
+    pub fn with_bridge_non_neighbors(mut self, bridge_non_neighbors: bool) -> Self {
+        self.bridge_non_neighbors = bridge_non_neighbors;
+        self
+    }
+
+    /// The minimum number of edges of a chain to be replaced by a longedge. Defaults to 3,
+    /// which is also the lowest accepted value.
+    pub fn with_min_longedge_length(mut self, min_longedge_length: usize) -> Self {
+        self.min_longedge_length = min_longedge_length;
+        self
+    }
+
+    /// add an edge leading from `origin_cell` to `destination_cell`.
+    pub fn add_edge(
+        &mut self,
+        origin_cell: H3Cell,
+        destination_cell: H3Cell,
+        weight: W,
+    ) -> Result<(), Error> {
+        for cell in [origin_cell, destination_cell] {
+            if cell.resolution() != self.graph.h3_resolution {
+                return Err(Error::MixedH3Resolutions(
+                    self.graph.h3_resolution,
+                    cell.resolution(),
+                ));
+            }
+        }
+        if origin_cell == destination_cell {
+            return Err(Error::OriginIsDestination);
+        }
+
+        if origin_cell.are_neighbor_cells(destination_cell)? {
+            return self
+                .graph
+                .add_edge_using_cells(origin_cell, destination_cell, weight);
+        }
+        if !self.bridge_non_neighbors {
+            return Err(Error::NotNeighbors(origin_cell, destination_cell));
+        }
+
+        let line_cells = h3ron::line(
+            &LineString::from(vec![
+                origin_cell.to_coordinate()?,
+                destination_cell.to_coordinate()?,
+            ]),
+            self.graph.h3_resolution,
+        )?;
+        for (i, window) in line_cells.iter().collect::<Vec<_>>().windows(2).enumerate() {
+            let edge_weight = if i == 0 { weight } else { W::zero() };
+            self.graph
+                .add_edge_using_cells(window[0], window[1], edge_weight)?;
+        }
+        self.num_bridged_pairs += 1;
+        Ok(())
+    }
+
+    /// add all `(origin_cell, destination_cell, weight)` tuples of `edges`.
+    pub fn add_edges<I>(&mut self, edges: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (H3Cell, H3Cell, W)>,
+    {
+        for (origin_cell, destination_cell, weight) in edges {
+            self.add_edge(origin_cell, destination_cell, weight)?;
+        }
+        Ok(())
+    }
+
+    /// Build the graph and run the longedge simplification pass.
+    pub fn build(self) -> Result<(PreparedH3EdgeGraph<W>, GraphBuilderStats), Error> {
+        validate_min_longedge_length(self.min_longedge_length)?;
+        let h3_resolution = self.graph.h3_resolution();
+        let graph_nodes = self.graph.nodes()?;
+
+        let mut outgoing: HashMap<H3Cell, Vec<(H3DirectedEdge, W)>> = Default::default();
+        let mut num_incoming: HashMap<H3Cell, usize> = Default::default();
+        for (edge, weight) in self.graph.iter_edges() {
+            outgoing
+                .entry(edge.origin_cell()?)
+                .or_default()
+                .push((edge, *weight));
+            *num_incoming.entry(edge.destination_cell()?).or_default() += 1;
+        }
+        let is_chain_node = |cell: &H3Cell| {
+            num_incoming.get(cell) == Some(&1)
+                && outgoing.get(cell).map(|edges| edges.len()) == Some(1)
+        };
+
+        // chains start at every edge originating from a node which is not part of a chain.
+        // Cycles consisting only of chain nodes are left untouched.
+        let edge_tuples = self
+            .graph
+            .edges
+            .par_iter()
+            .map(|(edge, weight)| {
+                let origin_cell = edge.origin_cell()?;
+                let mut longedge = None;
+                let mut num_absorbed_edges = 0;
+                if !is_chain_node(&origin_cell) {
+                    let mut path = vec![(*edge, *weight)];
+                    let mut cell = edge.destination_cell()?;
+                    while is_chain_node(&cell) {
+                        let next = outgoing[&cell][0];
+                        path.push(next);
+                        cell = next.0.destination_cell()?;
+                    }
+                    if path.len() >= self.min_longedge_length {
+                        num_absorbed_edges = path.len();
+                        longedge = Some(Box::new(LongEdge::try_from_edges_with_weights(path)?));
+                    }
+                }
+                Ok((
+                    num_absorbed_edges,
+                    (
+                        origin_cell,
+                        (
+                            *edge,
+                            OwnedEdgeValue {
+                                weight: *weight,
+                                longedge,
+                            },
+                        ),
+                    ),
+                ))
+            })
+            .collect::<Result<Vec<(usize, (H3Cell, OwnedEdgeTuple<W>))>, Error>>()?;
+
+        let mut stats = GraphBuilderStats {
+            h3_resolution,
+            num_nodes: graph_nodes.len(),
+            num_edges: edge_tuples.len(),
+            num_bridged_pairs: self.num_bridged_pairs,
+            ..Default::default()
+        };
+        let edge_tuples = edge_tuples
+            .into_iter()
+            .map(|(num_absorbed_edges, edge_tuple)| {
+                if num_absorbed_edges > 0 {
+                    stats.num_long_edges += 1;
+                    stats.num_absorbed_edges += num_absorbed_edges;
+                }
+                edge_tuple
+            })
+            .collect();
+
+        Ok((
+            PreparedH3EdgeGraph {
+                outgoing_edges: group_outgoing_edges(edge_tuples),
+                h3_resolution,
+                graph_nodes,
+                landmark_fingerprint: OnceLock::new(),
+            },
+            stats,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::Coordinate;
+
+    use h3ron::H3Cell;
+
+    use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+    use crate::error::Error;
+    use crate::graph::{GetStats, H3EdgeGraph, PreparedH3EdgeGraph};
+
+    use super::GraphBuilder;
+
+    /// edges of a grid graph: a hub of bidirectional edges surrounded by one-directional spokes
+    /// leading outwards, connected by one-directional paths along a ring.
+    ///
+    /// Returns the center of the hub, the middle cells of the ring paths and the edges.
+    fn grid_edges() -> (H3Cell, Vec<H3Cell>, Vec<(H3Cell, H3Cell, u32)>) {
+        let center = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8).unwrap();
+        let mut edges = vec![];
+        let mut ring_path_middles = vec![];
+        for cell in center.grid_disk(2).unwrap().iter() {
+            for neighbor in cell.grid_ring_unsafe(1).unwrap().iter() {
+                if center.grid_distance_to(neighbor).unwrap() <= 2 {
+                    edges.push((cell, neighbor, 2));
+                }
+            }
+        }
+
+        let ring: Vec<_> = center.grid_ring_unsafe(12).unwrap().iter().collect();
+        let spoke_ends: Vec<_> = ring.iter().step_by(9).copied().collect();
+        for (i, spoke_end) in spoke_ends.iter().enumerate() {
+            let spoke: Vec<_> = h3ron::grid_path_cells(center, *spoke_end)
+                .unwrap()
+                .iter()
+                .collect();
+            for (j, window) in spoke.windows(2).enumerate() {
+                edges.push((window[0], window[1], 1 + (i + j) as u32 % 4));
+            }
+            let next_spoke_end = spoke_ends[(i + 1) % spoke_ends.len()];
+            let ring_path: Vec<_> = h3ron::grid_path_cells(*spoke_end, next_spoke_end)
+                .unwrap()
+                .iter()
+                .collect();
+            for window in ring_path.windows(2) {
+                edges.push((window[0], window[1], 3));
+            }
+            ring_path_middles.push(ring_path[ring_path.len() / 2]);
+        }
+        (center, ring_path_middles, edges)
+    }
+
+    #[test]
+    fn simplified_routes_equal_original() {
+        let (center, destinations, edges) = grid_edges();
+
+        let mut builder = GraphBuilder::new(8);
+        builder.add_edges(edges.iter().copied()).unwrap();
+        let (simplified, stats) = builder.build().unwrap();
+        assert!(stats.num_long_edges > 0);
+        assert!(stats.num_absorbed_edges >= stats.num_long_edges * 3);
+        assert_eq!(stats.num_bridged_pairs, 0);
+        assert_eq!(
+            simplified.count_edges(),
+            (stats.num_edges, stats.num_long_edges)
+        );
+
+        let mut graph = H3EdgeGraph::new(8);
+        for (origin_cell, destination_cell, weight) in edges {
+            graph
+                .add_edge_using_cells(origin_cell, destination_cell, weight)
+                .unwrap();
+        }
+        let graph_stats = graph.get_stats().unwrap();
+        assert_eq!(stats.num_nodes, graph_stats.num_nodes);
+        assert_eq!(stats.num_edges, graph_stats.num_edges);
+
+        // no longedges at all
+        let original = PreparedH3EdgeGraph::from_h3edge_graph(graph, usize::MAX).unwrap();
+        assert_eq!(original.count_edges().1, 0);
+
+        // the spoke longedges do not contain any of the destinations and get used for routing
+        let options = DefaultShortestPathOptions::default();
+        let mut expected = original
+            .shortest_path(center, &destinations, &options)
+            .unwrap();
+        let mut paths = simplified
+            .shortest_path(center, &destinations, &options)
+            .unwrap();
+        assert_eq!(paths.len(), destinations.len());
+        expected.sort_unstable_by_key(|path| path.destination_cell);
+        paths.sort_unstable_by_key(|path| path.destination_cell);
+        assert_eq!(paths.len(), expected.len());
+        for (path, expected_path) in paths.iter().zip(expected.iter()) {
+            assert_eq!(path.destination_cell, expected_path.destination_cell);
+            assert_eq!(path.cost, expected_path.cost);
+        }
+    }
+
+    #[test]
+    fn bridge_non_neighbors() {
+        let origin_cell = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8).unwrap();
+        let destination_cell = origin_cell
+            .grid_ring_unsafe(5)
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+
+        let mut builder = GraphBuilder::new(8);
+        assert!(matches!(
+            builder.add_edge(origin_cell, destination_cell, 7_u32),
+            Err(Error::NotNeighbors(_, _))
+        ));
+
+        let mut builder = GraphBuilder::new(8).with_bridge_non_neighbors(true);
+        builder
+            .add_edge(origin_cell, destination_cell, 7_u32)
+            .unwrap();
+        let (graph, stats) = builder.build().unwrap();
+        assert_eq!(stats.num_bridged_pairs, 1);
+        assert_eq!(stats.num_edges, 5);
+        assert_eq!(stats.num_long_edges, 1);
+        assert_eq!(stats.num_absorbed_edges, 5);
+
+        let paths = graph
+            .shortest_path(
+                origin_cell,
+                [destination_cell],
+                &DefaultShortestPathOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].cost, 7);
+        assert_eq!(paths[0].len(), 5);
+    }
+}
@@ -0,0 +1,498 @@
+//! Graphs sharing the same structure with differing edge weights.
+//!
+//! Snapshots of the weights of a network - like hourly travel times - use the same edges and
+//! [`LongEdge`]s. Instead of keeping a complete [`PreparedH3EdgeGraph`] for each snapshot,
+//! the structure is stored once in a [`GraphTopology`] and the weights of each snapshot in
+//! a [`WeightLayer`]. A [`TopologyBoundGraph`] combines both and can be used with all routing
+//! algorithms.
+use std::mem::size_of;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
+
+use h3ron::collections::HashMap;
+use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution, Index};
+
+use crate::algorithm::landmarks::splitmix64;
+use crate::error::Error;
+use crate::graph::longedge::LongEdge;
+use crate::graph::node::NodeType;
+use crate::graph::prepared::{OwnedEdgeTupleList, OwnedEdgeValue};
+use crate::graph::{
+    EdgeWeight, GetCellEdges, GetCellNode, GetStats, GraphStats, IterateCellNodes,
+    PreparedH3EdgeGraph,
+};
+
+/// Identifies the set of edges of a graph.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EdgeSetFingerprint {
+    pub h3_resolution: u8,
+    pub num_edges: usize,
+
+    /// hash over all edges of the graph
+    pub hash: u64,
+}
+
+impl EdgeSetFingerprint {
+    fn from_sorted_edges(h3_resolution: u8, edges: &[H3DirectedEdge]) -> Self {
+        let mut hash = 0_u64;
+        for edge in edges {
+            let mut state = hash ^ edge.h3index();
+            hash = splitmix64(&mut state);
+        }
+        Self {
+            h3_resolution,
+            num_edges: edges.len(),
+            hash,
+        }
+    }
+
+    fn from_graph<W: Copy>(graph: &PreparedH3EdgeGraph<W>) -> Self {
+        let mut edges: Vec<_> = graph.iter_edges().map(|(edge, _)| edge).collect();
+        edges.sort_unstable();
+        Self::from_sorted_edges(graph.h3_resolution(), &edges)
+    }
+}
+
+/// The structure of a [`PreparedH3EdgeGraph`] without the weights: cells, edges
+/// and [`LongEdge`]s.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GraphTopology {
+    fingerprint: EdgeSetFingerprint,
+
+    /// sorted cells having outgoing edges
+    origin_cells: Vec<H3Cell>,
+
+    /// the edges originating from `origin_cells[i]` are located at
+    /// `edges[edge_offsets[i]..edge_offsets[i + 1]]`.
+    edge_offsets: Vec<usize>,
+
+    /// the edges sorted by their origin cell. The weights of a [`WeightLayer`] are stored in the
+    /// same order.
+    edges: Vec<H3DirectedEdge>,
+
+    /// sorted positions in `edges` of the edges having a longedge
+    longedge_positions: Vec<usize>,
+
+    /// the longedges of the edges at `longedge_positions`
+    longedges: Vec<LongEdge>,
+
+    graph_nodes: HashMap<H3Cell, NodeType>,
+}
+
+impl GraphTopology {
+    /// extract the topology of `graph`.
+    pub fn from_graph<W>(graph: &PreparedH3EdgeGraph<W>) -> Self {
+        let mut origin_cells: Vec<_> = graph.outgoing_edges.keys().copied().collect();
+        origin_cells.sort_unstable();
+
+        let mut edge_offsets = Vec::with_capacity(origin_cells.len() + 1);
+        let mut edges = Vec::new();
+        let mut longedge_positions = Vec::new();
+        let mut longedges = Vec::new();
+        edge_offsets.push(0);
+        for cell in origin_cells.iter() {
+            for (edge, owned_edge_value) in graph.outgoing_edges[cell].iter() {
+                if let Some(boxed_longedge) = owned_edge_value.longedge.as_ref() {
+                    longedge_positions.push(edges.len());
+                    longedges.push(boxed_longedge.0.clone());
+                }
+                edges.push(*edge);
+            }
+            edge_offsets.push(edges.len());
+        }
+
+        let mut sorted_edges = edges.clone();
+        sorted_edges.sort_unstable();
+        Self {
+            fingerprint: EdgeSetFingerprint::from_sorted_edges(graph.h3_resolution, &sorted_edges),
+            origin_cells,
+            edge_offsets,
+            edges,
+            longedge_positions,
+            longedges,
+            graph_nodes: graph.graph_nodes.clone(),
+        }
+    }
+
+    pub const fn fingerprint(&self) -> &EdgeSetFingerprint {
+        &self.fingerprint
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn num_longedges(&self) -> usize {
+        self.longedges.len()
+    }
+
+    /// approximate size of the topology in memory in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        size_of::<Self>()
+            + self.origin_cells.capacity() * size_of::<H3Cell>()
+            + self.edge_offsets.capacity() * size_of::<usize>()
+            + self.edges.capacity() * size_of::<H3DirectedEdge>()
+            + self.longedge_positions.capacity() * size_of::<usize>()
+            + self
+                .longedges
+                .iter()
+                .map(|longedge| size_of::<LongEdge>() + longedge.h3edges_len() * size_of::<u64>())
+                .sum::<usize>()
+            + self.graph_nodes.capacity() * size_of::<(H3Cell, NodeType)>()
+    }
+
+    /// the position of the longedge of the edge at `edge_position`
+    fn longedge_position(&self, edge_position: usize) -> Option<usize> {
+        self.longedge_positions.binary_search(&edge_position).ok()
+    }
+}
+
+/// The weights of the edges and longedges of a [`GraphTopology`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WeightLayer<W> {
+    fingerprint: EdgeSetFingerprint,
+
+    /// weights in the order of the edges of the topology
+    weights: Vec<W>,
+
+    /// aggregated weights of the longedges of the topology
+    longedge_weights: Vec<W>,
+}
+
+impl<W> WeightLayer<W>
+where
+    W: Copy,
+{
+    /// Extract the weights of `graph` for the `topology`.
+    ///
+    /// Fails with [`Error::TopologyMismatch`] when the edges or longedges of the graph differ
+    /// from the topology.
+    pub fn from_graph(
+        topology: &GraphTopology,
+        graph: &PreparedH3EdgeGraph<W>,
+    ) -> Result<Self, Error> {
+        if EdgeSetFingerprint::from_graph(graph) != topology.fingerprint {
+            return Err(Error::TopologyMismatch);
+        }
+
+        let mut weights = Vec::with_capacity(topology.edges.len());
+        let mut longedge_weights = Vec::with_capacity(topology.longedges.len());
+        for (i, cell) in topology.origin_cells.iter().enumerate() {
+            let graph_edges = graph
+                .outgoing_edges
+                .get(cell)
+                .ok_or(Error::TopologyMismatch)?;
+            let positions = topology.edge_offsets[i]..topology.edge_offsets[i + 1];
+            if graph_edges.len() != positions.len() {
+                return Err(Error::TopologyMismatch);
+            }
+            for ((edge, owned_edge_value), position) in graph_edges.iter().zip(positions) {
+                if edge != &topology.edges[position] {
+                    return Err(Error::TopologyMismatch);
+                }
+                weights.push(owned_edge_value.weight);
+
+                match (
+                    owned_edge_value.longedge.as_ref(),
+                    topology.longedge_position(position),
+                ) {
+                    (Some(boxed_longedge), Some(_)) => longedge_weights.push(boxed_longedge.1),
+                    (None, None) => (),
+                    _ => return Err(Error::TopologyMismatch),
+                }
+            }
+        }
+
+        Ok(Self {
+            fingerprint: topology.fingerprint,
+            weights,
+            longedge_weights,
+        })
+    }
+
+    pub const fn fingerprint(&self) -> &EdgeSetFingerprint {
+        &self.fingerprint
+    }
+
+    /// approximate size of the layer in memory in bytes.
+    pub fn size_in_bytes(&self) -> usize {
+        size_of::<Self>()
+            + (self.weights.capacity() + self.longedge_weights.capacity()) * size_of::<W>()
+    }
+}
+
+/// A [`GraphTopology`] combined with one of its [`WeightLayer`]s.
+///
+/// Implements the graph traits required by the routing algorithms.
+pub struct TopologyBoundGraph<'a, W> {
+    topology: &'a GraphTopology,
+    layer: &'a WeightLayer<W>,
+}
+
+impl<'a, W> TopologyBoundGraph<'a, W>
+where
+    W: Copy,
+{
+    /// Fails with [`Error::TopologyMismatch`] when the `layer` has been created for
+    /// another topology.
+    pub fn new(topology: &'a GraphTopology, layer: &'a WeightLayer<W>) -> Result<Self, Error> {
+        if topology.fingerprint != layer.fingerprint
+            || topology.edges.len() != layer.weights.len()
+            || topology.longedges.len() != layer.longedge_weights.len()
+        {
+            return Err(Error::TopologyMismatch);
+        }
+        Ok(Self { topology, layer })
+    }
+
+    /// build a standalone [`PreparedH3EdgeGraph`] with the weights of the layer.
+    pub fn to_prepared_graph(&self) -> PreparedH3EdgeGraph<W> {
+        let mut outgoing_edges = HashMap::default();
+        for (i, cell) in self.topology.origin_cells.iter().enumerate() {
+            let edge_tuples: OwnedEdgeTupleList<W> = (self.topology.edge_offsets[i]
+                ..self.topology.edge_offsets[i + 1])
+                .map(|position| {
+                    let longedge = self.topology.longedge_position(position).map(|lp| {
+                        Box::new((
+                            self.topology.longedges[lp].clone(),
+                            self.layer.longedge_weights[lp],
+                        ))
+                    });
+                    (
+                        self.topology.edges[position],
+                        OwnedEdgeValue {
+                            weight: self.layer.weights[position],
+                            longedge,
+                        },
+                    )
+                })
+                .collect::<SmallVec<_>>();
+            outgoing_edges.insert(*cell, edge_tuples);
+        }
+        PreparedH3EdgeGraph {
+            outgoing_edges,
+            h3_resolution: self.topology.fingerprint.h3_resolution,
+            graph_nodes: self.topology.graph_nodes.clone(),
+            landmark_fingerprint: OnceLock::new(),
+        }
+    }
+}
+
+impl<'a, W> GetCellEdges for TopologyBoundGraph<'a, W>
+where
+    W: Copy,
+{
+    type EdgeWeightType = W;
+
+    fn get_edges_originating_from(
+        &self,
+        cell: &H3Cell,
+    ) -> Result<Vec<(H3DirectedEdge, EdgeWeight<Self::EdgeWeightType>)>, Error> {
+        let mut out_vec = Vec::with_capacity(7);
+        if let Ok(i) = self.topology.origin_cells.binary_search(cell) {
+            for position in self.topology.edge_offsets[i]..self.topology.edge_offsets[i + 1] {
+                let longedge = self.topology.longedge_position(position).map(|lp| {
+                    (
+                        &self.topology.longedges[lp],
+                        self.layer.longedge_weights[lp],
+                    )
+                });
+                out_vec.push((
+                    self.topology.edges[position],
+                    EdgeWeight {
+                        weight: self.layer.weights[position],
+                        longedge,
+                    },
+                ));
+            }
+        }
+        Ok(out_vec)
+    }
+}
+
+impl<'a, W> GetCellNode for TopologyBoundGraph<'a, W> {
+    fn get_cell_node(&self, cell: &H3Cell) -> Option<NodeType> {
+        self.topology.graph_nodes.get(cell).copied()
+    }
+}
+
+impl<'a, W> HasH3Resolution for TopologyBoundGraph<'a, W> {
+    fn h3_resolution(&self) -> u8 {
+        self.topology.fingerprint.h3_resolution
+    }
+}
+
+impl<'a, W> GetStats for TopologyBoundGraph<'a, W> {
+    fn get_stats(&self) -> Result<GraphStats, Error> {
+        Ok(GraphStats {
+            h3_resolution: self.topology.fingerprint.h3_resolution,
+            num_nodes: self.topology.graph_nodes.len(),
+            num_edges: self.topology.edges.len(),
+        })
+    }
+}
+
+impl<'a, 'b, W> IterateCellNodes<'b> for TopologyBoundGraph<'a, W> {
+    type CellNodeIterator = h3ron::collections::hashbrown::hash_map::Iter<'b, H3Cell, NodeType>;
+
+    fn iter_cell_nodes(&'b self) -> Self::CellNodeIterator {
+        self.topology.graph_nodes.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+    use std::mem::size_of;
+
+    use geo_types::Coordinate;
+
+    use h3ron::{grid_path_cells, H3Cell};
+
+    use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+    use crate::error::Error;
+    use crate::graph::layered::{GraphTopology, TopologyBoundGraph, WeightLayer};
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    /// graph on a grid disk with some edges left out, and a linear chain of cells leading away
+    /// from the disk which gets turned into longedges. The weights depend on `variant`.
+    fn build_graph(variant: usize) -> (H3Cell, PreparedH3EdgeGraph<u32>) {
+        let origin = H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8).unwrap();
+        let mut graph = H3EdgeGraph::new(8);
+        for (i, cell) in origin.grid_disk(8).unwrap().iter().enumerate() {
+            for (j, edge) in cell.directed_edges().unwrap().iter().enumerate() {
+                if (i + j) % 5 != 0 {
+                    graph
+                        .add_edge(edge, 1 + ((i * (7 + variant) + j * variant) % 9) as u32)
+                        .unwrap();
+                }
+            }
+        }
+
+        let chain = chain_cells(origin);
+        for (i, window) in chain.windows(2).enumerate() {
+            graph
+                .add_edge_using_cells_bidirectional(
+                    window[0],
+                    window[1],
+                    1 + ((i * (2 + variant)) % 5) as u32,
+                )
+                .unwrap();
+        }
+        (origin, graph.try_into().unwrap())
+    }
+
+    /// straight line of cells from the outermost ring of the disk of `build_graph` outwards.
+    fn chain_cells(origin: H3Cell) -> Vec<H3Cell> {
+        let start = origin.grid_ring_unsafe(8).unwrap().iter().next().unwrap();
+        let origin_ij = origin.to_localij(origin).unwrap();
+        let start_ij = start.to_localij(origin).unwrap();
+        let end = H3Cell::from_localij(origin, start_ij + start_ij - origin_ij).unwrap();
+        grid_path_cells(start, end).unwrap().iter().collect()
+    }
+
+    #[test]
+    fn routes_equal_monolithic_graph() {
+        let (origin, graph_a) = build_graph(0);
+        let (_, graph_b) = build_graph(3);
+        let topology = GraphTopology::from_graph(&graph_a);
+        assert!(topology.num_longedges() > 0);
+
+        let mut destinations: Vec<_> = origin.grid_ring_unsafe(7).unwrap().iter().collect();
+        // routing to the end of the chain uses the longedges
+        destinations.push(*chain_cells(origin).last().unwrap());
+        let options = DefaultShortestPathOptions::default();
+        let mut costs_differ = false;
+        for graph in [&graph_a, &graph_b] {
+            let layer = WeightLayer::from_graph(&topology, graph).unwrap();
+            let bound = TopologyBoundGraph::new(&topology, &layer).unwrap();
+
+            let mut expected = graph
+                .shortest_path(origin, &destinations, &options)
+                .unwrap();
+            let mut paths = bound
+                .shortest_path(origin, &destinations, &options)
+                .unwrap();
+            expected.sort_unstable_by_key(|path| path.destination_cell);
+            paths.sort_unstable_by_key(|path| path.destination_cell);
+            assert_eq!(paths.len(), expected.len());
+            for (path, expected_path) in paths.iter().zip(expected.iter()) {
+                assert_eq!(path.destination_cell, expected_path.destination_cell);
+                assert_eq!(path.cost, expected_path.cost);
+            }
+
+            let mut a_paths = graph_a
+                .shortest_path(origin, &destinations, &options)
+                .unwrap();
+            a_paths.sort_unstable_by_key(|path| path.destination_cell);
+            costs_differ |= a_paths
+                .iter()
+                .zip(paths.iter())
+                .any(|(a, p)| a.cost != p.cost);
+
+            // conversion back to a monolithic graph
+            let converted = bound.to_prepared_graph();
+            let mut converted_edges: Vec<_> = converted
+                .iter_edges()
+                .map(|(edge, ew)| (edge, ew.weight, ew.longedge.map(|(_, w)| w)))
+                .collect();
+            let mut original_edges: Vec<_> = graph
+                .iter_edges()
+                .map(|(edge, ew)| (edge, ew.weight, ew.longedge.map(|(_, w)| w)))
+                .collect();
+            converted_edges.sort_unstable();
+            original_edges.sort_unstable();
+            assert_eq!(converted_edges, original_edges);
+        }
+        assert!(costs_differ);
+    }
+
+    #[test]
+    fn layers_are_small() {
+        let (_, graph_a) = build_graph(0);
+        let (_, graph_b) = build_graph(3);
+        let topology = GraphTopology::from_graph(&graph_a);
+        let layers = [
+            WeightLayer::from_graph(&topology, &graph_a).unwrap(),
+            WeightLayer::from_graph(&topology, &graph_b).unwrap(),
+        ];
+
+        for layer in layers.iter() {
+            assert_eq!(
+                layer.size_in_bytes(),
+                size_of::<WeightLayer<u32>>()
+                    + (topology.num_edges() + topology.num_longedges()) * size_of::<u32>()
+            );
+            assert!(layer.size_in_bytes() * 2 < topology.size_in_bytes());
+        }
+    }
+
+    #[test]
+    fn fingerprint_mismatch() {
+        let (origin, graph) = build_graph(0);
+        let topology = GraphTopology::from_graph(&graph);
+
+        let full_graph: H3EdgeGraph<u32> = graph.into();
+        let mut smaller_graph = H3EdgeGraph::new(8);
+        for (edge, weight) in full_graph.iter_edges() {
+            if edge.origin_cell().unwrap() != origin {
+                smaller_graph.add_edge(edge, *weight).unwrap();
+            }
+        }
+        let smaller_graph: PreparedH3EdgeGraph<u32> = smaller_graph.try_into().unwrap();
+
+        assert!(matches!(
+            WeightLayer::from_graph(&topology, &smaller_graph),
+            Err(Error::TopologyMismatch)
+        ));
+
+        let other_topology = GraphTopology::from_graph(&smaller_graph);
+        let layer = WeightLayer::from_graph(&other_topology, &smaller_graph).unwrap();
+        assert!(matches!(
+            TopologyBoundGraph::new(&topology, &layer),
+            Err(Error::TopologyMismatch)
+        ));
+    }
+}
@@ -1,12 +1,14 @@
 use std::borrow::Borrow;
+use std::ops::Add;
 
-use geo_types::LineString;
+use geo_types::{Coordinate, LineString, MultiLineString};
+use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
 
 use h3ron::collections::compressed::{IndexBlock, OwningDecompressedIter};
 use h3ron::collections::H3Treemap;
-use h3ron::to_geo::{ToLineString, ToMultiLineString};
-use h3ron::{H3Cell, H3DirectedEdge};
+use h3ron::to_geo::ToLineString;
+use h3ron::{H3Cell, H3DirectedEdge, ToCoordinate};
 
 use crate::error::Error;
 
@@ -28,6 +30,60 @@ where
     Ok(out_vec)
 }
 
+/// Fails with [`Error::DiscontinuousPath`] when an edge of `h3edges` does not start at the
+/// destination cell of the preceding edge.
+fn validate_continuity(h3edges: &[H3DirectedEdge]) -> Result<(), Error> {
+    for (pos, window) in h3edges.windows(2).enumerate() {
+        if window[0].destination_cell()? != window[1].origin_cell()? {
+            return Err(Error::DiscontinuousPath(pos + 1));
+        }
+    }
+    Ok(())
+}
+
+/// Build the continuous path of `H3DirectedEdge` values connecting the consecutive cells
+/// of `h3cell_path` - the reverse of converting an edge path to cells.
+///
+/// Repeated identical consecutive cells are skipped. Consecutive cells which are no neighbors
+/// are connected using the cells of [`h3ron::grid_path_cells`] when `bridge_gaps` is set,
+/// otherwise this fails with [`Error::NonNeighborCells`] naming both cells and the index of
+/// the offending pair. The pair at `index` consists of the cells at the positions `index` and
+/// `index + 1` of `h3cell_path`.
+pub fn h3cell_path_to_h3edge_path<I>(
+    h3cell_path: I,
+    bridge_gaps: bool,
+) -> Result<Vec<H3DirectedEdge>, Error>
+where
+    I: IntoIterator,
+    I::Item: Borrow<H3Cell>,
+{
+    let iter = h3cell_path.into_iter();
+    let mut h3edges = Vec::with_capacity(iter.size_hint().0.saturating_sub(1));
+    let mut previous_cell: Option<H3Cell> = None;
+    for (pos, cell) in iter.enumerate() {
+        let cell = *cell.borrow();
+        if let Some(previous_cell) = previous_cell {
+            if previous_cell == cell {
+                continue;
+            }
+            if previous_cell.are_neighbor_cells(cell)? {
+                h3edges.push(previous_cell.directed_edge_to(cell)?);
+            } else if bridge_gaps {
+                let bridge_cells: Vec<_> = h3ron::grid_path_cells(previous_cell, cell)?
+                    .iter()
+                    .collect();
+                for window in bridge_cells.windows(2) {
+                    h3edges.push(window[0].directed_edge_to(window[1])?);
+                }
+            } else {
+                return Err(Error::NonNeighborCells(pos - 1, previous_cell, cell));
+            }
+        }
+        previous_cell = Some(cell);
+    }
+    Ok(h3edges)
+}
+
 /// A `LongEdge` is an artificial construct to combine a continuous path
 /// of [`H3DirectedEdge`] values into a single edge.
 ///
@@ -45,9 +101,93 @@ pub struct LongEdge {
     /// provides an efficient lookup to check for intersection of
     /// the edge with `H3Cell` values.
     cell_lookup: H3Treemap<H3Cell>,
+
+    /// the summed weight of the edges of the path up to and including the edge at the
+    /// same position. Only set when constructed using [`LongEdge::try_from_edges_with_weights`].
+    #[serde(default)]
+    cumulative_weights: Option<Vec<f64>>,
 }
 
 impl LongEdge {
+    /// Construct a longedge from a continuous path of `H3DirectedEdge` values and their
+    /// weights.
+    ///
+    /// Returns the longedge together with the summed weight of all edges. The cumulative
+    /// weights along the path are kept in the longedge, see [`LongEdge::cumulative_weights`].
+    ///
+    /// Fails with [`Error::DiscontinuousPath`] naming the position of the first edge not starting
+    /// at the destination cell of its predecessor, and with [`Error::InsufficientNumberOfEdges`]
+    /// for less than two edges.
+    pub fn try_from_edges_with_weights<W>(
+        edges: Vec<(H3DirectedEdge, W)>,
+    ) -> Result<(Self, W), Error>
+    where
+        W: Add<Output = W> + Copy + ToPrimitive,
+    {
+        if edges.len() < 2 {
+            return Err(Error::InsufficientNumberOfEdges);
+        }
+        let mut h3edges = Vec::with_capacity(edges.len());
+        let mut cumulative_weights = Vec::with_capacity(edges.len());
+        let mut total_weight = edges[0].1;
+        for (pos, (h3edge, weight)) in edges.into_iter().enumerate() {
+            if pos > 0 {
+                total_weight = total_weight + weight;
+            }
+            h3edges.push(h3edge);
+            cumulative_weights.push(total_weight.to_f64().ok_or_else(|| {
+                Error::Other("edge weight is not representable as f64".to_string())
+            })?);
+        }
+        validate_continuity(&h3edges)?;
+        Ok((
+            Self::from_path(h3edges, Some(cumulative_weights))?,
+            total_weight,
+        ))
+    }
+
+    /// Construct a longedge from a sequence of cells, e.g. a GPS trace snapped to cells.
+    ///
+    /// See [`h3cell_path_to_h3edge_path`] for the handling of repeated cells and of cells which
+    /// are no neighbors.
+    pub fn try_from_cell_path<I>(h3cell_path: I, bridge_gaps: bool) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<H3Cell>,
+    {
+        Self::try_from(h3cell_path_to_h3edge_path(h3cell_path, bridge_gaps)?)
+    }
+
+    /// `h3edges` must form a continuous path of at least two edges.
+    fn from_path(
+        h3edges: Vec<H3DirectedEdge>,
+        cumulative_weights: Option<Vec<f64>>,
+    ) -> Result<Self, Error> {
+        let cell_lookup: H3Treemap<_> = h3edge_path_to_h3cell_path(&h3edges)?.iter().collect();
+        Ok(Self {
+            in_edge: h3edges[0],
+            out_edge: *h3edges.last().unwrap(),
+            edge_path: h3edges.into(),
+            cell_lookup,
+            cumulative_weights,
+        })
+    }
+
+    /// The summed weights along the path of the longedge - one for each edge, including the
+    /// weight of the edge itself.
+    ///
+    /// `None` when `self` has not been constructed using
+    /// [`LongEdge::try_from_edges_with_weights`].
+    pub fn cumulative_weights(&self) -> Option<&[f64]> {
+        self.cumulative_weights.as_deref()
+    }
+
+    /// The summed weight of all edges of the path. See [`LongEdge::cumulative_weights`].
+    pub fn total_weight(&self) -> Option<f64> {
+        self.cumulative_weights
+            .as_ref()
+            .and_then(|weights| weights.last().copied())
+    }
     pub fn destination_cell(&self) -> Result<H3Cell, Error> {
         Ok(self.out_edge.destination_cell()?)
     }
@@ -60,6 +200,121 @@ impl LongEdge {
         self.cell_lookup.is_disjoint(celltreemap)
     }
 
+    /// number of cells of the path of `self` contained in `celltreemap`.
+    ///
+    /// Uses the cell lookup of `self`, so the path does not need to be decompressed.
+    pub fn intersection_count(&self, celltreemap: &H3Treemap<H3Cell>) -> usize {
+        self.cell_lookup.intersection_len(celltreemap)
+    }
+
+    /// the cells of the path of `self` contained in `celltreemap`, in the order of the path.
+    ///
+    /// Cells visited multiple times by the path are only included at their first
+    /// occurrence, so the length equals [`LongEdge::intersection_count`]. The path only gets
+    /// decompressed when `self` is not disjoint with `celltreemap`.
+    pub fn intersection(&self, celltreemap: &H3Treemap<H3Cell>) -> Result<Vec<H3Cell>, Error> {
+        if self.is_disjoint(celltreemap) {
+            return Ok(vec![]);
+        }
+        let mut seen = H3Treemap::default();
+        Ok(h3edge_path_to_h3cell_path(self.h3edge_path()?)?
+            .into_iter()
+            .filter(|cell| celltreemap.contains(cell) && seen.insert(*cell))
+            .collect())
+    }
+
+    /// check if `cell` is part of the path of `self`.
+    pub fn contains_cell(&self, cell: &H3Cell) -> bool {
+        self.cell_lookup.contains(cell)
+    }
+
+    /// Split `self` at the first occurrence of `cell` in its path.
+    ///
+    /// Returns the part leading to `cell` and the part starting at `cell`. A part is `None`
+    /// when `cell` is the origin or the destination of `self`, and a
+    /// [`LongEdgePart::Single`] when it consists of only one edge.
+    ///
+    /// Parts of longedges carrying [`LongEdge::cumulative_weights`] keep the weights of their
+    /// edges, so the weight of `self` gets apportioned to the parts.
+    ///
+    /// Fails with [`Error::CellNotInLongEdge`] when `cell` is not part of the path. Use
+    /// [`LongEdge::contains_cell`] to check upfront.
+    pub fn split_at_cell(
+        &self,
+        cell: H3Cell,
+    ) -> Result<(Option<LongEdgePart>, Option<LongEdgePart>), Error> {
+        if !self.contains_cell(&cell) {
+            return Err(Error::CellNotInLongEdge);
+        }
+        let mut h3edges: Vec<_> = self.h3edge_path()?.collect();
+        let mut split_pos = None;
+        for (pos, h3edge) in h3edges.iter().enumerate() {
+            if h3edge.origin_cell()? == cell {
+                split_pos = Some(pos);
+                break;
+            }
+            if h3edge.destination_cell()? == cell {
+                split_pos = Some(pos + 1);
+                break;
+            }
+        }
+        let split_pos = split_pos.ok_or(Error::CellNotInLongEdge)?;
+
+        let (first_weights, second_weights) = match self.cumulative_weights.as_ref() {
+            Some(weights) => {
+                let offset = split_pos
+                    .checked_sub(1)
+                    .map_or(0.0, |last_pos| weights[last_pos]);
+                (
+                    Some(weights[..split_pos].to_vec()),
+                    Some(weights[split_pos..].iter().map(|w| w - offset).collect()),
+                )
+            }
+            None => (None, None),
+        };
+        let second = LongEdgePart::from_h3edges(h3edges.split_off(split_pos), second_weights)?;
+        let first = LongEdgePart::from_h3edges(h3edges, first_weights)?;
+        Ok((first, second))
+    }
+
+    /// Append the path of `other` to the path of `self`.
+    ///
+    /// The cell lookup of the result is the union of the cell lookups of both longedges, so
+    /// it does not get rebuilt from the path. The cumulative weights are only kept when both
+    /// longedges carry weights.
+    ///
+    /// Fails with [`Error::LongEdgesNotConnected`] when `other` does not start at the
+    /// destination cell of `self`.
+    pub fn concat(self, other: Self) -> Result<Self, Error> {
+        let (destination_cell, origin_cell) = (self.destination_cell()?, other.origin_cell()?);
+        if destination_cell != origin_cell {
+            return Err(Error::LongEdgesNotConnected(destination_cell, origin_cell));
+        }
+
+        let mut h3edges: Vec<_> = self.h3edge_path()?.collect();
+        h3edges.extend(other.h3edge_path()?);
+
+        let mut cell_lookup = self.cell_lookup;
+        cell_lookup.union_with(&other.cell_lookup);
+
+        let cumulative_weights = match (self.cumulative_weights, other.cumulative_weights) {
+            (Some(mut weights), Some(other_weights)) => {
+                let offset = weights.last().copied().unwrap_or(0.0);
+                weights.extend(other_weights.iter().map(|w| w + offset));
+                Some(weights)
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            in_edge: self.in_edge,
+            out_edge: other.out_edge,
+            edge_path: h3edges.into(),
+            cell_lookup,
+            cumulative_weights,
+        })
+    }
+
     /// length of `self` as the number of contained h3edges
     pub const fn h3edges_len(&self) -> usize {
         self.edge_path.len().saturating_sub(1)
@@ -69,11 +324,57 @@ impl LongEdge {
     pub fn h3edge_path(&self) -> Result<OwningDecompressedIter<H3DirectedEdge>, Error> {
         Ok(self.edge_path.iter_uncompressed()?)
     }
+
+    /// the edge at position `pos` of the path of the longedge. `None` when `pos` is out of range.
+    ///
+    /// Avoids decompressing the complete path.
+    pub fn h3edge_at(&self, pos: usize) -> Result<Option<H3DirectedEdge>, Error> {
+        Ok(self.edge_path.get(pos)?)
+    }
+}
+
+/// A part of a [`LongEdge`] created by [`LongEdge::split_at_cell`].
+#[derive(Clone)]
+pub enum LongEdgePart {
+    Single(H3DirectedEdge),
+    Long(LongEdge),
+}
+
+impl LongEdgePart {
+    /// `h3edges` must be a continuous path.
+    fn from_h3edges(
+        h3edges: Vec<H3DirectedEdge>,
+        cumulative_weights: Option<Vec<f64>>,
+    ) -> Result<Option<Self>, Error> {
+        match h3edges.len() {
+            0 => Ok(None),
+            1 => Ok(Some(Self::Single(h3edges[0]))),
+            _ => Ok(Some(Self::Long(LongEdge::from_path(
+                h3edges,
+                cumulative_weights,
+            )?))),
+        }
+    }
+
+    pub fn origin_cell(&self) -> Result<H3Cell, Error> {
+        match self {
+            Self::Single(h3edge) => Ok(h3edge.origin_cell()?),
+            Self::Long(longedge) => longedge.origin_cell(),
+        }
+    }
+
+    pub fn destination_cell(&self) -> Result<H3Cell, Error> {
+        match self {
+            Self::Single(h3edge) => Ok(h3edge.destination_cell()?),
+            Self::Long(longedge) => longedge.destination_cell(),
+        }
+    }
 }
 
 /// construct an longedge from a vec of `H3DirectedEdge`.
 ///
-/// The `H3DirectedEdge` must be sorted according to the path they describe
+/// The `H3DirectedEdge` must be sorted according to the path they describe. Paths which
+/// are not continuous fail with [`Error::DiscontinuousPath`].
 impl TryFrom<Vec<H3DirectedEdge>> for LongEdge {
     type Error = Error;
 
@@ -81,13 +382,8 @@ impl TryFrom<Vec<H3DirectedEdge>> for LongEdge {
         h3edges.dedup();
         h3edges.shrink_to_fit();
         if h3edges.len() >= 2 {
-            let cell_lookup: H3Treemap<_> = h3edge_path_to_h3cell_path(&h3edges)?.iter().collect();
-            Ok(Self {
-                in_edge: h3edges[0],
-                out_edge: *h3edges.last().unwrap(),
-                edge_path: h3edges.into(),
-                cell_lookup,
-            })
+            validate_continuity(&h3edges)?;
+            Self::from_path(h3edges, None)
         } else {
             Err(Error::InsufficientNumberOfEdges)
         }
@@ -97,21 +393,515 @@ impl TryFrom<Vec<H3DirectedEdge>> for LongEdge {
 impl ToLineString for LongEdge {
     type Error = Error;
 
+    /// The linestring through the centroids of the cells of the path. Segments are joined
+    /// using the [`DEFAULT_SEGMENT_EPSILON`], see [`LongEdge::to_linestring_with_epsilon`].
     fn to_linestring(&self) -> Result<LineString<f64>, Self::Error> {
-        match self
+        self.to_linestring_with_epsilon(DEFAULT_SEGMENT_EPSILON)
+    }
+}
+
+/// Maximum distance in degrees between the end of a segment and the start of the following
+/// segment for both to be joined to a single linestring.
+pub const DEFAULT_SEGMENT_EPSILON: f64 = 1e-9;
+
+/// Join the consecutive linestrings of `mls` to a single linestring.
+///
+/// Linestrings are joined when the end of a linestring is located within `epsilon` degrees
+/// of the start of the following linestring, the start coordinate of the following
+/// linestring is dropped. Gaps larger than `epsilon` fail with [`Error::SegmentedPath`]
+/// containing both coordinates of the gap.
+pub fn join_linestrings(mls: MultiLineString<f64>, epsilon: f64) -> Result<LineString<f64>, Error> {
+    let mut parts = mls.0.into_iter().filter(|ls| !ls.0.is_empty());
+    let mut joined = parts.next().ok_or(Error::InsufficientNumberOfEdges)?;
+    for part in parts {
+        // both linestrings contain coordinates
+        let (end, start) = (joined.0[joined.0.len() - 1], part.0[0]);
+        if (end.x - start.x).hypot(end.y - start.y) > epsilon {
+            return Err(Error::SegmentedPath(end, start));
+        }
+        joined.0.extend(part.0.into_iter().skip(1));
+    }
+    Ok(joined)
+}
+
+impl LongEdge {
+    /// The linestring through the centroids of the cells of the path.
+    ///
+    /// Segments of the path get joined when their ends are located within `epsilon` degrees
+    /// of each other. See [`join_linestrings`].
+    pub fn to_linestring_with_epsilon(&self, epsilon: f64) -> Result<LineString<f64>, Error> {
+        self.to_linestring_with_coordinates(epsilon, |cell| Ok(cell.to_coordinate()?))
+    }
+
+    /// Join the segments of the individual edges of the path, using `to_coordinate` to
+    /// locate the cells.
+    fn to_linestring_with_coordinates<F>(
+        &self,
+        epsilon: f64,
+        to_coordinate: F,
+    ) -> Result<LineString<f64>, Error>
+    where
+        F: Fn(H3Cell) -> Result<Coordinate<f64>, Error>,
+    {
+        let segments = self
             .h3edge_path()?
-            .collect::<Vec<_>>()
-            .as_slice()
-            .to_multilinestring()
-        {
-            Ok(mut mls) => {
-                if mls.0.len() != 1 {
-                    Err(Error::SegmentedPath)
-                } else {
-                    Ok(mls.0.swap_remove(0))
-                }
+            .map(|h3edge| {
+                Ok(LineString::from(vec![
+                    to_coordinate(h3edge.origin_cell()?)?,
+                    to_coordinate(h3edge.destination_cell()?)?,
+                ]))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        join_linestrings(MultiLineString(segments), epsilon)
+    }
+
+    /// The linestring of the path as WKT.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use h3ron::H3Cell;
+    /// use h3ron_graph::graph::longedge::LongEdge;
+    ///
+    /// let cell = H3Cell::from_coordinate((23.3, 12.3).into(), 8).unwrap();
+    /// let neighbors: Vec<_> = cell.grid_ring_unsafe(1).unwrap().iter().collect();
+    /// let longedge = LongEdge::try_from_cell_path([neighbors[0], cell, neighbors[3]], false).unwrap();
+    ///
+    /// assert!(longedge.to_wkt().unwrap().starts_with("LINESTRING("));
+    /// ```
+    pub fn to_wkt(&self) -> Result<String, Error> {
+        let coordinates = self
+            .to_linestring()?
+            .0
+            .iter()
+            .map(|c| format!("{} {}", c.x, c.y))
+            .collect::<Vec<_>>();
+        Ok(format!("LINESTRING({})", coordinates.join(", ")))
+    }
+
+    /// The linestring of the path as a GeoJSON geometry.
+    pub fn to_geojson_string(&self) -> Result<String, Error> {
+        let coordinates = self
+            .to_linestring()?
+            .0
+            .iter()
+            .map(|c| format!("[{},{}]", c.x, c.y))
+            .collect::<Vec<_>>();
+        Ok(format!(
+            r#"{{"type":"LineString","coordinates":[{}]}}"#,
+            coordinates.join(",")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use geo_types::{Coordinate, LineString, MultiLineString};
+    use h3ron::collections::H3Treemap;
+    use h3ron::to_geo::ToLineString;
+    use h3ron::{H3Cell, H3DirectedEdge, ToCoordinate};
+
+    use crate::error::Error;
+    use crate::graph::longedge::{
+        h3cell_path_to_h3edge_path, h3edge_path_to_h3cell_path, join_linestrings, LongEdge,
+        LongEdgePart, DEFAULT_SEGMENT_EPSILON,
+    };
+
+    fn line_cells() -> Vec<H3Cell> {
+        h3ron::line(
+            &LineString::from(vec![
+                Coordinate::from((23.3, 12.3)),
+                Coordinate::from((23.4, 12.2)),
+            ]),
+            8,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn build_longedge(cells: &[H3Cell]) -> LongEdge {
+        let h3edges: Vec<H3DirectedEdge> = cells
+            .windows(2)
+            .map(|w| w[0].directed_edge_to(w[1]).unwrap())
+            .collect();
+        LongEdge::try_from(h3edges).unwrap()
+    }
+
+    fn h3edges_of(part: &LongEdgePart) -> Vec<H3DirectedEdge> {
+        match part {
+            LongEdgePart::Single(h3edge) => vec![*h3edge],
+            LongEdgePart::Long(longedge) => longedge.h3edge_path().unwrap().collect(),
+        }
+    }
+
+    #[test]
+    fn split_at_cell() {
+        let cells = line_cells();
+        assert!(cells.len() > 6);
+        let longedge = build_longedge(&cells);
+        let all_h3edges: Vec<_> = longedge.h3edge_path().unwrap().collect();
+
+        for (i, cell) in cells.iter().enumerate() {
+            assert!(longedge.contains_cell(cell));
+            let (first, second) = longedge.split_at_cell(*cell).unwrap();
+
+            let mut joined = vec![];
+            if let Some(first) = first.as_ref() {
+                assert_eq!(first.origin_cell().unwrap(), cells[0]);
+                assert_eq!(first.destination_cell().unwrap(), *cell);
+                joined.extend(h3edges_of(first));
+            }
+            if let Some(second) = second.as_ref() {
+                assert_eq!(second.origin_cell().unwrap(), *cell);
+                assert_eq!(second.destination_cell().unwrap(), *cells.last().unwrap());
+                joined.extend(h3edges_of(second));
+            }
+            assert_eq!(joined, all_h3edges);
+
+            assert_eq!(first.is_none(), i == 0);
+            assert_eq!(second.is_none(), i == cells.len() - 1);
+            assert_eq!(matches!(first, Some(LongEdgePart::Single(_))), i == 1);
+            assert_eq!(
+                matches!(second, Some(LongEdgePart::Single(_))),
+                i == cells.len() - 2
+            );
+        }
+    }
+
+    #[test]
+    fn split_at_cell_outside() {
+        let cells = line_cells();
+        let longedge = build_longedge(&cells);
+        let outside = H3Cell::from_coordinate((10.0, 10.0).into(), 8).unwrap();
+        assert!(!longedge.contains_cell(&outside));
+        assert!(matches!(
+            longedge.split_at_cell(outside),
+            Err(Error::CellNotInLongEdge)
+        ));
+    }
+
+    fn weighted_h3edges(cells: &[H3Cell]) -> Vec<(H3DirectedEdge, u32)> {
+        cells
+            .windows(2)
+            .enumerate()
+            .map(|(i, w)| (w[0].directed_edge_to(w[1]).unwrap(), i as u32 + 1))
+            .collect()
+    }
+
+    #[test]
+    fn try_from_edges_with_weights() {
+        let cells = line_cells();
+        let edges = weighted_h3edges(&cells);
+        let n = edges.len() as u32;
+
+        let (longedge, weight) = LongEdge::try_from_edges_with_weights(edges.clone()).unwrap();
+        assert_eq!(weight, n * (n + 1) / 2);
+        assert_eq!(longedge.total_weight(), Some(f64::from(weight)));
+        let cumulative_weights = longedge.cumulative_weights().unwrap();
+        assert_eq!(cumulative_weights.len(), edges.len());
+        assert_eq!(cumulative_weights[0], 1.0);
+        assert_eq!(cumulative_weights[2], 6.0);
+        assert_eq!(
+            longedge.h3edge_path().unwrap().collect::<Vec<_>>(),
+            edges.iter().map(|(h3edge, _)| *h3edge).collect::<Vec<_>>()
+        );
+        assert!(longedge.to_linestring().is_ok());
+        for (pos, (h3edge, _)) in edges.iter().enumerate() {
+            assert_eq!(longedge.h3edge_at(pos).unwrap(), Some(*h3edge));
+        }
+        assert_eq!(longedge.h3edge_at(edges.len()).unwrap(), None);
+
+        assert!(
+            LongEdge::try_from(edges.iter().map(|(h3edge, _)| *h3edge).collect::<Vec<_>>())
+                .unwrap()
+                .cumulative_weights()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn cell_path_to_edge_path() {
+        let cells = line_cells();
+        let h3edges = h3cell_path_to_h3edge_path(&cells, false).unwrap();
+        assert_eq!(h3edges.len(), cells.len() - 1);
+        assert_eq!(h3edge_path_to_h3cell_path(&h3edges).unwrap(), cells);
+
+        // repeated cells are skipped
+        let mut repeated_cells = cells.clone();
+        repeated_cells.insert(3, cells[3]);
+        repeated_cells.insert(0, cells[0]);
+        assert_eq!(
+            h3cell_path_to_h3edge_path(&repeated_cells, false).unwrap(),
+            h3edges
+        );
+
+        let longedge = LongEdge::try_from_cell_path(&repeated_cells, false).unwrap();
+        assert_eq!(longedge.h3edge_path().unwrap().collect::<Vec<_>>(), h3edges);
+    }
+
+    #[test]
+    fn cell_path_with_gaps() {
+        let cells = line_cells();
+        let mut cells_with_gap = cells.clone();
+        cells_with_gap.drain(2..5);
+
+        assert!(matches!(
+            h3cell_path_to_h3edge_path(&cells_with_gap, false),
+            Err(Error::NonNeighborCells(1, a, b)) if a == cells[1] && b == cells[5]
+        ));
+        assert!(matches!(
+            LongEdge::try_from_cell_path(&cells_with_gap, false),
+            Err(Error::NonNeighborCells(1, a, b)) if a == cells[1] && b == cells[5]
+        ));
+
+        let h3edges = h3cell_path_to_h3edge_path(&cells_with_gap, true).unwrap();
+        let bridged_cells = h3edge_path_to_h3cell_path(&h3edges).unwrap();
+        assert_eq!(bridged_cells.first(), cells.first());
+        assert_eq!(bridged_cells.last(), cells.last());
+        assert!(bridged_cells.len() > cells_with_gap.len());
+        assert!(LongEdge::try_from_cell_path(&cells_with_gap, true).is_ok());
+    }
+
+    #[test]
+    fn discontinuous_path() {
+        let cells = line_cells();
+        let mut edges = weighted_h3edges(&cells);
+        edges.remove(3);
+
+        assert!(matches!(
+            LongEdge::try_from_edges_with_weights(edges.clone()),
+            Err(Error::DiscontinuousPath(3))
+        ));
+        assert!(matches!(
+            LongEdge::try_from(edges.iter().map(|(h3edge, _)| *h3edge).collect::<Vec<_>>()),
+            Err(Error::DiscontinuousPath(3))
+        ));
+        assert!(matches!(
+            LongEdge::try_from_edges_with_weights(edges[..1].to_vec()),
+            Err(Error::InsufficientNumberOfEdges)
+        ));
+    }
+
+    #[test]
+    fn split_at_cell_apportions_weights() {
+        let cells = line_cells();
+        let (longedge, weight) =
+            LongEdge::try_from_edges_with_weights(weighted_h3edges(&cells)).unwrap();
+        let part_weight = |part: &Option<LongEdgePart>, first_edge_weight: f64| match part {
+            Some(LongEdgePart::Long(longedge)) => longedge.total_weight().unwrap(),
+            Some(LongEdgePart::Single(_)) => first_edge_weight,
+            None => 0.0,
+        };
+
+        for (i, cell) in cells.iter().enumerate() {
+            let (first, second) = longedge.split_at_cell(*cell).unwrap();
+            // the weight of the edge at position `j` is `j + 1`
+            let first_weight = part_weight(&first, 1.0);
+            let second_weight = part_weight(&second, i as f64 + 1.0);
+            assert_eq!(first_weight, (i * (i + 1) / 2) as f64);
+            assert_eq!(first_weight + second_weight, f64::from(weight));
+        }
+    }
+
+    #[test]
+    fn concat() {
+        let cells = line_cells();
+        let split_pos = cells.len() / 2;
+        let direct = build_longedge(&cells);
+        let first = build_longedge(&cells[..=split_pos]);
+        let second = build_longedge(&cells[split_pos..]);
+
+        let concatenated = first.clone().concat(second.clone()).unwrap();
+        assert_eq!(concatenated.h3edges_len(), direct.h3edges_len());
+        assert_eq!(
+            concatenated.h3edge_path().unwrap().collect::<Vec<_>>(),
+            direct.h3edge_path().unwrap().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            concatenated.to_linestring().unwrap(),
+            direct.to_linestring().unwrap()
+        );
+        assert_eq!(concatenated.origin_cell().unwrap(), cells[0]);
+        assert_eq!(
+            concatenated.destination_cell().unwrap(),
+            *cells.last().unwrap()
+        );
+        for cell in cells.iter() {
+            let single: H3Treemap<H3Cell> = std::iter::once(*cell).collect();
+            assert!(!concatenated.is_disjoint(&single));
+            assert_eq!(
+                concatenated.is_disjoint(&single),
+                direct.is_disjoint(&single)
+            );
+        }
+        let outside: H3Treemap<H3Cell> =
+            std::iter::once(H3Cell::from_coordinate((-10.0, 40.0).into(), 8).unwrap()).collect();
+        assert!(concatenated.is_disjoint(&outside));
+
+        assert!(matches!(
+            second.concat(first),
+            Err(Error::LongEdgesNotConnected(destination, origin))
+                if destination == *cells.last().unwrap() && origin == cells[0]
+        ));
+    }
+
+    #[test]
+    fn concat_keeps_weights() {
+        let cells = line_cells();
+        let split_pos = cells.len() / 2;
+        let edges = weighted_h3edges(&cells);
+        let (direct, weight) = LongEdge::try_from_edges_with_weights(edges.clone()).unwrap();
+        let (first, _) =
+            LongEdge::try_from_edges_with_weights(edges[..split_pos].to_vec()).unwrap();
+        let (second, _) =
+            LongEdge::try_from_edges_with_weights(edges[split_pos..].to_vec()).unwrap();
+
+        let concatenated = first.clone().concat(second).unwrap();
+        assert_eq!(concatenated.total_weight(), Some(f64::from(weight)));
+        assert_eq!(
+            concatenated.cumulative_weights(),
+            direct.cumulative_weights()
+        );
+
+        let unweighted = build_longedge(&cells[split_pos..]);
+        assert!(first
+            .concat(unweighted)
+            .unwrap()
+            .cumulative_weights()
+            .is_none());
+    }
+
+    #[test]
+    fn intersection_with_treemap() {
+        let cells = line_cells();
+        assert!(cells.len() > 6);
+        let longedge = build_longedge(&cells);
+
+        let outside = H3Cell::from_coordinate((-10.0, 40.0).into(), 8).unwrap();
+        let treemap: H3Treemap<H3Cell> = cells
+            .iter()
+            .step_by(2)
+            .copied()
+            .chain(std::iter::once(outside))
+            .collect();
+        let expected: Vec<_> = cells.iter().step_by(2).copied().collect();
+
+        assert!(!longedge.is_disjoint(&treemap));
+        assert_eq!(longedge.intersection_count(&treemap), expected.len());
+        assert_eq!(longedge.intersection(&treemap).unwrap(), expected);
+
+        let disjoint: H3Treemap<H3Cell> = std::iter::once(outside).collect();
+        assert_eq!(longedge.intersection_count(&disjoint), 0);
+        assert!(longedge.intersection(&disjoint).unwrap().is_empty());
+    }
+
+    /// split `ls` into linestrings of two coordinates each, shifting the start of each
+    /// linestring by `offset`.
+    fn split_linestring(ls: &LineString<f64>, offset: f64) -> MultiLineString<f64> {
+        MultiLineString(
+            ls.0.windows(2)
+                .map(|w| {
+                    LineString::from(vec![
+                        Coordinate::from((w[0].x + offset, w[0].y - offset)),
+                        w[1],
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn join_perturbed_linestrings() {
+        let cells = line_cells();
+        let linestring = build_longedge(&cells).to_linestring().unwrap();
+        assert_eq!(linestring.0.len(), cells.len());
+
+        let joined = join_linestrings(
+            split_linestring(&linestring, 1e-12),
+            DEFAULT_SEGMENT_EPSILON,
+        )
+        .unwrap();
+        assert_eq!(joined.0.len(), linestring.0.len());
+        for (c_joined, c_expected) in joined.0.iter().zip(linestring.0.iter()).skip(1) {
+            assert_eq!(c_joined, c_expected);
+        }
+
+        assert!(matches!(
+            join_linestrings(split_linestring(&linestring, 1e-12), 0.0),
+            Err(Error::SegmentedPath(_, _))
+        ));
+        assert!(matches!(
+            join_linestrings(MultiLineString(vec![]), DEFAULT_SEGMENT_EPSILON),
+            Err(Error::InsufficientNumberOfEdges)
+        ));
+    }
+
+    #[test]
+    fn to_linestring_with_perturbed_coordinates() {
+        let cells = line_cells();
+        let longedge = build_longedge(&cells);
+        let linestring = longedge.to_linestring().unwrap();
+        assert_eq!(linestring.0.len(), cells.len());
+
+        // each lookup of a cell results in a slightly different coordinate, so the end of
+        // each segment differs from the start of the following segment
+        let num_lookups = Cell::new(0_u32);
+        let perturbed_coordinate = |cell: H3Cell| {
+            num_lookups.set(num_lookups.get() + 1);
+            let offset = f64::from(num_lookups.get() % 3) * 1e-12;
+            let c = cell.to_coordinate()?;
+            Ok(Coordinate::from((c.x + offset, c.y - offset)))
+        };
+
+        let perturbed = longedge
+            .to_linestring_with_coordinates(DEFAULT_SEGMENT_EPSILON, perturbed_coordinate)
+            .unwrap();
+        assert_eq!(perturbed.0.len(), linestring.0.len());
+        for (c_perturbed, c_expected) in perturbed.0.iter().zip(linestring.0.iter()) {
+            assert!((c_perturbed.x - c_expected.x).abs() < 1e-11);
+            assert!((c_perturbed.y - c_expected.y).abs() < 1e-11);
+        }
+
+        assert!(matches!(
+            longedge.to_linestring_with_coordinates(0.0, perturbed_coordinate),
+            Err(Error::SegmentedPath(_, _))
+        ));
+    }
+
+    #[test]
+    fn join_linestrings_with_gap() {
+        let cells = line_cells();
+        let linestring = build_longedge(&cells).to_linestring().unwrap();
+        let mut mls = split_linestring(&linestring, 0.0);
+        mls.0.remove(2);
+
+        match join_linestrings(mls, DEFAULT_SEGMENT_EPSILON) {
+            Err(Error::SegmentedPath(end, start)) => {
+                assert_eq!(end, linestring.0[2]);
+                assert_eq!(start, linestring.0[3]);
             }
-            Err(e) => Err(e.into()),
+            _ => panic!("expected a segmented path"),
         }
     }
+
+    #[test]
+    fn to_wkt_and_geojson() {
+        let cells = line_cells();
+        let longedge = build_longedge(&cells);
+        let first = cells[0].to_coordinate().unwrap();
+
+        let wkt = longedge.to_wkt().unwrap();
+        assert!(wkt.starts_with(&format!("LINESTRING({} {}, ", first.x, first.y)));
+        assert_eq!(wkt.matches(", ").count(), cells.len() - 1);
+
+        let geojson = longedge.to_geojson_string().unwrap();
+        assert!(geojson.starts_with(&format!(
+            r#"{{"type":"LineString","coordinates":[[{},{}],"#,
+            first.x, first.y
+        )));
+        assert!(geojson.ends_with("]]}"));
+        assert_eq!(geojson.matches("],[").count(), cells.len() - 1);
+    }
 }
@@ -1,6 +1,7 @@
 use serde::Serialize;
 
 use crate::error::Error;
+pub use builder::{GraphBuilder, GraphBuilderStats};
 pub use h3edge::{H3EdgeGraph, H3EdgeGraphBuilder};
 use h3ron::{H3Cell, H3DirectedEdge};
 use node::NodeType;
@@ -8,7 +9,9 @@ pub use prepared::PreparedH3EdgeGraph;
 
 use crate::graph::longedge::LongEdge;
 
+pub mod builder;
 pub mod h3edge;
+pub mod layered;
 pub mod longedge;
 pub mod modifiers;
 pub mod node;
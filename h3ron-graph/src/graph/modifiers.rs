@@ -1,7 +1,11 @@
+use std::fmt::{Debug, Formatter};
 use std::marker::PhantomData;
+use std::ops::Add;
+
+use num_traits::ToPrimitive;
 
 use crate::error::Error;
-use h3ron::collections::H3Treemap;
+use h3ron::collections::{H3CellMap, H3Treemap};
 use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution};
 
 use crate::graph::node::NodeType;
@@ -93,3 +97,370 @@ where
         self.inner_graph.h3_resolution()
     }
 }
+
+/// How the penalty of a [`SoftExclusion`] decreases with the grid distance to the
+/// nearest source cell.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PenaltyDecay {
+    /// Linear decrease from the full penalty at the source cells to zero at `max_k + 1`.
+    Linear,
+
+    /// Exponential decrease with the given rate per grid distance: `exp(-rate * distance)`.
+    Exponential(f64),
+}
+
+impl PenaltyDecay {
+    fn factor(&self, distance: u32, max_k: u32) -> f64 {
+        match self {
+            Self::Linear => 1.0 - f64::from(distance) / f64::from(max_k + 1),
+            Self::Exponential(rate) => (-rate * f64::from(distance)).exp(),
+        }
+    }
+}
+
+/// Penalties for cells near a set of source cells to route away from these cells
+/// without forbidding them like [`ExcludeCells`] does.
+///
+/// The penalty of a cell is `peak_penalty * decay(distance)` with `distance` being the
+/// grid distance to the nearest source cell. Cells further away than `max_k` are not
+/// penalized. The k-neighborhood of the sources is labeled once during the construction
+/// using a breadth-first search starting from all source cells.
+///
+/// Use [`SoftExcludeCells`] to apply the penalties to a graph.
+#[derive(Clone)]
+pub struct SoftExclusion<W> {
+    /// grid distance to the nearest source cell for all cells within `max_k`.
+    distances: H3CellMap<u32>,
+
+    /// all cells contained in `distances`
+    labeled_cells: H3Treemap<H3Cell>,
+
+    /// penalty by grid distance
+    penalties: Vec<W>,
+}
+
+impl<W> Debug for SoftExclusion<W>
+where
+    W: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SoftExclusion")
+            .field("distances", &self.distances)
+            .field("num_labeled_cells", &self.labeled_cells.len())
+            .field("penalties", &self.penalties)
+            .finish()
+    }
+}
+
+impl<W> SoftExclusion<W>
+where
+    W: Copy + ToPrimitive + num_traits::NumCast,
+{
+    /// Label all cells within the grid distance `max_k` of the `source_cells` and compute
+    /// the penalty for each distance.
+    ///
+    /// Fails when `peak_penalty` or one of the decayed penalties can not be represented
+    /// in the weight type `W`.
+    pub fn new(
+        source_cells: &H3Treemap<H3Cell>,
+        peak_penalty: W,
+        decay: PenaltyDecay,
+        max_k: u32,
+    ) -> Result<Self, Error> {
+        let peak_penalty = peak_penalty
+            .to_f64()
+            .ok_or_else(|| Error::Other("peak penalty is not representable as f64".to_string()))?;
+        let penalties = (0..=max_k)
+            .map(|distance| {
+                W::from(peak_penalty * decay.factor(distance, max_k)).ok_or_else(|| {
+                    Error::Other(format!(
+                        "penalty at grid distance {} is not representable in the weight type",
+                        distance
+                    ))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut distances = H3CellMap::default();
+        let mut frontier: Vec<_> = source_cells.iter().collect();
+        for cell in frontier.iter() {
+            distances.insert(*cell, 0);
+        }
+        for distance in 1..=max_k {
+            let mut next_frontier = Vec::with_capacity(frontier.len() * 2);
+            for cell in frontier {
+                for neighbor in cell.grid_disk(1)?.iter() {
+                    if !distances.contains_key(&neighbor) {
+                        distances.insert(neighbor, distance);
+                        next_frontier.push(neighbor);
+                    }
+                }
+            }
+            frontier = next_frontier;
+        }
+
+        Ok(Self {
+            labeled_cells: distances.keys().copied().collect(),
+            distances,
+            penalties,
+        })
+    }
+
+    /// grid distance to the nearest source cell. `None` for cells further away than `max_k`.
+    pub fn distance_to_source(&self, cell: &H3Cell) -> Option<u32> {
+        self.distances.get(cell).copied()
+    }
+
+    /// The penalty for entering `cell`. `None` for cells further away than `max_k`.
+    pub fn penalty(&self, cell: &H3Cell) -> Option<W> {
+        self.distance_to_source(cell)
+            .map(|distance| self.penalties[distance as usize])
+    }
+
+    /// all cells within `max_k` of the source cells.
+    pub const fn labeled_cells(&self) -> &H3Treemap<H3Cell> {
+        &self.labeled_cells
+    }
+}
+
+/// wrapper to add the penalties of a [`SoftExclusion`] to the weights of the edges leading
+/// into penalized cells during routing.
+///
+/// Can be combined with [`ExcludeCells`] by wrapping one in the other. Longedges containing
+/// any penalized cell are removed, so these get evaluated edge by edge.
+pub struct SoftExcludeCells<'a, G, W> {
+    soft_exclusion: &'a SoftExclusion<W>,
+    inner_graph: &'a G,
+}
+
+impl<'a, G, W> SoftExcludeCells<'a, G, W>
+where
+    G: GetCellNode + GetCellEdges<EdgeWeightType = W> + HasH3Resolution,
+{
+    pub fn new(inner_graph: &'a G, soft_exclusion: &'a SoftExclusion<W>) -> Self {
+        Self {
+            soft_exclusion,
+            inner_graph,
+        }
+    }
+}
+
+impl<'a, G, W> GetCellNode for SoftExcludeCells<'a, G, W>
+where
+    G: GetCellNode,
+{
+    fn get_cell_node(&self, cell: &H3Cell) -> Option<NodeType> {
+        self.inner_graph.get_cell_node(cell)
+    }
+}
+
+impl<'a, G, W> GetCellEdges for SoftExcludeCells<'a, G, W>
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: Copy + ToPrimitive + num_traits::NumCast + Add<Output = W>,
+{
+    type EdgeWeightType = G::EdgeWeightType;
+
+    fn get_edges_originating_from(
+        &self,
+        cell: &H3Cell,
+    ) -> Result<Vec<(H3DirectedEdge, EdgeWeight<Self::EdgeWeightType>)>, Error> {
+        let mut edges = self.inner_graph.get_edges_originating_from(cell)?;
+        for (edge, edge_value) in edges.iter_mut() {
+            if let Some(penalty) = self.soft_exclusion.penalty(&edge.destination_cell()?) {
+                edge_value.weight = edge_value.weight + penalty;
+            }
+
+            // fall back to the individual edges when the longedge contains any penalized cell
+            let longedge_is_penalized =
+                edge_value.longedge.as_ref().is_some_and(|(longedge, _)| {
+                    !longedge.is_disjoint(self.soft_exclusion.labeled_cells())
+                });
+            if longedge_is_penalized {
+                edge_value.longedge = None;
+            }
+        }
+        Ok(edges)
+    }
+}
+
+impl<'a, G, W> HasH3Resolution for SoftExcludeCells<'a, G, W>
+where
+    G: HasH3Resolution,
+{
+    fn h3_resolution(&self) -> u8 {
+        self.inner_graph.h3_resolution()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use geo_types::Coordinate;
+
+    use h3ron::collections::{H3CellSet, H3Treemap};
+    use h3ron::H3Cell;
+
+    use crate::algorithm::path::Path;
+    use crate::algorithm::shortest_path::{DefaultShortestPathOptions, ShortestPath};
+    use crate::graph::modifiers::{PenaltyDecay, SoftExcludeCells, SoftExclusion};
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    fn center() -> H3Cell {
+        H3Cell::from_coordinate(Coordinate::from((10.0, 20.0)), 8).unwrap()
+    }
+
+    fn build_graph() -> PreparedH3EdgeGraph<u32> {
+        let mut graph = H3EdgeGraph::new(8);
+        for cell in center().grid_disk(8).unwrap().iter() {
+            for edge in cell.directed_edges().unwrap().iter() {
+                graph.add_edge(edge, 10).unwrap();
+            }
+        }
+        graph.try_into().unwrap()
+    }
+
+    /// two corner cells of a ring on opposite sides of the center cell. The only shortest
+    /// path between them is the straight line through the center cell.
+    fn origin_and_destination() -> (H3Cell, H3Cell) {
+        let ring: Vec<_> = center().grid_ring_unsafe(6).unwrap().iter().collect();
+        // corner cells have three neighbors in the next outer ring, all others two
+        let origin = ring
+            .iter()
+            .copied()
+            .find(|cell| {
+                cell.grid_ring_unsafe(1)
+                    .unwrap()
+                    .iter()
+                    .filter(|neighbor| neighbor.grid_distance_to(center()).unwrap() == 7)
+                    .count()
+                    == 3
+            })
+            .unwrap();
+        // the cell at the mirrored position of the origin
+        let center_ij = center().to_localij(center()).unwrap();
+        let destination = H3Cell::from_localij(
+            center(),
+            center_ij + center_ij - origin.to_localij(center()).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(origin.grid_distance_to(destination).unwrap(), 12);
+        (origin, destination)
+    }
+
+    fn route<G: ShortestPath<u32>>(graph: &G, origin: H3Cell, destination: H3Cell) -> Path<u32> {
+        graph
+            .shortest_path(
+                origin,
+                [destination],
+                &DefaultShortestPathOptions::default(),
+            )
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn route_shifts_away_with_increasing_penalty() {
+        let graph = build_graph();
+        let (origin, destination) = origin_and_destination();
+        let sources: H3Treemap<_> = std::iter::once(center()).collect();
+
+        // penalties are exact integers for multiples of 4 with a linear decay over k = 3
+        let reference = SoftExclusion::new(&sources, 400_u32, PenaltyDecay::Linear, 3).unwrap();
+        let exposure = |path: &Path<u32>| -> u32 {
+            path.directed_edge_path
+                .cells()
+                .unwrap()
+                .iter()
+                .skip(1)
+                .filter_map(|cell| reference.penalty(cell))
+                .sum()
+        };
+
+        let mut exposures = vec![];
+        for peak_penalty in [0_u32, 4, 20, 100, 400] {
+            let soft_exclusion =
+                SoftExclusion::new(&sources, peak_penalty, PenaltyDecay::Linear, 3).unwrap();
+            let path = route(
+                &SoftExcludeCells::new(&graph, &soft_exclusion),
+                origin,
+                destination,
+            );
+            exposures.push(exposure(&path));
+        }
+
+        // the unpenalized route passes the center
+        assert!(exposures[0] > 0);
+        assert!(exposures.windows(2).all(|pair| pair[1] <= pair[0]));
+        // avoiding the neighborhood is cheaper than entering it
+        assert_eq!(exposures[exposures.len() - 1], 0);
+    }
+
+    #[test]
+    fn route_outside_of_neighborhood_unchanged() {
+        let graph = build_graph();
+        let (origin, destination) = origin_and_destination();
+
+        // the neighborhood only touches the outermost ring of the graph
+        let source = center()
+            .grid_ring_unsafe(11)
+            .unwrap()
+            .iter()
+            .next()
+            .unwrap();
+        let sources: H3Treemap<_> = std::iter::once(source).collect();
+        let soft_exclusion =
+            SoftExclusion::new(&sources, 1000_u32, PenaltyDecay::Linear, 3).unwrap();
+
+        let path = route(&graph, origin, destination);
+        let soft_path = route(
+            &SoftExcludeCells::new(&graph, &soft_exclusion),
+            origin,
+            destination,
+        );
+        assert_eq!(path.cost, soft_path.cost);
+        assert_eq!(path.len(), soft_path.len());
+        for cell in soft_path.directed_edge_path.cells().unwrap() {
+            assert!(soft_exclusion.distance_to_source(&cell).is_none());
+        }
+    }
+
+    #[test]
+    fn labels_equal_brute_force() {
+        let ring: Vec<_> = center().grid_ring_unsafe(3).unwrap().iter().collect();
+        let sources = [center(), ring[0], ring[7]];
+        let max_k = 2;
+        let soft_exclusion = SoftExclusion::new(
+            &sources.iter().copied().collect(),
+            100_u32,
+            PenaltyDecay::Exponential(0.5),
+            max_k,
+        )
+        .unwrap();
+
+        let mut expected_cells = H3CellSet::default();
+        for source in sources.iter() {
+            expected_cells.extend(source.grid_disk(max_k + 2).unwrap().iter());
+        }
+        let mut num_labeled = 0;
+        for cell in expected_cells {
+            let nearest = sources
+                .iter()
+                .map(|source| cell.grid_distance_to(*source).unwrap() as u32)
+                .min()
+                .unwrap();
+            if nearest <= max_k {
+                num_labeled += 1;
+                assert_eq!(soft_exclusion.distance_to_source(&cell), Some(nearest));
+                assert_eq!(
+                    soft_exclusion.penalty(&cell),
+                    Some((100.0 * (-0.5 * f64::from(nearest)).exp()) as u32)
+                );
+            } else {
+                assert_eq!(soft_exclusion.distance_to_source(&cell), None);
+            }
+        }
+        assert_eq!(soft_exclusion.labeled_cells().len(), num_labeled);
+    }
+}
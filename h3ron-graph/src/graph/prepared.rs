@@ -1,4 +1,5 @@
 use std::ops::Add;
+use std::sync::OnceLock;
 
 use geo::bounding_rect::BoundingRect;
 use geo::concave_hull::ConcaveHull;
@@ -15,6 +16,7 @@ use h3ron::iter::H3DirectedEdgesBuilder;
 use h3ron::{H3Cell, H3DirectedEdge, HasH3Resolution, ToCoordinate};
 
 use crate::algorithm::covered_area::{cells_covered_area, CoveredArea};
+use crate::algorithm::landmarks::GraphFingerprint;
 use crate::error::Error;
 use crate::graph::longedge::LongEdge;
 use crate::graph::node::NodeType;
@@ -23,7 +25,7 @@ use crate::graph::{
 };
 
 #[derive(Serialize, Deserialize, Clone)]
-struct OwnedEdgeValue<W> {
+pub(crate) struct OwnedEdgeValue<W> {
     pub weight: W,
 
     /// the longedge is a shortcut which includes many consequent edges while
@@ -50,11 +52,11 @@ where
     }
 }
 
-type OwnedEdgeTuple<W> = (H3DirectedEdge, OwnedEdgeValue<W>);
+pub(crate) type OwnedEdgeTuple<W> = (H3DirectedEdge, OwnedEdgeValue<W>);
 
 /// A smallvec with an array length of 2 allows storing the - probably - most common
 /// number of edges on the heap
-type OwnedEdgeTupleList<W> = SmallVec<[OwnedEdgeTuple<W>; 2]>;
+pub(crate) type OwnedEdgeTupleList<W> = SmallVec<[OwnedEdgeTuple<W>; 2]>;
 
 /// A prepared graph which can be used with a few algorithms.
 ///
@@ -72,9 +74,14 @@ type OwnedEdgeTupleList<W> = SmallVec<[OwnedEdgeTuple<W>; 2]>;
 ///
 #[derive(Serialize, Deserialize, Clone)]
 pub struct PreparedH3EdgeGraph<W> {
-    outgoing_edges: HashMap<H3Cell, OwnedEdgeTupleList<W>>,
-    h3_resolution: u8,
-    graph_nodes: HashMap<H3Cell, NodeType>,
+    pub(crate) outgoing_edges: HashMap<H3Cell, OwnedEdgeTupleList<W>>,
+    pub(crate) h3_resolution: u8,
+    pub(crate) graph_nodes: HashMap<H3Cell, NodeType>,
+
+    /// fingerprint validating [`LandmarkSet`]s against this graph, computed on first use.
+    /// The graph is immutable, so it never needs to be invalidated.
+    #[serde(skip)]
+    pub(crate) landmark_fingerprint: OnceLock<GraphFingerprint>,
 }
 
 unsafe impl<W> Sync for PreparedH3EdgeGraph<W> where W: Sync {}
@@ -182,7 +189,21 @@ impl<W: Copy> GetCellEdges for PreparedH3EdgeGraph<W> {
     }
 }
 
-const MIN_LONGEDGE_LENGTH: usize = 3;
+pub(crate) const MIN_LONGEDGE_LENGTH: usize = 3;
+
+/// An edge of the input graph together with the edge path and the weight of the longedge
+/// starting at the edge - if there is one.
+pub(crate) type EdgeChain<W> = (H3DirectedEdge, W, Option<(Vec<H3DirectedEdge>, W)>);
+
+pub(crate) fn validate_min_longedge_length(min_longedge_length: usize) -> Result<(), Error> {
+    if min_longedge_length < MIN_LONGEDGE_LENGTH {
+        return Err(Error::Other(format!(
+            "minimum longedge length must be >= {}",
+            MIN_LONGEDGE_LENGTH
+        )));
+    }
+    Ok(())
+}
 
 fn to_longedge_edges<W>(
     input_graph: H3EdgeGraph<W>,
@@ -191,72 +212,56 @@ fn to_longedge_edges<W>(
 where
     W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync,
 {
-    if min_longedge_length < MIN_LONGEDGE_LENGTH {
-        return Err(Error::Other(format!(
-            "minimum longedge length must be >= {}",
-            MIN_LONGEDGE_LENGTH
-        )));
-    }
+    validate_min_longedge_length(min_longedge_length)?;
+    let chains = detect_chains(&input_graph.edges, min_longedge_length)?;
+    let edge_tuples = construct_longedges(chains)?;
+    Ok(group_outgoing_edges(edge_tuples))
+}
 
-    let outgoing_edge_vecs = input_graph
-        .edges
+/// Find the paths of consequent edges without forks which will be turned into longedges.
+pub(crate) fn detect_chains<W>(
+    input_edges: &HashMap<H3DirectedEdge, W>,
+    min_longedge_length: usize,
+) -> Result<Vec<EdgeChain<W>>, Error>
+where
+    W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync,
+{
+    let chain_vecs = input_edges
         .par_iter()
         .try_fold(
             || (Vec::new(), H3DirectedEdgesBuilder::new()),
             |(mut output_vec, mut edge_builder), (edge, weight)| {
-                assemble_edge_with_longedge(
-                    &input_graph.edges,
+                detect_chain(
+                    input_edges,
                     min_longedge_length,
                     edge,
                     weight,
                     &mut edge_builder,
                 )
-                .map(|cell_edge| {
-                    output_vec.push(cell_edge);
+                .map(|chain| {
+                    output_vec.push(chain);
                     (output_vec, edge_builder)
                 })
             },
         )
         .collect::<Result<Vec<_>, _>>()?;
 
-    let mut outgoing_edges: HashMap<H3Cell, OwnedEdgeTupleList<W>> = Default::default();
-    for (outgoing_edge_vec, _) in outgoing_edge_vecs.into_iter() {
-        for (cell, edge_with_weight) in outgoing_edge_vec.into_iter() {
-            match outgoing_edges.entry(cell) {
-                Entry::Occupied(mut occ) => occ.get_mut().push(edge_with_weight),
-                Entry::Vacant(vac) => {
-                    vac.insert(smallvec![edge_with_weight]);
-                }
-            }
-        }
-    }
-
-    // remove duplicates if there are any. Ignores any differences in weights
-    outgoing_edges
-        .par_iter_mut()
-        .for_each(|(_cell, edges_with_weights)| {
-            edges_with_weights.sort_unstable_by_key(|eww| eww.0);
-            edges_with_weights.dedup_by(|a, b| a.0 == b.0);
-        });
-
-    Ok(outgoing_edges)
+    Ok(chain_vecs
+        .into_iter()
+        .flat_map(|(chain_vec, _)| chain_vec)
+        .collect())
 }
 
-fn assemble_edge_with_longedge<W>(
+fn detect_chain<W>(
     input_edges: &HashMap<H3DirectedEdge, W>,
     min_longedge_length: usize,
     edge: &H3DirectedEdge,
     weight: &W,
     edge_builder: &mut H3DirectedEdgesBuilder,
-) -> Result<(H3Cell, OwnedEdgeTuple<W>), Error>
+) -> Result<EdgeChain<W>, Error>
 where
     W: PartialOrd + PartialEq + Add<Output = W> + Copy,
 {
-    let mut graph_entry = OwnedEdgeValue {
-        weight: *weight,
-        longedge: None,
-    };
-
     let origin_cell = edge.origin_cell()?;
 
     // number of upstream edges leading to this one
@@ -311,11 +316,64 @@ where
         }
 
         if edge_path.len() >= min_longedge_length {
-            graph_entry.longedge =
-                Some(Box::new((LongEdge::try_from(edge_path)?, longedge_weight)));
+            return Ok((*edge, *weight, Some((edge_path, longedge_weight))));
+        }
+    }
+    Ok((*edge, *weight, None))
+}
+
+/// Build the `LongEdge`s of the detected chains.
+///
+/// The order of the chains is preserved.
+pub(crate) fn construct_longedges<W>(
+    chains: Vec<EdgeChain<W>>,
+) -> Result<Vec<(H3Cell, OwnedEdgeTuple<W>)>, Error>
+where
+    W: Send + Sync,
+{
+    chains
+        .into_par_iter()
+        .map(|(edge, weight, longedge_path)| {
+            let longedge = match longedge_path {
+                Some((edge_path, longedge_weight)) => {
+                    Some(Box::new((LongEdge::try_from(edge_path)?, longedge_weight)))
+                }
+                None => None,
+            };
+            Ok((
+                edge.origin_cell()?,
+                (edge, OwnedEdgeValue { weight, longedge }),
+            ))
+        })
+        .collect()
+}
+
+/// Group the edges by their origin cell.
+pub(crate) fn group_outgoing_edges<W>(
+    edge_tuples: Vec<(H3Cell, OwnedEdgeTuple<W>)>,
+) -> HashMap<H3Cell, OwnedEdgeTupleList<W>>
+where
+    W: Send + Sync,
+{
+    let mut outgoing_edges: HashMap<H3Cell, OwnedEdgeTupleList<W>> = Default::default();
+    for (cell, edge_with_weight) in edge_tuples.into_iter() {
+        match outgoing_edges.entry(cell) {
+            Entry::Occupied(mut occ) => occ.get_mut().push(edge_with_weight),
+            Entry::Vacant(vac) => {
+                vac.insert(smallvec![edge_with_weight]);
+            }
         }
     }
-    Ok((origin_cell, (*edge, graph_entry)))
+
+    // remove duplicates if there are any. Ignores any differences in weights
+    outgoing_edges
+        .par_iter_mut()
+        .for_each(|(_cell, edges_with_weights)| {
+            edges_with_weights.sort_unstable_by_key(|eww| eww.0);
+            edges_with_weights.dedup_by(|a, b| a.0 == b.0);
+        });
+
+    outgoing_edges
 }
 
 impl<W> PreparedH3EdgeGraph<W>
@@ -333,6 +391,7 @@ where
             graph_nodes,
             h3_resolution,
             outgoing_edges,
+            landmark_fingerprint: OnceLock::new(),
         })
     }
 }
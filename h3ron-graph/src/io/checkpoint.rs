@@ -0,0 +1,527 @@
+//! Checkpointed preparation of graphs
+//!
+//! Preparing a [`PreparedH3EdgeGraph`] from a large [`H3EdgeGraph`] may take a long time.
+//! [`prepare_with_checkpoints`] writes a checkpoint to a directory after each
+//! [`PreparationPhase`], so an interrupted preparation can be continued using
+//! [`resume_preparation`] instead of starting from scratch.
+//!
+//! Each checkpoint contains the version of this crate and a fingerprint of the input graph.
+//! Checkpoints created by a different version of this crate or for a different input graph
+//! are refused - to start over, the checkpoint directory needs to be removed.
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::ops::Add;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use rayon::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use h3ron::{H3Cell, H3DirectedEdge};
+
+use crate::error::Error;
+use crate::graph::node::NodeType;
+use crate::graph::prepared::{
+    construct_longedges, detect_chains, group_outgoing_edges, validate_min_longedge_length,
+    EdgeChain, OwnedEdgeTuple,
+};
+use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+use crate::io::serde_util::{deserialize_from, serialize_into};
+
+/// version of the layout of the checkpoint files
+const CHECKPOINT_FORMAT_VERSION: u32 = 2;
+
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const INPUT_FILE_NAME: &str = "input.checkpoint";
+
+/// The phases of the preparation of a graph. A checkpoint is written after each of them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreparationPhase {
+    /// extraction of the nodes from the edges of the input graph
+    EdgeIndexing,
+
+    /// detection of the paths of edges without forks
+    ChainDetection,
+
+    /// construction of the `LongEdge`s from the detected paths
+    LongEdgeConstruction,
+
+    /// assembly of the prepared graph
+    Finalization,
+}
+
+impl PreparationPhase {
+    fn file_name(&self) -> &'static str {
+        match self {
+            Self::EdgeIndexing => "edge_indexing.checkpoint",
+            Self::ChainDetection => "chain_detection.checkpoint",
+            Self::LongEdgeConstruction => "longedge_construction.checkpoint",
+            Self::Finalization => "finalization.checkpoint",
+        }
+    }
+}
+
+/// The contents of a checkpoint file.
+#[derive(Serialize, Deserialize)]
+struct PreparationCheckpoint {
+    format_version: u32,
+    crate_version: String,
+
+    /// the phase after which the checkpoint has been written. `None` for the checkpoint
+    /// of the input graph.
+    phase: Option<PreparationPhase>,
+
+    /// fingerprint of the serialized input graph
+    input_fingerprint: u64,
+
+    /// fingerprint of `payload`
+    payload_fingerprint: u64,
+
+    /// the bincode-serialized output of the phase
+    payload: Vec<u8>,
+}
+
+/// The input graph in a deterministic order.
+#[derive(Serialize, Deserialize)]
+struct CheckpointedInput<W> {
+    edges: Vec<(H3DirectedEdge, W)>,
+    h3_resolution: u8,
+    min_longedge_length: usize,
+}
+
+/// 64bit FNV-1a hash. In contrast to the hashers of the std library the result is guaranteed
+/// to be stable across releases of rust.
+fn fingerprint(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+struct CheckpointDir<'a> {
+    path: &'a Path,
+    input_fingerprint: u64,
+}
+
+impl<'a> CheckpointDir<'a> {
+    fn file_path(&self, phase: Option<PreparationPhase>) -> PathBuf {
+        self.path
+            .join(phase.map_or(INPUT_FILE_NAME, |phase| phase.file_name()))
+    }
+
+    fn write<T: Serialize>(&self, phase: Option<PreparationPhase>, value: &T) -> Result<(), Error> {
+        write_checkpoint(&self.file_path(phase), phase, self.input_fingerprint, value)
+    }
+
+    /// read the checkpoint of `phase` - when it exists.
+    fn read<T: DeserializeOwned>(&self, phase: PreparationPhase) -> Result<Option<T>, Error> {
+        let file_path = self.file_path(Some(phase));
+        if !file_path.exists() {
+            return Ok(None);
+        }
+        let checkpoint = read_checkpoint(&file_path)?;
+        if checkpoint.phase != Some(phase) {
+            return Err(Error::InvalidCheckpoint(format!(
+                "{:?} contains the checkpoint of phase {:?}",
+                file_path, checkpoint.phase
+            )));
+        }
+        if checkpoint.input_fingerprint != self.input_fingerprint {
+            return Err(Error::StaleCheckpoint);
+        }
+        Ok(Some(bincode::deserialize(&checkpoint.payload)?))
+    }
+}
+
+fn write_checkpoint<T: Serialize>(
+    file_path: &Path,
+    phase: Option<PreparationPhase>,
+    input_fingerprint: u64,
+    value: &T,
+) -> Result<(), Error> {
+    let payload = bincode::serialize(value)?;
+    let checkpoint = PreparationCheckpoint {
+        format_version: CHECKPOINT_FORMAT_VERSION,
+        crate_version: CRATE_VERSION.to_string(),
+        phase,
+        input_fingerprint,
+        payload_fingerprint: fingerprint(&payload),
+        payload,
+    };
+
+    // write to a temporary file first to never leave a partially written checkpoint behind
+    let tmp_path = file_path.with_extension("tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serialize_into(&mut writer, &checkpoint, true)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+    }
+    std::fs::rename(tmp_path, file_path)?;
+    Ok(())
+}
+
+/// read a checkpoint file and validate its version and integrity.
+fn read_checkpoint(file_path: &Path) -> Result<PreparationCheckpoint, Error> {
+    let checkpoint: PreparationCheckpoint =
+        deserialize_from(BufReader::new(File::open(file_path)?))?;
+    if checkpoint.format_version != CHECKPOINT_FORMAT_VERSION
+        || checkpoint.crate_version != CRATE_VERSION
+    {
+        return Err(Error::InvalidCheckpoint(format!(
+            "{:?} has been created by h3ron-graph {} (format version {}), expected h3ron-graph {} (format version {})",
+            file_path,
+            checkpoint.crate_version,
+            checkpoint.format_version,
+            CRATE_VERSION,
+            CHECKPOINT_FORMAT_VERSION
+        )));
+    }
+    if fingerprint(&checkpoint.payload) != checkpoint.payload_fingerprint {
+        return Err(Error::InvalidCheckpoint(format!(
+            "the contents of {:?} are corrupted",
+            file_path
+        )));
+    }
+    Ok(checkpoint)
+}
+
+/// Prepare `graph` like [`PreparedH3EdgeGraph::from_h3edge_graph`] while writing checkpoints
+/// to `checkpoint_dir`.
+///
+/// When `checkpoint_dir` already contains checkpoints for the same input graph and
+/// `min_longedge_length`, the preparation continues after the last completed phase. Fails with
+/// [`Error::StaleCheckpoint`] when the existing checkpoints have been created for a different
+/// input.
+pub fn prepare_with_checkpoints<W>(
+    graph: H3EdgeGraph<W>,
+    min_longedge_length: usize,
+    checkpoint_dir: &Path,
+) -> Result<PreparedH3EdgeGraph<W>, Error>
+where
+    W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync + Serialize + DeserializeOwned,
+{
+    prepare_with_checkpoints_and_hook(graph, min_longedge_length, checkpoint_dir, &|_| Ok(()))
+}
+
+/// Continue the preparation of the graph stored in the checkpoints in `checkpoint_dir`.
+///
+/// The directory must have been populated by [`prepare_with_checkpoints`].
+pub fn resume_preparation<W>(checkpoint_dir: &Path) -> Result<PreparedH3EdgeGraph<W>, Error>
+where
+    W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync + Serialize + DeserializeOwned,
+{
+    let input_checkpoint = read_checkpoint(&checkpoint_dir.join(INPUT_FILE_NAME))?;
+    if input_checkpoint.phase.is_some() {
+        return Err(Error::InvalidCheckpoint(format!(
+            "{:?} does not contain the input graph",
+            checkpoint_dir.join(INPUT_FILE_NAME)
+        )));
+    }
+    let input: CheckpointedInput<W> = bincode::deserialize(&input_checkpoint.payload)?;
+    let dir = CheckpointDir {
+        path: checkpoint_dir,
+        input_fingerprint: input_checkpoint.input_fingerprint,
+    };
+    run_phases(input, &dir, &|_| Ok(()))
+}
+
+/// `after_phase` gets called after the checkpoint of each computed phase has been written.
+fn prepare_with_checkpoints_and_hook<W>(
+    graph: H3EdgeGraph<W>,
+    min_longedge_length: usize,
+    checkpoint_dir: &Path,
+    after_phase: &dyn Fn(PreparationPhase) -> Result<(), Error>,
+) -> Result<PreparedH3EdgeGraph<W>, Error>
+where
+    W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync + Serialize + DeserializeOwned,
+{
+    validate_min_longedge_length(min_longedge_length)?;
+    let mut edges: Vec<_> = graph.edges.into_iter().collect();
+    edges.par_sort_unstable_by_key(|(edge, _)| *edge);
+    let input = CheckpointedInput {
+        edges,
+        h3_resolution: graph.h3_resolution,
+        min_longedge_length,
+    };
+    let input_fingerprint = fingerprint(&bincode::serialize(&input)?);
+
+    std::fs::create_dir_all(checkpoint_dir)?;
+    let dir = CheckpointDir {
+        path: checkpoint_dir,
+        input_fingerprint,
+    };
+    let input_path = dir.file_path(None);
+    if input_path.exists() {
+        if read_checkpoint(&input_path)?.input_fingerprint != input_fingerprint {
+            return Err(Error::StaleCheckpoint);
+        }
+    } else {
+        dir.write(None, &input)?;
+    }
+    run_phases(input, &dir, after_phase)
+}
+
+fn run_phases<W>(
+    input: CheckpointedInput<W>,
+    dir: &CheckpointDir,
+    after_phase: &dyn Fn(PreparationPhase) -> Result<(), Error>,
+) -> Result<PreparedH3EdgeGraph<W>, Error>
+where
+    W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync + Serialize + DeserializeOwned,
+{
+    if let Some(prepared) = dir.read(PreparationPhase::Finalization)? {
+        return Ok(prepared);
+    }
+    validate_min_longedge_length(input.min_longedge_length)?;
+    let graph = H3EdgeGraph {
+        edges: input.edges.into_iter().collect(),
+        h3_resolution: input.h3_resolution,
+    };
+
+    let graph_nodes: Vec<(H3Cell, NodeType)> = match dir.read(PreparationPhase::EdgeIndexing)? {
+        Some(graph_nodes) => graph_nodes,
+        None => {
+            let mut graph_nodes: Vec<_> = graph.nodes()?.into_iter().collect();
+            graph_nodes.par_sort_unstable_by_key(|(cell, _)| *cell);
+            dir.write(Some(PreparationPhase::EdgeIndexing), &graph_nodes)?;
+            after_phase(PreparationPhase::EdgeIndexing)?;
+            graph_nodes
+        }
+    };
+
+    let edge_tuples: Vec<(H3Cell, OwnedEdgeTuple<W>)> =
+        match dir.read(PreparationPhase::LongEdgeConstruction)? {
+            Some(edge_tuples) => edge_tuples,
+            None => {
+                let chains: Vec<EdgeChain<W>> = match dir.read(PreparationPhase::ChainDetection)? {
+                    Some(chains) => chains,
+                    None => {
+                        let mut chains = detect_chains(&graph.edges, input.min_longedge_length)?;
+                        chains.par_sort_unstable_by_key(|(edge, _, _)| *edge);
+                        dir.write(Some(PreparationPhase::ChainDetection), &chains)?;
+                        after_phase(PreparationPhase::ChainDetection)?;
+                        chains
+                    }
+                };
+                let edge_tuples = construct_longedges(chains)?;
+                dir.write(Some(PreparationPhase::LongEdgeConstruction), &edge_tuples)?;
+                after_phase(PreparationPhase::LongEdgeConstruction)?;
+                edge_tuples
+            }
+        };
+
+    let prepared = PreparedH3EdgeGraph {
+        outgoing_edges: group_outgoing_edges(edge_tuples),
+        h3_resolution: graph.h3_resolution,
+        graph_nodes: graph_nodes.into_iter().collect(),
+        landmark_fingerprint: OnceLock::new(),
+    };
+    dir.write(Some(PreparationPhase::Finalization), &prepared)?;
+    after_phase(PreparationPhase::Finalization)?;
+    Ok(prepared)
+}
+
+/// Aborts the preparation after a phase to simulate a crash.
+#[cfg(test)]
+struct FailureInjector {
+    fail_after: PreparationPhase,
+}
+
+#[cfg(test)]
+impl FailureInjector {
+    fn check(&self, phase: PreparationPhase) -> Result<(), Error> {
+        if phase == self.fail_after {
+            Err(Error::Other(format!("injected failure after {:?}", phase)))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use geo_types::{Coordinate, LineString};
+
+    use h3ron::H3Cell;
+
+    use crate::error::Error;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use crate::io::checkpoint::{
+        prepare_with_checkpoints, prepare_with_checkpoints_and_hook, read_checkpoint,
+        resume_preparation, FailureInjector, PreparationCheckpoint, PreparationPhase,
+        INPUT_FILE_NAME,
+    };
+    use crate::io::serde_util::serialize_into;
+
+    const PHASES: [PreparationPhase; 4] = [
+        PreparationPhase::EdgeIndexing,
+        PreparationPhase::ChainDetection,
+        PreparationPhase::LongEdgeConstruction,
+        PreparationPhase::Finalization,
+    ];
+
+    fn add_line(graph: &mut H3EdgeGraph<u32>, from: (f64, f64), to: (f64, f64)) {
+        let cells: Vec<H3Cell> = h3ron::line(
+            &LineString::from(vec![Coordinate::from(from), Coordinate::from(to)]),
+            graph.h3_resolution,
+        )
+        .unwrap()
+        .into();
+        for w in cells.windows(2) {
+            graph
+                .add_edge_using_cells_bidirectional(w[0], w[1], 20u32)
+                .unwrap();
+        }
+    }
+
+    /// two crossing lines, resulting in multiple longedges
+    fn build_graph() -> H3EdgeGraph<u32> {
+        let mut graph = H3EdgeGraph::new(8);
+        add_line(&mut graph, (23.3, 12.3), (24.2, 12.2));
+        add_line(&mut graph, (23.7, 11.9), (23.8, 12.7));
+        graph
+    }
+
+    /// the serialized graph with all hashmaps replaced by sorted vecs, as the iteration
+    /// order of the hashmaps differs between instances.
+    fn canonical_bytes(prepared: &PreparedH3EdgeGraph<u32>) -> Vec<u8> {
+        let mut graph_nodes: Vec<_> = prepared.graph_nodes.iter().collect();
+        graph_nodes.sort_unstable_by_key(|(cell, _)| **cell);
+        let mut outgoing_edges: Vec<_> = prepared.outgoing_edges.iter().collect();
+        outgoing_edges.sort_unstable_by_key(|(cell, _)| **cell);
+        bincode::serialize(&(prepared.h3_resolution, graph_nodes, outgoing_edges)).unwrap()
+    }
+
+    fn checkpoint_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "h3ron-graph-checkpoint-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn resume_after_each_phase() {
+        let expected =
+            canonical_bytes(&PreparedH3EdgeGraph::from_h3edge_graph(build_graph(), 4).unwrap());
+        let (_, num_longedges) = PreparedH3EdgeGraph::from_h3edge_graph(build_graph(), 4)
+            .unwrap()
+            .count_edges();
+        assert!(num_longedges > 2);
+
+        for phase in PHASES {
+            let dir = checkpoint_dir(&format!("resume-{:?}", phase));
+            let injector = FailureInjector { fail_after: phase };
+            let result =
+                prepare_with_checkpoints_and_hook(build_graph(), 4, &dir, &|p| injector.check(p));
+            assert!(matches!(result, Err(Error::Other(_))));
+            assert!(dir.join(phase.file_name()).exists());
+
+            let resumed = resume_preparation::<u32>(&dir).unwrap();
+            assert_eq!(canonical_bytes(&resumed), expected);
+
+            // restarting with the same input continues from the checkpoints as well
+            let restarted = prepare_with_checkpoints(build_graph(), 4, &dir).unwrap();
+            assert_eq!(canonical_bytes(&restarted), expected);
+
+            std::fs::remove_dir_all(&dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn reject_stale_checkpoint() {
+        let dir = checkpoint_dir("stale");
+        let injector = FailureInjector {
+            fail_after: PreparationPhase::ChainDetection,
+        };
+        assert!(
+            prepare_with_checkpoints_and_hook(build_graph(), 4, &dir, &|p| injector.check(p))
+                .is_err()
+        );
+
+        let mut changed_graph = build_graph();
+        add_line(&mut changed_graph, (23.5, 12.5), (23.6, 12.6));
+        assert!(matches!(
+            prepare_with_checkpoints(changed_graph, 4, &dir),
+            Err(Error::StaleCheckpoint)
+        ));
+
+        // a different min_longedge_length changes the input as well
+        assert!(matches!(
+            prepare_with_checkpoints(build_graph(), 5, &dir),
+            Err(Error::StaleCheckpoint)
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reject_invalid_checkpoint() {
+        let dir = checkpoint_dir("version");
+        let injector = FailureInjector {
+            fail_after: PreparationPhase::EdgeIndexing,
+        };
+        assert!(
+            prepare_with_checkpoints_and_hook(build_graph(), 4, &dir, &|p| injector.check(p))
+                .is_err()
+        );
+
+        let input_path = dir.join(INPUT_FILE_NAME);
+        let original = read_checkpoint(&input_path).unwrap();
+        // write a modified copy of the original checkpoint
+        let write_input = |modify: &dyn Fn(&mut PreparationCheckpoint)| {
+            let mut checkpoint = PreparationCheckpoint {
+                format_version: original.format_version,
+                crate_version: original.crate_version.clone(),
+                phase: original.phase,
+                input_fingerprint: original.input_fingerprint,
+                payload_fingerprint: original.payload_fingerprint,
+                payload: original.payload.clone(),
+            };
+            modify(&mut checkpoint);
+            let file = std::fs::File::create(&input_path).unwrap();
+            serialize_into(file, &checkpoint, true).unwrap();
+        };
+
+        write_input(&|checkpoint| checkpoint.crate_version = "0.0.1".to_string());
+        match read_checkpoint(&input_path) {
+            Err(Error::InvalidCheckpoint(msg)) => assert!(msg.contains("0.0.1"), "{}", msg),
+            _ => panic!("expected the version to be rejected"),
+        }
+        assert!(matches!(
+            resume_preparation::<u32>(&dir),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+
+        write_input(&|checkpoint| checkpoint.format_version += 1);
+        assert!(matches!(
+            read_checkpoint(&input_path),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+
+        // corrupted payload
+        write_input(&|checkpoint| checkpoint.payload[10] ^= 0xff);
+        match read_checkpoint(&input_path) {
+            Err(Error::InvalidCheckpoint(msg)) => assert!(msg.contains("corrupted"), "{}", msg),
+            _ => panic!("expected the corrupted payload to be rejected"),
+        }
+        assert!(matches!(
+            resume_preparation::<u32>(&dir),
+            Err(Error::InvalidCheckpoint(_))
+        ));
+
+        // the unmodified checkpoint is accepted
+        write_input(&|_| ());
+        assert!(read_checkpoint(&input_path).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
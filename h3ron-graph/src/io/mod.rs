@@ -1,3 +1,5 @@
+#[cfg(feature = "io_serde_util")]
+pub mod checkpoint;
 #[cfg(feature = "io_gdal")]
 pub mod gdal;
 #[cfg(feature = "io_osm")]
@@ -14,6 +14,8 @@ use h3ron::H3DirectedEdge;
 
 use crate::error::Error;
 use crate::graph::{H3EdgeGraph, H3EdgeGraphBuilder};
+#[cfg(feature = "expr-weights")]
+use crate::weight_expr::{CompiledWeightExpr, EdgeFields, FieldType, FieldValue};
 
 /// hide errors in the io error to avoid having osmpbfreader in the public api.
 impl From<osmpbfreader::Error> for Error {
@@ -41,6 +43,163 @@ pub trait WayAnalyzer<T> {
     ) -> Result<EdgeProperties<T>, Error>;
 }
 
+/// The field of a weight expression receiving the length of the edge in meters.
+#[cfg(feature = "expr-weights")]
+pub const EDGE_LENGTH_FIELD: &str = "edge_length_m";
+
+/// A [`WayAnalyzer`] computing the weights of the edges using a [`CompiledWeightExpr`].
+///
+/// The fields of the expression are read from the tags of the way - numeric fields get parsed from
+/// the tag value, boolean fields accept `yes`/`no`, `true`/`false` and `1`/`0`. The numeric field
+/// named [`EDGE_LENGTH_FIELD`] receives the length of each edge instead.
+///
+/// Ways lacking one of the required tags or a tag used by the expression are skipped, as well as ways
+/// with tag values which can not be parsed. Ways are bidirectional unless tagged with `oneway=yes`.
+#[cfg(feature = "expr-weights")]
+pub struct ExprWayAnalyzer {
+    expr: CompiledWeightExpr,
+    required_tags: Vec<String>,
+    edge_length_index: Option<usize>,
+}
+
+#[cfg(feature = "expr-weights")]
+impl ExprWayAnalyzer {
+    pub fn new(expr: CompiledWeightExpr) -> Self {
+        let edge_length_index = expr.schema().index_of(EDGE_LENGTH_FIELD);
+        Self {
+            expr,
+            required_tags: vec![],
+            edge_length_index,
+        }
+    }
+
+    /// Only use ways having the tag `tag`, for example `highway`.
+    pub fn require_tag<S: AsRef<str>>(mut self, tag: S) -> Self {
+        self.required_tags.push(tag.as_ref().to_string());
+        self
+    }
+}
+
+#[cfg(feature = "expr-weights")]
+#[derive(Debug, Clone, PartialEq)]
+enum WayFieldValue {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Missing,
+}
+
+/// The tag values of a way used by an [`ExprWayAnalyzer`].
+#[cfg(feature = "expr-weights")]
+pub struct ExprWayProperties {
+    values: Vec<WayFieldValue>,
+    is_bidirectional: bool,
+}
+
+#[cfg(feature = "expr-weights")]
+struct WayEdgeFields<'a> {
+    values: &'a [WayFieldValue],
+    edge_length_index: Option<usize>,
+    edge_length_m: f64,
+}
+
+#[cfg(feature = "expr-weights")]
+impl<'a> EdgeFields for WayEdgeFields<'a> {
+    fn field_value(&self, index: usize) -> FieldValue<'_> {
+        if self.edge_length_index == Some(index) {
+            return FieldValue::Number(self.edge_length_m);
+        }
+        match self.values.get(index) {
+            Some(WayFieldValue::Number(n)) => FieldValue::Number(*n),
+            Some(WayFieldValue::String(s)) => FieldValue::String(s.as_str()),
+            Some(WayFieldValue::Bool(b)) => FieldValue::Bool(*b),
+            Some(WayFieldValue::Missing) | None => FieldValue::Missing,
+        }
+    }
+}
+
+#[cfg(feature = "expr-weights")]
+impl<T> WayAnalyzer<T> for ExprWayAnalyzer
+where
+    T: From<f64>,
+{
+    type WayProperties = ExprWayProperties;
+
+    fn analyze_way_tags(&self, tags: &Tags) -> Result<Option<Self::WayProperties>, Error> {
+        if !self
+            .required_tags
+            .iter()
+            .all(|tag| tags.get(tag.as_str()).is_some())
+        {
+            return Ok(None);
+        }
+
+        let referenced_fields = self.expr.referenced_fields();
+        let mut values = Vec::with_capacity(self.expr.schema().len());
+        for (index, (name, field_type)) in self.expr.schema().iter().enumerate() {
+            if Some(index) == self.edge_length_index
+                || referenced_fields.binary_search(&index).is_err()
+            {
+                values.push(WayFieldValue::Missing);
+                continue;
+            }
+            let tag_value = match tags.get(name) {
+                Some(tag_value) => tag_value.trim(),
+                None => return Ok(None),
+            };
+            let value = match field_type {
+                FieldType::Number => match tag_value.parse::<f64>() {
+                    Ok(n) => WayFieldValue::Number(n),
+                    Err(_) => return Ok(None),
+                },
+                FieldType::String => WayFieldValue::String(tag_value.to_string()),
+                FieldType::Bool => match tag_value {
+                    "yes" | "true" | "1" => WayFieldValue::Bool(true),
+                    "no" | "false" | "0" => WayFieldValue::Bool(false),
+                    _ => return Ok(None),
+                },
+            };
+            values.push(value);
+        }
+
+        let is_bidirectional = tags
+            .get("oneway")
+            .map(|v| v.to_lowercase() != "yes")
+            .unwrap_or(true);
+        Ok(Some(ExprWayProperties {
+            values,
+            is_bidirectional,
+        }))
+    }
+
+    fn way_edge_properties(
+        &self,
+        edge: H3DirectedEdge,
+        way_properties: &Self::WayProperties,
+    ) -> Result<EdgeProperties<T>, Error> {
+        let edge_length_m = if self.edge_length_index.is_some() {
+            edge.length_m()?
+        } else {
+            0.0
+        };
+        let weight = self.expr.evaluate(&WayEdgeFields {
+            values: &way_properties.values,
+            edge_length_index: self.edge_length_index,
+            edge_length_m,
+        })?;
+        if !weight.is_finite() || weight < 0.0 {
+            return Err(Error::Other(format!(
+                "weight expression resulted in the invalid weight {}",
+                weight
+            )));
+        }
+        Ok(EdgeProperties {
+            is_bidirectional: way_properties.is_bidirectional,
+            weight: T::from(weight),
+        })
+    }
+}
+
 /// Builds [`H3EdgeGraph`] instances from .osm.pbf files.
 pub struct OsmPbfH3EdgeGraphBuilder<
     T: PartialOrd + PartialEq + Add + Copy + Sync + Send,
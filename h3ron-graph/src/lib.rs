@@ -2,7 +2,9 @@
 //!
 //! * **io_gdal**: Writing of graphs to GDAL OGR datasets.
 //! * **io_osm**: Enables parsing of OpenStreetMap files.
-//! * **io_serde_util**: Convenience serialization helpers.
+//! * **io_serde_util**: Convenience serialization helpers and checkpointed preparation of graphs.
+//! * **expr-weights**: Edge weights computed from expressions compiled at runtime. Used by the
+//!   `ExprWayAnalyzer` for OpenStreetMap files when combined with **io_osm**.
 
 #![warn(
     clippy::all,
@@ -18,5 +20,7 @@ pub mod algorithm;
 pub mod error;
 pub mod graph;
 pub mod io;
+#[cfg(feature = "expr-weights")]
+pub mod weight_expr;
 
 pub use crate::error::Error;
@@ -0,0 +1,1057 @@
+//! Edge weights computed from user-supplied expressions.
+//!
+//! The expression language is deliberately small and has no access to anything besides the
+//! fields of the edge it gets evaluated for:
+//!
+//! * number, string (`"gravel"` or `'gravel'`) and boolean (`true`, `false`) literals
+//! * fields declared in a [`WeightExprSchema`]
+//! * arithmetic: `+`, `-`, `*`, `/`, `%` and unary `-`
+//! * comparisons: `==` and `!=` for values of the same type, `<`, `<=`, `>` and `>=` for numbers
+//! * boolean logic: `and`, `or`, `not`
+//! * conditionals: `a if condition else b`
+//! * the functions `min(a, b, ...)`, `max(a, b, ...)` and `abs(a)`
+//!
+//! Expressions are parsed and type-checked once against the schema by [`CompiledWeightExpr::compile`].
+//! The evaluation does not allocate.
+//!
+//! # Example
+//!
+//! ```
+//! use h3ron_graph::weight_expr::{CompiledWeightExpr, FieldValue, WeightExprSchema};
+//!
+//! let schema = WeightExprSchema::new()
+//!     .number("length_m")
+//!     .number("speed_kmh")
+//!     .string("surface");
+//! let expr = CompiledWeightExpr::compile(
+//!     r#"length_m / speed_kmh * (1.2 if surface == "gravel" else 1.0)"#,
+//!     &schema,
+//! )
+//! .unwrap();
+//!
+//! let weight = expr
+//!     .evaluate(&[
+//!         FieldValue::Number(100.0),
+//!         FieldValue::Number(50.0),
+//!         FieldValue::String("gravel"),
+//!     ][..])
+//!     .unwrap();
+//! assert!((weight - 2.4).abs() < 1e-9);
+//! ```
+use std::fmt;
+
+use crate::error::Error;
+
+/// maximum nesting of an expression. Protects the parser and the evaluation from overflowing
+/// the stack.
+const MAX_DEPTH: usize = 128;
+
+/// The type of a field or of a part of an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Number,
+    String,
+    Bool,
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Bool => "bool",
+        })
+    }
+}
+
+/// The fields available to an expression. The position of a field in the schema is the index
+/// used to look up its value in [`EdgeFields`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WeightExprSchema {
+    fields: Vec<(String, FieldType)>,
+}
+
+impl WeightExprSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a field of the given type.
+    pub fn field<S: AsRef<str>>(mut self, name: S, field_type: FieldType) -> Self {
+        self.fields.push((name.as_ref().to_string(), field_type));
+        self
+    }
+
+    /// Add a numeric field.
+    pub fn number<S: AsRef<str>>(self, name: S) -> Self {
+        self.field(name, FieldType::Number)
+    }
+
+    /// Add a string field.
+    pub fn string<S: AsRef<str>>(self, name: S) -> Self {
+        self.field(name, FieldType::String)
+    }
+
+    /// The index of the field named `name`.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.fields
+            .iter()
+            .position(|(field_name, _)| field_name == name)
+    }
+
+    /// Iterate over the names and types of the fields.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, FieldType)> {
+        self.fields
+            .iter()
+            .map(|(name, field_type)| (name.as_str(), *field_type))
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+/// The value of a field of an edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldValue<'a> {
+    Number(f64),
+    String(&'a str),
+    Bool(bool),
+
+    /// the edge has no value for the field
+    Missing,
+}
+
+/// The field values of a single edge an expression gets evaluated for.
+pub trait EdgeFields {
+    /// The value of the field at position `index` of the schema.
+    fn field_value(&self, index: usize) -> FieldValue<'_>;
+}
+
+impl<'a> EdgeFields for [FieldValue<'a>] {
+    fn field_value(&self, index: usize) -> FieldValue<'_> {
+        self.get(index).copied().unwrap_or(FieldValue::Missing)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Min,
+    Max,
+    Abs,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Field(usize),
+    Neg(usize),
+    Not(usize),
+    And(usize, usize),
+    Or(usize, usize),
+    Binary(BinaryOp, usize, usize),
+    Conditional {
+        condition: usize,
+        then: usize,
+        otherwise: usize,
+    },
+    Call(Function, Vec<usize>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Value<'a> {
+    Number(f64),
+    String(&'a str),
+    Bool(bool),
+}
+
+impl<'a> Value<'a> {
+    // the types are ensured by the type check of the compilation, the fallbacks are never reached.
+    fn number(self) -> f64 {
+        match self {
+            Self::Number(n) => n,
+            _ => f64::NAN,
+        }
+    }
+
+    fn bool(self) -> bool {
+        matches!(self, Self::Bool(true))
+    }
+}
+
+/// An expression compiled against a [`WeightExprSchema`] to compute the weight of an edge.
+#[derive(Debug, Clone)]
+pub struct CompiledWeightExpr {
+    nodes: Vec<Node>,
+    root: usize,
+    schema: WeightExprSchema,
+    referenced_fields: Vec<usize>,
+}
+
+impl CompiledWeightExpr {
+    /// Parse and type-check `source` against `schema`. The expression must evaluate to a number.
+    ///
+    /// Syntax errors, type errors and references to fields missing in the schema fail with
+    /// [`Error::InvalidWeightExpression`] containing the byte offset of the offending
+    /// part of `source`.
+    pub fn compile(source: &str, schema: &WeightExprSchema) -> Result<Self, Error> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens: &tokens,
+            pos: 0,
+            depth: 0,
+            nodes: vec![],
+            node_depths: vec![],
+            schema,
+        };
+        let (root, root_type) = parser.parse_expression()?;
+        let token = parser.peek();
+        if token.kind != TokenKind::End {
+            return Err(invalid(token.position, "unexpected token"));
+        }
+        if root_type != FieldType::Number {
+            return Err(invalid(
+                0,
+                format!(
+                    "the expression must evaluate to a number, found {}",
+                    root_type
+                ),
+            ));
+        }
+
+        let mut referenced_fields: Vec<_> = parser
+            .nodes
+            .iter()
+            .filter_map(|node| match node {
+                Node::Field(index) => Some(*index),
+                _ => None,
+            })
+            .collect();
+        referenced_fields.sort_unstable();
+        referenced_fields.dedup();
+
+        Ok(Self {
+            nodes: parser.nodes,
+            root,
+            schema: schema.clone(),
+            referenced_fields,
+        })
+    }
+
+    /// The schema the expression has been compiled against.
+    pub fn schema(&self) -> &WeightExprSchema {
+        &self.schema
+    }
+
+    /// The schema indexes of the fields used by the expression, in ascending order.
+    pub fn referenced_fields(&self) -> &[usize] {
+        &self.referenced_fields
+    }
+
+    /// Evaluate the expression for the fields of an edge.
+    ///
+    /// Fields used by the expression which are missing or have a type other than declared in
+    /// the schema fail with [`Error::InvalidFieldValue`].
+    pub fn evaluate<F>(&self, fields: &F) -> Result<f64, Error>
+    where
+        F: EdgeFields + ?Sized,
+    {
+        Ok(self.eval(self.root, fields)?.number())
+    }
+
+    fn eval<'a, F>(&'a self, node: usize, fields: &'a F) -> Result<Value<'a>, Error>
+    where
+        F: EdgeFields + ?Sized,
+    {
+        let value = match &self.nodes[node] {
+            Node::Number(n) => Value::Number(*n),
+            Node::String(s) => Value::String(s.as_str()),
+            Node::Bool(b) => Value::Bool(*b),
+            Node::Field(index) => {
+                match (fields.field_value(*index), self.schema.fields[*index].1) {
+                    (FieldValue::Number(n), FieldType::Number) => Value::Number(n),
+                    (FieldValue::String(s), FieldType::String) => Value::String(s),
+                    (FieldValue::Bool(b), FieldType::Bool) => Value::Bool(b),
+                    _ => {
+                        return Err(Error::InvalidFieldValue(
+                            self.schema.fields[*index].0.clone(),
+                        ))
+                    }
+                }
+            }
+            Node::Neg(operand) => Value::Number(-self.eval(*operand, fields)?.number()),
+            Node::Not(operand) => Value::Bool(!self.eval(*operand, fields)?.bool()),
+            Node::And(left, right) => {
+                Value::Bool(self.eval(*left, fields)?.bool() && self.eval(*right, fields)?.bool())
+            }
+            Node::Or(left, right) => {
+                Value::Bool(self.eval(*left, fields)?.bool() || self.eval(*right, fields)?.bool())
+            }
+            Node::Binary(op, left, right) => {
+                let left = self.eval(*left, fields)?;
+                let right = self.eval(*right, fields)?;
+                match op {
+                    BinaryOp::Add => Value::Number(left.number() + right.number()),
+                    BinaryOp::Sub => Value::Number(left.number() - right.number()),
+                    BinaryOp::Mul => Value::Number(left.number() * right.number()),
+                    BinaryOp::Div => Value::Number(left.number() / right.number()),
+                    BinaryOp::Rem => Value::Number(left.number() % right.number()),
+                    BinaryOp::Eq => Value::Bool(values_equal(left, right)),
+                    BinaryOp::Ne => Value::Bool(!values_equal(left, right)),
+                    BinaryOp::Lt => Value::Bool(left.number() < right.number()),
+                    BinaryOp::Le => Value::Bool(left.number() <= right.number()),
+                    BinaryOp::Gt => Value::Bool(left.number() > right.number()),
+                    BinaryOp::Ge => Value::Bool(left.number() >= right.number()),
+                }
+            }
+            Node::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                if self.eval(*condition, fields)?.bool() {
+                    self.eval(*then, fields)?
+                } else {
+                    self.eval(*otherwise, fields)?
+                }
+            }
+            Node::Call(function, args) => {
+                let mut result = self.eval(args[0], fields)?.number();
+                for arg in &args[1..] {
+                    let value = self.eval(*arg, fields)?.number();
+                    result = match function {
+                        Function::Min => result.min(value),
+                        Function::Max => result.max(value),
+                        Function::Abs => result,
+                    };
+                }
+                if *function == Function::Abs {
+                    result = result.abs();
+                }
+                Value::Number(result)
+            }
+        };
+        Ok(value)
+    }
+}
+
+fn values_equal(left: Value, right: Value) -> bool {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l == r,
+        (Value::String(l), Value::String(r)) => l == r,
+        (Value::Bool(l), Value::Bool(r)) => l == r,
+        _ => false,
+    }
+}
+
+fn invalid<S: Into<String>>(position: usize, message: S) -> Error {
+    Error::InvalidWeightExpression(position, message.into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Number(f64),
+    String(String),
+    Ident(String),
+    LParen,
+    RParen,
+    Comma,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    End,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut chars = source.char_indices().peekable();
+    while let Some((position, c)) = chars.next() {
+        let kind = match c {
+            c if c.is_whitespace() => continue,
+            '(' => TokenKind::LParen,
+            ')' => TokenKind::RParen,
+            ',' => TokenKind::Comma,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            '=' | '!' | '<' | '>' => {
+                let followed_by_eq = chars.next_if(|(_, next)| *next == '=').is_some();
+                match (c, followed_by_eq) {
+                    ('=', true) => TokenKind::Eq,
+                    ('!', true) => TokenKind::Ne,
+                    ('<', false) => TokenKind::Lt,
+                    ('<', true) => TokenKind::Le,
+                    ('>', false) => TokenKind::Gt,
+                    ('>', true) => TokenKind::Ge,
+                    _ => return Err(invalid(position, format!("unexpected character `{}`", c))),
+                }
+            }
+            '"' | '\'' => {
+                let start = position + c.len_utf8();
+                let end = loop {
+                    match chars.next() {
+                        Some((end, next)) if next == c => break end,
+                        Some(_) => continue,
+                        None => return Err(invalid(position, "unterminated string")),
+                    }
+                };
+                TokenKind::String(source[start..end].to_string())
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let mut end = position + c.len_utf8();
+                let mut previous = c;
+                while let Some((next_position, next)) = chars.next_if(|(_, next)| {
+                    next.is_ascii_digit()
+                        || *next == '.'
+                        || *next == 'e'
+                        || *next == 'E'
+                        || ((*next == '+' || *next == '-') && (previous == 'e' || previous == 'E'))
+                }) {
+                    end = next_position + next.len_utf8();
+                    previous = next;
+                }
+                let number = source[position..end]
+                    .parse::<f64>()
+                    .map_err(|_| invalid(position, "invalid number"))?;
+                TokenKind::Number(number)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut end = position + c.len_utf8();
+                while let Some((next_position, next)) =
+                    chars.next_if(|(_, next)| next.is_alphanumeric() || *next == '_')
+                {
+                    end = next_position + next.len_utf8();
+                }
+                TokenKind::Ident(source[position..end].to_string())
+            }
+            _ => return Err(invalid(position, format!("unexpected character `{}`", c))),
+        };
+        tokens.push(Token { kind, position });
+    }
+    tokens.push(Token {
+        kind: TokenKind::End,
+        position: source.len(),
+    });
+    Ok(tokens)
+}
+
+/// Recursive descent parser building the nodes of the expression while checking the types.
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    depth: usize,
+    nodes: Vec<Node>,
+    node_depths: Vec<usize>,
+    schema: &'a WeightExprSchema,
+}
+
+type Typed = (usize, FieldType);
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> &'a Token {
+        // the tokens always end with `TokenKind::End`
+        &self.tokens[self.pos.min(self.tokens.len() - 1)]
+    }
+
+    fn next(&mut self) -> &'a Token {
+        let token = self.peek();
+        if token.kind != TokenKind::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn next_is_keyword(&self, keyword: &str) -> bool {
+        matches!(&self.peek().kind, TokenKind::Ident(ident) if ident == keyword)
+    }
+
+    fn push(&mut self, node: Node, position: usize) -> Result<usize, Error> {
+        let child_depth = match &node {
+            Node::Number(_) | Node::String(_) | Node::Bool(_) | Node::Field(_) => 0,
+            Node::Neg(operand) | Node::Not(operand) => self.node_depths[*operand],
+            Node::And(left, right) | Node::Or(left, right) | Node::Binary(_, left, right) => {
+                self.node_depths[*left].max(self.node_depths[*right])
+            }
+            Node::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => self.node_depths[*condition]
+                .max(self.node_depths[*then])
+                .max(self.node_depths[*otherwise]),
+            Node::Call(_, args) => args
+                .iter()
+                .map(|arg| self.node_depths[*arg])
+                .max()
+                .unwrap_or(0),
+        };
+        if child_depth >= MAX_DEPTH {
+            return Err(invalid(position, "the expression is nested too deeply"));
+        }
+        self.nodes.push(node);
+        self.node_depths.push(child_depth + 1);
+        Ok(self.nodes.len() - 1)
+    }
+
+    /// Guards the recursion of the parser.
+    fn descend<T>(
+        &mut self,
+        parse_fn: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(invalid(
+                self.peek().position,
+                "the expression is nested too deeply",
+            ));
+        }
+        self.depth += 1;
+        let result = parse_fn(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expect_type(
+        &self,
+        (_, found): Typed,
+        expected: FieldType,
+        position: usize,
+    ) -> Result<(), Error> {
+        if found == expected {
+            Ok(())
+        } else {
+            Err(invalid(
+                position,
+                format!("expected {}, found {}", expected, found),
+            ))
+        }
+    }
+
+    /// `or_expr [ "if" or_expr "else" expression ]`
+    fn parse_expression(&mut self) -> Result<Typed, Error> {
+        self.descend(|parser| {
+            let position = parser.peek().position;
+            let then = parser.parse_or()?;
+            if !parser.next_is_keyword("if") {
+                return Ok(then);
+            }
+            parser.next();
+            let condition_position = parser.peek().position;
+            let condition = parser.parse_or()?;
+            parser.expect_type(condition, FieldType::Bool, condition_position)?;
+
+            let token = parser.next();
+            if !matches!(&token.kind, TokenKind::Ident(ident) if ident == "else") {
+                return Err(invalid(token.position, "expected `else`"));
+            }
+            let otherwise_position = parser.peek().position;
+            let otherwise = parser.parse_expression()?;
+            parser.expect_type(otherwise, then.1, otherwise_position)?;
+            let node = parser.push(
+                Node::Conditional {
+                    condition: condition.0,
+                    then: then.0,
+                    otherwise: otherwise.0,
+                },
+                position,
+            )?;
+            Ok((node, then.1))
+        })
+    }
+
+    fn parse_or(&mut self) -> Result<Typed, Error> {
+        let position = self.peek().position;
+        let mut left = self.parse_and()?;
+        while self.next_is_keyword("or") {
+            self.expect_type(left, FieldType::Bool, position)?;
+            self.next();
+            let right_position = self.peek().position;
+            let right = self.parse_and()?;
+            self.expect_type(right, FieldType::Bool, right_position)?;
+            left = (
+                self.push(Node::Or(left.0, right.0), position)?,
+                FieldType::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Typed, Error> {
+        let position = self.peek().position;
+        let mut left = self.parse_not()?;
+        while self.next_is_keyword("and") {
+            self.expect_type(left, FieldType::Bool, position)?;
+            self.next();
+            let right_position = self.peek().position;
+            let right = self.parse_not()?;
+            self.expect_type(right, FieldType::Bool, right_position)?;
+            left = (
+                self.push(Node::And(left.0, right.0), position)?,
+                FieldType::Bool,
+            );
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Typed, Error> {
+        if !self.next_is_keyword("not") {
+            return self.parse_comparison();
+        }
+        let position = self.next().position;
+        self.descend(|parser| {
+            let operand_position = parser.peek().position;
+            let operand = parser.parse_not()?;
+            parser.expect_type(operand, FieldType::Bool, operand_position)?;
+            Ok((
+                parser.push(Node::Not(operand.0), position)?,
+                FieldType::Bool,
+            ))
+        })
+    }
+
+    fn parse_comparison(&mut self) -> Result<Typed, Error> {
+        let position = self.peek().position;
+        let left = self.parse_additive()?;
+        let op_token = self.peek();
+        let op = match op_token.kind {
+            TokenKind::Eq => BinaryOp::Eq,
+            TokenKind::Ne => BinaryOp::Ne,
+            TokenKind::Lt => BinaryOp::Lt,
+            TokenKind::Le => BinaryOp::Le,
+            TokenKind::Gt => BinaryOp::Gt,
+            TokenKind::Ge => BinaryOp::Ge,
+            _ => return Ok(left),
+        };
+        self.next();
+        let right_position = self.peek().position;
+        let right = self.parse_additive()?;
+        if matches!(op, BinaryOp::Eq | BinaryOp::Ne) {
+            self.expect_type(right, left.1, right_position)?;
+        } else {
+            self.expect_type(left, FieldType::Number, position)?;
+            self.expect_type(right, FieldType::Number, right_position)?;
+        }
+        Ok((
+            self.push(Node::Binary(op, left.0, right.0), op_token.position)?,
+            FieldType::Bool,
+        ))
+    }
+
+    fn parse_additive(&mut self) -> Result<Typed, Error> {
+        let position = self.peek().position;
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Sub,
+                _ => return Ok(left),
+            };
+            self.expect_type(left, FieldType::Number, position)?;
+            self.next();
+            let right_position = self.peek().position;
+            let right = self.parse_multiplicative()?;
+            self.expect_type(right, FieldType::Number, right_position)?;
+            left = (
+                self.push(Node::Binary(op, left.0, right.0), position)?,
+                FieldType::Number,
+            );
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Typed, Error> {
+        let position = self.peek().position;
+        let mut left = self.parse_unary()?;
+        loop {
+            let op = match self.peek().kind {
+                TokenKind::Star => BinaryOp::Mul,
+                TokenKind::Slash => BinaryOp::Div,
+                TokenKind::Percent => BinaryOp::Rem,
+                _ => return Ok(left),
+            };
+            self.expect_type(left, FieldType::Number, position)?;
+            self.next();
+            let right_position = self.peek().position;
+            let right = self.parse_unary()?;
+            self.expect_type(right, FieldType::Number, right_position)?;
+            left = (
+                self.push(Node::Binary(op, left.0, right.0), position)?,
+                FieldType::Number,
+            );
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Typed, Error> {
+        if self.peek().kind != TokenKind::Minus {
+            return self.parse_primary();
+        }
+        let position = self.next().position;
+        self.descend(|parser| {
+            let operand_position = parser.peek().position;
+            let operand = parser.parse_unary()?;
+            parser.expect_type(operand, FieldType::Number, operand_position)?;
+            Ok((
+                parser.push(Node::Neg(operand.0), position)?,
+                FieldType::Number,
+            ))
+        })
+    }
+
+    fn parse_primary(&mut self) -> Result<Typed, Error> {
+        let token = self.next();
+        let position = token.position;
+        match &token.kind {
+            TokenKind::Number(n) => Ok((self.push(Node::Number(*n), position)?, FieldType::Number)),
+            TokenKind::String(s) => Ok((
+                self.push(Node::String(s.clone()), position)?,
+                FieldType::String,
+            )),
+            TokenKind::LParen => {
+                let inner = self.parse_expression()?;
+                let closing = self.next();
+                if closing.kind != TokenKind::RParen {
+                    return Err(invalid(closing.position, "expected `)`"));
+                }
+                Ok(inner)
+            }
+            TokenKind::Ident(ident) => match ident.as_str() {
+                "true" => Ok((self.push(Node::Bool(true), position)?, FieldType::Bool)),
+                "false" => Ok((self.push(Node::Bool(false), position)?, FieldType::Bool)),
+                "if" | "else" | "and" | "or" | "not" => {
+                    Err(invalid(position, format!("unexpected keyword `{}`", ident)))
+                }
+                _ if self.peek().kind == TokenKind::LParen => self.parse_call(ident, position),
+                _ => match self.schema.index_of(ident) {
+                    Some(index) => Ok((
+                        self.push(Node::Field(index), position)?,
+                        self.schema.fields[index].1,
+                    )),
+                    None => Err(invalid(position, format!("unknown field `{}`", ident))),
+                },
+            },
+            TokenKind::End => Err(invalid(position, "unexpected end of the expression")),
+            _ => Err(invalid(position, "unexpected token")),
+        }
+    }
+
+    fn parse_call(&mut self, name: &str, position: usize) -> Result<Typed, Error> {
+        let (function, min_args, max_args) = match name {
+            "min" => (Function::Min, 1, usize::MAX),
+            "max" => (Function::Max, 1, usize::MAX),
+            "abs" => (Function::Abs, 1, 1),
+            _ => return Err(invalid(position, format!("unknown function `{}`", name))),
+        };
+        self.next(); // the opening parenthesis
+
+        let mut args = vec![];
+        if self.peek().kind != TokenKind::RParen {
+            loop {
+                let arg_position = self.peek().position;
+                let arg = self.parse_expression()?;
+                self.expect_type(arg, FieldType::Number, arg_position)?;
+                args.push(arg.0);
+                if self.peek().kind != TokenKind::Comma {
+                    break;
+                }
+                self.next();
+            }
+        }
+        let closing = self.next();
+        if closing.kind != TokenKind::RParen {
+            return Err(invalid(closing.position, "expected `)`"));
+        }
+        if args.len() < min_args || args.len() > max_args {
+            return Err(invalid(
+                position,
+                format!(
+                    "wrong number of arguments for `{}`: {} given",
+                    name,
+                    args.len()
+                ),
+            ));
+        }
+        Ok((
+            self.push(Node::Call(function, args), position)?,
+            FieldType::Number,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use h3ron::{H3Cell, H3DirectedEdge};
+
+    use crate::algorithm::landmarks::splitmix64;
+    use crate::error::Error;
+    use crate::graph::H3EdgeGraph;
+
+    use super::{CompiledWeightExpr, FieldValue, WeightExprSchema};
+
+    fn schema() -> WeightExprSchema {
+        WeightExprSchema::new()
+            .number("length_m")
+            .number("speed_kmh")
+            .string("surface")
+    }
+
+    /// the edges around a cell together with their metadata
+    fn fixture_edges() -> Vec<(H3DirectedEdge, f64, f64, &'static str)> {
+        let surfaces = ["asphalt", "gravel", "cobblestone"];
+        H3Cell::from_coordinate((10.0, 20.0).into(), 8)
+            .unwrap()
+            .grid_disk(3)
+            .unwrap()
+            .iter()
+            .flat_map(|cell| cell.directed_edges().unwrap().iter().collect::<Vec<_>>())
+            .enumerate()
+            .map(|(i, edge)| {
+                (
+                    edge,
+                    edge.length_m().unwrap(),
+                    [30.0, 50.0, 80.0, 100.0][i % 4],
+                    surfaces[i % surfaces.len()],
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parity_with_closure() {
+        let expr = CompiledWeightExpr::compile(
+            r#"length_m / speed_kmh * (1.2 if surface == "gravel" else 1.0)
+                + max(0, 5 - speed_kmh / 10) * (2 if not (surface != 'cobblestone') and speed_kmh >= 50 else 0)
+                - -min(length_m % 7, abs(-3))"#,
+            &schema(),
+        )
+        .unwrap();
+        assert_eq!(expr.referenced_fields(), &[0, 1, 2]);
+
+        let closure = |length_m: f64, speed_kmh: f64, surface: &str| {
+            length_m / speed_kmh * (if surface == "gravel" { 1.2 } else { 1.0 })
+                + 0.0_f64.max(5.0 - speed_kmh / 10.0)
+                    * (if surface == "cobblestone" && speed_kmh >= 50.0 {
+                        2.0
+                    } else {
+                        0.0
+                    })
+                + (length_m % 7.0).min(3.0)
+        };
+
+        let mut expr_graph = H3EdgeGraph::new(8);
+        let mut closure_graph = H3EdgeGraph::new(8);
+        for (edge, length_m, speed_kmh, surface) in fixture_edges() {
+            let fields = [
+                FieldValue::Number(length_m),
+                FieldValue::Number(speed_kmh),
+                FieldValue::String(surface),
+            ];
+            expr_graph
+                .add_edge(edge, expr.evaluate(&fields[..]).unwrap())
+                .unwrap();
+            closure_graph
+                .add_edge(edge, closure(length_m, speed_kmh, surface))
+                .unwrap();
+        }
+        assert!(expr_graph.num_edges() > 100);
+        assert_eq!(expr_graph.num_edges(), closure_graph.num_edges());
+        for (edge, weight) in closure_graph.edges.iter() {
+            let expr_weight = expr_graph.edge_weight(edge).unwrap();
+            assert!((expr_weight - weight).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn missing_field_values() {
+        let expr = CompiledWeightExpr::compile("length_m * 2", &schema()).unwrap();
+        assert!(matches!(
+            expr.evaluate(&[FieldValue::Missing][..]),
+            Err(Error::InvalidFieldValue(name)) if name == "length_m"
+        ));
+        assert!(matches!(
+            expr.evaluate(&[FieldValue::String("5")][..]),
+            Err(Error::InvalidFieldValue(_))
+        ));
+        // unused fields do not need to be present
+        assert_eq!(expr.evaluate(&[FieldValue::Number(3.0)][..]).unwrap(), 6.0);
+    }
+
+    fn compile_error(source: &str) -> (usize, String) {
+        match CompiledWeightExpr::compile(source, &schema()) {
+            Err(Error::InvalidWeightExpression(position, message)) => (position, message),
+            other => panic!("expected a compile error for {}, got {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn compile_errors() {
+        assert_eq!(
+            compile_error("length_m / speed"),
+            (11, "unknown field `speed`".to_string())
+        );
+        assert_eq!(
+            compile_error("length_m * surface"),
+            (11, "expected number, found string".to_string())
+        );
+        assert_eq!(
+            compile_error("1 if surface else 2"),
+            (5, "expected bool, found string".to_string())
+        );
+        assert_eq!(
+            compile_error("1 if surface == 'x' else 'y'"),
+            (25, "expected number, found string".to_string())
+        );
+        assert_eq!(
+            compile_error("surface == 'gravel'"),
+            (
+                0,
+                "the expression must evaluate to a number, found bool".to_string()
+            )
+        );
+        assert_eq!(
+            compile_error("(length_m + 1"),
+            (13, "expected `)`".to_string())
+        );
+        assert_eq!(
+            compile_error("length_m * 'abc"),
+            (11, "unterminated string".to_string())
+        );
+        assert_eq!(
+            compile_error("sqrt(length_m)"),
+            (0, "unknown function `sqrt`".to_string())
+        );
+        assert_eq!(
+            compile_error("length_m $ 2"),
+            (9, "unexpected character `$`".to_string())
+        );
+        assert_eq!(
+            compile_error("length_m 2"),
+            (9, "unexpected token".to_string())
+        );
+        assert_eq!(
+            compile_error("length_m +"),
+            (10, "unexpected end of the expression".to_string())
+        );
+
+        let deeply_nested = format!("{}1{}", "(".repeat(1000), ")".repeat(1000));
+        assert_eq!(
+            compile_error(&deeply_nested).1,
+            "the expression is nested too deeply"
+        );
+        let long_chain = vec!["1"; 1000].join(" + ");
+        assert_eq!(
+            compile_error(&long_chain).1,
+            "the expression is nested too deeply"
+        );
+    }
+
+    #[test]
+    fn fuzz_never_panics() {
+        let fragments = [
+            "length_m",
+            "speed_kmh",
+            "surface",
+            "unknown",
+            "1",
+            "2.5",
+            "1e3",
+            ".",
+            "e",
+            "'a'",
+            "\"gravel\"",
+            "'",
+            "\"",
+            "(",
+            ")",
+            ",",
+            "+",
+            "-",
+            "*",
+            "/",
+            "%",
+            "==",
+            "!=",
+            "<",
+            "<=",
+            ">",
+            ">=",
+            "=",
+            "!",
+            "if",
+            "else",
+            "and",
+            "or",
+            "not",
+            "true",
+            "false",
+            "min",
+            "max",
+            "abs",
+            " ",
+            "ä",
+            "0",
+            "1e",
+            "1e+",
+            "_",
+        ];
+        let fields = [
+            FieldValue::Number(12.5),
+            FieldValue::Number(0.0),
+            FieldValue::String("gravel"),
+        ];
+        let mut state = 42_u64;
+        let mut compiled = 0;
+        for _ in 0..50_000 {
+            let num_fragments = (splitmix64(&mut state) % 16) as usize;
+            let source: String = (0..num_fragments)
+                .map(|_| fragments[(splitmix64(&mut state) % fragments.len() as u64) as usize])
+                .collect();
+            if let Ok(expr) = CompiledWeightExpr::compile(&source, &schema()) {
+                compiled += 1;
+                let _ = expr.evaluate(&fields[..]);
+                let _ = expr.evaluate::<[FieldValue]>(&[]);
+            }
+
+            // arbitrary bytes
+            let bytes: Vec<u8> = (0..(splitmix64(&mut state) % 24))
+                .map(|_| splitmix64(&mut state) as u8)
+                .collect();
+            let _ = CompiledWeightExpr::compile(&String::from_utf8_lossy(&bytes), &schema());
+        }
+        assert!(compiled > 100);
+    }
+}
@@ -0,0 +1,86 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use ndarray::{Array2, ArrayView, Ix2};
+
+use h3ron_ndarray::array::H3Converter;
+use h3ron_ndarray::transform::Transform;
+use h3ron_ndarray::AxisOrder;
+
+/// allocator tracking the peak of the allocated bytes
+struct PeakAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for PeakAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let allocated = ALLOCATED.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(allocated, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static GLOBAL: PeakAlloc = PeakAlloc;
+
+/// peak of the bytes allocated by `f` in addition to the already allocated bytes
+fn peak_allocation<F: FnOnce()>(f: F) -> usize {
+    let allocated_before = ALLOCATED.load(Ordering::SeqCst);
+    PEAK.store(allocated_before, Ordering::SeqCst);
+    f();
+    PEAK.load(Ordering::SeqCst) - allocated_before
+}
+
+fn test_array() -> (Array2<u8>, Transform) {
+    let arr = Array2::from_shape_fn((1000, 1000), |(y, x)| ((x / 100 + y / 100) % 4) as u8);
+    let transform = Transform::from_gdal(&[8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135]);
+    (arr, transform)
+}
+
+fn convert(view: &ArrayView<u8, Ix2>, transform: &Transform, h3_resolution: u8, f32_coords: bool) {
+    // a single thread to make the peak allocation reproducible
+    let converter = H3Converter::new(view, &None, transform, AxisOrder::YX).with_num_threads(1);
+    let _ = if f32_coords {
+        converter.to_h3_f32_coords(h3_resolution, true).unwrap()
+    } else {
+        converter.to_h3(h3_resolution, true).unwrap()
+    };
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let (arr, transform) = test_array();
+    let view = arr.view();
+    let h3_res = 10;
+
+    for f32_coords in [false, true] {
+        let peak = peak_allocation(|| convert(&view, &transform, h3_res, f32_coords));
+        println!(
+            "peak allocation with {} coordinates: {} bytes",
+            if f32_coords { "f32" } else { "f64" },
+            peak
+        );
+    }
+
+    let mut group = c.benchmark_group("coordinate precision");
+    group.sample_size(10);
+    group.bench_function(format!("f64_h3_res_{}", h3_res), |b| {
+        b.iter(|| convert(&view, &transform, black_box(h3_res), false))
+    });
+    group.bench_function(format!("f32_h3_res_{}", h3_res), |b| {
+        b.iter(|| convert(&view, &transform, black_box(h3_res), true))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
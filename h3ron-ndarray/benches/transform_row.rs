@@ -0,0 +1,49 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use geo_types::Coordinate;
+
+use h3ron_ndarray::transform::Transform;
+use h3ron_ndarray::AxisOrder;
+
+const ROW_WIDTH: usize = 4096;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let transform = Transform::from_gdal(&[8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135]);
+    let mut out_x = vec![0.0; ROW_WIDTH];
+    let mut out_y = vec![0.0; ROW_WIDTH];
+
+    let mut group = c.benchmark_group("transform row");
+    group.bench_function("scalar", |b| {
+        b.iter(|| {
+            for col in 0..ROW_WIDTH {
+                let coordinate = &transform
+                    * Coordinate {
+                        x: col as f64 + 0.5,
+                        y: black_box(100_usize) as f64 + 0.5,
+                    };
+                out_x[col] = coordinate.x;
+                out_y[col] = coordinate.y;
+            }
+        })
+    });
+    group.bench_function("row_coordinates", |b| {
+        b.iter(|| {
+            transform.row_coordinates(
+                black_box(100),
+                0..ROW_WIDTH,
+                AxisOrder::YX,
+                &mut out_x,
+                &mut out_y,
+            )
+        })
+    });
+
+    let xs: Vec<_> = (0..ROW_WIDTH).map(|col| col as f64 + 0.5).collect();
+    let ys = vec![100.5; ROW_WIDTH];
+    group.bench_function("apply_batch", |b| {
+        b.iter(|| transform.apply_batch(black_box(&xs), black_box(&ys), &mut out_x, &mut out_y))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,176 @@
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// The method used by [`H3Converter::to_h3_aggregated`](crate::H3Converter::to_h3_aggregated)
+/// to aggregate the values of all pixels located within a cell.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AggregationMethod {
+    /// The value of the first pixel of the cell in the order of the array.
+    First,
+
+    /// The smallest value.
+    Min,
+
+    /// The largest value.
+    Max,
+
+    /// The most frequent value. Ties are resolved in favour of the value found first in
+    /// the order of the array.
+    Mode,
+
+    /// The mean of the values. Only supported for numeric values.
+    Mean,
+
+    /// The sum of the values. Only supported for numeric values.
+    Sum,
+}
+
+impl AggregationMethod {
+    /// true for the methods computing a new value instead of selecting one of the values
+    /// of the pixels.
+    pub const fn is_numeric(&self) -> bool {
+        matches!(self, Self::Mean | Self::Sum)
+    }
+}
+
+impl FromStr for AggregationMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "first" => Ok(Self::First),
+            "min" => Ok(Self::Min),
+            "max" => Ok(Self::Max),
+            "mode" => Ok(Self::Mode),
+            "mean" => Ok(Self::Mean),
+            "sum" => Ok(Self::Sum),
+            _ => Err(Error::UnknownAggregationMethod(s.to_string())),
+        }
+    }
+}
+
+/// The values of the pixels within a single cell, aggregated to one of these values.
+///
+/// Only the [`AggregationMethod::Mode`] keeps all distinct values together with their counts in
+/// the order they were found, all other methods keep a single value.
+pub(crate) struct ValueAggregate<'a, T> {
+    counts: Vec<(&'a T, usize)>,
+}
+
+impl<'a, T> Default for ValueAggregate<'a, T> {
+    fn default() -> Self {
+        Self { counts: vec![] }
+    }
+}
+
+impl<'a, T> ValueAggregate<'a, T>
+where
+    T: Ord,
+{
+    pub(crate) fn add(&mut self, method: AggregationMethod, value: &'a T) {
+        self.add_count(method, value, 1);
+    }
+
+    /// merge the aggregate of pixels located after the pixels of this aggregate in the order
+    /// of the array.
+    pub(crate) fn merge(&mut self, method: AggregationMethod, other: Self) {
+        for (value, count) in other.counts {
+            self.add_count(method, value, count);
+        }
+    }
+
+    fn add_count(&mut self, method: AggregationMethod, value: &'a T, count: usize) {
+        let replace = match (method, self.counts.first()) {
+            (_, None) => {
+                self.counts.push((value, count));
+                return;
+            }
+            (AggregationMethod::Min, Some((current, _))) => value < *current,
+            (AggregationMethod::Max, Some((current, _))) => value > *current,
+            (AggregationMethod::Mode, _) => {
+                match self
+                    .counts
+                    .iter_mut()
+                    .find(|(counted, _)| *counted == value)
+                {
+                    Some((_, existing_count)) => *existing_count += count,
+                    None => self.counts.push((value, count)),
+                }
+                return;
+            }
+            _ => false,
+        };
+        if replace {
+            self.counts[0] = (value, count);
+        }
+    }
+
+    /// the aggregated value. `None` when no value was added.
+    pub(crate) fn value(&self) -> Option<&'a T> {
+        // `max_by_key` returns the last maximum, but ties go to the value found first
+        let mut aggregated: Option<(&'a T, usize)> = None;
+        for (value, count) in self.counts.iter() {
+            if aggregated.is_none_or(|(_, max_count)| *count > max_count) {
+                aggregated = Some((value, *count));
+            }
+        }
+        aggregated.map(|(value, _)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::aggregation::{AggregationMethod, ValueAggregate};
+
+    fn aggregate(method: AggregationMethod, values: &[u8], split_at: usize) -> Option<u8> {
+        let mut first = ValueAggregate::default();
+        for value in values[..split_at].iter() {
+            first.add(method, value);
+        }
+        let mut second = ValueAggregate::default();
+        for value in values[split_at..].iter() {
+            second.add(method, value);
+        }
+        first.merge(method, second);
+        first.value().copied()
+    }
+
+    #[test]
+    fn aggregate_values() {
+        let values = [3_u8, 1, 4, 1, 5, 9, 2, 6, 5, 3];
+        for split_at in 0..=values.len() {
+            assert_eq!(
+                aggregate(AggregationMethod::First, &values, split_at),
+                Some(3)
+            );
+            assert_eq!(
+                aggregate(AggregationMethod::Min, &values, split_at),
+                Some(1)
+            );
+            assert_eq!(
+                aggregate(AggregationMethod::Max, &values, split_at),
+                Some(9)
+            );
+            // 3, 1 and 5 are found twice, 3 comes first
+            assert_eq!(
+                aggregate(AggregationMethod::Mode, &values, split_at),
+                Some(3)
+            );
+        }
+        assert_eq!(aggregate(AggregationMethod::Mode, &[], 0), None);
+    }
+
+    #[test]
+    fn parse_aggregation_method() {
+        assert_eq!(
+            "mode".parse::<AggregationMethod>().unwrap(),
+            AggregationMethod::Mode
+        );
+        assert_eq!(
+            "Max".parse::<AggregationMethod>().unwrap(),
+            AggregationMethod::Max
+        );
+        assert!("median".parse::<AggregationMethod>().is_err());
+    }
+}
@@ -1,19 +1,43 @@
 use std::cmp::{max, min};
 use std::hash::Hash;
+use std::ops::{BitAnd, Range};
+use std::time::Instant;
 
 use geo_types::{Coordinate, Rect};
 use log::debug;
-use ndarray::{ArrayView2, Axis};
+use ndarray::{Array2, ArrayView2, Axis};
 use rayon::prelude::*;
 
-use h3ron::collections::HashMap;
-use h3ron::{collections::CompactedCellVec, ToCoordinate, ToH3Cells};
+use h3ron::collections::{H3CellMap, HashMap, HashSet};
+use h3ron::iter::CellBoundaryBuilder;
+use h3ron::{collections::CompactedCellVec, H3Cell, ToCoordinate, ToH3Cells};
 
-use crate::resolution::{nearest_h3_resolution, ResolutionSearchMode};
+use crate::aggregation::{AggregationMethod, ValueAggregate};
+use crate::cell_sink::{CellSink, CompactedCellsSink};
+use crate::estimate::{
+    BoxEstimate, ConversionEstimate, BOX_DETECTION_MAX_PIXELS, MAX_SAMPLES_PER_BOX,
+};
+use crate::failure::{FailedBox, FailureReport};
+use crate::jitter::JitterOptions;
+use crate::mask::{data_indicator, MaskRule};
+use crate::nodata::NodataCheck;
+use crate::resolution::{h3_resolution_areas, nearest_h3_resolution, ResolutionSearchMode};
+use crate::scaling::ValueScaling;
+use crate::sphere::area_squaremeters_rect;
+use crate::stats::{ConversionStats, CountingSink};
+use crate::transform::{CoordFloat, LocalTransform};
 use crate::{error::Error, transform::Transform};
 
+/// The finest h3 resolution supported by [`H3Converter::to_h3_f32_coords`].
+pub const F32_COORDS_MAX_H3_RESOLUTION: u8 = 10;
+
+/// maximum deviation in pixels between coordinates computed using `f64` and using
+/// another [`CoordFloat`]. Checked on a subset of the coordinates in debug builds.
+#[cfg(debug_assertions)]
+const MAX_PIXEL_DEVIATION: f64 = 0.01;
+
 /// The order of the axis in the two-dimensional array
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[allow(clippy::upper_case_acronyms)]
 pub enum AxisOrder {
     /// `X,Y` ordering
@@ -41,19 +65,62 @@ impl AxisOrder {
     }
 }
 
-fn find_continuous_chunks_along_axis<T>(
+/// The location(s) within a cell used to look up the value of the cell in the array.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum AnchorStrategy {
+    /// The pixel under the centroid of the cell.
+    #[default]
+    Centroid,
+
+    /// The pixel under the centroid of the center child of the cell at the given
+    /// resolution.
+    ///
+    /// The center child follows the alignment of the H3 hierarchy and is slightly offset
+    /// from the centroid of the cell. The resolution must not be coarser than the resolution
+    /// of the conversion.
+    CenterChildAt(u8),
+
+    /// The majority of the non-nodata values of the pixels under the vertexes and the
+    /// centroid of the cell. Ties are resolved in favour of the value under the centroid.
+    ///
+    /// Catches thin linear features in categorical data which do not cover the centroid.
+    VertexMajority,
+}
+
+/// The algorithm used to find the boxes of the array containing data.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum BoxDetection {
+    /// Split the array at completely empty rows and columns once. Fast, but often
+    /// recognizes multiple smaller clusters as one box.
+    #[default]
+    Simple,
+
+    /// Repeat the splitting within each found box. Boxes which can not be split any further
+    /// get bisected along their longer axis, until both sides of a box are not larger
+    /// than `min_box_size` pixels.
+    ///
+    /// Produces far tighter boxes for sparse arrays - like coastlines or diagonal features -
+    /// at the cost of visiting the pixels of the boxes multiple times.
+    Recursive { min_box_size: usize },
+}
+
+fn find_continuous_chunks_along_axis<T, F>(
     a: &ArrayView2<T>,
     axis: usize,
-    nodata_value: &T,
+    is_nodata: &F,
 ) -> Vec<(usize, usize)>
 where
-    T: Sized + PartialEq,
+    F: Fn(&T) -> bool,
 {
     let mut chunks = Vec::new();
+    if a.is_empty() {
+        // arrays with a zero-length axis contain no data
+        return chunks;
+    }
     let mut current_chunk_start: Option<usize> = None;
 
     for (r0pos, r0) in a.axis_iter(Axis(axis)).enumerate() {
-        if r0.iter().any(|v| v != nodata_value) {
+        if r0.iter().any(|v| !is_nodata(v)) {
             if current_chunk_start.is_none() {
                 current_chunk_start = Some(r0pos);
             }
@@ -68,21 +135,21 @@ where
     chunks
 }
 
-/// Find all boxes in the array where there are any values except the `nodata_value`
+/// Find all boxes in the array where there are any values for which `is_nodata` is `false`
 ///
 /// This implementation is far from perfect and often recognizes multiple smaller
 /// clusters as one as its based on completely empty columns and rows, but it is probably
 /// sufficient for the purpose to reduce the number of hexagons
 /// to be generated when dealing with fragmented/sparse datasets.
-fn find_boxes_containing_data<T>(
+fn find_boxes_containing_data<T, F>(
     a: &ArrayView2<T>,
-    nodata_value: &T,
+    is_nodata: &F,
     axis_order: &AxisOrder,
 ) -> Vec<Rect<usize>>
 where
-    T: Sized + PartialEq,
+    F: Fn(&T) -> bool,
 {
-    find_continuous_chunks_along_axis(a, axis_order.x_axis(), nodata_value)
+    find_continuous_chunks_along_axis(a, axis_order.x_axis(), is_nodata)
         .into_iter()
         .flat_map(|chunk_x_raw_indexes| {
             let sv = {
@@ -92,7 +159,7 @@ where
                     AxisOrder::YX => a.slice(s![.., x_raw_range]),
                 }
             };
-            find_continuous_chunks_along_axis(&sv, axis_order.y_axis(), nodata_value)
+            find_continuous_chunks_along_axis(&sv, axis_order.y_axis(), is_nodata)
                 .into_iter()
                 .flat_map(move |chunks_y_raw_indexes| {
                     let sv2 = {
@@ -105,7 +172,7 @@ where
                     };
 
                     // one more iteration along axis 0 to get the specific range for that axis 1 range
-                    find_continuous_chunks_along_axis(&sv2, axis_order.x_axis(), nodata_value)
+                    find_continuous_chunks_along_axis(&sv2, axis_order.x_axis(), is_nodata)
                         .into_iter()
                         .map(move |chunks_x_indexes| {
                             Rect::new(
@@ -124,6 +191,120 @@ where
         .collect::<Vec<_>>()
 }
 
+/// Like [`find_boxes_containing_data`], but recursively splitting the found boxes further as
+/// described for [`BoxDetection::Recursive`].
+///
+/// The union of the returned boxes still covers all values for which `is_nodata` is `false`.
+fn find_boxes_containing_data_recursive<T, F>(
+    a: &ArrayView2<T>,
+    is_nodata: &F,
+    axis_order: &AxisOrder,
+    min_box_size: usize,
+) -> Vec<Rect<usize>>
+where
+    F: Fn(&T) -> bool,
+{
+    let mut boxes = Vec::new();
+    collect_boxes_recursive(
+        a,
+        is_nodata,
+        axis_order,
+        max(min_box_size, 1),
+        Coordinate { x: 0, y: 0 },
+        &mut boxes,
+    );
+    boxes
+}
+
+fn collect_boxes_recursive<T, F>(
+    a: &ArrayView2<T>,
+    is_nodata: &F,
+    axis_order: &AxisOrder,
+    min_box_size: usize,
+    offset: Coordinate<usize>,
+    boxes: &mut Vec<Rect<usize>>,
+) where
+    F: Fn(&T) -> bool,
+{
+    let x_size = a.shape()[axis_order.x_axis()];
+    let y_size = a.shape()[axis_order.y_axis()];
+
+    for rect in find_boxes_containing_data(a, is_nodata, axis_order) {
+        let width = rect.max().x - rect.min().x + 1;
+        let height = rect.max().y - rect.min().y + 1;
+        let rect_offset = Coordinate {
+            x: offset.x + rect.min().x,
+            y: offset.y + rect.min().y,
+        };
+        if width <= min_box_size && height <= min_box_size {
+            boxes.push(Rect::new(
+                rect_offset,
+                Coordinate {
+                    x: rect_offset.x + width - 1,
+                    y: rect_offset.y + height - 1,
+                },
+            ));
+            continue;
+        }
+
+        let slice =
+            |x_range: std::ops::Range<usize>, y_range: std::ops::Range<usize>| match axis_order {
+                AxisOrder::XY => a.slice(s![x_range, y_range]),
+                AxisOrder::YX => a.slice(s![y_range, x_range]),
+            };
+        let x_range = rect.min().x..(rect.max().x + 1);
+        let y_range = rect.min().y..(rect.max().y + 1);
+
+        if width < x_size || height < y_size {
+            // the box is smaller than the array, so the splitting may be continued within the box
+            collect_boxes_recursive(
+                &slice(x_range, y_range),
+                is_nodata,
+                axis_order,
+                min_box_size,
+                rect_offset,
+                boxes,
+            );
+        } else if width >= height {
+            let x_split = rect.min().x + width / 2;
+            for (x_range, x_offset) in [
+                (x_range.start..x_split, rect_offset.x),
+                (x_split..x_range.end, rect_offset.x + width / 2),
+            ] {
+                collect_boxes_recursive(
+                    &slice(x_range, y_range.clone()),
+                    is_nodata,
+                    axis_order,
+                    min_box_size,
+                    Coordinate {
+                        x: x_offset,
+                        y: rect_offset.y,
+                    },
+                    boxes,
+                );
+            }
+        } else {
+            let y_split = rect.min().y + height / 2;
+            for (y_range, y_offset) in [
+                (y_range.start..y_split, rect_offset.y),
+                (y_split..y_range.end, rect_offset.y + height / 2),
+            ] {
+                collect_boxes_recursive(
+                    &slice(x_range.clone(), y_range),
+                    is_nodata,
+                    axis_order,
+                    min_box_size,
+                    Coordinate {
+                        x: rect_offset.x,
+                        y: y_offset,
+                    },
+                    boxes,
+                );
+            }
+        }
+    }
+}
+
 /// convert a 2-d ndarray to h3
 pub struct H3Converter<'a, T>
 where
@@ -133,6 +314,24 @@ where
     nodata_value: &'a Option<T>,
     transform: &'a Transform,
     axis_order: AxisOrder,
+
+    /// additional check for nodata values besides the `nodata_value`.
+    nodata_check: Option<NodataCheck<T>>,
+
+    /// `true` for all pixels which are valid according to the validity mask.
+    validity_mask: Option<Array2<bool>>,
+
+    anchor_strategy: AnchorStrategy,
+
+    box_detection: BoxDetection,
+
+    sample_jitter: Option<JitterOptions>,
+
+    /// coarsest resolution the cells get compacted to.
+    min_compacted_resolution: u8,
+
+    /// number of threads of a dedicated thread pool. `None` uses the global thread pool.
+    num_threads: Option<usize>,
 }
 
 impl<'a, T> H3Converter<'a, T>
@@ -150,7 +349,149 @@ where
             nodata_value,
             transform,
             axis_order,
+            nodata_check: None,
+            validity_mask: None,
+            anchor_strategy: AnchorStrategy::default(),
+            box_detection: BoxDetection::default(),
+            sample_jitter: None,
+            min_compacted_resolution: 0,
+            num_threads: None,
+        }
+    }
+
+    /// Limit the number of threads used by the conversion methods.
+    ///
+    /// By default the conversion runs on the global rayon thread pool. Setting the number
+    /// of threads runs it on a dedicated thread pool instead, which avoids multiple
+    /// concurrent conversions competing for all cores. The result does not depend on
+    /// the number of threads.
+    pub fn with_num_threads(mut self, num_threads: usize) -> Self {
+        self.num_threads = Some(num_threads);
+        self
+    }
+
+    /// run `op` within the thread pool configured for this converter.
+    fn install<R, F>(&self, op: F) -> Result<R, Error>
+    where
+        F: FnOnce() -> Result<R, Error> + Send,
+        R: Send,
+    {
+        match self.num_threads {
+            Some(num_threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()?
+                .install(op),
+            None => op(),
+        }
+    }
+
+    /// Set the [`AnchorStrategy`] used by [`H3Converter::to_h3`] to find the value of a cell.
+    pub fn with_anchor_strategy(mut self, anchor_strategy: AnchorStrategy) -> Self {
+        self.anchor_strategy = anchor_strategy;
+        self
+    }
+
+    /// Displace the points at which the values of cells are looked up in the array by a
+    /// deterministic jitter to reduce aliasing artifacts. See [`JitterOptions`] on when to
+    /// use it. Disabled by default.
+    ///
+    /// Applies to the `Centroid` and `CenterChildAt` [`AnchorStrategy`], the vertexes of the
+    /// `VertexMajority` strategy are not jittered. Sample points displaced beyond the edges of
+    /// the array are clamped to the array, so the jitter does not change which cells are
+    /// generated.
+    pub fn with_sample_jitter(mut self, sample_jitter: JitterOptions) -> Self {
+        self.sample_jitter = Some(sample_jitter);
+        self
+    }
+
+    /// Do not compact the cells to resolutions coarser than `min_compacted_resolution` when
+    /// compacting is enabled, like for systems assuming no cells coarser than a resolution.
+    /// See [`CompactedCellVec::compact_to_min_resolution`]. Defaults to 0, which does not
+    /// restrict the compaction.
+    pub fn with_min_compacted_resolution(mut self, min_compacted_resolution: u8) -> Self {
+        self.min_compacted_resolution = min_compacted_resolution;
+        self
+    }
+
+    fn compacted_cells_sink(&self, compact: bool) -> CompactedCellsSink<'a, T> {
+        CompactedCellsSink::new(compact)
+            .with_min_compacted_resolution(self.min_compacted_resolution)
+    }
+
+    /// Set the [`BoxDetection`] used to find the boxes of the array containing data. Defaults
+    /// to [`BoxDetection::Simple`].
+    pub fn with_box_detection(mut self, box_detection: BoxDetection) -> Self {
+        self.box_detection = box_detection;
+        self
+    }
+
+    /// find the boxes of `a` containing data using the configured [`BoxDetection`].
+    fn find_boxes<V, F>(&self, a: &ArrayView2<V>, is_nodata: &F) -> Vec<Rect<usize>>
+    where
+        F: Fn(&V) -> bool,
+    {
+        match self.box_detection {
+            BoxDetection::Simple => find_boxes_containing_data(a, is_nodata, &self.axis_order),
+            BoxDetection::Recursive { min_box_size } => {
+                find_boxes_containing_data_recursive(a, is_nodata, &self.axis_order, min_box_size)
+            }
+        }
+    }
+
+    /// Use a [`NodataCheck`] to decide which values are nodata - like multiple sentinel
+    /// values or all values below a threshold.
+    ///
+    /// The check is applied in addition to the nodata value passed to [`H3Converter::new`]. The
+    /// detection of the boxes containing data and the conversion of the pixels both use the
+    /// same check.
+    pub fn with_nodata_check(mut self, nodata_check: NodataCheck<T>) -> Self {
+        self.nodata_check = Some(nodata_check);
+        self
+    }
+
+    /// Use a separate `mask` array - like an alpha band or a bitmask of quality flags - to
+    /// decide which pixels contain data.
+    ///
+    /// A pixel is only converted when its value is not the nodata value and the value of
+    /// the `mask` at the same position is valid according to the `rule`.
+    ///
+    /// The `mask` must have the same shape as the array of values.
+    pub fn with_validity_mask<M>(
+        mut self,
+        mask: &ArrayView2<M>,
+        rule: &MaskRule<M>,
+    ) -> Result<Self, Error>
+    where
+        M: Copy + PartialEq + Default + BitAnd<Output = M>,
+    {
+        if mask.shape() != self.arr.shape() {
+            return Err(Error::MaskShapeMismatch);
+        }
+        self.validity_mask = Some(rule.apply(mask));
+        Ok(self)
+    }
+
+    /// `true` when the converter has a nodata value or a nodata check.
+    fn has_nodata(&self) -> bool {
+        self.nodata_value.is_some() || self.nodata_check.is_some()
+    }
+
+    fn is_nodata(&self, value: &T) -> bool {
+        self.nodata_value.as_ref() == Some(value)
+            || self
+                .nodata_check
+                .as_ref()
+                .is_some_and(|nodata_check| nodata_check.is_nodata(value))
+    }
+
+    /// check if the pixel at `arr_coord` with the `value` contains data
+    fn is_data(&self, arr_coord: [usize; 2], value: &T) -> bool {
+        if self.is_nodata(value) {
+            return false;
         }
+        self.validity_mask
+            .as_ref()
+            .is_none_or(|validity| validity.get(arr_coord) == Some(&true))
     }
 
     /// find the h3 resolution closest to the size of a pixel in an array
@@ -163,20 +504,33 @@ where
         )
     }
 
-    fn rects_with_data_with_nodata(&self, rect_size: usize, nodata: &T) -> Vec<Rect<f64>> {
-        self.arr
-            .axis_chunks_iter(Axis(self.axis_order.x_axis()), rect_size)
-            .into_par_iter() // requires T to be Sync
+    /// the areas of the cells at the center of the array for all h3 resolutions compared to the
+    /// area of a pixel. See [`h3_resolution_areas`].
+    pub fn h3_resolution_areas(&self) -> Result<Vec<(u8, f64, f64)>, Error> {
+        h3_resolution_areas(self.arr.shape(), self.transform, &self.axis_order)
+    }
+
+    fn rects_with_data_with_nodata<V, F>(
+        &self,
+        arr: &ArrayView2<V>,
+        rect_size: usize,
+        is_nodata: &F,
+    ) -> Vec<Rect<f64>>
+    where
+        V: Sized + Sync,
+        F: Fn(&V) -> bool + Sync,
+    {
+        arr.axis_chunks_iter(Axis(self.axis_order.x_axis()), rect_size)
+            .into_par_iter() // requires V to be Sync
             .enumerate()
             .map(|(axis_x_chunk_i, axis_x_chunk)| {
                 let mut rects = Vec::new();
-                for chunk_x_rect in
-                    find_boxes_containing_data(&axis_x_chunk, nodata, &self.axis_order)
-                {
+                for chunk_x_rect in self.find_boxes(&axis_x_chunk, is_nodata) {
                     let offset_x = (axis_x_chunk_i * rect_size) + chunk_x_rect.min().x;
                     let chunk_rect_view = {
-                        let x_range = chunk_x_rect.min().x..chunk_x_rect.max().x;
-                        let y_range = chunk_x_rect.min().y..chunk_x_rect.max().y;
+                        // inclusive, boxes with a size of a single pixel would get lost otherwise
+                        let x_range = chunk_x_rect.min().x..=chunk_x_rect.max().x;
+                        let y_range = chunk_x_rect.min().y..=chunk_x_rect.max().y;
                         match self.axis_order {
                             AxisOrder::XY => axis_x_chunk.slice(s![x_range, y_range]),
                             AxisOrder::YX => axis_x_chunk.slice(s![y_range, x_range]),
@@ -195,14 +549,14 @@ where
                                         x: offset_x as f64,
                                         y: offset_y as f64,
                                     },
-                                    // add 1 to the max coordinate to include the whole last pixel
+                                    // the max coordinate is the far edge of the last pixel
                                     Coordinate {
                                         x: (offset_x
-                                            + axis_y_chunk.shape()[self.axis_order.x_axis()]
-                                            + 1) as f64,
+                                            + axis_y_chunk.shape()[self.axis_order.x_axis()])
+                                            as f64,
                                         y: (offset_y
-                                            + axis_y_chunk.shape()[self.axis_order.y_axis()]
-                                            + 1) as f64,
+                                            + axis_y_chunk.shape()[self.axis_order.y_axis()])
+                                            as f64,
                                     },
                                 )
                             }),
@@ -237,34 +591,177 @@ where
     }
 
     fn rects_with_data(&self, rect_size: usize) -> Vec<Rect<f64>> {
-        self.nodata_value.as_ref().map_or_else(
-            || self.rects_with_data_without_nodata(rect_size),
-            |nodata| self.rects_with_data_with_nodata(rect_size, nodata),
-        )
+        if self.arr.is_empty() {
+            return vec![];
+        }
+        match (self.validity_mask.as_ref(), self.has_nodata()) {
+            (Some(validity), _) => {
+                // search the boxes using the combined predicate of the nodata check and the mask
+                let indicator =
+                    data_indicator(self.arr, &validity.view(), |value| self.is_nodata(value));
+                self.rects_with_data_with_nodata(&indicator.view(), rect_size, &|valid: &bool| {
+                    !*valid
+                })
+            }
+            (None, true) => self.rects_with_data_with_nodata(self.arr, rect_size, &|value: &T| {
+                self.is_nodata(value)
+            }),
+            (None, false) => self.rects_with_data_without_nodata(rect_size),
+        }
+    }
+
+    /// The boxes of the array the conversion visits, as inclusive ranges of pixel indexes.
+    ///
+    /// The `x` and `y` coordinates of the boxes follow the [`AxisOrder`] of the converter.
+    /// Together the boxes cover all pixels containing data. Boxes larger than the windows the
+    /// array gets split into during the conversion are returned once per window.
+    ///
+    /// Intended for inspecting which regions of the array get converted.
+    pub fn boxes_containing_data(&self) -> Result<Vec<Rect<usize>>, Error> {
+        self.install(|| {
+            Ok(self
+                .rects_with_data(self.window_size())
+                .iter()
+                .map(|array_window| self.pixel_rect(array_window))
+                .collect())
+        })
+    }
+
+    /// Like [`H3Converter::boxes_containing_data`], but in the coordinates of the transform of
+    /// the converter. The boxes cover the complete area of their pixels.
+    pub fn boxes_containing_data_coordinates(&self) -> Result<Vec<Rect<f64>>, Error> {
+        self.install(|| {
+            Ok(self
+                .rects_with_data(self.window_size())
+                .iter()
+                .map(|array_window| self.transform * array_window)
+                .collect())
+        })
+    }
+
+    /// size of the windows the array gets split into during the conversion
+    fn window_size(&self) -> usize {
+        (self.arr.shape()[self.axis_order.x_axis()] / 10).clamp(10, 100)
     }
 
+    /// Convert the array to `h3_resolution`.
+    ///
+    /// Without `compact` all cells are located at `h3_resolution`, which avoids compacting
+    /// cells which would get uncompacted again anyway, e.g. to join them against other data
+    /// at the same resolution.
+    ///
+    /// The cells of the returned `CompactedCellVec`s are sorted and deduplicated (see
+    /// [`CompactedCellVec::is_sorted`]), so converting the same array twice results in equal
+    /// vecs with identical [`CompactedCellVec::to_bytes`] output.
     pub fn to_h3(
         &self,
         h3_resolution: u8,
         compact: bool,
     ) -> Result<HashMap<&'a T, CompactedCellVec>, Error> {
+        self.drive(h3_resolution, self.compacted_cells_sink(compact))?
+    }
+
+    /// Convert the array to `h3_resolution` and pass the cells containing data to `sink`.
+    ///
+    /// The array is split into boxes which are converted in parallel, each one into its own
+    /// sink created by [`CellSink::fork`]. The sinks of the boxes are merged into `sink` in the
+    /// order of the boxes, so the output does not depend on the number of threads. See the
+    /// [`cell_sink`](crate::cell_sink) module for details.
+    pub fn drive<S>(&self, h3_resolution: u8, sink: S) -> Result<S::Output, Error>
+    where
+        S: CellSink<'a, T>,
+        S::Output: Send,
+    {
+        self.install(|| self.drive_in_pool::<f64, S>(h3_resolution, sink, None))
+    }
+
+    /// Like [`H3Converter::to_h3`], but also returning [`ConversionStats`] on the work done
+    /// by the conversion.
+    ///
+    /// Collecting the statistics requires visiting all pixels of the boxes containing data a
+    /// second time and uncompacting the result, so this is a bit slower than
+    /// [`H3Converter::to_h3`].
+    pub fn to_h3_with_stats(
+        &self,
+        h3_resolution: u8,
+        compact: bool,
+    ) -> Result<(HashMap<&'a T, CompactedCellVec>, ConversionStats), Error> {
+        self.install(|| {
+            let mut stats = ConversionStats::new(h3_resolution);
+            let (cells, generated_cells) = self.drive_in_pool::<f64, _>(
+                h3_resolution,
+                CountingSink::new(self.compacted_cells_sink(compact)),
+                Some(&mut stats),
+            )?;
+            let cells = cells?;
+            stats.generated_cells = generated_cells;
+            for compacted_vec in cells.values() {
+                stats.output_cells += compacted_vec.len();
+                stats.uncompacted_cells +=
+                    compacted_vec.iter_uncompacted_cells(h3_resolution).count();
+            }
+            Ok((cells, stats))
+        })
+    }
+
+    /// Like [`H3Converter::to_h3`], but using `f32` instead of `f64` for the intermediate
+    /// coordinates. This reduces the memory used during the conversion.
+    ///
+    /// The coordinates are made relative to the boxes of the array the conversion gets split
+    /// into, which keeps the error of the pixel lookups far below the size of a pixel. The
+    /// boxes themselves are computed using absolute `f32` coordinates, which have an error
+    /// of up to about a meter. So only h3 resolutions up to [`F32_COORDS_MAX_H3_RESOLUTION`]
+    /// are supported, finer resolutions fail with [`Error::ResolutionTooFineForF32Coordinates`].
+    ///
+    /// The `VertexMajority` [`AnchorStrategy`] always uses `f64`.
+    pub fn to_h3_f32_coords(
+        &self,
+        h3_resolution: u8,
+        compact: bool,
+    ) -> Result<HashMap<&'a T, CompactedCellVec>, Error> {
+        if h3_resolution > F32_COORDS_MAX_H3_RESOLUTION {
+            return Err(Error::ResolutionTooFineForF32Coordinates(h3_resolution));
+        }
+        self.install(|| {
+            self.drive_in_pool::<f32, _>(h3_resolution, self.compacted_cells_sink(compact), None)
+        })?
+    }
+
+    /// `stats` receives the timings of the phases of the conversion and the pixel counts.
+    fn drive_in_pool<F, S>(
+        &self,
+        h3_resolution: u8,
+        mut sink: S,
+        mut stats: Option<&mut ConversionStats>,
+    ) -> Result<S::Output, Error>
+    where
+        F: CoordFloat,
+        S: CellSink<'a, T>,
+    {
         let inverse_transform = self.transform.invert()?;
 
-        let rect_size = min(
-            max(self.arr.shape()[self.axis_order.x_axis()] / 10, 10),
-            100,
-        );
+        let started = Instant::now();
+        let rect_size = self.window_size();
         let rects = self.rects_with_data(rect_size);
         let n_rects = rects.len();
+        if let Some(stats) = stats.as_deref_mut() {
+            stats.box_detection_time = started.elapsed();
+            stats.num_boxes = n_rects;
+            stats.num_pixels = self.arr.len();
+        }
+        let count_pixels = stats.is_some();
         debug!(
             "to_h3: found {} rects containing non-nodata values",
             n_rects
         );
 
-        let chunk_h3_maps = rects
+        let started = Instant::now();
+        let box_sinks: Vec<_> = rects.iter().map(|_| sink.fork()).collect();
+        let box_sinks = rects
             .into_par_iter()
+            .zip(box_sinks)
             .enumerate()
-            .map(|(array_window_i, array_window)| {
+            .map(|(array_window_i, (array_window, mut box_sink))| {
                 debug!(
                     "to_h3: rect {}/{} with size {} x {}",
                     array_window_i,
@@ -274,162 +771,2147 @@ where
                 );
 
                 // the window in geographical coordinates
-                let window_box = self.transform * &array_window;
+                let window_box =
+                    LocalTransform::<F>::new(self.transform, None).apply_rect(&array_window);
 
-                convert_array_window(
-                    self.arr,
+                self.convert_array_window::<F, S>(
                     window_box,
                     &inverse_transform,
-                    self.axis_order,
-                    self.nodata_value,
                     h3_resolution,
-                    compact,
-                )
+                    &mut box_sink,
+                )?;
+                let pixel_rect = self.pixel_rect(&array_window);
+                box_sink.finish_box(&pixel_rect)?;
+
+                let pixel_counts = if count_pixels {
+                    let visited_pixels = (pixel_rect.width() + 1) * (pixel_rect.height() + 1);
+                    (
+                        visited_pixels,
+                        visited_pixels - self.count_data_pixels(&array_window),
+                    )
+                } else {
+                    (0, 0)
+                };
+                Ok((box_sink, pixel_counts))
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .collect::<Result<Vec<_>, Error>>()?;
+        if let Some(stats) = stats.as_deref_mut() {
+            stats.conversion_time = started.elapsed();
+        }
 
-        // combine the results from all chunks
-        let mut h3_map = HashMap::default();
-        for chunk_h3_map in chunk_h3_maps.into_iter() {
-            for (value, mut compacted_vec) in chunk_h3_map {
-                h3_map
-                    .entry(value)
-                    .or_insert_with(CompactedCellVec::new)
-                    .append(&mut compacted_vec, false)?;
+        // combine the results from all boxes
+        let started = Instant::now();
+        for (box_sink, (visited_pixels, nodata_pixels)) in box_sinks.into_iter() {
+            sink.merge(box_sink)?;
+            if let Some(stats) = stats.as_deref_mut() {
+                stats.visited_pixels += visited_pixels;
+                stats.nodata_pixels += nodata_pixels;
             }
         }
+        let output = sink.finalize();
+        if let Some(stats) = stats {
+            stats.compaction_time = started.elapsed();
+        }
+        Ok(output)
+    }
 
-        finalize_chunk_map(h3_map, compact)
+    /// Like [`H3Converter::to_h3`], but boxes of the array which fail to convert are skipped
+    /// instead of aborting the whole conversion.
+    ///
+    /// The skipped boxes are listed in the returned [`FailureReport`]. When more than
+    /// `max_failed_fraction` of the data pixels are located in failed boxes, the conversion
+    /// fails with [`Error::TooManyFailedBoxes`] containing the report.
+    pub fn to_h3_resilient(
+        &self,
+        h3_resolution: u8,
+        compact: bool,
+        max_failed_fraction: f64,
+    ) -> Result<(HashMap<&'a T, CompactedCellVec>, FailureReport), Error> {
+        self.install(|| {
+            self.to_h3_isolating_failures(h3_resolution, compact, max_failed_fraction, |window| {
+                self.transform * window
+            })
+        })
     }
-}
 
-fn convert_array_window<'a, T>(
-    arr: &'a ArrayView2<'a, T>,
-    window_box: Rect<f64>,
-    inverse_transform: &Transform,
-    axis_order: AxisOrder,
-    nodata_value: &Option<T>,
-    h3_resolution: u8,
-    compact: bool,
-) -> Result<HashMap<&'a T, CompactedCellVec>, Error>
-where
-    T: Sized + PartialEq + Sync + Eq + Hash,
-{
-    let mut chunk_h3_map = HashMap::<&T, CompactedCellVec>::default();
-    for cell in window_box.to_h3_cells(h3_resolution)?.iter() {
-        // find the array element for the coordinate of the h3ron index
-        let arr_coord = {
-            let transformed = inverse_transform * cell.to_coordinate()?;
-
-            match axis_order {
-                AxisOrder::XY => [
-                    transformed.x.floor() as usize,
-                    transformed.y.floor() as usize,
-                ],
-                AxisOrder::YX => [
-                    transformed.y.floor() as usize,
-                    transformed.x.floor() as usize,
-                ],
-            }
-        };
-        if let Some(value) = arr.get(arr_coord) {
-            if let Some(nodata) = nodata_value {
-                if nodata == value {
-                    continue;
+    /// `window_box` maps a window in array coordinates to the geographical box of the window.
+    fn to_h3_isolating_failures<F>(
+        &self,
+        h3_resolution: u8,
+        compact: bool,
+        max_failed_fraction: f64,
+        window_box: F,
+    ) -> Result<(HashMap<&'a T, CompactedCellVec>, FailureReport), Error>
+    where
+        F: Fn(&Rect<f64>) -> Rect<f64> + Sync,
+    {
+        let (cells, report) = self.drive_isolating_failures(
+            h3_resolution,
+            self.compacted_cells_sink(compact),
+            max_failed_fraction,
+            window_box,
+        )?;
+        Ok((cells?, report))
+    }
+
+    /// Like [`H3Converter::drive`], but the sinks of boxes which fail to convert are
+    /// discarded and the boxes are listed in the returned [`FailureReport`].
+    fn drive_isolating_failures<S, F>(
+        &self,
+        h3_resolution: u8,
+        mut sink: S,
+        max_failed_fraction: f64,
+        window_box: F,
+    ) -> Result<(S::Output, FailureReport), Error>
+    where
+        S: CellSink<'a, T>,
+        F: Fn(&Rect<f64>) -> Rect<f64> + Sync,
+    {
+        let inverse_transform = self.transform.invert()?;
+        let rects = self.rects_with_data(self.window_size());
+
+        let box_sinks: Vec<_> = rects.iter().map(|_| sink.fork()).collect();
+        let outcomes: Vec<_> = rects
+            .into_par_iter()
+            .zip(box_sinks)
+            .map(|(array_window, mut box_sink)| {
+                let data_pixels = self.count_data_pixels(&array_window);
+                let converted = self
+                    .convert_array_window::<f64, S>(
+                        window_box(&array_window),
+                        &inverse_transform,
+                        h3_resolution,
+                        &mut box_sink,
+                    )
+                    .and_then(|_| box_sink.finish_box(&self.pixel_rect(&array_window)))
+                    .map(|_| box_sink);
+                (array_window, data_pixels, converted)
+            })
+            .collect();
+
+        let mut report = FailureReport::default();
+        for (array_window, data_pixels, converted) in outcomes {
+            report.data_pixels += data_pixels;
+            match converted {
+                Ok(box_sink) => sink.merge(box_sink)?,
+                Err(e) => {
+                    debug!("to_h3_resilient: skipping failed rect: {}", e);
+                    report.failed_data_pixels += data_pixels;
+                    report.failed_boxes.push(FailedBox {
+                        rect: self.pixel_rect(&array_window),
+                        error: e.to_string(),
+                        data_pixels,
+                    });
                 }
             }
-            chunk_h3_map
-                .entry(value)
-                .or_insert_with(CompactedCellVec::new)
-                .add_cell(cell, false)?;
         }
+
+        if report.failed_fraction() > max_failed_fraction {
+            return Err(Error::TooManyFailedBoxes(Box::new(report)));
+        }
+        Ok((sink.finalize(), report))
     }
 
-    // do an early compacting to free a bit of memory
-    finalize_chunk_map(chunk_h3_map, compact)
-}
+    /// the pixels of the array covered by the window in array coordinates. Both `min` and `max`
+    /// are inclusive.
+    fn pixel_rect(&self, array_window: &Rect<f64>) -> Rect<usize> {
+        let x_size = self.arr.shape()[self.axis_order.x_axis()];
+        let y_size = self.arr.shape()[self.axis_order.y_axis()];
+        Rect::new(
+            Coordinate {
+                x: array_window.min().x as usize,
+                y: array_window.min().y as usize,
+            },
+            Coordinate {
+                x: min(
+                    (array_window.max().x as usize).saturating_sub(1),
+                    x_size - 1,
+                ),
+                y: min(
+                    (array_window.max().y as usize).saturating_sub(1),
+                    y_size - 1,
+                ),
+            },
+        )
+    }
 
-fn finalize_chunk_map<T>(
-    chunk_map: HashMap<&T, CompactedCellVec>,
-    compact: bool,
-) -> Result<HashMap<&T, CompactedCellVec>, Error>
-where
-    T: Sync + Eq + Hash,
-{
-    chunk_map
-        .into_par_iter()
-        .map(|(k, mut compact_vec)| {
-            if compact {
-                compact_vec.compact().map_err(Error::from)
-            } else {
-                compact_vec.dedup().map_err(Error::from)
+    fn count_data_pixels(&self, array_window: &Rect<f64>) -> usize {
+        let rect = self.pixel_rect(array_window);
+        let mut data_pixels = 0;
+        for x in rect.min().x..=rect.max().x {
+            for y in rect.min().y..=rect.max().y {
+                let arr_coord = match self.axis_order {
+                    AxisOrder::XY => [x, y],
+                    AxisOrder::YX => [y, x],
+                };
+                if let Some(value) = self.arr.get(arr_coord) {
+                    if self.is_data(arr_coord, value) {
+                        data_pixels += 1;
+                    }
+                }
             }
-            .map(|_| {
-                compact_vec.shrink_to_fit();
-                (k, compact_vec)
-            })
-        })
-        .collect()
-}
+        }
+        data_pixels
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::array::find_boxes_containing_data;
-    use crate::{AxisOrder, H3Converter, ResolutionSearchMode, Transform};
+    /// pass the cells of the window `window_box` containing data to `sink`.
+    fn convert_array_window<F, S>(
+        &self,
+        window_box: Rect<f64>,
+        inverse_transform: &Transform,
+        h3_resolution: u8,
+        sink: &mut S,
+    ) -> Result<(), Error>
+    where
+        F: CoordFloat,
+        S: CellSink<'a, T>,
+    {
+        if !(window_box.min().x.is_finite()
+            && window_box.min().y.is_finite()
+            && window_box.max().x.is_finite()
+            && window_box.max().y.is_finite())
+        {
+            return Err(Error::NonFiniteCoordinates);
+        }
 
-    #[test]
-    fn test_find_boxes_containing_data() {
-        let arr = array![
-            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0],
-            [0, 1, 1, 0, 0, 0, 0, 1, 1, 1, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 1],
-            [0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 1, 1],
-        ];
-        let mut arr_copy = arr.clone();
+        let cells = window_box.to_h3_cells(h3_resolution)?;
+        match self.anchor_strategy {
+            AnchorStrategy::Centroid | AnchorStrategy::CenterChildAt(_) => {
+                let local_inverse_transform = LocalTransform::<F>::new(
+                    inverse_transform,
+                    F::RELATIVE_COORDINATES.then_some(window_box.min()),
+                );
 
-        let n_elements = arr_copy.shape()[0] * arr_copy.shape()[1];
-        let mut n_elements_in_boxes = 0;
+                for cell in cells.iter() {
+                    let anchor = self.anchor_coordinate(&cell)?;
+                    let (x, y) = local_inverse_transform.local(&anchor);
+                    let mut pixel = local_inverse_transform.apply(x, y);
 
-        for rect in find_boxes_containing_data(&arr.view(), &0, &AxisOrder::YX) {
-            n_elements_in_boxes +=
-                (rect.max().x - rect.min().x + 1) * (rect.max().y - rect.min().y + 1);
+                    #[cfg(debug_assertions)]
+                    {
+                        let exact = inverse_transform * anchor;
+                        debug_assert!(
+                            (exact.x - pixel.0.into_f64()).abs() < MAX_PIXEL_DEVIATION
+                                && (exact.y - pixel.1.into_f64()).abs() < MAX_PIXEL_DEVIATION
+                        );
+                    }
 
-            for x in rect.min().x..=rect.max().x {
-                for y in rect.min().y..=rect.max().y {
-                    arr_copy[(y, x)] = 0;
+                    if let Some(sample_jitter) = self.sample_jitter.as_ref() {
+                        let (x, y) =
+                            local_inverse_transform.local(&sample_jitter.jitter(&cell, anchor)?);
+                        pixel =
+                            self.clamp_jittered_pixel(pixel, local_inverse_transform.apply(x, y));
+                    }
+
+                    if let Some(value) = self.data_value_at_pixel(Coordinate {
+                        x: pixel.0.into_f64(),
+                        y: pixel.1.into_f64(),
+                    }) {
+                        sink.accept(cell, value)?;
+                    }
+                }
+            }
+            AnchorStrategy::VertexMajority => {
+                let mut boundary_builder = CellBoundaryBuilder::new();
+                for cell in cells.iter() {
+                    if let Some(value) =
+                        self.vertex_majority_value(inverse_transform, &mut boundary_builder, &cell)?
+                    {
+                        sink.accept(cell, value)?;
+                    }
                 }
             }
         }
+        Ok(())
+    }
 
-        // there should be far less indexes to visit now
-        assert!(n_elements_in_boxes < (n_elements / 2));
+    /// the location within `cell` used to look up its value with the `Centroid` and
+    /// `CenterChildAt` anchor strategies, without the jitter.
+    #[inline]
+    fn anchor_coordinate(&self, cell: &H3Cell) -> Result<Coordinate<f64>, Error> {
+        Ok(match self.anchor_strategy {
+            AnchorStrategy::CenterChildAt(resolution) => {
+                cell.center_child(resolution)?.to_coordinate()?
+            }
+            _ => cell.to_coordinate()?,
+        })
+    }
 
-        // all elements should have been removed
-        assert_eq!(arr_copy.sum(), 0);
+    /// replace the pixel coordinate of an anchor located within the array by its
+    /// jittered counterpart, clamped to the extent of the array.
+    ///
+    /// Anchors outside of the array are kept, so jittering never changes which cells get a
+    /// value - only the pixel the value is taken from.
+    fn clamp_jittered_pixel<F: CoordFloat>(&self, pixel: (F, F), jittered_pixel: (F, F)) -> (F, F) {
+        let shape = self.arr.shape();
+        let (width, height) = match self.axis_order {
+            AxisOrder::XY => (shape[0] as f64, shape[1] as f64),
+            AxisOrder::YX => (shape[1] as f64, shape[0] as f64),
+        };
+
+        let (x, y) = (pixel.0.into_f64(), pixel.1.into_f64());
+        if (0.0..width).contains(&x) && (0.0..height).contains(&y) {
+            // keep a margin to the far edges to stay within the last pixel with `f32`
+            (
+                F::from_f64(jittered_pixel.0.into_f64().clamp(0.0, width - 0.5)),
+                F::from_f64(jittered_pixel.1.into_f64().clamp(0.0, height - 0.5)),
+            )
+        } else {
+            pixel
+        }
     }
 
-    #[test]
-    fn preserve_nan_values() {
-        use ordered_float::OrderedFloat;
-        #[rustfmt::skip]
-        let arr = array![
-            [OrderedFloat(f32::NAN), OrderedFloat(1.0_f32)],
-            [OrderedFloat(f32::NAN), OrderedFloat(1.0_f32)],
-        ];
-        let transform = Transform::from_gdal(&[11.0, 1.0, 0.0, 10.0, 1.2, 0.2]);
+    /// the value of the array element at the geographical `coordinate`, in case it
+    /// contains data.
+    #[inline]
+    fn data_value_at(
+        &self,
+        inverse_transform: &Transform,
+        coordinate: Coordinate<f64>,
+    ) -> Option<&'a T> {
+        self.data_value_at_pixel(inverse_transform * coordinate)
+    }
 
-        let view = arr.view();
-        let converter = H3Converter::new(&view, &None, &transform, AxisOrder::XY);
-        let h3_resolution = converter
-            .nearest_h3_resolution(ResolutionSearchMode::SmallerThanPixel)
-            .unwrap();
-        let cell_map = converter.to_h3(h3_resolution, false).unwrap();
-        assert!(cell_map.contains_key(&OrderedFloat(f32::NAN)));
-        assert!(cell_map.contains_key(&OrderedFloat(1.0_f32)));
+    /// the value of the array element at the pixel coordinate `transformed`, in case it
+    /// contains data.
+    #[inline]
+    fn data_value_at_pixel(&self, transformed: Coordinate<f64>) -> Option<&'a T> {
+        let arr: &'a ArrayView2<'a, T> = self.arr;
+        let arr_coord = match self.axis_order {
+            AxisOrder::XY => [
+                transformed.x.floor() as usize,
+                transformed.y.floor() as usize,
+            ],
+            AxisOrder::YX => [
+                transformed.y.floor() as usize,
+                transformed.x.floor() as usize,
+            ],
+        };
+        arr.get(arr_coord)
+            .filter(|value| self.is_data(arr_coord, value))
+    }
+
+    /// the majority value of the pixels under the vertexes and the centroid of `cell`.
+    fn vertex_majority_value(
+        &self,
+        inverse_transform: &Transform,
+        boundary_builder: &mut CellBoundaryBuilder,
+        cell: &H3Cell,
+    ) -> Result<Option<&'a T>, Error> {
+        let centroid_value = self.data_value_at(inverse_transform, cell.to_coordinate()?);
+
+        // cells have at most 10 vertexes, so a linear search is sufficient
+        let mut counts: Vec<(&'a T, usize)> = Vec::with_capacity(11);
+        let vertex_values = boundary_builder
+            .iter_cell_boundary_vertices(cell, false)?
+            .filter_map(|vertex| self.data_value_at(inverse_transform, vertex));
+        for value in centroid_value.into_iter().chain(vertex_values) {
+            match counts.iter_mut().find(|(counted, _)| *counted == value) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((value, 1)),
+            }
+        }
+
+        // the centroid value comes first, so `max_by_key` - which returns the last maximum
+        // - can not be used here.
+        let mut majority: Option<(&'a T, usize)> = None;
+        for (value, count) in counts {
+            if majority.is_none_or(|(_, max_count)| count > max_count) {
+                majority = Some((value, count));
+            }
+        }
+        Ok(majority.map(|(value, _)| value))
+    }
+
+    /// Convert to h3 by aggregating the values of all pixels located within a cell to a
+    /// single value using the [`AggregationMethod`] `method`.
+    ///
+    /// In contrast to [`H3Converter::to_h3`] - which looks up the value of each cell at a single
+    /// location and may list a cell under multiple values when it is found in multiple boxes
+    /// of the array - each cell is guaranteed to be listed under exactly one value.
+    ///
+    /// Pixels are assigned to the cell containing their center, so the `h3_resolution`
+    /// should be chosen to result in cells larger than the pixels. Otherwise some cells
+    /// will not receive any value.
+    ///
+    /// The numeric methods `Mean` and `Sum` fail with [`Error::UnsupportedAggregationMethod`],
+    /// use [`H3Converter::to_h3_aggregated_f64`] for these.
+    pub fn to_h3_aggregated(
+        &self,
+        h3_resolution: u8,
+        compact: bool,
+        method: AggregationMethod,
+    ) -> Result<HashMap<&'a T, CompactedCellVec>, Error>
+    where
+        T: Ord,
+    {
+        if method.is_numeric() {
+            return Err(Error::UnsupportedAggregationMethod(method));
+        }
+        self.install(|| {
+            let mut grouped: HashMap<&'a T, CompactedCellVec> = HashMap::default();
+            for (cell, aggregate) in self.aggregate_pixels_by_cell(h3_resolution, method)? {
+                if let Some(value) = aggregate.value() {
+                    grouped
+                        .entry(value)
+                        .or_insert_with(CompactedCellVec::new)
+                        .add_cell(cell, false)?;
+                }
+            }
+            for compacted_vec in grouped.values_mut() {
+                if compact {
+                    compacted_vec.compact()?;
+                } else {
+                    compacted_vec.dedup()?;
+                }
+            }
+            Ok(grouped)
+        })
+    }
+
+    /// aggregate the values of the data pixels of each cell using `method`.
+    fn aggregate_pixels_by_cell(
+        &self,
+        h3_resolution: u8,
+        method: AggregationMethod,
+    ) -> Result<H3CellMap<ValueAggregate<'a, T>>, Error>
+    where
+        T: Ord,
+    {
+        self.fold_pixels_by_cell(
+            h3_resolution,
+            |aggregate: &mut ValueAggregate<'a, T>, value| aggregate.add(method, value),
+            |aggregate, other| aggregate.merge(method, other),
+        )
+    }
+
+    /// Assign the data pixels to the cells at `h3_resolution` containing their centers and
+    /// `fold` their values per cell.
+    ///
+    /// Only the parts of the rows within the boxes of the array containing data are visited.
+    /// The rows are folded in parallel, the partial results get combined using `merge` in the
+    /// order of the rows. So the pixels are folded in the order of the array and the result
+    /// does not depend on the number of threads.
+    fn fold_pixels_by_cell<A, F, M>(
+        &self,
+        h3_resolution: u8,
+        fold: F,
+        merge: M,
+    ) -> Result<H3CellMap<A>, Error>
+    where
+        A: Default + Send,
+        F: Fn(&mut A, &'a T) + Sync,
+        M: Fn(&mut A, A),
+    {
+        // the column ranges of the boxes within each row. The rows are the first axis
+        // of the array.
+        let mut col_ranges_by_row: Vec<Vec<Range<usize>>> = vec![Vec::new(); self.arr.nrows()];
+        for array_window in self.rects_with_data(self.window_size()) {
+            let pixel_rect = self.pixel_rect(&array_window);
+            let (rows, cols) = match self.axis_order {
+                AxisOrder::XY => (
+                    pixel_rect.min().x..=pixel_rect.max().x,
+                    pixel_rect.min().y..(pixel_rect.max().y + 1),
+                ),
+                AxisOrder::YX => (
+                    pixel_rect.min().y..=pixel_rect.max().y,
+                    pixel_rect.min().x..(pixel_rect.max().x + 1),
+                ),
+            };
+            for row in rows {
+                col_ranges_by_row[row].push(cols.clone());
+            }
+        }
+
+        let arr: &'a ArrayView2<'a, T> = self.arr;
+        let row_aggregates = col_ranges_by_row
+            .into_par_iter()
+            .enumerate()
+            .filter(|(_, col_ranges)| !col_ranges.is_empty())
+            .map(|(axis0_i, mut col_ranges)| {
+                let mut aggregates: H3CellMap<A> = H3CellMap::default();
+                col_ranges.sort_unstable_by_key(|cols| cols.start);
+
+                for cols in col_ranges {
+                    // use the centers of the pixels
+                    let mut xs = vec![0.0; cols.len()];
+                    let mut ys = vec![0.0; cols.len()];
+                    self.transform.row_coordinates(
+                        axis0_i,
+                        cols.clone(),
+                        self.axis_order,
+                        &mut xs,
+                        &mut ys,
+                    );
+
+                    for (i, axis1_i) in cols.enumerate() {
+                        let value = &arr[[axis0_i, axis1_i]];
+                        if !self.is_data([axis0_i, axis1_i], value) {
+                            continue;
+                        }
+                        let coordinate = Coordinate { x: xs[i], y: ys[i] };
+                        let cell = H3Cell::from_coordinate(coordinate, h3_resolution)?;
+                        fold(aggregates.entry(cell).or_default(), value);
+                    }
+                }
+                Ok(aggregates)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut aggregates: H3CellMap<A> = H3CellMap::default();
+        for row_aggregate in row_aggregates {
+            for (cell, aggregate) in row_aggregate {
+                merge(aggregates.entry(cell).or_default(), aggregate);
+            }
+        }
+        Ok(aggregates)
+    }
+
+    /// Estimate the number of cells and the peak memory usage of a conversion
+    /// to `h3_resolution` using [`H3Converter::to_h3`] without performing it.
+    ///
+    /// The runtime does not depend on the size of the array: The boxes containing data
+    /// are searched on a subsampled view of large arrays and the fraction of the data pixels
+    /// within each box is estimated from a bounded number of sampled pixels. Small
+    /// isolated clusters of data may be missed by the subsampling.
+    pub fn estimate(&self, h3_resolution: u8) -> Result<ConversionEstimate, Error> {
+        let rect_size = self.window_size();
+        let mut distinct_values = HashSet::default();
+        let mut num_windows = 0;
+
+        let boxes = self
+            .estimate_boxes_containing_data()
+            .into_iter()
+            .map(|rect| -> Result<BoxEstimate, Error> {
+                let width = rect.max().x - rect.min().x + 1;
+                let height = rect.max().y - rect.min().y + 1;
+                num_windows += width.div_ceil(rect_size) * height.div_ceil(rect_size);
+
+                let data_fraction = self.sample_data_fraction(&rect, &mut distinct_values);
+                let num_pixels = width * height;
+
+                // the box in geographical coordinates
+                let box_geo = self.transform
+                    * &Rect::new(
+                        Coordinate {
+                            x: rect.min().x as f64,
+                            y: rect.min().y as f64,
+                        },
+                        // add 1 to the max coordinate to include the whole last pixel
+                        Coordinate {
+                            x: (rect.max().x + 1) as f64,
+                            y: (rect.max().y + 1) as f64,
+                        },
+                    );
+                let data_area_m2 = area_squaremeters_rect(&box_geo) * data_fraction;
+                let cell_area_m2 =
+                    H3Cell::from_coordinate(box_geo.center(), h3_resolution)?.area_m2()?;
+
+                Ok(BoxEstimate {
+                    rect,
+                    num_pixels,
+                    data_pixels: (num_pixels as f64 * data_fraction).round() as usize,
+                    data_area_m2,
+                    expected_cells: (data_area_m2 / cell_area_m2).ceil() as usize,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ConversionEstimate::new(
+            h3_resolution,
+            boxes,
+            distinct_values.len(),
+            num_windows,
+        ))
+    }
+
+    /// boxes containing data in array coordinates. Large arrays are subsampled to
+    /// visit at most `BOX_DETECTION_MAX_PIXELS` pixels.
+    fn estimate_boxes_containing_data(&self) -> Vec<Rect<usize>> {
+        let x_size = self.arr.shape()[self.axis_order.x_axis()];
+        let y_size = self.arr.shape()[self.axis_order.y_axis()];
+        if x_size == 0 || y_size == 0 {
+            return vec![];
+        }
+
+        let step = sampling_step(x_size * y_size, BOX_DETECTION_MAX_PIXELS);
+        let subsampled = self.arr.slice(s![..;step, ..;step]);
+        let boxes = match (self.validity_mask.as_ref(), self.has_nodata()) {
+            (Some(validity), _) => {
+                let indicator = data_indicator(
+                    &subsampled,
+                    &validity.slice(s![..;step, ..;step]),
+                    |value| self.is_nodata(value),
+                );
+                self.find_boxes(&indicator.view(), &|valid: &bool| !*valid)
+            }
+            (None, true) => self.find_boxes(&subsampled, &|value: &T| self.is_nodata(value)),
+            (None, false) => {
+                return vec![Rect::new(
+                    Coordinate { x: 0, y: 0 },
+                    Coordinate {
+                        x: x_size - 1,
+                        y: y_size - 1,
+                    },
+                )]
+            }
+        };
+
+        boxes
+            .into_iter()
+            .map(|rect| {
+                Rect::new(
+                    Coordinate {
+                        x: rect.min().x * step,
+                        y: rect.min().y * step,
+                    },
+                    Coordinate {
+                        x: min(rect.max().x * step + step - 1, x_size - 1),
+                        y: min(rect.max().y * step + step - 1, y_size - 1),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// fraction of the pixels within `rect` containing data. Visits
+    /// about `MAX_SAMPLES_PER_BOX` evenly spaced pixels.
+    fn sample_data_fraction(
+        &self,
+        rect: &Rect<usize>,
+        distinct_values: &mut HashSet<&'a T>,
+    ) -> f64 {
+        let width = rect.max().x - rect.min().x + 1;
+        let height = rect.max().y - rect.min().y + 1;
+        let step = sampling_step(width * height, MAX_SAMPLES_PER_BOX);
+
+        let mut num_sampled = 0_usize;
+        let mut num_data = 0_usize;
+        for x in (rect.min().x..=rect.max().x).step_by(step) {
+            for y in (rect.min().y..=rect.max().y).step_by(step) {
+                let arr_coord = match self.axis_order {
+                    AxisOrder::XY => [x, y],
+                    AxisOrder::YX => [y, x],
+                };
+                if let Some(value) = self.arr.get(arr_coord) {
+                    num_sampled += 1;
+                    if self.is_data(arr_coord, value) {
+                        num_data += 1;
+                        distinct_values.insert(value);
+                    }
+                }
+            }
+        }
+
+        if num_sampled == 0 {
+            0.0
+        } else {
+            num_data as f64 / num_sampled as f64
+        }
+    }
+}
+
+/// step along both axis to visit at most about `max_visited` of `num_pixels`.
+fn sampling_step(num_pixels: usize, max_visited: usize) -> usize {
+    max(
+        (num_pixels as f64 / max_visited as f64).sqrt().ceil() as usize,
+        1,
+    )
+}
+
+impl<'a, T> H3Converter<'a, T>
+where
+    T: Sized + PartialEq + Sync + Eq + Hash + Copy + Into<f64>,
+{
+    /// Convert to h3 by aggregating the values of all pixels located within a cell to
+    /// their mean.
+    ///
+    /// In contrast to [`H3Converter::to_h3`] the values do not get grouped, instead
+    /// each raw value is converted to its physical value using the `scaling` before
+    /// being aggregated. The `nodata_value` is compared to the raw value, so pixels
+    /// containing the nodata-sentinel are skipped before any scaling is applied. Like
+    /// [`H3Converter::to_h3`] only the boxes of the array containing data are visited.
+    ///
+    /// Pixels are assigned to the cell containing their center, so the `h3_resolution`
+    /// should be chosen to result in cells larger than the pixels. Otherwise some cells
+    /// will not receive any value.
+    pub fn to_h3_mean(
+        &self,
+        h3_resolution: u8,
+        scaling: &ValueScaling,
+    ) -> Result<H3CellMap<f64>, Error> {
+        self.install(|| {
+            Ok(self
+                .sum_pixels_by_cell(h3_resolution, scaling)?
+                .into_iter()
+                .map(|(cell, (value_sum, count))| (cell, value_sum / count as f64))
+                .collect())
+        })
+    }
+
+    /// Like [`H3Converter::to_h3_mean`], but aggregating the scaled values of the pixels
+    /// located within a cell to their sum.
+    pub fn to_h3_sum(
+        &self,
+        h3_resolution: u8,
+        scaling: &ValueScaling,
+    ) -> Result<H3CellMap<f64>, Error> {
+        self.install(|| {
+            Ok(self
+                .sum_pixels_by_cell(h3_resolution, scaling)?
+                .into_iter()
+                .map(|(cell, (value_sum, _))| (cell, value_sum))
+                .collect())
+        })
+    }
+
+    /// the sum of the scaled values and the number of the data pixels of each cell.
+    fn sum_pixels_by_cell(
+        &self,
+        h3_resolution: u8,
+        scaling: &ValueScaling,
+    ) -> Result<H3CellMap<(f64, usize)>, Error> {
+        self.fold_pixels_by_cell(
+            h3_resolution,
+            |sum: &mut (f64, usize), value| {
+                sum.0 += scaling.apply((*value).into());
+                sum.1 += 1;
+            },
+            |sum, (value_sum, count)| {
+                sum.0 += value_sum;
+                sum.1 += count;
+            },
+        )
+    }
+
+    /// Like [`H3Converter::to_h3_aggregated`], but also supporting the numeric
+    /// [`AggregationMethod`]s `Mean` and `Sum`. The values of all methods are returned as `f64`.
+    pub fn to_h3_aggregated_f64(
+        &self,
+        h3_resolution: u8,
+        method: AggregationMethod,
+    ) -> Result<H3CellMap<f64>, Error>
+    where
+        T: Ord,
+    {
+        self.install(|| {
+            let scaling = ValueScaling::default();
+            Ok(match method {
+                AggregationMethod::Mean => self
+                    .sum_pixels_by_cell(h3_resolution, &scaling)?
+                    .into_iter()
+                    .map(|(cell, (value_sum, count))| (cell, value_sum / count as f64))
+                    .collect(),
+                AggregationMethod::Sum => self
+                    .sum_pixels_by_cell(h3_resolution, &scaling)?
+                    .into_iter()
+                    .map(|(cell, (value_sum, _))| (cell, value_sum))
+                    .collect(),
+                _ => self
+                    .aggregate_pixels_by_cell(h3_resolution, method)?
+                    .into_iter()
+                    .filter_map(|(cell, aggregate)| {
+                        aggregate.value().map(|value| (cell, (*value).into()))
+                    })
+                    .collect(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{Coordinate, Rect};
+    use h3ron::collections::H3CellMap;
+    use h3ron::{H3Cell, Index, ToCoordinate};
+
+    use crate::array::{find_boxes_containing_data, find_boxes_containing_data_recursive};
+    use crate::{
+        AggregationMethod, AnchorStrategy, AxisOrder, BoxDetection, CellSink, ColumnarSink, Error,
+        FailureReport, H3Converter, JitterOptions, MaskRule, NodataCheck, ResolutionSearchMode,
+        Transform, ValueScaling,
+    };
+
+    /// zero out all `boxes` in a copy of `arr` and return the number of pixels within the boxes.
+    fn assert_boxes_cover_data(arr: &ndarray::Array2<i32>, boxes: &[Rect<usize>]) -> usize {
+        let mut arr_copy = arr.clone();
+        let mut n_elements_in_boxes = 0;
+        for rect in boxes {
+            n_elements_in_boxes +=
+                (rect.max().x - rect.min().x + 1) * (rect.max().y - rect.min().y + 1);
+
+            for x in rect.min().x..=rect.max().x {
+                for y in rect.min().y..=rect.max().y {
+                    arr_copy[(y, x)] = 0;
+                }
+            }
+        }
+
+        // all elements should have been removed
+        assert_eq!(arr_copy.sum(), 0);
+        n_elements_in_boxes
+    }
+
+    #[test]
+    fn test_find_boxes_containing_data() {
+        let arr = array![
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0],
+            [0, 1, 1, 0, 0, 0, 0, 1, 1, 1, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0],
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0],
+            [0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 1],
+            [0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 1, 1],
+        ];
+        let n_elements = arr.shape()[0] * arr.shape()[1];
+
+        let boxes = find_boxes_containing_data(&arr.view(), &|v: &i32| *v == 0, &AxisOrder::YX);
+        let n_elements_in_boxes = assert_boxes_cover_data(&arr, &boxes);
+
+        // there should be far less indexes to visit now
+        assert!(n_elements_in_boxes < (n_elements / 2));
+
+        for axis_order in [AxisOrder::YX, AxisOrder::XY] {
+            let boxes = find_boxes_containing_data_recursive(
+                &arr.view(),
+                &|v: &i32| *v == 0,
+                &axis_order,
+                1,
+            );
+            let boxes = match axis_order {
+                AxisOrder::YX => boxes,
+                AxisOrder::XY => boxes
+                    .into_iter()
+                    .map(|rect| {
+                        Rect::new(
+                            Coordinate {
+                                x: rect.min().y,
+                                y: rect.min().x,
+                            },
+                            Coordinate {
+                                x: rect.max().y,
+                                y: rect.max().x,
+                            },
+                        )
+                    })
+                    .collect(),
+            };
+            // single pixels containing data
+            assert_eq!(
+                assert_boxes_cover_data(&arr, &boxes),
+                arr.iter().filter(|v| **v != 0).count()
+            );
+        }
+    }
+
+    #[test]
+    fn find_boxes_containing_data_diagonal() {
+        // every row and every column contains data
+        let arr = ndarray::Array2::from_shape_fn((64, 64), |(y, x)| i32::from(x == y));
+        let n_elements = arr.shape()[0] * arr.shape()[1];
+
+        let boxes = find_boxes_containing_data(&arr.view(), &|v: &i32| *v == 0, &AxisOrder::YX);
+        assert_eq!(boxes.len(), 1);
+        assert_eq!(assert_boxes_cover_data(&arr, &boxes), n_elements);
+
+        let boxes = find_boxes_containing_data_recursive(
+            &arr.view(),
+            &|v: &i32| *v == 0,
+            &AxisOrder::YX,
+            4,
+        );
+        let n_elements_in_boxes = assert_boxes_cover_data(&arr, &boxes);
+        assert!(n_elements_in_boxes <= 16 * 4 * 4);
+        for rect in boxes {
+            assert!(rect.max().x - rect.min().x < 4);
+            assert!(rect.max().y - rect.min().y < 4);
+        }
+    }
+
+    #[test]
+    fn to_h3_with_recursive_box_detection() {
+        let arr = ndarray::Array2::from_shape_fn((300, 300), |(y, x)| {
+            if x.abs_diff(y) < 2 || (x + y == 299) {
+                1_u8 + (x / 100) as u8
+            } else {
+                0
+            }
+        });
+        let view = arr.view();
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        let nodata = Some(0_u8);
+        let simple = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let recursive = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+            .with_box_detection(BoxDetection::Recursive { min_box_size: 8 });
+
+        let area = |rects: Vec<geo_types::Rect<f64>>| -> f64 {
+            rects.iter().map(|rect| rect.width() * rect.height()).sum()
+        };
+        assert!(area(recursive.rects_with_data(30)) < area(simple.rects_with_data(30)) * 0.5);
+
+        let h3_resolution = simple
+            .nearest_h3_resolution(ResolutionSearchMode::SmallerThanPixel)
+            .unwrap();
+        let expected = simple.to_h3(h3_resolution, true).unwrap();
+        let result = recursive.to_h3(h3_resolution, true).unwrap();
+        assert_eq!(result.len(), expected.len());
+        for (value, cells) in expected.iter() {
+            let mut expected_cells: Vec<_> = cells.iter_compacted_cells().collect();
+            let mut result_cells: Vec<_> =
+                result.get(value).unwrap().iter_compacted_cells().collect();
+            expected_cells.sort_unstable();
+            result_cells.sort_unstable();
+            assert_eq!(result_cells, expected_cells);
+        }
+    }
+
+    #[test]
+    fn preserve_nan_values() {
+        use ordered_float::OrderedFloat;
+        #[rustfmt::skip]
+        let arr = array![
+            [OrderedFloat(f32::NAN), OrderedFloat(1.0_f32)],
+            [OrderedFloat(f32::NAN), OrderedFloat(1.0_f32)],
+        ];
+        let transform = Transform::from_gdal(&[11.0, 1.0, 0.0, 10.0, 1.2, 0.2]);
+
+        let view = arr.view();
+        let converter = H3Converter::new(&view, &None, &transform, AxisOrder::XY);
+        let h3_resolution = converter
+            .nearest_h3_resolution(ResolutionSearchMode::SmallerThanPixel)
+            .unwrap();
+        let cell_map = converter.to_h3(h3_resolution, false).unwrap();
+        assert!(cell_map.contains_key(&OrderedFloat(f32::NAN)));
+        assert!(cell_map.contains_key(&OrderedFloat(1.0_f32)));
+    }
+
+    /// array of raw `i16` values with a `-32768` sentinel, located completely within a single
+    /// cell at resolution 3.
+    fn scaled_i16_fixture() -> (ndarray::Array2<i16>, Transform, H3Cell) {
+        #[rustfmt::skip]
+        let arr = array![
+            [100_i16, 200, -32768, 300],
+            [-32768, 400, 500, 600],
+            [700, -32768, 800, 900],
+            [1000, 1100, 1200, -32768],
+        ];
+        let cell = H3Cell::from_coordinate((10.0, 20.0).into(), 3).unwrap();
+        let center = cell.to_coordinate().unwrap();
+        let transform = Transform::from_gdal(&[center.x, 0.001, 0.0, center.y, 0.0, -0.001]);
+        (arr, transform, cell)
+    }
+
+    /// mean of the raw values of the fixture with the sentinel masked
+    fn scaled_i16_fixture_raw_mean() -> f64 {
+        let (arr, _, _) = scaled_i16_fixture();
+        let valid: Vec<f64> = arr
+            .iter()
+            .filter(|v| **v != -32768)
+            .map(|v| f64::from(*v))
+            .collect();
+        valid.iter().sum::<f64>() / valid.len() as f64
+    }
+
+    #[test]
+    fn to_h3_mean_scaled_i16() {
+        let (arr, transform, cell) = scaled_i16_fixture();
+        let view = arr.view();
+        let nodata = Some(-32768_i16);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+
+        let cell_map = converter
+            .to_h3_mean(3, &ValueScaling::new(Some(0.01), Some(-1.0)))
+            .unwrap();
+        assert_eq!(cell_map.len(), 1);
+        assert_relative_eq!(
+            *cell_map.get(&cell).unwrap(),
+            scaled_i16_fixture_raw_mean() * 0.01 - 1.0,
+            epsilon = 1e-9
+        );
+    }
+
+    #[test]
+    fn to_h3_mean_single_scaling_parameter() {
+        let (arr, transform, cell) = scaled_i16_fixture();
+        let view = arr.view();
+        let nodata = Some(-32768_i16);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let raw_mean = scaled_i16_fixture_raw_mean();
+
+        let only_scale = converter
+            .to_h3_mean(3, &ValueScaling::new(Some(0.01), None))
+            .unwrap();
+        assert_relative_eq!(
+            *only_scale.get(&cell).unwrap(),
+            raw_mean * 0.01,
+            epsilon = 1e-9
+        );
+
+        let only_offset = converter
+            .to_h3_mean(3, &ValueScaling::new(None, Some(5.0)))
+            .unwrap();
+        assert_relative_eq!(
+            *only_offset.get(&cell).unwrap(),
+            raw_mean + 5.0,
+            epsilon = 1e-9
+        );
+    }
+
+    /// array of raw `i16` values with a `-32768` sentinel spanning multiple cells at
+    /// resolution 7, and the per-cell mean and sum of the scaled values computed by visiting
+    /// every pixel.
+    fn scaled_i16_multi_cell_fixture(
+        scaling: &ValueScaling,
+    ) -> (ndarray::Array2<i16>, Transform, H3CellMap<(f64, f64)>) {
+        let arr = ndarray::Array2::from_shape_fn((60, 80), |(row, col)| {
+            // the empty columns split the array into separate boxes
+            if (row * 7 + col * 3) % 11 == 0 || (30..40).contains(&col) {
+                -32768_i16
+            } else {
+                (row * 100 + col) as i16
+            }
+        });
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 20.0, 0.0, -0.001]);
+
+        let mut sums: H3CellMap<(f64, usize)> = H3CellMap::default();
+        for ((row, col), value) in arr.indexed_iter() {
+            if *value == -32768 {
+                continue;
+            }
+            let coordinate = &transform
+                * Coordinate {
+                    x: col as f64 + 0.5,
+                    y: row as f64 + 0.5,
+                };
+            let sum = sums
+                .entry(H3Cell::from_coordinate(coordinate, 7).unwrap())
+                .or_default();
+            sum.0 += scaling.apply(f64::from(*value));
+            sum.1 += 1;
+        }
+        let expected = sums
+            .into_iter()
+            .map(|(cell, (sum, count))| (cell, (sum / count as f64, sum)))
+            .collect();
+        (arr, transform, expected)
+    }
+
+    #[test]
+    fn to_h3_mean_and_sum_multiple_cells() {
+        let scaling = ValueScaling::new(Some(0.01), Some(-1.0));
+        let (arr, transform, expected) = scaled_i16_multi_cell_fixture(&scaling);
+        assert!(expected.len() > 10);
+        let view = arr.view();
+        let nodata = Some(-32768_i16);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        assert!(converter.boxes_containing_data().unwrap().len() > 1);
+
+        let means = converter.to_h3_mean(7, &scaling).unwrap();
+        let sums = converter.to_h3_sum(7, &scaling).unwrap();
+        assert_eq!(means.len(), expected.len());
+        assert_eq!(sums.len(), expected.len());
+        for (cell, (expected_mean, expected_sum)) in expected {
+            assert_relative_eq!(*means.get(&cell).unwrap(), expected_mean, epsilon = 1e-6);
+            assert_relative_eq!(*sums.get(&cell).unwrap(), expected_sum, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn uncompacted_conversion_equals_uncompacted_compaction() {
+        let mut arr = ndarray::Array2::<u8>::zeros((100, 100));
+        for ((y, x), value) in arr.indexed_iter_mut() {
+            let dx = x as f64 - 50.0;
+            let dy = y as f64 - 50.0;
+            if (dx * dx + dy * dy).sqrt() < 40.0 {
+                *value = if x < 60 { 1 } else { 2 };
+            }
+        }
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let h3_resolution = 11;
+
+        let uncompacted = converter.to_h3(h3_resolution, false).unwrap();
+        let compacted = converter.to_h3(h3_resolution, true).unwrap();
+        assert_eq!(uncompacted.len(), 2);
+        assert_eq!(compacted.len(), uncompacted.len());
+
+        for (value, compacted_vec) in compacted.iter() {
+            let uncompacted_vec = uncompacted.get(value).unwrap();
+            assert_eq!(
+                uncompacted_vec
+                    .get_compacted_cells_at_resolution(h3_resolution)
+                    .len(),
+                uncompacted_vec.len()
+            );
+            assert!(compacted_vec.len() < uncompacted_vec.len());
+
+            let mut expected = compacted_vec.uncompact(h3_resolution).unwrap();
+            let mut actual = uncompacted_vec.uncompact(h3_resolution).unwrap();
+            expected.sort_unstable();
+            actual.sort_unstable();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn boxes_containing_data_cover_all_data_pixels() {
+        let mut arr = ndarray::Array2::<u8>::zeros((120, 80));
+        for ((y, x), value) in arr.indexed_iter_mut() {
+            if (x > 5 && x < 20 && y > 10 && y < 30) || (x == 70 && y > 90) || (x, y) == (0, 119) {
+                *value = 1;
+            }
+        }
+        let transform = Transform::from_gdal(&[10.0, 0.01, 0.0, 50.0, 0.0, -0.01]);
+        let nodata = Some(0_u8);
+
+        for axis_order in [AxisOrder::YX, AxisOrder::XY] {
+            let view = arr.view();
+            let converter = H3Converter::new(&view, &nodata, &transform, axis_order);
+            let boxes = converter.boxes_containing_data().unwrap();
+            assert!(!boxes.is_empty());
+
+            for ((i0, i1), value) in arr.indexed_iter() {
+                let (x, y) = match axis_order {
+                    AxisOrder::YX => (i1, i0),
+                    AxisOrder::XY => (i0, i1),
+                };
+                let covered = boxes
+                    .iter()
+                    .any(|b| b.min().x <= x && x <= b.max().x && b.min().y <= y && y <= b.max().y);
+                if *value != 0 {
+                    assert!(covered, "pixel {:?} not covered", (x, y));
+                }
+            }
+
+            let coordinate_boxes = converter.boxes_containing_data_coordinates().unwrap();
+            assert_eq!(coordinate_boxes.len(), boxes.len());
+            for (pixel_box, coordinate_box) in boxes.iter().zip(coordinate_boxes.iter()) {
+                let upper_left = &transform
+                    * Coordinate {
+                        x: pixel_box.min().x as f64,
+                        y: pixel_box.min().y as f64,
+                    };
+                let lower_right = &transform
+                    * Coordinate {
+                        x: (pixel_box.max().x + 1) as f64,
+                        y: (pixel_box.max().y + 1) as f64,
+                    };
+                assert_relative_eq!(coordinate_box.min().x, upper_left.x, epsilon = 1e-9);
+                assert_relative_eq!(coordinate_box.max().y, upper_left.y, epsilon = 1e-9);
+                assert_relative_eq!(coordinate_box.max().x, lower_right.x, epsilon = 1e-9);
+                assert_relative_eq!(coordinate_box.min().y, lower_right.y, epsilon = 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn min_compacted_resolution() {
+        let mut arr = ndarray::Array2::<u8>::zeros((100, 100));
+        for ((y, x), value) in arr.indexed_iter_mut() {
+            if x > 10 && y > 10 {
+                *value = if x < 60 { 1 } else { 2 };
+            }
+        }
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let h3_resolution = 11;
+
+        let unrestricted = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+            .to_h3(h3_resolution, true)
+            .unwrap();
+        assert!(unrestricted.values().any(|compacted_vec| compacted_vec
+            .iter_compacted_cells()
+            .any(|cell| cell.resolution() < 9)));
+
+        let min_resolution = 9;
+        let restricted = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+            .with_min_compacted_resolution(min_resolution)
+            .to_h3(h3_resolution, true)
+            .unwrap();
+        assert_eq!(restricted.len(), unrestricted.len());
+        for (value, compacted_vec) in restricted.iter() {
+            assert!(compacted_vec
+                .iter_compacted_cells()
+                .all(|cell| cell.resolution() >= min_resolution));
+
+            let mut expected = unrestricted
+                .get(value)
+                .unwrap()
+                .uncompact(h3_resolution)
+                .unwrap();
+            let mut actual = compacted_vec.uncompact(h3_resolution).unwrap();
+            expected.sort_unstable();
+            actual.sort_unstable();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn to_h3_is_deterministic() {
+        let (arr, transform) = ones_fixture();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        for (compact, min_resolution) in [(false, None), (true, None), (true, Some(9))] {
+            let convert = || {
+                let mut converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+                if let Some(min_resolution) = min_resolution {
+                    converter = converter.with_min_compacted_resolution(min_resolution);
+                }
+                converter.to_h3(11, compact).unwrap()
+            };
+            let first = convert();
+            let second = convert();
+            assert!(!first.is_empty());
+            assert_eq!(first.len(), second.len());
+            for (value, compacted_vec) in first.iter() {
+                assert!(compacted_vec.is_sorted());
+                let other = second.get(value).unwrap();
+                assert_eq!(compacted_vec, other);
+                assert_eq!(compacted_vec.to_bytes(), other.to_bytes());
+            }
+        }
+    }
+
+    /// number of cells created by a real conversion
+    fn converted_num_cells(converter: &H3Converter<u8>, h3_resolution: u8, compact: bool) -> usize {
+        converter
+            .to_h3(h3_resolution, compact)
+            .unwrap()
+            .values()
+            .map(|compacted_vec| compacted_vec.len())
+            .sum()
+    }
+
+    fn assert_within_factor_2(estimated: usize, actual: usize) {
+        assert!(
+            estimated * 2 >= actual && estimated <= actual * 2,
+            "estimated {} vs actual {}",
+            estimated,
+            actual
+        );
+    }
+
+    #[test]
+    fn estimate_matches_conversion() {
+        // large enough to get subsampled during the box detection
+        let size = 1500;
+        let mut arr = ndarray::Array2::<u8>::zeros((size, size));
+        for ((y, x), value) in arr.indexed_iter_mut() {
+            let dx = x as f64 - 500.0;
+            let dy = y as f64 - 500.0;
+            if (dx * dx + dy * dy).sqrt() < 300.0 {
+                *value = 1;
+            } else if x > 1100 && y > 1000 && y < 1300 {
+                *value = 2;
+            }
+        }
+        let transform = Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]);
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+
+        for h3_resolution in [10, 12] {
+            let estimate = converter.estimate(h3_resolution).unwrap();
+            assert_eq!(estimate.h3_resolution, h3_resolution);
+            assert_eq!(estimate.sampled_distinct_values, 2);
+
+            let actual_data_pixels = arr.iter().filter(|v| **v != 0).count();
+            assert_within_factor_2(estimate.data_pixels, actual_data_pixels);
+
+            assert_within_factor_2(
+                estimate.expected_cells,
+                converted_num_cells(&converter, h3_resolution, false),
+            );
+            assert!(
+                converted_num_cells(&converter, h3_resolution, true)
+                    <= estimate.compacted_cells_upper_bound * 2
+            );
+            assert!(estimate.peak_memory_bytes > estimate.expected_cells * 8);
+        }
+    }
+
+    #[test]
+    fn estimate_r_tiff() {
+        let filename = format!("{}/../data/r.tiff", env!("CARGO_MANIFEST_DIR"));
+        let dataset = gdal::Dataset::open(&filename).unwrap();
+        let transform = Transform::from_gdal(&dataset.geo_transform().unwrap());
+        let band = dataset.rasterband(1).unwrap();
+        let arr = band
+            .read_as_array::<u8>((0, 0), band.size(), band.size(), None)
+            .unwrap();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let h3_resolution = converter
+            .nearest_h3_resolution(ResolutionSearchMode::MinDiff)
+            .unwrap();
+
+        let estimate = converter.estimate(h3_resolution).unwrap();
+        assert!(!estimate.boxes.is_empty());
+        assert_within_factor_2(
+            estimate.expected_cells,
+            converted_num_cells(&converter, h3_resolution, false),
+        );
+    }
+
+    #[test]
+    fn num_threads_r_tiff() {
+        let filename = format!("{}/../data/r.tiff", env!("CARGO_MANIFEST_DIR"));
+        let dataset = gdal::Dataset::open(&filename).unwrap();
+        let transform = Transform::from_gdal(&dataset.geo_transform().unwrap());
+        let band = dataset.rasterband(1).unwrap();
+        let arr = band
+            .read_as_array::<u8>((0, 0), band.size(), band.size(), None)
+            .unwrap();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let h3_resolution = 9;
+
+        for compact in [false, true] {
+            let sequential = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+                .with_num_threads(1)
+                .to_h3(h3_resolution, compact)
+                .unwrap();
+            assert!(!sequential.is_empty());
+
+            for num_threads in [None, Some(3)] {
+                let mut converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+                if let Some(num_threads) = num_threads {
+                    converter = converter.with_num_threads(num_threads);
+                }
+                let parallel = converter.to_h3(h3_resolution, compact).unwrap();
+                assert_eq!(parallel.len(), sequential.len());
+                for (value, compacted_vec) in sequential.iter() {
+                    let mut expected: Vec<_> = compacted_vec.iter_compacted_cells().collect();
+                    let mut actual: Vec<_> = parallel
+                        .get(value)
+                        .unwrap()
+                        .iter_compacted_cells()
+                        .collect();
+                    expected.sort_unstable();
+                    actual.sort_unstable();
+                    assert_eq!(expected, actual);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn f32_coords_r_tiff() {
+        let filename = format!("{}/../data/r.tiff", env!("CARGO_MANIFEST_DIR"));
+        let dataset = gdal::Dataset::open(&filename).unwrap();
+        let transform = Transform::from_gdal(&dataset.geo_transform().unwrap());
+        let band = dataset.rasterband(1).unwrap();
+        let arr = band
+            .read_as_array::<u8>((0, 0), band.size(), band.size(), None)
+            .unwrap();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+
+        for h3_resolution in [7, 8, 9] {
+            let f64_cells = converted_cell_values(&converter, h3_resolution);
+            let mut f32_cells = h3ron::collections::H3CellMap::default();
+            for (value, compacted_vec) in converter.to_h3_f32_coords(h3_resolution, false).unwrap()
+            {
+                for cell in compacted_vec.iter_uncompacted_cells(h3_resolution) {
+                    f32_cells.insert(cell.unwrap(), *value);
+                }
+            }
+            assert!(!f64_cells.is_empty());
+            assert_eq!(f32_cells, f64_cells);
+        }
+
+        assert!(matches!(
+            converter.to_h3_f32_coords(13, false),
+            Err(Error::ResolutionTooFineForF32Coordinates(13))
+        ));
+    }
+
+    #[test]
+    fn estimate_without_nodata() {
+        let arr = ndarray::Array2::<u8>::ones((100, 100));
+        let transform = Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]);
+        let view = arr.view();
+        let converter = H3Converter::new(&view, &None, &transform, AxisOrder::YX);
+
+        let estimate = converter.estimate(12).unwrap();
+        assert_eq!(estimate.boxes.len(), 1);
+        assert_eq!(estimate.data_pixels, 100 * 100);
+        assert_eq!(estimate.sampled_distinct_values, 1);
+        assert_within_factor_2(
+            estimate.expected_cells,
+            converted_num_cells(&converter, 12, false),
+        );
+    }
+
+    fn masked_fixture() -> (ndarray::Array2<u8>, ndarray::Array2<u8>, Transform) {
+        let values = ndarray::Array2::<u8>::ones((100, 100));
+        // only the left half is valid
+        let mask =
+            ndarray::Array2::from_shape_fn((100, 100), |(_, x)| if x < 50 { 255_u8 } else { 0 });
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        (values, mask, transform)
+    }
+
+    #[test]
+    fn validity_mask_converts_unmasked_half() {
+        let (values, mask, transform) = masked_fixture();
+        let view = values.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+            .with_validity_mask(&mask.view(), &MaskRule::NonZero)
+            .unwrap();
+        let unmasked_converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+
+        let h3_resolution = converter
+            .nearest_h3_resolution(ResolutionSearchMode::MinDiff)
+            .unwrap();
+        let cells = converter.to_h3(h3_resolution, false).unwrap();
+        let unmasked_cells = unmasked_converter.to_h3(h3_resolution, false).unwrap();
+
+        let inverse_transform = transform.invert().unwrap();
+        let num_cells = cells.get(&1).unwrap().len();
+        for cell in cells.get(&1).unwrap().iter_uncompacted_cells(h3_resolution) {
+            let coord = &inverse_transform * cell.unwrap().to_coordinate().unwrap();
+            assert!(coord.x < 50.0);
+        }
+        let num_unmasked_cells = unmasked_cells.get(&1).unwrap().len();
+        assert!(num_cells * 10 > num_unmasked_cells * 4);
+        assert!(num_cells * 10 < num_unmasked_cells * 6);
+
+        // the boxes shrink accordingly
+        let area = |rects: Vec<geo_types::Rect<f64>>| -> f64 {
+            rects.iter().map(|rect| rect.width() * rect.height()).sum()
+        };
+        let masked_area = area(converter.rects_with_data(10));
+        let unmasked_area = area(unmasked_converter.rects_with_data(10));
+        assert!(masked_area < unmasked_area * 0.6);
+    }
+
+    #[test]
+    fn validity_mask_bits_set() {
+        let (values, _, transform) = masked_fixture();
+        let flags = ndarray::Array2::from_shape_fn((100, 100), |(y, x)| {
+            if (x + y) % 2 == 0 {
+                0x0c_u16
+            } else {
+                0x04
+            }
+        });
+        let view = values.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+            .with_validity_mask(&flags.view(), &MaskRule::BitsSet(0x0c))
+            .unwrap();
+
+        for ((y, x), value) in values.indexed_iter() {
+            assert_eq!(converter.is_data([y, x], value), (x + y) % 2 == 0);
+        }
+    }
+
+    #[test]
+    fn validity_mask_shape_mismatch() {
+        let (values, _, transform) = masked_fixture();
+        let mask = ndarray::Array2::<u8>::ones((10, 10));
+        let view = values.view();
+        let nodata = Some(0_u8);
+        let result = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+            .with_validity_mask(&mask.view(), &MaskRule::NonZero);
+        assert!(matches!(result, Err(Error::MaskShapeMismatch)));
+    }
+
+    /// the value of each cell of a non-compacted conversion
+    fn converted_cell_values<T>(
+        converter: &H3Converter<T>,
+        h3_resolution: u8,
+    ) -> h3ron::collections::H3CellMap<T>
+    where
+        T: Sized + PartialEq + Sync + Eq + std::hash::Hash + Copy,
+    {
+        let mut cell_values = h3ron::collections::H3CellMap::default();
+        for (value, compacted_vec) in converter.to_h3(h3_resolution, false).unwrap() {
+            for cell in compacted_vec.iter_uncompacted_cells(h3_resolution) {
+                cell_values.insert(cell.unwrap(), *value);
+            }
+        }
+        cell_values
+    }
+
+    #[test]
+    fn anchor_vertex_majority_captures_thin_line() {
+        let mut arr = ndarray::Array2::<u8>::zeros((200, 200));
+        for i in 0..200 {
+            arr[[i, i]] = 1;
+        }
+        let transform = Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]);
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let h3_resolution = 11;
+
+        let centroid_cells = converted_cell_values(
+            &H3Converter::new(&view, &nodata, &transform, AxisOrder::YX),
+            h3_resolution,
+        );
+        let vertex_cells = converted_cell_values(
+            &H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+                .with_anchor_strategy(AnchorStrategy::VertexMajority),
+            h3_resolution,
+        );
+
+        assert!(!centroid_cells.is_empty());
+        assert!(vertex_cells.len() > centroid_cells.len() * 2);
+        for cell in centroid_cells.keys() {
+            assert_eq!(vertex_cells.get(cell), Some(&1));
+        }
+    }
+
+    #[test]
+    fn anchor_center_child_equals_centroid_within_pixel() {
+        // unique values for each pixel
+        let arr = ndarray::Array2::from_shape_fn((100, 100), |(y, x)| (y * 100 + x) as u16);
+        let transform = Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]);
+        let view = arr.view();
+        let h3_resolution = 11;
+
+        let centroid_cells = converted_cell_values(
+            &H3Converter::new(&view, &None, &transform, AxisOrder::YX),
+            h3_resolution,
+        );
+        let center_child_cells = converted_cell_values(
+            &H3Converter::new(&view, &None, &transform, AxisOrder::YX)
+                .with_anchor_strategy(AnchorStrategy::CenterChildAt(h3_resolution + 1)),
+            h3_resolution,
+        );
+
+        let inverse_transform = transform.invert().unwrap();
+        let pixel = |coordinate: geo_types::Coordinate<f64>| {
+            let transformed = &inverse_transform * coordinate;
+            (transformed.x.floor() as i64, transformed.y.floor() as i64)
+        };
+        let mut num_compared = 0;
+        for (cell, value) in centroid_cells.iter() {
+            let center_child = cell.center_child(h3_resolution + 1).unwrap();
+            if pixel(cell.to_coordinate().unwrap()) == pixel(center_child.to_coordinate().unwrap())
+            {
+                assert_eq!(center_child_cells.get(cell), Some(value));
+                num_compared += 1;
+            }
+        }
+        assert!(num_compared > centroid_cells.len() / 2);
+    }
+
+    /// converts with a wrapper transform producing non-finite coordinates for the window
+    /// starting at pixel `(40, 40)`. Returns the converted cells and the report.
+    fn convert_poisoned(
+        converter: &H3Converter<u8>,
+        transform: &Transform,
+        h3_resolution: u8,
+        max_failed_fraction: f64,
+    ) -> Result<(h3ron::collections::H3CellSet, FailureReport), Error> {
+        let (cell_map, report) = converter.to_h3_isolating_failures(
+            h3_resolution,
+            false,
+            max_failed_fraction,
+            |window| {
+                if window.min().x as usize == 40 && window.min().y as usize == 40 {
+                    geo_types::Rect::new((f64::NAN, f64::NAN), (f64::NAN, f64::NAN))
+                } else {
+                    transform * window
+                }
+            },
+        )?;
+
+        let mut cells = h3ron::collections::H3CellSet::default();
+        for compacted_vec in cell_map.values() {
+            for cell in compacted_vec.iter_uncompacted_cells(h3_resolution) {
+                cells.insert(cell.unwrap());
+            }
+        }
+        Ok((cells, report))
+    }
+
+    fn ones_fixture() -> (ndarray::Array2<u8>, Transform) {
+        (
+            ndarray::Array2::<u8>::ones((200, 200)),
+            Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]),
+        )
+    }
+
+    #[test]
+    fn resilient_skips_failed_box() {
+        let (arr, transform) = ones_fixture();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let h3_resolution = 11;
+
+        let (cells, report) = convert_poisoned(&converter, &transform, h3_resolution, 0.1).unwrap();
+        assert_eq!(report.failed_boxes.len(), 1);
+        let failed = &report.failed_boxes[0];
+        assert_eq!(failed.rect.min(), (40, 40).into());
+        assert!(failed.rect.max().x >= 59 && failed.rect.max().y >= 59);
+        assert!(failed.data_pixels >= 400);
+        assert_eq!(report.failed_data_pixels, failed.data_pixels);
+        assert_eq!(failed.error, Error::NonFiniteCoordinates.to_string());
+        assert!(report.failed_fraction() > 0.0 && report.failed_fraction() < 0.1);
+
+        // all cells outside of the failed box are present
+        let all_cells = converted_cell_values(&converter, h3_resolution);
+        let inverse_transform = transform.invert().unwrap();
+        let mut num_missing = 0;
+        for cell in all_cells.keys() {
+            let pixel = &inverse_transform * cell.to_coordinate().unwrap();
+            if (39.0..62.0).contains(&pixel.x) && (39.0..62.0).contains(&pixel.y) {
+                if !cells.contains(cell) {
+                    num_missing += 1;
+                }
+            } else {
+                assert!(cells.contains(cell));
+            }
+        }
+        assert!(num_missing > 0);
+    }
+
+    #[test]
+    fn resilient_aborts_above_threshold() {
+        let (arr, transform) = ones_fixture();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+
+        match convert_poisoned(&converter, &transform, 11, 0.001) {
+            Err(Error::TooManyFailedBoxes(report)) => {
+                assert_eq!(report.failed_boxes.len(), 1);
+                assert_eq!(report.failed_boxes[0].rect.min(), (40, 40).into());
+            }
+            _ => panic!("expected TooManyFailedBoxes"),
+        }
+    }
+
+    #[test]
+    fn resilient_without_failures() {
+        let (arr, transform) = ones_fixture();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+
+        let (cell_map, report) = converter.to_h3_resilient(11, false, 0.0).unwrap();
+        assert!(report.is_empty());
+        assert!(report.data_pixels >= 200 * 200);
+
+        let mut num_cells = 0;
+        let all_cells = converted_cell_values(&converter, 11);
+        for compacted_vec in cell_map.values() {
+            for cell in compacted_vec.iter_uncompacted_cells(11) {
+                assert!(all_cells.contains_key(&cell.unwrap()));
+                num_cells += 1;
+            }
+        }
+        assert_eq!(num_cells, all_cells.len());
+    }
+
+    #[test]
+    fn float_values_with_nan_nodata() {
+        use ordered_float::OrderedFloat;
+
+        // floats are converted by wrapping them in a type implementing `Eq` and `Hash`.
+        // `OrderedFloat` considers NaN equal to NaN, so NaN can be used as nodata.
+        let arr = ndarray::Array2::from_shape_fn((100, 100), |(_, x)| {
+            OrderedFloat(match x {
+                0..=32 => f64::NAN,
+                33..=65 => 0.25,
+                _ => -1.5,
+            })
+        });
+        let transform = Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]);
+        let view = arr.view();
+        let nodata = Some(OrderedFloat(f64::NAN));
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+
+        let cells = converter.to_h3(11, false).unwrap();
+        assert_eq!(cells.len(), 2);
+        assert!(cells.contains_key(&OrderedFloat(0.25)));
+        assert!(cells.contains_key(&OrderedFloat(-1.5)));
+        assert!(!cells.contains_key(&OrderedFloat(f64::NAN)));
+    }
+
+    #[test]
+    fn multiple_nodata_values() {
+        // sentinel values interleaved with the data in the right half, only sentinels in the left half
+        let arr =
+            ndarray::Array2::from_shape_fn((100, 100), |(y, x)| match (x < 50, (x + y) % 4) {
+                (_, 0) => 0_u8,
+                (_, 1) => 254,
+                (true, _) => 255,
+                (false, _) => 7,
+            });
+        let transform = Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]);
+        let view = arr.view();
+
+        // the same array with a single nodata value as reference
+        let single_arr = arr.map(|v| if *v == 7 { 7_u8 } else { 0 });
+        let single_view = single_arr.view();
+        let single_nodata = Some(0_u8);
+        let expected = H3Converter::new(&single_view, &single_nodata, &transform, AxisOrder::YX)
+            .to_h3(11, false)
+            .unwrap();
+        assert_eq!(expected.len(), 1);
+
+        let nodata = Some(0_u8);
+        let no_nodata = None;
+        for converter in [
+            H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+                .with_nodata_check(NodataCheck::Values(vec![254, 255])),
+            H3Converter::new(&view, &no_nodata, &transform, AxisOrder::YX)
+                .with_nodata_check(NodataCheck::predicate(|v: &u8| *v < 5 || *v > 250)),
+        ] {
+            let cells = converter.to_h3(11, false).unwrap();
+            assert_eq!(cells.len(), 1);
+            assert!(cells.contains_key(&7));
+            assert_eq!(
+                cells.get(&7).unwrap().len(),
+                expected.get(&7).unwrap().len()
+            );
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum SinkCall {
+        Accept(H3Cell, u8),
+        FinishBox(Rect<usize>),
+    }
+
+    /// records the calls of the conversion
+    #[derive(Default)]
+    struct RecordingSink {
+        calls: Vec<SinkCall>,
+    }
+
+    impl<'a> CellSink<'a, u8> for RecordingSink {
+        type Output = Vec<SinkCall>;
+
+        fn fork(&self) -> Self {
+            Self::default()
+        }
+
+        fn accept(&mut self, cell: H3Cell, value: &'a u8) -> Result<(), Error> {
+            self.calls.push(SinkCall::Accept(cell, *value));
+            Ok(())
+        }
+
+        fn finish_box(&mut self, box_rect: &Rect<usize>) -> Result<(), Error> {
+            self.calls.push(SinkCall::FinishBox(*box_rect));
+            Ok(())
+        }
+
+        fn merge(&mut self, mut other: Self) -> Result<(), Error> {
+            self.calls.append(&mut other.calls);
+            Ok(())
+        }
+
+        fn finalize(self) -> Self::Output {
+            self.calls
+        }
+    }
+
+    fn sink_fixture() -> (ndarray::Array2<u8>, Transform) {
+        let arr = ndarray::Array2::from_shape_fn((120, 180), |(y, x)| match (y / 40, x / 60) {
+            (0, 0) | (2, 1) => 0_u8,
+            (1, _) => 2,
+            _ => 1,
+        });
+        (
+            arr,
+            Transform::from_gdal(&[10.0, 0.0005, 0.0, 50.0, 0.0, -0.0005]),
+        )
+    }
+
+    #[test]
+    fn sink_calls_follow_boxes() {
+        let (arr, transform) = sink_fixture();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let h3_resolution = converter
+            .nearest_h3_resolution(ResolutionSearchMode::SmallerThanPixel)
+            .unwrap();
+
+        let calls = converter
+            .drive(h3_resolution, RecordingSink::default())
+            .unwrap();
+
+        // one finish_box per detected box, in the order of the boxes
+        let expected_boxes: Vec<_> = converter
+            .rects_with_data(converter.window_size())
+            .iter()
+            .map(|array_window| converter.pixel_rect(array_window))
+            .collect();
+        let finished_boxes: Vec<_> = calls
+            .iter()
+            .filter_map(|call| match call {
+                SinkCall::FinishBox(rect) => Some(*rect),
+                _ => None,
+            })
+            .collect();
+        assert!(expected_boxes.len() > 1);
+        assert_eq!(finished_boxes, expected_boxes);
+
+        // each box got its own cells before being finished
+        assert!(matches!(calls.last(), Some(SinkCall::FinishBox(_))));
+        let mut accepted_per_box = vec![0_usize];
+        for call in calls.iter() {
+            match call {
+                SinkCall::Accept(_, value) => {
+                    assert_ne!(*value, 0);
+                    *accepted_per_box.last_mut().unwrap() += 1;
+                }
+                SinkCall::FinishBox(_) => accepted_per_box.push(0),
+            }
+        }
+        assert_eq!(accepted_per_box.pop(), Some(0));
+        assert!(accepted_per_box
+            .iter()
+            .all(|num_accepted| *num_accepted > 0));
+
+        // the accepted cells are the cells of the grouped output
+        let mut accepted_cells: Vec<_> = calls
+            .iter()
+            .filter_map(|call| match call {
+                SinkCall::Accept(cell, value) => Some((*cell, *value)),
+                _ => None,
+            })
+            .collect();
+        accepted_cells.sort_unstable();
+        accepted_cells.dedup();
+        let mut expected_cells: Vec<_> = converter
+            .to_h3(h3_resolution, false)
+            .unwrap()
+            .iter()
+            .flat_map(|(value, cells)| cells.iter_compacted_cells().map(|cell| (cell, **value)))
+            .collect();
+        expected_cells.sort_unstable();
+        assert_eq!(accepted_cells, expected_cells);
+    }
+
+    #[test]
+    fn columnar_sink_equals_grouped_output() {
+        let (arr, transform) = sink_fixture();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let h3_resolution = converter
+            .nearest_h3_resolution(ResolutionSearchMode::SmallerThanPixel)
+            .unwrap();
+
+        let (cells, values) = converter
+            .drive(h3_resolution, ColumnarSink::default())
+            .unwrap();
+        assert_eq!(cells.len(), values.len());
+        assert!(cells.windows(2).all(|window| window[0] < window[1]));
+
+        let mut columnar: Vec<_> = cells.into_iter().zip(values.into_iter().copied()).collect();
+        columnar.sort_unstable();
+
+        let mut grouped: Vec<_> = converter
+            .to_h3(h3_resolution, false)
+            .unwrap()
+            .iter()
+            .flat_map(|(value, cells)| {
+                cells
+                    .iter_uncompacted_cells(h3_resolution)
+                    .map(|cell| (cell.unwrap(), **value))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        grouped.sort_unstable();
+        assert_eq!(columnar, grouped);
+    }
+
+    #[test]
+    fn to_h3_with_stats_counts() {
+        let (arr, transform) = sink_fixture();
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let h3_resolution = converter
+            .nearest_h3_resolution(ResolutionSearchMode::SmallerThanPixel)
+            .unwrap();
+        let num_data_pixels = arr.iter().filter(|v| **v != 0).count();
+
+        let (cells, stats) = converter.to_h3_with_stats(h3_resolution, false).unwrap();
+        assert_eq!(cells, converter.to_h3(h3_resolution, false).unwrap());
+        assert_eq!(stats.h3_resolution, h3_resolution);
+        assert!(stats.num_boxes > 1);
+        assert_eq!(stats.num_pixels, arr.len());
+        assert_eq!(stats.visited_pixels - stats.nodata_pixels, num_data_pixels);
+        assert!(stats.visited_pixels < arr.len());
+        let num_cells: usize = cells
+            .values()
+            .map(|compacted_vec| compacted_vec.len())
+            .sum();
+        assert_eq!(stats.output_cells, num_cells);
+        assert_eq!(stats.uncompacted_cells, num_cells);
+        assert!(stats.generated_cells >= num_cells);
+
+        let (compacted, compacted_stats) = converter.to_h3_with_stats(h3_resolution, true).unwrap();
+        assert_eq!(compacted, converter.to_h3(h3_resolution, true).unwrap());
+        assert_eq!(compacted_stats.uncompacted_cells, num_cells);
+        assert_eq!(compacted_stats.generated_cells, stats.generated_cells);
+        assert_eq!(compacted_stats.visited_pixels, stats.visited_pixels);
+        assert!(compacted_stats.output_cells < compacted_stats.uncompacted_cells);
+        assert_eq!(
+            compacted_stats.output_cells,
+            compacted
+                .values()
+                .map(|compacted_vec| compacted_vec.len())
+                .sum::<usize>()
+        );
+    }
+
+    /// vertical stripes `stripe_width` pixels wide alternating between the values `1` and `2`.
+    fn striped_fixture(stripe_width: usize) -> (ndarray::Array2<u8>, Transform) {
+        let arr =
+            ndarray::Array2::from_shape_fn((400, 400), |(_, x)| ((x / stripe_width) % 2) as u8 + 1);
+        let transform = Transform::from_gdal(&[10.0, 0.0001, 0.0, 50.0, 0.0, -0.0001]);
+        (arr, transform)
+    }
+
+    #[test]
+    fn jitter_is_reproducible() {
+        let (arr, transform) = striped_fixture(1);
+        let view = arr.view();
+        let h3_resolution = 10;
+        let convert = |seed: u64, num_threads: usize| {
+            converted_cell_values(
+                &H3Converter::new(&view, &None, &transform, AxisOrder::YX)
+                    .with_sample_jitter(JitterOptions::new(0.9, seed))
+                    .with_num_threads(num_threads),
+                h3_resolution,
+            )
+        };
+
+        let seed_1 = convert(1, 1);
+        assert!(seed_1.len() > 500);
+        assert_eq!(seed_1, convert(1, 4));
+        assert_eq!(seed_1, convert(1, 1));
+
+        let seed_2 = convert(2, 3);
+        assert_eq!(seed_2, convert(2, 1));
+        assert_ne!(seed_1, seed_2);
+
+        // the same cells, only the sampled values differ
+        let mut cells_1: Vec<_> = seed_1.keys().copied().collect();
+        let mut cells_2: Vec<_> = seed_2.keys().copied().collect();
+        cells_1.sort_unstable();
+        cells_2.sort_unstable();
+        assert_eq!(cells_1, cells_2);
+    }
+
+    /// mean deviation from `0.5` of the share of neighboring cells with equal values, computed
+    /// per direction of the neighbors. Close to `0` for uncorrelated values of the neighbors.
+    fn neighbor_correlation(cell_values: &h3ron::collections::H3CellMap<u8>) -> f64 {
+        let mut tallies = [(0_usize, 0_usize); 6];
+        for (cell, value) in cell_values.iter() {
+            for (direction, edge) in cell.directed_edges().unwrap().iter().enumerate() {
+                if let Some(neighbor_value) = cell_values.get(&edge.destination_cell().unwrap()) {
+                    let tally = &mut tallies[direction % 6];
+                    tally.0 += usize::from(neighbor_value == value);
+                    tally.1 += 1;
+                }
+            }
+        }
+        let deviations: Vec<_> = tallies
+            .iter()
+            .filter(|(_, num_pairs)| *num_pairs >= 100)
+            .map(|(num_equal, num_pairs)| (*num_equal as f64 / *num_pairs as f64 - 0.5).abs())
+            .collect();
+        assert!(!deviations.is_empty());
+        deviations.iter().sum::<f64>() / deviations.len() as f64
+    }
+
+    #[test]
+    fn jitter_reduces_aliasing() {
+        let h3_resolution = 10;
+        let mut max_plain_correlation = 0.0_f64;
+        let mut max_jittered_correlation = 0.0_f64;
+
+        // the stripes are far narrower than the distance between neighboring cells
+        for stripe_width in [1, 2, 3] {
+            let (arr, transform) = striped_fixture(stripe_width);
+            let view = arr.view();
+            let plain = converted_cell_values(
+                &H3Converter::new(&view, &None, &transform, AxisOrder::YX),
+                h3_resolution,
+            );
+            let jittered = converted_cell_values(
+                &H3Converter::new(&view, &None, &transform, AxisOrder::YX)
+                    .with_sample_jitter(JitterOptions::new(0.9, 42)),
+                h3_resolution,
+            );
+            assert_eq!(plain.len(), jittered.len());
+
+            max_plain_correlation = max_plain_correlation.max(neighbor_correlation(&plain));
+            max_jittered_correlation =
+                max_jittered_correlation.max(neighbor_correlation(&jittered));
+        }
+        assert!(max_jittered_correlation < 0.1);
+        assert!(max_jittered_correlation < max_plain_correlation);
+    }
+
+    /// checkerboard with the value `1` on all dark squares and alternating rows of `2` and `3` on
+    /// the light squares, located in cells far larger than the pixels at resolution 6.
+    fn checkerboard_fixture() -> (ndarray::Array2<u8>, Transform) {
+        let arr = ndarray::Array2::from_shape_fn((150, 150), |(row, col)| {
+            if (row + col) % 2 == 0 {
+                1
+            } else if row % 2 == 0 {
+                2
+            } else {
+                3
+            }
+        });
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 20.0, 0.0, -0.001]);
+        (arr, transform)
+    }
+
+    /// the values of the pixels of each cell, computed pixel by pixel
+    fn checkerboard_pixel_values(h3_resolution: u8) -> h3ron::collections::H3CellMap<Vec<u8>> {
+        let (arr, transform) = checkerboard_fixture();
+        let mut pixel_values = h3ron::collections::H3CellMap::<Vec<u8>>::default();
+        for ((row, col), value) in arr.indexed_iter() {
+            let center = &transform
+                * Coordinate {
+                    x: col as f64 + 0.5,
+                    y: row as f64 + 0.5,
+                };
+            pixel_values
+                .entry(H3Cell::from_coordinate(center, h3_resolution).unwrap())
+                .or_default()
+                .push(*value);
+        }
+        pixel_values
+    }
+
+    /// the most frequent of `values`, ties go to the value found first
+    fn mode_of(values: &[u8]) -> u8 {
+        let count = |value: &u8| values.iter().filter(|v| *v == value).count();
+        let max_count = values.iter().map(count).max().unwrap();
+        *values.iter().find(|v| count(v) == max_count).unwrap()
+    }
+
+    #[test]
+    fn to_h3_aggregated_checkerboard() {
+        let h3_resolution = 6;
+        let (arr, transform) = checkerboard_fixture();
+        let view = arr.view();
+        let converter = H3Converter::new(&view, &None, &transform, AxisOrder::YX);
+        let pixel_values = checkerboard_pixel_values(h3_resolution);
+        assert!(pixel_values.len() > 5);
+
+        let aggregated = |method| {
+            let mut cell_values = h3ron::collections::H3CellMap::default();
+            for (value, compacted_vec) in converter
+                .to_h3_aggregated(h3_resolution, false, method)
+                .unwrap()
+            {
+                for cell in compacted_vec.iter_uncompacted_cells(h3_resolution) {
+                    // each cell is listed under exactly one value
+                    assert!(cell_values.insert(cell.unwrap(), *value).is_none());
+                }
+            }
+            cell_values
+        };
+
+        let modes = aggregated(AggregationMethod::Mode);
+        let maxima = aggregated(AggregationMethod::Max);
+        assert_eq!(modes.len(), pixel_values.len());
+        assert_eq!(maxima.len(), pixel_values.len());
+        let minima = aggregated(AggregationMethod::Min);
+        let firsts = aggregated(AggregationMethod::First);
+        for (cell, values) in pixel_values.iter() {
+            assert_eq!(modes[cell], mode_of(values));
+            assert_eq!(maxima[cell], *values.iter().max().unwrap());
+            assert_eq!(minima[cell], *values.iter().min().unwrap());
+            assert_eq!(firsts[cell], values[0]);
+        }
+
+        // half of the pixels are `1`, but `3` is found in nearly all cells
+        let num_differing = pixel_values
+            .keys()
+            .filter(|cell| modes[*cell] == 1 && maxima[*cell] == 3)
+            .count();
+        assert!(num_differing > pixel_values.len() / 2);
+
+        assert!(matches!(
+            converter.to_h3_aggregated(h3_resolution, false, AggregationMethod::Mean),
+            Err(Error::UnsupportedAggregationMethod(AggregationMethod::Mean))
+        ));
+    }
+
+    #[test]
+    fn to_h3_aggregated_f64_checkerboard() {
+        let h3_resolution = 6;
+        let (arr, transform) = checkerboard_fixture();
+        let view = arr.view();
+        let converter = H3Converter::new(&view, &None, &transform, AxisOrder::YX);
+        let pixel_values = checkerboard_pixel_values(h3_resolution);
+
+        let sums = converter
+            .to_h3_aggregated_f64(h3_resolution, AggregationMethod::Sum)
+            .unwrap();
+        let means = converter
+            .to_h3_aggregated_f64(h3_resolution, AggregationMethod::Mean)
+            .unwrap();
+        let modes = converter
+            .to_h3_aggregated_f64(h3_resolution, AggregationMethod::Mode)
+            .unwrap();
+        assert_eq!(sums.len(), pixel_values.len());
+        for (cell, values) in pixel_values.iter() {
+            let sum: f64 = values.iter().map(|v| f64::from(*v)).sum();
+            assert_relative_eq!(sums[cell], sum);
+            assert_relative_eq!(means[cell], sum / values.len() as f64);
+            assert_relative_eq!(modes[cell], f64::from(mode_of(values)));
+        }
+    }
+
+    #[test]
+    fn zero_length_axes() {
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        // the converter requires the nodata value to live as long as the view
+        let nodata_values = [None, Some(0_u8)];
+        for shape in [(0, 10), (10, 0), (0, 0)] {
+            let arr = ndarray::Array2::<u8>::zeros(shape);
+            let view = arr.view();
+            for nodata_value in nodata_values.iter() {
+                for axis_order in [AxisOrder::XY, AxisOrder::YX] {
+                    for box_detection in [
+                        BoxDetection::Simple,
+                        BoxDetection::Recursive { min_box_size: 2 },
+                    ] {
+                        let converter =
+                            H3Converter::new(&view, nodata_value, &transform, axis_order)
+                                .with_box_detection(box_detection);
+
+                        assert!(converter.boxes_containing_data().unwrap().is_empty());
+                        assert!(converter.to_h3(8, true).unwrap().is_empty());
+                        assert!(converter
+                            .to_h3_aggregated(8, true, AggregationMethod::Mode)
+                            .unwrap()
+                            .is_empty());
+                        assert_eq!(converter.estimate(8).unwrap().expected_cells, 0);
+                        assert!(matches!(
+                            converter.nearest_h3_resolution(ResolutionSearchMode::SmallerThanPixel),
+                            Err(Error::EmptyArray)
+                        ));
+                    }
+                }
+            }
+        }
     }
 }
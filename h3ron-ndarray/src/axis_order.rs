@@ -0,0 +1,213 @@
+//! Inference and validation of the [`AxisOrder`] of an array.
+//!
+//! Both axis orders get tested by mapping the corners of the array through the transform.
+//! An axis order is plausible when the resulting bounding box is non-degenerate and located
+//! within the bounds of WGS84 coordinates. As the transform is the same for both axis orders,
+//! only their extents differ - so arrays with the same size along both axes are always
+//! ambiguous.
+use std::fmt;
+
+use geo_types::{Coordinate, Rect};
+
+use crate::{AxisOrder, Error, Transform};
+
+/// tolerance in degrees when comparing with the bounds of WGS84 coordinates
+const WORLD_BOUNDS_TOLERANCE: f64 = 1e-6;
+
+/// How certain the result of [`infer_axis_order`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    /// Only the returned axis order is plausible.
+    Certain,
+
+    /// Both axis orders are plausible. The returned axis order is [`AxisOrder::YX`], as used
+    /// by gdal.
+    Ambiguous,
+}
+
+/// The claimed axis order does not yield a plausible bounding box.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AxisOrderMismatch {
+    pub claimed: AxisOrder,
+
+    /// the bounding box resulting from the claimed axis order
+    pub claimed_bbox: Rect<f64>,
+
+    /// the bounding box resulting from the other axis order
+    pub alternative_bbox: Rect<f64>,
+}
+
+impl fmt::Display for AxisOrderMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The axis order {:?} yields the implausible bounding box {}, the other axis order yields {}",
+            self.claimed,
+            BBoxDisplay(&self.claimed_bbox),
+            BBoxDisplay(&self.alternative_bbox)
+        )
+    }
+}
+
+impl std::error::Error for AxisOrderMismatch {}
+
+struct BBoxDisplay<'a>(&'a Rect<f64>);
+
+impl<'a> fmt::Display for BBoxDisplay<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}, {}, {}, {}]",
+            self.0.min().x,
+            self.0.min().y,
+            self.0.max().x,
+            self.0.max().y
+        )
+    }
+}
+
+/// The bounding box of an array of the given `shape` when using `axis_order`.
+fn bbox(shape: &[usize], transform: &Transform, axis_order: AxisOrder) -> Rect<f64> {
+    let x_size = shape[axis_order.x_axis()] as f64;
+    let y_size = shape[axis_order.y_axis()] as f64;
+    let corners = [(0.0, 0.0), (x_size, 0.0), (0.0, y_size), (x_size, y_size)]
+        .map(|(x, y)| transform.transform_coordinate(&Coordinate { x, y }));
+    let min = corners.iter().fold(corners[0], |acc, c| Coordinate {
+        x: acc.x.min(c.x),
+        y: acc.y.min(c.y),
+    });
+    let max = corners.iter().fold(corners[0], |acc, c| Coordinate {
+        x: acc.x.max(c.x),
+        y: acc.y.max(c.y),
+    });
+    Rect::new(min, max)
+}
+
+fn is_plausible(bbox: &Rect<f64>) -> bool {
+    let (min, max) = (bbox.min(), bbox.max());
+    min.x.is_finite()
+        && min.y.is_finite()
+        && max.x.is_finite()
+        && max.y.is_finite()
+        && bbox.width() > 0.0
+        && bbox.height() > 0.0
+        && min.x >= -180.0 - WORLD_BOUNDS_TOLERANCE
+        && max.x <= 180.0 + WORLD_BOUNDS_TOLERANCE
+        && min.y >= -90.0 - WORLD_BOUNDS_TOLERANCE
+        && max.y <= 90.0 + WORLD_BOUNDS_TOLERANCE
+}
+
+const fn other(axis_order: AxisOrder) -> AxisOrder {
+    match axis_order {
+        AxisOrder::XY => AxisOrder::YX,
+        AxisOrder::YX => AxisOrder::XY,
+    }
+}
+
+/// Infer the axis order of an array of the given `shape` from its `transform`.
+///
+/// Fails with [`Error::NoPlausibleAxisOrder`] when none of the axis orders yields a plausible
+/// bounding box.
+pub fn infer_axis_order(
+    shape: &[usize],
+    transform: &Transform,
+) -> Result<(AxisOrder, Confidence), Error> {
+    if shape.len() != 2 {
+        return Err(Error::UnsupportedArrayShape);
+    }
+    if shape.contains(&0) {
+        return Err(Error::EmptyArray);
+    }
+    match (
+        is_plausible(&bbox(shape, transform, AxisOrder::YX)),
+        is_plausible(&bbox(shape, transform, AxisOrder::XY)),
+    ) {
+        (true, true) => Ok((AxisOrder::YX, Confidence::Ambiguous)),
+        (true, false) => Ok((AxisOrder::YX, Confidence::Certain)),
+        (false, true) => Ok((AxisOrder::XY, Confidence::Certain)),
+        (false, false) => Err(Error::NoPlausibleAxisOrder),
+    }
+}
+
+/// Validate that the `claimed` axis order yields a plausible bounding box for an array of
+/// the given `shape`.
+///
+/// An ambiguous axis order is not an error.
+///
+/// # Panics
+///
+/// Panics when `shape` is not two-dimensional.
+pub fn validate_axis_order(
+    shape: &[usize],
+    transform: &Transform,
+    claimed: &AxisOrder,
+) -> Result<(), AxisOrderMismatch> {
+    let claimed_bbox = bbox(shape, transform, *claimed);
+    if is_plausible(&claimed_bbox) {
+        Ok(())
+    } else {
+        Err(AxisOrderMismatch {
+            claimed: *claimed,
+            claimed_bbox,
+            alternative_bbox: bbox(shape, transform, other(*claimed)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::axis_order::{infer_axis_order, validate_axis_order, Confidence};
+    use crate::{AxisOrder, Error, Transform};
+
+    fn r_tiff_transform() -> Transform {
+        Transform::from_gdal(&[8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135])
+    }
+
+    #[test]
+    fn infer_r_tiff_strip() {
+        // a long strip using the transform of r.tiff. Along the y axis the strip would
+        // extend beyond the south pole.
+        let shape = [2000, 120_000];
+        assert_eq!(
+            infer_axis_order(&shape, &r_tiff_transform()).unwrap(),
+            (AxisOrder::YX, Confidence::Certain)
+        );
+        assert_eq!(
+            infer_axis_order(&[120_000, 2000], &r_tiff_transform()).unwrap(),
+            (AxisOrder::XY, Confidence::Certain)
+        );
+        assert!(validate_axis_order(&shape, &r_tiff_transform(), &AxisOrder::YX).is_ok());
+    }
+
+    #[test]
+    fn square_is_ambiguous() {
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        assert_eq!(
+            infer_axis_order(&[2000, 2000], &transform).unwrap(),
+            (AxisOrder::YX, Confidence::Ambiguous)
+        );
+        assert!(validate_axis_order(&[2000, 2000], &transform, &AxisOrder::XY).is_ok());
+    }
+
+    #[test]
+    fn mismatch() {
+        let shape = [2000, 120_000];
+        let err = validate_axis_order(&shape, &r_tiff_transform(), &AxisOrder::XY).unwrap_err();
+        assert_eq!(err.claimed, AxisOrder::XY);
+        assert!(err.claimed_bbox.min().y < -90.0);
+        assert!(err.alternative_bbox.min().y > -90.0);
+
+        let message = err.to_string();
+        assert!(message.contains(&err.claimed_bbox.min().y.to_string()));
+        assert!(message.contains(&err.alternative_bbox.max().x.to_string()));
+    }
+
+    #[test]
+    fn no_plausible_axis_order() {
+        let transform = Transform::from_gdal(&[10.0, 1.0, 0.0, 50.0, 0.0, -1.0]);
+        assert!(matches!(
+            infer_axis_order(&[1000, 1000], &transform),
+            Err(Error::NoPlausibleAxisOrder)
+        ));
+    }
+}
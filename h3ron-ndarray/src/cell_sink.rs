@@ -0,0 +1,204 @@
+//! Consumers of the cells produced by [`H3Converter::drive`](crate::H3Converter::drive).
+//!
+//! The conversion splits the array into boxes which are converted in parallel. Each box
+//! gets its own sink created by [`CellSink::fork`]. After all cells of the box have been passed
+//! to [`CellSink::accept`], [`CellSink::finish_box`] gets called. The sinks of all boxes
+//! are merged into the sink passed to the conversion in the order of the boxes using
+//! [`CellSink::merge`], before [`CellSink::finalize`] creates the output.
+use std::hash::Hash;
+
+use geo_types::Rect;
+use rayon::prelude::*;
+
+use h3ron::collections::{CompactedCellVec, HashMap};
+use h3ron::H3Cell;
+
+use crate::Error;
+
+/// A consumer of the cells containing data found by a conversion together with their value.
+pub trait CellSink<'a, T>: Sized + Send
+where
+    T: 'a,
+{
+    /// The result of the conversion. Use a `Result` for outputs which may fail to finalize.
+    type Output;
+
+    /// Create an empty sink with the configuration of this sink to receive the cells of a
+    /// single box.
+    fn fork(&self) -> Self;
+
+    /// Receive a cell and its value.
+    fn accept(&mut self, cell: H3Cell, value: &'a T) -> Result<(), Error>;
+
+    /// Called after all cells of the box `box_rect` have been accepted. `box_rect` is given
+    /// in array coordinates, both `min` and `max` are inclusive.
+    fn finish_box(&mut self, _box_rect: &Rect<usize>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Merge the sink of a finished box into this sink.
+    fn merge(&mut self, other: Self) -> Result<(), Error>;
+
+    /// Create the output from all merged boxes.
+    fn finalize(self) -> Self::Output;
+}
+
+/// The cells grouped by their value, like returned by
+/// [`H3Converter::to_h3`](crate::H3Converter::to_h3).
+///
+/// The cells of each box get deduplicated - or compacted when `compact` is set - when the box is
+/// finished to free a bit of memory early.
+pub struct CompactedCellsSink<'a, T> {
+    compact: bool,
+    min_compacted_resolution: u8,
+    cells: HashMap<&'a T, CompactedCellVec>,
+}
+
+impl<'a, T> CompactedCellsSink<'a, T> {
+    pub fn new(compact: bool) -> Self {
+        Self {
+            compact,
+            min_compacted_resolution: 0,
+            cells: HashMap::default(),
+        }
+    }
+
+    /// Do not compact the cells to resolutions coarser than `min_compacted_resolution`. See
+    /// [`CompactedCellVec::compact_to_min_resolution`]. Defaults to 0.
+    pub fn with_min_compacted_resolution(mut self, min_compacted_resolution: u8) -> Self {
+        self.min_compacted_resolution = min_compacted_resolution;
+        self
+    }
+
+    /// `None` when the cells are not compacted
+    const fn compact_to(&self) -> Option<u8> {
+        if self.compact {
+            Some(self.min_compacted_resolution)
+        } else {
+            None
+        }
+    }
+}
+
+impl<'a, T> CellSink<'a, T> for CompactedCellsSink<'a, T>
+where
+    T: Sync + Eq + Hash,
+{
+    type Output = Result<HashMap<&'a T, CompactedCellVec>, Error>;
+
+    fn fork(&self) -> Self {
+        Self::new(self.compact).with_min_compacted_resolution(self.min_compacted_resolution)
+    }
+
+    fn accept(&mut self, cell: H3Cell, value: &'a T) -> Result<(), Error> {
+        self.cells.entry(value).or_default().add_cell(cell, false)?;
+        Ok(())
+    }
+
+    fn finish_box(&mut self, _box_rect: &Rect<usize>) -> Result<(), Error> {
+        self.cells = finalize_chunk_map(std::mem::take(&mut self.cells), self.compact_to())?;
+        Ok(())
+    }
+
+    fn merge(&mut self, other: Self) -> Result<(), Error> {
+        merge_chunk_map(&mut self.cells, other.cells)
+    }
+
+    fn finalize(self) -> Self::Output {
+        let compact_to = self.compact_to();
+        finalize_chunk_map(self.cells, compact_to)
+    }
+}
+
+/// The uncompacted cells and their values as two columns of the same length, ordered by
+/// the cells.
+///
+/// Cells found in multiple boxes keep the value of the first box.
+pub struct ColumnarSink<'a, T> {
+    cells: Vec<H3Cell>,
+    values: Vec<&'a T>,
+}
+
+impl<'a, T> Default for ColumnarSink<'a, T> {
+    fn default() -> Self {
+        Self {
+            cells: vec![],
+            values: vec![],
+        }
+    }
+}
+
+impl<'a, T> CellSink<'a, T> for ColumnarSink<'a, T>
+where
+    T: Sync,
+{
+    type Output = (Vec<H3Cell>, Vec<&'a T>);
+
+    fn fork(&self) -> Self {
+        Self::default()
+    }
+
+    fn accept(&mut self, cell: H3Cell, value: &'a T) -> Result<(), Error> {
+        self.cells.push(cell);
+        self.values.push(value);
+        Ok(())
+    }
+
+    fn merge(&mut self, mut other: Self) -> Result<(), Error> {
+        self.cells.append(&mut other.cells);
+        self.values.append(&mut other.values);
+        Ok(())
+    }
+
+    fn finalize(self) -> Self::Output {
+        let mut rows: Vec<_> = self.cells.into_iter().zip(self.values).collect();
+        // stable to keep the value of the first box
+        rows.sort_by_key(|(cell, _)| *cell);
+        rows.dedup_by_key(|(cell, _)| *cell);
+        rows.into_iter().unzip()
+    }
+}
+
+fn merge_chunk_map<'a, T>(
+    h3_map: &mut HashMap<&'a T, CompactedCellVec>,
+    chunk_h3_map: HashMap<&'a T, CompactedCellVec>,
+) -> Result<(), Error>
+where
+    T: Eq + Hash,
+{
+    for (value, mut compacted_vec) in chunk_h3_map {
+        h3_map
+            .entry(value)
+            .or_insert_with(CompactedCellVec::new)
+            .append(&mut compacted_vec, false)?;
+    }
+    Ok(())
+}
+
+/// `compact_to` is the minimum resolution to compact the cells to. The cells only get
+/// deduplicated when it is `None`. In both cases the cells are sorted afterwards.
+fn finalize_chunk_map<T>(
+    chunk_map: HashMap<&T, CompactedCellVec>,
+    compact_to: Option<u8>,
+) -> Result<HashMap<&T, CompactedCellVec>, Error>
+where
+    T: Sync + Eq + Hash,
+{
+    chunk_map
+        .into_par_iter()
+        .map(|(k, mut compact_vec)| {
+            if let Some(min_resolution) = compact_to {
+                compact_vec
+                    .compact_to_min_resolution(min_resolution)
+                    .and_then(|_| compact_vec.dedup())
+                    .map_err(Error::from)
+            } else {
+                compact_vec.dedup().map_err(Error::from)
+            }
+            .map(|_| {
+                compact_vec.shrink_to_fit();
+                (k, compact_vec)
+            })
+        })
+        .collect()
+}
@@ -1,13 +1,53 @@
 use thiserror::Error as DeriveError;
 
+use crate::aggregation::AggregationMethod;
+use crate::failure::FailureReport;
+
 #[derive(Debug, DeriveError)]
 pub enum Error {
     #[error("Transform is not invertible")]
     TransformNotInvertible,
+    #[error("Transform results in a pixel size of zero, coefficient {0} is zero")]
+    ZeroPixelSize(char),
+    #[error("Transform has non-orthogonal pixel axes with a zero scale coefficient, the coefficients are likely in the wrong ordering")]
+    SwappedCoefficientOrdering,
     #[error("Empty array")]
     EmptyArray,
+    #[error("Empty or degenerate rect")]
+    EmptyRect,
+    #[error("No h3 resolution results in at most {0} h3indexes")]
+    NoResolutionWithinBudget(usize),
     #[error("Unsupported array shape")]
     UnsupportedArrayShape,
+    #[error("The shape of the mask does not match the shape of the array")]
+    MaskShapeMismatch,
+    #[error("The bands differ in their shape")]
+    BandShapeMismatch,
+    #[error("No bands given")]
+    NoBands,
+    #[error("Invalid factor {0} of the resolution search mode, must be finite and positive")]
+    InvalidResolutionSearchFactor(f64),
+    #[error("The h3 resolution {0} is too fine for f32 coordinates")]
+    ResolutionTooFineForF32Coordinates(u8),
+    #[error("None of the axis orders yields a plausible bounding box")]
+    NoPlausibleAxisOrder,
+    #[error("Unknown aggregation method: {0}")]
+    UnknownAggregationMethod(String),
+    #[error("The aggregation method {0:?} requires numeric values")]
+    UnsupportedAggregationMethod(AggregationMethod),
+    #[error("Non-finite coordinates")]
+    NonFiniteCoordinates,
+    #[error(
+        "{} of {} data pixels are located in boxes which failed to convert",
+        .0.failed_data_pixels,
+        .0.data_pixels
+    )]
+    TooManyFailedBoxes(Box<FailureReport>),
+    #[error("thread pool error: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
     #[error("h3ron error: {0}")]
     H3ron(#[from] h3ron::Error),
+    #[cfg(feature = "geotiff-write")]
+    #[error("gdal error: {0}")]
+    Gdal(#[from] gdal::errors::GdalError),
 }
@@ -0,0 +1,127 @@
+use std::mem::size_of;
+
+use geo_types::Rect;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use h3ron::collections::CompactedCellVec;
+use h3ron::H3Cell;
+
+/// maximum number of pixels visited when searching for the boxes containing data. Larger
+/// arrays get subsampled.
+pub(crate) const BOX_DETECTION_MAX_PIXELS: usize = 1 << 20;
+
+/// maximum number of pixels sampled within each box to estimate the fraction of data pixels.
+pub(crate) const MAX_SAMPLES_PER_BOX: usize = 4096;
+
+/// Estimate of a single box of the array containing data.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BoxEstimate {
+    /// the box in array coordinates. Both `min` and `max` are inclusive.
+    pub rect: Rect<usize>,
+
+    /// total number of pixels within the box
+    pub num_pixels: usize,
+
+    /// estimated number of pixels not containing the nodata value
+    pub data_pixels: usize,
+
+    /// estimated area covered by the data pixels in square meters
+    pub data_area_m2: f64,
+
+    /// estimated number of cells generated for this box
+    pub expected_cells: usize,
+}
+
+/// Estimate of the result and the resource usage of a conversion using
+/// [`H3Converter::to_h3`](crate::H3Converter::to_h3).
+///
+/// Created by [`H3Converter::estimate`](crate::H3Converter::estimate) without visiting
+/// every pixel of the array. The numbers are approximations, expect them to deviate
+/// from the real counts by up to a factor of 2.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConversionEstimate {
+    /// the h3 resolution the estimate was created for
+    pub h3_resolution: u8,
+
+    /// the boxes containing data
+    pub boxes: Vec<BoxEstimate>,
+
+    /// estimated number of pixels not containing the nodata value
+    pub data_pixels: usize,
+
+    /// estimated number of cells when converting without compacting
+    pub expected_cells: usize,
+
+    /// upper bound of the number of cells when converting with compacting enabled
+    pub compacted_cells_upper_bound: usize,
+
+    /// number of distinct values found while sampling. The real number of values, and by that
+    /// the number of groups in the output, may be higher.
+    pub sampled_distinct_values: usize,
+
+    /// estimated peak memory usage of the conversion in bytes
+    pub peak_memory_bytes: usize,
+}
+
+impl ConversionEstimate {
+    pub(crate) fn new(
+        h3_resolution: u8,
+        boxes: Vec<BoxEstimate>,
+        sampled_distinct_values: usize,
+        num_windows: usize,
+    ) -> Self {
+        let data_pixels = boxes.iter().map(|b| b.data_pixels).sum();
+        let expected_cells = boxes.iter().map(|b| b.expected_cells).sum();
+
+        // compacting never increases the number of cells
+        let compacted_cells_upper_bound = expected_cells;
+
+        Self {
+            h3_resolution,
+            boxes,
+            data_pixels,
+            expected_cells,
+            compacted_cells_upper_bound,
+            sampled_distinct_values,
+            peak_memory_bytes: peak_memory_bytes(
+                expected_cells,
+                sampled_distinct_values,
+                num_windows,
+            ),
+        }
+    }
+}
+
+/// The results of all windows of the array are kept in memory until they get merged, so
+/// during merging there are two copies of all cells. Each of them may be stored in
+/// `Vec`s with up to twice the required capacity.
+///
+/// Each window holds a `CompactedCellVec` for every value found within it.
+fn peak_memory_bytes(num_cells: usize, num_groups: usize, num_windows: usize) -> usize {
+    const CELL_COPIES: usize = 2;
+    const VEC_OVERALLOCATION: usize = 2;
+    let per_cell = size_of::<H3Cell>() * CELL_COPIES * VEC_OVERALLOCATION;
+    let per_group = size_of::<CompactedCellVec>() + size_of::<usize>() * 2;
+
+    num_cells.saturating_mul(per_cell).saturating_add(
+        num_groups
+            .saturating_mul(num_windows + 1)
+            .saturating_mul(per_group),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::estimate::peak_memory_bytes;
+
+    #[test]
+    fn peak_memory_grows_with_cells_and_groups() {
+        assert_eq!(peak_memory_bytes(0, 0, 10), 0);
+        assert!(peak_memory_bytes(1000, 1, 1) > peak_memory_bytes(100, 1, 1));
+        assert!(peak_memory_bytes(100, 10, 1) > peak_memory_bytes(100, 1, 1));
+        assert!(peak_memory_bytes(100, 10, 20) > peak_memory_bytes(100, 10, 1));
+    }
+}
@@ -0,0 +1,46 @@
+use geo_types::Rect;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A box of the array which failed to convert.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FailedBox {
+    /// the box in array coordinates. Both `min` and `max` are inclusive.
+    pub rect: Rect<usize>,
+
+    /// description of the error which caused the failure
+    pub error: String,
+
+    /// number of pixels containing data within the box which are missing in the result
+    pub data_pixels: usize,
+}
+
+/// Report of the boxes skipped by
+/// [`H3Converter::to_h3_resilient`](crate::H3Converter::to_h3_resilient).
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FailureReport {
+    pub failed_boxes: Vec<FailedBox>,
+
+    /// number of pixels containing data within all boxes of the array
+    pub data_pixels: usize,
+
+    /// number of pixels containing data within the failed boxes
+    pub failed_data_pixels: usize,
+}
+
+impl FailureReport {
+    pub fn is_empty(&self) -> bool {
+        self.failed_boxes.is_empty()
+    }
+
+    /// fraction of the data pixels within the failed boxes
+    pub fn failed_fraction(&self) -> f64 {
+        if self.data_pixels == 0 {
+            0.0
+        } else {
+            self.failed_data_pixels as f64 / self.data_pixels as f64
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Writing arrays to GeoTIFF files.
+//!
+//! The files are written using [gdal](https://github.com/georust/gdal). This requires the
+//! GDAL library to be installed, but leaves the handling of the GeoTIFF keys, the
+//! compression and the tiling to GDAL instead of writing the GeoTIFF tags manually using
+//! a pure-rust TIFF encoder.
+use std::path::Path;
+
+use gdal::raster::{Buffer, GdalType, RasterCreationOption};
+use gdal::spatial_ref::SpatialRef;
+use gdal::Driver;
+use ndarray::Array2;
+
+use crate::{AxisOrder, Error, Transform};
+
+/// Compression of the written GeoTIFF.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TiffCompression {
+    None,
+    Deflate,
+    Lzw,
+}
+
+impl TiffCompression {
+    const fn creation_option_value(&self) -> &'static str {
+        match self {
+            Self::None => "NONE",
+            Self::Deflate => "DEFLATE",
+            Self::Lzw => "LZW",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TiffOptions {
+    pub compression: TiffCompression,
+
+    /// write the raster in tiles instead of strips.
+    pub tiled: bool,
+}
+
+impl Default for TiffOptions {
+    fn default() -> Self {
+        Self {
+            compression: TiffCompression::Deflate,
+            tiled: false,
+        }
+    }
+}
+
+/// Write `array` as a single-band GeoTIFF to `path`.
+///
+/// The `transform` maps pixel coordinates to WGS84 coordinates, which is set as the CRS
+/// of the file.
+pub fn write_geotiff<T>(
+    array: &Array2<T>,
+    transform: &Transform,
+    axis_order: AxisOrder,
+    nodata_value: Option<T>,
+    path: &Path,
+    options: &TiffOptions,
+) -> Result<(), Error>
+where
+    T: GdalType + Copy + Into<f64>,
+{
+    // gdal expects the rows to be the first axis
+    let rows = match axis_order {
+        AxisOrder::XY => array.t(),
+        AxisOrder::YX => array.view(),
+    };
+    let (height, width) = rows.dim();
+    if width == 0 || height == 0 {
+        return Err(Error::EmptyArray);
+    }
+
+    let creation_options = [
+        RasterCreationOption {
+            key: "COMPRESS",
+            value: options.compression.creation_option_value(),
+        },
+        RasterCreationOption {
+            key: "TILED",
+            value: if options.tiled { "YES" } else { "NO" },
+        },
+    ];
+    let mut dataset = Driver::get("GTiff")?.create_with_band_type_with_options::<T, _>(
+        path,
+        width as isize,
+        height as isize,
+        1,
+        &creation_options,
+    )?;
+    dataset.set_geo_transform(&transform.to_gdal())?;
+    dataset.set_spatial_ref(&SpatialRef::from_epsg(4326)?)?;
+
+    let mut band = dataset.rasterband(1)?;
+    if let Some(nodata_value) = nodata_value {
+        band.set_no_data_value(nodata_value.into())?;
+    }
+    let buffer = Buffer::new((width, height), rows.iter().copied().collect());
+    band.write((0, 0), (width, height), &buffer)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use crate::geotiff::{write_geotiff, TiffCompression, TiffOptions};
+    use crate::{AxisOrder, Transform};
+
+    fn tiff_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("h3ron-ndarray-{}-{}.tif", name, std::process::id()))
+    }
+
+    fn read_back(path: &Path) -> (ndarray::Array2<u16>, [f64; 6], Option<f64>, i32) {
+        let dataset = gdal::Dataset::open(path).unwrap();
+        let band = dataset.rasterband(1).unwrap();
+        let arr = band
+            .read_as_array::<u16>((0, 0), band.size(), band.size(), None)
+            .unwrap();
+        (
+            arr,
+            dataset.geo_transform().unwrap(),
+            band.no_data_value(),
+            dataset.spatial_ref().unwrap().auth_code().unwrap(),
+        )
+    }
+
+    #[test]
+    fn roundtrip() {
+        let arr = ndarray::Array2::from_shape_fn((20, 30), |(y, x)| (y * 30 + x) as u16);
+        let gdal_transform = [10.0, 0.001, 0.0, 50.0, 0.0, -0.002];
+        let path = tiff_path("roundtrip");
+        write_geotiff(
+            &arr,
+            &Transform::from_gdal(&gdal_transform),
+            AxisOrder::YX,
+            Some(0),
+            &path,
+            &TiffOptions {
+                compression: TiffCompression::Lzw,
+                tiled: true,
+            },
+        )
+        .unwrap();
+
+        let (read_arr, read_transform, nodata_value, epsg) = read_back(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read_arr, arr);
+        for (read, expected) in read_transform.iter().zip(gdal_transform.iter()) {
+            assert_relative_eq!(*read, *expected);
+        }
+        assert_eq!(nodata_value, Some(0.0));
+        assert_eq!(epsg, 4326);
+    }
+
+    #[test]
+    fn roundtrip_xy() {
+        let arr = ndarray::Array2::from_shape_fn((30, 20), |(x, y)| (y * 30 + x) as u16);
+        let path = tiff_path("roundtrip-xy");
+        write_geotiff(
+            &arr,
+            &Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]),
+            AxisOrder::XY,
+            None,
+            &path,
+            &TiffOptions::default(),
+        )
+        .unwrap();
+
+        let (read_arr, _, nodata_value, _) = read_back(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read_arr, arr.t());
+        assert_eq!(nodata_value, None);
+    }
+}
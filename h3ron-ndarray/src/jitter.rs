@@ -0,0 +1,174 @@
+use std::f64::consts::TAU;
+
+use geo_types::Coordinate;
+use h3ron::{H3Cell, H3DirectedEdge, Index};
+
+use crate::error::Error;
+
+/// approximate length of a degree of latitude in meters
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// maximum number of times the displacement gets halved to keep a sample point within its cell
+const MAX_SHRINK_STEPS: usize = 8;
+
+/// Deterministic pseudo-random displacement of the points at which the values of cells are
+/// looked up in the array.
+///
+/// Converting rasters with high-frequency content - like categorical data with thin
+/// structures - to coarse h3 resolutions can produce moiré patterns, as the regular lattice of
+/// the sample points beats against the pixel grid. Jittering the sample points breaks up this
+/// regularity at the cost of some noise in the output. There is no benefit for smooth data or
+/// when the pixels are larger than the cells.
+///
+/// The displacement of a cell only depends on its index and the `seed`, so the results are
+/// reproducible and independent of the iteration order and the number of threads.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct JitterOptions {
+    /// maximum length of the displacement as a fraction of the circumradius of the cell,
+    /// clamped to `0.0..=1.0`. Displaced sample points are always kept within their cell.
+    pub max_fraction_of_cell: f64,
+
+    /// seed of the pseudo-random displacements.
+    pub seed: u64,
+}
+
+impl JitterOptions {
+    pub const fn new(max_fraction_of_cell: f64, seed: u64) -> Self {
+        Self {
+            max_fraction_of_cell,
+            seed,
+        }
+    }
+
+    /// displace `anchor` - a coordinate within `cell` - by the jitter of `cell`.
+    ///
+    /// Displacements leaving the cell are halved until the sample point is within the
+    /// cell again, falling back to `anchor`.
+    pub(crate) fn jitter(
+        &self,
+        cell: &H3Cell,
+        anchor: Coordinate<f64>,
+    ) -> Result<Coordinate<f64>, Error> {
+        let fraction = self.max_fraction_of_cell.clamp(0.0, 1.0);
+        if fraction.is_nan() || fraction == 0.0 {
+            return Ok(anchor);
+        }
+        let resolution = cell.resolution();
+
+        let hash = mix(cell.h3index() ^ mix(self.seed));
+        let angle = unit_interval(hash >> 32) * TAU;
+        // the square root distributes the sample points uniformly over the disk
+        let radius_fraction = unit_interval(hash & 0xffff_ffff).sqrt();
+
+        // the circumradius of a hexagon equals the length of its edges
+        let radius_m = radius_fraction * fraction * H3DirectedEdge::edge_length_avg_m(resolution)?;
+        let mut dy = radius_m * angle.sin() / METERS_PER_DEGREE;
+        let mut dx =
+            radius_m * angle.cos() / (METERS_PER_DEGREE * anchor.y.to_radians().cos().max(1e-6));
+
+        for _ in 0..MAX_SHRINK_STEPS {
+            let candidate = Coordinate {
+                x: anchor.x + dx,
+                y: anchor.y + dy,
+            };
+            if H3Cell::from_coordinate(candidate, resolution)? == *cell {
+                return Ok(candidate);
+            }
+            dx /= 2.0;
+            dy /= 2.0;
+        }
+        Ok(anchor)
+    }
+}
+
+/// splitmix64 finalizer
+const fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// map the lower 32 bits of `bits` to `[0, 1)`
+fn unit_interval(bits: u64) -> f64 {
+    (bits & 0xffff_ffff) as f64 / (1_u64 << 32) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use h3ron::{H3Cell, Index, ToCoordinate};
+
+    use crate::jitter::JitterOptions;
+
+    fn sample_cells() -> Vec<H3Cell> {
+        [
+            (10.0, 50.0, 7),
+            (-70.3, -33.4, 10),
+            (179.99, 65.0, 5),
+            (2.3, 0.1, 12),
+        ]
+        .iter()
+        .flat_map(|(x, y, resolution)| {
+            H3Cell::from_coordinate((*x, *y).into(), *resolution)
+                .unwrap()
+                .grid_disk(4)
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        })
+        // a pentagon
+        .chain(std::iter::once(H3Cell::new(0x8009fffffffffff_u64)))
+        .collect()
+    }
+
+    #[test]
+    fn jittered_points_stay_within_cells() {
+        let mut num_displaced = 0;
+        let mut num_cells = 0;
+        for seed in [0, 1, 12345] {
+            let jitter = JitterOptions::new(1.0, seed);
+            for cell in sample_cells() {
+                let anchor = cell.to_coordinate().unwrap();
+                let jittered = jitter.jitter(&cell, anchor).unwrap();
+                assert_eq!(
+                    H3Cell::from_coordinate(jittered, cell.resolution()).unwrap(),
+                    cell
+                );
+                if jittered != anchor {
+                    num_displaced += 1;
+                }
+                num_cells += 1;
+            }
+        }
+        assert!(num_displaced > num_cells * 9 / 10);
+    }
+
+    #[test]
+    fn jitter_is_seeded() {
+        let cells = sample_cells();
+        let jittered = |jitter: JitterOptions| {
+            cells
+                .iter()
+                .map(|cell| jitter.jitter(cell, cell.to_coordinate().unwrap()).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let seed_1 = jittered(JitterOptions::new(0.5, 1));
+        assert_eq!(seed_1, jittered(JitterOptions::new(0.5, 1)));
+
+        let seed_2 = jittered(JitterOptions::new(0.5, 2));
+        assert_eq!(seed_2, jittered(JitterOptions::new(0.5, 2)));
+        assert!(
+            seed_1
+                .iter()
+                .zip(seed_2.iter())
+                .filter(|(a, b)| a != b)
+                .count()
+                > cells.len() * 9 / 10
+        );
+
+        // no displacement
+        for (cell, coordinate) in cells.iter().zip(jittered(JitterOptions::new(0.0, 1))) {
+            assert_eq!(coordinate, cell.to_coordinate().unwrap());
+        }
+    }
+}
@@ -22,13 +22,41 @@ extern crate approx;
 #[macro_use]
 extern crate ndarray;
 
-pub use crate::array::{AxisOrder, H3Converter};
+pub use crate::aggregation::AggregationMethod;
+pub use crate::array::{AnchorStrategy, AxisOrder, BoxDetection, H3Converter};
+pub use crate::axis_order::{infer_axis_order, validate_axis_order, AxisOrderMismatch, Confidence};
+pub use crate::cell_sink::{CellSink, ColumnarSink, CompactedCellsSink};
 pub use crate::error::Error;
-pub use crate::resolution::ResolutionSearchMode;
+pub use crate::estimate::{BoxEstimate, ConversionEstimate};
+pub use crate::failure::{FailedBox, FailureReport};
+pub use crate::jitter::JitterOptions;
+pub use crate::mask::MaskRule;
+pub use crate::multiband::{H3MultiConverter, MultiBandCells};
+pub use crate::nodata::NodataCheck;
+pub use crate::rasterize::{compacted_h3_to_array, h3_to_array};
+pub use crate::resolution::{h3_resolution_areas, h3_resolution_for_rect, ResolutionSearchMode};
+pub use crate::scaling::ValueScaling;
+pub use crate::sink::H3ConverterSink;
+pub use crate::stats::ConversionStats;
 pub use crate::transform::Transform;
 
+pub mod aggregation;
 pub mod array;
+pub mod axis_order;
+pub mod cell_sink;
 pub mod error;
+pub mod estimate;
+pub mod failure;
+#[cfg(feature = "geotiff-write")]
+pub mod geotiff;
+pub mod jitter;
+pub mod mask;
+pub mod multiband;
+pub mod nodata;
+pub mod rasterize;
 pub mod resolution;
-mod sphere;
+pub mod scaling;
+pub mod sink;
+pub mod sphere;
+pub mod stats;
 pub mod transform;
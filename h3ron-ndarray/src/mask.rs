@@ -0,0 +1,69 @@
+use std::ops::BitAnd;
+
+use ndarray::{Array2, ArrayView2, Zip};
+
+/// Rule to decide which values of a validity mask mark valid pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskRule<M> {
+    /// All values except zero are valid - like in alpha bands.
+    NonZero,
+
+    /// Only the given value is valid.
+    Equals(M),
+
+    /// Values having all of the given bits set are valid - like in bitmasks of quality flags.
+    BitsSet(M),
+}
+
+impl<M> MaskRule<M>
+where
+    M: Copy + PartialEq + Default + BitAnd<Output = M>,
+{
+    pub fn is_valid(&self, value: &M) -> bool {
+        match self {
+            Self::NonZero => *value != M::default(),
+            Self::Equals(expected) => value == expected,
+            Self::BitsSet(bits) => (*value & *bits) == *bits,
+        }
+    }
+
+    /// apply the rule to all elements of the `mask`.
+    pub fn apply(&self, mask: &ArrayView2<M>) -> Array2<bool> {
+        mask.map(|value| self.is_valid(value))
+    }
+}
+
+/// `true` for all pixels which are valid according to the `validity` and for which
+/// `is_nodata` is `false`.
+///
+/// Both arrays must have the same shape.
+pub(crate) fn data_indicator<T, F>(
+    values: &ArrayView2<T>,
+    validity: &ArrayView2<bool>,
+    is_nodata: F,
+) -> Array2<bool>
+where
+    F: Fn(&T) -> bool,
+{
+    Zip::from(values)
+        .and(validity)
+        .map_collect(|value, valid| *valid && !is_nodata(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MaskRule;
+
+    #[test]
+    fn rules() {
+        assert!(MaskRule::NonZero.is_valid(&255_u8));
+        assert!(!MaskRule::NonZero.is_valid(&0_u8));
+
+        assert!(MaskRule::Equals(3_u8).is_valid(&3));
+        assert!(!MaskRule::Equals(3_u8).is_valid(&2));
+
+        assert!(MaskRule::BitsSet(0x0c_u16).is_valid(&0x0c));
+        assert!(MaskRule::BitsSet(0x0c_u16).is_valid(&0xff));
+        assert!(!MaskRule::BitsSet(0x0c_u16).is_valid(&0x04));
+    }
+}
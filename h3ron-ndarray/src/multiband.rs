@@ -0,0 +1,226 @@
+//! Conversion of rasters with multiple bands.
+use ndarray::{Array2, ArrayView2, ArrayView3, Axis};
+
+use h3ron::H3Cell;
+
+use crate::array::{AxisOrder, H3Converter};
+use crate::cell_sink::ColumnarSink;
+use crate::error::Error;
+use crate::nodata::NodataCheck;
+use crate::transform::Transform;
+
+/// the position of pixels without data in any of the bands.
+const NODATA_PIXEL: usize = usize::MAX;
+
+/// The cells of a multi-band conversion with the value of each band as separate columns.
+///
+/// All columns have the same length. The cells are ordered and not compacted, so the values
+/// of all bands are aligned on the same cells.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiBandCells<T> {
+    pub cells: Vec<H3Cell>,
+
+    /// the values of each band in the order of the bands. `None` for cells where the band
+    /// contains no data.
+    pub bands: Vec<Vec<Option<T>>>,
+}
+
+/// Converts the bands of a raster sharing the same [`Transform`] and nodata value to h3.
+///
+/// In contrast to converting each band using a [`H3Converter`], all bands are converted to the
+/// same cells, so the values of the bands can be used as columns of a table.
+pub struct H3MultiConverter<'a, T> {
+    bands: Vec<ArrayView2<'a, T>>,
+    nodata_value: &'a Option<T>,
+    transform: &'a Transform,
+    axis_order: AxisOrder,
+    nodata_check: Option<NodataCheck<T>>,
+}
+
+impl<'a, T> H3MultiConverter<'a, T>
+where
+    T: PartialEq + Clone,
+{
+    /// Create a converter for the `bands`. All bands must have the same shape.
+    pub fn new(
+        bands: Vec<ArrayView2<'a, T>>,
+        nodata_value: &'a Option<T>,
+        transform: &'a Transform,
+        axis_order: AxisOrder,
+    ) -> Result<Self, Error> {
+        match bands.first() {
+            Some(first) => {
+                if bands.iter().any(|band| band.shape() != first.shape()) {
+                    return Err(Error::BandShapeMismatch);
+                }
+            }
+            None => return Err(Error::NoBands),
+        }
+        Ok(Self {
+            bands,
+            nodata_value,
+            transform,
+            axis_order,
+            nodata_check: None,
+        })
+    }
+
+    /// Create a converter for a 3-dimensional array with the bands along the first axis.
+    pub fn from_array3(
+        arr: ArrayView3<'a, T>,
+        nodata_value: &'a Option<T>,
+        transform: &'a Transform,
+        axis_order: AxisOrder,
+    ) -> Result<Self, Error> {
+        let bands = (0..arr.shape()[0])
+            .map(|band_i| arr.index_axis_move(Axis(0), band_i))
+            .collect();
+        Self::new(bands, nodata_value, transform, axis_order)
+    }
+
+    /// Use a [`NodataCheck`] in addition to the nodata value to decide which values are
+    /// nodata. See [`H3Converter::with_nodata_check`].
+    pub fn with_nodata_check(mut self, nodata_check: NodataCheck<T>) -> Self {
+        self.nodata_check = Some(nodata_check);
+        self
+    }
+
+    fn is_nodata(&self, value: &T) -> bool {
+        self.nodata_value.as_ref() == Some(value)
+            || self
+                .nodata_check
+                .as_ref()
+                .is_some_and(|nodata_check| nodata_check.is_nodata(value))
+    }
+
+    /// Convert all bands to uncompacted cells at `h3_resolution`.
+    ///
+    /// The value of a cell is taken from the pixel at its centroid. Cells are skipped when
+    /// none of the bands contains data at that pixel, bands without data result in `None`.
+    ///
+    /// The conversion uses an additional array of `usize` with the shape of the bands to
+    /// locate the pixels of the cells.
+    pub fn to_h3(&self, h3_resolution: u8) -> Result<MultiBandCells<T>, Error> {
+        let shape = self.bands[0].raw_dim();
+        let pixels = Array2::from_shape_fn(shape, |position| {
+            if self
+                .bands
+                .iter()
+                .any(|band| !self.is_nodata(&band[position]))
+            {
+                position.0 * shape[1] + position.1
+            } else {
+                NODATA_PIXEL
+            }
+        });
+
+        let pixels_view = pixels.view();
+        let nodata_pixel = Some(NODATA_PIXEL);
+        let (cells, cell_pixels) =
+            H3Converter::new(&pixels_view, &nodata_pixel, self.transform, self.axis_order)
+                .drive(h3_resolution, ColumnarSink::default())?;
+
+        let bands = self
+            .bands
+            .iter()
+            .map(|band| {
+                cell_pixels
+                    .iter()
+                    .map(|pixel| {
+                        let value = &band[[**pixel / shape[1], **pixel % shape[1]]];
+                        if self.is_nodata(value) {
+                            None
+                        } else {
+                            Some(value.clone())
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Ok(MultiBandCells { cells, bands })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use h3ron::collections::H3CellMap;
+
+    use crate::{AxisOrder, Error, H3Converter, H3MultiConverter, Transform};
+
+    fn bands_fixture() -> (ndarray::Array3<u8>, Transform) {
+        // band 0 contains data in the left half, band 1 in the upper half and the lower
+        // right quarter contains no data at all
+        let mut arr = ndarray::Array3::<u8>::zeros((2, 40, 40));
+        arr.slice_mut(s![0, .., ..20]).fill(3);
+        arr.slice_mut(s![1, ..20, ..]).fill(7);
+        arr[[1, 5, 30]] = 8;
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        (arr, transform)
+    }
+
+    #[test]
+    fn multiband_equals_single_band_conversions() {
+        let h3_resolution = 10;
+        let (arr, transform) = bands_fixture();
+        let nodata = Some(0_u8);
+        let converter =
+            H3MultiConverter::from_array3(arr.view(), &nodata, &transform, AxisOrder::YX).unwrap();
+        let multiband = converter.to_h3(h3_resolution).unwrap();
+
+        assert!(!multiband.cells.is_empty());
+        assert_eq!(multiband.bands.len(), 2);
+        assert!(multiband.cells.windows(2).all(|w| w[0] < w[1]));
+        for band in multiband.bands.iter() {
+            assert_eq!(band.len(), multiband.cells.len());
+        }
+        for i in 0..multiband.cells.len() {
+            assert!(multiband.bands.iter().any(|band| band[i].is_some()));
+        }
+
+        for (band_i, band_values) in multiband.bands.iter().enumerate() {
+            let band = arr.index_axis(ndarray::Axis(0), band_i);
+            let single = H3Converter::new(&band, &nodata, &transform, AxisOrder::YX)
+                .to_h3(h3_resolution, false)
+                .unwrap();
+            let mut expected: H3CellMap<u8> = H3CellMap::default();
+            for (value, cells) in single {
+                for cell in cells.iter_uncompacted_cells(h3_resolution) {
+                    expected.insert(cell.unwrap(), *value);
+                }
+            }
+
+            let found: H3CellMap<u8> = multiband
+                .cells
+                .iter()
+                .zip(band_values.iter())
+                .filter_map(|(cell, value)| value.map(|value| (*cell, value)))
+                .collect();
+            assert_eq!(found, expected);
+        }
+
+        // both bands overlap in the upper left quarter, and each has cells without data
+        assert!(multiband
+            .cells
+            .iter()
+            .enumerate()
+            .any(|(i, _)| multiband.bands[0][i].is_some() && multiband.bands[1][i].is_some()));
+        assert!(multiband.bands[0].iter().any(Option::is_none));
+        assert!(multiband.bands[1].iter().any(Option::is_none));
+    }
+
+    #[test]
+    fn multiband_band_shapes() {
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        let nodata = None;
+        let a = ndarray::Array2::<u8>::ones((10, 10));
+        let b = ndarray::Array2::<u8>::ones((10, 12));
+        assert!(matches!(
+            H3MultiConverter::new(vec![a.view(), b.view()], &nodata, &transform, AxisOrder::YX),
+            Err(Error::BandShapeMismatch)
+        ));
+        assert!(matches!(
+            H3MultiConverter::<u8>::new(vec![], &nodata, &transform, AxisOrder::YX),
+            Err(Error::NoBands)
+        ));
+    }
+}
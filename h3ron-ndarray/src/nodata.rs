@@ -0,0 +1,71 @@
+use std::fmt;
+
+/// Check to decide which values of an array are nodata.
+///
+/// Used by [`H3Converter::with_nodata_check`](crate::H3Converter::with_nodata_check) for rasters
+/// using more than a single nodata value.
+pub enum NodataCheck<T> {
+    /// The given value is nodata.
+    Value(T),
+
+    /// All of the given values are nodata - like multiple sentinel values.
+    Values(Vec<T>),
+
+    /// All values for which the predicate returns `true` are nodata - like values
+    /// below a threshold.
+    Predicate(Box<dyn Fn(&T) -> bool + Send + Sync>),
+}
+
+impl<T> NodataCheck<T>
+where
+    T: PartialEq,
+{
+    /// create a [`NodataCheck::Predicate`] from the closure `f`.
+    pub fn predicate<F>(f: F) -> Self
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        Self::Predicate(Box::new(f))
+    }
+
+    pub fn is_nodata(&self, value: &T) -> bool {
+        match self {
+            Self::Value(nodata_value) => nodata_value == value,
+            Self::Values(nodata_values) => nodata_values.contains(value),
+            Self::Predicate(f) => f(value),
+        }
+    }
+}
+
+impl<T> fmt::Debug for NodataCheck<T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Value(value) => f.debug_tuple("Value").field(value).finish(),
+            Self::Values(values) => f.debug_tuple("Values").field(values).finish(),
+            Self::Predicate(_) => f.write_str("Predicate(..)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::NodataCheck;
+
+    #[test]
+    fn checks() {
+        assert!(NodataCheck::Value(3_u8).is_nodata(&3));
+        assert!(!NodataCheck::Value(3_u8).is_nodata(&2));
+
+        let values = NodataCheck::Values(vec![0_u8, 254, 255]);
+        assert!(values.is_nodata(&0));
+        assert!(values.is_nodata(&254));
+        assert!(!values.is_nodata(&253));
+
+        let below = NodataCheck::predicate(|v: &i16| *v < -100);
+        assert!(below.is_nodata(&-101));
+        assert!(!below.is_nodata(&-100));
+    }
+}
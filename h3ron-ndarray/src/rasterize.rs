@@ -0,0 +1,177 @@
+//! Rasterizing H3 cells back to arrays.
+//!
+//! This is the inverse of [`H3Converter::to_h3`](crate::H3Converter::to_h3): Each pixel
+//! receives the value of the cell containing the center of the pixel.
+use std::hash::Hash;
+
+use geo_types::Coordinate;
+use ndarray::{Array2, Axis};
+use rayon::prelude::*;
+
+use h3ron::collections::{CompactedCellVec, H3CellMap, HashMap};
+use h3ron::{H3Cell, HasH3Resolution};
+
+use crate::{AxisOrder, Error, Transform};
+
+/// Rasterize the values of `cell_values` to an array of the given `shape`.
+///
+/// The `transform` maps pixel coordinates to coordinates, like the transform
+/// passed to [`H3Converter::new`](crate::H3Converter::new). Pixels with a center not
+/// located in any of the cells are set to `nodata_value`.
+///
+/// The cells may be of mixed resolutions. When cells of different resolutions overlap,
+/// the value of the finest cell is used.
+pub fn h3_to_array<T>(
+    cell_values: &H3CellMap<T>,
+    transform: &Transform,
+    shape: (usize, usize),
+    axis_order: AxisOrder,
+    nodata_value: T,
+) -> Result<Array2<T>, Error>
+where
+    T: Copy + Send + Sync,
+{
+    // finest resolution first
+    let mut resolutions: Vec<u8> = cell_values
+        .keys()
+        .map(|cell| cell.h3_resolution())
+        .collect::<Vec<_>>();
+    resolutions.sort_unstable_by(|a, b| b.cmp(a));
+    resolutions.dedup();
+
+    let mut arr = Array2::from_elem(shape, nodata_value);
+    let finest_resolution = match resolutions.first() {
+        Some(resolution) => *resolution,
+        None => return Ok(arr),
+    };
+
+    arr.axis_iter_mut(Axis(0))
+        .into_par_iter()
+        .enumerate()
+        .try_for_each(|(row, mut lane)| {
+            let mut xs = vec![0.0; lane.len()];
+            let mut ys = vec![0.0; lane.len()];
+            transform.row_coordinates(row, 0..lane.len(), axis_order, &mut xs, &mut ys);
+
+            for ((pixel, x), y) in lane.iter_mut().zip(xs).zip(ys) {
+                let coordinate = Coordinate { x, y };
+                if !(coordinate.x.is_finite() && coordinate.y.is_finite()) {
+                    return Err(Error::NonFiniteCoordinates);
+                }
+                let cell = H3Cell::from_coordinate(coordinate, finest_resolution)?;
+                for resolution in resolutions.iter() {
+                    let cell = if *resolution == finest_resolution {
+                        cell
+                    } else {
+                        cell.get_parent(*resolution)?
+                    };
+                    if let Some(value) = cell_values.get(&cell) {
+                        *pixel = *value;
+                        break;
+                    }
+                }
+            }
+            Ok(())
+        })?;
+    Ok(arr)
+}
+
+/// Rasterize the compacted cells of the values of `compacted_values` - as returned by
+/// [`H3Converter::to_h3`](crate::H3Converter::to_h3) - to an array of the given `shape`.
+///
+/// The cells get resolved without uncompacting them. See [`h3_to_array`].
+pub fn compacted_h3_to_array<T>(
+    compacted_values: &HashMap<&T, CompactedCellVec>,
+    transform: &Transform,
+    shape: (usize, usize),
+    axis_order: AxisOrder,
+    nodata_value: T,
+) -> Result<Array2<T>, Error>
+where
+    T: Copy + Send + Sync + Eq + Hash,
+{
+    let mut cell_values = H3CellMap::default();
+    for (value, compacted_vec) in compacted_values.iter() {
+        for cell in compacted_vec.iter_compacted_cells() {
+            cell_values.insert(cell, **value);
+        }
+    }
+    h3_to_array(&cell_values, transform, shape, axis_order, nodata_value)
+}
+
+#[cfg(test)]
+mod tests {
+    use h3ron::collections::H3CellMap;
+    use h3ron::{H3Cell, ToCoordinate};
+
+    use crate::rasterize::{compacted_h3_to_array, h3_to_array};
+    use crate::{AxisOrder, H3Converter, Transform};
+
+    #[test]
+    fn mixed_resolutions() {
+        let parent = H3Cell::from_coordinate((10.2, 50.3).into(), 6).unwrap();
+        let child = parent.center_child(8).unwrap();
+        let mut cell_values = H3CellMap::default();
+        cell_values.insert(parent, 1_u8);
+        cell_values.insert(child, 2_u8);
+
+        let center = parent.to_coordinate().unwrap();
+        let transform =
+            Transform::from_gdal(&[center.x - 0.1, 0.001, 0.0, center.y + 0.1, 0.0, -0.001]);
+        let arr = h3_to_array(&cell_values, &transform, (200, 200), AxisOrder::YX, 0).unwrap();
+
+        // the pixel at the center of the child
+        let inverse_transform = transform.invert().unwrap();
+        let child_pixel = &inverse_transform * child.to_coordinate().unwrap();
+        assert_eq!(
+            arr[[child_pixel.y as usize, child_pixel.x as usize]],
+            2,
+            "child"
+        );
+
+        // the corners are outside of the parent
+        assert_eq!(arr[[0, 0]], 0);
+        assert_eq!(arr[[199, 199]], 0);
+
+        assert!(arr.iter().any(|v| *v == 1));
+        assert!(arr.iter().filter(|v| **v == 2).count() < arr.iter().filter(|v| **v == 1).count());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let arr = ndarray::Array2::from_shape_fn((100, 120), |(y, x)| match (y < 50, x < 60) {
+            (true, true) => 1_u8,
+            (true, false) => 2,
+            (false, true) => 3,
+            (false, false) => 0,
+        });
+        let transform = Transform::from_gdal(&[10.0, 0.001, 0.0, 50.0, 0.0, -0.001]);
+        let view = arr.view();
+        let nodata = Some(0_u8);
+        let converter = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX);
+        let h3_resolution = 11;
+
+        let compacted = converter.to_h3(h3_resolution, true).unwrap();
+        let rasterized =
+            compacted_h3_to_array(&compacted, &transform, arr.dim(), AxisOrder::YX, 0).unwrap();
+
+        let mut cell_values = H3CellMap::default();
+        for (value, compacted_vec) in compacted.iter() {
+            for cell in compacted_vec.iter_uncompacted_cells(h3_resolution) {
+                cell_values.insert(cell.unwrap(), **value);
+            }
+        }
+        let uncompacted_rasterized =
+            h3_to_array(&cell_values, &transform, arr.dim(), AxisOrder::YX, 0).unwrap();
+        assert_eq!(rasterized, uncompacted_rasterized);
+
+        // the cells are smaller than the pixels, so only pixels at the borders between
+        // the values may differ
+        let num_differing = arr
+            .iter()
+            .zip(rasterized.iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert!(num_differing < arr.len() / 20);
+    }
+}
@@ -16,6 +16,16 @@ pub enum ResolutionSearchMode {
 
     /// Chose the h3 resolution where the area of the h3index is smaller than the area of a pixel.
     SmallerThanPixel,
+
+    /// Chose the coarsest h3 resolution where each pixel contains at least about the given number
+    /// of h3indexes. The area of the h3index is smaller than the area of a pixel divided by
+    /// the factor.
+    MinIndexesPerPixel(f64),
+
+    /// Chose the coarsest h3 resolution where each h3index contains at most about the given number
+    /// of pixels. The area of the h3index is smaller than the area of a pixel multiplied by
+    /// the factor.
+    MaxPixelsPerIndex(f64),
 }
 
 /// Find the h3 resolution closed to the size of a pixel in an array
@@ -26,6 +36,46 @@ pub fn nearest_h3_resolution(
     axis_order: &AxisOrder,
     search_mode: ResolutionSearchMode,
 ) -> Result<u8, Error> {
+    let (center, area_pixel) = pixel_area(shape, transform, axis_order)?;
+    validate_search_mode(&search_mode)?;
+
+    let nearest_h3_res =
+        resolution_for_pixel_area(center, area_pixel, &search_mode)?.unwrap_or(match search_mode {
+            // used when no resolution is fine enough
+            ResolutionSearchMode::MinIndexesPerPixel(_)
+            | ResolutionSearchMode::MaxPixelsPerIndex(_) => H3_MAX_RESOLUTION,
+            _ => 0,
+        });
+    Ok(nearest_h3_res)
+}
+
+/// The area of the cell at the center of an array of the given shape with the given transform
+/// for each h3 resolution, compared to the area of a pixel.
+///
+/// Returns the resolution, the area of the cell in square meters and the ratio of the area of
+/// the cell to the area of a pixel for all resolutions, ordered from coarse to fine. These are
+/// the areas [`nearest_h3_resolution`] chooses from, so the table can be used to log the
+/// choice or to apply a different policy.
+pub fn h3_resolution_areas(
+    shape: &[usize],
+    transform: &Transform,
+    axis_order: &AxisOrder,
+) -> Result<Vec<(u8, f64, f64)>, Error> {
+    let (center, area_pixel) = pixel_area(shape, transform, axis_order)?;
+    (H3_MIN_RESOLUTION..=H3_MAX_RESOLUTION)
+        .map(|h3_res| {
+            let area_h3_index = center_cell_area(center, h3_res)?;
+            Ok((h3_res, area_h3_index, area_h3_index / area_pixel))
+        })
+        .collect()
+}
+
+/// the center of an array of the given shape and the area of its pixels in square meters.
+fn pixel_area(
+    shape: &[usize],
+    transform: &Transform,
+    axis_order: &AxisOrder,
+) -> Result<(Coordinate<f64>, f64), Error> {
     if shape.len() != 2 {
         return Err(Error::UnsupportedArrayShape);
     }
@@ -42,55 +92,192 @@ pub fn nearest_h3_resolution(
     );
     let area_pixel = area_squaremeters_rect(&bbox_array)
         / (shape[axis_order.x_axis()] * shape[axis_order.y_axis()]) as f64;
-    let center_of_array = bbox_array.center();
+    Ok((bbox_array.center(), area_pixel))
+}
 
-    let mut nearest_h3_res = 0;
-    let mut area_difference = None;
-    for h3_res in H3_MIN_RESOLUTION..=H3_MAX_RESOLUTION {
-        // calculate the area of the center index to avoid using the approximate values
-        // of the h3ron hexArea functions
-        let area_h3_index = area_squaremeters_linearring(
-            H3Cell::from_coordinate(center_of_array, h3_res)?
-                .to_polygon()?
-                .exterior(),
-        );
+/// Find the finest h3 resolution where the estimated number of cells covering `rect` (WGS84
+/// coordinates) does not exceed `max_indexes`.
+///
+/// The rect is treated like an array of `max_indexes` pixels: `search_mode` selects the
+/// resolution for the area available to each cell, which then gets coarsened until the
+/// estimated number of cells fits into `max_indexes`. Like [`nearest_h3_resolution`], the number
+/// of cells is estimated using the exact area of the cell at the center of the rect.
+///
+/// `ResolutionSearchMode::SmallerThanPixel` results in the finest resolution within the budget,
+/// modes selecting coarser cells - like `MinDiff` or `MaxPixelsPerIndex` with a factor above `1` -
+/// may result in a coarser resolution.
+///
+/// Rects without width or height fail with [`Error::EmptyRect`]. When not even the coarsest
+/// resolution fits into `max_indexes`, [`Error::NoResolutionWithinBudget`] is returned.
+pub fn h3_resolution_for_rect(
+    rect: &Rect<f64>,
+    max_indexes: usize,
+    search_mode: ResolutionSearchMode,
+) -> Result<u8, Error> {
+    if !(rect.min().x.is_finite()
+        && rect.min().y.is_finite()
+        && rect.max().x.is_finite()
+        && rect.max().y.is_finite())
+    {
+        return Err(Error::NonFiniteCoordinates);
+    }
+    if rect.width() <= 0.0 || rect.height() <= 0.0 {
+        return Err(Error::EmptyRect);
+    }
+    if max_indexes == 0 {
+        return Err(Error::NoResolutionWithinBudget(max_indexes));
+    }
+    validate_search_mode(&search_mode)?;
 
-        match search_mode {
-            ResolutionSearchMode::SmallerThanPixel => {
-                if area_h3_index <= area_pixel {
-                    nearest_h3_res = h3_res;
-                    break;
-                }
-            }
+    let area_rect = area_squaremeters_rect(rect);
+    let center = rect.center();
+    let mut h3_res =
+        resolution_for_pixel_area(center, area_rect / max_indexes as f64, &search_mode)?
+            .unwrap_or(H3_MAX_RESOLUTION);
+    loop {
+        if area_rect / center_cell_area(center, h3_res)? <= max_indexes as f64 {
+            return Ok(h3_res);
+        }
+        if h3_res == H3_MIN_RESOLUTION {
+            return Err(Error::NoResolutionWithinBudget(max_indexes));
+        }
+        h3_res -= 1;
+    }
+}
 
-            ResolutionSearchMode::MinDiff => {
-                let new_area_difference = if area_h3_index > area_pixel {
-                    area_h3_index - area_pixel
-                } else {
-                    area_pixel - area_h3_index
-                };
-                if let Some(old_area_difference) = area_difference {
-                    if old_area_difference < new_area_difference {
-                        nearest_h3_res = h3_res - 1;
-                        break;
-                    } else {
-                        area_difference = Some(new_area_difference);
-                    }
-                } else {
-                    area_difference = Some(new_area_difference);
-                }
+fn validate_search_mode(search_mode: &ResolutionSearchMode) -> Result<(), Error> {
+    if let ResolutionSearchMode::MinIndexesPerPixel(factor)
+    | ResolutionSearchMode::MaxPixelsPerIndex(factor) = search_mode
+    {
+        if !(factor.is_finite() && *factor > 0.0) {
+            return Err(Error::InvalidResolutionSearchFactor(*factor));
+        }
+    }
+    Ok(())
+}
+
+/// the area of the cell at `center` in square meters.
+///
+/// Calculates the area of the cell to avoid using the approximate values
+/// of the h3ron hexArea functions
+fn center_cell_area(center: Coordinate<f64>, h3_res: u8) -> Result<f64, Error> {
+    Ok(area_squaremeters_linearring(
+        H3Cell::from_coordinate(center, h3_res)?
+            .to_polygon()?
+            .exterior(),
+    ))
+}
+
+/// The exact areas of the cells at `center`, calculated on demand.
+struct CenterCellAreas {
+    center: Coordinate<f64>,
+    areas: [Option<f64>; H3_MAX_RESOLUTION as usize + 1],
+}
+
+impl CenterCellAreas {
+    fn new(center: Coordinate<f64>) -> Self {
+        Self {
+            center,
+            areas: Default::default(),
+        }
+    }
+
+    fn area(&mut self, h3_res: u8) -> Result<f64, Error> {
+        if let Some(area) = self.areas[h3_res as usize] {
+            return Ok(area);
+        }
+        let area = center_cell_area(self.center, h3_res)?;
+        self.areas[h3_res as usize] = Some(area);
+        Ok(area)
+    }
+
+    /// The coarsest resolution where the area of the cell is not larger than `max_area`.
+    ///
+    /// The search starts at the resolution estimated from the average cell areas of h3 and
+    /// only calculates the exact areas of the resolutions next to the estimate. As the area
+    /// of the cells decreases with each finer resolution, this equals testing all resolutions
+    /// starting from the coarsest one.
+    fn coarsest_resolution_within(&mut self, max_area: f64) -> Result<Option<u8>, Error> {
+        let mut h3_res = estimate_resolution_within(max_area)?;
+        if self.area(h3_res)? <= max_area {
+            while h3_res > H3_MIN_RESOLUTION && self.area(h3_res - 1)? <= max_area {
+                h3_res -= 1;
+            }
+            return Ok(Some(h3_res));
+        }
+        while h3_res < H3_MAX_RESOLUTION {
+            h3_res += 1;
+            if self.area(h3_res)? <= max_area {
+                return Ok(Some(h3_res));
             }
         }
+        Ok(None)
     }
+}
 
-    Ok(nearest_h3_res)
+/// the coarsest resolution where the average area of the cells is not larger than `max_area`.
+fn estimate_resolution_within(max_area: f64) -> Result<u8, Error> {
+    for h3_res in H3_MIN_RESOLUTION..=H3_MAX_RESOLUTION {
+        if H3Cell::area_avg_m2(h3_res)? <= max_area {
+            return Ok(h3_res);
+        }
+    }
+    Ok(H3_MAX_RESOLUTION)
+}
+
+/// Find the h3 resolution for pixels of the area `area_pixel` located at `center` using the
+/// `search_mode`. `None` when no resolution matches.
+fn resolution_for_pixel_area(
+    center: Coordinate<f64>,
+    area_pixel: f64,
+    search_mode: &ResolutionSearchMode,
+) -> Result<Option<u8>, Error> {
+    let mut areas = CenterCellAreas::new(center);
+    match search_mode {
+        ResolutionSearchMode::SmallerThanPixel => areas.coarsest_resolution_within(area_pixel),
+        ResolutionSearchMode::MinIndexesPerPixel(factor) => {
+            areas.coarsest_resolution_within(area_pixel / factor)
+        }
+        ResolutionSearchMode::MaxPixelsPerIndex(factor) => {
+            areas.coarsest_resolution_within(area_pixel * factor)
+        }
+        ResolutionSearchMode::MinDiff => {
+            // the smallest difference is found either at the first resolution smaller than
+            // the pixel or at the resolution before. Ties go to the finer resolution. No
+            // resolution is returned when the differences still decrease at the finest resolution.
+            let h3_res = match areas.coarsest_resolution_within(area_pixel)? {
+                Some(h3_res) => h3_res,
+                None => return Ok(None),
+            };
+            if h3_res == H3_MIN_RESOLUTION {
+                return Ok(Some(h3_res));
+            }
+            let difference_coarser = areas.area(h3_res - 1)? - area_pixel;
+            let difference = area_pixel - areas.area(h3_res)?;
+            if difference_coarser < difference {
+                Ok(Some(h3_res - 1))
+            } else if h3_res == H3_MAX_RESOLUTION {
+                Ok(None)
+            } else {
+                Ok(Some(h3_res))
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::resolution::{nearest_h3_resolution, ResolutionSearchMode};
+    use geo_types::{Coordinate, Rect};
+
+    use h3ron::{H3Cell, Index, ToCoordinate, H3_MAX_RESOLUTION, H3_MIN_RESOLUTION};
+
+    use crate::resolution::{
+        center_cell_area, h3_resolution_areas, h3_resolution_for_rect, nearest_h3_resolution,
+        resolution_for_pixel_area, ResolutionSearchMode,
+    };
+    use crate::sphere::area_squaremeters_rect;
     use crate::transform::Transform;
-    use crate::AxisOrder;
+    use crate::{AxisOrder, Error};
 
     #[test]
     fn test_nearest_h3_resolution() {
@@ -121,4 +308,212 @@ mod tests {
         .unwrap();
         assert_eq!(h3_res2, 11); // TODO: validate
     }
+
+    fn r_tiff_resolution(search_mode: ResolutionSearchMode) -> u8 {
+        let gt = Transform::from_gdal(&[8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135]);
+        nearest_h3_resolution(&[2000_usize, 2000_usize], &gt, &AxisOrder::YX, search_mode).unwrap()
+    }
+
+    #[test]
+    fn test_factor_one_matches_smaller_than_pixel() {
+        let smaller_than_pixel = r_tiff_resolution(ResolutionSearchMode::SmallerThanPixel);
+        assert_eq!(
+            r_tiff_resolution(ResolutionSearchMode::MinIndexesPerPixel(1.0)),
+            smaller_than_pixel
+        );
+        assert_eq!(
+            r_tiff_resolution(ResolutionSearchMode::MaxPixelsPerIndex(1.0)),
+            smaller_than_pixel
+        );
+    }
+
+    #[test]
+    fn test_factor_monotonic() {
+        let factors = [
+            0.1, 0.5, 1.0, 2.0, 4.0, 7.0, 10.0, 50.0, 343.0, 1.0e4, 1.0e9,
+        ];
+        let mut previous_min_indexes = 0;
+        let mut previous_max_pixels = 15;
+        for factor in factors {
+            // more indexes per pixel never results in a coarser resolution
+            let min_indexes = r_tiff_resolution(ResolutionSearchMode::MinIndexesPerPixel(factor));
+            assert!(min_indexes >= previous_min_indexes);
+            previous_min_indexes = min_indexes;
+
+            // more pixels per index never results in a finer resolution
+            let max_pixels = r_tiff_resolution(ResolutionSearchMode::MaxPixelsPerIndex(factor));
+            assert!(max_pixels <= previous_max_pixels);
+            previous_max_pixels = max_pixels;
+        }
+        assert!(r_tiff_resolution(ResolutionSearchMode::MinIndexesPerPixel(7.0)) > 11);
+        assert!(r_tiff_resolution(ResolutionSearchMode::MaxPixelsPerIndex(7.0)) < 11);
+    }
+
+    #[test]
+    fn test_invalid_factor() {
+        let gt = Transform::from_gdal(&[8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135]);
+        for factor in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(nearest_h3_resolution(
+                &[2000_usize, 2000_usize],
+                &gt,
+                &AxisOrder::YX,
+                ResolutionSearchMode::MinIndexesPerPixel(factor),
+            )
+            .is_err());
+        }
+    }
+
+    /// the search calculating the exact areas of all resolutions starting from the coarsest one
+    fn resolution_for_pixel_area_exhaustive(
+        center: Coordinate<f64>,
+        area_pixel: f64,
+        search_mode: &ResolutionSearchMode,
+    ) -> Option<u8> {
+        let mut area_difference = None;
+        for h3_res in H3_MIN_RESOLUTION..=H3_MAX_RESOLUTION {
+            let area_h3_index = center_cell_area(center, h3_res).unwrap();
+            match search_mode {
+                ResolutionSearchMode::SmallerThanPixel => {
+                    if area_h3_index <= area_pixel {
+                        return Some(h3_res);
+                    }
+                }
+                ResolutionSearchMode::MinIndexesPerPixel(factor) => {
+                    if area_h3_index <= area_pixel / factor {
+                        return Some(h3_res);
+                    }
+                }
+                ResolutionSearchMode::MaxPixelsPerIndex(factor) => {
+                    if area_h3_index <= area_pixel * factor {
+                        return Some(h3_res);
+                    }
+                }
+                ResolutionSearchMode::MinDiff => {
+                    let new_area_difference = (area_h3_index - area_pixel).abs();
+                    match area_difference {
+                        Some(old_area_difference) if old_area_difference < new_area_difference => {
+                            return Some(h3_res - 1)
+                        }
+                        _ => area_difference = Some(new_area_difference),
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_resolution_for_pixel_area_equals_exhaustive_search() {
+        let pentagon = H3Cell::new(0x8009fffffffffff);
+        assert!(pentagon.is_pentagon());
+        let centers = [
+            Coordinate::from((8.5, 49.3)),
+            Coordinate::from((-70.2, -40.1)),
+            Coordinate::from((120.0, 65.5)),
+            pentagon.to_coordinate().unwrap(),
+        ];
+        for center in centers {
+            let mut area_pixel = 1.0e-3;
+            while area_pixel < 1.0e15 {
+                for search_mode in [
+                    ResolutionSearchMode::MinDiff,
+                    ResolutionSearchMode::SmallerThanPixel,
+                    ResolutionSearchMode::MinIndexesPerPixel(7.0),
+                    ResolutionSearchMode::MaxPixelsPerIndex(3.5),
+                ] {
+                    assert_eq!(
+                        resolution_for_pixel_area(center, area_pixel, &search_mode).unwrap(),
+                        resolution_for_pixel_area_exhaustive(center, area_pixel, &search_mode)
+                    );
+                }
+                area_pixel *= 1.3;
+            }
+        }
+    }
+
+    #[test]
+    fn test_h3_resolution_areas() {
+        let gt = Transform::from_gdal(&[8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135]);
+        let shape = [2000_usize, 2000_usize];
+        let areas = h3_resolution_areas(&shape, &gt, &AxisOrder::YX).unwrap();
+        assert_eq!(areas.len(), H3_MAX_RESOLUTION as usize + 1);
+        for (i, (h3_res, area, ratio)) in areas.iter().enumerate() {
+            assert_eq!(*h3_res as usize, i);
+            assert!(*area > 0.0);
+            if i > 0 {
+                assert!(*area < areas[i - 1].1);
+                assert!((areas[i - 1].2 / ratio - areas[i - 1].1 / area).abs() < 1e-9);
+            }
+        }
+
+        // the first resolution smaller than a pixel
+        let smaller_than_pixel = areas.iter().find(|(_, _, ratio)| *ratio <= 1.0).unwrap().0;
+        assert_eq!(
+            smaller_than_pixel,
+            r_tiff_resolution(ResolutionSearchMode::SmallerThanPixel)
+        );
+
+        assert!(matches!(
+            h3_resolution_areas(&[0, 10], &gt, &AxisOrder::YX),
+            Err(Error::EmptyArray)
+        ));
+    }
+
+    fn estimated_num_cells(rect: &Rect<f64>, h3_res: u8) -> f64 {
+        area_squaremeters_rect(rect) / center_cell_area(rect.center(), h3_res).unwrap()
+    }
+
+    #[test]
+    fn test_h3_resolution_for_rect() {
+        let rect = Rect::new(
+            Coordinate::from((8.11, 47.4)),
+            Coordinate::from((10.5, 49.4)),
+        );
+        let mut previous_h3_res = 0;
+        for max_indexes in [10, 1000, 100_000, 10_000_000] {
+            let h3_res =
+                h3_resolution_for_rect(&rect, max_indexes, ResolutionSearchMode::SmallerThanPixel)
+                    .unwrap();
+            // the finest resolution within the budget
+            assert!(estimated_num_cells(&rect, h3_res) <= max_indexes as f64);
+            assert!(estimated_num_cells(&rect, h3_res + 1) > max_indexes as f64);
+
+            assert!(h3_res >= previous_h3_res);
+            previous_h3_res = h3_res;
+
+            for search_mode in [
+                ResolutionSearchMode::MinDiff,
+                ResolutionSearchMode::MaxPixelsPerIndex(7.0),
+            ] {
+                let coarser_h3_res =
+                    h3_resolution_for_rect(&rect, max_indexes, search_mode).unwrap();
+                assert!(coarser_h3_res <= h3_res);
+            }
+        }
+    }
+
+    #[test]
+    fn test_h3_resolution_for_rect_invalid() {
+        let line = Rect::new(Coordinate::from((8.0, 47.0)), Coordinate::from((8.0, 49.0)));
+        assert!(matches!(
+            h3_resolution_for_rect(&line, 1000, ResolutionSearchMode::SmallerThanPixel),
+            Err(Error::EmptyRect)
+        ));
+
+        let world = Rect::new(
+            Coordinate::from((-180.0, -85.0)),
+            Coordinate::from((180.0, 85.0)),
+        );
+        assert!(matches!(
+            h3_resolution_for_rect(&world, 10, ResolutionSearchMode::SmallerThanPixel),
+            Err(Error::NoResolutionWithinBudget(10))
+        ));
+        assert!(matches!(
+            h3_resolution_for_rect(&world, 0, ResolutionSearchMode::SmallerThanPixel),
+            Err(Error::NoResolutionWithinBudget(0))
+        ));
+        assert!(
+            h3_resolution_for_rect(&world, 1000, ResolutionSearchMode::SmallerThanPixel).is_ok()
+        );
+    }
 }
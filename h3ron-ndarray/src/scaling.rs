@@ -0,0 +1,43 @@
+/// Scaling of raw array values to physical values.
+///
+/// NetCDF and GeoTIFF files often store physical values as scaled integers. The
+/// physical value is calculated as `raw * scale_factor + add_offset`. Unset parameters
+/// default to the identity - a `scale_factor` of `1.0` and an `add_offset` of `0.0`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ValueScaling {
+    pub scale_factor: Option<f64>,
+    pub add_offset: Option<f64>,
+}
+
+impl ValueScaling {
+    pub const fn new(scale_factor: Option<f64>, add_offset: Option<f64>) -> Self {
+        Self {
+            scale_factor,
+            add_offset,
+        }
+    }
+
+    /// true when neither `scale_factor` nor `add_offset` is set.
+    pub const fn is_identity(&self) -> bool {
+        self.scale_factor.is_none() && self.add_offset.is_none()
+    }
+
+    /// convert a raw value to its physical value
+    #[inline]
+    pub fn apply(&self, raw: f64) -> f64 {
+        raw * self.scale_factor.unwrap_or(1.0) + self.add_offset.unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ValueScaling;
+
+    #[test]
+    fn apply_partial_scaling() {
+        assert_relative_eq!(ValueScaling::new(None, None).apply(3.0), 3.0);
+        assert_relative_eq!(ValueScaling::new(Some(0.5), None).apply(3.0), 1.5);
+        assert_relative_eq!(ValueScaling::new(None, Some(2.0)).apply(3.0), 5.0);
+        assert_relative_eq!(ValueScaling::new(Some(0.5), Some(2.0)).apply(3.0), 3.5);
+    }
+}
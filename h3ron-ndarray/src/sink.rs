@@ -0,0 +1,139 @@
+//! Incremental conversion of arrays which are too large to be converted at once.
+use std::hash::Hash;
+
+use ndarray::ArrayView2;
+use rayon::prelude::*;
+
+use h3ron::collections::{CompactedCellVec, HashMap};
+
+use crate::{AxisOrder, Error, H3Converter, Transform};
+
+/// Converts an array window by window.
+///
+/// The windows are read successively - for example from a GDAL dataset - and passed to
+/// [`H3ConverterSink::add_chunk`] together with their offset within the complete array. All
+/// windows share the `transform` of the complete array. The cells are accumulated per value and
+/// compacted once across all windows in [`H3ConverterSink::finish`], so the result is the same
+/// as converting the complete array using [`H3Converter::to_h3`].
+///
+/// Windows may overlap, cells found in multiple windows are deduplicated.
+pub struct H3ConverterSink<T> {
+    transform: Transform,
+    axis_order: AxisOrder,
+    nodata_value: Option<T>,
+    h3_resolution: u8,
+    cells: HashMap<T, CompactedCellVec>,
+}
+
+impl<T> H3ConverterSink<T>
+where
+    T: Sized + PartialEq + Sync + Send + Eq + Hash + Clone,
+{
+    pub fn new(
+        transform: Transform,
+        axis_order: AxisOrder,
+        nodata_value: Option<T>,
+        h3_resolution: u8,
+    ) -> Self {
+        Self {
+            transform,
+            axis_order,
+            nodata_value,
+            h3_resolution,
+            cells: HashMap::default(),
+        }
+    }
+
+    /// Convert the window `chunk` of the array.
+    ///
+    /// `offset` is the position of the first pixel of the window within the complete
+    /// array as `(x_offset, y_offset)` - like the column and row offsets of GDAL windows.
+    pub fn add_chunk(
+        &mut self,
+        chunk: &ArrayView2<T>,
+        offset: (usize, usize),
+    ) -> Result<(), Error> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        let transform = self.transform.window(offset.0, offset.1);
+        let chunk_view = chunk.view();
+        let converter =
+            H3Converter::new(&chunk_view, &self.nodata_value, &transform, self.axis_order);
+        for (value, mut chunk_cells) in converter.to_h3(self.h3_resolution, false)? {
+            self.cells
+                .entry(value.clone())
+                .or_insert_with(CompactedCellVec::new)
+                .append(&mut chunk_cells, false)?;
+        }
+        Ok(())
+    }
+
+    /// Finish the conversion.
+    ///
+    /// The cells of all windows get deduplicated and - when `compact` is set - compacted.
+    pub fn finish(self, compact: bool) -> Result<HashMap<T, CompactedCellVec>, Error> {
+        self.cells
+            .into_par_iter()
+            .map(|(value, mut cells)| -> Result<_, Error> {
+                if compact {
+                    cells.compact()?;
+                } else {
+                    cells.dedup()?;
+                }
+                cells.shrink_to_fit();
+                Ok((value, cells))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use h3ron::H3Cell;
+
+    use crate::{AxisOrder, H3Converter, H3ConverterSink, Transform};
+
+    #[test]
+    fn overlapping_chunks_equal_complete_array() {
+        let arr = ndarray::Array2::from_shape_fn((150, 200), |(y, x)| match (y / 40, x / 70) {
+            (0, _) => 0_u8,
+            (1, 1) => 2,
+            (_, 2) => 3,
+            _ => 1,
+        });
+        // coefficients which are exactly representable to avoid rounding differences
+        let transform = Transform::from_gdal(&[10.0, 1.0 / 1024.0, 0.0, 50.0, 0.0, -1.0 / 1024.0]);
+        let nodata = Some(0_u8);
+        let h3_resolution = 9;
+
+        let view = arr.view();
+        let expected = H3Converter::new(&view, &nodata, &transform, AxisOrder::YX)
+            .to_h3(h3_resolution, true)
+            .unwrap();
+
+        // windows of 64x64 pixels overlapping by 8 pixels
+        let mut sink =
+            H3ConverterSink::new(transform.clone(), AxisOrder::YX, nodata, h3_resolution);
+        for y_offset in (0..arr.shape()[0]).step_by(56) {
+            for x_offset in (0..arr.shape()[1]).step_by(56) {
+                let chunk = arr.slice(s![
+                    y_offset..(y_offset + 64).min(150),
+                    x_offset..(x_offset + 64).min(200)
+                ]);
+                sink.add_chunk(&chunk, (x_offset, y_offset)).unwrap();
+            }
+        }
+        let result = sink.finish(true).unwrap();
+
+        assert_eq!(result.len(), expected.len());
+        for (value, compacted_vec) in expected.iter() {
+            let mut expected_cells: Vec<H3Cell> = compacted_vec.iter_compacted_cells().collect();
+            let mut cells: Vec<H3Cell> =
+                result.get(*value).unwrap().iter_compacted_cells().collect();
+            expected_cells.sort_unstable();
+            cells.sort_unstable();
+            assert_eq!(cells, expected_cells);
+        }
+    }
+}
@@ -1,8 +1,15 @@
 use geo_types::{Coordinate, LineString, Rect};
 
+use h3ron::{H3Cell, ToCoordinate, ToPolygon};
+
+use crate::error::Error;
+
 /// earth radius at the equator in meters
 const EARTH_RADIUS_EQUATOR: f64 = 6_378_137_f64;
 
+/// authalic earth radius in meters, as used by h3
+const EARTH_RADIUS_AUTHALIC: f64 = 6_371_007.180_918_475_f64;
+
 /// Calculate the approximate area of the given linestring ring (wgs84 coordinates) in square meters
 ///
 /// Roughly taken from [stackoverflow](https://gis.stackexchange.com/questions/711/how-can-i-measure-area-from-geographic-coordinates).
@@ -22,6 +29,51 @@ pub fn area_squaremeters_linearring(ring: &LineString<f64>) -> f64 {
         / 2.0
 }
 
+/// Calculate the area of `cell` in square meters using the boundary ring of the cell.
+///
+/// In contrast to [`area_squaremeters_linearring`] the area is not approximated, the cell
+/// gets split into a fan of spherical triangles between its center and the vertices of its
+/// boundary. This follows `cellAreaRads2` of h3, which is used by `H3Cell::area_m2`, so the area
+/// of pentagons and cells crossing the antimeridian is correct as well.
+pub fn cell_area_m2(cell: &H3Cell) -> Result<f64, Error> {
+    let center = cell.to_coordinate()?;
+    let area: f64 = cell
+        .to_polygon()?
+        .exterior()
+        .0
+        .windows(2)
+        .map(|vertices| spherical_triangle_area(&vertices[0], &vertices[1], &center))
+        .sum();
+    Ok(area * EARTH_RADIUS_AUTHALIC.powi(2))
+}
+
+/// great circle distance in radians between two wgs84 coordinates using the haversine formula
+fn great_circle_distance_rads(a: &Coordinate<f64>, b: &Coordinate<f64>) -> f64 {
+    let sin_lat = ((b.y - a.y).to_radians() / 2.0).sin();
+    let sin_lng = ((b.x - a.x).to_radians() / 2.0).sin();
+    let h = sin_lat.mul_add(
+        sin_lat,
+        a.y.to_radians().cos() * b.y.to_radians().cos() * sin_lng * sin_lng,
+    );
+    2.0 * h.sqrt().atan2((1.0 - h).sqrt())
+}
+
+/// area of the spherical triangle `a`, `b`, `c` on the unit sphere using L'Huilier's theorem
+fn spherical_triangle_area(a: &Coordinate<f64>, b: &Coordinate<f64>, c: &Coordinate<f64>) -> f64 {
+    let (ab, bc, ca) = (
+        great_circle_distance_rads(a, b),
+        great_circle_distance_rads(b, c),
+        great_circle_distance_rads(c, a),
+    );
+    let s = (ab + bc + ca) / 2.0;
+    4.0 * ((s / 2.0).tan()
+        * ((s - ab) / 2.0).tan()
+        * ((s - bc) / 2.0).tan()
+        * ((s - ca) / 2.0).tan())
+    .sqrt()
+    .atan()
+}
+
 /// calculate the approximate area of the given rect (wgs84 coordinates) in square meters
 pub fn area_squaremeters_rect(bounds: &Rect<f64>) -> f64 {
     let ring = LineString::from(vec![
@@ -48,3 +100,51 @@ pub fn area_squaremeters_rect(bounds: &Rect<f64>) -> f64 {
     ]);
     area_squaremeters_linearring(&ring)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use geo_types::Coordinate;
+
+    use h3ron::{res0_cells, H3Cell};
+
+    use crate::sphere::{cell_area_m2, EARTH_RADIUS_AUTHALIC};
+
+    #[test]
+    fn cell_area_equals_h3_cell_area() {
+        let mut cells = vec![];
+        for resolution in 0..=12 {
+            for coordinate in [(8.5, 49.4), (-179.99, 12.3), (120.0, -89.9), (-65.2, 0.1)] {
+                cells.push(
+                    H3Cell::from_coordinate(Coordinate::from(coordinate), resolution).unwrap(),
+                );
+            }
+            cells.extend(
+                res0_cells()
+                    .iter()
+                    .filter(|cell| cell.is_pentagon())
+                    .map(|pentagon| pentagon.center_child(resolution).unwrap()),
+            );
+        }
+        assert!(cells.iter().filter(|cell| cell.is_pentagon()).count() >= 12 * 13);
+
+        for cell in cells {
+            let area = cell_area_m2(&cell).unwrap();
+            assert_relative_eq!(area, cell.area_m2().unwrap(), max_relative = 1e-6);
+        }
+    }
+
+    #[test]
+    fn res0_cell_areas_cover_the_sphere() {
+        let total_area: f64 = res0_cells()
+            .iter()
+            .map(|cell| cell_area_m2(&cell).unwrap())
+            .sum();
+        assert_relative_eq!(
+            total_area,
+            4.0 * PI * EARTH_RADIUS_AUTHALIC.powi(2),
+            max_relative = 1e-6
+        );
+    }
+}
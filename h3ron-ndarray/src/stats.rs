@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use geo_types::Rect;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use h3ron::H3Cell;
+
+use crate::cell_sink::CellSink;
+use crate::error::Error;
+
+/// Statistics of a conversion using
+/// [`H3Converter::to_h3_with_stats`](crate::H3Converter::to_h3_with_stats).
+///
+/// In contrast to the [`ConversionEstimate`](crate::ConversionEstimate) all counts are exact.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConversionStats {
+    /// the h3 resolution of the conversion
+    pub h3_resolution: u8,
+
+    /// number of boxes containing data the array was split into. Boxes larger than the
+    /// window size of the conversion are counted once per window.
+    pub num_boxes: usize,
+
+    /// total number of pixels of the array
+    pub num_pixels: usize,
+
+    /// number of pixels located within the boxes containing data. All other pixels are
+    /// skipped without being visited.
+    pub visited_pixels: usize,
+
+    /// number of the visited pixels not containing data
+    pub nodata_pixels: usize,
+
+    /// number of cells generated by all boxes before the cells of the boxes were merged.
+    /// Cells located in multiple boxes are counted once per box.
+    pub generated_cells: usize,
+
+    /// number of distinct cells of the result before compacting, summed over all values
+    pub uncompacted_cells: usize,
+
+    /// number of cells of the result, summed over all values. Equals `uncompacted_cells`
+    /// when compacting is disabled.
+    pub output_cells: usize,
+
+    /// time spent searching the boxes containing data
+    pub box_detection_time: Duration,
+
+    /// time spent generating the cells of the boxes
+    pub conversion_time: Duration,
+
+    /// time spent merging the cells of all boxes and compacting them
+    pub compaction_time: Duration,
+}
+
+impl ConversionStats {
+    pub fn new(h3_resolution: u8) -> Self {
+        Self {
+            h3_resolution,
+            ..Default::default()
+        }
+    }
+
+    /// fraction of the visited pixels containing data
+    pub fn data_fraction(&self) -> f64 {
+        if self.visited_pixels == 0 {
+            0.0
+        } else {
+            (self.visited_pixels - self.nodata_pixels) as f64 / self.visited_pixels as f64
+        }
+    }
+}
+
+/// Counts the cells accepted by the wrapped sink.
+pub(crate) struct CountingSink<S> {
+    inner: S,
+    num_accepted: usize,
+}
+
+impl<S> CountingSink<S> {
+    pub(crate) const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            num_accepted: 0,
+        }
+    }
+}
+
+impl<'a, T, S> CellSink<'a, T> for CountingSink<S>
+where
+    T: 'a,
+    S: CellSink<'a, T>,
+{
+    type Output = (S::Output, usize);
+
+    fn fork(&self) -> Self {
+        Self::new(self.inner.fork())
+    }
+
+    fn accept(&mut self, cell: H3Cell, value: &'a T) -> Result<(), Error> {
+        self.num_accepted += 1;
+        self.inner.accept(cell, value)
+    }
+
+    fn finish_box(&mut self, box_rect: &Rect<usize>) -> Result<(), Error> {
+        self.inner.finish_box(box_rect)
+    }
+
+    fn merge(&mut self, other: Self) -> Result<(), Error> {
+        self.num_accepted += other.num_accepted;
+        self.inner.merge(other.inner)
+    }
+
+    fn finalize(self) -> Self::Output {
+        (self.inner.finalize(), self.num_accepted)
+    }
+}
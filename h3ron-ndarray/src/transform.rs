@@ -1,7 +1,9 @@
-use std::ops::Mul;
+use std::ops::{Mul, Range};
 
 use geo_types::{Coordinate, Rect};
+use num_traits::Float;
 
+use crate::array::AxisOrder;
 use crate::error::Error;
 
 /// Affine Geotransform
@@ -63,6 +65,36 @@ impl Transform {
         )
     }
 
+    /// the coefficients in the ordering used by gdal
+    pub const fn to_gdal(&self) -> [f64; 6] {
+        [self.c, self.a, self.b, self.f, self.d, self.e]
+    }
+
+    /// the coefficients in the ordering used by rasterio
+    pub const fn to_rasterio(&self) -> [f64; 6] {
+        [self.a, self.b, self.c, self.d, self.e, self.f]
+    }
+
+    /// Fails with [`Error::ZeroPixelSize`] naming the coefficient when the pixels have a
+    /// width or a height of zero.
+    ///
+    /// Fails with [`Error::SwappedCoefficientOrdering`] when `a` or `e` is zero while the
+    /// pixel axes are not orthogonal. Reading the coefficients of a north-up raster in the
+    /// gdal ordering as rasterio coefficients - or the other way around - moves one of the
+    /// zero rotation terms into `a` or `e` and an origin term into the other axis, which
+    /// results in such a transform. Rotated rasters keep orthogonal axes and pass.
+    pub fn validate_pixel_size(&self) -> Result<(), Error> {
+        if self.a.hypot(self.d) == 0.0 {
+            Err(Error::ZeroPixelSize('a'))
+        } else if self.b.hypot(self.e) == 0.0 {
+            Err(Error::ZeroPixelSize('e'))
+        } else if (self.a == 0.0 || self.e == 0.0) && self.a * self.b + self.d * self.e != 0.0 {
+            Err(Error::SwappedCoefficientOrdering)
+        } else {
+            Ok(())
+        }
+    }
+
     /// The determinant of the transform matrix
     pub fn determinant(&self) -> f64 {
         self.a * self.e - self.b * self.d
@@ -99,10 +131,217 @@ impl Transform {
     /// Apply the transformation to a coordinate
     pub fn transform_coordinate(&self, coordinate: &Coordinate<f64>) -> Coordinate<f64> {
         Coordinate {
-            x: coordinate.x.mul_add(self.a, coordinate.y * self.b) + self.c,
-            y: coordinate.x.mul_add(self.d, coordinate.y * self.e) + self.f,
+            x: self.a * coordinate.x + self.b * coordinate.y + self.c,
+            y: self.d * coordinate.x + self.e * coordinate.y + self.f,
+        }
+    }
+
+    /// The transform of a window of the array starting at the pixel `x_offset`, `y_offset`.
+    ///
+    /// The coordinates of the pixels of the window only differ from the coordinates of the
+    /// same pixels in the complete array by floating point rounding.
+    pub fn window(&self, x_offset: usize, y_offset: usize) -> Self {
+        let origin = self.transform_coordinate(&Coordinate {
+            x: x_offset as f64,
+            y: y_offset as f64,
+        });
+        Self::new(self.a, self.b, origin.x, self.d, self.e, origin.y)
+    }
+
+    /// Apply the transformation to coordinates given as separate slices of their
+    /// x and y components.
+    ///
+    /// The results are bit-identical to [`Transform::transform_coordinate`].
+    ///
+    /// # Panics
+    ///
+    /// Panics when the slices do not all have the same length.
+    pub fn apply_batch(&self, xs: &[f64], ys: &[f64], out_x: &mut [f64], out_y: &mut [f64]) {
+        assert_eq!(xs.len(), ys.len());
+        assert_eq!(xs.len(), out_x.len());
+        assert_eq!(xs.len(), out_y.len());
+
+        // separate loops over plain slices allow the compiler to vectorize. `mul_add` would
+        // prevent this on targets without native FMA support.
+        for ((x_out, x), y) in out_x.iter_mut().zip(xs).zip(ys) {
+            *x_out = self.a * x + self.b * y + self.c;
+        }
+        for ((y_out, x), y) in out_y.iter_mut().zip(xs).zip(ys) {
+            *y_out = self.d * x + self.e * y + self.f;
+        }
+    }
+
+    /// Apply the inverse of the transformation to coordinates given as separate slices of
+    /// their x and y components.
+    ///
+    /// The results are bit-identical to transforming each coordinate using the transform
+    /// returned by [`Transform::invert`].
+    ///
+    /// # Panics
+    ///
+    /// Panics when the slices do not all have the same length.
+    pub fn apply_inverse_batch(
+        &self,
+        xs: &[f64],
+        ys: &[f64],
+        out_x: &mut [f64],
+        out_y: &mut [f64],
+    ) -> Result<(), Error> {
+        self.invert()?.apply_batch(xs, ys, out_x, out_y);
+        Ok(())
+    }
+
+    /// The coordinates of the centers of the pixels `col_range` within the row `row`
+    /// of an array with the given `axis_order`.
+    ///
+    /// Rows are the first axis of the array, so for [`AxisOrder::XY`] the row is
+    /// the x pixel coordinate, for [`AxisOrder::YX`] it is the y pixel coordinate.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the length of `col_range` differs from the lengths of the output slices.
+    pub fn row_coordinates(
+        &self,
+        row: usize,
+        col_range: Range<usize>,
+        axis_order: AxisOrder,
+        out_x: &mut [f64],
+        out_y: &mut [f64],
+    ) {
+        assert_eq!(col_range.len(), out_x.len());
+        assert_eq!(col_range.len(), out_y.len());
+
+        let row_center = row as f64 + 0.5;
+        let col_centers = col_range.map(|col| col as f64 + 0.5);
+        match axis_order {
+            AxisOrder::XY => {
+                for ((x_out, y_out), y) in out_x.iter_mut().zip(out_y.iter_mut()).zip(col_centers) {
+                    *x_out = self.a * row_center + self.b * y + self.c;
+                    *y_out = self.d * row_center + self.e * y + self.f;
+                }
+            }
+            AxisOrder::YX => {
+                for ((x_out, y_out), x) in out_x.iter_mut().zip(out_y.iter_mut()).zip(col_centers) {
+                    *x_out = self.a * x + self.b * row_center + self.c;
+                    *y_out = self.d * x + self.e * row_center + self.f;
+                }
+            }
+        }
+    }
+}
+
+/// Floating point type used for the coordinates within [`H3Converter`](crate::H3Converter).
+///
+/// Using `f32` halves the size of the intermediate coordinate buffers. To keep the precision
+/// of `f32` sufficient, coordinates are made relative to the box of the array they are
+/// located in before being narrowed to `f32`.
+pub trait CoordFloat: Float + Send + Sync {
+    /// `true` when coordinates are made relative to the box of the array before being
+    /// converted to this type.
+    const RELATIVE_COORDINATES: bool;
+
+    fn from_f64(value: f64) -> Self;
+    fn into_f64(self) -> f64;
+}
+
+impl CoordFloat for f64 {
+    const RELATIVE_COORDINATES: bool = false;
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    #[inline]
+    fn into_f64(self) -> f64 {
+        self
+    }
+}
+
+impl CoordFloat for f32 {
+    const RELATIVE_COORDINATES: bool = true;
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    #[inline]
+    fn into_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+/// A [`Transform`] with its coefficients converted to `F`, applied to coordinates relative
+/// to an `origin`.
+///
+/// For `f64` without an origin, the results are bit-identical to the results of [`Transform`].
+pub(crate) struct LocalTransform<F> {
+    a: F,
+    b: F,
+    c: F,
+    d: F,
+    e: F,
+    f: F,
+    origin: Option<Coordinate<f64>>,
+}
+
+impl<F> LocalTransform<F>
+where
+    F: CoordFloat,
+{
+    pub(crate) fn new(transform: &Transform, origin: Option<Coordinate<f64>>) -> Self {
+        // the translation is computed relative to the origin using f64
+        let (c, f) = match origin {
+            Some(origin) => (
+                transform.a * origin.x + transform.b * origin.y + transform.c,
+                transform.d * origin.x + transform.e * origin.y + transform.f,
+            ),
+            None => (transform.c, transform.f),
+        };
+        Self {
+            a: F::from_f64(transform.a),
+            b: F::from_f64(transform.b),
+            c: F::from_f64(c),
+            d: F::from_f64(transform.d),
+            e: F::from_f64(transform.e),
+            f: F::from_f64(f),
+            origin,
         }
     }
+
+    /// convert `coordinate` to `F`, relative to the origin
+    #[inline]
+    pub(crate) fn local(&self, coordinate: &Coordinate<f64>) -> (F, F) {
+        match self.origin {
+            Some(origin) => (
+                F::from_f64(coordinate.x - origin.x),
+                F::from_f64(coordinate.y - origin.y),
+            ),
+            None => (F::from_f64(coordinate.x), F::from_f64(coordinate.y)),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn apply(&self, x: F, y: F) -> (F, F) {
+        (
+            self.a * x + self.b * y + self.c,
+            self.d * x + self.e * y + self.f,
+        )
+    }
+
+    /// transform a rect of local coordinates
+    pub(crate) fn apply_rect(&self, rect: &Rect<f64>) -> Rect<f64> {
+        let corner = |coordinate: Coordinate<f64>| {
+            let (x, y) = self.local(&coordinate);
+            let (x, y) = self.apply(x, y);
+            Coordinate {
+                x: x.into_f64(),
+                y: y.into_f64(),
+            }
+        };
+        Rect::new(corner(rect.min()), corner(rect.max()))
+    }
 }
 
 /// apply the transformation to a coordinate
@@ -174,7 +413,180 @@ mod tests {
 
     use geo_types::Coordinate;
 
-    use crate::transform::Transform;
+    use crate::array::AxisOrder;
+    use crate::error::Error;
+    use crate::transform::{LocalTransform, Transform};
+
+    /// deterministic pseudo-random values in `[-1.0, 1.0)`
+    struct Values(u64);
+
+    impl Values {
+        fn next(&mut self) -> f64 {
+            // splitmix64
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^= z >> 31;
+            (z >> 11) as f64 / (1_u64 << 52) as f64 - 1.0
+        }
+
+        fn transform(&mut self) -> Transform {
+            Transform::new(
+                self.next() * 0.01,
+                self.next() * 0.001,
+                self.next() * 180.0,
+                self.next() * 0.001,
+                self.next() * 0.01,
+                self.next() * 90.0,
+            )
+        }
+    }
+
+    #[test]
+    fn test_rasterio_and_gdal_ordering() {
+        // Affine(0.5, 0.0, 10.0, 0.0, -0.25, 50.0) in rasterio
+        let rasterio = [0.5, 0.0, 10.0, 0.0, -0.25, 50.0];
+        let gdal = [10.0, 0.5, 0.0, 50.0, 0.0, -0.25];
+
+        let transform = Transform::from_rasterio(&rasterio);
+        assert_eq!(transform, Transform::from_gdal(&gdal));
+        assert_eq!(transform.to_rasterio(), rasterio);
+        assert_eq!(transform.to_gdal(), gdal);
+        assert!(transform.validate_pixel_size().is_ok());
+
+        let upper_left = &transform * Coordinate { x: 0.0, y: 0.0 };
+        assert_eq!(upper_left, Coordinate { x: 10.0, y: 50.0 });
+        let lower_right = &transform * Coordinate { x: 2.0, y: 4.0 };
+        assert_eq!(lower_right, Coordinate { x: 11.0, y: 49.0 });
+
+        // the gdal coefficients interpreted in the rasterio ordering and the other way around
+        assert!(matches!(
+            Transform::from_rasterio(&gdal).validate_pixel_size(),
+            Err(Error::SwappedCoefficientOrdering)
+        ));
+        assert!(matches!(
+            Transform::from_gdal(&rasterio).validate_pixel_size(),
+            Err(Error::SwappedCoefficientOrdering)
+        ));
+
+        // rotated by 90 degrees
+        assert!(
+            Transform::from_rasterio(&[0.0, 0.5, 10.0, -0.25, 0.0, 50.0])
+                .validate_pixel_size()
+                .is_ok()
+        );
+        assert!(matches!(
+            Transform::from_rasterio(&[0.0, 0.0, 10.0, 0.0, -0.25, 50.0]).validate_pixel_size(),
+            Err(Error::ZeroPixelSize('a'))
+        ));
+    }
+
+    #[test]
+    fn test_apply_batch_matches_scalar() {
+        let mut values = Values(7);
+        for _ in 0..100 {
+            let transform = values.transform();
+            let inverse = transform.invert().unwrap();
+            let xs: Vec<_> = (0..257).map(|_| values.next() * 1.0e4).collect();
+            let ys: Vec<_> = (0..257).map(|_| values.next() * 1.0e4).collect();
+            let mut out_x = vec![0.0; xs.len()];
+            let mut out_y = vec![0.0; xs.len()];
+            let mut inv_x = vec![0.0; xs.len()];
+            let mut inv_y = vec![0.0; xs.len()];
+            transform.apply_batch(&xs, &ys, &mut out_x, &mut out_y);
+            transform
+                .apply_inverse_batch(&xs, &ys, &mut inv_x, &mut inv_y)
+                .unwrap();
+
+            for i in 0..xs.len() {
+                let coordinate = Coordinate { x: xs[i], y: ys[i] };
+                let transformed = &transform * coordinate;
+                assert_eq!(out_x[i].to_bits(), transformed.x.to_bits());
+                assert_eq!(out_y[i].to_bits(), transformed.y.to_bits());
+                let inverted = &inverse * coordinate;
+                assert_eq!(inv_x[i].to_bits(), inverted.x.to_bits());
+                assert_eq!(inv_y[i].to_bits(), inverted.y.to_bits());
+            }
+        }
+    }
+
+    #[test]
+    fn test_local_transform_f64_matches_scalar() {
+        let mut values = Values(23);
+        for _ in 0..100 {
+            let transform = values.transform();
+            let local = LocalTransform::<f64>::new(&transform, None);
+            let coordinate = Coordinate {
+                x: values.next() * 1.0e4,
+                y: values.next() * 1.0e4,
+            };
+            let (x, y) = local.local(&coordinate);
+            let (x, y) = local.apply(x, y);
+            let transformed = &transform * coordinate;
+            assert_eq!(x.to_bits(), transformed.x.to_bits());
+            assert_eq!(y.to_bits(), transformed.y.to_bits());
+        }
+    }
+
+    #[test]
+    fn test_local_transform_f32_precision() {
+        // pixel coordinates of the r.tiff transform
+        let transform =
+            Transform::from_gdal(&[8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135])
+                .invert()
+                .unwrap();
+        let origin = Coordinate { x: 9.3, y: 48.2 };
+        let local = LocalTransform::<f32>::new(&transform, Some(origin));
+        let mut values = Values(29);
+        for _ in 0..1000 {
+            let coordinate = Coordinate {
+                x: origin.x + values.next() * 0.1,
+                y: origin.y + values.next() * 0.1,
+            };
+            let (x, y) = local.local(&coordinate);
+            let (x, y) = local.apply(x, y);
+            let transformed = &transform * coordinate;
+            assert!((x as f64 - transformed.x).abs() < 1.0e-3);
+            assert!((y as f64 - transformed.y).abs() < 1.0e-3);
+        }
+    }
+
+    #[test]
+    fn test_row_coordinates_matches_scalar() {
+        let mut values = Values(11);
+        for _ in 0..50 {
+            let transform = values.transform();
+            let row = (values.next().abs() * 5000.0) as usize;
+            let col_start = (values.next().abs() * 5000.0) as usize;
+            let col_range = col_start..(col_start + 300);
+            let mut out_x = vec![0.0; col_range.len()];
+            let mut out_y = vec![0.0; col_range.len()];
+
+            for axis_order in [AxisOrder::XY, AxisOrder::YX] {
+                transform.row_coordinates(
+                    row,
+                    col_range.clone(),
+                    axis_order,
+                    &mut out_x,
+                    &mut out_y,
+                );
+                for (i, col) in col_range.clone().enumerate() {
+                    let (x, y) = match axis_order {
+                        AxisOrder::XY => (row, col),
+                        AxisOrder::YX => (col, row),
+                    };
+                    let coordinate = &transform
+                        * Coordinate {
+                            x: x as f64 + 0.5,
+                            y: y as f64 + 0.5,
+                        };
+                    assert_eq!(out_x[i].to_bits(), coordinate.x.to_bits());
+                    assert_eq!(out_y[i].to_bits(), coordinate.y.to_bits());
+                }
+            }
+        }
+    }
 
     fn r_tiff_test_helper(gt: &Transform) {
         // upper left pixel
@@ -190,6 +602,15 @@ mod tests {
         assert_relative_eq!(px_ul_back.y, 0.0);
     }
 
+    #[test]
+    fn test_to_gdal_roundtrip() {
+        let gdal_transform = [8.11377, 0.001196505, 0.0, 49.40792, 0.0, -0.001215135];
+        assert_eq!(
+            Transform::from_gdal(&gdal_transform).to_gdal(),
+            gdal_transform
+        );
+    }
+
     #[test]
     fn test_r_tiff_from_gdal() {
         /*
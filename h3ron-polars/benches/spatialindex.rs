@@ -4,8 +4,8 @@ use polars_core::prelude::UInt64Chunked;
 
 use h3ron::H3Cell;
 use h3ron_polars::spatial_index::{
-    BuildKDTreeIndex, BuildPackedHilbertRTreeIndex, BuildRTreeIndex, SIKind, SpatialIndex,
-    SpatialIndexGeomOp,
+    BuildH3HierarchyIndex, BuildKDTreeIndex, BuildPackedHilbertRTreeIndex, BuildRTreeIndex, SIKind,
+    SpatialIndex, SpatialIndexGeomOp,
 };
 use h3ron_polars::{AsH3CellChunked, FromIndexIterator, IndexChunked};
 
@@ -69,10 +69,15 @@ fn bench_packed_hilbert_rtree(c: &mut Criterion) {
     )
 }
 
+fn bench_h3_hierarchy(c: &mut Criterion) {
+    bench_spatialindex(c, |ic| ic.h3_hierarchy_index(5).unwrap(), "h3_hierarchy")
+}
+
 criterion_group!(
     benches,
     bench_kdtree,
     bench_rtree,
-    bench_packed_hilbert_rtree
+    bench_packed_hilbert_rtree,
+    bench_h3_hierarchy
 );
 criterion_main!(benches);
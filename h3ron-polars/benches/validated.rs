@@ -0,0 +1,53 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use geo_types::Coordinate;
+use polars_core::prelude::UInt64Chunked;
+
+use h3ron::H3Cell;
+use h3ron_polars::algorithm::{H3ChangeResolution, H3GridDisk};
+use h3ron_polars::spatial_index::BuildPackedHilbertRTreeIndex;
+use h3ron_polars::{AsH3CellChunked, FromIndexIterator, ValidIndexes};
+
+/// number of algorithms applied to the same array in each iteration
+const NUM_STEPS: usize = 10;
+
+fn build_input() -> UInt64Chunked {
+    UInt64Chunked::from_index_iter(
+        H3Cell::from_coordinate(Coordinate::from((12.3, 45.4)), 8)
+            .unwrap()
+            .grid_disk(100)
+            .unwrap()
+            .iter(),
+    )
+}
+
+fn run_steps<T: ValidIndexes<H3Cell>>(cells: &T) {
+    for step in 0..NUM_STEPS {
+        match step % 3 {
+            0 => {
+                let _ = cells.h3_grid_disk(1).unwrap();
+            }
+            1 => {
+                let _ = cells.h3_change_resolution(7).unwrap();
+            }
+            _ => {
+                let _ = cells.packed_hilbert_rtree_index().unwrap();
+            }
+        }
+    }
+}
+
+fn bench_repeated_use(c: &mut Criterion) {
+    let ca = build_input();
+
+    let mut group = c.benchmark_group(format!("repeated-use-{}-cells", ca.len()));
+    group.bench_function("plain", |bencher| {
+        bencher.iter(|| run_steps(&ca.h3cell()));
+    });
+    group.bench_function("validated", |bencher| {
+        bencher.iter(|| run_steps(&ca.h3cell().validate()));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_repeated_use);
+criterion_main!(benches);
@@ -0,0 +1,92 @@
+use crate::algorithm::chunkedarray::util::map_indexes_to_f64;
+use crate::{Error, ValidIndexes};
+use h3ron::H3Cell;
+use polars_core::prelude::Float64Chunked;
+
+/// The exact area of the contained `H3Cell` values.
+///
+/// In contrast to the average area of the cells of a resolution, the area is computed for
+/// each individual cell.
+pub trait H3CellArea {
+    /// The area of each cell in square meters. `null` values and invalid cells result in `null`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use polars::prelude::UInt64Chunked;
+    /// use polars_core::prelude::TakeRandom;
+    /// use h3ron::{H3Cell, Index};
+    /// use h3ron_polars::algorithm::chunkedarray::H3CellArea;
+    /// use h3ron_polars::AsH3CellChunked;
+    ///
+    /// let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+    /// let ca = UInt64Chunked::from_iter([Some(cell.h3index()), Some(55), None]);
+    ///
+    /// let area = ca.h3cell().cell_area_m2().unwrap();
+    /// assert_eq!(area.get(0), Some(cell.area_m2().unwrap()));
+    /// assert_eq!(area.get(1), None);
+    /// assert_eq!(area.get(2), None);
+    /// ```
+    fn cell_area_m2(&self) -> Result<Float64Chunked, Error>;
+
+    /// The area of each cell in square kilometers. `null` values and invalid cells result in `null`.
+    fn cell_area_km2(&self) -> Result<Float64Chunked, Error>;
+
+    /// The area of each cell in square radians. `null` values and invalid cells result in `null`.
+    fn cell_area_rads2(&self) -> Result<Float64Chunked, Error>;
+}
+
+impl<T> H3CellArea for T
+where
+    T: ValidIndexes<H3Cell>,
+{
+    fn cell_area_m2(&self) -> Result<Float64Chunked, Error> {
+        map_indexes_to_f64(self, |cell: H3Cell| Ok(cell.area_m2()?))
+    }
+
+    fn cell_area_km2(&self) -> Result<Float64Chunked, Error> {
+        map_indexes_to_f64(self, |cell: H3Cell| Ok(cell.area_km2()?))
+    }
+
+    fn cell_area_rads2(&self) -> Result<Float64Chunked, Error> {
+        map_indexes_to_f64(self, |cell: H3Cell| Ok(cell.area_rads2()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::H3CellArea;
+    use crate::{AsH3CellChunked, FromIndexIterator};
+    use h3ron::{H3Cell, Index};
+    use polars_core::prelude::{TakeRandom, UInt64Chunked};
+
+    #[test]
+    fn cell_area() {
+        // a pentagon and a hexagon of the same resolution differ in their area
+        let pentagon = H3Cell::new(0x8009fffffffffff_u64);
+        assert!(pentagon.is_pentagon());
+        let hexagon = H3Cell::from_coordinate((4.5, 1.3).into(), pentagon.resolution()).unwrap();
+        let ca = UInt64Chunked::from_index_iter([
+            Some(pentagon),
+            Some(hexagon),
+            Some(H3Cell::new(55)), // invalid
+            None,
+        ]);
+
+        let area_m2 = ca.h3cell().cell_area_m2().unwrap();
+        assert_eq!(area_m2.len(), 4);
+        assert_eq!(area_m2.get(0), Some(pentagon.area_m2().unwrap()));
+        assert_eq!(area_m2.get(1), Some(hexagon.area_m2().unwrap()));
+        assert!(area_m2.get(0) < area_m2.get(1));
+        assert_eq!(area_m2.get(2), None);
+        assert_eq!(area_m2.get(3), None);
+
+        let area_km2 = ca.h3cell().cell_area_km2().unwrap();
+        assert!((area_km2.get(1).unwrap() * 1.0e6 - area_m2.get(1).unwrap()).abs() < 1.0);
+        assert_eq!(area_km2.get(2), None);
+
+        let area_rads2 = ca.h3cell().cell_area_rads2().unwrap();
+        assert_eq!(area_rads2.get(1), Some(hexagon.area_rads2().unwrap()));
+        assert_eq!(area_rads2.get(3), None);
+    }
+}
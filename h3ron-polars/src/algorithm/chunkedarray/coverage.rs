@@ -0,0 +1,272 @@
+use crate::{Error, IndexChunked, NamedFromIndexes};
+use h3ron::collections::{H3CellMap, H3CellSet};
+use h3ron::error::check_valid_h3_resolution;
+use h3ron::{H3Cell, Index, H3_MAX_RESOLUTION};
+use polars_core::frame::DataFrame;
+use polars_core::prelude::{NamedFrom, Series};
+
+/// How to handle cells with a resolution coarser than the parent resolution in
+/// [`H3CoverageByParent::h3_coverage_by_parent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoarserCells {
+    /// Fail with [`Error::ResolutionCoarserThanParent`].
+    Error,
+
+    /// Report the cell as its own parent with a full coverage.
+    FullCoverage,
+}
+
+pub trait H3CoverageByParent {
+    /// Compute the fraction of the area of parent cells at `parent_resolution` covered
+    /// by the contained cells.
+    ///
+    /// The coverage is measured in children at the finest resolution found in the array, so
+    /// cells of coarser resolutions count with all of their children at that resolution.
+    /// The number of expected children respects the reduced number of children of pentagons.
+    /// Duplicate cells are only counted once, but overlapping cells of different
+    /// resolutions are not detected. `null` values and invalid cells are ignored. The array
+    /// is scanned only once.
+    ///
+    /// Returns a new dataframe sorted by the parent cell with four columns:
+    /// * `parent_cell`: the parent cell at `parent_resolution`.
+    /// * `present_children`: number of children present in the array (u64).
+    /// * `expected_children`: number of children of the parent cell (u64).
+    /// * `coverage_fraction`: `present_children / expected_children` (f64).
+    fn h3_coverage_by_parent(
+        &self,
+        parent_resolution: u8,
+        coarser_cells: CoarserCells,
+    ) -> Result<DataFrame, Error>;
+}
+
+impl<'a> H3CoverageByParent for IndexChunked<'a, H3Cell> {
+    fn h3_coverage_by_parent(
+        &self,
+        parent_resolution: u8,
+        coarser_cells: CoarserCells,
+    ) -> Result<DataFrame, Error> {
+        check_valid_h3_resolution(parent_resolution)?;
+
+        // the number of distinct cells of each resolution under each parent, separated into
+        // hexagons and pentagons. The finest resolution is only known after the scan, so the
+        // children are counted afterwards.
+        let mut seen = H3CellSet::default();
+        let mut counts_by_parent: H3CellMap<[[usize; 2]; H3_MAX_RESOLUTION as usize + 1]> =
+            H3CellMap::default();
+        let mut child_resolution = parent_resolution;
+        for cell in self
+            .iter_indexes_validated()
+            .flatten()
+            .filter_map(Result::ok)
+        {
+            if !seen.insert(cell) {
+                continue;
+            }
+
+            let cell_resolution = cell.resolution();
+            let parent = if cell_resolution < parent_resolution {
+                match coarser_cells {
+                    CoarserCells::Error => {
+                        return Err(Error::ResolutionCoarserThanParent(
+                            cell_resolution,
+                            parent_resolution,
+                        ))
+                    }
+                    CoarserCells::FullCoverage => cell,
+                }
+            } else {
+                cell.get_parent(parent_resolution)?
+            };
+            child_resolution = child_resolution.max(cell_resolution);
+            counts_by_parent.entry(parent).or_default()[cell_resolution as usize]
+                [usize::from(cell.is_pentagon())] += 1;
+        }
+
+        let mut present_by_parent: Vec<_> = counts_by_parent
+            .into_iter()
+            .map(|(parent, counts)| {
+                let present = counts
+                    .iter()
+                    .enumerate()
+                    .map(|(resolution, [num_hexagons, num_pentagons])| {
+                        let depth = child_resolution.saturating_sub(resolution as u8);
+                        num_hexagons * num_children(depth, false)
+                            + num_pentagons * num_children(depth, true)
+                    })
+                    .sum::<usize>();
+                (parent, present)
+            })
+            .collect();
+        present_by_parent.sort_unstable_by_key(|(parent, _)| *parent);
+
+        let capacity = present_by_parent.len();
+        let mut parent_cells = Vec::with_capacity(capacity);
+        let mut present_children = Vec::with_capacity(capacity);
+        let mut expected_children = Vec::with_capacity(capacity);
+        let mut coverage_fraction = Vec::with_capacity(capacity);
+        for (parent, present) in present_by_parent {
+            let expected = parent.get_children_count(child_resolution)?;
+            parent_cells.push(parent);
+            present_children.push(present as u64);
+            expected_children.push(expected as u64);
+            coverage_fraction.push(present as f64 / expected as f64);
+        }
+
+        DataFrame::new(vec![
+            Series::new_from_indexes("parent_cell", parent_cells),
+            Series::new("present_children", present_children),
+            Series::new("expected_children", expected_children),
+            Series::new("coverage_fraction", coverage_fraction),
+        ])
+        .map_err(Error::from)
+    }
+}
+
+/// number of descendants `depth` resolutions finer than a hexagon or pentagon cell.
+fn num_children(depth: u8, is_pentagon: bool) -> usize {
+    let num_hexagon_children = 7_usize.pow(u32::from(depth));
+    if is_pentagon {
+        // the center child is a pentagon, all others are hexagons
+        1 + 5 * (num_hexagon_children - 1) / 6
+    } else {
+        num_hexagon_children
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::{CoarserCells, H3CoverageByParent};
+    use crate::{AsH3CellChunked, Error, NamedFromIndexes};
+    use h3ron::{H3Cell, Index};
+    use polars_core::prelude::{DataFrame, NamedFrom, TakeRandom, UInt64Chunked};
+
+    fn coverage(cells: Vec<H3Cell>, parent_resolution: u8) -> DataFrame {
+        UInt64Chunked::new_from_indexes("", cells)
+            .h3cell()
+            .h3_coverage_by_parent(parent_resolution, CoarserCells::Error)
+            .unwrap()
+    }
+
+    fn coverage_fraction(df: &DataFrame, row: usize) -> f64 {
+        df.column("coverage_fraction")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .get(row)
+            .unwrap()
+    }
+
+    #[test]
+    fn full_subtree() {
+        let parent = H3Cell::from_coordinate((12.0, 45.0).into(), 5).unwrap();
+        let df = coverage(parent.get_children(7).unwrap().iter().collect(), 5);
+
+        assert_eq!(df.height(), 1);
+        assert_eq!(
+            df.column("parent_cell").unwrap().u64().unwrap().get(0),
+            Some(parent.h3index())
+        );
+        assert_eq!(
+            df.column("expected_children")
+                .unwrap()
+                .u64()
+                .unwrap()
+                .get(0),
+            Some(49)
+        );
+        assert!((coverage_fraction(&df, 0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn half_populated_subtree() {
+        let parent = H3Cell::from_coordinate((12.0, 45.0).into(), 5).unwrap();
+        let children: Vec<_> = parent.get_children(7).unwrap().iter().collect();
+        let half = children.len() / 2;
+        let df = coverage(children.into_iter().take(half).collect(), 5);
+
+        assert_eq!(df.height(), 1);
+        assert!((coverage_fraction(&df, 0) - 24.0 / 49.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn pentagon_parent() {
+        let pentagon = H3Cell::new(0x8009fffffffffff_u64);
+        assert!(pentagon.is_pentagon());
+        let children: Vec<_> = pentagon.get_children(2).unwrap().iter().collect();
+        assert_eq!(children.len(), 41);
+
+        let df = coverage(children, 0);
+        assert_eq!(df.height(), 1);
+        assert_eq!(
+            df.column("expected_children")
+                .unwrap()
+                .u64()
+                .unwrap()
+                .get(0),
+            Some(41)
+        );
+        assert!((coverage_fraction(&df, 0) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn duplicates_do_not_inflate() {
+        let parent = H3Cell::from_coordinate((12.0, 45.0).into(), 5).unwrap();
+        let mut children: Vec<_> = parent.get_children(6).unwrap().iter().take(3).collect();
+        children.extend(children.clone());
+        let df = coverage(children, 5);
+
+        assert_eq!(
+            df.column("present_children").unwrap().u64().unwrap().get(0),
+            Some(3)
+        );
+        assert!((coverage_fraction(&df, 0) - 3.0 / 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn invalid_cells_are_ignored() {
+        let parent = H3Cell::from_coordinate((12.0, 45.0).into(), 5).unwrap();
+        let mut indexes: Vec<_> = parent
+            .get_children(6)
+            .unwrap()
+            .iter()
+            .take(3)
+            .map(|cell| Some(cell.h3index()))
+            .collect();
+        indexes.push(Some(0));
+        indexes.push(None);
+        let ca = UInt64Chunked::new("", indexes);
+        let df = ca
+            .h3cell()
+            .h3_coverage_by_parent(5, CoarserCells::Error)
+            .unwrap();
+
+        assert_eq!(df.height(), 1);
+        assert_eq!(
+            df.column("present_children").unwrap().u64().unwrap().get(0),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn coarser_cells() {
+        let coarse = H3Cell::from_coordinate((12.0, 45.0).into(), 3).unwrap();
+        let fine = H3Cell::from_coordinate((40.0, 10.0).into(), 7).unwrap();
+        let ca = UInt64Chunked::new_from_indexes("", vec![coarse, fine]);
+
+        assert!(matches!(
+            ca.h3cell().h3_coverage_by_parent(5, CoarserCells::Error),
+            Err(Error::ResolutionCoarserThanParent(3, 5))
+        ));
+
+        let df = ca
+            .h3cell()
+            .h3_coverage_by_parent(5, CoarserCells::FullCoverage)
+            .unwrap();
+        assert_eq!(df.height(), 2);
+        let parent_cells = df.column("parent_cell").unwrap().u64().unwrap();
+        let coarse_row = (0..df.height())
+            .find(|row| parent_cells.get(*row) == Some(coarse.h3index()))
+            .unwrap();
+        assert!((coverage_fraction(&df, coarse_row) - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{Error, ValidIndexes};
+use h3ron::{H3Cell, H3DirectedEdge, Index};
+use polars::prelude::{IntoSeries, StructChunked, UInt64Chunked};
+
+/// The cells of the contained `H3DirectedEdge` values.
+pub trait H3DirectedEdgeCells {
+    /// The origin cell of each edge. `null` values and invalid edges result in `null`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use polars::prelude::UInt64Chunked;
+    /// use polars_core::prelude::TakeRandom;
+    /// use h3ron::{H3Cell, H3DirectedEdge, Index};
+    /// use h3ron_polars::algorithm::chunkedarray::H3DirectedEdgeCells;
+    /// use h3ron_polars::AsH3DirectedEdgeChunked;
+    ///
+    /// let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+    /// let edge = cell.directed_edges().unwrap().iter().next().unwrap();
+    /// let ca = UInt64Chunked::from_iter([Some(edge.h3index()), Some(55), None]);
+    ///
+    /// let origin_cells = ca.h3directededge().origin_cells();
+    /// assert_eq!(origin_cells.get(0), Some(cell.h3index()));
+    /// assert_eq!(origin_cells.get(1), None);
+    /// assert_eq!(origin_cells.get(2), None);
+    /// ```
+    fn origin_cells(&self) -> UInt64Chunked;
+
+    /// The destination cell of each edge. `null` values and invalid edges result in `null`.
+    fn destination_cells(&self) -> UInt64Chunked;
+
+    /// The origin and destination cells of each edge as the fields `origin` and `destination`
+    /// of a struct. `null` values and invalid edges result in `null` fields.
+    fn cells(&self) -> Result<StructChunked, Error>;
+}
+
+impl<T> H3DirectedEdgeCells for T
+where
+    T: ValidIndexes<H3DirectedEdge>,
+{
+    fn origin_cells(&self) -> UInt64Chunked {
+        map_edges_to_cells(self, |edge| edge.origin_cell())
+    }
+
+    fn destination_cells(&self) -> UInt64Chunked {
+        map_edges_to_cells(self, |edge| edge.destination_cell())
+    }
+
+    fn cells(&self) -> Result<StructChunked, Error> {
+        let mut origin = self.origin_cells();
+        origin.rename("origin");
+        let mut destination = self.destination_cells();
+        destination.rename("destination");
+        Ok(StructChunked::new(
+            self.chunked_array().name(),
+            &[origin.into_series(), destination.into_series()],
+        )?)
+    }
+}
+
+fn map_edges_to_cells<T, F>(edges: &T, map_fn: F) -> UInt64Chunked
+where
+    T: ValidIndexes<H3DirectedEdge> + ?Sized,
+    F: Fn(&H3DirectedEdge) -> Result<H3Cell, h3ron::Error>,
+{
+    UInt64Chunked::from_iter(edges.iter_valid_indexes().map(|maybe_edge| {
+        maybe_edge
+            .and_then(|edge| map_fn(&edge).ok())
+            .map(|cell| cell.h3index())
+    }))
+}
+
+/// The directed edges from the cells of `origin` to the cells of `destination` at the same
+/// position.
+///
+/// Pairs of cells which are no neighbors, `null` values and invalid cells result in `null`.
+/// Fails when both arrays differ in their length.
+pub fn cells_to_edges<O, D>(origin: &O, destination: &D) -> Result<UInt64Chunked, Error>
+where
+    O: ValidIndexes<H3Cell>,
+    D: ValidIndexes<H3Cell>,
+{
+    let (origin_len, destination_len) = (
+        origin.chunked_array().len(),
+        destination.chunked_array().len(),
+    );
+    if origin_len != destination_len {
+        return Err(Error::LengthMismatch(origin_len, destination_len));
+    }
+    Ok(UInt64Chunked::from_iter(
+        origin
+            .iter_valid_indexes()
+            .zip(destination.iter_valid_indexes())
+            .map(|pair| match pair {
+                (Some(origin_cell), Some(destination_cell)) => origin_cell
+                    .directed_edge_to(destination_cell)
+                    .ok()
+                    .map(|edge| edge.h3index()),
+                _ => None,
+            }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::{cells_to_edges, H3DirectedEdgeCells};
+    use crate::{AsH3CellChunked, AsH3DirectedEdgeChunked, Error, FromIndexIterator};
+    use h3ron::{H3Cell, H3DirectedEdge, Index};
+    use polars::prelude::{IntoSeries, TakeRandom, UInt64Chunked};
+
+    #[test]
+    fn edge_cells_roundtrip() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 7).unwrap();
+        let edges: Vec<_> = cell.directed_edges().unwrap().iter().collect();
+        let ca = UInt64Chunked::from_index_iter([
+            Some(edges[0]),
+            Some(edges[3]),
+            Some(H3DirectedEdge::new(55)), // invalid
+            None,
+        ]);
+
+        let origin = ca.h3directededge().origin_cells();
+        let destination = ca.h3directededge().destination_cells();
+        assert_eq!(origin.len(), 4);
+        assert_eq!(destination.len(), 4);
+        for i in 0..2 {
+            assert_eq!(origin.get(i), Some(cell.h3index()));
+        }
+        assert_eq!(
+            destination.get(1),
+            Some(edges[3].destination_cell().unwrap().h3index())
+        );
+        for i in 2..4 {
+            assert_eq!(origin.get(i), None);
+            assert_eq!(destination.get(i), None);
+        }
+
+        let cells = ca.h3directededge().cells().unwrap();
+        assert_eq!(cells.len(), 4);
+        let fields = cells.fields();
+        // the fields only differ from the separately computed cells by their name
+        for (field, (name, expected)) in fields
+            .iter()
+            .zip([("origin", &origin), ("destination", &destination)])
+        {
+            assert_eq!(field.name(), name);
+            let mut expected = expected.clone();
+            expected.rename(name);
+            assert!(field.series_equal_missing(&expected.into_series()));
+        }
+
+        let roundtrip = cells_to_edges(&origin.h3cell(), &destination.h3cell()).unwrap();
+        assert_eq!(roundtrip.len(), 4);
+        assert_eq!(roundtrip.get(0), Some(edges[0].h3index()));
+        assert_eq!(roundtrip.get(1), Some(edges[3].h3index()));
+        assert_eq!(roundtrip.get(2), None);
+        assert_eq!(roundtrip.get(3), None);
+    }
+
+    #[test]
+    fn cells_to_edges_non_neighbors() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 7).unwrap();
+        let far_away = H3Cell::from_coordinate((-60.5, -60.5).into(), 7).unwrap();
+        let origin = UInt64Chunked::from_index_iter([Some(cell), Some(cell), Some(cell)]);
+        let destination = UInt64Chunked::from_index_iter([
+            Some(far_away),
+            Some(cell),
+            Some(cell.get_parent(6).unwrap()),
+        ]);
+
+        let edges = cells_to_edges(&origin.h3cell(), &destination.h3cell()).unwrap();
+        assert_eq!(edges.len(), 3);
+        assert!(edges.into_iter().all(|edge| edge.is_none()));
+
+        let shorter = UInt64Chunked::from_index_iter([Some(far_away)]);
+        assert!(matches!(
+            cells_to_edges(&origin.h3cell(), &shorter.h3cell()),
+            Err(Error::LengthMismatch(3, 1))
+        ));
+    }
+}
@@ -0,0 +1,64 @@
+use crate::algorithm::chunkedarray::util::map_indexes_to_f64;
+use crate::{Error, ValidIndexes};
+use h3ron::H3DirectedEdge;
+use polars_core::prelude::Float64Chunked;
+
+/// The exact length of the contained `H3DirectedEdge` values.
+///
+/// In contrast to the average edge length of a resolution, the length is computed for
+/// each individual edge.
+pub trait H3EdgeLength {
+    /// The length of each edge in meters. `null` values and invalid edges result in `null`.
+    fn edge_length_m(&self) -> Result<Float64Chunked, Error>;
+
+    /// The length of each edge in kilometers. `null` values and invalid edges result in `null`.
+    fn edge_length_km(&self) -> Result<Float64Chunked, Error>;
+}
+
+impl<T> H3EdgeLength for T
+where
+    T: ValidIndexes<H3DirectedEdge>,
+{
+    fn edge_length_m(&self) -> Result<Float64Chunked, Error> {
+        map_indexes_to_f64(self, |edge: H3DirectedEdge| Ok(edge.length_m()?))
+    }
+
+    fn edge_length_km(&self) -> Result<Float64Chunked, Error> {
+        map_indexes_to_f64(self, |edge: H3DirectedEdge| Ok(edge.length_km()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::H3EdgeLength;
+    use crate::{AsH3DirectedEdgeChunked, FromIndexIterator};
+    use h3ron::{H3Cell, H3DirectedEdge, Index};
+    use polars_core::prelude::{TakeRandom, UInt64Chunked};
+
+    #[test]
+    fn edge_length() {
+        let edges: Vec<_> = H3Cell::from_coordinate((4.5, 1.3).into(), 7)
+            .unwrap()
+            .directed_edges()
+            .unwrap()
+            .iter()
+            .collect();
+        let ca = UInt64Chunked::from_index_iter([
+            Some(edges[0]),
+            Some(edges[1]),
+            Some(H3DirectedEdge::new(55)), // invalid
+            None,
+        ]);
+
+        let length_m = ca.h3directededge().edge_length_m().unwrap();
+        assert_eq!(length_m.len(), 4);
+        assert_eq!(length_m.get(0), Some(edges[0].length_m().unwrap()));
+        assert_eq!(length_m.get(1), Some(edges[1].length_m().unwrap()));
+        assert_eq!(length_m.get(2), None);
+        assert_eq!(length_m.get(3), None);
+
+        let length_km = ca.h3directededge().edge_length_km().unwrap();
+        assert!((length_km.get(0).unwrap() * 1000.0 - length_m.get(0).unwrap()).abs() < 1.0e-6);
+        assert_eq!(length_km.get(2), None);
+    }
+}
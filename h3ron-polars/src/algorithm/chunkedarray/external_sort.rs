@@ -0,0 +1,427 @@
+use crate::Error;
+use polars_core::prelude::UInt64Chunked;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of values collected before they are appended as a new chunk to the output array.
+const OUTPUT_CHUNK_LEN: usize = 64 * 1024;
+
+/// Minimum size of the read buffer of each spilled run during the merge.
+const MIN_READ_BUFFER_BYTES: usize = 64;
+
+/// counter to create distinct spill file names within this process
+static SPILL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// How to handle `null` values in [`external_sort_unique_with_nulls`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NullHandling {
+    /// `null` values are not part of the output.
+    Drop,
+
+    /// A single `null` value is emitted as the first element when the input
+    /// contains `null` values.
+    EmitFirst,
+}
+
+/// Sort and deduplicate `cells` without holding more than `memory_budget_bytes` of
+/// values - and one output chunk - in memory.
+///
+/// The input is split in sorted runs fitting into the memory budget. In case there
+/// is more than a single run, the runs are spilled to files in `spill_dir` and merged
+/// afterwards. The spill files are removed on success and on failure.
+///
+/// `null` values are dropped. See [`external_sort_unique_with_nulls`].
+pub fn external_sort_unique(
+    cells: &UInt64Chunked,
+    memory_budget_bytes: usize,
+    spill_dir: &Path,
+) -> Result<UInt64Chunked, Error> {
+    external_sort_unique_with_nulls(cells, memory_budget_bytes, spill_dir, NullHandling::Drop)
+}
+
+/// Like [`external_sort_unique`], but with control over the handling of `null` values.
+pub fn external_sort_unique_with_nulls(
+    cells: &UInt64Chunked,
+    memory_budget_bytes: usize,
+    spill_dir: &Path,
+    null_handling: NullHandling,
+) -> Result<UInt64Chunked, Error> {
+    external_sort_unique_impl(cells, memory_budget_bytes, spill_dir, null_handling, |_| {
+        Ok(())
+    })
+}
+
+/// `on_merged_value` is called for each value emitted by the merge of the spilled runs.
+fn external_sort_unique_impl<F>(
+    cells: &UInt64Chunked,
+    memory_budget_bytes: usize,
+    spill_dir: &Path,
+    null_handling: NullHandling,
+    on_merged_value: F,
+) -> Result<UInt64Chunked, Error>
+where
+    F: FnMut(u64) -> Result<(), Error>,
+{
+    let run_len = (memory_budget_bytes / std::mem::size_of::<u64>()).max(1);
+    let mut output = OutputBuilder::new(cells.name());
+
+    let mut spilled_runs = SpilledRuns::default();
+    let mut run = Vec::with_capacity(run_len.min(cells.len()));
+    let mut has_nulls = false;
+    for maybe_cell in cells.into_iter() {
+        match maybe_cell {
+            Some(cell) => {
+                run.push(cell);
+                if run.len() >= run_len {
+                    sort_unique(&mut run);
+                    spilled_runs.spill(spill_dir, &run)?;
+                    run.clear();
+                }
+            }
+            None => has_nulls = true,
+        }
+    }
+
+    if has_nulls && null_handling == NullHandling::EmitFirst {
+        output.push_null();
+    }
+
+    if spilled_runs.paths.is_empty() {
+        // everything fits into the memory budget
+        sort_unique(&mut run);
+        for cell in run {
+            output.push(cell);
+        }
+    } else {
+        if !run.is_empty() {
+            sort_unique(&mut run);
+            spilled_runs.spill(spill_dir, &run)?;
+        }
+        drop(run);
+        spilled_runs.merge(memory_budget_bytes, &mut output, on_merged_value)?;
+    }
+    Ok(output.finish())
+}
+
+fn sort_unique(values: &mut Vec<u64>) {
+    values.sort_unstable();
+    values.dedup();
+}
+
+/// Collects the output values in chunks of [`OUTPUT_CHUNK_LEN`].
+struct OutputBuilder {
+    output: UInt64Chunked,
+    chunk: Vec<Option<u64>>,
+}
+
+impl OutputBuilder {
+    fn new(name: &str) -> Self {
+        Self {
+            output: UInt64Chunked::from_vec(name, vec![]),
+            chunk: Vec::with_capacity(OUTPUT_CHUNK_LEN),
+        }
+    }
+
+    fn push(&mut self, value: u64) {
+        self.chunk.push(Some(value));
+        if self.chunk.len() >= OUTPUT_CHUNK_LEN {
+            self.flush();
+        }
+    }
+
+    fn push_null(&mut self) {
+        self.chunk.push(None);
+    }
+
+    fn flush(&mut self) {
+        if !self.chunk.is_empty() {
+            let chunk = UInt64Chunked::from_iter(self.chunk.drain(..));
+            self.output.append(&chunk);
+        }
+    }
+
+    fn finish(mut self) -> UInt64Chunked {
+        self.flush();
+        self.output
+    }
+}
+
+/// Sorted runs spilled to files. The files get removed when this struct is dropped.
+#[derive(Default)]
+struct SpilledRuns {
+    paths: Vec<PathBuf>,
+}
+
+impl SpilledRuns {
+    /// write the sorted and deduplicated `run` to a new file in `spill_dir`.
+    fn spill(&mut self, spill_dir: &Path, run: &[u64]) -> Result<(), Error> {
+        let path = spill_dir.join(format!(
+            "h3ron-sort-{}-{}.run",
+            std::process::id(),
+            SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        self.paths.push(path);
+
+        let mut writer = BufWriter::new(file);
+        let mut previous = 0_u64;
+        for value in run {
+            write_varint(&mut writer, value - previous)?;
+            previous = *value;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// k-way merge of all runs. Values found in multiple runs are only emitted once.
+    fn merge<F>(
+        &self,
+        memory_budget_bytes: usize,
+        output: &mut OutputBuilder,
+        mut on_merged_value: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(u64) -> Result<(), Error>,
+    {
+        let buffer_bytes = (memory_budget_bytes / self.paths.len()).max(MIN_READ_BUFFER_BYTES);
+        let mut readers = self
+            .paths
+            .iter()
+            .map(|path| File::open(path).map(|file| RunReader::new(file, buffer_bytes)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut heap = BinaryHeap::with_capacity(readers.len());
+        for (run_i, reader) in readers.iter_mut().enumerate() {
+            if let Some(value) = reader.next_value()? {
+                heap.push(Reverse((value, run_i)));
+            }
+        }
+
+        let mut last_value = None;
+        while let Some(Reverse((value, run_i))) = heap.pop() {
+            if last_value != Some(value) {
+                on_merged_value(value)?;
+                output.push(value);
+                last_value = Some(value);
+            }
+            if let Some(next_value) = readers[run_i].next_value()? {
+                heap.push(Reverse((next_value, run_i)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SpilledRuns {
+    fn drop(&mut self) {
+        for path in self.paths.iter() {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Reads the delta- and varint-encoded values of a spilled run.
+struct RunReader {
+    reader: BufReader<File>,
+    previous: u64,
+}
+
+impl RunReader {
+    fn new(file: File, buffer_bytes: usize) -> Self {
+        Self {
+            reader: BufReader::with_capacity(buffer_bytes, file),
+            previous: 0,
+        }
+    }
+
+    fn next_value(&mut self) -> Result<Option<u64>, Error> {
+        Ok(read_varint(&mut self.reader)?.map(|delta| {
+            self.previous += delta;
+            self.previous
+        }))
+    }
+}
+
+/// LEB128 encoding of `value`.
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> std::io::Result<()> {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_all(&[byte]);
+        }
+        writer.write_all(&[byte | 0x80])?;
+    }
+}
+
+/// Read a LEB128-encoded value. Returns `None` at the end of the input.
+fn read_varint<R: Read>(reader: &mut R) -> std::io::Result<Option<u64>> {
+    let mut value = 0_u64;
+    let mut shift = 0;
+    let mut byte = [0_u8];
+    loop {
+        if let Err(e) = reader.read_exact(&mut byte) {
+            return if e.kind() == ErrorKind::UnexpectedEof && shift == 0 {
+                Ok(None)
+            } else {
+                Err(e)
+            };
+        }
+        value |= u64::from(byte[0] & 0x7f) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+        shift += 7;
+        if shift >= 64 {
+            return Err(std::io::Error::new(
+                ErrorKind::InvalidData,
+                "varint exceeds 64 bits",
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        external_sort_unique, external_sort_unique_impl, external_sort_unique_with_nulls,
+        read_varint, write_varint, NullHandling,
+    };
+    use crate::{Error, FromIndexIterator};
+    use h3ron::H3Cell;
+    use polars_core::prelude::{IntoSeries, UInt64Chunked};
+    use std::path::PathBuf;
+
+    fn spill_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "h3ron-polars-external-sort-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn assert_spill_dir_empty_and_remove(dir: PathBuf) {
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(dir).unwrap();
+    }
+
+    /// cells with duplicates and nulls in no particular order
+    fn unsorted_cells() -> UInt64Chunked {
+        let disk: Vec<_> = H3Cell::from_coordinate((12.3, 45.4).into(), 8)
+            .unwrap()
+            .grid_disk(12)
+            .unwrap()
+            .iter()
+            .collect();
+        let values = disk
+            .iter()
+            .rev()
+            .chain(disk.iter().step_by(3))
+            .enumerate()
+            .map(|(i, cell)| if i % 17 == 0 { None } else { Some(*cell) });
+        UInt64Chunked::from_index_iter(values)
+    }
+
+    fn polars_sort_unique(ca: &UInt64Chunked) -> Vec<u64> {
+        ca.clone()
+            .into_series()
+            .unique()
+            .unwrap()
+            .sort(false)
+            .u64()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    fn values(ca: &UInt64Chunked) -> Vec<Option<u64>> {
+        ca.into_iter().collect()
+    }
+
+    #[test]
+    fn varint_roundtrip() {
+        let mut buf = vec![];
+        for value in [0, 1, 127, 128, 300, u64::MAX] {
+            write_varint(&mut buf, value).unwrap();
+        }
+        let mut reader = buf.as_slice();
+        for value in [0, 1, 127, 128, 300, u64::MAX] {
+            assert_eq!(read_varint(&mut reader).unwrap(), Some(value));
+        }
+        assert_eq!(read_varint(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn in_memory_equals_polars() {
+        let ca = unsorted_cells();
+        let dir = spill_dir("in-memory");
+        let sorted = external_sort_unique(&ca, 1024 * 1024, &dir).unwrap();
+        assert_eq!(sorted.null_count(), 0);
+        assert_eq!(
+            values(&sorted),
+            polars_sort_unique(&ca)
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>()
+        );
+        assert_spill_dir_empty_and_remove(dir);
+    }
+
+    #[test]
+    fn spilled_runs_equal_polars() {
+        let ca = unsorted_cells();
+        let dir = spill_dir("spilled");
+
+        // 8 values per run
+        let sorted = external_sort_unique(&ca, 64, &dir).unwrap();
+        assert_eq!(
+            values(&sorted),
+            polars_sort_unique(&ca)
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>()
+        );
+        assert_spill_dir_empty_and_remove(dir);
+    }
+
+    #[test]
+    fn nulls_first() {
+        let ca = unsorted_cells();
+        let dir = spill_dir("nulls-first");
+        let sorted =
+            external_sort_unique_with_nulls(&ca, 64, &dir, NullHandling::EmitFirst).unwrap();
+        assert_eq!(sorted.null_count(), 1);
+        assert_eq!(sorted.len(), polars_sort_unique(&ca).len() + 1);
+        assert_eq!(values(&sorted)[0], None);
+        assert_spill_dir_empty_and_remove(dir);
+    }
+
+    #[test]
+    fn error_during_merge_removes_spill_files() {
+        let ca = unsorted_cells();
+        let dir = spill_dir("merge-error");
+        let mut num_merged = 0;
+        let result = external_sort_unique_impl(&ca, 64, &dir, NullHandling::Drop, |_| {
+            num_merged += 1;
+            if num_merged > 20 {
+                // the spill files exist during the merge
+                assert!(std::fs::read_dir(&dir).unwrap().count() > 1);
+                Err(Error::InvalidH3Indexes)
+            } else {
+                Ok(())
+            }
+        });
+        assert!(matches!(result, Err(Error::InvalidH3Indexes)));
+        assert_spill_dir_empty_and_remove(dir);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::algorithm::chunkedarray::check_single_resolution;
+use crate::{Error, IndexChunked, ValidIndexes};
+use h3ron::collections::H3CellMap;
+use h3ron::H3Cell;
+use polars::export::rayon::prelude::*;
+use polars::prelude::Float64Chunked;
+use polars_core::POOL;
+
+/// number of rows aggregated by a single task
+const ROWS_PER_TASK: usize = 4096;
+
+/// The aggregation applied by [`h3_focal_aggregate`] to the values within the k-ring of a cell.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FocalAgg {
+    Mean,
+    Min,
+    Max,
+    Sum,
+}
+
+#[derive(Default)]
+struct FocalAccumulator {
+    count: usize,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl FocalAccumulator {
+    fn add(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+
+    fn value(&self, agg: FocalAgg) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        Some(match agg {
+            FocalAgg::Mean => self.sum / self.count as f64,
+            FocalAgg::Min => self.min,
+            FocalAgg::Max => self.max,
+            FocalAgg::Sum => self.sum,
+        })
+    }
+}
+
+/// Aggregate the `values` of all rows whose cells are located within the grid distance `k`
+/// of the cell of each row - including the row itself.
+///
+/// Cells contained in multiple rows contribute the value of each of these rows, cells of the
+/// k-ring not contained in `cells` are skipped. `null` values and invalid cells result in `null`,
+/// rows with a `null` value do not contribute to the aggregates of their neighbors.
+///
+/// Fails with [`Error::MixedResolutions`] when `cells` contains cells of multiple resolutions
+/// and with [`Error::LengthMismatch`] when both arrays differ in their length.
+pub fn h3_focal_aggregate(
+    cells: &IndexChunked<H3Cell>,
+    values: &Float64Chunked,
+    k: u32,
+    agg: FocalAgg,
+) -> Result<Float64Chunked, Error> {
+    if cells.len() != values.len() {
+        return Err(Error::LengthMismatch(cells.len(), values.len()));
+    }
+    match check_single_resolution(cells) {
+        Ok(_) => {}
+        // no valid cells at all, every row results in `null`
+        Err(mix) if mix.histogram.is_empty() => {}
+        Err(mix) => return Err(Error::MixedResolutions(mix)),
+    }
+
+    let rows: Vec<_> = cells
+        .iter_valid_indexes()
+        .zip(values)
+        .map(|(maybe_cell, maybe_value)| maybe_cell.zip(maybe_value))
+        .collect();
+
+    let mut cell_values: H3CellMap<Vec<f64>> = H3CellMap::default();
+    for (cell, value) in rows.iter().flatten() {
+        cell_values.entry(*cell).or_default().push(*value);
+    }
+
+    let aggregated = POOL.install(|| {
+        rows.par_chunks(ROWS_PER_TASK)
+            .map(|rows_chunk| {
+                rows_chunk
+                    .iter()
+                    .map(|row| match row {
+                        Some((cell, _)) => {
+                            let mut accumulator = FocalAccumulator::default();
+                            for neighbor in cell.grid_disk(k)?.iter() {
+                                if let Some(neighbor_values) = cell_values.get(&neighbor) {
+                                    for value in neighbor_values {
+                                        accumulator.add(*value);
+                                    }
+                                }
+                            }
+                            Ok(accumulator.value(agg))
+                        }
+                        None => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    Ok(Float64Chunked::from_iter(aggregated.into_iter().flatten()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::{h3_focal_aggregate, FocalAgg};
+    use crate::{AsH3CellChunked, Error, FromIndexIterator};
+    use h3ron::H3Cell;
+    use polars::prelude::{Float64Chunked, TakeRandom, UInt64Chunked};
+
+    fn brute_force(
+        cells: &[Option<H3Cell>],
+        values: &[Option<f64>],
+        k: u32,
+        agg: FocalAgg,
+    ) -> Vec<Option<f64>> {
+        cells
+            .iter()
+            .zip(values.iter())
+            .map(|(maybe_cell, maybe_value)| {
+                let cell = (*maybe_cell)?;
+                maybe_value.as_ref()?;
+                let neighbor_values: Vec<f64> = cells
+                    .iter()
+                    .zip(values.iter())
+                    .filter_map(
+                        |(other_cell, other_value)| match (other_cell, other_value) {
+                            (Some(other_cell), Some(other_value))
+                                if cell.grid_distance_to(*other_cell).unwrap() <= k as usize =>
+                            {
+                                Some(*other_value)
+                            }
+                            _ => None,
+                        },
+                    )
+                    .collect();
+                Some(match agg {
+                    FocalAgg::Mean => {
+                        neighbor_values.iter().sum::<f64>() / neighbor_values.len() as f64
+                    }
+                    FocalAgg::Min => neighbor_values.iter().copied().fold(f64::MAX, f64::min),
+                    FocalAgg::Max => neighbor_values.iter().copied().fold(f64::MIN, f64::max),
+                    FocalAgg::Sum => neighbor_values.iter().sum(),
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn focal_aggregate_equals_brute_force() {
+        let center = H3Cell::from_coordinate((12.3, 45.4).into(), 7).unwrap();
+        let mut cells: Vec<_> = center
+            .grid_disk(4)
+            .unwrap()
+            .iter()
+            .step_by(2)
+            .map(Some)
+            .collect();
+        // duplicated cell, null cell and a cell with a null value
+        cells.push(Some(center));
+        cells.push(None);
+        cells.push(Some(center.grid_disk(1).unwrap().iter().nth(3).unwrap()));
+        let mut values: Vec<_> = (0..cells.len())
+            .map(|i| Some((i * 7 % 11) as f64))
+            .collect();
+        *values.last_mut().unwrap() = None;
+
+        let cells_ca = UInt64Chunked::from_index_iter(cells.iter().copied());
+        let values_ca = Float64Chunked::from_iter(values.iter().copied());
+        for k in [0, 1, 2] {
+            for agg in [FocalAgg::Mean, FocalAgg::Min, FocalAgg::Max, FocalAgg::Sum] {
+                let aggregated =
+                    h3_focal_aggregate(&cells_ca.h3cell(), &values_ca, k, agg).unwrap();
+                let expected = brute_force(&cells, &values, k, agg);
+                assert_eq!(aggregated.len(), expected.len());
+                for (i, expected_value) in expected.iter().enumerate() {
+                    match (aggregated.get(i), expected_value) {
+                        (Some(value), Some(expected_value)) => {
+                            assert!((value - expected_value).abs() < 1e-9)
+                        }
+                        (None, None) => {}
+                        (value, expected_value) => {
+                            panic!("row {}: {:?} != {:?}", i, value, expected_value)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn focal_aggregate_mixed_resolutions() {
+        let cell = H3Cell::from_coordinate((12.3, 45.4).into(), 7).unwrap();
+        let cells_ca =
+            UInt64Chunked::from_index_iter([Some(cell), Some(cell.get_parent(6).unwrap())]);
+        let values_ca = Float64Chunked::from_iter([Some(1.0), Some(2.0)]);
+        assert!(matches!(
+            h3_focal_aggregate(&cells_ca.h3cell(), &values_ca, 1, FocalAgg::Mean),
+            Err(Error::MixedResolutions(_))
+        ));
+
+        let shorter = Float64Chunked::from_iter([Some(1.0)]);
+        assert!(matches!(
+            h3_focal_aggregate(&cells_ca.h3cell(), &shorter, 1, FocalAgg::Mean),
+            Err(Error::LengthMismatch(2, 1))
+        ));
+    }
+}
@@ -0,0 +1,548 @@
+use crate::algorithm::chunkedarray::CellPredicate;
+use crate::{Error, ValidIndexes};
+use geo::{BoundingRect, Contains, Intersects, Relate};
+use geo_types::{Geometry, Polygon, Rect};
+use geojson::{Feature, GeoJson, JsonObject, JsonValue};
+use h3ron::{H3Cell, Index, ToCoordinate, ToPolygon};
+use polars::prelude::{AnyValue, BooleanChunked, DataFrame, IntoSeries, NewChunkedArray, Series};
+use rstar::primitives::{GeomWithData, Rectangle};
+use rstar::{RTree, AABB};
+use std::collections::HashSet;
+use std::io::Write;
+
+type Coord = [f64; 2];
+
+struct TaggingFeature {
+    name: String,
+    geometry: Geometry<f64>,
+}
+
+/// Tag `cells` with the features of a GeoJSON `FeatureCollection`.
+///
+/// Returns a dataframe with one boolean column per feature, in the order of the features
+/// in the collection. Each column is named by the string value of the `name_property` of the
+/// feature, falling back to `feature_<index>` when the property is not set. Names occurring more
+/// than once get a numeric suffix. `null` and invalid cells result in `null` in all columns.
+///
+/// The bounding boxes of all features are indexed once in a R-tree, so the predicate is only
+/// evaluated for the candidate features of a cell.
+pub fn tag_cells_with_geojson<C>(
+    cells: &C,
+    geojson: &str,
+    predicate: CellPredicate,
+    name_property: Option<&str>,
+) -> Result<DataFrame, Error>
+where
+    C: ValidIndexes<H3Cell> + ?Sized,
+{
+    let features = parse_features(geojson, name_property)?;
+
+    let tree = RTree::bulk_load(
+        features
+            .iter()
+            .enumerate()
+            .filter_map(|(i, feature)| {
+                feature
+                    .geometry
+                    .bounding_rect()
+                    .map(|rect| GeomWithData::new(Rectangle::from(envelope_of(&rect)), i))
+            })
+            .collect(),
+    );
+
+    let mut columns = vec![Vec::with_capacity(cells.chunked_array().len()); features.len()];
+    let mut hits = vec![false; features.len()];
+    for cell in cells.iter_valid_indexes() {
+        match cell {
+            Some(cell) => {
+                hits.iter_mut().for_each(|hit| *hit = false);
+                tag_cell(&cell, &features, &tree, predicate, &mut hits)?;
+                for (column, hit) in columns.iter_mut().zip(hits.iter()) {
+                    column.push(Some(*hit));
+                }
+            }
+            None => columns.iter_mut().for_each(|column| column.push(None)),
+        }
+    }
+
+    let series = features
+        .iter()
+        .zip(columns)
+        .map(|(feature, column)| {
+            BooleanChunked::from_slice_options(&feature.name, &column).into_series()
+        })
+        .collect::<Vec<Series>>();
+    Ok(DataFrame::new(series)?)
+}
+
+fn tag_cell(
+    cell: &H3Cell,
+    features: &[TaggingFeature],
+    tree: &RTree<GeomWithData<Rectangle<Coord>, usize>>,
+    predicate: CellPredicate,
+    hits: &mut [bool],
+) -> Result<(), Error> {
+    match predicate {
+        CellPredicate::CentroidWithin => {
+            let centroid = cell.to_coordinate()?;
+            let envelope = AABB::from_point(centroid.x_y().into());
+            for candidate in tree.locate_in_envelope_intersecting(&envelope) {
+                if features[candidate.data].geometry.contains(&centroid) {
+                    hits[candidate.data] = true;
+                }
+            }
+        }
+        CellPredicate::Intersects | CellPredicate::Within => {
+            let polygon = cell.to_polygon()?;
+            let rect = match polygon.bounding_rect() {
+                Some(rect) => rect,
+                None => return Ok(()),
+            };
+            let envelope = envelope_of(&rect);
+            let cell_geometry = Geometry::from(polygon);
+            for candidate in tree.locate_in_envelope_intersecting(&envelope) {
+                let geometry = &features[candidate.data].geometry;
+                let hit = if predicate == CellPredicate::Within {
+                    geometry.relate(&cell_geometry).is_contains()
+                } else {
+                    geometry.intersects(&cell_geometry)
+                };
+                if hit {
+                    hits[candidate.data] = true;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn envelope_of(rect: &Rect<f64>) -> AABB<Coord> {
+    AABB::from_corners(rect.min().x_y().into(), rect.max().x_y().into())
+}
+
+fn parse_features(
+    geojson: &str,
+    name_property: Option<&str>,
+) -> Result<Vec<TaggingFeature>, Error> {
+    let collection = match geojson
+        .parse::<GeoJson>()
+        .map_err(|e| Error::InvalidGeoJson(e.to_string()))?
+    {
+        GeoJson::FeatureCollection(collection) => collection,
+        _ => {
+            return Err(Error::InvalidGeoJson(
+                "expected a FeatureCollection".to_string(),
+            ))
+        }
+    };
+
+    let mut used_names = HashSet::new();
+    collection
+        .features
+        .into_iter()
+        .enumerate()
+        .map(|(i, feature)| {
+            let base_name = name_property
+                .and_then(|key| feature.property(key))
+                .and_then(|value| value.as_str())
+                .map(ToString::to_string)
+                .unwrap_or_else(|| format!("feature_{}", i));
+            let geometry = feature
+                .geometry
+                .ok_or_else(|| Error::InvalidGeoJsonFeature(i, "missing geometry".to_string()))
+                .and_then(|geometry| {
+                    Geometry::<f64>::try_from(geometry)
+                        .map_err(|e| Error::InvalidGeoJsonFeature(i, e.to_string()))
+                })?;
+
+            let mut name = base_name.clone();
+            let mut suffix = 1_usize;
+            while used_names.contains(&name) {
+                name = format!("{}_{}", base_name, suffix);
+                suffix += 1;
+            }
+            used_names.insert(name.clone());
+            Ok(TaggingFeature { name, geometry })
+        })
+        .collect()
+}
+
+/// Write `cells` as a GeoJSON `FeatureCollection` with one `Polygon` feature per cell to `writer`.
+///
+/// The features are written one after another, so the collection is never built in memory. Each
+/// feature has the hexadecimal representation of the cell as `h3index` property and the values of
+/// the columns of `properties` at the position of the cell as further properties. `null` values
+/// and invalid cells are skipped.
+///
+/// Polygons of cells crossing the antimeridian get their coordinates in the western hemisphere
+/// shifted by 360 degrees, so they extend beyond 180 degrees of longitude instead of spanning
+/// the globe.
+///
+/// # Example
+///
+/// ```
+/// use polars::prelude::{DataFrame, NamedFrom, Series, UInt64Chunked};
+/// use h3ron::{H3Cell, Index};
+/// use h3ron_polars::algorithm::chunkedarray::write_cells_geojson;
+/// use h3ron_polars::AsH3CellChunked;
+///
+/// let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+/// let ca = UInt64Chunked::new("", &[cell.h3index()]);
+/// let properties = DataFrame::new(vec![Series::new("value", &[5_u32])]).unwrap();
+///
+/// let mut buf = vec![];
+/// write_cells_geojson(&ca.h3cell(), Some(&properties), &mut buf).unwrap();
+/// let geojson = String::from_utf8(buf).unwrap();
+/// assert!(geojson.starts_with(r#"{"type":"FeatureCollection","features":[{"#));
+/// assert!(geojson.contains(r#""value":5"#));
+/// ```
+pub fn write_cells_geojson<C, W>(
+    cells: &C,
+    properties: Option<&DataFrame>,
+    writer: &mut W,
+) -> Result<(), Error>
+where
+    C: ValidIndexes<H3Cell> + ?Sized,
+    W: Write + ?Sized,
+{
+    let columns: &[Series] = match properties {
+        Some(df) => {
+            if df.height() != cells.chunked_array().len() {
+                return Err(Error::LengthMismatch(
+                    cells.chunked_array().len(),
+                    df.height(),
+                ));
+            }
+            df.get_columns()
+        }
+        None => &[],
+    };
+
+    writer.write_all(br#"{"type":"FeatureCollection","features":["#)?;
+    let mut is_first = true;
+    for (position, maybe_cell) in cells.iter_valid_indexes().enumerate() {
+        let cell = match maybe_cell {
+            Some(cell) => cell,
+            None => continue,
+        };
+        let mut feature_properties = JsonObject::new();
+        feature_properties.insert(
+            "h3index".to_string(),
+            JsonValue::from(format!("{:x}", cell.h3index())),
+        );
+        for column in columns {
+            feature_properties.insert(
+                column.name().to_string(),
+                anyvalue_to_json(column.get(position)),
+            );
+        }
+        let polygon = antimeridian_shifted_polygon(&cell)?;
+        let feature = Feature {
+            bbox: None,
+            geometry: Some(geojson::Geometry::new(geojson::Value::from(&polygon))),
+            id: None,
+            properties: Some(feature_properties),
+            foreign_members: None,
+        };
+
+        if !is_first {
+            writer.write_all(b",")?;
+        }
+        is_first = false;
+        write!(writer, "{}", feature)?;
+    }
+    writer.write_all(b"]}")?;
+    Ok(())
+}
+
+/// Build a GeoJSON `FeatureCollection` from `cells`. See [`write_cells_geojson`].
+pub fn cells_to_geojson_string<C>(
+    cells: &C,
+    properties: Option<&DataFrame>,
+) -> Result<String, Error>
+where
+    C: ValidIndexes<H3Cell> + ?Sized,
+{
+    let mut buf = vec![];
+    write_cells_geojson(cells, properties, &mut buf)?;
+    String::from_utf8(buf).map_err(|e| Error::InvalidGeoJson(e.to_string()))
+}
+
+/// The polygon of `cell`. Polygons crossing the antimeridian extend beyond 180 degrees.
+fn antimeridian_shifted_polygon(cell: &H3Cell) -> Result<Polygon<f64>, Error> {
+    let mut polygon = cell.to_polygon()?;
+    let (min_x, max_x) = polygon
+        .exterior()
+        .0
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min_x, max_x), c| {
+            (min_x.min(c.x), max_x.max(c.x))
+        });
+    if max_x - min_x > 180.0 {
+        polygon.exterior_mut(|ring| {
+            for c in ring.0.iter_mut() {
+                if c.x < 0.0 {
+                    c.x += 360.0;
+                }
+            }
+        });
+    }
+    Ok(polygon)
+}
+
+fn anyvalue_to_json(value: AnyValue) -> JsonValue {
+    match value {
+        AnyValue::Null => JsonValue::Null,
+        AnyValue::Boolean(v) => JsonValue::from(v),
+        AnyValue::Utf8(v) => JsonValue::from(v),
+        AnyValue::UInt8(v) => JsonValue::from(v),
+        AnyValue::UInt32(v) => JsonValue::from(v),
+        AnyValue::UInt64(v) => JsonValue::from(v),
+        AnyValue::Int32(v) => JsonValue::from(v),
+        AnyValue::Int64(v) => JsonValue::from(v),
+        AnyValue::Float32(v) => JsonValue::from(v),
+        AnyValue::Float64(v) => JsonValue::from(v),
+        other => JsonValue::from(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::{
+        cells_to_geojson_string, tag_cells_with_geojson, CellPredicate,
+    };
+    use crate::{AsH3CellChunked, Error};
+    use geojson::{GeoJson, JsonValue};
+    use h3ron::{H3Cell, Index, ToCoordinate, ToPolygon};
+    use polars::prelude::{DataFrame, NamedFrom, Series, TakeRandom, UInt64Chunked};
+
+    const COLLECTION: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "properties": {"name": "zone"},
+                "geometry": {"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]]}
+            },
+            {
+                "type": "Feature",
+                "properties": {"name": "zone"},
+                "geometry": {"type": "Polygon", "coordinates": [[[0.5, 0.5], [1.5, 0.5], [1.5, 1.5], [0.5, 1.5], [0.5, 0.5]]]}
+            },
+            {
+                "type": "Feature",
+                "properties": {},
+                "geometry": {"type": "Polygon", "coordinates": [
+                    [[10, 10], [11, 10], [11, 11], [10, 11], [10, 10]],
+                    [[10.3, 10.3], [10.7, 10.3], [10.7, 10.7], [10.3, 10.7], [10.3, 10.3]]
+                ]}
+            }
+        ]
+    }"#;
+
+    fn tag(predicate: CellPredicate) -> DataFrame {
+        let cell =
+            |x: f64, y: f64| Some(H3Cell::from_coordinate((x, y).into(), 7).unwrap().h3index());
+        let ca = UInt64Chunked::new(
+            "",
+            &[
+                cell(0.75, 0.75),  // both zones
+                cell(0.2, 0.2),    // first zone
+                cell(10.1, 10.1),  // polygon with hole
+                cell(10.5, 10.5),  // within the hole
+                cell(-20.0, 20.0), // outside
+                None,
+            ],
+        );
+        tag_cells_with_geojson(&ca.h3cell(), COLLECTION, predicate, Some("name")).unwrap()
+    }
+
+    fn column(df: &DataFrame, name: &str) -> Vec<Option<bool>> {
+        let ca = df.column(name).unwrap().bool().unwrap();
+        (0..ca.len()).map(|i| ca.get(i)).collect()
+    }
+
+    #[test]
+    fn tag_overlapping_and_holes() {
+        for predicate in [
+            CellPredicate::CentroidWithin,
+            CellPredicate::Intersects,
+            CellPredicate::Within,
+        ] {
+            let df = tag(predicate);
+            assert_eq!(df.get_column_names(), vec!["zone", "zone_1", "feature_2"]);
+            assert_eq!(
+                column(&df, "zone"),
+                vec![
+                    Some(true),
+                    Some(true),
+                    Some(false),
+                    Some(false),
+                    Some(false),
+                    None
+                ]
+            );
+            assert_eq!(
+                column(&df, "zone_1"),
+                vec![
+                    Some(true),
+                    Some(false),
+                    Some(false),
+                    Some(false),
+                    Some(false),
+                    None
+                ]
+            );
+            assert_eq!(
+                column(&df, "feature_2"),
+                vec![
+                    Some(false),
+                    Some(false),
+                    Some(true),
+                    Some(false),
+                    Some(false),
+                    None
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn intersects_cells_on_the_border() {
+        // a cell with its centroid outside of the polygon, but overlapping it
+        let cell = H3Cell::from_coordinate((1.0, 0.2).into(), 7)
+            .unwrap()
+            .grid_disk(1)
+            .unwrap()
+            .iter()
+            .find(|cell| {
+                cell.to_coordinate().unwrap().x > 1.0
+                    && cell
+                        .to_polygon()
+                        .unwrap()
+                        .exterior()
+                        .points()
+                        .any(|p| p.x() < 1.0)
+            })
+            .unwrap();
+        let ca = UInt64Chunked::new("", &[cell.h3index()]);
+
+        let within = tag_cells_with_geojson(
+            &ca.h3cell(),
+            COLLECTION,
+            CellPredicate::CentroidWithin,
+            None,
+        )
+        .unwrap();
+        assert_eq!(within.get_column_names()[0], "feature_0");
+        assert_eq!(column(&within, "feature_0"), vec![Some(false)]);
+
+        let intersects =
+            tag_cells_with_geojson(&ca.h3cell(), COLLECTION, CellPredicate::Intersects, None)
+                .unwrap();
+        assert_eq!(column(&intersects, "feature_0"), vec![Some(true)]);
+
+        let within =
+            tag_cells_with_geojson(&ca.h3cell(), COLLECTION, CellPredicate::Within, None).unwrap();
+        assert_eq!(column(&within, "feature_0"), vec![Some(false)]);
+    }
+
+    #[test]
+    fn invalid_geojson() {
+        let ca = UInt64Chunked::new("", &[0_u64]);
+        assert!(matches!(
+            tag_cells_with_geojson(&ca.h3cell(), "{", CellPredicate::Intersects, None),
+            Err(Error::InvalidGeoJson(_))
+        ));
+
+        let without_geometry = r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {}, "geometry": {"type": "Point", "coordinates": [1, 2]}},
+            {"type": "Feature", "properties": {}, "geometry": null}
+        ]}"#;
+        assert!(matches!(
+            tag_cells_with_geojson(
+                &ca.h3cell(),
+                without_geometry,
+                CellPredicate::Intersects,
+                None
+            ),
+            Err(Error::InvalidGeoJsonFeature(1, _))
+        ));
+    }
+
+    #[test]
+    fn cells_to_feature_collection() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+        let dateline_cell = H3Cell::from_coordinate((180.0, 10.0).into(), 2).unwrap();
+        let ca = UInt64Chunked::new(
+            "",
+            &[Some(cell.h3index()), None, Some(dateline_cell.h3index())],
+        );
+        let properties = DataFrame::new(vec![
+            Series::new("value", &[Some(1.5_f64), Some(2.0), None]),
+            Series::new("name", &["a", "b", "c"]),
+        ])
+        .unwrap();
+
+        let collection = match cells_to_geojson_string(&ca.h3cell(), Some(&properties))
+            .unwrap()
+            .parse::<GeoJson>()
+            .unwrap()
+        {
+            GeoJson::FeatureCollection(collection) => collection,
+            _ => panic!("expected a FeatureCollection"),
+        };
+        assert_eq!(collection.features.len(), 2);
+
+        let feature = &collection.features[0];
+        assert_eq!(
+            feature.property("h3index"),
+            Some(&JsonValue::from(format!("{:x}", cell.h3index())))
+        );
+        assert_eq!(feature.property("value"), Some(&JsonValue::from(1.5)));
+        assert_eq!(feature.property("name"), Some(&JsonValue::from("a")));
+        let polygon =
+            geo_types::Polygon::<f64>::try_from(feature.geometry.as_ref().unwrap().value.clone())
+                .unwrap();
+        // coordinates in lon/lat order
+        let expected = cell.to_polygon().unwrap();
+        assert_eq!(polygon.exterior().0.len(), expected.exterior().0.len());
+        for (c, c_expected) in polygon
+            .exterior()
+            .0
+            .iter()
+            .zip(expected.exterior().0.iter())
+        {
+            assert!((c.x - c_expected.x).abs() < 1e-12);
+            assert!((c.y - c_expected.y).abs() < 1e-12);
+        }
+
+        let feature = &collection.features[1];
+        assert_eq!(feature.property("value"), Some(&JsonValue::Null));
+        assert_eq!(feature.property("name"), Some(&JsonValue::from("c")));
+        let polygon =
+            geo_types::Polygon::<f64>::try_from(feature.geometry.as_ref().unwrap().value.clone())
+                .unwrap();
+        let xs: Vec<_> = polygon.exterior().0.iter().map(|c| c.x).collect();
+        let min_x = xs.iter().copied().fold(f64::INFINITY, f64::min);
+        let max_x = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        assert!(max_x - min_x < 180.0);
+        assert!(max_x > 180.0);
+    }
+
+    #[test]
+    fn cells_to_geojson_length_mismatch() {
+        let ca = UInt64Chunked::new("", &[0_u64, 1_u64]);
+        let properties = DataFrame::new(vec![Series::new("value", &[1_u32])]).unwrap();
+        assert!(matches!(
+            cells_to_geojson_string(&ca.h3cell(), Some(&properties)),
+            Err(Error::LengthMismatch(2, 1))
+        ));
+
+        let empty = UInt64Chunked::new("", &[0_u64]);
+        assert_eq!(
+            cells_to_geojson_string(&empty.h3cell(), None).unwrap(),
+            r#"{"type":"FeatureCollection","features":[]}"#
+        );
+    }
+}
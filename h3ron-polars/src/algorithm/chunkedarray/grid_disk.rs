@@ -1,7 +1,7 @@
 use crate::algorithm::chunkedarray::util::list_map_cells;
-use crate::{Error, FromIndexIterator, IndexChunked};
-use h3ron::H3Cell;
-use polars_core::prelude::{ListChunked, UInt64Chunked};
+use crate::{Error, FromIndexIterator, ValidIndexes};
+use h3ron::{H3Cell, Index};
+use polars_core::prelude::{DataFrame, ListChunked, NamedFrom, Series, UInt64Chunked};
 
 /// Produces all cells within k distance of the origin cell.
 pub trait H3GridDisk {
@@ -9,10 +9,25 @@ pub trait H3GridDisk {
     ///
     /// k=0 is defined as the origin cell, k=1 is defined as k=0 + all
     /// neighboring cells, and so on.
+    ///
+    /// `null` values and invalid cells result in `null`.
     fn h3_grid_disk(&self, k: u32) -> Result<ListChunked, Error>;
+
+    /// Produces all cells within k distance of the origin cell together with their distance
+    /// to the origin cell.
+    ///
+    /// The returned dataframe is exploded and contains the columns `cell` with the origin cell,
+    /// `neighbor` and `k` with the distance of the neighbor to the origin cell. `null` values and
+    /// invalid cells result in a single row with `null` in the `neighbor` and `k` columns. The
+    /// `cell` column of these rows contains the input value unchanged - including invalid cells -
+    /// so the rows can be joined back to the input.
+    fn h3_grid_disk_distances(&self, k: u32) -> Result<DataFrame, Error>;
 }
 
-impl<'a> H3GridDisk for IndexChunked<'a, H3Cell> {
+impl<T> H3GridDisk for T
+where
+    T: ValidIndexes<H3Cell>,
+{
     fn h3_grid_disk(&self, k: u32) -> Result<ListChunked, Error> {
         list_map_cells(self, |cell| {
             cell.grid_disk(k)
@@ -20,6 +35,37 @@ impl<'a> H3GridDisk for IndexChunked<'a, H3Cell> {
                 .map_err(Error::from)
         })
     }
+
+    fn h3_grid_disk_distances(&self, k: u32) -> Result<DataFrame, Error> {
+        let mut cells = Vec::with_capacity(self.chunked_array().len());
+        let mut neighbors = Vec::with_capacity(self.chunked_array().len());
+        let mut distances = Vec::with_capacity(self.chunked_array().len());
+        for (value, maybe_cell) in self
+            .chunked_array()
+            .into_iter()
+            .zip(self.iter_valid_indexes())
+        {
+            match maybe_cell {
+                Some(cell) => {
+                    for (distance, neighbor) in cell.grid_disk_distances(0, k)? {
+                        cells.push(value);
+                        neighbors.push(Some(neighbor.h3index()));
+                        distances.push(Some(distance));
+                    }
+                }
+                None => {
+                    cells.push(value);
+                    neighbors.push(None);
+                    distances.push(None);
+                }
+            }
+        }
+        Ok(DataFrame::new(vec![
+            Series::new("cell", cells),
+            Series::new("neighbor", neighbors),
+            Series::new("k", distances),
+        ])?)
+    }
 }
 
 #[cfg(test)]
@@ -73,6 +119,39 @@ mod tests {
         assert_eq!(changed.h3cell().get(0), Some(cell));
     }
 
+    #[test]
+    fn cell_grid_disk_distances() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+        // `from_index_iter` would turn the invalid cell into `null`
+        let ca = UInt64Chunked::from_iter([
+            Some(cell.h3index()),
+            Some(55), // invalid
+            None,
+        ]);
+
+        let df = ca.h3cell().h3_grid_disk_distances(2).unwrap();
+        assert_eq!(df.get_column_names(), vec!["cell", "neighbor", "k"]);
+        assert_eq!(df.height(), 19 + 2);
+
+        let cells = df.column("cell").unwrap().u64().unwrap();
+        let neighbors = df.column("neighbor").unwrap().u64().unwrap();
+        let distances = df.column("k").unwrap().u32().unwrap();
+        for i in 0..19 {
+            assert_eq!(cells.get(i), Some(cell.h3index()));
+            let neighbor = H3Cell::new(neighbors.get(i).unwrap());
+            assert_eq!(
+                distances.get(i),
+                Some(cell.grid_distance_to(neighbor).unwrap() as u32)
+            );
+        }
+        assert_eq!(cells.get(19), Some(55));
+        assert_eq!(neighbors.get(19), None);
+        assert_eq!(distances.get(19), None);
+        assert_eq!(cells.get(20), None);
+        assert_eq!(neighbors.get(20), None);
+        assert_eq!(distances.get(20), None);
+    }
+
     #[test]
     fn cell_grid_disk() {
         let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
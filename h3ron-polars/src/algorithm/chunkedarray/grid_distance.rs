@@ -0,0 +1,246 @@
+use crate::algorithm::chunkedarray::util::par_map_indexes;
+use crate::{Error, IndexChunked, ValidIndexes};
+use h3ron::H3Cell;
+use polars::export::rayon::prelude::*;
+use polars::prelude::{
+    BooleanChunked, Int32Chunked, IntoSeries, NewChunkedArray, StructChunked, UInt32Chunked,
+};
+use polars_core::POOL;
+
+/// number of rows computed by a single task
+const ROWS_PER_TASK: usize = 4096;
+
+/// The grid distances computed by [`grid_distance_with_failures`].
+pub struct GridDistances {
+    /// The grid distance between the cells of each row. `null` when any of both cells is
+    /// `null` or invalid, or when h3 fails to compute the distance.
+    pub distances: UInt32Chunked,
+
+    /// `true` for the rows of valid cells h3 fails to compute the distance for. This happens
+    /// for cells of different resolutions, cells too far apart or on opposite sides of a
+    /// pentagon. `null` when any of both cells is `null` or invalid.
+    pub failed: BooleanChunked,
+}
+
+/// The grid distance between the cells of `origins` and `destinations` at the same position.
+///
+/// Rows for which h3 fails to compute the distance result in `null`, as do `null` values and
+/// invalid cells. Use [`grid_distance_with_failures`] to tell both cases apart. Fails when both
+/// arrays differ in their length.
+///
+/// # Example
+///
+/// ```
+/// use polars::prelude::{TakeRandom, UInt64Chunked};
+/// use h3ron::{H3Cell, Index};
+/// use h3ron_polars::algorithm::chunkedarray::grid_distance;
+/// use h3ron_polars::AsH3CellChunked;
+///
+/// let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 7).unwrap();
+/// let other = cell.grid_ring_unsafe(3).unwrap().iter().next().unwrap();
+/// let origins = UInt64Chunked::from_iter([Some(cell.h3index()), None]);
+/// let destinations = UInt64Chunked::from_iter([Some(other.h3index()), Some(other.h3index())]);
+///
+/// let distances = grid_distance(&origins.h3cell(), &destinations.h3cell()).unwrap();
+/// assert_eq!(distances.get(0), Some(3));
+/// assert_eq!(distances.get(1), None);
+/// ```
+pub fn grid_distance(
+    origins: &IndexChunked<H3Cell>,
+    destinations: &IndexChunked<H3Cell>,
+) -> Result<UInt32Chunked, Error> {
+    Ok(grid_distance_with_failures(origins, destinations)?.distances)
+}
+
+/// The grid distance between the cells of `origins` and `destinations` at the same position,
+/// together with the rows h3 failed to compute the distance for.
+///
+/// Fails when both arrays differ in their length.
+pub fn grid_distance_with_failures(
+    origins: &IndexChunked<H3Cell>,
+    destinations: &IndexChunked<H3Cell>,
+) -> Result<GridDistances, Error> {
+    if origins.len() != destinations.len() {
+        return Err(Error::LengthMismatch(origins.len(), destinations.len()));
+    }
+    let rows: Vec<_> = origins
+        .iter_valid_indexes()
+        .zip(destinations.iter_valid_indexes())
+        .map(|(maybe_origin, maybe_destination)| maybe_origin.zip(maybe_destination))
+        .collect();
+
+    let results: Vec<Option<Option<u32>>> = POOL.install(|| {
+        rows.par_chunks(ROWS_PER_TASK)
+            .flat_map_iter(|rows_chunk| {
+                rows_chunk.iter().map(|row| {
+                    row.map(|(origin, destination)| {
+                        origin
+                            .grid_distance_to(destination)
+                            .ok()
+                            .map(|distance| distance as u32)
+                    })
+                })
+            })
+            // Ordering is preserved. see https://github.com/rayon-rs/rayon/issues/551
+            .collect()
+    });
+
+    let mut distances = UInt32Chunked::from_iter(results.iter().map(|result| result.flatten()));
+    distances.rename(origins.chunked_array.name());
+    let failed = BooleanChunked::from_iter(
+        results
+            .iter()
+            .map(|result| result.map(|distance| distance.is_none())),
+    );
+    Ok(GridDistances { distances, failed })
+}
+
+/// The local IJ coordinates of `cells` anchored by the cell `anchor` as the fields `i` and
+/// `j` of a struct.
+///
+/// Cells h3 fails to compute the coordinates for - cells too far from the anchor, on the
+/// other side of a pentagon or of a different resolution - result in `null` fields, as do
+/// `null` values and invalid cells. The coordinate space may contain deleted regions or
+/// warping due to pentagonal distortion, see [`H3Cell::to_localij`].
+pub fn to_local_ij(cells: &IndexChunked<H3Cell>, anchor: H3Cell) -> Result<StructChunked, Error> {
+    let coordijs = par_map_indexes(cells, |cell: H3Cell| Ok(cell.to_localij(anchor).ok()))?;
+
+    let i = Int32Chunked::from_slice_options(
+        "i",
+        &coordijs
+            .iter()
+            .map(|coordij| coordij.flatten().map(|coordij| coordij.i))
+            .collect::<Vec<_>>(),
+    );
+    let j = Int32Chunked::from_slice_options(
+        "j",
+        &coordijs
+            .iter()
+            .map(|coordij| coordij.flatten().map(|coordij| coordij.j))
+            .collect::<Vec<_>>(),
+    );
+    Ok(StructChunked::new(
+        cells.chunked_array.name(),
+        &[i.into_series(), j.into_series()],
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::{grid_distance, grid_distance_with_failures, to_local_ij};
+    use crate::{AsH3CellChunked, Error, FromIndexIterator};
+    use h3ron::{H3Cell, Index};
+    use polars::prelude::{TakeRandom, UInt64Chunked};
+
+    #[test]
+    fn grid_distance_null_and_failures() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 7).unwrap();
+        let neighbor = cell.grid_ring_unsafe(1).unwrap().iter().next().unwrap();
+        let origins = UInt64Chunked::from_index_iter([
+            Some(cell),
+            Some(cell),
+            None,
+            Some(H3Cell::new(55)), // invalid
+            Some(cell),
+        ]);
+        let destinations = UInt64Chunked::from_index_iter([
+            Some(neighbor),
+            Some(cell.get_parent(6).unwrap()), // resolution mismatch
+            Some(cell),
+            Some(cell),
+            None,
+        ]);
+
+        let result =
+            grid_distance_with_failures(&origins.h3cell(), &destinations.h3cell()).unwrap();
+        assert_eq!(result.distances.len(), 5);
+        assert_eq!(result.distances.get(0), Some(1));
+        assert_eq!(result.failed.get(0), Some(false));
+        assert_eq!(result.distances.get(1), None);
+        assert_eq!(result.failed.get(1), Some(true));
+        for i in 2..5 {
+            assert_eq!(result.distances.get(i), None);
+            assert_eq!(result.failed.get(i), None);
+        }
+
+        let distances = grid_distance(&origins.h3cell(), &destinations.h3cell()).unwrap();
+        assert!(distances.into_iter().eq(result.distances.into_iter()));
+
+        let shorter = UInt64Chunked::from_index_iter([Some(cell)]);
+        assert!(matches!(
+            grid_distance(&origins.h3cell(), &shorter.h3cell()),
+            Err(Error::LengthMismatch(5, 1))
+        ));
+    }
+
+    #[test]
+    fn grid_distance_around_pentagon() {
+        let pentagon = H3Cell::new(0x8009fffffffffff)
+            .get_children(4)
+            .unwrap()
+            .iter()
+            .find(|cell| cell.is_pentagon())
+            .unwrap();
+        let cells: Vec<_> = pentagon.grid_disk(3).unwrap().iter().collect();
+        let (origins, destinations): (Vec<_>, Vec<_>) = cells
+            .iter()
+            .flat_map(|origin| cells.iter().map(move |destination| (*origin, *destination)))
+            .unzip();
+
+        let result = grid_distance_with_failures(
+            &UInt64Chunked::from_index_iter(origins.iter().copied()).h3cell(),
+            &UInt64Chunked::from_index_iter(destinations.iter().copied()).h3cell(),
+        )
+        .unwrap();
+        assert_eq!(result.distances.len(), origins.len());
+        for (i, (origin, destination)) in origins.iter().zip(destinations.iter()).enumerate() {
+            // valid cells never result in null in the failure report
+            match origin.grid_distance_to(*destination) {
+                Ok(distance) => {
+                    assert_eq!(result.distances.get(i), Some(distance as u32));
+                    assert_eq!(result.failed.get(i), Some(false));
+                }
+                Err(_) => {
+                    assert_eq!(result.distances.get(i), None);
+                    assert_eq!(result.failed.get(i), Some(true));
+                }
+            }
+        }
+        // the distance of each cell to itself
+        for (i, (origin, destination)) in origins.iter().zip(destinations.iter()).enumerate() {
+            if origin == destination {
+                assert_eq!(result.distances.get(i), Some(0));
+            }
+        }
+    }
+
+    #[test]
+    fn local_ij_roundtrip() {
+        let anchor = H3Cell::from_coordinate((4.5, 1.3).into(), 7).unwrap();
+        let disk: Vec<_> = anchor.grid_disk(2).unwrap().iter().collect();
+        let far_away = H3Cell::from_coordinate((120.0, -30.0).into(), 7).unwrap();
+        let ca = UInt64Chunked::from_index_iter(disk.iter().copied().map(Some).chain([
+            None,
+            Some(far_away),
+            Some(anchor.get_parent(5).unwrap()),
+        ]));
+
+        let local_ij = to_local_ij(&ca.h3cell(), anchor).unwrap();
+        assert_eq!(local_ij.len(), disk.len() + 3);
+        let fields = local_ij.fields();
+        assert_eq!(fields[0].name(), "i");
+        assert_eq!(fields[1].name(), "j");
+        let (i, j) = (fields[0].i32().unwrap(), fields[1].i32().unwrap());
+
+        for (pos, cell) in disk.iter().enumerate() {
+            let coordij = cell.to_localij(anchor).unwrap();
+            assert_eq!(i.get(pos), Some(coordij.i));
+            assert_eq!(j.get(pos), Some(coordij.j));
+            assert_eq!(H3Cell::from_localij(anchor, coordij).unwrap(), *cell);
+        }
+        for pos in disk.len()..(disk.len() + 3) {
+            assert_eq!(i.get(pos), None);
+            assert_eq!(j.get(pos), None);
+        }
+    }
+}
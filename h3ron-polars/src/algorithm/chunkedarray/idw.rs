@@ -0,0 +1,226 @@
+use crate::algorithm::chunkedarray::sphere_tree::{
+    chord_to_distance_m, distance_m_to_chord, to_unit_vector, Neighbor, SphereTree,
+};
+use crate::{Error, ValidIndexes};
+use geo_types::Coordinate;
+use h3ron::{H3Cell, ToCoordinate};
+use polars::export::rayon::prelude::*;
+use polars::prelude::Float64Chunked;
+use polars_core::POOL;
+use std::collections::BinaryHeap;
+
+/// number of cells interpolated by a single task
+const CELLS_PER_TASK: usize = 4096;
+
+/// Interpolate values for the centroids of `cells` from scattered `samples` using
+/// inverse distance weighting (IDW) over the `k` nearest samples.
+///
+/// The weight of a sample is `1 / distance^power`, using the haversine distance. Only samples
+/// within `max_distance_m` are used when it is set. A cell at the exact location of a sample
+/// gets the value of that sample. `null` and invalid cells, as well as cells without any
+/// qualifying samples, result in `null`.
+///
+/// The samples are indexed once in a KD-tree. Samples sharing the same distance to a
+/// cell are ordered by their position in `samples`, so the selection of the `k` nearest
+/// samples is deterministic.
+pub fn idw_enrich<C>(
+    cells: &C,
+    samples: &[(Coordinate<f64>, f64)],
+    k: usize,
+    power: f64,
+    max_distance_m: Option<f64>,
+) -> Result<Float64Chunked, Error>
+where
+    C: ValidIndexes<H3Cell> + ?Sized,
+{
+    let tree = SphereTree::new(samples.iter().map(|(coordinate, _)| coordinate));
+    let sample_values: Vec<_> = samples.iter().map(|(_, value)| *value).collect();
+    let max_chord = max_distance_m.map(distance_m_to_chord);
+
+    let cells: Vec<_> = cells.iter_valid_indexes().collect();
+    let values = POOL.install(|| {
+        cells
+            .par_chunks(CELLS_PER_TASK)
+            .map(|cells_chunk| {
+                let mut neighbors = BinaryHeap::with_capacity(k + 1);
+                cells_chunk
+                    .iter()
+                    .map(|maybe_cell| match maybe_cell {
+                        Some(cell) => {
+                            let query = to_unit_vector(&cell.to_coordinate()?);
+                            tree.k_nearest(&query, k, max_chord, &mut neighbors);
+                            Ok(idw_value(&sample_values, neighbors.drain(), power))
+                        }
+                        None => Ok(None),
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    Ok(Float64Chunked::from_iter(values.into_iter().flatten()))
+}
+
+fn idw_value<I>(values: &[f64], neighbors: I, power: f64) -> Option<f64>
+where
+    I: Iterator<Item = Neighbor>,
+{
+    let mut neighbors: Vec<_> = neighbors.collect();
+    if neighbors.is_empty() {
+        return None;
+    }
+    neighbors.sort_unstable();
+
+    // exact hit
+    if neighbors[0].chord == 0.0 {
+        return Some(values[neighbors[0].position]);
+    }
+
+    let mut weighted_sum = 0.0;
+    let mut weight_sum = 0.0;
+    for neighbor in neighbors {
+        let weight = 1.0 / chord_to_distance_m(neighbor.chord).powf(power);
+        weighted_sum += weight * values[neighbor.position];
+        weight_sum += weight;
+    }
+    Some(weighted_sum / weight_sum)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::idw_enrich;
+    use crate::{AsH3CellChunked, FromIndexIterator};
+    use geo::algorithm::haversine_distance::HaversineDistance;
+    use geo_types::{Coordinate, Point};
+    use h3ron::{H3Cell, ToCoordinate};
+    use polars::prelude::{TakeRandom, UInt64Chunked};
+
+    fn cells_fixture() -> Vec<H3Cell> {
+        H3Cell::from_coordinate((12.3, 45.4).into(), 5)
+            .unwrap()
+            .grid_disk(6)
+            .unwrap()
+            .iter()
+            .collect()
+    }
+
+    fn samples_fixture(cells: &[H3Cell]) -> Vec<(Coordinate<f64>, f64)> {
+        cells
+            .iter()
+            .step_by(7)
+            .enumerate()
+            .map(|(i, cell)| {
+                let c = cell.to_coordinate().unwrap();
+                (
+                    Coordinate::from((c.x + 0.013 * (i % 3) as f64, c.y - 0.007)),
+                    (i * 17 % 23) as f64,
+                )
+            })
+            .collect()
+    }
+
+    fn brute_force(
+        cell: &H3Cell,
+        samples: &[(Coordinate<f64>, f64)],
+        k: usize,
+        power: f64,
+        max_distance_m: Option<f64>,
+    ) -> Option<f64> {
+        let centroid = Point::from(cell.to_coordinate().unwrap());
+        let mut distances: Vec<_> = samples
+            .iter()
+            .enumerate()
+            .map(|(i, (c, v))| (centroid.haversine_distance(&Point::from(*c)), i, *v))
+            .filter(|(d, _, _)| max_distance_m.is_none_or(|max| *d <= max))
+            .collect();
+        distances.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        distances.truncate(k);
+        if distances.is_empty() {
+            return None;
+        }
+        if distances[0].0 == 0.0 {
+            return Some(distances[0].2);
+        }
+        let weights: Vec<_> = distances
+            .iter()
+            .map(|(d, _, _)| 1.0 / d.powf(power))
+            .collect();
+        Some(
+            distances
+                .iter()
+                .zip(weights.iter())
+                .map(|((_, _, v), w)| v * w)
+                .sum::<f64>()
+                / weights.iter().sum::<f64>(),
+        )
+    }
+
+    #[test]
+    fn exact_hits_return_sample_values() {
+        let cells = cells_fixture();
+        let samples: Vec<_> = cells
+            .iter()
+            .take(10)
+            .enumerate()
+            .map(|(i, cell)| (cell.to_coordinate().unwrap(), i as f64 * 1.5))
+            .collect();
+        let ca = UInt64Chunked::from_index_iter::<_, H3Cell>(cells.iter().take(10));
+        let values = idw_enrich(&ca.h3cell(), &samples, 4, 2.0, None).unwrap();
+        for (i, value) in values.into_iter().enumerate() {
+            assert_eq!(value, Some(i as f64 * 1.5));
+        }
+    }
+
+    #[test]
+    fn equidistant_samples_return_mean() {
+        let cell = cells_fixture()[0];
+        let c = cell.to_coordinate().unwrap();
+        let samples = vec![
+            (Coordinate::from((c.x, c.y + 0.01)), 4.0),
+            (Coordinate::from((c.x, c.y - 0.01)), 10.0),
+        ];
+        let ca = UInt64Chunked::from_index_iter::<_, H3Cell>([cell]);
+        let value = idw_enrich(&ca.h3cell(), &samples, 2, 1.0, None)
+            .unwrap()
+            .get(0)
+            .unwrap();
+        assert!((value - 7.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn max_distance_produces_nulls() {
+        let cells = cells_fixture();
+        let samples = vec![(cells[0].to_coordinate().unwrap(), 1.0)];
+        let ca = UInt64Chunked::from_index_iter::<_, H3Cell>(cells.iter());
+        let values = idw_enrich(&ca.h3cell(), &samples, 3, 2.0, Some(20_000.0)).unwrap();
+        assert_eq!(values.get(0), Some(1.0));
+        assert!(values.null_count() > 0);
+        assert!(values.null_count() < cells.len());
+        for (cell, value) in cells.iter().zip(&values) {
+            assert_eq!(brute_force(cell, &samples, 3, 2.0, Some(20_000.0)), value);
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let cells = cells_fixture();
+        let samples = samples_fixture(&cells);
+        let mut maybe_cells: Vec<_> = cells.iter().copied().map(Some).collect();
+        maybe_cells.insert(5, None);
+        let ca = UInt64Chunked::from_index_iter::<_, Option<H3Cell>>(maybe_cells.iter());
+
+        for (k, power, max_distance_m) in [(1, 2.0, None), (5, 2.0, None), (8, 1.0, Some(30_000.0))]
+        {
+            let values = idw_enrich(&ca.h3cell(), &samples, k, power, max_distance_m).unwrap();
+            assert_eq!(values.len(), maybe_cells.len());
+            for (maybe_cell, value) in maybe_cells.iter().zip(&values) {
+                let expected = maybe_cell
+                    .and_then(|cell| brute_force(&cell, &samples, k, power, max_distance_m));
+                match (value, expected) {
+                    (Some(v), Some(e)) => assert!((v - e).abs() < 1e-6 * e.abs().max(1.0)),
+                    (v, e) => assert_eq!(v, e),
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,39 @@
+mod area;
 mod cell_clusters;
 mod compact;
+mod coverage;
+mod directed_edge;
+mod edge_length;
+mod external_sort;
+mod focal;
+#[cfg(feature = "geojson")]
+mod geojson;
 mod grid_disk;
+mod grid_distance;
+mod idw;
+mod nearest_join;
+mod polygon;
 mod resolution;
+pub(crate) mod sphere_tree;
 mod util;
 mod valid;
+mod wkb;
 
+pub use area::*;
 pub use cell_clusters::*;
 pub use compact::*;
+pub use coverage::*;
+pub use directed_edge::*;
+pub use edge_length::*;
+pub use external_sort::*;
+pub use focal::*;
+#[cfg(feature = "geojson")]
+pub use geojson::*;
 pub use grid_disk::*;
+pub use grid_distance::*;
+pub use idw::*;
+pub use nearest_join::*;
+pub use polygon::*;
 pub use resolution::*;
 pub use valid::*;
+pub use wkb::*;
@@ -0,0 +1,415 @@
+use crate::algorithm::chunkedarray::sphere_tree::{
+    chord_to_distance_m, distance_m_to_chord, to_unit_vector, SphereTree,
+};
+use crate::{Error, ValidIndexes};
+use h3ron::{H3Cell, ToCoordinate};
+use polars::export::rayon::prelude::*;
+use polars::export::rayon::ThreadPool;
+use polars::prelude::{
+    DataFrame, Float64Chunked, IntoSeries, NewChunkedArray, UInt32Chunked, UInt64Chunked,
+};
+use polars_core::POOL;
+use std::collections::BinaryHeap;
+
+/// number of left cells probed by a single task
+const CELLS_PER_TASK: usize = 4096;
+
+/// The result of [`nearest_k_join`].
+#[derive(Debug, Clone)]
+pub struct NearestKJoin {
+    /// The joined pairs with the columns `left_row_idx` and `right_row_idx` (both `u64`),
+    /// `distance_m` (`f64`) and `rank` (`u32`, starting at 1).
+    pub joined: DataFrame,
+
+    /// number of `null` and invalid cells of the left side which were skipped.
+    pub left_skipped: usize,
+
+    /// number of `null` and invalid cells of the right side which were skipped.
+    pub right_skipped: usize,
+}
+
+/// Join each cell of `left` with its `k` nearest cells of `right`.
+///
+/// The distance is the haversine distance between the centroids of the cells in meters.
+/// Only right cells within `max_distance_m` are joined when it is set. Left cells without
+/// any right cell are omitted from the result, except when `keep_unmatched` is set - then they
+/// are included with a single row having `null` in all columns except `left_row_idx`.
+///
+/// The rows are ordered by the left row and the rank. Right cells sharing the same distance
+/// to a left cell are ranked by their row, so the result is deterministic.
+///
+/// The full product of both sides is never materialized: the centroids of `right` are indexed
+/// once in a KD-tree, which is then probed by the cells of `left` in parallel.
+pub fn nearest_k_join<L, R>(
+    left: &L,
+    right: &R,
+    k: usize,
+    max_distance_m: Option<f64>,
+    keep_unmatched: bool,
+) -> Result<NearestKJoin, Error>
+where
+    L: ValidIndexes<H3Cell> + ?Sized,
+    R: ValidIndexes<H3Cell> + ?Sized,
+{
+    nearest_k_join_in_pool(
+        &POOL,
+        CELLS_PER_TASK,
+        left,
+        right,
+        k,
+        max_distance_m,
+        keep_unmatched,
+    )
+}
+
+fn nearest_k_join_in_pool<L, R>(
+    pool: &ThreadPool,
+    cells_per_task: usize,
+    left: &L,
+    right: &R,
+    k: usize,
+    max_distance_m: Option<f64>,
+    keep_unmatched: bool,
+) -> Result<NearestKJoin, Error>
+where
+    L: ValidIndexes<H3Cell> + ?Sized,
+    R: ValidIndexes<H3Cell> + ?Sized,
+{
+    let mut right_rows = vec![];
+    let mut right_coordinates = vec![];
+    let mut right_skipped = 0;
+    for (row, maybe_cell) in right.iter_valid_indexes().enumerate() {
+        match maybe_cell {
+            Some(cell) => {
+                right_rows.push(row as u64);
+                right_coordinates.push(cell.to_coordinate()?);
+            }
+            None => right_skipped += 1,
+        }
+    }
+    // the positions within the tree are ascending with the right rows, so ties are
+    // ordered by the right row.
+    let tree = SphereTree::new(right_coordinates.iter());
+    let max_chord = max_distance_m.map(distance_m_to_chord);
+
+    let left_cells: Vec<_> = left.iter_valid_indexes().collect();
+    let left_skipped = left_cells.iter().filter(|cell| cell.is_none()).count();
+
+    let chunks = pool.install(|| {
+        left_cells
+            .par_chunks(cells_per_task.max(1))
+            .enumerate()
+            .map(|(chunk_i, cells_chunk)| {
+                let mut neighbors = BinaryHeap::with_capacity(k + 1);
+                let mut pairs = JoinedPairs::default();
+                for (i, maybe_cell) in cells_chunk.iter().enumerate() {
+                    let cell = match maybe_cell {
+                        Some(cell) => cell,
+                        None => continue,
+                    };
+                    let left_row = (chunk_i * cells_per_task.max(1) + i) as u64;
+                    let query = to_unit_vector(&cell.to_coordinate()?);
+                    tree.k_nearest(&query, k, max_chord, &mut neighbors);
+
+                    if neighbors.is_empty() {
+                        if keep_unmatched {
+                            pairs.push(left_row, None, None, None);
+                        }
+                        continue;
+                    }
+                    let mut sorted: Vec<_> = neighbors.drain().collect();
+                    sorted.sort_unstable();
+                    for (rank, neighbor) in sorted.iter().enumerate() {
+                        pairs.push(
+                            left_row,
+                            Some(right_rows[neighbor.position]),
+                            Some(chord_to_distance_m(neighbor.chord)),
+                            Some(rank as u32 + 1),
+                        );
+                    }
+                }
+                Ok(pairs)
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    let mut pairs = JoinedPairs::default();
+    for chunk in chunks {
+        pairs.extend(chunk);
+    }
+    Ok(NearestKJoin {
+        joined: pairs.into_dataframe()?,
+        left_skipped,
+        right_skipped,
+    })
+}
+
+#[derive(Default)]
+struct JoinedPairs {
+    left_row_idx: Vec<u64>,
+    right_row_idx: Vec<Option<u64>>,
+    distance_m: Vec<Option<f64>>,
+    rank: Vec<Option<u32>>,
+}
+
+impl JoinedPairs {
+    fn push(
+        &mut self,
+        left_row: u64,
+        right_row: Option<u64>,
+        distance_m: Option<f64>,
+        rank: Option<u32>,
+    ) {
+        self.left_row_idx.push(left_row);
+        self.right_row_idx.push(right_row);
+        self.distance_m.push(distance_m);
+        self.rank.push(rank);
+    }
+
+    fn extend(&mut self, other: Self) {
+        self.left_row_idx.extend(other.left_row_idx);
+        self.right_row_idx.extend(other.right_row_idx);
+        self.distance_m.extend(other.distance_m);
+        self.rank.extend(other.rank);
+    }
+
+    fn into_dataframe(self) -> Result<DataFrame, Error> {
+        Ok(DataFrame::new(vec![
+            UInt64Chunked::from_vec("left_row_idx", self.left_row_idx).into_series(),
+            UInt64Chunked::from_slice_options("right_row_idx", &self.right_row_idx).into_series(),
+            Float64Chunked::from_slice_options("distance_m", &self.distance_m).into_series(),
+            UInt32Chunked::from_slice_options("rank", &self.rank).into_series(),
+        ])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::nearest_k_join_in_pool;
+    use crate::algorithm::chunkedarray::nearest_k_join;
+    use crate::{AsH3CellChunked, FromIndexIterator};
+    use geo::algorithm::haversine_distance::HaversineDistance;
+    use geo_types::Point;
+    use h3ron::{H3Cell, Index, ToCoordinate};
+    use polars::export::rayon::ThreadPoolBuilder;
+    use polars::prelude::{DataFrame, UInt64Chunked};
+
+    fn left_fixture() -> Vec<Option<H3Cell>> {
+        let mut cells: Vec<_> = H3Cell::from_coordinate((12.3, 45.4).into(), 7)
+            .unwrap()
+            .grid_disk(5)
+            .unwrap()
+            .iter()
+            .map(Some)
+            .collect();
+        cells.insert(3, None);
+        cells.insert(10, Some(H3Cell::new(55))); // invalid
+        cells
+    }
+
+    fn right_fixture() -> Vec<Option<H3Cell>> {
+        let mut cells: Vec<_> = H3Cell::from_coordinate((12.35, 45.42).into(), 8)
+            .unwrap()
+            .grid_disk(12)
+            .unwrap()
+            .iter()
+            .step_by(5)
+            .map(Some)
+            .collect();
+        cells.insert(4, None);
+        // duplicates of an earlier cell are at the same distance to all left cells
+        cells.push(cells[7]);
+        cells.insert(2, cells[7]);
+        cells
+    }
+
+    fn ca(cells: &[Option<H3Cell>]) -> UInt64Chunked {
+        UInt64Chunked::from_index_iter::<_, Option<H3Cell>>(cells.iter())
+    }
+
+    /// rows of (left row, right row, distance, rank)
+    fn brute_force(
+        left: &[Option<H3Cell>],
+        right: &[Option<H3Cell>],
+        k: usize,
+        max_distance_m: Option<f64>,
+    ) -> Vec<(u64, u64, f64, u32)> {
+        let mut rows = vec![];
+        for (left_row, left_cell) in left.iter().enumerate() {
+            // invalid cells are skipped by the join
+            let left_point = match left_cell
+                .filter(|cell| cell.is_valid())
+                .and_then(|cell| cell.to_coordinate().ok())
+            {
+                Some(c) => Point::from(c),
+                None => continue,
+            };
+            let mut distances: Vec<_> = right
+                .iter()
+                .enumerate()
+                .filter_map(|(right_row, right_cell)| {
+                    right_cell.map(|cell| {
+                        let right_point = Point::from(cell.to_coordinate().unwrap());
+                        (left_point.haversine_distance(&right_point), right_row)
+                    })
+                })
+                .filter(|(d, _)| max_distance_m.is_none_or(|max| *d <= max))
+                .collect();
+            distances.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            distances.truncate(k);
+            for (rank, (d, right_row)) in distances.into_iter().enumerate() {
+                rows.push((left_row as u64, right_row as u64, d, rank as u32 + 1));
+            }
+        }
+        rows
+    }
+
+    /// (left row, right row, distance, rank) as read from the joined dataframe
+    type JoinedRow = (Option<u64>, Option<u64>, Option<f64>, Option<u32>);
+
+    fn rows(df: &DataFrame) -> Vec<JoinedRow> {
+        let left = df.column("left_row_idx").unwrap().u64().unwrap();
+        let right = df.column("right_row_idx").unwrap().u64().unwrap();
+        let distance = df.column("distance_m").unwrap().f64().unwrap();
+        let rank = df.column("rank").unwrap().u32().unwrap();
+        left.into_iter()
+            .zip(right)
+            .zip(distance)
+            .zip(rank)
+            .map(|(((l, r), d), rank)| (l, r, d, rank))
+            .collect()
+    }
+
+    fn assert_matches_brute_force(joined: &DataFrame, expected: &[(u64, u64, f64, u32)]) {
+        let rows = rows(joined);
+        assert_eq!(rows.len(), expected.len());
+        for (row, expected_row) in rows.iter().zip(expected.iter()) {
+            assert_eq!(row.0, Some(expected_row.0));
+            assert_eq!(row.1, Some(expected_row.1));
+            assert!((row.2.unwrap() - expected_row.2).abs() < 1e-6 * expected_row.2.max(1.0));
+            assert_eq!(row.3, Some(expected_row.3));
+        }
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let (left, right) = (left_fixture(), right_fixture());
+        for k in [1, 3, 8] {
+            let result =
+                nearest_k_join(&ca(&left).h3cell(), &ca(&right).h3cell(), k, None, false).unwrap();
+            assert_eq!(result.left_skipped, 2);
+            assert_eq!(result.right_skipped, 1);
+            assert_matches_brute_force(&result.joined, &brute_force(&left, &right, k, None));
+        }
+    }
+
+    #[test]
+    fn ties_are_ranked_by_right_row() {
+        let right = right_fixture();
+        assert_eq!(right[2], right[8]);
+        assert_eq!(right[2], right[right.len() - 1]);
+
+        let result = nearest_k_join(
+            &ca(&[right[2]]).h3cell(),
+            &ca(&right).h3cell(),
+            3,
+            None,
+            false,
+        )
+        .unwrap();
+        let rows = rows(&result.joined);
+        assert_eq!(
+            rows.iter().map(|row| row.1).collect::<Vec<_>>(),
+            vec![Some(2), Some(8), Some(right.len() as u64 - 1)]
+        );
+        assert!(rows.iter().all(|row| row.2 == Some(0.0)));
+        assert!(rows.iter().all(|row| row.0 == Some(0)));
+    }
+
+    #[test]
+    fn max_distance_cutoff() {
+        let (left, right) = (left_fixture(), right_fixture());
+        let max_distance_m = Some(600.0);
+        let result = nearest_k_join(
+            &ca(&left).h3cell(),
+            &ca(&right).h3cell(),
+            4,
+            max_distance_m,
+            false,
+        )
+        .unwrap();
+        let expected = brute_force(&left, &right, 4, max_distance_m);
+        assert_matches_brute_force(&result.joined, &expected);
+
+        // some left cells are not within the cutoff of any right cell
+        let matched_left_rows: std::collections::HashSet<_> =
+            expected.iter().map(|row| row.0).collect();
+        let unmatched = left.len() - 2 - matched_left_rows.len();
+        assert!(unmatched > 0);
+        assert!(!matched_left_rows.is_empty());
+
+        let with_unmatched = nearest_k_join(
+            &ca(&left).h3cell(),
+            &ca(&right).h3cell(),
+            4,
+            max_distance_m,
+            true,
+        )
+        .unwrap();
+        let rows = rows(&with_unmatched.joined);
+        assert_eq!(rows.len(), expected.len() + unmatched);
+        let null_rows: Vec<_> = rows.iter().filter(|row| row.1.is_none()).collect();
+        assert_eq!(null_rows.len(), unmatched);
+        for row in null_rows {
+            assert!(!matched_left_rows.contains(&row.0.unwrap()));
+            assert_eq!(row.2, None);
+            assert_eq!(row.3, None);
+        }
+    }
+
+    #[test]
+    fn rank_column() {
+        let (left, right) = (left_fixture(), right_fixture());
+        let result =
+            nearest_k_join(&ca(&left).h3cell(), &ca(&right).h3cell(), 5, None, false).unwrap();
+        let rows = rows(&result.joined);
+        for window in rows.windows(2) {
+            if window[0].0 == window[1].0 {
+                assert_eq!(window[1].3.unwrap(), window[0].3.unwrap() + 1);
+                assert!(window[0].2.unwrap() <= window[1].2.unwrap());
+            } else {
+                assert!(window[0].0 < window[1].0);
+                assert_eq!(window[1].3, Some(1));
+            }
+        }
+    }
+
+    #[test]
+    fn deterministic_across_thread_counts() {
+        let (left, right) = (left_fixture(), right_fixture());
+        let (left_ca, right_ca) = (ca(&left), ca(&right));
+        let results: Vec<_> = [(1, 1000), (4, 7), (3, 1)]
+            .into_iter()
+            .map(|(num_threads, cells_per_task)| {
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(num_threads)
+                    .build()
+                    .unwrap();
+                nearest_k_join_in_pool(
+                    &pool,
+                    cells_per_task,
+                    &left_ca.h3cell(),
+                    &right_ca.h3cell(),
+                    4,
+                    Some(2_000.0),
+                    true,
+                )
+                .unwrap()
+                .joined
+            })
+            .collect();
+        assert!(results[0].height() > 0);
+        assert!(results[0].frame_equal_missing(&results[1]));
+        assert!(results[0].frame_equal_missing(&results[2]));
+    }
+}
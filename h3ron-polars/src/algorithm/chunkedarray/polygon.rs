@@ -0,0 +1,203 @@
+use crate::{Error, ValidIndexes};
+use geo::{BoundingRect, Contains, Intersects, Relate};
+use geo_types::{Polygon, Rect};
+use h3ron::{H3Cell, ToCoordinate, ToPolygon};
+use polars::prelude::{BooleanChunked, NewChunkedArray};
+
+/// The predicate deciding if a cell is related to a geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellPredicate {
+    /// The centroid of the cell is located within the geometry.
+    CentroidWithin,
+
+    /// The polygon of the cell intersects the geometry.
+    Intersects,
+
+    /// The polygon of the cell is located completely within the geometry. Cells
+    /// touching the boundary of the geometry from the inside are still within.
+    Within,
+}
+
+/// Test the contained `H3Cell` values against a polygon.
+pub trait H3PolygonPredicate {
+    /// Evaluate `predicate` for all cells and `polygon`. The holes of the polygon are
+    /// respected. `null` values and invalid cells result in `null`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use geo_types::{LineString, Polygon};
+    /// use polars::prelude::UInt64Chunked;
+    /// use polars_core::prelude::TakeRandom;
+    /// use h3ron::{H3Cell, Index};
+    /// use h3ron_polars::algorithm::chunkedarray::{CellPredicate, H3PolygonPredicate};
+    /// use h3ron_polars::AsH3CellChunked;
+    ///
+    /// let polygon = Polygon::new(
+    ///     LineString::from(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)]),
+    ///     vec![],
+    /// );
+    /// let cell = H3Cell::from_coordinate((0.5, 0.5).into(), 7).unwrap();
+    /// let ca = UInt64Chunked::from_iter([Some(cell.h3index()), None]);
+    ///
+    /// let within = ca
+    ///     .h3cell()
+    ///     .h3_polygon_predicate(&polygon, CellPredicate::Within)
+    ///     .unwrap();
+    /// assert_eq!(within.get(0), Some(true));
+    /// assert_eq!(within.get(1), None);
+    /// ```
+    fn h3_polygon_predicate(
+        &self,
+        polygon: &Polygon,
+        predicate: CellPredicate,
+    ) -> Result<BooleanChunked, Error>;
+}
+
+impl<T> H3PolygonPredicate for T
+where
+    T: ValidIndexes<H3Cell> + ?Sized,
+{
+    fn h3_polygon_predicate(
+        &self,
+        polygon: &Polygon,
+        predicate: CellPredicate,
+    ) -> Result<BooleanChunked, Error> {
+        let polygon_rect = polygon.bounding_rect();
+        let values = self
+            .iter_valid_indexes()
+            .map(|maybe_cell| {
+                maybe_cell
+                    .map(|cell| cell_predicate(&cell, polygon, polygon_rect.as_ref(), predicate))
+                    .transpose()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(BooleanChunked::from_slice_options(
+            self.chunked_array().name(),
+            &values,
+        ))
+    }
+}
+
+fn cell_predicate(
+    cell: &H3Cell,
+    polygon: &Polygon,
+    polygon_rect: Option<&Rect>,
+    predicate: CellPredicate,
+) -> Result<bool, Error> {
+    let polygon_rect = match polygon_rect {
+        Some(rect) => rect,
+        None => return Ok(false), // empty polygon
+    };
+    match predicate {
+        CellPredicate::CentroidWithin => Ok(polygon.contains(&cell.to_coordinate()?)),
+        CellPredicate::Intersects | CellPredicate::Within => {
+            let cell_polygon = cell.to_polygon()?;
+            if !cell_polygon
+                .bounding_rect()
+                .is_some_and(|rect| rect.intersects(polygon_rect))
+            {
+                return Ok(false);
+            }
+            Ok(if predicate == CellPredicate::Within {
+                polygon.relate(&cell_polygon).is_contains()
+            } else {
+                polygon.intersects(&cell_polygon)
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::{CellPredicate, H3PolygonPredicate};
+    use crate::AsH3CellChunked;
+    use geo_types::{LineString, Polygon};
+    use h3ron::{H3Cell, Index, ToCoordinate};
+    use polars::prelude::{NamedFrom, TakeRandom, UInt64Chunked};
+
+    fn square(min: f64, max: f64) -> LineString {
+        LineString::from(vec![
+            (min, min),
+            (max, min),
+            (max, max),
+            (min, max),
+            (min, min),
+        ])
+    }
+
+    /// cells straddling the boundaries of the polygon. The centroids of these cells may be
+    /// located on either side of the boundary.
+    fn straddling_cells() -> [H3Cell; 2] {
+        [
+            H3Cell::from_coordinate((1.0, 0.5).into(), 7).unwrap(), // outer boundary
+            H3Cell::from_coordinate((0.3, 0.5).into(), 7).unwrap(), // boundary of the hole
+        ]
+    }
+
+    fn predicate_values(predicate: CellPredicate) -> Vec<Option<bool>> {
+        let polygon = Polygon::new(square(0.0, 1.0), vec![square(0.3, 0.7)]);
+        let cell =
+            |x: f64, y: f64| Some(H3Cell::from_coordinate((x, y).into(), 7).unwrap().h3index());
+        let [outer_boundary, hole_boundary] = straddling_cells();
+        let ca = UInt64Chunked::new(
+            "",
+            &[
+                cell(0.15, 0.15), // inside
+                cell(0.5, 0.5),   // within the hole
+                Some(outer_boundary.h3index()),
+                Some(hole_boundary.h3index()),
+                cell(-20.0, 20.0), // outside
+                Some(55),          // invalid
+                None,
+            ],
+        );
+        let result = ca
+            .h3cell()
+            .h3_polygon_predicate(&polygon, predicate)
+            .unwrap();
+        assert_eq!(result.len(), ca.len());
+        (0..result.len()).map(|i| result.get(i)).collect()
+    }
+
+    #[test]
+    fn polygon_predicates() {
+        let [outer_boundary, hole_boundary] = straddling_cells();
+        assert_eq!(
+            predicate_values(CellPredicate::CentroidWithin),
+            vec![
+                Some(true),
+                Some(false),
+                Some(outer_boundary.to_coordinate().unwrap().x < 1.0),
+                Some(hole_boundary.to_coordinate().unwrap().x < 0.3),
+                Some(false),
+                None,
+                None
+            ]
+        );
+        assert_eq!(
+            predicate_values(CellPredicate::Intersects),
+            vec![
+                Some(true),
+                Some(false),
+                Some(true),
+                Some(true),
+                Some(false),
+                None,
+                None
+            ]
+        );
+        assert_eq!(
+            predicate_values(CellPredicate::Within),
+            vec![
+                Some(true),
+                Some(false),
+                Some(false),
+                Some(false),
+                Some(false),
+                None,
+                None
+            ]
+        );
+    }
+}
@@ -1,9 +1,10 @@
-use crate::algorithm::chunkedarray::util::list_map_cells;
-use crate::{Error, FromIndexIterator, IndexChunked, IndexValue};
+use crate::algorithm::chunkedarray::util::{list_map_cells, map_cells_to_u64};
+use crate::{Error, FromIndexIterator, IndexChunked, IndexValue, ValidIndexes};
 use h3ron::error::check_valid_h3_resolution;
 use h3ron::iter::change_resolution;
-use h3ron::H3Cell;
+use h3ron::{H3Cell, Index, H3_MAX_RESOLUTION};
 use polars_core::prelude::{ListChunked, UInt64Chunked, UInt8Chunked};
+use std::fmt;
 use std::iter::once;
 
 /// Obtain the H3 Resolutions at the array positions where
@@ -25,17 +26,119 @@ impl<'a, IX: IndexValue> H3Resolution for IndexChunked<'a, IX> {
     }
 }
 
+/// maximum number of example rows per resolution in a [`ResolutionMix`]
+const RESOLUTION_MIX_MAX_EXAMPLES: usize = 5;
+
+/// The resolutions found by [`check_single_resolution`] in an array not consisting of cells
+/// of a single resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolutionMix {
+    /// number of valid cells per resolution, ordered by resolution. Empty when the array
+    /// contains no valid cells at all.
+    pub histogram: Vec<(u8, usize)>,
+
+    /// positions of the first rows of each resolution, ordered by resolution.
+    pub examples: Vec<(u8, Vec<usize>)>,
+}
+
+impl fmt::Display for ResolutionMix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.histogram.is_empty() {
+            return write!(f, "no valid cells found");
+        }
+        write!(f, "cells of multiple resolutions found:")?;
+        for ((resolution, count), (_, examples)) in self.histogram.iter().zip(self.examples.iter())
+        {
+            write!(
+                f,
+                " r{}: {} cells (rows {:?});",
+                resolution, count, examples
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ResolutionMix {}
+
+/// Check that all valid cells of `cells` are of the same resolution and return it.
+///
+/// `null` values and invalid cells are ignored. Fails with a [`ResolutionMix`] when multiple
+/// resolutions - or no valid cells at all - are found.
+pub fn check_single_resolution(cells: &IndexChunked<H3Cell>) -> Result<u8, ResolutionMix> {
+    let mut counts = [0_usize; H3_MAX_RESOLUTION as usize + 1];
+    let mut examples: [Vec<usize>; H3_MAX_RESOLUTION as usize + 1] = Default::default();
+    for (position, maybe_cell) in cells.iter_indexes_validated().enumerate() {
+        if let Some(Ok(cell)) = maybe_cell {
+            let resolution = cell.resolution() as usize;
+            counts[resolution] += 1;
+            if examples[resolution].len() < RESOLUTION_MIX_MAX_EXAMPLES {
+                examples[resolution].push(position);
+            }
+        }
+    }
+
+    let histogram: Vec<_> = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count > 0)
+        .map(|(resolution, count)| (resolution as u8, *count))
+        .collect();
+    if histogram.len() == 1 {
+        return Ok(histogram[0].0);
+    }
+    Err(ResolutionMix {
+        examples: histogram
+            .iter()
+            .map(|(resolution, _)| {
+                (
+                    *resolution,
+                    std::mem::take(&mut examples[*resolution as usize]),
+                )
+            })
+            .collect(),
+        histogram,
+    })
+}
+
 /// Changes the resolution of the contained `H3Cell` values.
 pub trait H3ChangeResolution {
     /// Changes the resolution of the contained `H3Cell` values
     ///
     /// For each cell of the input array a list of cells is produced. This list may
     /// only contain a single element for cases where `target_resolution` is <= the array
-    /// elements resolution.
+    /// elements resolution. `null` values and invalid cells result in `null`.
     fn h3_change_resolution(&self, target_resolution: u8) -> Result<ListChunked, Error>;
+
+    /// Changes the resolution of the contained `H3Cell` values, returning the parents as
+    /// plain cells instead of single-element lists.
+    ///
+    /// When `target_resolution` is not finer than the finest cell of the array, each cell
+    /// is replaced by its parent at `target_resolution` - cells already at the target resolution
+    /// are passed through unchanged. Cells coarser than `target_resolution` fail with
+    /// [`Error::ResolutionCoarserThanParent`] naming the resolution of the cell.
+    ///
+    /// When `target_resolution` is finer than all cells, the children of each cell are
+    /// returned as a list.
+    ///
+    /// `null` values and invalid cells result in `null`.
+    fn change_resolution(&self, target_resolution: u8) -> Result<ChangedResolution, Error>;
 }
 
-impl<'a> H3ChangeResolution for IndexChunked<'a, H3Cell> {
+/// The result of [`H3ChangeResolution::change_resolution`].
+#[derive(Debug, Clone)]
+pub enum ChangedResolution {
+    /// the parent of each cell
+    Parents(UInt64Chunked),
+
+    /// the children of each cell
+    Children(ListChunked),
+}
+
+impl<T> H3ChangeResolution for T
+where
+    T: ValidIndexes<H3Cell>,
+{
     fn h3_change_resolution(&self, target_resolution: u8) -> Result<ListChunked, Error> {
         check_valid_h3_resolution(target_resolution)?;
         list_map_cells(self, |cell| {
@@ -46,11 +149,60 @@ impl<'a> H3ChangeResolution for IndexChunked<'a, H3Cell> {
             ))
         })
     }
+
+    fn change_resolution(&self, target_resolution: u8) -> Result<ChangedResolution, Error> {
+        check_valid_h3_resolution(target_resolution)?;
+        let finest_resolution = self
+            .iter_valid_indexes()
+            .flatten()
+            .map(|cell| cell.resolution())
+            .max();
+        if finest_resolution.is_none_or(|finest| finest >= target_resolution) {
+            Ok(ChangedResolution::Parents(
+                self.h3_to_parent(target_resolution)?,
+            ))
+        } else {
+            Ok(ChangedResolution::Children(
+                self.h3_change_resolution(target_resolution)?,
+            ))
+        }
+    }
+}
+
+/// Obtain the parents of the contained `H3Cell` values.
+pub trait H3ToParent {
+    /// The parent of each cell at `parent_resolution`. `null` values and invalid cells result in `null`.
+    ///
+    /// Fails with [`Error::ResolutionCoarserThanParent`] when a cell is of a coarser resolution
+    /// than `parent_resolution`.
+    fn h3_to_parent(&self, parent_resolution: u8) -> Result<UInt64Chunked, Error>;
+}
+
+impl<T> H3ToParent for T
+where
+    T: ValidIndexes<H3Cell>,
+{
+    fn h3_to_parent(&self, parent_resolution: u8) -> Result<UInt64Chunked, Error> {
+        check_valid_h3_resolution(parent_resolution)?;
+        map_cells_to_u64(self, |cell| {
+            if cell.resolution() < parent_resolution {
+                Err(Error::ResolutionCoarserThanParent(
+                    cell.resolution(),
+                    parent_resolution,
+                ))
+            } else {
+                Ok(cell.get_parent(parent_resolution)?.h3index())
+            }
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::algorithm::{H3ChangeResolution, H3Resolution};
+    use crate::algorithm::{
+        check_single_resolution, ChangedResolution, H3ChangeResolution, H3Resolution, H3ToParent,
+    };
+    use crate::Error;
     use crate::{AsH3CellChunked, FromIndexIterator, NamedFromIndexes};
     use h3ron::{H3Cell, Index};
     use polars_core::prelude::{ChunkExplode, TakeRandom, UInt64Chunked};
@@ -72,6 +224,37 @@ mod tests {
         assert_eq!(resolution_ca.get(2), None);
     }
 
+    #[test]
+    fn single_resolution() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+        let ca = UInt64Chunked::from_index_iter([Some(cell), None, Some(H3Cell::new(55))]);
+        assert_eq!(check_single_resolution(&ca.h3cell()), Ok(6));
+
+        let mix = check_single_resolution(
+            &UInt64Chunked::from_index_iter(Vec::<Option<H3Cell>>::new()).h3cell(),
+        )
+        .unwrap_err();
+        assert!(mix.histogram.is_empty());
+    }
+
+    #[test]
+    fn resolution_mix() {
+        let cells: Vec<_> = (0..10)
+            .map(|i| {
+                H3Cell::from_coordinate((4.5 + i as f64, 1.3).into(), if i < 7 { 6 } else { 8 })
+                    .unwrap()
+            })
+            .collect();
+        let ca = UInt64Chunked::from_index_iter(cells.iter().map(|cell| Some(*cell)));
+        let mix = check_single_resolution(&ca.h3cell()).unwrap_err();
+        assert_eq!(mix.histogram, vec![(6, 7), (8, 3)]);
+        assert_eq!(
+            mix.examples,
+            vec![(6, vec![0, 1, 2, 3, 4]), (8, vec![7, 8, 9])]
+        );
+        assert!(mix.to_string().contains("r8: 3 cells"));
+    }
+
     #[test]
     fn cell_change_resolution_to_child() {
         let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
@@ -83,4 +266,68 @@ mod tests {
         let exploded = changed.explode().unwrap().unique().unwrap();
         assert_eq!(exploded.len(), 7);
     }
+
+    #[test]
+    fn cell_to_parent() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+        let ca = UInt64Chunked::from_index_iter([Some(cell), Some(H3Cell::new(55)), None]);
+
+        let parents = ca.h3cell().h3_to_parent(4).unwrap();
+        assert_eq!(parents.len(), 3);
+        assert_eq!(parents.get(0), Some(cell.get_parent(4).unwrap().h3index()));
+        assert_eq!(parents.get(1), None);
+        assert_eq!(parents.get(2), None);
+
+        assert!(matches!(
+            ca.h3cell().h3_to_parent(7),
+            Err(Error::ResolutionCoarserThanParent(6, 7))
+        ));
+    }
+
+    #[test]
+    fn change_resolution_mixed() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 10).unwrap();
+        let ca = UInt64Chunked::from_index_iter([
+            Some(cell),
+            Some(cell.get_parent(9).unwrap()),
+            Some(cell.get_parent(7).unwrap()),
+            Some(H3Cell::new(55)), // invalid
+            None,
+        ]);
+
+        match ca.h3cell().change_resolution(7).unwrap() {
+            ChangedResolution::Parents(parents) => {
+                let expected = cell.get_parent(7).unwrap().h3index();
+                assert_eq!(parents.len(), 5);
+                assert_eq!(parents.get(0), Some(expected));
+                assert_eq!(parents.get(1), Some(expected));
+                // already at the target resolution
+                assert_eq!(parents.get(2), Some(expected));
+                assert_eq!(parents.get(3), None);
+                assert_eq!(parents.get(4), None);
+            }
+            ChangedResolution::Children(_) => panic!("expected parents"),
+        }
+
+        // the res 7 cell is coarser than the target resolution
+        assert!(matches!(
+            ca.h3cell().change_resolution(8),
+            Err(Error::ResolutionCoarserThanParent(7, 8))
+        ));
+
+        match ca.h3cell().change_resolution(11).unwrap() {
+            ChangedResolution::Children(children) => {
+                assert_eq!(children.len(), 5);
+                let num_children: Vec<_> = children
+                    .into_iter()
+                    .map(|maybe_list| maybe_list.map(|list| list.len()))
+                    .collect();
+                assert_eq!(
+                    num_children,
+                    vec![Some(7), Some(49), Some(2401), None, None]
+                );
+            }
+            ChangedResolution::Parents(_) => panic!("expected children"),
+        }
+    }
 }
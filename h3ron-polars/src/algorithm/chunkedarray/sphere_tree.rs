@@ -0,0 +1,161 @@
+//! KD-tree of coordinates on the unit sphere for the nearest neighbor searches of the
+//! algorithms of this crate.
+use geo_types::Coordinate;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// mean earth radius in meters, as used by the haversine distance of the `geo` crate.
+pub(crate) const MEAN_EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// position on the unit sphere. The euclidean distance between these vectors - the chord -
+/// is monotonic to the great circle distance.
+pub(crate) fn to_unit_vector(coordinate: &Coordinate<f64>) -> [f64; 3] {
+    let (lat, lon) = (coordinate.y.to_radians(), coordinate.x.to_radians());
+    [lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()]
+}
+
+fn chord_length(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// the chord length corresponding to the great circle distance `distance_m`.
+pub(crate) fn distance_m_to_chord(distance_m: f64) -> f64 {
+    let half_angle = (distance_m / (2.0 * MEAN_EARTH_RADIUS_M)).min(std::f64::consts::FRAC_PI_2);
+    2.0 * half_angle.sin()
+}
+
+/// the great circle distance corresponding to the chord length `chord`.
+pub(crate) fn chord_to_distance_m(chord: f64) -> f64 {
+    2.0 * MEAN_EARTH_RADIUS_M * (chord / 2.0).min(1.0).asin()
+}
+
+/// A candidate point ordered by the distance and the position of the point.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Neighbor {
+    pub chord: f64,
+    pub position: usize,
+}
+
+impl Eq for Neighbor {}
+
+impl PartialOrd for Neighbor {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbor {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.chord
+            .total_cmp(&other.chord)
+            .then_with(|| self.position.cmp(&other.position))
+    }
+}
+
+/// Static KD-tree of points on the unit sphere. The tree is stored implicitly: the node
+/// of each subrange of `nodes` is located at its middle.
+pub(crate) struct SphereTree {
+    points: Vec<[f64; 3]>,
+
+    /// positions of the points
+    nodes: Vec<usize>,
+}
+
+impl SphereTree {
+    pub fn new<'a, I>(coordinates: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Coordinate<f64>>,
+    {
+        let points: Vec<_> = coordinates.into_iter().map(to_unit_vector).collect();
+        let mut nodes: Vec<_> = (0..points.len()).collect();
+        build(&points, &mut nodes, 0);
+        Self { points, nodes }
+    }
+
+    /// collect the `k` nearest points with a chord length not exceeding `max_chord` into
+    /// the max-heap `neighbors`.
+    ///
+    /// Points sharing the same distance to `query` are ordered by their position, so the
+    /// selection is deterministic.
+    pub fn k_nearest(
+        &self,
+        query: &[f64; 3],
+        k: usize,
+        max_chord: Option<f64>,
+        neighbors: &mut BinaryHeap<Neighbor>,
+    ) {
+        neighbors.clear();
+        if k > 0 {
+            self.search(query, k, max_chord, 0, self.nodes.len(), 0, neighbors);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn search(
+        &self,
+        query: &[f64; 3],
+        k: usize,
+        max_chord: Option<f64>,
+        start: usize,
+        end: usize,
+        depth: usize,
+        neighbors: &mut BinaryHeap<Neighbor>,
+    ) {
+        if start >= end {
+            return;
+        }
+        let mid = start + (end - start) / 2;
+        let position = self.nodes[mid];
+        let point = &self.points[position];
+
+        let candidate = Neighbor {
+            chord: chord_length(query, point),
+            position,
+        };
+        if max_chord.is_none_or(|max_chord| candidate.chord <= max_chord) {
+            if neighbors.len() < k {
+                neighbors.push(candidate);
+            } else if neighbors.peek().is_some_and(|worst| &candidate < worst) {
+                neighbors.pop();
+                neighbors.push(candidate);
+            }
+        }
+
+        let axis = depth % 3;
+        let diff = query[axis] - point[axis];
+        let (near, far) = if diff < 0.0 {
+            ((start, mid), (mid + 1, end))
+        } else {
+            ((mid + 1, end), (start, mid))
+        };
+        self.search(query, k, max_chord, near.0, near.1, depth + 1, neighbors);
+
+        // the far side may contain points at an equal distance, which still may win due to
+        // their position.
+        let bound = if neighbors.len() < k {
+            max_chord.unwrap_or(f64::INFINITY)
+        } else {
+            neighbors.peek().map_or(f64::INFINITY, |worst| worst.chord)
+        };
+        if diff.abs() <= bound {
+            self.search(query, k, max_chord, far.0, far.1, depth + 1, neighbors);
+        }
+    }
+}
+
+/// arrange `nodes` as an implicit KD-tree.
+fn build(points: &[[f64; 3]], nodes: &mut [usize], depth: usize) {
+    if nodes.len() <= 1 {
+        return;
+    }
+    let axis = depth % 3;
+    let mid = nodes.len() / 2;
+    nodes.select_nth_unstable_by(mid, |a, b| {
+        points[*a][axis]
+            .total_cmp(&points[*b][axis])
+            .then_with(|| a.cmp(b))
+    });
+    let (left, right) = nodes.split_at_mut(mid);
+    build(points, left, depth + 1);
+    build(points, &mut right[1..], depth + 1);
+}
@@ -1,17 +1,194 @@
-use crate::{Error, IndexChunked};
+use crate::{Error, IndexValue, ValidIndexes};
 use h3ron::H3Cell;
-use polars_core::prelude::{IntoSeries, ListChunked, UInt64Chunked};
+use polars::export::rayon::prelude::*;
+use polars_core::prelude::{
+    DataType, Float64Chunked, IntoSeries, ListBuilderTrait, ListChunked,
+    ListPrimitiveChunkedBuilder, UInt64Chunked, UInt8Type,
+};
+use polars_core::POOL;
 
-#[inline]
-pub(crate) fn list_map_cells<F>(cc: &IndexChunked<H3Cell>, map_fn: F) -> Result<ListChunked, Error>
+/// number of indexes mapped by a single task
+const INDEXES_PER_TASK: usize = 4096;
+
+/// Map each valid index using `map_fn` in parallel on the polars thread pool.
+///
+/// The indexes are validated within the parallel tasks. The order of the output matches the order of `ic`. `null` and invalid indexes result in `None`.
+/// The first error returned by `map_fn` - in the order the tasks complete - aborts the mapping.
+pub(crate) fn par_map_indexes<C, IX, F, T>(ic: &C, map_fn: F) -> Result<Vec<Option<T>>, Error>
+where
+    C: ValidIndexes<IX> + ?Sized,
+    IX: IndexValue + Send + Sync,
+    F: Fn(IX) -> Result<T, Error> + Sync,
+    T: Send,
+{
+    let h3indexes: Vec<_> = ic.chunked_array().into_iter().collect();
+    let chunks = POOL.install(|| {
+        h3indexes
+            .par_chunks(INDEXES_PER_TASK)
+            .enumerate()
+            .map(|(chunk_i, h3indexes_chunk)| {
+                let offset = chunk_i * INDEXES_PER_TASK;
+                h3indexes_chunk
+                    .iter()
+                    .enumerate()
+                    .map(|(i, maybe_h3index)| {
+                        ic.valid_index_at(offset + i, *maybe_h3index)
+                            .map(&map_fn)
+                            .transpose()
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            // Ordering is preserved. see https://github.com/rayon-rs/rayon/issues/551
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+/// Map each valid cell to a list of cells. `null` and invalid cells result in `null`.
+pub(crate) fn list_map_cells<C, F>(cc: &C, map_fn: F) -> Result<ListChunked, Error>
+where
+    C: ValidIndexes<H3Cell> + ?Sized,
+    F: Fn(H3Cell) -> Result<UInt64Chunked, Error> + Sync,
+{
+    Ok(
+        par_map_indexes(cc, |cell| map_fn(cell).map(|uc| uc.into_series()))?
+            .into_iter()
+            .collect(),
+    )
+}
+
+/// Map each valid cell to a `u64`. `null` and invalid cells result in `null`.
+pub(crate) fn map_cells_to_u64<C, F>(cc: &C, map_fn: F) -> Result<UInt64Chunked, Error>
+where
+    C: ValidIndexes<H3Cell> + ?Sized,
+    F: Fn(H3Cell) -> Result<u64, Error> + Sync,
+{
+    Ok(UInt64Chunked::from_iter(par_map_indexes(cc, map_fn)?))
+}
+
+/// Map each valid index to a `f64`. `null` and invalid indexes result in `null`.
+pub(crate) fn map_indexes_to_f64<C, IX, F>(ic: &C, map_fn: F) -> Result<Float64Chunked, Error>
 where
-    F: Fn(H3Cell) -> Result<UInt64Chunked, Error>,
+    C: ValidIndexes<IX> + ?Sized,
+    IX: IndexValue + Send + Sync,
+    F: Fn(IX) -> Result<f64, Error> + Sync,
 {
-    // todo: parallelize
-    cc.iter_indexes_nonvalidated()
-        .map(|opt| match opt {
-            Some(cell) => map_fn(cell).map(|uc| Some(uc.into_series())),
-            None => Ok(None),
+    Ok(Float64Chunked::from_iter(par_map_indexes(ic, map_fn)?))
+}
+
+/// Map each valid index to a list of bytes. `null` and invalid indexes result in `null`.
+pub(crate) fn map_indexes_to_u8_list<C, IX, F>(ic: &C, map_fn: F) -> Result<ListChunked, Error>
+where
+    C: ValidIndexes<IX> + ?Sized,
+    IX: IndexValue + Send + Sync,
+    F: Fn(IX) -> Result<Vec<u8>, Error> + Sync,
+{
+    let values = par_map_indexes(ic, map_fn)?;
+    let mut builder = ListPrimitiveChunkedBuilder::<UInt8Type>::new(
+        ic.chunked_array().name(),
+        values.len(),
+        values.iter().flatten().map(|bytes| bytes.len()).sum(),
+        DataType::UInt8,
+    );
+    for value in values.iter() {
+        builder.append_slice(value.as_deref());
+    }
+    Ok(builder.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::util::{
+        list_map_cells, map_cells_to_u64, map_indexes_to_f64,
+    };
+    use crate::{AsH3CellChunked, Error, FromIndexIterator, ValidIndexes};
+    use h3ron::{H3Cell, Index};
+    use polars_core::prelude::{
+        Float64Chunked, IntoSeries, ListChunked, TakeRandom, UInt64Chunked,
+    };
+
+    /// more than two million cells with some `null` values and invalid cells in between
+    fn synthetic_cells() -> UInt64Chunked {
+        let parent = H3Cell::from_coordinate((12.3, 45.6).into(), 3).unwrap();
+        let children = parent.get_children(10).unwrap();
+        UInt64Chunked::from_iter((0..3).flat_map(|_| {
+            children.iter().enumerate().map(|(i, cell)| match i % 1000 {
+                0 => None,
+                1 => Some(55), // invalid
+                _ => Some(cell.h3index()),
+            })
+        }))
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let ca = synthetic_cells();
+        assert!(ca.len() > 2_000_000);
+        let cells = ca.h3cell();
+
+        let parallel = map_cells_to_u64(&cells, |cell| Ok(cell.get_parent(5)?.h3index())).unwrap();
+        let serial = UInt64Chunked::from_iter(
+            cells
+                .iter_valid_indexes()
+                .map(|maybe_cell| maybe_cell.map(|cell| cell.get_parent(5).unwrap().h3index())),
+        );
+        assert_eq!(parallel.len(), ca.len());
+        assert!(parallel.into_iter().eq(serial.into_iter()));
+
+        // validated using the bitmap of the validated array
+        let parallel =
+            map_cells_to_u64(&cells.validate(), |cell| Ok(cell.get_parent(5)?.h3index())).unwrap();
+        assert!(parallel.into_iter().eq(serial.into_iter()));
+
+        let parallel = map_indexes_to_f64(&cells, |cell: H3Cell| Ok(cell.area_m2()?)).unwrap();
+        let serial = Float64Chunked::from_iter(
+            cells
+                .iter_valid_indexes()
+                .map(|maybe_cell| maybe_cell.map(|cell| cell.area_m2().unwrap())),
+        );
+        assert!(parallel.into_iter().eq(serial.into_iter()));
+
+        let subset = UInt64Chunked::from_iter(ca.into_iter().take(100_000));
+        let parallel = list_map_cells(&subset.h3cell(), |cell| {
+            Ok(UInt64Chunked::from_index_iter(cell.grid_disk(1)?.iter()))
         })
-        .collect::<Result<ListChunked, _>>()
+        .unwrap();
+        let serial: ListChunked = subset
+            .h3cell()
+            .iter_valid_indexes()
+            .map(|maybe_cell| {
+                maybe_cell.map(|cell| {
+                    UInt64Chunked::from_index_iter(cell.grid_disk(1).unwrap().iter()).into_series()
+                })
+            })
+            .collect();
+        assert_eq!(parallel.len(), subset.len());
+        assert!(parallel
+            .into_iter()
+            .zip(serial.into_iter())
+            .all(|pair| match pair {
+                (Some(parallel_series), Some(serial_series)) => {
+                    parallel_series.series_equal(&serial_series)
+                }
+                (None, None) => true,
+                _ => false,
+            }));
+    }
+
+    #[test]
+    fn errors_abort_the_mapping() {
+        let ca = synthetic_cells();
+        let failing_cell = ca.get(123_456).unwrap();
+        let result = map_cells_to_u64(&ca.h3cell(), |cell| {
+            if cell.h3index() == failing_cell {
+                Err(Error::ResolutionCoarserThanParent(cell.resolution(), 0))
+            } else {
+                Ok(cell.h3index())
+            }
+        });
+        assert!(matches!(
+            result,
+            Err(Error::ResolutionCoarserThanParent(10, 0))
+        ));
+    }
 }
@@ -1,6 +1,50 @@
 use crate::{IndexChunked, IndexValue};
+use h3ron::H3_MAX_RESOLUTION;
 use polars_core::prelude::BooleanChunked;
 
+/// The contents of an array of h3 indexes, created by [`H3IsValid::validity_report`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidityReport {
+    /// number of rows of the array
+    pub num_rows: usize,
+
+    /// number of `null` values
+    pub num_null: usize,
+
+    /// number of values which are no valid indexes of the expected type. For example directed
+    /// edges in an array of cells are invalid.
+    pub num_invalid: usize,
+
+    /// number of valid indexes per resolution. The position in the array is the resolution.
+    pub resolution_counts: [usize; H3_MAX_RESOLUTION as usize + 1],
+
+    /// the resolution of the valid indexes when all of them are of the same resolution. `None`
+    /// for arrays of multiple resolutions and arrays without any valid index.
+    pub is_single_resolution: Option<u8>,
+}
+
+impl ValidityReport {
+    /// number of valid indexes
+    pub fn num_valid(&self) -> usize {
+        self.num_rows - self.num_null - self.num_invalid
+    }
+
+    /// true when there are neither `null` values nor invalid indexes
+    pub fn all_valid(&self) -> bool {
+        self.num_null == 0 && self.num_invalid == 0
+    }
+
+    /// the resolutions found together with the number of valid indexes, ordered by resolution
+    pub fn resolution_histogram(&self) -> Vec<(u8, usize)> {
+        self.resolution_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(resolution, count)| (resolution as u8, *count))
+            .collect()
+    }
+}
+
 pub trait H3IsValid {
     ///
     /// # Example
@@ -30,6 +74,32 @@ pub trait H3IsValid {
 
     /// Returns true when all contained h3indexes are valid.
     fn h3_all_valid(&self) -> bool;
+
+    /// Like [`H3IsValid::h3_is_valid`], but `null` values result in `false`, so the result can
+    /// directly be used to filter out the rows without a valid index of the expected type.
+    fn filter_valid(&self) -> BooleanChunked;
+
+    /// Count the `null` values, the invalid indexes and the valid indexes per resolution.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use polars::prelude::UInt64Chunked;
+    /// use h3ron::{H3Cell, Index};
+    /// use h3ron_polars::algorithm::chunkedarray::H3IsValid;
+    /// use h3ron_polars::AsH3CellChunked;
+    ///
+    /// let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+    /// let ca = UInt64Chunked::from_iter([Some(cell.h3index()), Some(55), None]);
+    ///
+    /// let report = ca.h3cell().validity_report();
+    /// assert_eq!(report.num_rows, 3);
+    /// assert_eq!(report.num_null, 1);
+    /// assert_eq!(report.num_invalid, 1);
+    /// assert_eq!(report.resolution_counts[6], 1);
+    /// assert_eq!(report.is_single_resolution, Some(6));
+    /// ```
+    fn validity_report(&self) -> ValidityReport;
 }
 
 impl<'a, IX: IndexValue> H3IsValid for IndexChunked<'a, IX> {
@@ -44,4 +114,90 @@ impl<'a, IX: IndexValue> H3IsValid for IndexChunked<'a, IX> {
         self.iter_indexes_validated()
             .all(|v| matches!(v, Some(Ok(_))))
     }
+
+    fn filter_valid(&self) -> BooleanChunked {
+        BooleanChunked::from_iter(
+            self.iter_indexes_validated()
+                .map(|v| Some(matches!(v, Some(Ok(_))))),
+        )
+    }
+
+    fn validity_report(&self) -> ValidityReport {
+        let mut report = ValidityReport {
+            num_rows: self.len(),
+            ..Default::default()
+        };
+        for v in self.iter_indexes_validated() {
+            match v {
+                Some(Ok(index)) => report.resolution_counts[index.resolution() as usize] += 1,
+                Some(Err(_)) => report.num_invalid += 1,
+                None => report.num_null += 1,
+            }
+        }
+        let mut resolutions = report
+            .resolution_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0);
+        report.is_single_resolution = match (resolutions.next(), resolutions.next()) {
+            (Some((resolution, _)), None) => Some(resolution as u8),
+            _ => None,
+        };
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::H3IsValid;
+    use crate::{AsH3CellChunked, AsH3DirectedEdgeChunked};
+    use h3ron::{H3Cell, Index};
+    use polars::prelude::{ChunkFilter, TakeRandom, UInt64Chunked};
+
+    #[test]
+    fn validity_report_and_filter() {
+        let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+        let edge = cell.directed_edges().unwrap().iter().next().unwrap();
+        let ca = UInt64Chunked::from_iter([
+            Some(cell.h3index()),
+            Some(edge.h3index()), // no cell
+            None,
+            Some(cell.get_parent(4).unwrap().h3index()),
+            Some(55), // invalid
+            Some(cell.h3index()),
+        ]);
+
+        let report = ca.h3cell().validity_report();
+        assert_eq!(report.num_rows, 6);
+        assert_eq!(report.num_null, 1);
+        assert_eq!(report.num_invalid, 2);
+        assert_eq!(report.num_valid(), 3);
+        assert!(!report.all_valid());
+        assert_eq!(report.resolution_histogram(), vec![(4, 1), (6, 2)]);
+        assert_eq!(report.is_single_resolution, None);
+
+        let mask = ca.h3cell().filter_valid();
+        assert_eq!(
+            (&mask).into_iter().collect::<Vec<_>>(),
+            [true, false, false, true, false, true]
+                .into_iter()
+                .map(Some)
+                .collect::<Vec<_>>()
+        );
+        let filtered = ca.filter(&mask).unwrap();
+        assert_eq!(filtered.len(), 3);
+        assert_eq!(filtered.get(1), Some(cell.get_parent(4).unwrap().h3index()));
+
+        // only the edge is valid in an array of edges
+        let edge_report = ca.h3directededge().validity_report();
+        assert_eq!(edge_report.num_invalid, 4);
+        assert_eq!(edge_report.is_single_resolution, Some(6));
+        assert_eq!(ca.h3directededge().filter_valid().get(1), Some(true));
+
+        let empty = UInt64Chunked::from_iter(Vec::<Option<u64>>::new());
+        let empty_report = empty.h3cell().validity_report();
+        assert_eq!(empty_report.num_rows, 0);
+        assert_eq!(empty_report.is_single_resolution, None);
+        assert!(empty_report.all_valid());
+    }
 }
@@ -0,0 +1,486 @@
+use crate::algorithm::chunkedarray::util::map_indexes_to_u8_list;
+use crate::{Error, IndexValue, ValidIndexes};
+use geo::winding_order::Winding;
+use geo_types::{Coordinate, Line, LineString, Polygon};
+use h3ron::to_geo::ToLine;
+use h3ron::{H3Cell, H3DirectedEdge, ToPolygon};
+use polars_core::prelude::ListChunked;
+
+const WKB_LITTLE_ENDIAN: u8 = 1;
+const WKB_LINESTRING: u32 = 2;
+const WKB_POLYGON: u32 = 3;
+const WKB_MULTILINESTRING: u32 = 5;
+const WKB_MULTIPOLYGON: u32 = 6;
+
+/// Encoding of the geometry of an `Index` as [WKB](https://libgeos.org/specifications/wkb/).
+pub trait ToWkbGeometry {
+    /// Encode the geometry as little-endian WKB.
+    ///
+    /// With `split_antimeridian` geometries crossing the antimeridian are split into a
+    /// multi-geometry with one part on each side of it, as their coordinates would
+    /// otherwise wrap around the globe.
+    fn to_wkb_geometry(&self, split_antimeridian: bool) -> Result<Vec<u8>, Error>;
+}
+
+impl ToWkbGeometry for H3Cell {
+    /// The boundary of the cell as a `Polygon` with a counter-clockwise exterior ring.
+    ///
+    /// Cells containing a pole are never split.
+    fn to_wkb_geometry(&self, split_antimeridian: bool) -> Result<Vec<u8>, Error> {
+        let mut ring = self.to_polygon()?.into_inner().0;
+        ring.make_ccw_winding();
+
+        let mut buf = Vec::with_capacity(wkb_polygon_size(ring.0.len()));
+        match split_antimeridian
+            .then(|| split_ring_at_antimeridian(&ring))
+            .flatten()
+        {
+            Some(parts) => {
+                write_header(&mut buf, WKB_MULTIPOLYGON);
+                write_u32(&mut buf, parts.len());
+                for part in parts {
+                    write_polygon(&mut buf, &Polygon::new(part, vec![]));
+                }
+            }
+            None => write_polygon(&mut buf, &Polygon::new(ring, vec![])),
+        }
+        Ok(buf)
+    }
+}
+
+impl ToWkbGeometry for H3DirectedEdge {
+    /// The edge as a `LineString` from the origin to the destination cell.
+    fn to_wkb_geometry(&self, split_antimeridian: bool) -> Result<Vec<u8>, Error> {
+        let line = self.to_line()?;
+        let mut buf = vec![];
+        match split_antimeridian
+            .then(|| split_line_at_antimeridian(&line))
+            .flatten()
+        {
+            Some(parts) => {
+                write_header(&mut buf, WKB_MULTILINESTRING);
+                write_u32(&mut buf, parts.len());
+                for part in parts {
+                    write_linestring(&mut buf, &part);
+                }
+            }
+            None => write_linestring(&mut buf, &LineString::from(line)),
+        }
+        Ok(buf)
+    }
+}
+
+/// WKB-encoded geometries of the contained `Index` values.
+pub trait H3ToWkb<IX: IndexValue + ToWkbGeometry> {
+    /// The WKB-encoded geometry of each index - polygons for cells, linestrings for
+    /// directed edges. Each geometry is a list of `u8` bytes. `null` values and invalid
+    /// indexes result in `null`.
+    ///
+    /// See [`ToWkbGeometry::to_wkb_geometry`] for `split_antimeridian`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use polars::prelude::UInt64Chunked;
+    /// use polars_core::prelude::TakeRandom;
+    /// use h3ron::{H3Cell, Index};
+    /// use h3ron_polars::algorithm::chunkedarray::H3ToWkb;
+    /// use h3ron_polars::AsH3CellChunked;
+    ///
+    /// let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+    /// let ca = UInt64Chunked::from_iter([Some(cell.h3index()), Some(55), None]);
+    ///
+    /// let wkb = ca.h3cell().to_wkb(false).unwrap();
+    /// assert_eq!(wkb.len(), 3);
+    /// assert!(wkb.get(0).is_some());
+    /// assert!(wkb.get(1).is_none());
+    /// assert!(wkb.get(2).is_none());
+    /// ```
+    fn to_wkb(&self, split_antimeridian: bool) -> Result<ListChunked, Error>;
+}
+
+impl<T, IX> H3ToWkb<IX> for T
+where
+    T: ValidIndexes<IX>,
+    IX: IndexValue + ToWkbGeometry + Send + Sync,
+{
+    fn to_wkb(&self, split_antimeridian: bool) -> Result<ListChunked, Error> {
+        map_indexes_to_u8_list(self, |index: IX| index.to_wkb_geometry(split_antimeridian))
+    }
+}
+
+/// coordinates with a difference in longitude of more than 180 degrees are located on
+/// different sides of the antimeridian.
+fn crosses_antimeridian(a: &Coordinate, b: &Coordinate) -> bool {
+    (a.x - b.x).abs() > 180.0
+}
+
+/// longitude shifted to `[0, 360)`
+fn shift_east(coord: Coordinate) -> Coordinate {
+    if coord.x < 0.0 {
+        Coordinate {
+            x: coord.x + 360.0,
+            y: coord.y,
+        }
+    } else {
+        coord
+    }
+}
+
+/// the intersection of the segment from `a` to `b` with the meridian at `x`.
+fn intersect_meridian(a: &Coordinate, b: &Coordinate, x: f64) -> Coordinate {
+    let t = (x - a.x) / (b.x - a.x);
+    Coordinate {
+        x,
+        y: a.y + t * (b.y - a.y),
+    }
+}
+
+/// split a closed ring crossing the antimeridian twice into one ring on each side.
+///
+/// `None` when the ring does not cross the antimeridian or contains a pole - crossing the
+/// antimeridian only once.
+fn split_ring_at_antimeridian(ring: &LineString) -> Option<Vec<LineString>> {
+    let num_crossings = ring
+        .0
+        .windows(2)
+        .filter(|w| crosses_antimeridian(&w[0], &w[1]))
+        .count();
+    if num_crossings != 2 {
+        return None;
+    }
+    let shifted: Vec<_> = ring
+        .0
+        .iter()
+        .take(ring.0.len().saturating_sub(1))
+        .map(|coord| shift_east(*coord))
+        .collect();
+
+    let east = clip_at_antimeridian(&shifted, |x| x <= 180.0);
+    let west = clip_at_antimeridian(&shifted, |x| x >= 180.0)
+        .into_iter()
+        .map(|coord| Coordinate {
+            x: coord.x - 360.0,
+            y: coord.y,
+        })
+        .collect();
+    Some(
+        [east, west]
+            .into_iter()
+            .filter(|coords: &Vec<Coordinate>| coords.len() >= 3)
+            .map(|coords| {
+                let mut part = LineString::from(coords);
+                part.close();
+                part.make_ccw_winding();
+                part
+            })
+            .collect(),
+    )
+}
+
+/// clip the - unclosed - ring `coords` with longitudes in `[0, 360)` to the side of the
+/// antimeridian where `inside` holds.
+fn clip_at_antimeridian<F>(coords: &[Coordinate], inside: F) -> Vec<Coordinate>
+where
+    F: Fn(f64) -> bool,
+{
+    let mut clipped = Vec::with_capacity(coords.len() + 2);
+    for (i, current) in coords.iter().enumerate() {
+        let previous = &coords[(i + coords.len() - 1) % coords.len()];
+        match (inside(previous.x), inside(current.x)) {
+            (true, true) => clipped.push(*current),
+            (false, true) => {
+                clipped.push(intersect_meridian(previous, current, 180.0));
+                clipped.push(*current);
+            }
+            (true, false) => clipped.push(intersect_meridian(previous, current, 180.0)),
+            (false, false) => (),
+        }
+    }
+    clipped
+}
+
+/// split a line crossing the antimeridian into one part on each side, keeping the direction.
+fn split_line_at_antimeridian(line: &Line) -> Option<Vec<LineString>> {
+    if !crosses_antimeridian(&line.start, &line.end) {
+        return None;
+    }
+    let crossing = intersect_meridian(&shift_east(line.start), &shift_east(line.end), 180.0);
+    let (start_x, end_x) = if line.start.x > line.end.x {
+        (180.0, -180.0)
+    } else {
+        (-180.0, 180.0)
+    };
+    Some(vec![
+        LineString::from(vec![
+            line.start,
+            Coordinate {
+                x: start_x,
+                y: crossing.y,
+            },
+        ]),
+        LineString::from(vec![
+            Coordinate {
+                x: end_x,
+                y: crossing.y,
+            },
+            line.end,
+        ]),
+    ])
+}
+
+/// size of a single-ring polygon with `num_coords` coordinates in bytes
+const fn wkb_polygon_size(num_coords: usize) -> usize {
+    1 + 4 + 4 + 4 + num_coords * 16
+}
+
+fn write_header(buf: &mut Vec<u8>, geometry_type: u32) {
+    buf.push(WKB_LITTLE_ENDIAN);
+    buf.extend_from_slice(&geometry_type.to_le_bytes());
+}
+
+fn write_u32(buf: &mut Vec<u8>, value: usize) {
+    buf.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_coordinates(buf: &mut Vec<u8>, linestring: &LineString) {
+    write_u32(buf, linestring.0.len());
+    for coord in linestring.0.iter() {
+        buf.extend_from_slice(&coord.x.to_le_bytes());
+        buf.extend_from_slice(&coord.y.to_le_bytes());
+    }
+}
+
+fn write_linestring(buf: &mut Vec<u8>, linestring: &LineString) {
+    write_header(buf, WKB_LINESTRING);
+    write_coordinates(buf, linestring);
+}
+
+fn write_polygon(buf: &mut Vec<u8>, polygon: &Polygon) {
+    write_header(buf, WKB_POLYGON);
+    write_u32(buf, 1 + polygon.interiors().len());
+    write_coordinates(buf, polygon.exterior());
+    for interior in polygon.interiors() {
+        write_coordinates(buf, interior);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::wkb::{
+        WKB_LINESTRING, WKB_MULTILINESTRING, WKB_MULTIPOLYGON, WKB_POLYGON,
+    };
+    use crate::algorithm::chunkedarray::H3ToWkb;
+    use crate::{AsH3CellChunked, AsH3DirectedEdgeChunked, FromIndexIterator};
+    use geo::winding_order::Winding;
+    use geo_types::{Coordinate, Geometry, LineString, MultiLineString, MultiPolygon, Polygon};
+    use h3ron::to_geo::ToLine;
+    use h3ron::{H3Cell, H3DirectedEdge, Index, ToPolygon};
+    use polars_core::prelude::{ListChunked, TakeRandom, UInt64Chunked};
+
+    /// the bytes of the geometry at `idx`
+    fn wkb_at(wkb: &ListChunked, idx: usize) -> Option<Vec<u8>> {
+        wkb.get(idx)
+            .map(|series| series.u8().unwrap().into_no_null_iter().collect())
+    }
+
+    /// minimal decoder for the little-endian geometries written by `to_wkb`
+    struct WkbReader<'a> {
+        bytes: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> WkbReader<'a> {
+        fn read_geometry(bytes: &'a [u8]) -> Geometry {
+            let mut reader = Self { bytes, pos: 0 };
+            let geometry = reader.geometry();
+            assert_eq!(reader.pos, bytes.len());
+            geometry
+        }
+
+        fn take<const N: usize>(&mut self) -> [u8; N] {
+            let taken = self.bytes[self.pos..self.pos + N].try_into().unwrap();
+            self.pos += N;
+            taken
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_le_bytes(self.take())
+        }
+
+        fn linestring(&mut self) -> LineString {
+            (0..self.u32())
+                .map(|_| Coordinate {
+                    x: f64::from_le_bytes(self.take()),
+                    y: f64::from_le_bytes(self.take()),
+                })
+                .collect()
+        }
+
+        fn polygon(&mut self) -> Polygon {
+            let mut rings: Vec<_> = (0..self.u32()).map(|_| self.linestring()).collect();
+            let exterior = rings.remove(0);
+            Polygon::new(exterior, rings)
+        }
+
+        fn geometry(&mut self) -> Geometry {
+            assert_eq!(self.take::<1>()[0], 1);
+            match self.u32() {
+                WKB_LINESTRING => self.linestring().into(),
+                WKB_POLYGON => self.polygon().into(),
+                WKB_MULTILINESTRING => MultiLineString::new(
+                    (0..self.u32())
+                        .map(|_| match self.geometry() {
+                            Geometry::LineString(ls) => ls,
+                            _ => panic!("expected linestring"),
+                        })
+                        .collect(),
+                )
+                .into(),
+                WKB_MULTIPOLYGON => MultiPolygon::new(
+                    (0..self.u32())
+                        .map(|_| match self.geometry() {
+                            Geometry::Polygon(p) => p,
+                            _ => panic!("expected polygon"),
+                        })
+                        .collect(),
+                )
+                .into(),
+                geometry_type => panic!("unsupported geometry type {}", geometry_type),
+            }
+        }
+    }
+
+    #[test]
+    fn cell_wkb_roundtrip() {
+        // a pentagon and a hexagon
+        let cells = [
+            H3Cell::new(0x8009fffffffffff_u64),
+            H3Cell::from_coordinate((4.5, 1.3).into(), 7).unwrap(),
+        ];
+        let ca = UInt64Chunked::from_index_iter([
+            Some(cells[0]),
+            Some(cells[1]),
+            Some(H3Cell::new(55)), // invalid
+            None,
+        ]);
+
+        for split_antimeridian in [false, true] {
+            let wkb = ca.h3cell().to_wkb(split_antimeridian).unwrap();
+            assert_eq!(wkb.len(), 4);
+            for (i, cell) in cells.iter().enumerate() {
+                let polygon = match WkbReader::read_geometry(&wkb_at(&wkb, i).unwrap()) {
+                    Geometry::Polygon(polygon) => polygon,
+                    _ => panic!("expected polygon"),
+                };
+                assert!(polygon.exterior().is_ccw());
+                assert!(polygon.interiors().is_empty());
+
+                let mut expected = cell.to_polygon().unwrap().into_inner().0;
+                expected.make_ccw_winding();
+                assert_eq!(polygon.exterior(), &expected);
+            }
+            assert_eq!(wkb_at(&wkb, 2), None);
+            assert_eq!(wkb_at(&wkb, 3), None);
+        }
+    }
+
+    #[test]
+    fn cell_wkb_split_at_antimeridian() {
+        let cell = H3Cell::from_coordinate((180.0, 10.0).into(), 2).unwrap();
+        let ca = UInt64Chunked::from_index_iter([cell]);
+
+        let unsplit = ca.h3cell().to_wkb(false).unwrap();
+        assert!(matches!(
+            WkbReader::read_geometry(&wkb_at(&unsplit, 0).unwrap()),
+            Geometry::Polygon(_)
+        ));
+
+        let split = ca.h3cell().to_wkb(true).unwrap();
+        let multipolygon = match WkbReader::read_geometry(&wkb_at(&split, 0).unwrap()) {
+            Geometry::MultiPolygon(multipolygon) => multipolygon,
+            _ => panic!("expected multipolygon"),
+        };
+        assert_eq!(multipolygon.0.len(), 2);
+        for (polygon, antimeridian_x) in multipolygon.0.iter().zip([180.0, -180.0]) {
+            let ring = polygon.exterior();
+            assert!(ring.is_closed());
+            assert!(ring.is_ccw());
+            assert!(ring.0.iter().all(|c| c.x.abs() <= 180.0));
+            let unclosed = &ring.0[..ring.0.len() - 1];
+            assert_eq!(
+                unclosed.iter().filter(|c| c.x == antimeridian_x).count(),
+                2,
+                "{:?}",
+                ring
+            );
+            // no segment wraps around the globe
+            assert!(ring.0.windows(2).all(|w| (w[0].x - w[1].x).abs() < 180.0));
+        }
+
+        // cells containing a pole cross the antimeridian only once and stay unsplit
+        let pole_cell = H3Cell::from_coordinate((0.0, 90.0).into(), 1).unwrap();
+        let ca = UInt64Chunked::from_index_iter([pole_cell]);
+        let wkb = ca.h3cell().to_wkb(true).unwrap();
+        assert!(matches!(
+            WkbReader::read_geometry(&wkb_at(&wkb, 0).unwrap()),
+            Geometry::Polygon(_)
+        ));
+    }
+
+    #[test]
+    fn edge_wkb_roundtrip() {
+        let edges: Vec<_> = H3Cell::from_coordinate((4.5, 1.3).into(), 7)
+            .unwrap()
+            .directed_edges()
+            .unwrap()
+            .iter()
+            .collect();
+        let ca = UInt64Chunked::from_index_iter([
+            Some(edges[0]),
+            Some(H3DirectedEdge::new(55)), // invalid
+            None,
+        ]);
+        let wkb = ca.h3directededge().to_wkb(true).unwrap();
+        assert_eq!(wkb.len(), 3);
+        let line = edges[0].to_line().unwrap();
+        assert_eq!(
+            WkbReader::read_geometry(&wkb_at(&wkb, 0).unwrap()),
+            Geometry::LineString(LineString::from(vec![line.start, line.end]))
+        );
+        assert_eq!(wkb_at(&wkb, 1), None);
+        assert_eq!(wkb_at(&wkb, 2), None);
+    }
+
+    #[test]
+    fn edge_wkb_split_at_antimeridian() {
+        let edge = H3Cell::from_coordinate((180.0, 10.0).into(), 2)
+            .unwrap()
+            .directed_edges()
+            .unwrap()
+            .iter()
+            .find(|edge| {
+                let line = edge.to_line().unwrap();
+                (line.start.x - line.end.x).abs() > 180.0
+            })
+            .unwrap();
+        let line = edge.to_line().unwrap();
+        let ca = UInt64Chunked::from_index_iter([edge]);
+        let wkb = ca.h3directededge().to_wkb(true).unwrap();
+        let multilinestring = match WkbReader::read_geometry(&wkb_at(&wkb, 0).unwrap()) {
+            Geometry::MultiLineString(mls) => mls,
+            _ => panic!("expected multilinestring"),
+        };
+        assert_eq!(multilinestring.0.len(), 2);
+        assert_eq!(multilinestring.0[0].0[0], line.start);
+        assert_eq!(multilinestring.0[1].0[1], line.end);
+        assert_eq!(
+            multilinestring.0[0].0[1].x.abs(),
+            180.0,
+            "{:?}",
+            multilinestring
+        );
+        assert_eq!(multilinestring.0[0].0[1].y, multilinestring.0[1].0[0].y);
+        assert_eq!(multilinestring.0[0].0[1].x, -multilinestring.0[1].0[0].x);
+    }
+}
@@ -0,0 +1,317 @@
+use crate::algorithm::frame::incremental_aggregate::ValueState;
+use crate::algorithm::frame::AggOp;
+use crate::{AsH3CellChunked, Error};
+use h3ron::collections::H3CellMap;
+use h3ron::error::check_valid_h3_resolution;
+use h3ron::{H3Cell, Index};
+use polars::prelude::{DataFrame, DataType, Float64Chunked, NamedFrom, Series};
+
+/// The resolution [`harmonize_to_resolution`] converts all cells to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HarmonizeTarget {
+    /// the finest resolution found in the cell column
+    Finest,
+
+    /// the coarsest resolution found in the cell column
+    Coarsest,
+
+    Exactly(u8),
+}
+
+/// How the value of a cell gets distributed to its children when the cell is expanded
+/// to a finer resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpandOp {
+    /// every child receives the value of the cell.
+    Broadcast,
+
+    /// the value is split in equal parts among the children, preserving the sum.
+    Split,
+}
+
+/// The handling of a value column by [`harmonize_to_resolution`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HarmonizeSpec {
+    /// name of the value column. The values get cast to `Float64`.
+    pub column: String,
+
+    /// the aggregation of all values ending up in the same cell
+    pub agg: AggOp,
+
+    /// the distribution of the values of cells coarser than the target resolution
+    pub expand: ExpandOp,
+}
+
+impl HarmonizeSpec {
+    pub fn new<S: AsRef<str>>(column: S, agg: AggOp, expand: ExpandOp) -> Self {
+        Self {
+            column: column.as_ref().to_string(),
+            agg,
+            expand,
+        }
+    }
+}
+
+/// Convert a dataframe containing cells of mixed resolutions to a dataframe of cells of a
+/// single resolution.
+///
+/// Cells finer than the target resolution are replaced by their parent, cells coarser than
+/// the target resolution are expanded to their children using the [`ExpandOp`] of each value
+/// column. Afterwards the values of all rows ending up in the same cell are aggregated using
+/// the [`AggOp`] of the value column.
+///
+/// The returned dataframe is sorted by the cell and contains the cell column and one column per
+/// [`HarmonizeSpec`], named like the value column. All other columns as well as rows with `null` or
+/// invalid cells are dropped.
+///
+/// As the expansion multiplies the number of rows by up to `7` per resolution, it fails with
+/// [`Error::ExpansionLimitExceeded`] when the rows resulting from the conversion would exceed
+/// `max_expansion_factor` times the number of rows of `df`.
+pub fn harmonize_to_resolution<S: AsRef<str>>(
+    df: &DataFrame,
+    cell_column_name: S,
+    target: HarmonizeTarget,
+    specs: &[HarmonizeSpec],
+    max_expansion_factor: usize,
+) -> Result<DataFrame, Error> {
+    let cell_column_name = cell_column_name.as_ref();
+    let cells: Vec<Option<H3Cell>> = df
+        .column(cell_column_name)?
+        .u64()?
+        .h3cell()
+        .iter_indexes_validated()
+        .map(|maybe_cell| maybe_cell.and_then(Result::ok))
+        .collect();
+
+    let resolutions = cells.iter().flatten().map(|cell| cell.resolution());
+    let target_resolution = match target {
+        HarmonizeTarget::Finest => resolutions.max(),
+        HarmonizeTarget::Coarsest => resolutions.min(),
+        HarmonizeTarget::Exactly(resolution) => {
+            check_valid_h3_resolution(resolution)?;
+            Some(resolution)
+        }
+    };
+
+    let mut state: H3CellMap<Vec<ValueState>> = Default::default();
+    if let Some(target_resolution) = target_resolution {
+        let mut num_rows = 0_usize;
+        for cell in cells.iter().flatten() {
+            num_rows += if cell.resolution() < target_resolution {
+                cell.get_children_count(target_resolution)?
+            } else {
+                1
+            };
+        }
+        let limit = max_expansion_factor.saturating_mul(df.height());
+        if num_rows > limit {
+            return Err(Error::ExpansionLimitExceeded(num_rows, limit));
+        }
+
+        let value_series = specs
+            .iter()
+            .map(|spec| Ok(df.column(&spec.column)?.cast(&DataType::Float64)?))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let value_cas = value_series
+            .iter()
+            .map(|series| series.f64())
+            .collect::<Result<Vec<&Float64Chunked>, _>>()?;
+        let mut value_iters: Vec<_> = value_cas.iter().map(|ca| ca.into_iter()).collect();
+
+        let mut row_values = vec![None; specs.len()];
+        for maybe_cell in cells.iter() {
+            for (row_value, value_iter) in row_values.iter_mut().zip(value_iters.iter_mut()) {
+                *row_value = value_iter.next().flatten();
+            }
+            let cell = match maybe_cell {
+                Some(cell) => *cell,
+                None => continue,
+            };
+
+            if cell.resolution() < target_resolution {
+                let children = cell.get_children(target_resolution)?;
+                let num_children = children.count() as f64;
+                for child in children.iter() {
+                    let child_state = state
+                        .entry(child)
+                        .or_insert_with(|| vec![ValueState::default(); specs.len()]);
+                    for ((value_state, value), spec) in
+                        child_state.iter_mut().zip(row_values.iter()).zip(specs)
+                    {
+                        if let Some(value) = value {
+                            value_state.insert(match spec.expand {
+                                ExpandOp::Broadcast => *value,
+                                ExpandOp::Split => *value / num_children,
+                            });
+                        }
+                    }
+                }
+            } else {
+                let target_cell = cell.get_parent(target_resolution)?;
+                let cell_state = state
+                    .entry(target_cell)
+                    .or_insert_with(|| vec![ValueState::default(); specs.len()]);
+                for (value_state, value) in cell_state.iter_mut().zip(row_values.iter()) {
+                    if let Some(value) = value {
+                        value_state.insert(*value);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut target_cells: Vec<_> = state.keys().copied().collect();
+    target_cells.sort_unstable();
+
+    let mut columns = Vec::with_capacity(specs.len() + 1);
+    columns.push(Series::new(
+        cell_column_name,
+        target_cells
+            .iter()
+            .map(|cell| cell.h3index())
+            .collect::<Vec<_>>(),
+    ));
+    for (i, spec) in specs.iter().enumerate() {
+        let states = target_cells.iter().map(|cell| &state[cell][i]);
+        let series = match spec.agg {
+            AggOp::Count => {
+                Series::new(&spec.column, states.map(|vs| vs.count).collect::<Vec<_>>())
+            }
+            AggOp::Sum => Series::new(&spec.column, states.map(|vs| vs.sum).collect::<Vec<_>>()),
+            AggOp::Min => Series::new(&spec.column, states.map(|vs| vs.min).collect::<Vec<_>>()),
+            AggOp::Max => Series::new(&spec.column, states.map(|vs| vs.max).collect::<Vec<_>>()),
+        };
+        columns.push(series);
+    }
+    Ok(DataFrame::new(columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use h3ron::{H3Cell, Index};
+    use polars::prelude::{DataFrame, NamedFrom, Series};
+
+    use crate::algorithm::chunkedarray::check_single_resolution;
+    use crate::algorithm::frame::{
+        harmonize_to_resolution, AggOp, ExpandOp, HarmonizeSpec, HarmonizeTarget,
+    };
+    use crate::{AsH3CellChunked, Error};
+
+    /// a res 5 cell, a res 6 cell and three res 7 cells - two of them sharing their parent
+    fn mixed_frame() -> DataFrame {
+        let siblings = H3Cell::from_coordinate((14.0, 50.0).into(), 6)
+            .unwrap()
+            .get_children(7)
+            .unwrap();
+        let cells = [
+            H3Cell::from_coordinate((10.0, 50.0).into(), 5).unwrap(),
+            H3Cell::from_coordinate((12.0, 50.0).into(), 6).unwrap(),
+            siblings.first().unwrap(),
+            siblings.iter().nth(1).unwrap(),
+            H3Cell::from_coordinate((16.0, 50.0).into(), 7).unwrap(),
+        ];
+        assert!(cells.iter().all(|cell| !cell.is_pentagon()));
+        DataFrame::new(vec![
+            Series::new(
+                "cell",
+                cells.iter().map(|cell| cell.h3index()).collect::<Vec<_>>(),
+            ),
+            Series::new("population", &[98.0, 14.0, 1.0, 2.0, 3.0]),
+            Series::new("landuse", &[1.0, 2.0, 3.0, 4.0, 5.0]),
+        ])
+        .unwrap()
+    }
+
+    fn sum(df: &DataFrame, column: &str) -> f64 {
+        df.column(column).unwrap().sum::<f64>().unwrap()
+    }
+
+    fn resolution(df: &DataFrame) -> u8 {
+        check_single_resolution(&df.column("cell").unwrap().u64().unwrap().h3cell()).unwrap()
+    }
+
+    #[test]
+    fn harmonize_to_finest() {
+        let df = mixed_frame();
+        let harmonized = harmonize_to_resolution(
+            &df,
+            "cell",
+            HarmonizeTarget::Finest,
+            &[
+                HarmonizeSpec::new("population", AggOp::Sum, ExpandOp::Split),
+                HarmonizeSpec::new("landuse", AggOp::Max, ExpandOp::Broadcast),
+            ],
+            100,
+        )
+        .unwrap();
+
+        assert_eq!(resolution(&harmonized), 7);
+        // 49 + 7 children and the three cells of resolution 7
+        assert_eq!(harmonized.height(), 49 + 7 + 3);
+        assert!((sum(&harmonized, "population") - sum(&df, "population")).abs() < 1e-9);
+
+        // each child carries the broadcasted value of its parent
+        let landuse = harmonized.column("landuse").unwrap().f64().unwrap();
+        assert_eq!(landuse.into_iter().filter(|v| *v == Some(1.0)).count(), 49);
+        assert_eq!(landuse.into_iter().filter(|v| *v == Some(2.0)).count(), 7);
+    }
+
+    #[test]
+    fn harmonize_to_coarsest() {
+        let df = mixed_frame();
+        let harmonized = harmonize_to_resolution(
+            &df,
+            "cell",
+            HarmonizeTarget::Coarsest,
+            &[
+                HarmonizeSpec::new("population", AggOp::Sum, ExpandOp::Split),
+                HarmonizeSpec::new("landuse", AggOp::Count, ExpandOp::Broadcast),
+            ],
+            100,
+        )
+        .unwrap();
+
+        assert_eq!(resolution(&harmonized), 5);
+        // the siblings share their parent
+        assert_eq!(harmonized.height(), 4);
+        assert!((sum(&harmonized, "population") - sum(&df, "population")).abs() < 1e-9);
+        assert_eq!(sum(&harmonized, "landuse") as usize, df.height());
+    }
+
+    #[test]
+    fn harmonize_to_intermediate_resolution() {
+        let df = mixed_frame();
+        let harmonized = harmonize_to_resolution(
+            &df,
+            "cell",
+            HarmonizeTarget::Exactly(6),
+            &[HarmonizeSpec::new(
+                "population",
+                AggOp::Sum,
+                ExpandOp::Split,
+            )],
+            100,
+        )
+        .unwrap();
+        assert_eq!(resolution(&harmonized), 6);
+        assert_eq!(harmonized.width(), 2);
+        assert!((sum(&harmonized, "population") - sum(&df, "population")).abs() < 1e-9);
+    }
+
+    #[test]
+    fn harmonize_expansion_limit() {
+        let df = mixed_frame();
+        let specs = [HarmonizeSpec::new(
+            "population",
+            AggOp::Sum,
+            ExpandOp::Split,
+        )];
+        assert!(matches!(
+            harmonize_to_resolution(&df, "cell", HarmonizeTarget::Exactly(9), &specs, 100),
+            Err(Error::ExpansionLimitExceeded(_, 500))
+        ));
+        assert!(
+            harmonize_to_resolution(&df, "cell", HarmonizeTarget::Exactly(9), &specs, 1000).is_ok()
+        );
+    }
+}
@@ -0,0 +1,680 @@
+use crate::{AsH3CellChunked, Error};
+use h3ron::collections::{H3CellMap, H3CellSet};
+use h3ron::{H3Cell, HasH3Resolution, Index};
+use polars::prelude::{DataFrame, DataType, Float64Chunked, NamedFrom, Series};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Aggregation operations supported by [`IncrementalCellAggregate`].
+///
+/// Only decomposable operations are supported, as these can be updated from the
+/// inserted and retracted rows alone.
+///
+/// `Count` and `Sum` can be fully retracted. `Min` and `Max` can not: retracting a value equal
+/// to the recorded extremum leaves the aggregate unknown until the affected parent cell
+/// gets rescanned using [`IncrementalCellAggregate::rescan`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum AggOp {
+    /// number of non-`null` values
+    Count,
+    Sum,
+    Min,
+    Max,
+}
+
+impl AggOp {
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Min => "min",
+            Self::Max => "max",
+        }
+    }
+}
+
+/// An aggregation of a value column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AggSpec {
+    /// name of the value column. The values get cast to `Float64`.
+    pub column: String,
+    pub op: AggOp,
+}
+
+impl AggSpec {
+    pub fn new<S: AsRef<str>>(column: S, op: AggOp) -> Self {
+        Self {
+            column: column.as_ref().to_string(),
+            op,
+        }
+    }
+
+    /// name of the column in the snapshot: `<column>_<op>`
+    pub fn output_column_name(&self) -> String {
+        format!("{}_{}", self.column, self.op.name())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) struct ValueState {
+    pub(crate) count: u64,
+    pub(crate) sum: f64,
+    pub(crate) min: Option<f64>,
+    pub(crate) max: Option<f64>,
+}
+
+impl ValueState {
+    pub(crate) fn insert(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = Some(self.min.map_or(value, |min| min.min(value)));
+        self.max = Some(self.max.map_or(value, |max| max.max(value)));
+    }
+
+    /// returns `true` when the min or max may have been invalidated by the retraction.
+    fn retract(&mut self, value: f64) -> bool {
+        self.count = self.count.saturating_sub(1);
+        self.sum -= value;
+        if self.count == 0 {
+            *self = Self::default();
+            return false;
+        }
+        self.min.is_some_and(|min| value <= min) || self.max.is_some_and(|max| value >= max)
+    }
+
+    fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = merge_option(self.min, other.min, f64::min);
+        self.max = merge_option(self.max, other.max, f64::max);
+    }
+}
+
+fn merge_option(a: Option<f64>, b: Option<f64>, f: fn(f64, f64) -> f64) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(f(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct ParentState {
+    num_rows: u64,
+
+    /// set when a retraction invalidated a min or max aggregate
+    needs_rescan: bool,
+
+    /// one state per value column of the `AggSpec`s
+    values: Vec<ValueState>,
+}
+
+impl ParentState {
+    fn new(num_value_columns: usize) -> Self {
+        Self {
+            num_rows: 0,
+            needs_rescan: false,
+            values: vec![ValueState::default(); num_value_columns],
+        }
+    }
+}
+
+/// Aggregates of value columns grouped by the parent cells of a cell column, maintained
+/// incrementally from batches of inserted and retracted rows.
+///
+/// Each batch is processed in a single pass over its rows, so the cost of an update does not
+/// depend on the number of rows aggregated so far. Partial aggregates - for example built
+/// in parallel from partitions of the data - can be combined using [`merge`](Self::merge).
+///
+/// With the `serde` feature, the state can be serialized for checkpointing.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct IncrementalCellAggregate {
+    cell_column_name: String,
+    parent_resolution: u8,
+    specs: Vec<AggSpec>,
+
+    /// distinct value columns of the specs
+    value_columns: Vec<String>,
+
+    state: H3CellMap<ParentState>,
+}
+
+impl IncrementalCellAggregate {
+    pub fn new<S: AsRef<str>>(
+        cell_column_name: S,
+        parent_resolution: u8,
+        specs: Vec<AggSpec>,
+    ) -> Result<Self, Error> {
+        if parent_resolution > 15 {
+            return Err(h3ron::Error::ResDomain.into());
+        }
+        let mut value_columns: Vec<String> = Vec::with_capacity(specs.len());
+        for spec in specs.iter() {
+            if !value_columns.contains(&spec.column) {
+                value_columns.push(spec.column.clone());
+            }
+        }
+        Ok(Self {
+            cell_column_name: cell_column_name.as_ref().to_string(),
+            parent_resolution,
+            specs,
+            value_columns,
+            state: Default::default(),
+        })
+    }
+
+    pub fn cell_column_name(&self) -> &str {
+        &self.cell_column_name
+    }
+
+    pub fn specs(&self) -> &[AggSpec] {
+        &self.specs
+    }
+
+    /// number of parent cells with at least one aggregated row
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    /// Add the rows of `df` to the aggregates.
+    ///
+    /// Rows with `null` or invalid cells are skipped.
+    pub fn apply_inserts(&mut self, df: &DataFrame) -> Result<(), Error> {
+        self.apply(df, None, |state, row_values| {
+            state.num_rows += 1;
+            for (value_state, value) in state.values.iter_mut().zip(row_values) {
+                if let Some(value) = value {
+                    value_state.insert(*value);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Remove previously inserted rows of `df` from the aggregates.
+    ///
+    /// Parent cells left without any rows are removed. Retracting a value equal to the
+    /// recorded min or max of a parent cell marks the parent cell as needing a
+    /// [`rescan`](Self::rescan).
+    ///
+    /// Retracting rows which have not been inserted before fails with
+    /// [`Error::RetractionWithoutInsert`]. The aggregate is left partially updated in
+    /// that case.
+    pub fn apply_retractions(&mut self, df: &DataFrame) -> Result<(), Error> {
+        let result = self.apply(df, None, |state, row_values| {
+            if state.num_rows == 0 {
+                return Err(Error::RetractionWithoutInsert);
+            }
+            state.num_rows -= 1;
+            for (value_state, value) in state.values.iter_mut().zip(row_values) {
+                if let Some(value) = value {
+                    if value_state.count == 0 {
+                        return Err(Error::RetractionWithoutInsert);
+                    }
+                    state.needs_rescan |= value_state.retract(*value);
+                }
+            }
+            Ok(())
+        });
+
+        // also removes the empty states `apply` created for parents which were never inserted
+        self.state.retain(|_, state| state.num_rows > 0);
+        result
+    }
+
+    /// Parent cells for which the min or max aggregates are unknown since a retraction.
+    pub fn cells_needing_rescan(&self) -> Vec<H3Cell> {
+        let mut cells: Vec<_> = self
+            .state
+            .iter()
+            .filter_map(|(parent, state)| state.needs_rescan.then_some(*parent))
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    /// Rebuild the aggregates of the parent cells needing a rescan.
+    ///
+    /// `df` must contain all currently aggregated rows of these parent cells. Rows of other
+    /// parent cells are ignored.
+    pub fn rescan(&mut self, df: &DataFrame) -> Result<(), Error> {
+        let mut rescan_parents = H3CellSet::default();
+        for (parent, state) in self.state.iter_mut() {
+            if state.needs_rescan {
+                *state = ParentState::new(self.value_columns.len());
+                rescan_parents.insert(*parent);
+            }
+        }
+        if rescan_parents.is_empty() {
+            return Ok(());
+        }
+
+        self.apply(df, Some(&rescan_parents), |state, row_values| {
+            state.num_rows += 1;
+            for (value_state, value) in state.values.iter_mut().zip(row_values) {
+                if let Some(value) = value {
+                    value_state.insert(*value);
+                }
+            }
+            Ok(())
+        })?;
+
+        // parent cells without any rows in `df`
+        self.state.retain(|_, state| state.num_rows > 0);
+        Ok(())
+    }
+
+    /// Merge the aggregates of `other` into this aggregate.
+    ///
+    /// Both aggregates must use the same cell column, parent resolution and specs.
+    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        if self.cell_column_name != other.cell_column_name
+            || self.parent_resolution != other.parent_resolution
+            || self.specs != other.specs
+        {
+            return Err(Error::IncompatibleAggregates);
+        }
+        for (parent, other_state) in other.state.iter() {
+            let num_value_columns = self.value_columns.len();
+            let state = self
+                .state
+                .entry(*parent)
+                .or_insert_with(|| ParentState::new(num_value_columns));
+            state.num_rows += other_state.num_rows;
+            state.needs_rescan |= other_state.needs_rescan;
+            for (value_state, other_value_state) in
+                state.values.iter_mut().zip(other_state.values.iter())
+            {
+                value_state.merge(other_value_state);
+            }
+        }
+        Ok(())
+    }
+
+    /// The current aggregates as a dataframe sorted by the parent cell.
+    ///
+    /// The dataframe contains the parent cells in a column named like the cell column, one
+    /// column per [`AggSpec`] named by [`AggSpec::output_column_name`] and a boolean
+    /// `needs_rescan` column. Min and max aggregates of parent cells needing a rescan
+    /// are `null`.
+    pub fn snapshot(&self) -> Result<DataFrame, Error> {
+        let mut parents: Vec<_> = self.state.keys().copied().collect();
+        parents.sort_unstable();
+
+        let mut columns = Vec::with_capacity(self.specs.len() + 2);
+        columns.push(Series::new(
+            &self.cell_column_name,
+            parents
+                .iter()
+                .map(|cell| cell.h3index())
+                .collect::<Vec<_>>(),
+        ));
+
+        for spec in self.specs.iter() {
+            let value_idx = self.value_column_position(&spec.column);
+            let states = parents.iter().map(|parent| {
+                let state = &self.state[parent];
+                (state.needs_rescan, &state.values[value_idx])
+            });
+            let name = spec.output_column_name();
+            let series = match spec.op {
+                AggOp::Count => {
+                    Series::new(&name, states.map(|(_, vs)| vs.count).collect::<Vec<_>>())
+                }
+                AggOp::Sum => Series::new(&name, states.map(|(_, vs)| vs.sum).collect::<Vec<_>>()),
+                AggOp::Min => Series::new(
+                    &name,
+                    states
+                        .map(|(needs_rescan, vs)| vs.min.filter(|_| !needs_rescan))
+                        .collect::<Vec<_>>(),
+                ),
+                AggOp::Max => Series::new(
+                    &name,
+                    states
+                        .map(|(needs_rescan, vs)| vs.max.filter(|_| !needs_rescan))
+                        .collect::<Vec<_>>(),
+                ),
+            };
+            columns.push(series);
+        }
+
+        columns.push(Series::new(
+            "needs_rescan",
+            parents
+                .iter()
+                .map(|parent| self.state[parent].needs_rescan)
+                .collect::<Vec<_>>(),
+        ));
+        Ok(DataFrame::new(columns)?)
+    }
+
+    fn value_column_position(&self, column: &str) -> usize {
+        self.value_columns
+            .iter()
+            .position(|vc| vc == column)
+            .expect("value column of spec")
+    }
+
+    /// single pass over the rows of `df`, calling `update` with the state of the parent cell
+    /// and the values of the row.
+    fn apply<F>(
+        &mut self,
+        df: &DataFrame,
+        parent_filter: Option<&H3CellSet>,
+        mut update: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&mut ParentState, &[Option<f64>]) -> Result<(), Error>,
+    {
+        let cells = df.column(&self.cell_column_name)?.u64()?;
+        let value_series = self
+            .value_columns
+            .iter()
+            .map(|name| Ok(df.column(name)?.cast(&DataType::Float64)?))
+            .collect::<Result<Vec<_>, Error>>()?;
+        let value_cas = value_series
+            .iter()
+            .map(|series| series.f64())
+            .collect::<Result<Vec<&Float64Chunked>, _>>()?;
+        let mut value_iters: Vec<_> = value_cas.iter().map(|ca| ca.into_iter()).collect();
+
+        let num_value_columns = self.value_columns.len();
+        let mut row_values = vec![None; num_value_columns];
+        for maybe_cell in cells.h3cell().iter_indexes_validated() {
+            for (row_value, value_iter) in row_values.iter_mut().zip(value_iters.iter_mut()) {
+                *row_value = value_iter.next().flatten();
+            }
+
+            let cell = match maybe_cell {
+                Some(Ok(cell)) => cell,
+                _ => continue,
+            };
+            let cell_resolution = cell.h3_resolution();
+            if cell_resolution < self.parent_resolution {
+                return Err(Error::ResolutionCoarserThanParent(
+                    cell_resolution,
+                    self.parent_resolution,
+                ));
+            }
+            let parent = cell.get_parent(self.parent_resolution)?;
+            if let Some(parent_filter) = parent_filter {
+                if !parent_filter.contains(&parent) {
+                    continue;
+                }
+            }
+            let state = self
+                .state
+                .entry(parent)
+                .or_insert_with(|| ParentState::new(num_value_columns));
+            update(state, &row_values)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::frame::{AggOp, AggSpec, IncrementalCellAggregate};
+    use h3ron::{H3Cell, Index};
+    use polars::prelude::{col, DataFrame, DataType, IntoLazy, NamedFrom, Series, TakeRandom};
+    use std::collections::HashMap;
+
+    const PARENT_RES: u8 = 5;
+
+    fn specs() -> Vec<AggSpec> {
+        vec![
+            AggSpec::new("value", AggOp::Count),
+            AggSpec::new("value", AggOp::Sum),
+            AggSpec::new("value", AggOp::Min),
+            AggSpec::new("value", AggOp::Max),
+        ]
+    }
+
+    fn rows_df(rows: &[(H3Cell, i64)]) -> DataFrame {
+        DataFrame::new(vec![
+            Series::new(
+                "cell",
+                rows.iter().map(|(c, _)| c.h3index()).collect::<Vec<_>>(),
+            ),
+            Series::new("value", rows.iter().map(|(_, v)| *v).collect::<Vec<_>>()),
+        ])
+        .unwrap()
+    }
+
+    fn source_cells() -> Vec<H3Cell> {
+        H3Cell::from_coordinate((12.3, 45.4).into(), 3)
+            .unwrap()
+            .grid_disk(1)
+            .unwrap()
+            .iter()
+            .flat_map(|c| {
+                c.get_children(7)
+                    .unwrap()
+                    .iter()
+                    .step_by(13)
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// tiny deterministic pseudo random number generator
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, bound: usize) -> usize {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((self.0 >> 33) as usize) % bound
+        }
+    }
+
+    type Aggregates = HashMap<u64, (f64, f64, Option<f64>, Option<f64>)>;
+
+    fn aggregates_from_snapshot(snapshot: &DataFrame) -> Aggregates {
+        let column = |name: &str| -> Vec<Option<f64>> {
+            snapshot
+                .column(name)
+                .unwrap()
+                .cast(&DataType::Float64)
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_iter()
+                .collect()
+        };
+        let cells: Vec<_> = snapshot
+            .column("cell")
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_iter()
+            .collect();
+        let counts = column("value_count");
+        let sums = column("value_sum");
+        let mins = column("value_min");
+        let maxs = column("value_max");
+        (0..cells.len())
+            .map(|i| {
+                (
+                    cells[i].unwrap(),
+                    (counts[i].unwrap(), sums[i].unwrap(), mins[i], maxs[i]),
+                )
+            })
+            .collect()
+    }
+
+    fn groupby_aggregates(rows: &[(H3Cell, i64)]) -> Aggregates {
+        let df = DataFrame::new(vec![
+            Series::new(
+                "cell",
+                rows.iter()
+                    .map(|(c, _)| c.get_parent(PARENT_RES).unwrap().h3index())
+                    .collect::<Vec<_>>(),
+            ),
+            Series::new("value", rows.iter().map(|(_, v)| *v).collect::<Vec<_>>()),
+        ])
+        .unwrap()
+        .lazy()
+        .groupby([col("cell")])
+        .agg([
+            col("value").count().alias("value_count"),
+            col("value").sum().alias("value_sum"),
+            col("value").min().alias("value_min"),
+            col("value").max().alias("value_max"),
+        ])
+        .collect()
+        .unwrap();
+        aggregates_from_snapshot(&df)
+    }
+
+    #[test]
+    fn random_inserts_and_retractions_equal_groupby() {
+        let cells = source_cells();
+        let mut rng = Lcg(7);
+        let mut surviving: Vec<(H3Cell, i64)> = vec![];
+        let mut agg = IncrementalCellAggregate::new("cell", PARENT_RES, specs()).unwrap();
+
+        for _ in 0..30 {
+            let inserts: Vec<_> = (0..rng.next(40))
+                .map(|_| (cells[rng.next(cells.len())], rng.next(1000) as i64 - 500))
+                .collect();
+            agg.apply_inserts(&rows_df(&inserts)).unwrap();
+            surviving.extend(inserts);
+
+            let mut retractions = vec![];
+            for _ in 0..rng.next(30).min(surviving.len()) {
+                retractions.push(surviving.swap_remove(rng.next(surviving.len())));
+            }
+            agg.apply_retractions(&rows_df(&retractions)).unwrap();
+
+            let expected = groupby_aggregates(&surviving);
+            let got = aggregates_from_snapshot(&agg.snapshot().unwrap());
+            assert_eq!(got.len(), expected.len());
+
+            let rescan: Vec<_> = agg
+                .cells_needing_rescan()
+                .iter()
+                .map(|c| c.h3index())
+                .collect();
+            for (cell, (count, sum, min, max)) in expected.iter() {
+                let (got_count, got_sum, got_min, got_max) = got[cell];
+                assert_eq!(got_count, *count);
+                assert!((got_sum - sum).abs() < 1e-9);
+                if rescan.contains(cell) {
+                    assert_eq!(got_min, None);
+                    assert_eq!(got_max, None);
+                } else {
+                    assert_eq!(got_min, *min);
+                    assert_eq!(got_max, *max);
+                }
+            }
+
+            agg.rescan(&rows_df(&surviving)).unwrap();
+            assert!(agg.cells_needing_rescan().is_empty());
+            assert_eq!(aggregates_from_snapshot(&agg.snapshot().unwrap()), expected);
+        }
+    }
+
+    #[test]
+    fn merge_is_associative() {
+        let cells = source_cells();
+        let mut rng = Lcg(3);
+        let parts: Vec<_> = (0..3)
+            .map(|_| {
+                let rows: Vec<_> = (0..50)
+                    .map(|_| (cells[rng.next(cells.len())], rng.next(100) as i64))
+                    .collect();
+                let mut agg = IncrementalCellAggregate::new("cell", PARENT_RES, specs()).unwrap();
+                agg.apply_inserts(&rows_df(&rows)).unwrap();
+                agg
+            })
+            .collect();
+
+        let mut left = parts[0].clone();
+        left.merge(&parts[1]).unwrap();
+        left.merge(&parts[2]).unwrap();
+
+        let mut right_inner = parts[1].clone();
+        right_inner.merge(&parts[2]).unwrap();
+        let mut right = parts[0].clone();
+        right.merge(&right_inner).unwrap();
+
+        assert!(left
+            .snapshot()
+            .unwrap()
+            .frame_equal_missing(&right.snapshot().unwrap()));
+
+        let other_res = IncrementalCellAggregate::new("cell", PARENT_RES + 1, specs()).unwrap();
+        assert!(left.merge(&other_res).is_err());
+    }
+
+    #[test]
+    fn retracting_extremum_needs_rescan() {
+        let cells = source_cells();
+        let parent = cells[0].get_parent(PARENT_RES).unwrap();
+        let mut agg = IncrementalCellAggregate::new("cell", PARENT_RES, specs()).unwrap();
+        agg.apply_inserts(&rows_df(&[(cells[0], 1), (cells[0], 5), (cells[0], 3)]))
+            .unwrap();
+
+        // not an extremum
+        agg.apply_retractions(&rows_df(&[(cells[0], 3)])).unwrap();
+        assert!(agg.cells_needing_rescan().is_empty());
+
+        agg.apply_retractions(&rows_df(&[(cells[0], 1)])).unwrap();
+        assert_eq!(agg.cells_needing_rescan(), vec![parent]);
+        let snapshot = agg.snapshot().unwrap();
+        assert_eq!(snapshot.column("value_min").unwrap().null_count(), 1);
+        assert_eq!(
+            snapshot.column("value_sum").unwrap().f64().unwrap().get(0),
+            Some(5.0)
+        );
+
+        agg.rescan(&rows_df(&[(cells[0], 5)])).unwrap();
+        assert!(agg.cells_needing_rescan().is_empty());
+        let snapshot = agg.snapshot().unwrap();
+        assert_eq!(
+            snapshot.column("value_min").unwrap().f64().unwrap().get(0),
+            Some(5.0)
+        );
+
+        // retracting from a parent which was never inserted leaves the state unchanged
+        let other_cell = cells
+            .iter()
+            .find(|cell| cell.get_parent(PARENT_RES).unwrap() != parent)
+            .copied()
+            .unwrap();
+        let before = agg.clone();
+        assert!(agg.apply_retractions(&rows_df(&[(other_cell, 5)])).is_err());
+        assert_eq!(agg, before);
+        assert_eq!(agg.snapshot().unwrap().height(), 1);
+
+        // retracting the last row removes the parent
+        agg.apply_retractions(&rows_df(&[(cells[0], 5)])).unwrap();
+        assert!(agg.is_empty());
+        assert!(agg.apply_retractions(&rows_df(&[(cells[0], 5)])).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let cells = source_cells();
+        let mut agg = IncrementalCellAggregate::new("cell", PARENT_RES, specs()).unwrap();
+        agg.apply_inserts(&rows_df(&[(cells[0], 1), (cells[1], 5)]))
+            .unwrap();
+        agg.apply_retractions(&rows_df(&[(cells[0], 1)])).unwrap();
+
+        let serialized = bincode::serialize(&agg).unwrap();
+        let deserialized: IncrementalCellAggregate = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(agg, deserialized);
+    }
+}
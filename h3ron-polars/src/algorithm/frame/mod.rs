@@ -1,7 +1,13 @@
 pub mod compact;
+pub mod harmonize;
+pub mod incremental_aggregate;
+pub mod quantile;
 pub mod resolution;
 pub mod valid;
 
 pub use compact::*;
+pub use harmonize::*;
+pub use incremental_aggregate::*;
+pub use quantile::*;
 pub use resolution::*;
 pub use valid::*;
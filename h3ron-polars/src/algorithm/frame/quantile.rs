@@ -0,0 +1,652 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::f64::consts::PI;
+
+use crate::{AsH3CellChunked, Error};
+use h3ron::collections::H3CellMap;
+use h3ron::{H3Cell, Index};
+use polars::export::rayon::prelude::*;
+use polars::prelude::{DataFrame, DataType, NamedFrom, Series};
+use polars_core::POOL;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// number of rows aggregated into a partial state by each task of [`h3_quantile_agg`].
+///
+/// Fixed to make the result independent of the number of threads.
+const ROWS_PER_TASK: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+impl Centroid {
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        self.mean
+            .total_cmp(&other.mean)
+            .then_with(|| self.weight.total_cmp(&other.weight))
+    }
+}
+
+/// A merging t-digest sketch of the distribution of values.
+///
+/// The digest keeps at most about `compression` centroids. Quantiles near the tails
+/// are estimated more accurately than quantiles near the median, the error in terms of the
+/// rank is roughly bounded by `q * (1 - q) * 4 / compression`.
+///
+/// See [Computing Extremely Accurate Quantiles Using t-Digests](https://arxiv.org/abs/1902.04023).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TDigest {
+    compression: f64,
+
+    /// sorted by mean
+    centroids: Vec<Centroid>,
+
+    /// values not yet merged into the centroids
+    buffer: Vec<f64>,
+
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new(compression: f64) -> Self {
+        Self {
+            compression,
+            centroids: vec![],
+            buffer: vec![],
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn compression(&self) -> f64 {
+        self.compression
+    }
+
+    /// number of inserted values
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Insert a value. `value` must not be NaN.
+    pub fn insert(&mut self, value: f64) {
+        debug_assert!(!value.is_nan());
+        self.buffer.push(value);
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if self.buffer.len() >= self.buffer_capacity() {
+            self.compress();
+        }
+    }
+
+    /// Merge `other` into this digest.
+    ///
+    /// The result does not depend on the order of the two digests. Merging more than
+    /// two digests is associative within the error of the digest.
+    pub fn merge(&mut self, other: &Self) {
+        self.centroids.extend_from_slice(&other.centroids);
+        self.buffer.extend_from_slice(&other.buffer);
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.compress();
+    }
+
+    /// Estimate the value at quantile `q` within `[0.0, 1.0]`.
+    ///
+    /// Returns `None` for empty digests.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.is_empty() {
+            return None;
+        }
+        let centroids = if self.buffer.is_empty() {
+            Cow::Borrowed(&self.centroids)
+        } else {
+            let mut compressed = self.clone();
+            compressed.compress();
+            Cow::Owned(compressed.centroids)
+        };
+        Some(interpolate_quantile(
+            &centroids,
+            self.count as f64,
+            self.min,
+            self.max,
+            q,
+        ))
+    }
+
+    fn buffer_capacity(&self) -> usize {
+        (self.compression * 5.0).ceil().max(16.0) as usize
+    }
+
+    /// merge the buffered values and all centroids which are close enough in terms
+    /// of the `k1` scale function.
+    fn compress(&mut self) {
+        let mut all: Vec<Centroid> = Vec::with_capacity(self.centroids.len() + self.buffer.len());
+        all.append(&mut self.centroids);
+        all.extend(self.buffer.drain(..).map(|value| Centroid {
+            mean: value,
+            weight: 1.0,
+        }));
+        if all.is_empty() {
+            return;
+        }
+        all.sort_unstable_by(Centroid::cmp_key);
+
+        let total_weight: f64 = all.iter().map(|c| c.weight).sum();
+        let mut merged = Vec::with_capacity(self.compression.ceil() as usize);
+        let mut weight_so_far = 0.0;
+        let mut q_limit = self.k1_inverse(self.k1(0.0) + 1.0);
+        let mut current = all[0];
+        for centroid in all.into_iter().skip(1) {
+            let proposed_weight = current.weight + centroid.weight;
+            if (weight_so_far + proposed_weight) / total_weight <= q_limit {
+                current.mean += (centroid.mean - current.mean) * centroid.weight / proposed_weight;
+                current.weight = proposed_weight;
+            } else {
+                weight_so_far += current.weight;
+                merged.push(current);
+                q_limit = self.k1_inverse(self.k1(weight_so_far / total_weight) + 1.0);
+                current = centroid;
+            }
+        }
+        merged.push(current);
+        self.centroids = merged;
+    }
+
+    fn k1(&self, q: f64) -> f64 {
+        self.compression / (2.0 * PI) * (2.0 * q - 1.0).asin()
+    }
+
+    fn k1_inverse(&self, k: f64) -> f64 {
+        let angle = k * 2.0 * PI / self.compression;
+        if angle >= PI / 2.0 {
+            1.0
+        } else {
+            (angle.sin() + 1.0) / 2.0
+        }
+    }
+}
+
+fn interpolate_quantile(centroids: &[Centroid], count: f64, min: f64, max: f64, q: f64) -> f64 {
+    let q = q.clamp(0.0, 1.0);
+    if centroids.len() == 1 {
+        return centroids[0].mean;
+    }
+    let index = q * count;
+
+    // between the min and the first centroid
+    let first = &centroids[0];
+    if index < first.weight / 2.0 {
+        return min + (first.mean - min) * index / (first.weight / 2.0);
+    }
+
+    // between the last centroid and the max
+    let last = &centroids[centroids.len() - 1];
+    if index > count - last.weight / 2.0 {
+        return last.mean
+            + (max - last.mean) * (index - (count - last.weight / 2.0)) / (last.weight / 2.0);
+    }
+
+    let mut cumulative = first.weight / 2.0;
+    for window in centroids.windows(2) {
+        let delta = (window[0].weight + window[1].weight) / 2.0;
+        if cumulative + delta > index {
+            let t = (index - cumulative) / delta;
+            return window[0].mean + t * (window[1].mean - window[0].mean);
+        }
+        cumulative += delta;
+    }
+    last.mean
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct CellEntry {
+    digest: TDigest,
+
+    /// number of skipped NaN values
+    nan_count: u64,
+}
+
+/// Per-cell t-digest sketches of a value column.
+///
+/// The state is built from batches of rows using [`insert_batch`](Self::insert_batch).
+/// Partial states - for example built on different machines - can be combined using
+/// [`merge`](Self::merge). With the `serde` feature, the state can be serialized for
+/// distributed partial aggregation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CellDigestState {
+    cell_column_name: String,
+    value_column_name: String,
+    compression: f64,
+    state: H3CellMap<CellEntry>,
+}
+
+impl CellDigestState {
+    /// `compression` controls the size and accuracy of the digests. `100.0` is a common value.
+    pub fn new<S1, S2>(
+        cell_column_name: S1,
+        value_column_name: S2,
+        compression: f64,
+    ) -> Result<Self, Error>
+    where
+        S1: AsRef<str>,
+        S2: AsRef<str>,
+    {
+        if !(compression.is_finite() && compression >= 1.0) {
+            return Err(Error::InvalidCompression(compression));
+        }
+        Ok(Self {
+            cell_column_name: cell_column_name.as_ref().to_string(),
+            value_column_name: value_column_name.as_ref().to_string(),
+            compression,
+            state: Default::default(),
+        })
+    }
+
+    pub fn cell_column_name(&self) -> &str {
+        &self.cell_column_name
+    }
+
+    pub fn value_column_name(&self) -> &str {
+        &self.value_column_name
+    }
+
+    /// number of cells with at least one value
+    pub fn len(&self) -> usize {
+        self.state
+            .values()
+            .filter(|entry| !entry.digest.is_empty())
+            .count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// total number of skipped NaN values
+    pub fn nan_count(&self) -> u64 {
+        self.state.values().map(|entry| entry.nan_count).sum()
+    }
+
+    /// The digest of `cell`, in case values have been inserted for it.
+    pub fn digest(&self, cell: &H3Cell) -> Option<&TDigest> {
+        self.state
+            .get(cell)
+            .map(|entry| &entry.digest)
+            .filter(|digest| !digest.is_empty())
+    }
+
+    /// Insert the values of the rows of `df` into the digests of their cells.
+    ///
+    /// Rows with `null` or invalid cells and `null` values are skipped. NaN values are
+    /// skipped as well, but counted.
+    pub fn insert_batch(&mut self, df: &DataFrame) -> Result<(), Error> {
+        let cells = df.column(&self.cell_column_name)?.u64()?;
+        let values_series = df
+            .column(&self.value_column_name)?
+            .cast(&DataType::Float64)?;
+        let values = values_series.f64()?;
+
+        for (maybe_cell, maybe_value) in cells.h3cell().iter_indexes_validated().zip(values) {
+            let (cell, value) = match (maybe_cell, maybe_value) {
+                (Some(Ok(cell)), Some(value)) => (cell, value),
+                _ => continue,
+            };
+            let compression = self.compression;
+            let entry = self.state.entry(cell).or_insert_with(|| CellEntry {
+                digest: TDigest::new(compression),
+                nan_count: 0,
+            });
+            if value.is_nan() {
+                entry.nan_count += 1;
+            } else {
+                entry.digest.insert(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Merge the digests of `other` into this state.
+    ///
+    /// Both states must use the same columns and compression.
+    pub fn merge(&mut self, other: &Self) -> Result<(), Error> {
+        if self.cell_column_name != other.cell_column_name
+            || self.value_column_name != other.value_column_name
+            || self.compression != other.compression
+        {
+            return Err(Error::IncompatibleAggregates);
+        }
+        for (cell, other_entry) in other.state.iter() {
+            match self.state.get_mut(cell) {
+                Some(entry) => {
+                    entry.digest.merge(&other_entry.digest);
+                    entry.nan_count += other_entry.nan_count;
+                }
+                None => {
+                    self.state.insert(*cell, other_entry.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The estimated `quantiles` of the values of each cell as a dataframe sorted by the cell.
+    ///
+    /// The dataframe contains the cells in a column named like the cell column, the number of
+    /// aggregated values in `count`, the number of skipped NaN values in `nan_count` and one
+    /// column per quantile named `<value column>_q<quantile>`. Cells without any non-NaN value
+    /// are omitted.
+    pub fn quantiles(&self, quantiles: &[f64]) -> Result<DataFrame, Error> {
+        if let Some(q) = quantiles.iter().find(|q| !(0.0..=1.0).contains(*q)) {
+            return Err(Error::InvalidQuantile(*q));
+        }
+
+        let mut cells: Vec<_> = self
+            .state
+            .iter()
+            .filter_map(|(cell, entry)| (!entry.digest.is_empty()).then_some(*cell))
+            .collect();
+        cells.sort_unstable();
+        let entries: Vec<_> = cells.iter().map(|cell| &self.state[cell]).collect();
+
+        let mut columns = Vec::with_capacity(quantiles.len() + 3);
+        columns.push(Series::new(
+            &self.cell_column_name,
+            cells.iter().map(|cell| cell.h3index()).collect::<Vec<_>>(),
+        ));
+        columns.push(Series::new(
+            "count",
+            entries
+                .iter()
+                .map(|entry| entry.digest.count())
+                .collect::<Vec<_>>(),
+        ));
+        columns.push(Series::new(
+            "nan_count",
+            entries
+                .iter()
+                .map(|entry| entry.nan_count)
+                .collect::<Vec<_>>(),
+        ));
+        for q in quantiles {
+            columns.push(Series::new(
+                &format!("{}_q{}", self.value_column_name, q),
+                entries
+                    .iter()
+                    .map(|entry| entry.digest.quantile(*q))
+                    .collect::<Vec<_>>(),
+            ));
+        }
+        Ok(DataFrame::new(columns)?)
+    }
+}
+
+/// Estimate `quantiles` of the values of `value_column_name` per distinct cell
+/// of `cell_column_name`.
+///
+/// The rows are inserted into t-digests in a single parallel pass. See
+/// [`CellDigestState::quantiles`] for the layout of the returned dataframe and
+/// [`TDigest`] for the accuracy of the estimates.
+pub fn h3_quantile_agg(
+    df: &DataFrame,
+    cell_column_name: &str,
+    value_column_name: &str,
+    quantiles: &[f64],
+    compression: f64,
+) -> Result<DataFrame, Error> {
+    let empty_state = CellDigestState::new(cell_column_name, value_column_name, compression)?;
+    let offsets: Vec<_> = (0..df.height()).step_by(ROWS_PER_TASK).collect();
+
+    let partial_states = POOL.install(|| {
+        offsets
+            .par_iter()
+            .map(|offset| {
+                let mut state = empty_state.clone();
+                state.insert_batch(&df.slice(*offset as i64, ROWS_PER_TASK))?;
+                Ok(state)
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    let mut state = empty_state;
+    for partial_state in partial_states.iter() {
+        state.merge(partial_state)?;
+    }
+    state.quantiles(quantiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::frame::{h3_quantile_agg, CellDigestState, TDigest};
+    use h3ron::{H3Cell, Index};
+    use polars::prelude::{DataFrame, NamedFrom, Series, TakeRandom};
+
+    /// tiny deterministic pseudo random number generator
+    struct Lcg(u64);
+
+    impl Lcg {
+        /// uniform in `[0.0, 1.0)`
+        fn next(&mut self) -> f64 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 11) as f64 / (1_u64 << 53) as f64
+        }
+    }
+
+    fn exact_rank(sorted: &[f64], value: f64) -> f64 {
+        sorted.partition_point(|v| *v < value) as f64 / sorted.len() as f64
+    }
+
+    fn cells() -> Vec<H3Cell> {
+        H3Cell::from_coordinate((12.3, 45.4).into(), 7)
+            .unwrap()
+            .grid_disk(1)
+            .unwrap()
+            .iter()
+            .collect()
+    }
+
+    /// cells with differently distributed values: uniform, exponential and a squared
+    /// uniform distribution, with a few NaN values
+    fn synthetic_df(num_rows: usize) -> (DataFrame, Vec<(H3Cell, Vec<f64>)>) {
+        let cells = cells();
+        let mut rng = Lcg(3);
+        let mut values_per_cell: Vec<_> = cells.iter().map(|cell| (*cell, vec![])).collect();
+        let mut cell_column = Vec::with_capacity(num_rows);
+        let mut value_column = Vec::with_capacity(num_rows);
+        for i in 0..num_rows {
+            let cell_i = i % cells.len();
+            let u = rng.next();
+            let value = if i % 1000 == 7 {
+                f64::NAN
+            } else {
+                match cell_i % 3 {
+                    0 => u * 100.0,
+                    1 => -(1.0 - u).ln() * 20.0,
+                    _ => u * u * 50.0 - 10.0,
+                }
+            };
+            if !value.is_nan() {
+                values_per_cell[cell_i].1.push(value);
+            }
+            cell_column.push(cells[cell_i].h3index());
+            value_column.push(value);
+        }
+        for (_, values) in values_per_cell.iter_mut() {
+            values.sort_unstable_by(f64::total_cmp);
+        }
+        let df = DataFrame::new(vec![
+            Series::new("cell", cell_column),
+            Series::new("value", value_column),
+        ])
+        .unwrap();
+        (df, values_per_cell)
+    }
+
+    #[test]
+    fn quantiles_within_error() {
+        let quantiles = [0.01, 0.1, 0.5, 0.9, 0.99];
+        let compression = 100.0;
+        let (df, values_per_cell) = synthetic_df(200_000);
+        let result = h3_quantile_agg(&df, "cell", "value", &quantiles, compression).unwrap();
+        assert_eq!(result.height(), values_per_cell.len());
+
+        let result_cells = result.column("cell").unwrap().u64().unwrap();
+        let counts = result.column("count").unwrap().u64().unwrap();
+        let nan_counts = result.column("nan_count").unwrap().u64().unwrap();
+        let num_nan: u64 = nan_counts.into_iter().map(|c| c.unwrap()).sum();
+        assert_eq!(num_nan, 200);
+
+        for row in 0..result.height() {
+            let cell = H3Cell::new(result_cells.get(row).unwrap());
+            let sorted = &values_per_cell.iter().find(|(c, _)| *c == cell).unwrap().1;
+            assert_eq!(counts.get(row).unwrap() as usize, sorted.len());
+
+            for q in quantiles {
+                let estimate = result
+                    .column(&format!("value_q{}", q))
+                    .unwrap()
+                    .f64()
+                    .unwrap()
+                    .get(row)
+                    .unwrap();
+                // rank error bound with some headroom
+                let tolerance = (q * (1.0 - q) * 4.0 / compression).max(0.002) * 2.0;
+                let rank = exact_rank(sorted, estimate);
+                assert!(
+                    (rank - q).abs() <= tolerance,
+                    "q={} rank={} tolerance={}",
+                    q,
+                    rank,
+                    tolerance
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nan_only_cells_are_omitted() {
+        let cells = cells();
+        let df = DataFrame::new(vec![
+            Series::new("cell", vec![cells[0].h3index(), cells[1].h3index()]),
+            Series::new("value", vec![f64::NAN, 4.0]),
+        ])
+        .unwrap();
+        let mut state = CellDigestState::new("cell", "value", 100.0).unwrap();
+        state.insert_batch(&df).unwrap();
+        assert_eq!(state.len(), 1);
+        assert_eq!(state.nan_count(), 1);
+        assert!(state.digest(&cells[0]).is_none());
+
+        let result = state.quantiles(&[0.5]).unwrap();
+        assert_eq!(result.height(), 1);
+        assert_eq!(
+            result.column("value_q0.5").unwrap().f64().unwrap().get(0),
+            Some(4.0)
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert!(CellDigestState::new("cell", "value", 0.0).is_err());
+        assert!(CellDigestState::new("cell", "value", f64::NAN).is_err());
+        let state = CellDigestState::new("cell", "value", 100.0).unwrap();
+        assert!(state.quantiles(&[1.5]).is_err());
+    }
+
+    fn digest_from(rng: &mut Lcg, num_values: usize, scale: f64) -> TDigest {
+        let mut digest = TDigest::new(100.0);
+        for _ in 0..num_values {
+            digest.insert(rng.next() * scale);
+        }
+        digest
+    }
+
+    #[test]
+    fn merge_commutative_and_associative() {
+        let mut rng = Lcg(17);
+        let a = digest_from(&mut rng, 5_000, 10.0);
+        let b = digest_from(&mut rng, 3_000, 20.0);
+        let c = digest_from(&mut rng, 7_000, 5.0);
+
+        let mut ab = a.clone();
+        ab.merge(&b);
+        let mut ba = b.clone();
+        ba.merge(&a);
+        assert_eq!(ab, ba);
+
+        let mut ab_c = ab;
+        ab_c.merge(&c);
+        let mut bc = b;
+        bc.merge(&c);
+        let mut a_bc = a;
+        a_bc.merge(&bc);
+        assert_eq!(ab_c.count(), a_bc.count());
+        for q in [0.0, 0.05, 0.25, 0.5, 0.75, 0.95, 1.0] {
+            let left = ab_c.quantile(q).unwrap();
+            let right = a_bc.quantile(q).unwrap();
+            // values range from 0 to 20
+            assert!((left - right).abs() < 0.2, "q={} {} {}", q, left, right);
+        }
+        assert_eq!(ab_c.quantile(0.0), a_bc.quantile(0.0));
+        assert_eq!(ab_c.quantile(1.0), a_bc.quantile(1.0));
+    }
+
+    #[test]
+    fn merge_states() {
+        let (df, _) = synthetic_df(10_000);
+        let mut full = CellDigestState::new("cell", "value", 100.0).unwrap();
+        full.insert_batch(&df).unwrap();
+
+        let mut first = CellDigestState::new("cell", "value", 100.0).unwrap();
+        first.insert_batch(&df.slice(0, 4_000)).unwrap();
+        let mut second = CellDigestState::new("cell", "value", 100.0).unwrap();
+        second.insert_batch(&df.slice(4_000, 6_000)).unwrap();
+        first.merge(&second).unwrap();
+
+        assert_eq!(first.len(), full.len());
+        assert_eq!(first.nan_count(), full.nan_count());
+        for cell in cells() {
+            assert_eq!(
+                first.digest(&cell).unwrap().count(),
+                full.digest(&cell).unwrap().count()
+            );
+        }
+
+        let other_compression = CellDigestState::new("cell", "value", 50.0).unwrap();
+        assert!(first.merge(&other_compression).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roundtrip() {
+        let (df, _) = synthetic_df(5_000);
+        let mut state = CellDigestState::new("cell", "value", 100.0).unwrap();
+        state.insert_batch(&df).unwrap();
+
+        let serialized = bincode::serialize(&state).unwrap();
+        let deserialized: CellDigestState = bincode::deserialize(&serialized).unwrap();
+        assert_eq!(state, deserialized);
+        assert!(state
+            .quantiles(&[0.5])
+            .unwrap()
+            .frame_equal(&deserialized.quantiles(&[0.5]).unwrap()));
+    }
+}
@@ -1,8 +1,9 @@
+use polars::prelude::{col, IntoLazy, LazyGroupBy};
 use polars_core::prelude::{ChunkCompare, DataFrame, NamedFrom, Series, UInt8Chunked};
 
-use crate::algorithm::chunkedarray::H3Resolution;
+use crate::algorithm::chunkedarray::{H3Resolution, H3ToParent};
 use crate::frame::H3DataFrame;
-use crate::{AsH3IndexChunked, Error, IndexValue};
+use crate::{AsH3CellChunked, AsH3IndexChunked, Error, IndexValue};
 
 pub trait H3ResolutionOp {
     /// obtain the contained H3 resolutions
@@ -101,13 +102,61 @@ impl<IX: IndexValue> H3DataFrame<IX> {
     }
 }
 
+/// Replace the cells in the column named `cell_column_name` by their parents at
+/// `target_resolution` and group the dataframe by the parents.
+///
+/// The aggregation of the other columns follows using [`LazyGroupBy::agg`]. Cells already
+/// at `target_resolution` are kept unchanged, `null` values and invalid cells are grouped
+/// as `null`. Cells coarser than `target_resolution` fail with
+/// [`Error::ResolutionCoarserThanParent`].
+///
+/// # Example
+///
+/// ```
+/// use polars::prelude::{col, DataFrame, NamedFrom, Series};
+/// use h3ron::{H3Cell, Index};
+/// use h3ron_polars::algorithm::frame::change_resolution_aggregate;
+///
+/// let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 10).unwrap();
+/// let df = DataFrame::new(vec![
+///     Series::new("cell", &[cell.h3index(), cell.get_parent(9).unwrap().h3index()]),
+///     Series::new("value", &[1.0, 2.0]),
+/// ])
+/// .unwrap();
+///
+/// let aggregated = change_resolution_aggregate(&df, "cell", 7)
+///     .unwrap()
+///     .agg([col("value").sum()])
+///     .collect()
+///     .unwrap();
+/// assert_eq!(aggregated.shape(), (1, 2));
+/// ```
+pub fn change_resolution_aggregate<S: AsRef<str>>(
+    df: &DataFrame,
+    cell_column_name: S,
+    target_resolution: u8,
+) -> Result<LazyGroupBy, Error> {
+    let cell_column_name = cell_column_name.as_ref();
+    let parents = df
+        .column(cell_column_name)?
+        .u64()?
+        .h3cell()
+        .h3_to_parent(target_resolution)?;
+
+    let mut df = df.clone();
+    df.with_column(Series::new(cell_column_name, parents))?;
+    Ok(df.lazy().groupby([col(cell_column_name)]))
+}
+
 #[cfg(test)]
 mod tests {
     use h3ron::{H3Cell, Index};
+    use polars::prelude::col;
     use polars_core::frame::DataFrame;
     use polars_core::prelude::{NamedFrom, Series};
 
-    use crate::algorithm::frame::H3ResolutionOp;
+    use crate::algorithm::frame::{change_resolution_aggregate, H3ResolutionOp};
+    use crate::Error;
 
     #[test]
     fn partition_frame_by_resolution() {
@@ -138,4 +187,60 @@ mod tests {
             assert_eq!(df.shape(), (expected, 2));
         }
     }
+
+    #[test]
+    fn aggregate_mixed_resolutions_to_parents() {
+        let cell_a = H3Cell::from_coordinate((4.5, 1.3).into(), 10).unwrap();
+        let cell_b = H3Cell::from_coordinate((20.5, 10.3).into(), 10).unwrap();
+        let df = DataFrame::new(vec![
+            Series::new(
+                "cell",
+                vec![
+                    cell_a.h3index(),
+                    cell_a.get_parent(9).unwrap().h3index(),
+                    cell_a.get_parent(7).unwrap().h3index(),
+                    cell_b.h3index(),
+                ],
+            ),
+            Series::new("value", &[1.0, 2.0, 4.0, 8.0]),
+        ])
+        .unwrap();
+
+        let aggregated = change_resolution_aggregate(&df, "cell", 7)
+            .unwrap()
+            .agg([col("value").sum()])
+            .collect()
+            .unwrap()
+            .sort(["value"], false)
+            .unwrap();
+        assert_eq!(aggregated.shape(), (2, 2));
+
+        let cells: Vec<_> = aggregated
+            .column("cell")
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(
+            cells,
+            vec![
+                Some(cell_a.get_parent(7).unwrap().h3index()),
+                Some(cell_b.get_parent(7).unwrap().h3index())
+            ]
+        );
+        let values: Vec<_> = aggregated
+            .column("value")
+            .unwrap()
+            .f64()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(values, vec![Some(7.0), Some(8.0)]);
+
+        assert!(matches!(
+            change_resolution_aggregate(&df, "cell", 8),
+            Err(Error::ResolutionCoarserThanParent(7, 8))
+        ));
+    }
 }
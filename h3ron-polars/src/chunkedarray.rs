@@ -1,13 +1,14 @@
 use crate::iter::{
     iter_indexes_nonvalidated, iter_indexes_validated, NonValidatedIndexIter, ValidatedIndexIter,
 };
+use crate::validated::ValidatedIndexChunked;
 use crate::Error;
 use h3ron::{H3Cell, H3DirectedEdge, Index};
 use polars::export::arrow::bitmap::{Bitmap, MutableBitmap};
 use polars::prelude::{TakeRandom, UInt64Chunked};
 use std::marker::PhantomData;
 
-pub trait IndexValue: Index + TryFrom<u64, Error = h3ron::Error> + Clone {}
+pub trait IndexValue: Index + TryFrom<u64, Error = h3ron::Error> + Clone + Send + Sync {}
 
 impl IndexValue for H3Cell {}
 impl IndexValue for H3DirectedEdge {}
@@ -52,6 +53,15 @@ impl<'a, IX: IndexValue> IndexChunked<'a, IX> {
         mask.into()
     }
 
+    /// Validate all `Index` values of this array in a single pass.
+    ///
+    /// The returned [`ValidatedIndexChunked`] can be used with the same algorithms as this
+    /// array, but skips the validation of the values on each use. Invalid values do not cause
+    /// an error, they are treated like `null` values.
+    pub fn validate(&self) -> ValidatedIndexChunked<IX> {
+        ValidatedIndexChunked::new(self)
+    }
+
     pub fn to_collection<C>(&self) -> Result<C, Error>
     where
         C: FromIterator<IX>,
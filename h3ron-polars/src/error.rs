@@ -8,10 +8,48 @@ pub enum Error {
     Arrow(#[from] polars::error::ArrowError),
     #[error(transparent)]
     H3ron(#[from] h3ron::Error),
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[cfg(feature = "graph")]
+    #[error(transparent)]
+    Graph(#[from] h3ron_graph::Error),
 
     #[error("spatial indexing error: {0}")]
     SpatialIndex(String),
 
+    #[error("the number of distinct partition key values exceeds the limit of {0}")]
+    TooManyPartitions(usize),
+
+    #[error("the resolution {0} of a cell is coarser than the parent resolution {1}")]
+    ResolutionCoarserThanParent(u8, u8),
+
+    #[error("retraction of rows which have not been inserted")]
+    RetractionWithoutInsert,
+
+    #[error("the aggregates differ in their cell column, parent resolution or aggregations")]
+    IncompatibleAggregates,
+
+    #[error("invalid t-digest compression {0}, must be finite and at least 1")]
+    InvalidCompression(f64),
+
+    #[error("invalid quantile {0}, must be within [0, 1]")]
+    InvalidQuantile(f64),
+
+    #[error("invalid geojson: {0}")]
+    InvalidGeoJson(String),
+
+    #[error("invalid geojson feature at index {0}: {1}")]
+    InvalidGeoJsonFeature(usize, String),
+
+    #[error("the conversion results in {0} rows, exceeding the limit of {1} rows")]
+    ExpansionLimitExceeded(usize, usize),
+
     #[error("invalid h3indexes")]
     InvalidH3Indexes,
+
+    #[error("the arrays differ in their length: {0} and {1}")]
+    LengthMismatch(usize, usize),
+
+    #[error("{0}")]
+    MixedResolutions(crate::algorithm::chunkedarray::ResolutionMix),
 }
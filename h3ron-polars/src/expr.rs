@@ -0,0 +1,255 @@
+//! `h3` namespace for polars expressions.
+//!
+//! Brings the algorithms of this crate to the lazy API of polars:
+//!
+//! ```
+//! use polars::prelude::{col, df, IntoLazy, NamedFrom};
+//! use h3ron::{H3Cell, Index};
+//! use h3ron_polars::expr::H3Expr;
+//!
+//! let cell = H3Cell::from_coordinate((4.5, 1.3).into(), 6).unwrap();
+//! let df = df!("cell" => [cell.h3index()]).unwrap();
+//!
+//! let out = df
+//!     .lazy()
+//!     .select([
+//!         col("cell").h3().resolution().alias("resolution"),
+//!         col("cell").h3().parent(4).alias("parent"),
+//!     ])
+//!     .collect()
+//!     .unwrap();
+//! assert_eq!(out.shape(), (1, 2));
+//! ```
+//!
+//! All expressions of the namespace operate elementwise on `UInt64` columns of `H3Cell`s, so they
+//! do not block optimizations of the query plan like projection- or predicate pushdown. In the
+//! query plan they show up using their name - for example `col("cell").h3_resolution()`.
+//!
+//! Invalid cells and `null` values are handled the same way as in the algorithms
+//! of the [`algorithm::chunkedarray`](crate::algorithm::chunkedarray) module.
+//!
+//! # Python
+//!
+//! A python extension built on this crate can mirror this namespace by registering a
+//! custom expression namespace named `h3` (`polars.api.register_expr_namespace("h3")`) which
+//! forwards each method to the corresponding expression of this module.
+use crate::algorithm::chunkedarray::{H3ChangeResolution, H3GridDisk, H3IsValid, H3Resolution};
+use crate::{AsH3CellChunked, Error};
+use h3ron::error::check_valid_h3_resolution;
+use h3ron::Index;
+use polars::prelude::{
+    DataType, Expr, GetOutput, IntoSeries, PolarsError, UInt64Chunked, Utf8Chunked,
+};
+
+/// Access the `h3` namespace of an [`Expr`]
+pub trait H3Expr {
+    fn h3(self) -> H3NameSpace;
+}
+
+impl H3Expr for Expr {
+    fn h3(self) -> H3NameSpace {
+        H3NameSpace(self)
+    }
+}
+
+/// The `h3` namespace of an [`Expr`]. Created by [`H3Expr::h3`].
+pub struct H3NameSpace(Expr);
+
+impl H3NameSpace {
+    /// The resolution of the cells. `null` for invalid cells.
+    ///
+    /// See [`H3Resolution`].
+    pub fn resolution(self) -> Expr {
+        self.0
+            .map(
+                |s| Ok(s.u64()?.h3cell().h3_resolution().into_series()),
+                GetOutput::from_type(DataType::UInt8),
+            )
+            .with_fmt("h3_resolution")
+    }
+
+    /// The parent cell at `parent_resolution`. `null` for invalid cells and for cells
+    /// with a resolution coarser than `parent_resolution`.
+    pub fn parent(self, parent_resolution: u8) -> Expr {
+        self.0
+            .map(
+                move |s| {
+                    check_valid_h3_resolution(parent_resolution)
+                        .map_err(|e| to_polars_error(e.into()))?;
+                    let parents: UInt64Chunked = s
+                        .u64()?
+                        .h3cell()
+                        .iter_indexes_validated()
+                        .map(|maybe_cell| match maybe_cell {
+                            Some(Ok(cell)) => cell
+                                .get_parent(parent_resolution)
+                                .ok()
+                                .map(|parent| parent.h3index()),
+                            _ => None,
+                        })
+                        .collect();
+                    Ok(parents.into_series())
+                },
+                GetOutput::from_type(DataType::UInt64),
+            )
+            .with_fmt("h3_parent")
+    }
+
+    /// Changes the resolution of the cells. Results in a list of cells for each input cell.
+    ///
+    /// See [`H3ChangeResolution`].
+    pub fn change_resolution(self, target_resolution: u8) -> Expr {
+        self.0
+            .map(
+                move |s| {
+                    s.u64()?
+                        .h3cell()
+                        .h3_change_resolution(target_resolution)
+                        .map(|lc| lc.into_series())
+                        .map_err(to_polars_error)
+                },
+                GetOutput::from_type(DataType::List(Box::new(DataType::UInt64))),
+            )
+            .with_fmt("h3_change_resolution")
+    }
+
+    /// All cells within `k` distance of the cells. Results in a list of cells for
+    /// each input cell.
+    ///
+    /// See [`H3GridDisk`].
+    pub fn grid_disk(self, k: u32) -> Expr {
+        self.0
+            .map(
+                move |s| {
+                    s.u64()?
+                        .h3cell()
+                        .h3_grid_disk(k)
+                        .map(|lc| lc.into_series())
+                        .map_err(to_polars_error)
+                },
+                GetOutput::from_type(DataType::List(Box::new(DataType::UInt64))),
+            )
+            .with_fmt("h3_grid_disk")
+    }
+
+    /// Validity of the cells. `null` values stay `null`.
+    ///
+    /// See [`H3IsValid`].
+    pub fn is_valid(self) -> Expr {
+        self.0
+            .map(
+                |s| Ok(s.u64()?.h3cell().h3_is_valid().into_series()),
+                GetOutput::from_type(DataType::Boolean),
+            )
+            .with_fmt("h3_is_valid")
+    }
+
+    /// The hexadecimal string representation of the cells. `null` for invalid cells.
+    #[allow(clippy::wrong_self_convention, clippy::inherent_to_string)]
+    pub fn to_string(self) -> Expr {
+        self.0
+            .map(
+                |s| {
+                    let strings: Utf8Chunked = s
+                        .u64()?
+                        .h3cell()
+                        .iter_indexes_validated()
+                        .map(|maybe_cell| match maybe_cell {
+                            Some(Ok(cell)) => Some(format!("{:x}", cell.h3index())),
+                            _ => None,
+                        })
+                        .collect();
+                    Ok(strings.into_series())
+                },
+                GetOutput::from_type(DataType::Utf8),
+            )
+            .with_fmt("h3_to_string")
+    }
+}
+
+fn to_polars_error(e: Error) -> PolarsError {
+    match e {
+        Error::Polars(pe) => pe,
+        e => PolarsError::ComputeError(e.to_string().into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::chunkedarray::{H3IsValid, H3Resolution};
+    use crate::expr::H3Expr;
+    use crate::AsH3CellChunked;
+    use h3ron::{H3Cell, Index};
+    use polars::prelude::{
+        col, ChunkFilter, DataFrame, DataType, IntoLazy, NamedFrom, Series, TakeRandom,
+        UInt64Chunked,
+    };
+
+    #[test]
+    fn lazy_equals_eager() {
+        let ca = UInt64Chunked::from_iter(
+            H3Cell::from_coordinate((10.0, 20.0).into(), 6)
+                .unwrap()
+                .grid_disk(2)
+                .unwrap()
+                .iter()
+                .map(|cell| Some(cell.h3index()))
+                .chain([Some(55), None]), // invalid and null
+        );
+        let df = DataFrame::new(vec![Series::new("cell", ca.clone())]).unwrap();
+
+        let lf = df
+            .lazy()
+            .with_columns([
+                col("cell").h3().resolution().alias("resolution"),
+                col("cell").h3().is_valid().alias("is_valid"),
+                col("cell").h3().parent(4).alias("parent"),
+                col("cell").h3().parent(7).alias("finer_parent"),
+                col("cell").h3().grid_disk(1).alias("disk"),
+                col("cell").h3().to_string().alias("string"),
+            ])
+            .filter(col("is_valid"));
+
+        // the output types are known to the schema without executing the query and
+        // match the types of the collected columns
+        let schema = lf.schema().unwrap();
+        let lazy_result = lf.collect().unwrap();
+        for (name, dtype) in [
+            ("resolution", DataType::UInt8),
+            ("is_valid", DataType::Boolean),
+            ("parent", DataType::UInt64),
+            ("finer_parent", DataType::UInt64),
+            ("disk", DataType::List(Box::new(DataType::UInt64))),
+            ("string", DataType::Utf8),
+        ] {
+            assert_eq!(schema.get(name), Some(&dtype), "{}", name);
+            assert_eq!(
+                lazy_result.column(name).unwrap().dtype(),
+                &dtype,
+                "{}",
+                name
+            );
+        }
+
+        // eager equivalent
+        let filtered = ca.filter(&ca.h3cell().h3_is_valid()).unwrap();
+        let resolutions = filtered.h3cell().h3_resolution();
+        assert_eq!(lazy_result.height(), filtered.len());
+        assert_eq!(lazy_result.height(), ca.len() - 2);
+        assert_eq!(
+            lazy_result.column("finer_parent").unwrap().null_count(),
+            lazy_result.height()
+        );
+
+        let lazy_resolutions = lazy_result.column("resolution").unwrap().u8().unwrap();
+        let lazy_parents = lazy_result.column("parent").unwrap().u64().unwrap();
+        for i in 0..filtered.len() {
+            assert_eq!(lazy_resolutions.get(i), resolutions.get(i));
+            let expected_parent = H3Cell::new(filtered.get(i).unwrap())
+                .get_parent(4)
+                .unwrap()
+                .h3index();
+            assert_eq!(lazy_parents.get(i), Some(expected_parent));
+        }
+    }
+}
@@ -0,0 +1,359 @@
+//! Conversion of the routing results of [`h3ron_graph`] to [`DataFrame`]s.
+//!
+//! Requires the `graph` feature.
+use crate::Error;
+use h3ron::collections::{H3CellMap, HashMap};
+use h3ron::{H3Cell, H3DirectedEdge, Index};
+use h3ron_graph::algorithm::path::{DirectedEdgePath, Path};
+use h3ron_graph::graph::longedge::LongEdge;
+use num_traits::ToPrimitive;
+use polars::prelude::{
+    DataFrame, DataType, IntoSeries, ListBuilderTrait, ListPrimitiveChunkedBuilder, NamedFrom,
+    Series, UInt64Type,
+};
+
+/// The edges a route passes through.
+pub trait RouteEdges {
+    /// The directed edges of the route in the order they are traversed. [`LongEdge`]s are
+    /// expanded to the edges they consist of.
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error>;
+}
+
+impl RouteEdges for DirectedEdgePath {
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error> {
+        Ok(self.edges().to_vec())
+    }
+}
+
+impl RouteEdges for [H3DirectedEdge] {
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error> {
+        Ok(self.to_vec())
+    }
+}
+
+impl RouteEdges for &[H3DirectedEdge] {
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error> {
+        (*self).route_edges()
+    }
+}
+
+impl RouteEdges for Vec<H3DirectedEdge> {
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error> {
+        self.as_slice().route_edges()
+    }
+}
+
+impl RouteEdges for LongEdge {
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error> {
+        Ok(self.h3edge_path()?.collect())
+    }
+}
+
+impl RouteEdges for [LongEdge] {
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error> {
+        let mut edges = Vec::new();
+        for longedge in self.iter() {
+            edges.extend(longedge.h3edge_path()?);
+        }
+        Ok(edges)
+    }
+}
+
+impl RouteEdges for Vec<LongEdge> {
+    fn route_edges(&self) -> Result<Vec<H3DirectedEdge>, Error> {
+        self.as_slice().route_edges()
+    }
+}
+
+/// The result of routing from `origin_cell` to `destination_cell`.
+#[derive(Debug, Clone)]
+pub struct Route<P, W> {
+    pub origin_cell: H3Cell,
+    pub destination_cell: H3Cell,
+
+    /// The path of the route together with its total cost. `None` when no route
+    /// was found.
+    pub path: Option<(P, W)>,
+}
+
+impl<P, W> Route<P, W> {
+    /// A pair of cells without a route between them.
+    pub const fn unroutable(origin_cell: H3Cell, destination_cell: H3Cell) -> Self {
+        Self {
+            origin_cell,
+            destination_cell,
+            path: None,
+        }
+    }
+}
+
+impl<W> From<Path<W>> for Route<DirectedEdgePath, W> {
+    fn from(path: Path<W>) -> Self {
+        Self {
+            origin_cell: path.origin_cell,
+            destination_cell: path.destination_cell,
+            path: Some((path.directed_edge_path, path.cost)),
+        }
+    }
+}
+
+/// Convert `routes` to a [`DataFrame`] with one row per route and the columns
+///
+/// * `origin` (`u64`): the origin cell.
+/// * `destination` (`u64`): the destination cell.
+/// * `cost` (`f64`): the total cost of the route.
+/// * `path_edges` (`List[u64]`): the directed edges of the route, see [`RouteEdges`].
+/// * `path_length_edges` (`u32`): the number of edges in `path_edges`.
+///
+/// Unroutable pairs of cells result in `null` values in all columns except `origin` and
+/// `destination`. Costs which can not be represented as `f64` result in `null`.
+pub fn routes_to_dataframe<I, P, W>(routes: I) -> Result<DataFrame, Error>
+where
+    I: IntoIterator<Item = Route<P, W>>,
+    P: RouteEdges,
+    W: ToPrimitive,
+{
+    let routes = routes.into_iter();
+    let capacity = routes.size_hint().0;
+    let mut origins = Vec::with_capacity(capacity);
+    let mut destinations = Vec::with_capacity(capacity);
+    let mut costs = Vec::with_capacity(capacity);
+    // a typed builder keeps the type and the length of the list column even when no
+    // route was found at all
+    let mut path_edges = ListPrimitiveChunkedBuilder::<UInt64Type>::new(
+        "path_edges",
+        capacity,
+        capacity * 8,
+        DataType::UInt64,
+    );
+    let mut path_lengths = Vec::with_capacity(capacity);
+
+    for route in routes {
+        origins.push(route.origin_cell.h3index());
+        destinations.push(route.destination_cell.h3index());
+        match route.path {
+            Some((path, cost)) => {
+                let edges: Vec<_> = path
+                    .route_edges()?
+                    .iter()
+                    .map(|edge| edge.h3index())
+                    .collect();
+                costs.push(cost.to_f64());
+                path_lengths.push(Some(edges.len() as u32));
+                path_edges.append_slice(Some(&edges));
+            }
+            None => {
+                costs.push(None);
+                path_lengths.push(None);
+                path_edges.append_null();
+            }
+        }
+    }
+
+    Ok(DataFrame::new(vec![
+        Series::new("origin", origins),
+        Series::new("destination", destinations),
+        Series::new("cost", costs),
+        path_edges.finish().into_series(),
+        Series::new("path_length_edges", path_lengths),
+    ])?)
+}
+
+/// Convert the `paths` found by
+/// [`ShortestPathManyToMany`](h3ron_graph::algorithm::ShortestPathManyToMany) to a [`DataFrame`]
+/// as described for [`routes_to_dataframe`].
+///
+/// The dataframe contains a row for each combination of `origin_cells` and `destination_cells`
+/// in the order of both slices, so pairs without a path are included as unroutable.
+pub fn shortest_paths_to_dataframe<W>(
+    origin_cells: &[H3Cell],
+    destination_cells: &[H3Cell],
+    paths: &H3CellMap<Vec<Path<W>>>,
+) -> Result<DataFrame, Error>
+where
+    W: ToPrimitive + Copy,
+{
+    let mut routes = Vec::with_capacity(origin_cells.len() * destination_cells.len());
+    for origin_cell in origin_cells {
+        let paths_by_destination: HashMap<_, _> = paths
+            .get(origin_cell)
+            .map(|origin_paths| {
+                origin_paths
+                    .iter()
+                    .map(|path| (path.destination_cell, path))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        for destination_cell in destination_cells {
+            routes.push(match paths_by_destination.get(destination_cell) {
+                Some(path) => Route {
+                    origin_cell: *origin_cell,
+                    destination_cell: *destination_cell,
+                    path: Some((path.directed_edge_path.edges(), path.cost)),
+                },
+                None => Route::unroutable(*origin_cell, *destination_cell),
+            });
+        }
+    }
+    routes_to_dataframe(routes)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::{routes_to_dataframe, shortest_paths_to_dataframe, Route};
+    use h3ron::{H3Cell, H3DirectedEdge, Index};
+    use h3ron_graph::algorithm::shortest_path::{
+        DefaultShortestPathOptions, ShortestPathManyToMany,
+    };
+    use h3ron_graph::graph::longedge::LongEdge;
+    use h3ron_graph::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use polars::prelude::{DataType, TakeRandom};
+
+    /// a line of cells connected by edges in both directions
+    fn line_fixture() -> (Vec<H3Cell>, PreparedH3EdgeGraph<u32>) {
+        let res = 8;
+        let start = H3Cell::from_coordinate((23.3, 12.3).into(), res).unwrap();
+        let end = H3Cell::from_coordinate((23.35, 12.32).into(), res).unwrap();
+        let cells: Vec<_> = h3ron::grid_path_cells(start, end).unwrap().iter().collect();
+
+        let mut graph = H3EdgeGraph::new(res);
+        for window in cells.windows(2) {
+            graph
+                .add_edge(window[0].directed_edge_to(window[1]).unwrap(), 3_u32)
+                .unwrap();
+            graph
+                .add_edge(window[1].directed_edge_to(window[0]).unwrap(), 3_u32)
+                .unwrap();
+        }
+        (cells, graph.try_into().unwrap())
+    }
+
+    #[test]
+    fn shortest_paths_with_unreachable_destinations() {
+        let (cells, graph) = line_fixture();
+        let origin = cells[0];
+        let far_away = H3Cell::from_coordinate((-40.0, -20.0).into(), 8).unwrap();
+        let disconnected = H3Cell::from_coordinate((23.5, 12.6).into(), 8).unwrap();
+        let destinations = vec![cells[3], far_away, *cells.last().unwrap(), disconnected];
+
+        let paths = graph
+            .shortest_path_many_to_many(
+                &vec![origin],
+                &destinations,
+                &DefaultShortestPathOptions::default(),
+            )
+            .unwrap();
+        let df = shortest_paths_to_dataframe(&[origin], &destinations, &paths).unwrap();
+
+        assert_eq!(
+            df.get_column_names(),
+            vec![
+                "origin",
+                "destination",
+                "cost",
+                "path_edges",
+                "path_length_edges"
+            ]
+        );
+        assert_eq!(
+            df.dtypes(),
+            vec![
+                DataType::UInt64,
+                DataType::UInt64,
+                DataType::Float64,
+                DataType::List(Box::new(DataType::UInt64)),
+                DataType::UInt32
+            ]
+        );
+        assert_eq!(df.height(), 4);
+
+        let destination = df.column("destination").unwrap().u64().unwrap();
+        let cost = df.column("cost").unwrap().f64().unwrap();
+        let path_edges = df.column("path_edges").unwrap().list().unwrap();
+        let path_length = df.column("path_length_edges").unwrap().u32().unwrap();
+        for (i, destination_cell) in destinations.iter().enumerate() {
+            assert_eq!(destination.get(i), Some(destination_cell.h3index()));
+        }
+
+        assert_eq!(cost.get(0), Some(9.0));
+        assert_eq!(path_length.get(0), Some(3));
+        let edges: Vec<_> = path_edges
+            .get(0)
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_no_null_iter()
+            .map(H3DirectedEdge::new)
+            .collect();
+        assert_eq!(edges.len(), 3);
+        assert_eq!(edges[0].origin_cell().unwrap(), origin);
+        assert_eq!(edges[2].destination_cell().unwrap(), cells[3]);
+
+        assert_eq!(
+            path_length.get(2),
+            Some((cells.len() - 1) as u32),
+            "the complete line"
+        );
+        for i in [1, 3] {
+            assert_eq!(cost.get(i), None);
+            assert!(path_edges.get(i).is_none());
+            assert_eq!(path_length.get(i), None);
+        }
+    }
+
+    #[test]
+    fn longedge_routes_are_expanded() {
+        let (cells, _) = line_fixture();
+        let longedge = LongEdge::try_from_cell_path(cells.iter().copied(), false).unwrap();
+        let routes = vec![
+            Route {
+                origin_cell: cells[0],
+                destination_cell: *cells.last().unwrap(),
+                path: Some((vec![longedge], 12_u32)),
+            },
+            Route::unroutable(cells[0], cells[1]),
+        ];
+        let df = routes_to_dataframe(routes).unwrap();
+        assert_eq!(df.height(), 2);
+        assert_eq!(
+            df.column("path_length_edges")
+                .unwrap()
+                .u32()
+                .unwrap()
+                .get(0),
+            Some((cells.len() - 1) as u32)
+        );
+        assert_eq!(df.column("cost").unwrap().f64().unwrap().get(0), Some(12.0));
+        assert_eq!(
+            df.column("path_length_edges")
+                .unwrap()
+                .u32()
+                .unwrap()
+                .get(1),
+            None
+        );
+    }
+
+    #[test]
+    fn all_routes_unroutable() {
+        let (cells, _) = line_fixture();
+        let df = routes_to_dataframe(vec![
+            Route::<Vec<LongEdge>, u32>::unroutable(cells[0], cells[1]),
+            Route::unroutable(cells[0], cells[2]),
+            Route::unroutable(cells[1], cells[2]),
+        ])
+        .unwrap();
+        assert_eq!(df.height(), 3);
+
+        let path_edges = df.column("path_edges").unwrap();
+        assert_eq!(
+            path_edges.dtype(),
+            &DataType::List(Box::new(DataType::UInt64))
+        );
+        assert_eq!(path_edges.null_count(), 3);
+        for column_name in ["cost", "path_length_edges"] {
+            assert_eq!(df.column(column_name).unwrap().null_count(), 3);
+        }
+    }
+}
@@ -1,11 +1,16 @@
 pub mod algorithm;
 pub mod chunkedarray;
 pub mod error;
+pub mod expr;
 pub mod frame;
 pub mod from;
+#[cfg(feature = "graph")]
+pub mod graph;
 pub mod iter;
 pub mod spatial_index;
+pub mod validated;
 
 pub use chunkedarray::*;
 pub use error::Error;
 pub use from::*;
+pub use validated::*;
@@ -0,0 +1,426 @@
+use crate::algorithm::chunkedarray::sphere_tree::MEAN_EARTH_RADIUS_M;
+use crate::spatial_index::{
+    antimeridian_query_rects, distance_squared_to_rect, finish_mask, finish_positions,
+    nearest_k_sorted, negative_mask, rects_intersect, NearestKImpl, RectIndexable, RectSIKind,
+    SpatialIndex,
+};
+use crate::{AsH3IndexChunked, Error, IndexChunked, IndexValue, ValidIndexes};
+use geo_types::{Coordinate, Rect};
+use h3ron::collections::H3CellMap;
+use h3ron::curve::{cell_to_curve_key, curve_range_for_cell};
+use h3ron::error::check_valid_h3_resolution;
+use h3ron::{H3Cell, H3DirectedEdge, Index, ToH3Cells};
+use polars::export::arrow::bitmap::MutableBitmap;
+use polars::prelude::{BooleanChunked, IdxCa, UInt64Chunked};
+use polars_core::prelude::TakeRandom;
+use std::marker::PhantomData;
+
+/// distance in average edge lengths of the index resolution the query rects get enlarged by
+/// before covering them with parent cells. Covers the descendants of parents located outside
+/// of the query rect, as well as their envelopes.
+const COVERING_MARGIN_EDGES: f64 = 4.0;
+
+/// latitude up to which query rects get covered with parent cells. All parents are checked
+/// for queries reaching closer to the poles.
+const MAX_COVERING_LATITUDE: f64 = 85.0;
+
+/// initial radius of the searches for the nearest elements
+const NEAREST_K_INITIAL_RADIUS: f64 = 0.01;
+
+/// radius covering all coordinates of the WGS84 coordinate space
+const NEAREST_K_MAX_RADIUS: f64 = 405.0;
+
+pub trait HierarchyIndexable {
+    /// the cell used to locate the element within the h3 hierarchy
+    fn hierarchy_cell(&self) -> Result<H3Cell, Error>;
+}
+
+impl HierarchyIndexable for H3Cell {
+    fn hierarchy_cell(&self) -> Result<H3Cell, Error> {
+        Ok(*self)
+    }
+}
+
+impl HierarchyIndexable for H3DirectedEdge {
+    fn hierarchy_cell(&self) -> Result<H3Cell, Error> {
+        Ok(self.origin_cell()?)
+    }
+}
+
+pub trait BuildH3HierarchyIndex<IX: IndexValue> {
+    /// Build a [`H3HierarchyIndex`] grouping the elements by their parent cell at `h3_resolution`.
+    fn h3_hierarchy_index(&self, h3_resolution: u8) -> Result<H3HierarchyIndex<IX>, Error>;
+}
+
+impl<IX, T> BuildH3HierarchyIndex<IX> for T
+where
+    IX: IndexValue + HierarchyIndexable,
+    T: ValidIndexes<IX>,
+{
+    fn h3_hierarchy_index(&self, h3_resolution: u8) -> Result<H3HierarchyIndex<IX>, Error> {
+        check_valid_h3_resolution(h3_resolution)?;
+        let mut positions_by_parent: H3CellMap<Vec<usize>> = H3CellMap::default();
+        for (position, maybe_index) in self.iter_valid_indexes().enumerate() {
+            if let Some(index) = maybe_index {
+                let cell = index.hierarchy_cell()?;
+                let parent = if cell.resolution() > h3_resolution {
+                    cell.get_parent(h3_resolution)?
+                } else {
+                    cell
+                };
+                positions_by_parent
+                    .entry(parent)
+                    .or_default()
+                    .push(position);
+            }
+        }
+
+        let mut coarse_cells: Vec<_> = positions_by_parent
+            .keys()
+            .filter(|cell| cell.resolution() < h3_resolution)
+            .copied()
+            .collect();
+        coarse_cells.sort_unstable();
+
+        let mut parents_by_curve_key: Vec<_> = positions_by_parent
+            .keys()
+            .map(|cell| (cell_to_curve_key(*cell), *cell))
+            .collect();
+        parents_by_curve_key.sort_unstable();
+
+        Ok(H3HierarchyIndex {
+            index_phantom: PhantomData::<IX>,
+            chunked_array: self.chunked_array().clone(),
+            h3_resolution,
+            positions_by_parent,
+            coarse_cells,
+            parents_by_curve_key,
+        })
+    }
+}
+
+/// Spatial index grouping the elements by their parent cell at a coarser resolution.
+///
+/// Building the index requires a single pass over the elements without calculating any
+/// geometries. Elements coarser than the resolution of the index are grouped under their own
+/// cell and checked on every query. Edges are grouped by their origin cell.
+///
+/// Queries for rects cover the rect with the cells at the resolution of the index and only
+/// check the envelopes of the elements grouped under these cells. Rects too large for
+/// this - or reaching the poles or the antimeridian - check the envelopes of all elements. The
+/// direct hierarchical query [`H3HierarchyIndex::rows_under_cells`] does not use any geometries
+/// at all. It locates the parent cells within the range of the [curve keys](h3ron::curve) of
+/// each queried cell.
+///
+/// # Example
+///
+/// ```
+/// use geo_types::Rect;
+/// use polars::prelude::{TakeRandom, UInt64Chunked};
+/// use h3ron::{H3Cell, Index};
+/// use h3ron_polars::{AsH3CellChunked, NamedFromIndexes};
+/// use h3ron_polars::spatial_index::{BuildH3HierarchyIndex, SpatialIndex};
+///
+/// let cell = H3Cell::from_coordinate((45.5, 45.5).into(), 9).unwrap();
+/// let uc = UInt64Chunked::new_from_indexes(
+///     "",
+///     vec![
+///         cell,
+///         H3Cell::from_coordinate((-60.5, -60.5).into(), 7).unwrap(),
+///         H3Cell::from_coordinate((120.5, 70.5).into(), 7).unwrap(),
+///     ],
+/// );
+///
+/// let idx = uc.h3cell().h3_hierarchy_index(5).unwrap();
+///
+/// let positions = idx.rows_under_cells(&[cell.get_parent(4).unwrap()]).unwrap();
+/// assert_eq!(positions.len(), 1);
+/// assert_eq!(positions.get(0), Some(0));
+///
+/// let mask = idx.envelopes_intersect(&Rect::new((45.0, 45.0), (46.0, 46.0)));
+/// assert_eq!(mask.get(0), Some(true));
+/// assert_eq!(mask.get(1), Some(false));
+/// ```
+pub struct H3HierarchyIndex<IX: IndexValue> {
+    index_phantom: PhantomData<IX>,
+    chunked_array: UInt64Chunked,
+    h3_resolution: u8,
+
+    /// positions of the elements in `chunked_array` by their parent cell
+    positions_by_parent: H3CellMap<Vec<usize>>,
+
+    /// the keys of `positions_by_parent` coarser than `h3_resolution`
+    coarse_cells: Vec<H3Cell>,
+
+    /// the keys of `positions_by_parent` sorted by their curve key, so the descendants of
+    /// a cell are located in a contiguous slice.
+    parents_by_curve_key: Vec<(u64, H3Cell)>,
+}
+
+impl<IX: IndexValue> H3HierarchyIndex<IX> {
+    /// the resolution of the parent cells the elements are grouped by
+    pub const fn h3_resolution(&self) -> u8 {
+        self.h3_resolution
+    }
+
+    /// The positions of the elements located within any of `cells` in the h3 hierarchy - so
+    /// the elements equal to one of `cells` or descendants of them.
+    ///
+    /// The positions are sorted ascending, contain no duplicates and can be passed to
+    /// `DataFrame::take`.
+    pub fn rows_under_cells(&self, cells: &[H3Cell]) -> Result<IdxCa, Error>
+    where
+        IX: HierarchyIndexable,
+    {
+        let ic = self.chunked_array.h3indexchunked::<IX>();
+        let mut positions = vec![];
+        for cell in cells {
+            let resolution = cell.resolution();
+            if resolution > self.h3_resolution {
+                // only some of the elements of the parent are descendants of the cell
+                let parent = cell.get_parent(self.h3_resolution)?;
+                self.extend_with_descendants(&ic, &parent, cell, &mut positions)?;
+                continue;
+            }
+
+            let range = curve_range_for_cell(*cell);
+            let start = self
+                .parents_by_curve_key
+                .partition_point(|(key, _)| key < range.start());
+            for (_, parent) in self.parents_by_curve_key[start..]
+                .iter()
+                .take_while(|(key, _)| range.contains(key))
+            {
+                // ancestors of the cell may share the key of the cell
+                if parent.resolution() >= resolution {
+                    positions.extend_from_slice(&self.positions_by_parent[parent]);
+                }
+            }
+        }
+        Ok(finish_positions(positions))
+    }
+
+    /// add the positions of the elements grouped under `parent` which are descendants of `cell`.
+    fn extend_with_descendants(
+        &self,
+        ic: &IndexChunked<IX>,
+        parent: &H3Cell,
+        cell: &H3Cell,
+        positions: &mut Vec<usize>,
+    ) -> Result<(), Error>
+    where
+        IX: HierarchyIndexable,
+    {
+        if let Some(parent_positions) = self.positions_by_parent.get(parent) {
+            for position in parent_positions {
+                if let Some(index) = ic.get(*position) {
+                    let element_cell = index.hierarchy_cell()?;
+                    if element_cell.resolution() >= cell.resolution()
+                        && element_cell.get_parent(cell.resolution())? == *cell
+                    {
+                        positions.push(*position);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The cells at the resolution of the index covering `rect` including a margin. `None` when
+    /// the rect can not be covered or the covering would contain more cells than the index.
+    fn covering_cells(&self, rect: &Rect) -> Option<Vec<H3Cell>> {
+        let margin_m =
+            COVERING_MARGIN_EDGES * H3DirectedEdge::edge_length_avg_m(self.h3_resolution).ok()?;
+        let margin_lat = (margin_m / MEAN_EARTH_RADIUS_M).to_degrees();
+        let (min_y, max_y) = (rect.min().y - margin_lat, rect.max().y + margin_lat);
+        if min_y <= -MAX_COVERING_LATITUDE || max_y >= MAX_COVERING_LATITUDE {
+            return None;
+        }
+        let margin_lon = margin_lat / min_y.abs().max(max_y.abs()).to_radians().cos();
+        let (min_x, max_x) = (rect.min().x - margin_lon, rect.max().x + margin_lon);
+        if min_x <= -180.0 || max_x >= 180.0 {
+            return None;
+        }
+
+        // area of the rect on the sphere
+        let area_m2 = MEAN_EARTH_RADIUS_M.powi(2)
+            * (max_x - min_x).to_radians()
+            * (max_y.to_radians().sin() - min_y.to_radians().sin());
+        let estimated_num_cells = area_m2 / H3Cell::area_avg_m2(self.h3_resolution).ok()?;
+        if estimated_num_cells > self.positions_by_parent.len() as f64 {
+            return None;
+        }
+
+        Rect::new((min_x, min_y), (max_x, max_y))
+            .to_polygon()
+            .to_h3_cells(self.h3_resolution)
+            .ok()
+            .map(|cells| cells.iter().collect())
+    }
+
+    /// positions of the elements which may have an envelope intersecting `rect`.
+    fn candidate_positions(&self, rect: &Rect) -> Vec<usize> {
+        let mut positions = vec![];
+        match self.covering_cells(rect) {
+            Some(cells) => {
+                for cell in cells.iter().chain(self.coarse_cells.iter()) {
+                    if let Some(parent_positions) = self.positions_by_parent.get(cell) {
+                        positions.extend_from_slice(parent_positions);
+                    }
+                }
+            }
+            None => {
+                for parent_positions in self.positions_by_parent.values() {
+                    positions.extend_from_slice(parent_positions);
+                }
+            }
+        }
+        positions
+    }
+
+    /// the envelope of the element at `position`.
+    fn envelope(&self, ic: &IndexChunked<IX>, position: usize) -> Option<Rect>
+    where
+        IX: RectIndexable,
+    {
+        ic.get(position)
+            .and_then(|index| index.spatial_index_rect().ok().flatten())
+    }
+}
+
+impl<IX> SpatialIndex<IX, RectSIKind> for H3HierarchyIndex<IX>
+where
+    IX: IndexValue + RectIndexable,
+{
+    fn h3indexchunked(&self) -> IndexChunked<IX> {
+        self.chunked_array.h3indexchunked()
+    }
+
+    fn envelopes_intersect_impl(&self, rect: &Rect) -> MutableBitmap {
+        let mut mask = negative_mask(&self.chunked_array);
+        for position in self.envelopes_intersect_positions_impl(rect) {
+            mask.set(position, true);
+        }
+        mask
+    }
+
+    fn envelopes_intersect_positions_impl(&self, rect: &Rect) -> Vec<usize> {
+        let ic = self.h3indexchunked();
+        let query_rects = antimeridian_query_rects(rect);
+        self.candidate_positions(rect)
+            .into_iter()
+            .filter(|position| {
+                self.envelope(&ic, *position).is_some_and(|envelope| {
+                    query_rects
+                        .iter()
+                        .any(|query_rect| rects_intersect(&envelope, query_rect))
+                })
+            })
+            .collect()
+    }
+
+    fn envelopes_within_distance(&self, coord: Coordinate, distance: f64) -> BooleanChunked {
+        let ic = self.h3indexchunked();
+        let mut mask = negative_mask(&self.chunked_array);
+        let query_rect = Rect::new(
+            (coord.x - distance, coord.y - distance),
+            (coord.x + distance, coord.y + distance),
+        );
+        for position in self.candidate_positions(&query_rect) {
+            if let Some(envelope) = self.envelope(&ic, position) {
+                if distance_squared_to_rect(coord, &envelope).sqrt() <= distance {
+                    mask.set(position, true);
+                }
+            }
+        }
+        finish_mask(mask.into(), &ic)
+    }
+}
+
+impl<IX> NearestKImpl for H3HierarchyIndex<IX>
+where
+    IX: IndexValue + RectIndexable,
+{
+    /// The distance to the envelopes is checked within a radius around `coord`, which gets
+    /// doubled until at least `k` elements are found.
+    fn nearest_k_impl(&self, coord: Coordinate, k: usize) -> Vec<(usize, f64)> {
+        if k == 0 {
+            return vec![];
+        }
+        let ic = self.h3indexchunked();
+        let mut radius = NEAREST_K_INITIAL_RADIUS;
+        loop {
+            let query_rect = Rect::new(
+                (coord.x - radius, coord.y - radius),
+                (coord.x + radius, coord.y + radius),
+            );
+            let mut found = vec![];
+            for position in self.candidate_positions(&query_rect) {
+                if let Some(envelope) = self.envelope(&ic, position) {
+                    let distance = distance_squared_to_rect(coord, &envelope).sqrt();
+                    if distance <= radius {
+                        found.push((position, distance));
+                    }
+                }
+            }
+            if found.len() >= k || radius > NEAREST_K_MAX_RADIUS {
+                return nearest_k_sorted(found, k);
+            }
+            radius *= 2.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::spatial_index::{BuildH3HierarchyIndex, H3HierarchyIndex};
+    use crate::IndexChunked;
+    use h3ron::H3Cell;
+
+    fn build_index(cc: &IndexChunked<H3Cell>) -> H3HierarchyIndex<H3Cell> {
+        cc.h3_hierarchy_index(5).unwrap()
+    }
+    crate::spatial_index::tests::impl_std_tests!(build_index);
+    crate::spatial_index::tests::impl_antimeridian_tests!(build_index);
+
+    #[test]
+    fn rows_under_cells_mixed_resolutions() {
+        let cell = H3Cell::from_coordinate((10.2, 50.3).into(), 9).unwrap();
+        let cells: Vec<_> = [3, 5, 7]
+            .iter()
+            .map(|resolution| cell.get_parent(*resolution).unwrap())
+            .chain([
+                cell,
+                H3Cell::from_coordinate((-60.5, -60.5).into(), 7).unwrap(),
+            ])
+            .collect();
+        let mut values: Vec<_> = cells.iter().map(|cell| Some(cell.h3index())).collect();
+        values.insert(2, None);
+        let ca = UInt64Chunked::new("", values);
+        let idx = ca.h3cell().h3_hierarchy_index(5).unwrap();
+        assert_eq!(idx.h3_resolution(), 5);
+
+        let positions = |query: &[H3Cell]| -> Vec<_> {
+            idx.rows_under_cells(query)
+                .unwrap()
+                .into_no_null_iter()
+                .collect()
+        };
+        // everything besides the last cell is located within the res 3 cell
+        assert_eq!(positions(&cells[..1]), vec![0, 1, 3, 4]);
+        assert_eq!(positions(&[cells[1]]), vec![1, 3, 4]);
+        assert_eq!(positions(&[cells[2]]), vec![3, 4]);
+        assert_eq!(positions(&[cells[3]]), vec![4]);
+        assert_eq!(
+            positions(&[cells[3].get_parent(8).unwrap(), cells[4]]),
+            vec![4, 5]
+        );
+        // the res 0 cell contains more children than the index contains parents
+        assert_eq!(
+            positions(&[cells[0].get_parent(0).unwrap()]),
+            vec![0, 1, 3, 4]
+        );
+        assert_eq!(positions(&[cells[4].get_parent(1).unwrap()]), vec![5]);
+        assert!(positions(&[]).is_empty());
+    }
+}
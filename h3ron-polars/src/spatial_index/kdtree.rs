@@ -1,14 +1,22 @@
 use crate::spatial_index::{
-    finish_mask, negative_mask, CoordinateIndexable, CoordinateSIKind, SpatialIndex,
+    antimeridian_query_rects, finish_mask, nearest_k_sorted, negative_mask,
+    validate_containment_of_coordinate, CoordinateContainment, CoordinateIndexable,
+    CoordinateSIKind, NearestKImpl, SpatialIndex,
 };
-use crate::{AsH3IndexChunked, IndexChunked, IndexValue};
+use crate::{AsH3IndexChunked, Error, IndexChunked, IndexValue, ValidIndexes};
 use geo_types::{Coordinate, Rect};
 use kdbush::{KDBush, PointReader};
 use polars::export::arrow::bitmap::MutableBitmap;
 use polars::prelude::BooleanChunked;
-use polars_core::prelude::UInt64Chunked;
+use polars_core::prelude::{TakeRandom, UInt64Chunked};
 use std::marker::PhantomData;
 
+/// initial radius of the searches for the nearest elements
+const NEAREST_K_INITIAL_RADIUS: f64 = 0.01;
+
+/// radius covering all coordinates of the WGS84 coordinate space
+const NEAREST_K_MAX_RADIUS: f64 = 405.0;
+
 struct Points(Vec<(usize, Coordinate)>);
 
 impl PointReader for Points {
@@ -69,19 +77,21 @@ where
     fn kdtree_index_with_node_size(&self, node_size: u8) -> KDTreeIndex<IX>;
 }
 
-impl<'a, IX: IndexValue> BuildKDTreeIndex<'a, IX> for IndexChunked<'a, IX>
+impl<'a, IX, T> BuildKDTreeIndex<'a, IX> for T
 where
-    IX: CoordinateIndexable,
+    IX: IndexValue + CoordinateIndexable,
+    T: ValidIndexes<IX>,
 {
     fn kdtree_index_with_node_size(&self, node_size: u8) -> KDTreeIndex<IX> {
         // KDBush requires at least one entry to be successful build, so we need to inspect
         // the data first.
         let entries: Vec<_> = self
-            .iter_indexes_validated()
+            .iter_valid_indexes()
             .enumerate()
-            .filter_map(|(pos, maybe_index)| match maybe_index {
-                Some(Ok(index)) => index.spatial_index_coordinate().ok().map(|c| (pos, c)),
-                _ => None,
+            .filter_map(|(pos, maybe_index)| {
+                maybe_index
+                    .and_then(|index| index.spatial_index_coordinate().ok())
+                    .map(|c| (pos, c))
             })
             .collect();
 
@@ -96,7 +106,7 @@ where
 
             // clones of arrow-backed arrays are cheap, so we clone this for the benefit of not
             // requiring a lifetime dependency
-            chunked_array: self.chunked_array.clone(),
+            chunked_array: self.chunked_array().clone(),
 
             kdbush,
         }
@@ -145,17 +155,50 @@ where
     fn envelopes_intersect_impl(&self, rect: &Rect) -> MutableBitmap {
         let mut mask = negative_mask(&self.chunked_array);
         if let Some(kdbush) = self.kdbush.as_ref() {
-            kdbush.range(
-                rect.min().x,
-                rect.min().y,
-                rect.max().x,
-                rect.max().y,
-                |id| mask.set(id, true),
-            );
+            for query_rect in antimeridian_query_rects(rect) {
+                kdbush.range(
+                    query_rect.min().x,
+                    query_rect.min().y,
+                    query_rect.max().x,
+                    query_rect.max().y,
+                    |id| mask.set(id, true),
+                );
+            }
         }
         mask
     }
 
+    fn envelopes_intersect_positions_impl(&self, rect: &Rect) -> Vec<usize> {
+        let mut positions = vec![];
+        if let Some(kdbush) = self.kdbush.as_ref() {
+            for query_rect in antimeridian_query_rects(rect) {
+                kdbush.range(
+                    query_rect.min().x,
+                    query_rect.min().y,
+                    query_rect.max().x,
+                    query_rect.max().y,
+                    |id| positions.push(id),
+                );
+            }
+        }
+        positions
+    }
+
+    /// The indexed elements contain the given [Coordinate] `coord`.
+    ///
+    /// This index only knows the coordinates of the elements, so the containment gets checked
+    /// for all elements.
+    fn contains_coordinate(&self, coord: Coordinate) -> Result<BooleanChunked, Error>
+    where
+        IX: CoordinateContainment,
+    {
+        let ic = self.h3indexchunked();
+        let mut candidates = MutableBitmap::new();
+        candidates.extend_constant(self.chunked_array.len(), true);
+        let mask = validate_containment_of_coordinate(candidates, &ic, coord)?;
+        Ok(finish_mask(mask.into(), &ic))
+    }
+
     fn envelopes_within_distance(&self, coord: Coordinate, distance: f64) -> BooleanChunked {
         let mut mask = negative_mask(&self.chunked_array);
         if let Some(kdbush) = self.kdbush.as_ref() {
@@ -165,6 +208,34 @@ where
     }
 }
 
+impl<IX: IndexValue> NearestKImpl for KDTreeIndex<IX>
+where
+    IX: CoordinateIndexable,
+{
+    /// KDBush only supports radius searches, so the radius gets doubled until at least
+    /// `k` elements are found.
+    fn nearest_k_impl(&self, coord: Coordinate, k: usize) -> Vec<(usize, f64)> {
+        let kdbush = match self.kdbush.as_ref() {
+            Some(kdbush) if k > 0 => kdbush,
+            _ => return vec![],
+        };
+        let ic = self.h3indexchunked();
+        let mut radius = NEAREST_K_INITIAL_RADIUS;
+        loop {
+            let mut found = vec![];
+            kdbush.within(coord.x, coord.y, radius, |id| {
+                if let Some(Ok(index_coord)) = ic.get(id).map(|ix| ix.spatial_index_coordinate()) {
+                    found.push((id, (index_coord.x - coord.x).hypot(index_coord.y - coord.y)));
+                }
+            });
+            if found.len() >= k || radius > NEAREST_K_MAX_RADIUS {
+                return nearest_k_sorted(found, k);
+            }
+            radius *= 2.0;
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::spatial_index::{BuildKDTreeIndex, KDTreeIndex};
@@ -175,4 +246,18 @@ mod test {
         cc.kdtree_index()
     }
     crate::spatial_index::tests::impl_std_tests!(build_index);
+
+    #[test]
+    fn positions_after_null_rows() {
+        let cell = H3Cell::from_coordinate((45.5, 45.5).into(), 7).unwrap();
+        let ca = UInt64Chunked::new("", &[None, Some(55), None, Some(cell.h3index())]);
+        let idx = ca.h3cell().kdtree_index();
+
+        let mask = idx.envelopes_within_distance((45.5, 45.5).into(), 0.1);
+        assert_eq!(mask.len(), 4);
+        assert_eq!(mask.get(0), None);
+        assert_eq!(mask.get(1), None);
+        assert_eq!(mask.get(2), None);
+        assert_eq!(mask.get(3), Some(true));
+    }
 }
@@ -6,6 +6,11 @@
 //! * `KDTreeIndex`: Fast to create and query, works only on centroids.
 //! * `PackedHilbertRTreeIndex`: Still fast to create and query, works on envelopes. Creation is a bit slower than `KDTreeIndex`
 //! * `RTreeIndex`: Also envelope based.
+//! * `PartitionedSpatialIndex`: One `PackedHilbertRTreeIndex` per distinct value of a partition key,
+//!   allowing to restrict queries to a single partition.
+//! * `H3HierarchyIndex`: Groups the elements by their parent cells. Very fast to create as no geometries
+//!   are required, works on envelopes. Additionally allows querying the elements located within cells of the
+//!   H3 hierarchy.
 //!
 //! All of the above spatial indexes provide a second stage which can perform fine-grained filtering
 //! by doing geometry intersections on the returned index-matches using the exact geometry of the indexed
@@ -23,18 +28,27 @@ pub mod rtree;
 #[cfg(feature = "si_packed_hilbert_rtree")]
 pub mod packed_hilbert_rtree;
 
+#[cfg(feature = "si_packed_hilbert_rtree")]
+pub mod partitioned;
+
+pub mod hierarchy;
+
 #[cfg(test)]
 pub(crate) mod tests;
 
+use crate::algorithm::chunkedarray::sphere_tree::MEAN_EARTH_RADIUS_M;
 use crate::{Error, IndexChunked, IndexValue};
+use geo::algorithm::haversine_distance::HaversineDistance;
 use geo::bounding_rect::BoundingRect;
 use geo::{Contains, Intersects};
-use geo_types::{Coordinate, MultiPolygon, Polygon, Rect};
+use geo_types::{Coordinate, MultiPolygon, Point, Polygon, Rect};
 use h3ron::to_geo::ToLine;
-use h3ron::{H3Cell, H3DirectedEdge, ToCoordinate, ToPolygon};
+use h3ron::{H3Cell, H3DirectedEdge, Index, ToCoordinate, ToPolygon};
 use polars::export::arrow::array::BooleanArray;
 use polars::export::arrow::bitmap::{Bitmap, MutableBitmap};
-use polars::prelude::{ArrowDataType, BooleanChunked};
+use polars::prelude::{
+    ArrowDataType, BooleanChunked, DataFrame, IdxCa, IdxSize, NamedFrom, Series,
+};
 use polars_core::prelude::{TakeRandom, UInt64Chunked};
 
 #[cfg(feature = "si_kdtree")]
@@ -46,6 +60,11 @@ pub use crate::spatial_index::rtree::*;
 #[cfg(feature = "si_packed_hilbert_rtree")]
 pub use crate::spatial_index::packed_hilbert_rtree::*;
 
+#[cfg(feature = "si_packed_hilbert_rtree")]
+pub use crate::spatial_index::partitioned::*;
+
+pub use crate::spatial_index::hierarchy::*;
+
 /// marker trait to restrict on what kind of geometries a spatial index
 /// operates.
 pub trait SIKind {}
@@ -58,13 +77,28 @@ impl SIKind for CoordinateSIKind {}
 pub struct RectSIKind {}
 impl SIKind for RectSIKind {}
 
-pub trait SpatialIndex<IX: IndexValue, Kind: SIKind> {
+/// internal
+///
+/// The nearest neighbour search of the spatial indexes backing [`SpatialIndex::nearest_k`].
+#[doc(hidden)]
+pub trait NearestKImpl {
+    /// The positions in the chunked array and the distances of up to `k` elements nearest to
+    /// `coord`, ordered by the distance.
+    fn nearest_k_impl(&self, coord: Coordinate, k: usize) -> Vec<(usize, f64)>;
+}
+
+pub trait SpatialIndex<IX: IndexValue, Kind: SIKind>: NearestKImpl {
     fn h3indexchunked(&self) -> IndexChunked<IX>;
 
     /// internal
     fn envelopes_intersect_impl(&self, rect: &Rect) -> MutableBitmap;
 
     /// The envelope of the indexed elements has some overlap with the given `rect`
+    ///
+    /// To query across the antimeridian, the `rect` has to extend beyond 180 degrees of
+    /// longitude - like the envelopes of the elements crossing it - for example from 170
+    /// to 190 degrees. Rects within -180 to 180 degrees never cross the antimeridian,
+    /// regardless of their width.
     fn envelopes_intersect(&self, rect: &Rect) -> BooleanChunked {
         finish_mask(
             self.envelopes_intersect_impl(rect).into(),
@@ -72,8 +106,314 @@ pub trait SpatialIndex<IX: IndexValue, Kind: SIKind> {
         )
     }
 
+    /// internal
+    ///
+    /// The positions in the chunked array of the elements whose envelope has some overlap with
+    /// the given `rect`. The positions may be unordered and contain duplicates.
+    fn envelopes_intersect_positions_impl(&self, rect: &Rect) -> Vec<usize> {
+        let mask: Bitmap = self.envelopes_intersect_impl(rect).into();
+        mask.iter()
+            .enumerate()
+            .filter_map(|(position, is_set)| is_set.then_some(position))
+            .collect()
+    }
+
+    /// Like [`SpatialIndex::envelopes_intersect`], but returning the positions of the matching
+    /// elements in the indexed chunked array instead of a mask.
+    ///
+    /// The positions are sorted ascending, contain no duplicates and can be passed to
+    /// `DataFrame::take`. This avoids allocating a mask with the length of the chunked array
+    /// for queries matching only a few elements.
+    fn envelopes_intersect_positions(&self, rect: &Rect) -> IdxCa {
+        finish_positions(self.envelopes_intersect_positions_impl(rect))
+    }
+
     /// The envelope of the indexed elements is with `distance` of the given [Coordinate] `coord`.
     fn envelopes_within_distance(&self, coord: Coordinate, distance: f64) -> BooleanChunked;
+
+    /// The coordinate of the indexed elements is within `distance_m` meters of the given
+    /// [Coordinate] `coord`, using the haversine distance.
+    ///
+    /// In contrast to [`SpatialIndex::envelopes_within_distance`] the distance does not depend
+    /// on the latitude. The index is queried using bounding boxes enclosing the circle around
+    /// `coord` - spanning all longitudes when the circle contains a pole and split at the
+    /// antimeridian - and the candidates are filtered by the haversine distance to their
+    /// coordinate. For cells this is the centroid.
+    fn envelopes_within_distance_haversine(
+        &self,
+        coord: Coordinate,
+        distance_m: f64,
+    ) -> BooleanChunked
+    where
+        IX: CoordinateIndexable,
+    {
+        let ic = self.h3indexchunked();
+        let mut mask = haversine_query_rects(coord, distance_m)
+            .iter()
+            .fold(negative_mask(ic.chunked_array), |acc_mask, rect| {
+                acc_mask | &(self.envelopes_intersect_impl(rect).into())
+            });
+
+        let center = Point::from(coord);
+        for i in 0..mask.len() {
+            if mask.get(i) {
+                let within = ic
+                    .get(i)
+                    .and_then(|index| index.spatial_index_coordinate().ok())
+                    .is_some_and(|c| center.haversine_distance(&Point::from(c)) <= distance_m);
+                if !within {
+                    mask.set(i, false);
+                }
+            }
+        }
+        finish_mask(mask.into(), &ic)
+    }
+
+    /// The indexed elements contain the given [Coordinate] `coord`.
+    ///
+    /// In contrast to [`SpatialIndex::envelopes_intersect`] this checks the exact geometry of the
+    /// candidates found using the envelopes, so overlapping envelopes of neighboring cells do
+    /// not result in false positives. The containment is checked at the resolution of each
+    /// element, so columns of mixed resolutions are supported.
+    fn contains_coordinate(&self, coord: Coordinate) -> Result<BooleanChunked, Error>
+    where
+        IX: CoordinateContainment,
+    {
+        let ic = self.h3indexchunked();
+        let mask = validate_containment_of_coordinate(
+            self.envelopes_intersect_impl(&Rect::new(coord, coord)),
+            &ic,
+            coord,
+        )?;
+        Ok(finish_mask(mask.into(), &ic))
+    }
+
+    /// The indexed elements intersect the given [Polygon] `polygon`.
+    ///
+    /// The index is queried using the bounding box of `polygon`. Unless `coarse` is set, the
+    /// candidates are refined by intersecting their exact geometry with `polygon`, so elements
+    /// located completely within a hole of `polygon` are not selected. With `coarse` the
+    /// envelope-level result is returned without refinement.
+    fn intersect_polygon(&self, polygon: &Polygon, coarse: bool) -> Result<BooleanChunked, Error>
+    where
+        IX: RectIndexable,
+    {
+        let ic = self.h3indexchunked();
+        let mask = intersect_polygon_mask(self, &ic, polygon, coarse)?;
+        Ok(finish_mask(mask.into(), &ic))
+    }
+
+    /// The indexed elements intersect the given [MultiPolygon] `multipolygon`.
+    ///
+    /// See [`SpatialIndex::intersect_polygon`].
+    fn intersect_multipolygon(
+        &self,
+        multipolygon: &MultiPolygon,
+        coarse: bool,
+    ) -> Result<BooleanChunked, Error>
+    where
+        IX: RectIndexable,
+    {
+        let ic = self.h3indexchunked();
+        let mut mask = negative_mask(ic.chunked_array);
+        for polygon in multipolygon.0.iter() {
+            mask = mask | &(intersect_polygon_mask(self, &ic, polygon, coarse)?.into());
+        }
+        Ok(finish_mask(mask.into(), &ic))
+    }
+
+    /// The `k` indexed elements nearest to the given [Coordinate] `coord`.
+    ///
+    /// Returns a dataframe with the columns `h3index`, `position` - the position of the element
+    /// in the indexed chunked array - and `distance`, ordered by the distance. The distance is the
+    /// euclidean distance in the units of the coordinates - so degrees - to the envelope of the element.
+    /// For indexes operating on coordinates this is the distance to the coordinate of the element.
+    ///
+    /// `null` and invalid indexes are never part of the result.
+    fn nearest_k(&self, coord: Coordinate, k: usize) -> Result<DataFrame, Error> {
+        let ic = self.h3indexchunked();
+        let mut h3indexes = Vec::with_capacity(k);
+        let mut positions = Vec::with_capacity(k);
+        let mut distances = Vec::with_capacity(k);
+        for (position, distance) in self.nearest_k_impl(coord, k) {
+            if let Some(h3index) = ic.chunked_array.get(position) {
+                h3indexes.push(h3index);
+                positions.push(position as u64);
+                distances.push(distance);
+            }
+        }
+        Ok(DataFrame::new(vec![
+            Series::new("h3index", h3indexes),
+            Series::new("position", positions),
+            Series::new("distance", distances),
+        ])?)
+    }
+}
+
+/// The mask of the elements of `spatial_index` intersecting `polygon`.
+fn intersect_polygon_mask<IX, Kind, SI>(
+    spatial_index: &SI,
+    ic: &IndexChunked<IX>,
+    polygon: &Polygon,
+    coarse: bool,
+) -> Result<MutableBitmap, Error>
+where
+    IX: IndexValue + RectIndexable,
+    Kind: SIKind,
+    SI: SpatialIndex<IX, Kind> + ?Sized,
+{
+    let mask = match polygon.bounding_rect() {
+        Some(rect) => spatial_index.envelopes_intersect_impl(&rect),
+        None => return Ok(negative_mask(ic.chunked_array)),
+    };
+    if coarse {
+        Ok(mask)
+    } else {
+        refine_geometry_intersection(mask, ic, polygon)
+    }
+}
+
+/// the envelopes `a` and `b` intersect, touching envelopes included.
+pub(crate) fn rects_intersect(a: &Rect, b: &Rect) -> bool {
+    a.min().x <= b.max().x
+        && a.max().x >= b.min().x
+        && a.min().y <= b.max().y
+        && a.max().y >= b.min().y
+}
+
+/// the squared euclidean distance of `coord` to `rect`. `0.0` when `rect` contains `coord`.
+pub(crate) fn distance_squared_to_rect(coord: Coordinate, rect: &Rect) -> f64 {
+    let dx = (rect.min().x - coord.x)
+        .max(coord.x - rect.max().x)
+        .max(0.0);
+    let dy = (rect.min().y - coord.y)
+        .max(coord.y - rect.max().y)
+        .max(0.0);
+    dx * dx + dy * dy
+}
+
+/// sort `found` by distance and position and keep the `k` nearest.
+pub(crate) fn nearest_k_sorted(mut found: Vec<(usize, f64)>, k: usize) -> Vec<(usize, f64)> {
+    found.sort_unstable_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+    found.truncate(k);
+    found
+}
+
+/// relative enlargement of the query boxes of `envelopes_within_distance_haversine` to
+/// compensate rounding errors.
+const HAVERSINE_QUERY_MARGIN: f64 = 1e-9;
+
+/// bounding boxes enclosing all coordinates within `distance_m` meters of `coord`.
+fn haversine_query_rects(coord: Coordinate, distance_m: f64) -> Vec<Rect> {
+    if distance_m.is_nan() || distance_m < 0.0 {
+        return vec![];
+    }
+    let angle = distance_m / MEAN_EARTH_RADIUS_M * (1.0 + HAVERSINE_QUERY_MARGIN);
+    let delta_lat = angle.to_degrees();
+    let (min_y, max_y) = (coord.y - delta_lat, coord.y + delta_lat);
+
+    // the maximal difference in longitude of a circle not containing a pole
+    let sin_delta_lon = angle.sin() / coord.y.to_radians().cos();
+    if min_y <= -90.0 || max_y >= 90.0 || sin_delta_lon.is_nan() || sin_delta_lon >= 1.0 {
+        return vec![Rect::new(
+            (-180.0, min_y.max(-90.0)),
+            (180.0, max_y.min(90.0)),
+        )];
+    }
+    let delta_lon = sin_delta_lon.asin().to_degrees();
+    let (min_x, max_x) = (coord.x - delta_lon, coord.x + delta_lon);
+    if min_x < -180.0 {
+        vec![
+            Rect::new((min_x + 360.0, min_y), (180.0, max_y)),
+            Rect::new((-180.0, min_y), (max_x, max_y)),
+        ]
+    } else if max_x > 180.0 {
+        vec![
+            Rect::new((min_x, min_y), (180.0, max_y)),
+            Rect::new((-180.0, min_y), (max_x - 360.0, max_y)),
+        ]
+    } else {
+        vec![Rect::new((min_x, min_y), (max_x, max_y))]
+    }
+}
+
+/// The bounding box of `coordinates` in degrees.
+///
+/// Geometries spanning more than 180 degrees of longitude are assumed to cross the
+/// antimeridian. Their bounding box starts at the coordinates in the eastern hemisphere and
+/// extends beyond 180 degrees to cover the coordinates in the western hemisphere. Geometries
+/// without such a gap between their longitudes contain a pole and span all longitudes.
+fn antimeridian_aware_bounding_rect(
+    coordinates: impl IntoIterator<Item = Coordinate>,
+) -> Option<Rect> {
+    let mut min_y = f64::INFINITY;
+    let mut max_y = f64::NEG_INFINITY;
+    let mut xs: Vec<_> = coordinates
+        .into_iter()
+        .map(|coord| {
+            min_y = min_y.min(coord.y);
+            max_y = max_y.max(coord.y);
+            coord.x
+        })
+        .collect();
+    xs.sort_unstable_by(f64::total_cmp);
+    let (min_x, max_x) = (*xs.first()?, *xs.last()?);
+    if max_x - min_x <= 180.0 {
+        return Some(Rect::new((min_x, min_y), (max_x, max_y)));
+    }
+
+    let (gap, gap_start) = xs
+        .windows(2)
+        .map(|w| (w[1] - w[0], w[0]))
+        .max_by(|a, b| a.0.total_cmp(&b.0))?;
+    if gap > 180.0 {
+        Some(Rect::new(
+            (gap_start + gap, min_y),
+            (gap_start + 360.0, max_y),
+        ))
+    } else {
+        if min_y + max_y > 0.0 {
+            max_y = 90.0;
+        } else {
+            min_y = -90.0;
+        }
+        Some(Rect::new((-180.0, min_y), (180.0, max_y)))
+    }
+}
+
+/// The rects to query the envelopes of the indexed elements with to find all envelopes
+/// intersecting `rect`.
+///
+/// Rects extending beyond 180 degrees of longitude cross the antimeridian and get split in two.
+/// Parts reaching into the western hemisphere get an additional copy shifted by 360 degrees to
+/// find the envelopes of elements crossing the antimeridian.
+/// See [`antimeridian_aware_bounding_rect`].
+pub(crate) fn antimeridian_query_rects(rect: &Rect) -> Vec<Rect> {
+    let (min, max) = (rect.min(), rect.max());
+    let parts = if max.x - min.x >= 360.0 {
+        vec![Rect::new((-180.0, min.y), (180.0, max.y))]
+    } else if min.x >= 180.0 {
+        vec![Rect::new((min.x - 360.0, min.y), (max.x - 360.0, max.y))]
+    } else if max.x > 180.0 {
+        vec![
+            Rect::new((min.x, min.y), (180.0, max.y)),
+            Rect::new((-180.0, min.y), (max.x - 360.0, max.y)),
+        ]
+    } else {
+        vec![*rect]
+    };
+
+    let mut rects = Vec::with_capacity(parts.len() * 2);
+    for part in parts {
+        if part.min().x < 0.0 {
+            rects.push(Rect::new(
+                (part.min().x + 360.0, part.min().y),
+                (part.max().x + 360.0, part.max().y),
+            ));
+        }
+        rects.push(part);
+    }
+    rects
 }
 
 pub trait SpatialIndexGeomOp<IX: IndexValue, Kind: SIKind> {
@@ -149,13 +489,17 @@ impl CoordinateIndexable for H3DirectedEdge {
 }
 
 pub trait RectIndexable {
+    /// bounding box to use for spatial indexing. Bounding boxes of elements crossing the
+    /// antimeridian extend beyond 180 degrees of longitude.
     fn spatial_index_rect(&self) -> Result<Option<Rect>, Error>;
     fn intersects_with_polygon(&self, poly: &Polygon) -> Result<bool, Error>;
 }
 
 impl RectIndexable for H3Cell {
     fn spatial_index_rect(&self) -> Result<Option<Rect>, Error> {
-        Ok(self.to_polygon()?.bounding_rect())
+        Ok(antimeridian_aware_bounding_rect(
+            self.to_polygon()?.exterior().0.iter().copied(),
+        ))
     }
 
     fn intersects_with_polygon(&self, poly: &Polygon) -> Result<bool, Error> {
@@ -165,7 +509,8 @@ impl RectIndexable for H3Cell {
 
 impl RectIndexable for H3DirectedEdge {
     fn spatial_index_rect(&self) -> Result<Option<Rect>, Error> {
-        Ok(Some(self.to_line()?.bounding_rect()))
+        let line = self.to_line()?;
+        Ok(antimeridian_aware_bounding_rect([line.start, line.end]))
     }
 
     fn intersects_with_polygon(&self, poly: &Polygon) -> Result<bool, Error> {
@@ -173,12 +518,35 @@ impl RectIndexable for H3DirectedEdge {
     }
 }
 
+pub trait CoordinateContainment {
+    /// the exact geometry of the element contains `coord`
+    fn contains_coordinate(&self, coord: Coordinate) -> Result<bool, Error>;
+}
+
+impl CoordinateContainment for H3Cell {
+    fn contains_coordinate(&self, coord: Coordinate) -> Result<bool, Error> {
+        Ok(H3Cell::from_coordinate(coord, self.resolution())? == *self)
+    }
+}
+
 pub(crate) fn negative_mask(ca: &UInt64Chunked) -> MutableBitmap {
     let mut mask = MutableBitmap::new();
     mask.extend_constant(ca.len(), false);
     mask
 }
 
+pub(crate) fn finish_positions(mut positions: Vec<usize>) -> IdxCa {
+    positions.sort_unstable();
+    positions.dedup();
+    IdxCa::from_vec(
+        "position",
+        positions
+            .into_iter()
+            .map(|position| position as IdxSize)
+            .collect(),
+    )
+}
+
 pub(crate) fn finish_mask<IX: IndexValue>(mask: Bitmap, ic: &IndexChunked<IX>) -> BooleanChunked {
     let validites = ic.validity_bitmap();
     let bool_arr = BooleanArray::from_data(ArrowDataType::Boolean, mask, Some(validites));
@@ -242,16 +610,64 @@ where
 {
     for i in 0..mask.len() {
         if mask.get(i) {
-            if let Some(index) = indexchunked.get(i) {
-                if let Ok(true) = index.intersects_with_polygon(polygon) {
-                    mask.set(i, true)
-                }
+            let intersects = indexchunked
+                .get(i)
+                .is_some_and(|index| matches!(index.intersects_with_polygon(polygon), Ok(true)));
+            if !intersects {
+                mask.set(i, false);
             }
         }
     }
     mask
 }
 
+/// unset all elements of `mask` not intersecting `polygon`. In contrast to
+/// [`validate_geometry_intersection`] failing geometry conversions are returned as errors.
+pub(crate) fn refine_geometry_intersection<IX>(
+    mut mask: MutableBitmap,
+    indexchunked: &IndexChunked<IX>,
+    polygon: &Polygon,
+) -> Result<MutableBitmap, Error>
+where
+    IX: RectIndexable + IndexValue,
+{
+    for i in 0..mask.len() {
+        if mask.get(i) {
+            let intersects = match indexchunked.get(i) {
+                Some(index) => index.intersects_with_polygon(polygon)?,
+                None => false,
+            };
+            if !intersects {
+                mask.set(i, false);
+            }
+        }
+    }
+    Ok(mask)
+}
+
+/// unset all elements of `mask` not containing `coord`.
+pub(crate) fn validate_containment_of_coordinate<IX>(
+    mut mask: MutableBitmap,
+    indexchunked: &IndexChunked<IX>,
+    coord: Coordinate,
+) -> Result<MutableBitmap, Error>
+where
+    IX: CoordinateContainment + IndexValue,
+{
+    for i in 0..mask.len() {
+        if mask.get(i) {
+            let contained = match indexchunked.get(i) {
+                Some(index) => index.contains_coordinate(coord)?,
+                None => false,
+            };
+            if !contained {
+                mask.set(i, false);
+            }
+        }
+    }
+    Ok(mask)
+}
+
 pub(crate) fn validate_coordinate_containment<IX>(
     mut mask: MutableBitmap,
     indexchunked: &IndexChunked<IX>,
@@ -262,12 +678,12 @@ where
 {
     for i in 0..mask.len() {
         if mask.get(i) {
-            if let Some(index) = indexchunked.get(i) {
-                if let Ok(coord) = index.spatial_index_coordinate() {
-                    if polygon.contains(&coord) {
-                        mask.set(i, true)
-                    }
-                }
+            let contained = indexchunked
+                .get(i)
+                .and_then(|index| index.spatial_index_coordinate().ok())
+                .is_some_and(|coord| polygon.contains(&coord));
+            if !contained {
+                mask.set(i, false);
             }
         }
     }
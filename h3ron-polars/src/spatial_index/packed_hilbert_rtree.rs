@@ -1,6 +1,9 @@
 use crate::algorithm::bounding_rect::BoundingRect;
-use crate::spatial_index::{finish_mask, negative_mask, RectIndexable, RectSIKind, SpatialIndex};
-use crate::{AsH3IndexChunked, Error, IndexChunked, IndexValue};
+use crate::spatial_index::{
+    antimeridian_query_rects, distance_squared_to_rect, finish_mask, nearest_k_sorted,
+    negative_mask, rects_intersect, NearestKImpl, RectIndexable, RectSIKind, SpatialIndex,
+};
+use crate::{AsH3IndexChunked, Error, IndexChunked, IndexValue, ValidIndexes};
 use geo_types::{coord, Coordinate, Rect};
 use polars::export::arrow::bitmap::MutableBitmap;
 use polars::prelude::{BooleanChunked, UInt64Chunked};
@@ -14,10 +17,10 @@ use std::marker::PhantomData;
 /// # Example
 ///
 /// ```
-/// use polars::prelude::UInt64Chunked;
+/// use polars::prelude::{TakeRandom, UInt64Chunked};
 /// use h3ron::H3Cell;
 /// use h3ron_polars::{AsH3CellChunked, NamedFromIndexes};
-/// use h3ron_polars::spatial_index::BuildPackedHilbertRTreeIndex;
+/// use h3ron_polars::spatial_index::{BuildPackedHilbertRTreeIndex, SpatialIndex};
 ///
 /// let uc = UInt64Chunked::new_from_indexes(
 ///     "",
@@ -28,8 +31,18 @@ use std::marker::PhantomData;
 ///     ],
 /// );
 ///
-/// let idx = uc.h3cell().packed_hilbert_rtree_index();
+/// let idx = uc.h3cell().packed_hilbert_rtree_index().unwrap();
+///
+/// // the two cells nearest to a coordinate
+/// let nearest = idx.nearest_k((44.0, 44.0).into(), 2).unwrap();
+/// assert_eq!(nearest.height(), 2);
+/// let positions = nearest.column("position").unwrap().u64().unwrap();
+/// assert_eq!(positions.get(0), Some(0));
 /// ```
+///
+/// The tree itself is static. Rows appended using [`PackedHilbertRTreeIndex::append`] are kept
+/// in an overflow list which is scanned by every query until [`PackedHilbertRTreeIndex::rebuild`]
+/// merges them into the tree.
 pub struct PackedHilbertRTreeIndex<IX: IndexValue> {
     pub index: Option<StaticAABB2DIndex<f64>>,
     index_phantom: PhantomData<IX>,
@@ -37,6 +50,10 @@ pub struct PackedHilbertRTreeIndex<IX: IndexValue> {
 
     /// maps the positions of the index contents to the position in the `chunked_array`
     positions_in_chunked_array: Box<[usize]>,
+
+    /// positions in the `chunked_array` and envelopes of the appended elements not yet
+    /// contained in `index`
+    overflow: Vec<(usize, Rect)>,
 }
 
 pub trait BuildPackedHilbertRTreeIndex<IX: IndexValue> {
@@ -46,47 +63,159 @@ pub trait BuildPackedHilbertRTreeIndex<IX: IndexValue> {
     fn packed_hilbert_rtree_index(&self) -> Result<PackedHilbertRTreeIndex<IX>, Error>;
 }
 
-impl<'a, IX: IndexValue> BuildPackedHilbertRTreeIndex<IX> for IndexChunked<'a, IX>
+impl<IX, T> BuildPackedHilbertRTreeIndex<IX> for T
 where
-    IX: RectIndexable,
+    IX: IndexValue + RectIndexable,
+    T: ValidIndexes<IX>,
 {
     fn packed_hilbert_rtree_index(&self) -> Result<PackedHilbertRTreeIndex<IX>, Error> {
-        let (positions_in_chunked_array, rects) = self.iter_indexes_validated().enumerate().fold(
-            (
-                Vec::with_capacity(self.len()),
-                Vec::with_capacity(self.len()),
-            ),
-            |(mut positions, mut rects), (pos, maybe_index)| {
-                if let Some(Ok(index)) = maybe_index {
-                    if let Ok(Some(rect)) = index.spatial_index_rect() {
-                        positions.push(pos);
-                        rects.push(rect)
-                    }
+        let chunked_array = self.chunked_array();
+        build_index(
+            chunked_array,
+            self.iter_valid_indexes().enumerate(),
+            chunked_array.len(),
+        )
+    }
+}
+
+/// Build an index from `candidates` - tuples of the position in `chunked_array` and
+/// the valid index found at that position. `None` values and indexes without an envelope
+/// are skipped.
+pub(crate) fn build_index<IX, I>(
+    chunked_array: &UInt64Chunked,
+    candidates: I,
+    capacity: usize,
+) -> Result<PackedHilbertRTreeIndex<IX>, Error>
+where
+    IX: IndexValue + RectIndexable,
+    I: Iterator<Item = (usize, Option<IX>)>,
+{
+    let (positions_in_chunked_array, rects) = candidates.fold(
+        (Vec::with_capacity(capacity), Vec::with_capacity(capacity)),
+        |(mut positions, mut rects), (pos, maybe_index)| {
+            if let Some(index) = maybe_index {
+                if let Ok(Some(rect)) = index.spatial_index_rect() {
+                    positions.push(pos);
+                    rects.push(rect)
                 }
-                (positions, rects)
-            },
-        );
+            }
+            (positions, rects)
+        },
+    );
+
+    let index = if !positions_in_chunked_array.is_empty() {
+        let mut builder = StaticAABB2DIndexBuilder::new(positions_in_chunked_array.len());
+        for rect in rects {
+            // add takes in (min_x, min_y, max_x, max_y) of the bounding box
+            builder.add(rect.min().x, rect.min().y, rect.max().x, rect.max().y);
+        }
+        Some(
+            builder
+                .build()
+                .map_err(|e| Error::SpatialIndex(e.to_string()))?,
+        )
+    } else {
+        None
+    };
+    Ok(PackedHilbertRTreeIndex {
+        index,
+        index_phantom: PhantomData::<IX>,
+        chunked_array: chunked_array.clone(),
+        positions_in_chunked_array: positions_in_chunked_array.into_boxed_slice(),
+        overflow: vec![],
+    })
+}
 
-        let index = if !positions_in_chunked_array.is_empty() {
-            let mut builder = StaticAABB2DIndexBuilder::new(positions_in_chunked_array.len());
-            for rect in rects {
-                // add takes in (min_x, min_y, max_x, max_y) of the bounding box
-                builder.add(rect.min().x, rect.min().y, rect.max().x, rect.max().y);
+impl<IX: IndexValue + RectIndexable> PackedHilbertRTreeIndex<IX> {
+    /// Append the elements of `additional` to the indexed elements.
+    ///
+    /// The elements are not added to the tree, they are kept in an overflow list which gets
+    /// scanned linearly by all queries. The query results equal the results of an index built
+    /// from all elements. Use [`PackedHilbertRTreeIndex::rebuild`] to merge the overflow
+    /// into the tree once it grows too large.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use geo_types::Rect;
+    /// use polars::prelude::{TakeRandom, UInt64Chunked};
+    /// use h3ron::H3Cell;
+    /// use h3ron_polars::{AsH3CellChunked, NamedFromIndexes};
+    /// use h3ron_polars::spatial_index::{BuildPackedHilbertRTreeIndex, SpatialIndex};
+    ///
+    /// let uc = UInt64Chunked::new_from_indexes(
+    ///     "",
+    ///     vec![H3Cell::from_coordinate((45.5, 45.5).into(), 7).unwrap()],
+    /// );
+    /// let mut idx = uc.h3cell().packed_hilbert_rtree_index().unwrap();
+    ///
+    /// let batch = UInt64Chunked::new_from_indexes(
+    ///     "",
+    ///     vec![H3Cell::from_coordinate((45.6, 45.6).into(), 7).unwrap()],
+    /// );
+    /// idx.append(&batch.h3cell()).unwrap();
+    /// assert_eq!(idx.len(), 2);
+    /// assert_eq!(idx.overflow_len(), 1);
+    ///
+    /// let mask = idx.envelopes_intersect(&Rect::new((45.0, 45.0), (46.0, 46.0)));
+    /// assert_eq!(mask.len(), 2);
+    /// assert_eq!(mask.get(1), Some(true));
+    ///
+    /// idx.rebuild().unwrap();
+    /// assert_eq!(idx.len(), 2);
+    /// assert_eq!(idx.overflow_len(), 0);
+    /// ```
+    pub fn append(&mut self, additional: &IndexChunked<IX>) -> Result<(), Error> {
+        let offset = self.chunked_array.len();
+        for (pos, maybe_index) in additional.iter_valid_indexes().enumerate() {
+            if let Some(index) = maybe_index {
+                if let Ok(Some(rect)) = index.spatial_index_rect() {
+                    self.overflow.push((offset + pos, rect));
+                }
             }
-            Some(
-                builder
-                    .build()
-                    .map_err(|e| Error::SpatialIndex(e.to_string()))?,
-            )
-        } else {
-            None
-        };
-        Ok(PackedHilbertRTreeIndex {
-            index,
-            index_phantom: PhantomData::<IX>::default(),
-            chunked_array: self.chunked_array.clone(),
-            positions_in_chunked_array: positions_in_chunked_array.into_boxed_slice(),
-        })
+        }
+        self.chunked_array.append(additional.chunked_array);
+        Ok(())
+    }
+
+    /// Rebuild the tree from all elements, including the appended elements of the overflow.
+    pub fn rebuild(&mut self) -> Result<(), Error> {
+        if !self.overflow.is_empty() {
+            let chunked_array = self.chunked_array.clone();
+            let ic: IndexChunked<IX> = chunked_array.h3indexchunked();
+            *self = build_index(
+                &chunked_array,
+                ic.iter_valid_indexes().enumerate(),
+                chunked_array.len(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<IX: IndexValue> PackedHilbertRTreeIndex<IX> {
+    /// number of indexed elements, including the elements of the overflow.
+    ///
+    /// `null` values and elements without an envelope are not indexed.
+    pub fn len(&self) -> usize {
+        self.positions_in_chunked_array.len() + self.overflow.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// number of appended elements which are not yet contained in the tree.
+    pub fn overflow_len(&self) -> usize {
+        self.overflow.len()
+    }
+
+    /// positions of the elements of the overflow with an envelope intersecting `rect`.
+    fn overflow_intersecting<'a>(&'a self, rect: &'a Rect) -> impl Iterator<Item = usize> + 'a {
+        self.overflow
+            .iter()
+            .filter(|(_, envelope)| rects_intersect(envelope, rect))
+            .map(|(pos, _)| *pos)
     }
 }
 
@@ -98,15 +227,48 @@ impl<IX: IndexValue> SpatialIndex<IX, RectSIKind> for PackedHilbertRTreeIndex<IX
     fn envelopes_intersect_impl(&self, rect: &Rect) -> MutableBitmap {
         let mut mask = negative_mask(&self.chunked_array);
         if let Some(index) = self.index.as_ref() {
-            for index_position in
-                index.query(rect.min().x, rect.min().y, rect.max().x, rect.max().y)
-            {
-                mask.set(self.positions_in_chunked_array[index_position], true);
+            for query_rect in antimeridian_query_rects(rect) {
+                for index_position in index.query(
+                    query_rect.min().x,
+                    query_rect.min().y,
+                    query_rect.max().x,
+                    query_rect.max().y,
+                ) {
+                    mask.set(self.positions_in_chunked_array[index_position], true);
+                }
+            }
+        }
+        for query_rect in antimeridian_query_rects(rect) {
+            for pos in self.overflow_intersecting(&query_rect) {
+                mask.set(pos, true);
             }
         }
         mask
     }
 
+    fn envelopes_intersect_positions_impl(&self, rect: &Rect) -> Vec<usize> {
+        let mut positions = vec![];
+        if let Some(index) = self.index.as_ref() {
+            for query_rect in antimeridian_query_rects(rect) {
+                positions.extend(
+                    index
+                        .query(
+                            query_rect.min().x,
+                            query_rect.min().y,
+                            query_rect.max().x,
+                            query_rect.max().y,
+                        )
+                        .into_iter()
+                        .map(|index_position| self.positions_in_chunked_array[index_position]),
+                );
+            }
+        }
+        for query_rect in antimeridian_query_rects(rect) {
+            positions.extend(self.overflow_intersecting(&query_rect));
+        }
+        positions
+    }
+
     fn envelopes_within_distance(&self, coord: Coordinate, distance: f64) -> BooleanChunked {
         let mut mask = negative_mask(&self.chunked_array);
 
@@ -121,21 +283,71 @@ impl<IX: IndexValue> SpatialIndex<IX, RectSIKind> for PackedHilbertRTreeIndex<IX
                 mask.set(self.positions_in_chunked_array[index_position], true);
             }
         }
+        // same comparison as used by the `Visitor`
+        for (pos, envelope) in self.overflow.iter() {
+            if distance_squared_to_rect(coord, envelope) <= distance {
+                mask.set(*pos, true);
+            }
+        }
 
         finish_mask(mask.into(), &self.h3indexchunked())
     }
 }
 
+impl<IX: IndexValue> NearestKImpl for PackedHilbertRTreeIndex<IX> {
+    fn nearest_k_impl(&self, coord: Coordinate, k: usize) -> Vec<(usize, f64)> {
+        if k == 0 {
+            return vec![];
+        }
+        let mut found = Vec::with_capacity(k + self.overflow.len());
+        if let Some(index) = self.index.as_ref() {
+            let mut visitor = NearestKVisitor {
+                found: Vec::with_capacity(k),
+                k,
+            };
+            index.visit_neighbors(coord.x, coord.y, &mut visitor);
+            found.extend(visitor.found.into_iter().map(|(index_position, distance)| {
+                (self.positions_in_chunked_array[index_position], distance)
+            }));
+        }
+        found.extend(
+            self.overflow
+                .iter()
+                .map(|(pos, envelope)| (*pos, distance_squared_to_rect(coord, envelope).sqrt())),
+        );
+        nearest_k_sorted(found, k)
+    }
+}
+
 impl<IX: IndexValue> BoundingRect for PackedHilbertRTreeIndex<IX> {
+    /// The bounding box of all indexed elements. Spans all longitudes when an element crosses the
+    /// antimeridian.
     fn bounding_rect(&self) -> Result<Option<Rect>, Error> {
-        if let Some(index) = self.index.as_ref() {
-            Ok(Some(Rect::new(
-                coord! {x: index.min_x(), y: index.min_y()},
-                coord! {x: index.max_x(), y: index.max_y()},
-            )))
-        } else {
-            Ok(None)
+        let mut bounds = self
+            .index
+            .as_ref()
+            .map(|index| (index.min_x(), index.min_y(), index.max_x(), index.max_y()));
+        for (_, envelope) in self.overflow.iter() {
+            let (min, max) = (envelope.min(), envelope.max());
+            bounds = Some(match bounds {
+                Some((min_x, min_y, max_x, max_y)) => (
+                    min_x.min(min.x),
+                    min_y.min(min.y),
+                    max_x.max(max.x),
+                    max_y.max(max.y),
+                ),
+                None => (min.x, min.y, max.x, max.y),
+            });
         }
+
+        Ok(bounds.map(|(min_x, min_y, max_x, max_y)| {
+            let (min_x, max_x) = if max_x > 180.0 {
+                (-180.0, 180.0)
+            } else {
+                (min_x, max_x)
+            };
+            Rect::new(coord! {x: min_x, y: min_y}, coord! {x: max_x, y: max_y})
+        }))
     }
 }
 
@@ -155,6 +367,23 @@ impl NeighborVisitor<f64, Result<(), ()>> for Visitor {
     }
 }
 
+/// visits the neighbors nearest first and stops after `k` neighbors.
+struct NearestKVisitor {
+    found: Vec<(usize, f64)>,
+    k: usize,
+}
+
+impl NeighborVisitor<f64, Result<(), ()>> for NearestKVisitor {
+    fn visit(&mut self, index_pos: usize, dist_squared: f64) -> Result<(), ()> {
+        self.found.push((index_pos, dist_squared.sqrt()));
+        if self.found.len() < self.k {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::spatial_index::packed_hilbert_rtree::BuildPackedHilbertRTreeIndex;
@@ -166,4 +395,134 @@ mod tests {
         cc.packed_hilbert_rtree_index().unwrap()
     }
     crate::spatial_index::tests::impl_std_tests!(build_index);
+    crate::spatial_index::tests::impl_antimeridian_tests!(build_index);
+
+    #[test]
+    fn bounding_rect_across_antimeridian() {
+        use crate::algorithm::BoundingRect;
+
+        let ca = crate::spatial_index::tests::dateline_and_europe_cells().0;
+        let rect = build_index(&ca.h3cell()).bounding_rect().unwrap().unwrap();
+        assert_eq!(rect.min().x, -180.0);
+        assert_eq!(rect.max().x, 180.0);
+        assert!(rect.min().y <= -60.0);
+        assert!(rect.max().y >= 60.0);
+    }
+
+    /// cells near the antimeridian and in europe followed by a disk of cells, invalid
+    /// values and duplicated cells
+    fn cells_to_append() -> UInt64Chunked {
+        let mut ca = crate::spatial_index::tests::dateline_and_europe_cells().0;
+        let disk = UInt64Chunked::new_from_indexes(
+            "",
+            H3Cell::from_coordinate((12.3, 45.4).into(), 6)
+                .unwrap()
+                .grid_disk(8)
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+        );
+        ca.append(&disk);
+        ca.append(&UInt64Chunked::new("", &[None, Some(55), None]));
+        ca.append(&disk.slice(10, 20));
+        ca
+    }
+
+    fn assert_same_results(
+        idx: &PackedHilbertRTreeIndex<H3Cell>,
+        expected: &PackedHilbertRTreeIndex<H3Cell>,
+    ) {
+        use crate::algorithm::BoundingRect;
+
+        for rect in [
+            Rect::new((179.5, -70.0), (180.5, 70.0)),
+            Rect::new((-10.0, 30.0), (30.0, 60.0)),
+            Rect::new((12.2, 45.3), (12.4, 45.5)),
+            Rect::new((12.35, 45.35), (12.36, 45.36)),
+        ] {
+            assert_eq!(
+                idx.envelopes_intersect(&rect)
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+                expected
+                    .envelopes_intersect(&rect)
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                idx.envelopes_intersect_positions(&rect)
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+                expected
+                    .envelopes_intersect_positions(&rect)
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        let distances = |idx: &PackedHilbertRTreeIndex<H3Cell>, x: f64, y: f64| {
+            idx.nearest_k((x, y).into(), 5)
+                .unwrap()
+                .column("distance")
+                .unwrap()
+                .f64()
+                .unwrap()
+                .into_no_null_iter()
+                .collect::<Vec<_>>()
+        };
+        for (x, y) in [(12.3, 45.4), (12.5, 45.2), (179.9, 10.0), (0.0, 0.0)] {
+            assert_eq!(
+                idx.envelopes_within_distance((x, y).into(), 0.05)
+                    .into_iter()
+                    .collect::<Vec<_>>(),
+                expected
+                    .envelopes_within_distance((x, y).into(), 0.05)
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            );
+
+            let (found, expected_found) = (distances(idx, x, y), distances(expected, x, y));
+            assert_eq!(found.len(), expected_found.len());
+            for (distance, expected_distance) in found.iter().zip(expected_found.iter()) {
+                assert!((distance - expected_distance).abs() < 1e-12);
+            }
+        }
+
+        assert_eq!(
+            idx.bounding_rect().unwrap(),
+            expected.bounding_rect().unwrap()
+        );
+    }
+
+    #[test]
+    fn append_in_batches_equals_full_build() {
+        let ca = cells_to_append();
+        let full = build_index(&ca.h3cell());
+        assert_eq!(full.overflow_len(), 0);
+
+        let mut seed = 7_usize;
+        for initial_len in [0, 1, 30] {
+            let initial = build_index(&ca.slice(0, initial_len).h3cell());
+            let mut idx = build_index(&ca.slice(0, initial_len).h3cell());
+            let mut offset = initial_len;
+            while offset < ca.len() {
+                // pseudo-random batch sizes between 1 and 40
+                seed = (seed * 1_103_515_245 + 12_345) % (1 << 31);
+                let batch_len = (seed % 40 + 1).min(ca.len() - offset);
+                idx.append(&ca.slice(offset as i64, batch_len).h3cell())
+                    .unwrap();
+                offset += batch_len;
+
+                assert_eq!(idx.h3indexchunked().chunked_array.len(), offset);
+                assert_same_results(&idx, &build_index(&ca.slice(0, offset).h3cell()));
+            }
+            assert_eq!(idx.len(), full.len());
+            assert_eq!(idx.overflow_len(), full.len() - initial.len());
+
+            idx.rebuild().unwrap();
+            assert_eq!(idx.len(), full.len());
+            assert_eq!(idx.overflow_len(), 0);
+            assert_same_results(&idx, &full);
+        }
+    }
 }
@@ -0,0 +1,303 @@
+use crate::spatial_index::packed_hilbert_rtree::build_index;
+use crate::spatial_index::{
+    finish_mask, nearest_k_sorted, negative_mask, NearestKImpl, PackedHilbertRTreeIndex,
+    RectIndexable, RectSIKind, SpatialIndex,
+};
+use crate::{AsH3IndexChunked, Error, IndexChunked, IndexValue, ValidIndexes};
+use geo_types::{Coordinate, Rect};
+use h3ron::collections::HashMap;
+use polars::export::arrow::bitmap::MutableBitmap;
+use polars::prelude::{BooleanChunked, DataType, Series, UInt64Chunked};
+
+/// Default limit for the number of distinct partition key values.
+pub const DEFAULT_MAX_PARTITIONS: usize = 1000;
+
+/// A spatial index consisting of one [`PackedHilbertRTreeIndex`] per distinct value of a
+/// partition key.
+///
+/// Each sub-index is built on the complete chunked array but only contains the positions of
+/// the rows of its partition, so all returned masks have the length of the original
+/// chunked array.
+///
+/// The values of the partition key are compared by their string representation. Rows
+/// with a `null` partition key are only reachable using [`PartitionedSpatialIndex::envelopes_intersect_all`]
+/// and the methods of the [`SpatialIndex`] trait.
+///
+/// # Example
+///
+/// ```
+/// use polars::prelude::{NamedFrom, Series, TakeRandom, UInt64Chunked};
+/// use geo_types::Rect;
+/// use h3ron::H3Cell;
+/// use h3ron_polars::{AsH3CellChunked, NamedFromIndexes};
+/// use h3ron_polars::spatial_index::BuildPartitionedPackedHilbertRTreeIndex;
+///
+/// let uc = UInt64Chunked::new_from_indexes(
+///     "",
+///     vec![
+///         H3Cell::from_coordinate((45.5, 45.5).into(), 7).unwrap(),
+///         H3Cell::from_coordinate((45.6, 45.6).into(), 7).unwrap(),
+///     ],
+/// );
+/// let category = Series::new("category", &["a", "b"]);
+///
+/// let idx = uc
+///     .h3cell()
+///     .packed_hilbert_rtree_index_partitioned(&category)
+///     .unwrap();
+/// let mask = idx.envelopes_intersect_in_partition(&Rect::new((40.0, 40.0), (50.0, 50.0)), "b");
+/// assert_eq!(mask.get(0), Some(false));
+/// assert_eq!(mask.get(1), Some(true));
+/// ```
+pub struct PartitionedSpatialIndex<IX: IndexValue> {
+    chunked_array: UInt64Chunked,
+    partitions: HashMap<Option<String>, PackedHilbertRTreeIndex<IX>>,
+}
+
+pub trait BuildPartitionedPackedHilbertRTreeIndex<IX: IndexValue> {
+    /// Build a [`PartitionedSpatialIndex`] with one packed Hilbert R-tree per distinct value
+    /// of `partition_key`.
+    ///
+    /// Fails when `partition_key` has more than [`DEFAULT_MAX_PARTITIONS`] distinct values.
+    fn packed_hilbert_rtree_index_partitioned(
+        &self,
+        partition_key: &Series,
+    ) -> Result<PartitionedSpatialIndex<IX>, Error> {
+        self.packed_hilbert_rtree_index_partitioned_with_limit(
+            partition_key,
+            DEFAULT_MAX_PARTITIONS,
+        )
+    }
+
+    /// Build a [`PartitionedSpatialIndex`] with one packed Hilbert R-tree per distinct value
+    /// of `partition_key`.
+    ///
+    /// Fails when `partition_key` has more than `max_partitions` distinct values.
+    fn packed_hilbert_rtree_index_partitioned_with_limit(
+        &self,
+        partition_key: &Series,
+        max_partitions: usize,
+    ) -> Result<PartitionedSpatialIndex<IX>, Error>;
+}
+
+impl<IX, T> BuildPartitionedPackedHilbertRTreeIndex<IX> for T
+where
+    IX: IndexValue + RectIndexable,
+    T: ValidIndexes<IX>,
+{
+    fn packed_hilbert_rtree_index_partitioned_with_limit(
+        &self,
+        partition_key: &Series,
+        max_partitions: usize,
+    ) -> Result<PartitionedSpatialIndex<IX>, Error> {
+        let chunked_array = self.chunked_array();
+        if partition_key.len() != chunked_array.len() {
+            return Err(Error::SpatialIndex(format!(
+                "length of the partition key ({}) does not match the number of indexes ({})",
+                partition_key.len(),
+                chunked_array.len()
+            )));
+        }
+
+        let keys = partition_key.cast(&DataType::Utf8)?;
+        let mut candidates_by_key: HashMap<Option<String>, Vec<_>> = HashMap::default();
+        for (pos, (maybe_key, maybe_index)) in keys
+            .utf8()?
+            .into_iter()
+            .zip(self.iter_valid_indexes())
+            .enumerate()
+        {
+            let key = maybe_key.map(|k| k.to_string());
+            if !candidates_by_key.contains_key(&key) && candidates_by_key.len() >= max_partitions {
+                return Err(Error::TooManyPartitions(max_partitions));
+            }
+            candidates_by_key
+                .entry(key)
+                .or_insert_with(Vec::new)
+                .push((pos, maybe_index));
+        }
+
+        let partitions = candidates_by_key
+            .into_iter()
+            .map(|(key, candidates)| {
+                let capacity = candidates.len();
+                build_index(chunked_array, candidates.into_iter(), capacity)
+                    .map(|index| (key, index))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(PartitionedSpatialIndex {
+            chunked_array: chunked_array.clone(),
+            partitions,
+        })
+    }
+}
+
+impl<IX: IndexValue> PartitionedSpatialIndex<IX> {
+    /// number of partitions, including the partition of rows with a `null` key.
+    pub fn num_partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    /// The envelope of the indexed elements of the partition `key` has some overlap with
+    /// the given `rect`. Rows of all other partitions are `false`.
+    pub fn envelopes_intersect_in_partition(&self, rect: &Rect, key: &str) -> BooleanChunked {
+        let mask = match self.partitions.get(&Some(key.to_string())) {
+            Some(index) => index.envelopes_intersect_impl(rect),
+            None => negative_mask(&self.chunked_array),
+        };
+        finish_mask(mask.into(), &self.h3indexchunked())
+    }
+
+    /// The envelope of the indexed elements of any partition has some overlap with
+    /// the given `rect`.
+    ///
+    /// The result is the same as querying a non-partitioned index.
+    pub fn envelopes_intersect_all(&self, rect: &Rect) -> BooleanChunked {
+        self.envelopes_intersect(rect)
+    }
+}
+
+impl<IX: IndexValue> SpatialIndex<IX, RectSIKind> for PartitionedSpatialIndex<IX> {
+    fn h3indexchunked(&self) -> IndexChunked<IX> {
+        self.chunked_array.h3indexchunked()
+    }
+
+    fn envelopes_intersect_impl(&self, rect: &Rect) -> MutableBitmap {
+        self.partitions
+            .values()
+            .fold(negative_mask(&self.chunked_array), |acc_mask, index| {
+                acc_mask | &(index.envelopes_intersect_impl(rect).into())
+            })
+    }
+
+    fn envelopes_intersect_positions_impl(&self, rect: &Rect) -> Vec<usize> {
+        self.partitions
+            .values()
+            .flat_map(|index| index.envelopes_intersect_positions_impl(rect))
+            .collect()
+    }
+
+    fn envelopes_within_distance(&self, coord: Coordinate, distance: f64) -> BooleanChunked {
+        let mask = self.partitions.values().fold(
+            negative_mask(&self.chunked_array),
+            |mut acc_mask, index| {
+                for (i, found) in index
+                    .envelopes_within_distance(coord, distance)
+                    .into_iter()
+                    .enumerate()
+                {
+                    if found == Some(true) {
+                        acc_mask.set(i, true);
+                    }
+                }
+                acc_mask
+            },
+        );
+        finish_mask(mask.into(), &self.h3indexchunked())
+    }
+}
+
+impl<IX: IndexValue> NearestKImpl for PartitionedSpatialIndex<IX> {
+    fn nearest_k_impl(&self, coord: Coordinate, k: usize) -> Vec<(usize, f64)> {
+        nearest_k_sorted(
+            self.partitions
+                .values()
+                .flat_map(|index| index.nearest_k_impl(coord, k))
+                .collect(),
+            k,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::from::NamedFromIndexes;
+    use crate::spatial_index::{
+        BuildPackedHilbertRTreeIndex, BuildPartitionedPackedHilbertRTreeIndex, RectSIKind,
+        SpatialIndex,
+    };
+    use crate::{AsH3CellChunked, Error};
+    use geo_types::Rect;
+    use h3ron::H3Cell;
+    use polars::prelude::{IdxSize, NamedFrom, Series, TakeRandom, UInt64Chunked};
+
+    fn build_cells_and_categories() -> (UInt64Chunked, Series) {
+        let cells = H3Cell::from_coordinate((10.0, 20.0).into(), 7)
+            .unwrap()
+            .grid_disk(6)
+            .unwrap();
+        let categories = (0..cells.iter().count())
+            .map(|i| (i % 3) as u32)
+            .collect::<Vec<_>>();
+        (
+            UInt64Chunked::new_from_indexes("", cells.iter().collect::<Vec<_>>()),
+            Series::new("category", categories),
+        )
+    }
+
+    fn query_rect() -> Rect {
+        Rect::new((9.95, 19.95), (10.1, 20.05))
+    }
+
+    #[test]
+    fn partition_equals_base_and_category() {
+        let (ca, categories) = build_cells_and_categories();
+        let base = ca.h3cell().packed_hilbert_rtree_index().unwrap();
+        let partitioned = ca
+            .h3cell()
+            .packed_hilbert_rtree_index_partitioned(&categories)
+            .unwrap();
+        assert_eq!(partitioned.num_partitions(), 3);
+
+        let base_mask = base.envelopes_intersect(&query_rect());
+        let partition_mask = partitioned.envelopes_intersect_in_partition(&query_rect(), "1");
+        assert_eq!(base_mask.len(), partition_mask.len());
+
+        let categories = categories.u32().unwrap();
+        let mut num_found = 0;
+        for i in 0..base_mask.len() {
+            let expected = base_mask.get(i).unwrap() && categories.get(i) == Some(1);
+            assert_eq!(partition_mask.get(i), Some(expected));
+            if expected {
+                num_found += 1;
+            }
+        }
+        assert!(num_found > 0);
+    }
+
+    #[test]
+    fn all_partitions_equal_base() {
+        let (ca, categories) = build_cells_and_categories();
+        let base = ca.h3cell().packed_hilbert_rtree_index().unwrap();
+        let partitioned = ca
+            .h3cell()
+            .packed_hilbert_rtree_index_partitioned(&categories)
+            .unwrap();
+
+        let base_mask = base.envelopes_intersect(&query_rect());
+        let all_mask = partitioned.envelopes_intersect_all(&query_rect());
+        assert_eq!(base_mask.len(), all_mask.len());
+        for i in 0..base_mask.len() {
+            assert_eq!(base_mask.get(i), all_mask.get(i));
+        }
+
+        fn positions<SI: SpatialIndex<H3Cell, RectSIKind>>(idx: &SI) -> Vec<IdxSize> {
+            idx.envelopes_intersect_positions(&query_rect())
+                .into_no_null_iter()
+                .collect()
+        }
+        let base_positions = positions(&base);
+        assert!(!base_positions.is_empty());
+        assert_eq!(base_positions, positions(&partitioned));
+    }
+
+    #[test]
+    fn cardinality_limit() {
+        let (ca, categories) = build_cells_and_categories();
+        let result = ca
+            .h3cell()
+            .packed_hilbert_rtree_index_partitioned_with_limit(&categories, 2);
+        assert!(matches!(result, Err(Error::TooManyPartitions(2))));
+    }
+}
@@ -1,11 +1,14 @@
-use crate::spatial_index::{finish_mask, negative_mask, RectIndexable, RectSIKind, SpatialIndex};
-use crate::{AsH3IndexChunked, IndexChunked, IndexValue};
+use crate::spatial_index::{
+    antimeridian_query_rects, finish_mask, nearest_k_sorted, negative_mask, NearestKImpl,
+    RectIndexable, RectSIKind, SpatialIndex,
+};
+use crate::{AsH3IndexChunked, IndexChunked, IndexValue, ValidIndexes};
 use geo_types::{Coordinate, Rect};
 use polars::export::arrow::bitmap::MutableBitmap;
 use polars::prelude::UInt64Chunked;
 use polars_core::datatypes::BooleanChunked;
 use rstar::primitives::{GeomWithData, Rectangle};
-use rstar::{RTree, AABB};
+use rstar::{PointDistance, RTree, AABB};
 use std::marker::PhantomData;
 
 // todo: Use the Line type supported by rtree instead of Rectangle to index H3DirectedEdges
@@ -38,9 +41,10 @@ where
     fn rtree_index(&self) -> RTreeIndex<IX>;
 }
 
-impl<'a, IX: IndexValue> BuildRTreeIndex<'a, IX> for IndexChunked<'a, IX>
+impl<'a, IX, T> BuildRTreeIndex<'a, IX> for T
 where
-    IX: RectIndexable,
+    IX: IndexValue + RectIndexable,
+    T: ValidIndexes<IX>,
 {
     /// Build a [R-Tree](https://en.wikipedia.org/wiki/R-tree) spatial index
     ///
@@ -73,19 +77,18 @@ where
     /// ```
     fn rtree_index(&self) -> RTreeIndex<IX> {
         let entries: Vec<_> = self
-            .iter_indexes_validated()
+            .iter_valid_indexes()
             .enumerate()
-            .filter_map(|(pos, maybe_index)| match maybe_index {
-                Some(Ok(index)) => index.spatial_index_rect().ok().and_then(|maybe_rect| {
-                    maybe_rect.map(|rect| LocatedArrayPosition::new(to_bbox(&rect), pos))
-                }),
-                _ => None,
+            .filter_map(|(pos, maybe_index)| {
+                maybe_index
+                    .and_then(|index| index.spatial_index_rect().ok().flatten())
+                    .map(|rect| LocatedArrayPosition::new(to_bbox(&rect), pos))
             })
             .collect();
 
         RTreeIndex {
             index_phantom: PhantomData::<IX>::default(),
-            chunked_array: self.chunked_array.clone(),
+            chunked_array: self.chunked_array().clone(),
             rtree: RTree::bulk_load(entries),
         }
     }
@@ -101,14 +104,31 @@ where
 
     fn envelopes_intersect_impl(&self, rect: &Rect) -> MutableBitmap {
         let mut mask = negative_mask(&self.chunked_array);
-        let envelope = AABB::from_corners(to_coord(rect.min()), to_coord(rect.max()));
-        let locator = self.rtree.locate_in_envelope_intersecting(&envelope);
-        for located_array_position in locator {
-            mask.set(located_array_position.data, true);
+        for query_rect in antimeridian_query_rects(rect) {
+            let envelope =
+                AABB::from_corners(to_coord(query_rect.min()), to_coord(query_rect.max()));
+            let locator = self.rtree.locate_in_envelope_intersecting(&envelope);
+            for located_array_position in locator {
+                mask.set(located_array_position.data, true);
+            }
         }
         mask
     }
 
+    fn envelopes_intersect_positions_impl(&self, rect: &Rect) -> Vec<usize> {
+        let mut positions = vec![];
+        for query_rect in antimeridian_query_rects(rect) {
+            let envelope =
+                AABB::from_corners(to_coord(query_rect.min()), to_coord(query_rect.max()));
+            positions.extend(
+                self.rtree
+                    .locate_in_envelope_intersecting(&envelope)
+                    .map(|located_array_position| located_array_position.data),
+            );
+        }
+        positions
+    }
+
     fn envelopes_within_distance(&self, coord: Coordinate, distance: f64) -> BooleanChunked {
         let mut mask = negative_mask(&self.chunked_array);
 
@@ -121,6 +141,28 @@ where
     }
 }
 
+impl<IX: IndexValue> NearestKImpl for RTreeIndex<IX>
+where
+    IX: RectIndexable,
+{
+    fn nearest_k_impl(&self, coord: Coordinate, k: usize) -> Vec<(usize, f64)> {
+        let point = to_coord(coord);
+        nearest_k_sorted(
+            self.rtree
+                .nearest_neighbor_iter(&point)
+                .take(k)
+                .map(|located_array_position| {
+                    (
+                        located_array_position.data,
+                        located_array_position.distance_2(&point).sqrt(),
+                    )
+                })
+                .collect(),
+            k,
+        )
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::spatial_index::{BuildRTreeIndex, RTreeIndex};
@@ -131,4 +173,5 @@ mod test {
         cc.rtree_index()
     }
     crate::spatial_index::tests::impl_std_tests!(build_index);
+    crate::spatial_index::tests::impl_antimeridian_tests!(build_index);
 }
@@ -4,8 +4,9 @@ macro_rules! impl_std_tests {
         use crate::from::NamedFromIndexes;
         use crate::spatial_index::{SpatialIndex, SpatialIndexGeomOp};
         use crate::AsH3CellChunked;
-        use geo_types::{coord, polygon, Rect};
-        use h3ron::{Index};
+        use geo::Intersects;
+        use geo_types::{coord, polygon, MultiPolygon, Rect};
+        use h3ron::{Index, ToPolygon};
         use polars::prelude::{TakeRandom, UInt64Chunked, NamedFrom};
 
         fn build_cell_ca() -> UInt64Chunked {
@@ -40,6 +41,165 @@ macro_rules! impl_std_tests {
             assert_eq!(mask.get(3), None);
         }
 
+        fn assert_within_distance_haversine_matches_brute_force(
+            cells: Vec<H3Cell>,
+            coord: geo_types::Coordinate,
+            distance_m: f64,
+        ) {
+            use geo::algorithm::haversine_distance::HaversineDistance;
+            use geo_types::Point;
+            use h3ron::ToCoordinate;
+
+            let ca = UInt64Chunked::new_from_indexes("", cells.clone());
+            let idx = $mk_index(&ca.h3cell());
+            let mask = idx.envelopes_within_distance_haversine(coord, distance_m);
+
+            assert_eq!(mask.len(), cells.len());
+            let mut num_within = 0;
+            for (i, cell) in cells.iter().enumerate() {
+                let expected = Point::from(cell.to_coordinate().unwrap())
+                    .haversine_distance(&Point::from(coord))
+                    <= distance_m;
+                assert_eq!(mask.get(i), Some(expected));
+                if expected {
+                    num_within += 1;
+                }
+            }
+            assert!(num_within > 0);
+            assert!(num_within < cells.len());
+        }
+
+        #[test]
+        fn cell_envelopes_within_distance_haversine() {
+            let ca = build_cell_ca();
+            let idx = $mk_index(&ca.h3cell());
+            // about 1.5 degrees of longitude at this latitude
+            let mask = idx.envelopes_within_distance_haversine((-62.0, -60.5).into(), 90_000.0);
+
+            assert_eq!(mask.len(), 4);
+            assert_eq!(mask.get(0), Some(false));
+            assert_eq!(mask.get(1), Some(true));
+            assert_eq!(mask.get(2), Some(false));
+            assert_eq!(mask.get(3), None);
+
+            // pole: the circle spans all longitudes
+            let cells = H3Cell::from_coordinate((0.0, 89.9).into(), 5)
+                .unwrap()
+                .grid_disk(8)
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>();
+            assert_within_distance_haversine_matches_brute_force(
+                cells,
+                (170.0, 89.9).into(),
+                60_000.0,
+            );
+
+            // antimeridian
+            let cells = H3Cell::from_coordinate((179.95, 10.0).into(), 6)
+                .unwrap()
+                .grid_disk(10)
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>();
+            for x in [-179.98, 179.98] {
+                assert_within_distance_haversine_matches_brute_force(
+                    cells.clone(),
+                    (x, 10.0).into(),
+                    20_000.0,
+                );
+            }
+        }
+
+        #[test]
+        fn cell_nearest_k() {
+            let ca = build_cell_ca();
+            let idx = $mk_index(&ca.h3cell());
+
+            let df = idx.nearest_k((-60.0, -60.0).into(), 2).unwrap();
+            assert_eq!(df.shape(), (2, 3));
+            let positions = df.column("position").unwrap().u64().unwrap();
+            assert_eq!(positions.get(0), Some(1));
+            assert_eq!(positions.get(1), Some(0));
+            let h3indexes = df.column("h3index").unwrap().u64().unwrap();
+            assert_eq!(h3indexes.get(0), ca.get(1));
+            let distances = df.column("distance").unwrap().f64().unwrap();
+            assert!(distances.get(0).unwrap() < 1.0);
+            assert!(distances.get(0).unwrap() <= distances.get(1).unwrap());
+
+            // the invalid index is never returned
+            let df = idx.nearest_k((-60.0, -60.0).into(), 10).unwrap();
+            assert_eq!(df.height(), 3);
+            let positions = df.column("position").unwrap().u64().unwrap();
+            assert!(positions.into_iter().all(|pos| pos != Some(3)));
+
+            assert_eq!(idx.nearest_k((-60.0, -60.0).into(), 0).unwrap().height(), 0);
+        }
+
+        #[test]
+        fn cell_envelopes_intersect_positions() {
+            use polars::prelude::{DataFrame, IntoSeries};
+
+            let center = H3Cell::from_coordinate((10.0, 50.0).into(), 9).unwrap();
+            let mut values = vec![None];
+            values.extend(
+                center
+                    .grid_disk(6)
+                    .unwrap()
+                    .iter()
+                    .map(|cell| Some(cell.h3index())),
+            );
+            values.insert(20, None);
+            values.push(Some(55)); // invalid
+            let ca = UInt64Chunked::new("h3index", values);
+            let idx = $mk_index(&ca.h3cell());
+            let df = DataFrame::new(vec![ca.clone().into_series()]).unwrap();
+
+            for (rect, expect_found) in [
+                (Rect::new((9.99, 49.99), (10.01, 50.01)), true),
+                (Rect::new((0.0, 0.0), (1.0, 1.0)), false),
+            ] {
+                let positions = idx.envelopes_intersect_positions(&rect);
+                assert_eq!(!positions.is_empty(), expect_found);
+                let positions_vec: Vec<_> = positions.into_no_null_iter().collect();
+                assert!(positions_vec.windows(2).all(|w| w[0] < w[1]));
+
+                let mask = idx.envelopes_intersect(&rect);
+                assert!(df
+                    .take(&positions)
+                    .unwrap()
+                    .frame_equal_missing(&df.filter(&mask).unwrap()));
+            }
+        }
+
+        #[test]
+        fn cell_contains_coordinate_mixed_resolutions() {
+            let coord = (10.2, 50.3).into();
+            let mut cells = vec![];
+            for resolution in [4, 7, 9] {
+                let cell = H3Cell::from_coordinate(coord, resolution).unwrap();
+                // the envelopes of the neighbors overlap with the envelope of the cell
+                cells.extend(cell.grid_disk(1).unwrap().iter());
+            }
+            let mut values: Vec<_> = cells.iter().map(|cell| Some(cell.h3index())).collect();
+            values.push(None);
+            values.push(Some(55)); // invalid
+            let ca = UInt64Chunked::new("", values);
+
+            let idx = $mk_index(&ca.h3cell());
+            let mask = idx.contains_coordinate(coord).unwrap();
+            assert_eq!(mask.len(), ca.len());
+            for (i, cell) in cells.iter().enumerate() {
+                assert_eq!(
+                    mask.get(i),
+                    Some(H3Cell::from_coordinate(coord, cell.resolution()).unwrap() == *cell)
+                );
+            }
+            assert_eq!((&mask).into_iter().filter(|v| *v == Some(true)).count(), 3);
+            assert_eq!(mask.get(cells.len()), None);
+            assert_eq!(mask.get(cells.len() + 1), None);
+        }
+
         #[test]
         fn cell_geometries_intersect() {
             let ca = build_cell_ca();
@@ -71,9 +231,152 @@ macro_rules! impl_std_tests {
             assert_eq!(mask.get(2), Some(false));
             assert_eq!(mask.get(3), None);
         }
+
+        #[test]
+        fn cell_intersect_polygon_with_hole() {
+            let center = H3Cell::from_coordinate((10.0, 50.0).into(), 9).unwrap();
+            let cells: Vec<_> = center.grid_disk(12).unwrap().iter().collect();
+            let mut values: Vec<_> = cells.iter().map(|cell| Some(cell.h3index())).collect();
+            values.push(None);
+            values.push(Some(55)); // invalid
+            let ca = UInt64Chunked::new("", values);
+            let idx = $mk_index(&ca.h3cell());
+
+            let polygon = polygon!(
+                exterior: [
+                    coord! {x: 9.9, y: 49.9},
+                    coord! {x: 10.1, y: 49.9},
+                    coord! {x: 10.1, y: 50.1},
+                    coord! {x: 9.9, y: 50.1},
+                    coord! {x: 9.9, y: 49.9},
+                ],
+                interiors: [[
+                    coord! {x: 9.99, y: 49.99},
+                    coord! {x: 10.01, y: 49.99},
+                    coord! {x: 10.01, y: 50.01},
+                    coord! {x: 9.99, y: 50.01},
+                    coord! {x: 9.99, y: 49.99},
+                ]]
+            );
+
+            let coarse = idx.intersect_polygon(&polygon, true).unwrap();
+            let refined = idx.intersect_polygon(&polygon, false).unwrap();
+            assert_eq!(refined.len(), ca.len());
+            let mut num_in_hole = 0;
+            for (i, cell) in cells.iter().enumerate() {
+                assert_eq!(coarse.get(i), Some(true));
+                let intersects = polygon.intersects(&cell.to_polygon().unwrap());
+                assert_eq!(refined.get(i), Some(intersects));
+                if !intersects {
+                    num_in_hole += 1;
+                }
+            }
+            assert!(num_in_hole > 0);
+            assert_eq!(refined.get(0), Some(false)); // the center
+            for mask in [&coarse, &refined] {
+                assert_eq!(mask.get(cells.len()), None);
+                assert_eq!(mask.get(cells.len() + 1), None);
+            }
+
+            // a second polygon covering the hole
+            let multipolygon = MultiPolygon::new(vec![
+                polygon,
+                Rect::new((9.995, 49.995), (10.005, 50.005)).to_polygon(),
+            ]);
+            let refined = idx.intersect_multipolygon(&multipolygon, false).unwrap();
+            assert_eq!(refined.get(0), Some(true));
+            assert!(
+                (&refined).into_iter().filter(|v| *v == Some(true)).count()
+                    > cells.len() - num_in_hole
+            );
+        }
     }
 }
 
 // make the macro available to other modules
 #[allow(unused_imports)]
 pub(crate) use impl_std_tests;
+
+/// resolution 2 cells along the antimeridian followed by cells in europe. The second element
+/// is the number of cells along the antimeridian.
+#[allow(dead_code)]
+pub(crate) fn dateline_and_europe_cells() -> (polars::prelude::UInt64Chunked, usize) {
+    use crate::from::NamedFromIndexes;
+    use h3ron::H3Cell;
+
+    let mut cells: Vec<_> = (-12..=12)
+        .map(|i| H3Cell::from_coordinate((180.0, f64::from(i) * 5.0).into(), 2).unwrap())
+        .collect();
+    cells.sort_unstable();
+    cells.dedup();
+    let num_dateline_cells = cells.len();
+    for (x, y) in [(10.0, 50.0), (2.3, 48.8), (-3.7, 40.4), (23.7, 37.9)] {
+        cells.push(H3Cell::from_coordinate((x, y).into(), 2).unwrap());
+    }
+    (
+        polars::prelude::UInt64Chunked::new_from_indexes("", cells),
+        num_dateline_cells,
+    )
+}
+
+/// tests for indexes of the envelopes of the elements
+#[allow(unused_macros)]
+macro_rules! impl_antimeridian_tests {
+    ($mk_index:expr) => {
+        #[test]
+        fn cell_envelopes_intersect_across_antimeridian() {
+            let (ca, num_dateline_cells) = crate::spatial_index::tests::dateline_and_europe_cells();
+            let idx = $mk_index(&crate::AsH3CellChunked::h3cell(&ca));
+            let mask = crate::spatial_index::SpatialIndex::envelopes_intersect(
+                &idx,
+                &geo_types::Rect::new((179.5, -70.0), (180.5, 70.0)),
+            );
+
+            assert_eq!(mask.len(), ca.len());
+            for i in 0..ca.len() {
+                assert_eq!(
+                    polars::prelude::TakeRandom::get(&mask, i),
+                    Some(i < num_dateline_cells)
+                );
+            }
+        }
+
+        #[test]
+        fn cell_envelopes_intersect_europe_with_antimeridian_cells() {
+            let (ca, num_dateline_cells) = crate::spatial_index::tests::dateline_and_europe_cells();
+            let idx = $mk_index(&crate::AsH3CellChunked::h3cell(&ca));
+            let mask = crate::spatial_index::SpatialIndex::envelopes_intersect(
+                &idx,
+                &geo_types::Rect::new((-10.0, 30.0), (30.0, 60.0)),
+            );
+            for i in 0..ca.len() {
+                assert_eq!(
+                    polars::prelude::TakeRandom::get(&mask, i),
+                    Some(i >= num_dateline_cells)
+                );
+            }
+        }
+
+        #[test]
+        fn cell_envelopes_intersect_wide_rect() {
+            let (ca, num_dateline_cells) = crate::spatial_index::tests::dateline_and_europe_cells();
+            let idx = $mk_index(&crate::AsH3CellChunked::h3cell(&ca));
+
+            // wider than 180 degrees, but not crossing the antimeridian
+            let mask = crate::spatial_index::SpatialIndex::envelopes_intersect(
+                &idx,
+                &geo_types::Rect::new((-100.0, 20.0), (100.0, 70.0)),
+            );
+            for i in 0..ca.len() {
+                assert_eq!(
+                    polars::prelude::TakeRandom::get(&mask, i),
+                    Some(i >= num_dateline_cells)
+                );
+            }
+        }
+    };
+}
+
+// make the macro available to other modules
+#[allow(unused_imports)]
+pub(crate) use impl_antimeridian_tests;
@@ -0,0 +1,247 @@
+use crate::{AsH3IndexChunked, IndexChunked, IndexValue};
+use polars::export::arrow::bitmap::{Bitmap, MutableBitmap};
+use polars::prelude::UInt64Chunked;
+use std::marker::PhantomData;
+
+/// Access to the valid `Index` values of an array.
+///
+/// Implemented by [`IndexChunked`], which validates each value during the iteration,
+/// and by [`ValidatedIndexChunked`], which only validates once. Algorithms accepting this
+/// trait can be used with both.
+pub trait ValidIndexes<IX: IndexValue>: Sync {
+    /// the array containing the `Index` values
+    fn chunked_array(&self) -> &UInt64Chunked;
+
+    /// iterate over the `Index` values in the array.
+    ///
+    /// `null` values and invalid `Index` values are returned as `None`.
+    fn iter_valid_indexes(&self) -> Box<dyn Iterator<Item = Option<IX>> + '_>;
+
+    /// the `Index` value of `maybe_h3index` found at `position` in the array.
+    ///
+    /// `null` values and invalid `Index` values are returned as `None`. Allows validating
+    /// the values of the array out of order, e.g. in parallel.
+    fn valid_index_at(&self, position: usize, maybe_h3index: Option<u64>) -> Option<IX> {
+        let _ = position;
+        maybe_h3index.map(IX::new).filter(|index| index.is_valid())
+    }
+}
+
+impl<'a, IX: IndexValue> ValidIndexes<IX> for IndexChunked<'a, IX> {
+    fn chunked_array(&self) -> &UInt64Chunked {
+        self.chunked_array
+    }
+
+    fn iter_valid_indexes(&self) -> Box<dyn Iterator<Item = Option<IX>> + '_> {
+        Box::new(
+            self.iter_indexes_nonvalidated()
+                .map(|maybe_index| maybe_index.filter(|index| index.is_valid())),
+        )
+    }
+}
+
+/// An array of `Index` values which has been validated once, to avoid validating the
+/// values again in each subsequent algorithm using it.
+///
+/// Created by [`IndexChunked::validate`]. The validity of the values is stored in a bitmap
+/// distinct from the `null`-bitmap of the array. Invalid values are treated like `null`
+/// values.
+///
+/// The wrapper holds its own reference-counted copy of the array and the bitmap, so it is cheap to
+/// clone and can not get out of sync when the array it was created from gets modified.
+#[derive(Clone)]
+pub struct ValidatedIndexChunked<IX: IndexValue> {
+    chunked_array: UInt64Chunked,
+    validity: Bitmap,
+    num_invalid: usize,
+    index_phantom: PhantomData<IX>,
+}
+
+impl<IX: IndexValue> ValidatedIndexChunked<IX> {
+    pub(crate) fn new(indexchunked: &IndexChunked<IX>) -> Self {
+        let mut validity = MutableBitmap::with_capacity(indexchunked.len());
+        let mut num_invalid = 0;
+        for maybe_index in indexchunked.iter_indexes_nonvalidated() {
+            let is_valid = match maybe_index {
+                Some(index) => {
+                    let is_valid = index.is_valid();
+                    if !is_valid {
+                        num_invalid += 1;
+                    }
+                    is_valid
+                }
+                None => false,
+            };
+            validity.push(is_valid);
+        }
+
+        Self {
+            chunked_array: indexchunked.chunked_array.clone(),
+            validity: validity.into(),
+            num_invalid,
+            index_phantom: PhantomData::<IX>,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunked_array.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunked_array.is_empty()
+    }
+
+    /// bitmap with all positions set which contain a valid `Index` value.
+    pub fn validity_bitmap(&self) -> &Bitmap {
+        &self.validity
+    }
+
+    /// number of valid `Index` values
+    pub fn num_valid(&self) -> usize {
+        self.len() - self.num_invalid - self.num_null()
+    }
+
+    /// number of non-`null` values which are not valid `Index` values
+    pub const fn num_invalid(&self) -> usize {
+        self.num_invalid
+    }
+
+    /// number of `null` values
+    pub fn num_null(&self) -> usize {
+        self.chunked_array.null_count()
+    }
+
+    pub fn h3indexchunked(&self) -> IndexChunked<IX> {
+        self.chunked_array.h3indexchunked()
+    }
+}
+
+impl<IX: IndexValue> ValidIndexes<IX> for ValidatedIndexChunked<IX> {
+    fn chunked_array(&self) -> &UInt64Chunked {
+        &self.chunked_array
+    }
+
+    fn iter_valid_indexes(&self) -> Box<dyn Iterator<Item = Option<IX>> + '_> {
+        Box::new(
+            self.chunked_array
+                .into_iter()
+                .zip(self.validity.iter())
+                .map(|(maybe_h3index, is_valid)| {
+                    if is_valid {
+                        maybe_h3index.map(IX::new)
+                    } else {
+                        None
+                    }
+                }),
+        )
+    }
+
+    fn valid_index_at(&self, position: usize, maybe_h3index: Option<u64>) -> Option<IX> {
+        if self.validity.get_bit(position) {
+            maybe_h3index.map(IX::new)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithm::{H3ChangeResolution, H3GridDisk, H3ToWkb};
+    use crate::{AsH3CellChunked, FromIndexIterator, ValidIndexes};
+    use h3ron::{H3Cell, Index};
+    use polars::prelude::{ListChunked, UInt64Chunked};
+
+    fn cells_with_garbage() -> UInt64Chunked {
+        let mut values: Vec<_> = H3Cell::from_coordinate((12.3, 45.4).into(), 6)
+            .unwrap()
+            .grid_disk(2)
+            .unwrap()
+            .iter()
+            .map(|cell| Some(cell.h3index()))
+            .collect();
+        values.insert(3, Some(55)); // garbage
+        values.insert(7, None);
+        values.push(Some(u64::MAX)); // garbage
+        UInt64Chunked::from_iter(values)
+    }
+
+    fn list_values(lc: &ListChunked) -> Vec<Option<Vec<Option<u64>>>> {
+        lc.into_iter()
+            .map(|maybe_series| {
+                maybe_series.map(|series| series.u64().unwrap().into_iter().collect())
+            })
+            .collect()
+    }
+
+    fn list_values_u8(lc: &ListChunked) -> Vec<Option<Vec<Option<u8>>>> {
+        lc.into_iter()
+            .map(|maybe_series| {
+                maybe_series.map(|series| series.u8().unwrap().into_iter().collect())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn counts() {
+        let ca = cells_with_garbage();
+        let validated = ca.h3cell().validate();
+        assert_eq!(validated.len(), ca.len());
+        assert_eq!(validated.num_invalid(), 2);
+        assert_eq!(validated.num_null(), 1);
+        assert_eq!(validated.num_valid(), ca.len() - 3);
+    }
+
+    #[test]
+    fn iter_valid_indexes_equal() {
+        let ca = cells_with_garbage();
+        let validated = ca.h3cell().validate();
+        let plain: Vec<Option<H3Cell>> = ca.h3cell().iter_valid_indexes().collect();
+        let from_validated: Vec<Option<H3Cell>> = validated.iter_valid_indexes().collect();
+        assert_eq!(plain, from_validated);
+        assert_eq!(plain.iter().filter(|c| c.is_none()).count(), 3);
+    }
+
+    #[test]
+    fn algorithms_equal() {
+        let ca = cells_with_garbage();
+        let validated = ca.h3cell().validate();
+
+        let disk = ca.h3cell().h3_grid_disk(1).unwrap();
+        assert_eq!(
+            list_values(&disk),
+            list_values(&validated.h3_grid_disk(1).unwrap())
+        );
+        assert_eq!(disk.null_count(), 3);
+
+        assert_eq!(
+            list_values(&ca.h3cell().h3_change_resolution(7).unwrap()),
+            list_values(&validated.h3_change_resolution(7).unwrap())
+        );
+
+        let wkb = ca.h3cell().to_wkb(false).unwrap();
+        assert_eq!(
+            list_values_u8(&wkb),
+            list_values_u8(&validated.to_wkb(false).unwrap())
+        );
+        assert_eq!(wkb.null_count(), 3);
+    }
+
+    #[test]
+    fn independent_of_source_array() {
+        let mut ca = UInt64Chunked::from_index_iter(
+            H3Cell::from_coordinate((12.3, 45.4).into(), 6)
+                .unwrap()
+                .grid_disk(1)
+                .unwrap()
+                .iter(),
+        );
+        let validated = ca.h3cell().validate();
+        let before: Vec<Option<H3Cell>> = validated.iter_valid_indexes().collect();
+
+        ca.append(&UInt64Chunked::from_iter([Some(55_u64)]));
+        let after: Vec<Option<H3Cell>> = validated.iter_valid_indexes().collect();
+        assert_eq!(before, after);
+        assert_eq!(validated.len(), ca.len() - 1);
+    }
+}
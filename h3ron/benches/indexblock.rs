@@ -34,6 +34,18 @@ fn criterion_benchmark(c: &mut Criterion) {
         },
     );
 
+    group.bench_function("get cell at position 3", |bencher| {
+        bencher.iter(|| {
+            let _cell = ib.get(3).unwrap();
+        });
+    });
+
+    group.bench_function("iterate the first 10 cells", |bencher| {
+        bencher.iter(|| {
+            let _cells2: Vec<_> = ib.iter_uncompressed_range(0..10).unwrap().collect();
+        });
+    });
+
     let cell = cells[cells.len() / 2];
     //let cell = H3Cell::from_coordinate(Coordinate::from((-62.3, 45.4)), 10).unwrap();
     group.bench_function("cell contains", |bencher| {
@@ -112,19 +112,32 @@ impl H3Cell {
         .map(|_| Self::new(cell_index))
     }
 
-    /// Retrieves all children of `self` at resolution `child_resolution`
-    pub fn get_children(&self, child_resolution: u8) -> Result<IndexVec<Self>, Error> {
-        let child_resolution = c_int::from(child_resolution);
-
+    /// Number of children of `self` at resolution `child_resolution`.
+    ///
+    /// Pentagons and their descendants have fewer children than the other cells, so this
+    /// is not always `7^(child_resolution - resolution)`.
+    pub fn get_children_count(&self, child_resolution: u8) -> Result<usize, Error> {
         let mut children_size: i64 = 0;
         Error::check_returncode(unsafe {
-            h3ron_h3_sys::cellToChildrenSize(self.h3index(), child_resolution, &mut children_size)
-        })?;
+            h3ron_h3_sys::cellToChildrenSize(
+                self.h3index(),
+                c_int::from(child_resolution),
+                &mut children_size,
+            )
+        })
+        .map(|_| children_size as usize)
+    }
 
-        let mut index_vec = IndexVec::with_length(children_size as usize);
+    /// Retrieves all children of `self` at resolution `child_resolution`
+    pub fn get_children(&self, child_resolution: u8) -> Result<IndexVec<Self>, Error> {
+        let mut index_vec = IndexVec::with_length(self.get_children_count(child_resolution)?);
 
         Error::check_returncode(unsafe {
-            h3ron_h3_sys::cellToChildren(self.h3index(), child_resolution, index_vec.as_mut_ptr())
+            h3ron_h3_sys::cellToChildren(
+                self.h3index(),
+                c_int::from(child_resolution),
+                index_vec.as_mut_ptr(),
+            )
         })?;
         Ok(index_vec)
     }
@@ -501,6 +514,24 @@ mod tests {
         assert_eq!(H3Cell::try_from(0x89283080ddbffff_u64).unwrap(), index);
     }
 
+    #[test]
+    fn test_get_children_count() {
+        let hexagon = H3Cell::from_coordinate((12.0, 45.0).into(), 5).unwrap();
+        assert_eq!(hexagon.get_children_count(7).unwrap(), 49);
+        assert_eq!(
+            hexagon.get_children_count(7).unwrap(),
+            hexagon.get_children(7).unwrap().count()
+        );
+
+        let pentagon = H3Cell::new(0x8009fffffffffff_u64);
+        assert!(pentagon.is_pentagon());
+        assert_eq!(pentagon.get_children_count(2).unwrap(), 41);
+        assert_eq!(
+            pentagon.get_children_count(2).unwrap(),
+            pentagon.get_children(2).unwrap().count()
+        );
+    }
+
     #[test]
     fn test_is_valid() {
         assert!(H3Cell::try_from(0x89283080ddbffff_u64).unwrap().is_valid());
@@ -13,6 +13,15 @@ use crate::{Error, H3Cell};
 const H3_RESOLUTION_RANGE_USIZE: RangeInclusive<usize> =
     (H3_MIN_RESOLUTION as usize)..=(H3_MAX_RESOLUTION as usize);
 
+/// magic bytes at the start of the binary representation
+const BYTES_MAGIC: &[u8; 4] = b"H3CV";
+
+/// version of the binary representation. To be increased on every change of the layout.
+const BYTES_FORMAT_VERSION: u8 = 1;
+
+/// magic bytes, version and the number of cells of each resolution
+const BYTES_HEADER_LEN: usize = BYTES_MAGIC.len() + 1 + 4 * (H3_MAX_RESOLUTION as usize + 1);
+
 /// structure to keep compacted h3ron cells to allow more or less efficient
 /// adding of further cells
 #[derive(PartialEq, Eq, Debug)]
@@ -55,6 +64,25 @@ impl CompactedCellVec {
         self.compact_from_resolution_up(H3_MAX_RESOLUTION as usize, H3_RESOLUTION_RANGE_USIZE)
     }
 
+    /// Compact the cells, but not to resolutions coarser than `min_resolution`.
+    ///
+    /// Cells coarser than `min_resolution` - including the cells which have been contained
+    /// before - are replaced by their children at `min_resolution`. So uncompacting the
+    /// result yields the same cells as uncompacting the result of [`CompactedCellVec::compact`].
+    pub fn compact_to_min_resolution(&mut self, min_resolution: u8) -> Result<(), Error> {
+        if min_resolution > H3_MAX_RESOLUTION {
+            return Err(Error::ResDomain);
+        }
+        self.compact()?;
+        for res in (H3_MIN_RESOLUTION as usize)..(min_resolution as usize) {
+            for cell in std::mem::take(&mut self.cells_by_resolution[res]) {
+                self.cells_by_resolution[min_resolution as usize]
+                    .extend(cell.get_children(min_resolution)?.iter());
+            }
+        }
+        Ok(())
+    }
+
     /// append the contents of a vector. The caller is responsible to ensure that
     /// the append cells all are at resolution `resolution`.
     ///
@@ -185,7 +213,43 @@ impl CompactedCellVec {
         }
     }
 
+    /// uncompact all cells to the given `resolution`.
+    ///
+    /// In contrast to [`CompactedCellVec::iter_uncompacted_cells`], containing cells at
+    /// resolutions finer than `resolution` results in an [`Error::ResMismatch`].
+    ///
+    /// Cells which have not been compacted - all located at `resolution` - are copied
+    /// without iterating over them.
+    pub fn uncompact(&self, resolution: u8) -> Result<Vec<H3Cell>, Error> {
+        if resolution > H3_MAX_RESOLUTION {
+            return Err(Error::ResDomain);
+        }
+        if let Some(finest_resolution) = self.finest_resolution_contained() {
+            if finest_resolution > resolution {
+                return Err(Error::ResMismatch);
+            }
+        }
+        let cells_at_resolution = self.get_compacted_cells_at_resolution(resolution);
+        if cells_at_resolution.len() == self.len() {
+            return Ok(cells_at_resolution.to_vec());
+        }
+
+        let mut capacity = 0;
+        for cells in self.cells_by_resolution[..=(resolution as usize)].iter() {
+            for cell in cells.iter() {
+                capacity += cell.get_children_count(resolution)?;
+            }
+        }
+        let mut uncompacted = Vec::with_capacity(capacity);
+        for cell in self.iter_uncompacted_cells(resolution) {
+            uncompacted.push(cell?);
+        }
+        Ok(uncompacted)
+    }
+
     /// deduplicate the internal cell vectors
+    ///
+    /// Afterwards the cells of each resolution are sorted, see [`CompactedCellVec::is_sorted`].
     pub fn dedup(&mut self) -> Result<(), Error> {
         self.cells_by_resolution.iter_mut().for_each(|cells| {
             cells.sort_unstable();
@@ -194,6 +258,17 @@ impl CompactedCellVec {
         self.purge_children()
     }
 
+    /// true when the cells of each resolution are sorted in ascending order and contain
+    /// no duplicates.
+    ///
+    /// Only sorted vecs containing the same cells compare equal, so vecs should be sorted
+    /// using [`CompactedCellVec::dedup`] before comparing them.
+    pub fn is_sorted(&self) -> bool {
+        self.cells_by_resolution
+            .iter()
+            .all(|cells| is_strictly_ascending(cells))
+    }
+
     /// the finest resolution contained
     pub fn finest_resolution_contained(&self) -> Option<u8> {
         for resolution in H3_RESOLUTION_RANGE_USIZE.rev() {
@@ -204,6 +279,130 @@ impl CompactedCellVec {
         None
     }
 
+    /// Serialize to a canonical, human-diffable text representation.
+    ///
+    /// Each non-empty resolution is written to its own line as `res:<r> <hex> <hex> ...`, followed
+    /// by a newline. Resolutions are written in ascending order, the cells of each line are
+    /// sorted ascending and deduplicated. So the same set of compacted cells always results in the
+    /// identical string - regardless of the order the cells have been added in.
+    /// Cells contained in a cell of a coarser resolution, as found in uncompacted vecs, are
+    /// omitted as they are already covered by it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use h3ron::collections::CompactedCellVec;
+    /// use h3ron::{H3Cell, Index};
+    ///
+    /// let mut cv = CompactedCellVec::new();
+    /// cv.add_cell(H3Cell::new(0x89283080ddbffff), false).unwrap();
+    /// assert_eq!(cv.to_text(), "res:9 89283080ddbffff\n");
+    /// ```
+    pub fn to_text(&self) -> String {
+        resolution_slices_to_text(self.cells_by_resolution.iter().map(Vec::as_slice))
+    }
+
+    /// Parse the text representation created by [`CompactedCellVec::to_text`].
+    ///
+    /// Every cell is validated and must match the resolution declared at the start
+    /// of its line. Duplicate cells and cells contained in a cell of a coarser resolution
+    /// are rejected. The returned [`Error::TextParseError`] contains the position
+    /// of the offending token.
+    pub fn from_text(text: &str) -> Result<Self, Error> {
+        let mut cv = Self::new();
+        for cell in parse_cells_text(text)? {
+            cv.cells_by_resolution[cell.resolution() as usize].push(cell);
+        }
+        cv.cells_by_resolution
+            .iter_mut()
+            .for_each(|cells| cells.sort_unstable());
+        Ok(cv)
+    }
+
+    /// Serialize to a compact binary representation.
+    ///
+    /// The representation starts with a header consisting of magic bytes, a format version
+    /// and the number of cells of each resolution, followed by the cells as little-endian
+    /// `u64` values. The cells are written as they are stored - including duplicates and
+    /// cells contained in cells of coarser resolutions of uncompacted vecs - so
+    /// [`CompactedCellVec::from_bytes`] restores an equal vec.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let num_cells = self.len();
+        let mut bytes = Vec::with_capacity(BYTES_HEADER_LEN + num_cells * 8);
+        bytes.extend_from_slice(BYTES_MAGIC);
+        bytes.push(BYTES_FORMAT_VERSION);
+        for cells in self.cells_by_resolution.iter() {
+            bytes.extend_from_slice(&(cells.len() as u32).to_le_bytes());
+        }
+        for cell in self.cells_by_resolution.iter().flatten() {
+            bytes.extend_from_slice(&cell.h3index().to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Read the binary representation created by [`CompactedCellVec::to_bytes`].
+    ///
+    /// Truncated or corrupted input, as well as input of an unknown format version, is
+    /// rejected with [`Error::BinaryFormatError`]. Every cell is validated and must match the
+    /// resolution it is stored under.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < BYTES_HEADER_LEN {
+            return Err(Error::BinaryFormatError(format!(
+                "expected at least {} bytes, found {}",
+                BYTES_HEADER_LEN,
+                bytes.len()
+            )));
+        }
+        let (magic, rest) = bytes.split_at(BYTES_MAGIC.len());
+        if magic != BYTES_MAGIC {
+            return Err(Error::BinaryFormatError("unknown magic bytes".to_string()));
+        }
+        let (version, rest) = rest.split_at(1);
+        if version[0] != BYTES_FORMAT_VERSION {
+            return Err(Error::BinaryFormatError(format!(
+                "unsupported format version {}, expected {}",
+                version[0], BYTES_FORMAT_VERSION
+            )));
+        }
+        let (counts, mut cell_bytes) = rest.split_at(BYTES_HEADER_LEN - BYTES_MAGIC.len() - 1);
+
+        let mut cv = Self::new();
+        for (resolution, count_bytes) in counts.chunks_exact(4).enumerate() {
+            let count = u32::from_le_bytes(count_bytes.try_into().expect("4 bytes")) as usize;
+            if cell_bytes.len() / 8 < count {
+                return Err(Error::BinaryFormatError(format!(
+                    "the input is truncated, expected {} cells at resolution {}",
+                    count, resolution
+                )));
+            }
+            let (resolution_cell_bytes, rest) = cell_bytes.split_at(count * 8);
+            cell_bytes = rest;
+
+            for h3index_bytes in resolution_cell_bytes.chunks_exact(8) {
+                let h3index = u64::from_le_bytes(h3index_bytes.try_into().expect("8 bytes"));
+                let cell = H3Cell::try_from(h3index).map_err(|_| {
+                    Error::BinaryFormatError(format!("invalid h3 cell {:x}", h3index))
+                })?;
+                if cell.resolution() as usize != resolution {
+                    return Err(Error::BinaryFormatError(format!(
+                        "cell {:x} has resolution {}, expected resolution {}",
+                        h3index,
+                        cell.resolution(),
+                        resolution
+                    )));
+                }
+                cv.cells_by_resolution[resolution].push(cell);
+            }
+        }
+        if !cell_bytes.is_empty() {
+            return Err(Error::BinaryFormatError(format!(
+                "found {} trailing bytes",
+                cell_bytes.len()
+            )));
+        }
+        Ok(cv)
+    }
+
     /// compact all resolution from the given to 0
     ///
     /// resolutions are skipped when the compacting of the
@@ -276,6 +475,10 @@ impl CompactedCellVec {
     }
 }
 
+fn is_strictly_ascending(cells: &[H3Cell]) -> bool {
+    cells.windows(2).all(|window| window[0] < window[1])
+}
+
 impl Default for CompactedCellVec {
     fn default() -> Self {
         Self::new()
@@ -369,6 +572,179 @@ impl<'a> Iterator for CompactedCellVecUncompactedIterator<'a> {
     }
 }
 
+/// Serialize a slice of cells to the text representation used by [`CompactedCellVec::to_text`].
+///
+/// The cells do not get compacted, but cells contained in a cell of a coarser resolution
+/// are omitted.
+pub fn cells_to_text(cells: &[H3Cell]) -> String {
+    let mut cells_by_resolution: [Vec<H3Cell>; H3_MAX_RESOLUTION as usize + 1] = Default::default();
+    for cell in cells {
+        cells_by_resolution[cell.resolution() as usize].push(*cell);
+    }
+    resolution_slices_to_text(cells_by_resolution.iter().map(Vec::as_slice))
+}
+
+/// Parse cells from the text representation used by [`CompactedCellVec::to_text`].
+///
+/// The same validations as in [`CompactedCellVec::from_text`] are applied.
+pub fn cells_from_text(text: &str) -> Result<Vec<H3Cell>, Error> {
+    parse_cells_text(text)
+}
+
+fn resolution_slices_to_text<'a, I>(slices: I) -> String
+where
+    I: Iterator<Item = &'a [H3Cell]>,
+{
+    let slices: Vec<_> = slices.collect();
+
+    // uncompacted cells may contain cells together with their descendants
+    let known_cells: H3CellSet = if slices.iter().filter(|cells| !cells.is_empty()).count() > 1 {
+        slices
+            .iter()
+            .flat_map(|cells| cells.iter().copied())
+            .collect()
+    } else {
+        Default::default()
+    };
+
+    let mut text = String::new();
+    for (resolution, cells) in slices.into_iter().enumerate() {
+        let mut cells: Vec<_> = cells
+            .iter()
+            .filter(|cell| matches!(known_parent(cell, &known_cells), Ok(None)))
+            .copied()
+            .collect();
+        if cells.is_empty() {
+            continue;
+        }
+        cells.sort_unstable();
+        cells.dedup();
+
+        text.push_str(&format!("res:{}", resolution));
+        for cell in cells {
+            text.push_str(&format!(" {:x}", cell.h3index()));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn text_parse_error(line: usize, column: usize, message: String) -> Error {
+    Error::TextParseError {
+        line,
+        column,
+        message,
+    }
+}
+
+/// tokens of a line separated by spaces together with their 1-based column
+fn tokens_with_column(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split(' ')
+        .scan(1, |column, token| {
+            let token_column = *column;
+            *column += token.chars().count() + 1;
+            Some((token_column, token))
+        })
+        .filter(|(_, token)| !token.is_empty())
+}
+
+fn parse_cells_text(text: &str) -> Result<Vec<H3Cell>, Error> {
+    let mut known_cells = H3CellSet::default();
+    let mut cells_with_position = Vec::new();
+    let mut resolutions_declared = [false; H3_MAX_RESOLUTION as usize + 1];
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let line_number = line_idx + 1;
+        let mut tokens = tokens_with_column(line);
+        let (res_column, res_token) = match tokens.next() {
+            Some(t) => t,
+            None => continue, // skip empty lines
+        };
+
+        let resolution = res_token
+            .strip_prefix("res:")
+            .and_then(|r| r.parse::<u8>().ok())
+            .filter(|r| *r <= H3_MAX_RESOLUTION)
+            .ok_or_else(|| {
+                text_parse_error(
+                    line_number,
+                    res_column,
+                    format!(
+                        "expected resolution declaration 'res:<r>', found '{}'",
+                        res_token
+                    ),
+                )
+            })?;
+        if resolutions_declared[resolution as usize] {
+            return Err(text_parse_error(
+                line_number,
+                res_column,
+                format!("resolution {} is declared more than once", resolution),
+            ));
+        }
+        resolutions_declared[resolution as usize] = true;
+
+        for (column, token) in tokens {
+            let cell = u64::from_str_radix(token, 16)
+                .ok()
+                .and_then(|h3index| H3Cell::try_from(h3index).ok())
+                .ok_or_else(|| {
+                    text_parse_error(line_number, column, format!("invalid h3 cell '{}'", token))
+                })?;
+            if cell.resolution() != resolution {
+                return Err(text_parse_error(
+                    line_number,
+                    column,
+                    format!(
+                        "cell {} has resolution {}, expected resolution {}",
+                        token,
+                        cell.resolution(),
+                        resolution
+                    ),
+                ));
+            }
+            if !known_cells.insert(cell) {
+                return Err(text_parse_error(
+                    line_number,
+                    column,
+                    format!("duplicate cell {}", token),
+                ));
+            }
+            cells_with_position.push((cell, line_number, column));
+        }
+    }
+
+    for (cell, line_number, column) in cells_with_position.iter() {
+        if let Some(parent) = known_parent(cell, &known_cells)? {
+            return Err(text_parse_error(
+                *line_number,
+                *column,
+                format!(
+                    "cell {:x} is contained in cell {:x}",
+                    cell.h3index(),
+                    parent.h3index()
+                ),
+            ));
+        }
+    }
+
+    Ok(cells_with_position
+        .into_iter()
+        .map(|(cell, _, _)| cell)
+        .collect())
+}
+
+/// the first parent of `cell` contained in `known_cells`
+fn known_parent(cell: &H3Cell, known_cells: &H3CellSet) -> Result<Option<H3Cell>, Error> {
+    for parent_resolution in H3_MIN_RESOLUTION..cell.resolution() {
+        let parent = cell.get_parent(parent_resolution)?;
+        if known_cells.contains(&parent) {
+            return Ok(Some(parent));
+        }
+    }
+    Ok(None)
+}
+
 #[cfg(test)]
 mod tests {
     use std::convert::TryInto;
@@ -376,7 +752,9 @@ mod tests {
     #[cfg(feature = "use-serde")]
     use bincode::{deserialize, serialize};
 
+    use crate::collections::compactedcellvec::{cells_from_text, cells_to_text};
     use crate::collections::CompactedCellVec;
+    use crate::{Error, H3Cell, Index};
 
     #[test]
     fn compactedvec_is_empty() {
@@ -389,6 +767,88 @@ mod tests {
         assert_eq!(cv.len(), 1);
     }
 
+    #[test]
+    fn compactedvec_uncompact() {
+        let cell: H3Cell = 0x89283080ddbffff_u64.try_into().unwrap();
+        let mut cv = CompactedCellVec::new();
+        cv.add_cells(cell.get_children(10).unwrap().iter(), false)
+            .unwrap();
+        cv.compact().unwrap();
+        assert_eq!(cv.len(), 1);
+
+        let uncompacted = cv.uncompact(11).unwrap();
+        assert_eq!(uncompacted.len(), 49);
+        assert!(uncompacted.iter().all(|c| c.resolution() == 11));
+        assert_eq!(cv.uncompact(9).unwrap(), vec![cell]);
+        assert!(matches!(cv.uncompact(8), Err(Error::ResMismatch)));
+        assert!(matches!(cv.uncompact(16), Err(Error::ResDomain)));
+        assert!(CompactedCellVec::new().uncompact(3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn compactedvec_compact_to_min_resolution() {
+        let cell = H3Cell::from_coordinate((12.3, 45.6).into(), 4).unwrap();
+        let cells: Vec<_> = cell.get_children(8).unwrap().iter().collect();
+        let mut expected = cells.clone();
+        expected.sort_unstable();
+
+        let mut unrestricted = CompactedCellVec::new();
+        unrestricted
+            .add_cells(cells.iter().copied(), false)
+            .unwrap();
+        unrestricted.compact().unwrap();
+        assert_eq!(unrestricted.len(), 1);
+
+        for min_resolution in [0, 4, 6, 8] {
+            let mut cv = CompactedCellVec::new();
+            cv.add_cells(cells.iter().copied(), false).unwrap();
+            cv.compact_to_min_resolution(min_resolution).unwrap();
+            assert!(cv
+                .iter_compacted_cells()
+                .all(|cell| cell.resolution() >= min_resolution));
+            assert_eq!(
+                cv.len(),
+                7_usize.pow(u32::from(min_resolution.saturating_sub(4)))
+            );
+
+            let mut uncompacted = cv.uncompact(8).unwrap();
+            uncompacted.sort_unstable();
+            assert_eq!(uncompacted, expected);
+        }
+
+        // already compacted input with mixed resolutions
+        let mut cv = CompactedCellVec::new();
+        cv.add_cell(cell, false).unwrap();
+        let neighbor = cell.grid_ring_unsafe(1).unwrap().iter().next().unwrap();
+        cv.add_cells(neighbor.get_children(7).unwrap().iter(), false)
+            .unwrap();
+        cv.compact_to_min_resolution(5).unwrap();
+        assert_eq!(cv.len_resolutions()[5], 14);
+        assert_eq!(cv.len(), 14);
+
+        assert!(cv.compact_to_min_resolution(16).is_err());
+    }
+
+    #[test]
+    fn compactedvec_dedup_sorts() {
+        let cells: Vec<_> = disk_cells().into_iter().rev().collect();
+        let mut cv = CompactedCellVec::new();
+        cv.add_cells(cells.iter().copied(), false).unwrap();
+        cv.add_cells(cells.iter().take(3).copied(), false).unwrap();
+        assert!(!cv.is_sorted());
+
+        let mut cv_2 = CompactedCellVec::new();
+        cv_2.add_cells(cells.iter().rev().copied(), false).unwrap();
+        assert_ne!(cv, cv_2);
+
+        cv.dedup().unwrap();
+        cv_2.dedup().unwrap();
+        assert!(cv.is_sorted());
+        assert_eq!(cv.len(), cells.len());
+        assert_eq!(cv, cv_2);
+        assert!(CompactedCellVec::new().is_sorted());
+    }
+
     #[cfg(feature = "use-serde")]
     #[test]
     fn compactedvec_serde_roundtrip() {
@@ -400,4 +860,170 @@ mod tests {
         let cv_2: CompactedCellVec = deserialize(&serialized_data).unwrap();
         assert_eq!(cv, cv_2);
     }
+
+    fn disk_cells() -> Vec<H3Cell> {
+        H3Cell::new(0x89283080ddbffff)
+            .grid_disk(3)
+            .unwrap()
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn compactedvec_text_roundtrip() {
+        let cv: CompactedCellVec = disk_cells().try_into().unwrap();
+        let text = cv.to_text();
+        assert!(text.ends_with('\n'));
+
+        let cv_2 = CompactedCellVec::from_text(&text).unwrap();
+        assert_eq!(cv.len(), cv_2.len());
+        assert_eq!(text, cv_2.to_text());
+    }
+
+    #[test]
+    fn compactedvec_text_roundtrip_uncompacted() {
+        let cell = H3Cell::new(0x89283080ddbffff);
+        let mut cv = CompactedCellVec::new();
+        cv.add_cells(cell.get_children(10).unwrap().iter().take(3), false)
+            .unwrap();
+        cv.add_cell(cell, false).unwrap();
+        cv.add_cell(cell, false).unwrap();
+        assert_eq!(cv.len(), 5);
+
+        let text = cv.to_text();
+        assert_eq!(text, "res:9 89283080ddbffff\n");
+        let cv_2 = CompactedCellVec::from_text(&text).unwrap();
+        assert_eq!(cv_2.len(), 1);
+
+        let mut cells = cell
+            .get_children(10)
+            .unwrap()
+            .iter()
+            .take(2)
+            .collect::<Vec<_>>();
+        cells.push(cell);
+        assert_eq!(cells_from_text(&cells_to_text(&cells)).unwrap(), vec![cell]);
+    }
+
+    #[test]
+    fn compactedvec_text_is_canonical() {
+        let cells = disk_cells();
+        let mut cells_reversed = cells.clone();
+        cells_reversed.reverse();
+
+        let cv: CompactedCellVec = cells.try_into().unwrap();
+        let cv_reversed: CompactedCellVec = cells_reversed.try_into().unwrap();
+        assert_eq!(cv.to_text(), cv_reversed.to_text());
+    }
+
+    #[test]
+    fn cells_text_roundtrip() {
+        let mut cells = disk_cells();
+        let text = cells_to_text(&cells);
+        let mut cells_2 = cells_from_text(&text).unwrap();
+        cells.sort_unstable();
+        cells_2.sort_unstable();
+        assert_eq!(cells, cells_2);
+    }
+
+    #[test]
+    fn compactedvec_bytes_roundtrip() {
+        let cv: CompactedCellVec = disk_cells().try_into().unwrap();
+        // mixed resolutions after compacting
+        assert!(cv.len_resolutions().iter().filter(|l| **l > 0).count() > 1);
+
+        let bytes = cv.to_bytes();
+        let cv_2 = CompactedCellVec::from_bytes(&bytes).unwrap();
+        assert_eq!(cv.len_resolutions(), cv_2.len_resolutions());
+        assert_eq!(cv.to_text(), cv_2.to_text());
+        assert_eq!(bytes, cv_2.to_bytes());
+
+        let empty = CompactedCellVec::new();
+        assert!(CompactedCellVec::from_bytes(&empty.to_bytes())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn compactedvec_bytes_roundtrip_uncompacted() {
+        let cell = H3Cell::new(0x89283080ddbffff);
+        let mut cv = CompactedCellVec::new();
+        cv.add_cells(cell.get_children(10).unwrap().iter().take(3), false)
+            .unwrap();
+        cv.add_cell(cell, false).unwrap();
+        cv.add_cell(
+            cell.grid_ring_unsafe(1).unwrap().iter().next().unwrap(),
+            false,
+        )
+        .unwrap();
+        cv.add_cell(cell, false).unwrap();
+        assert_eq!(cv.len(), 6);
+
+        let cv_2 = CompactedCellVec::from_bytes(&cv.to_bytes()).unwrap();
+        assert_eq!(cv_2.len(), 6);
+        assert_eq!(cv, cv_2);
+    }
+
+    #[test]
+    fn compactedvec_bytes_invalid() {
+        let cv: CompactedCellVec = disk_cells().try_into().unwrap();
+        let bytes = cv.to_bytes();
+
+        for len in 0..bytes.len() {
+            assert!(matches!(
+                CompactedCellVec::from_bytes(&bytes[..len]),
+                Err(Error::BinaryFormatError(_))
+            ));
+        }
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(CompactedCellVec::from_bytes(&trailing).is_err());
+
+        let mut wrong_magic = bytes.clone();
+        wrong_magic[0] = b'X';
+        assert!(CompactedCellVec::from_bytes(&wrong_magic).is_err());
+
+        let mut unknown_version = bytes.clone();
+        unknown_version[4] = 99;
+        assert!(CompactedCellVec::from_bytes(&unknown_version).is_err());
+
+        // corrupt the mode bits of the last cell
+        let mut corrupted = bytes;
+        let last = corrupted.len() - 1;
+        corrupted[last] ^= 0x40;
+        assert!(matches!(
+            CompactedCellVec::from_bytes(&corrupted),
+            Err(Error::BinaryFormatError(_))
+        ));
+    }
+
+    fn assert_parse_error(text: &str, expected_line: usize, expected_column: usize) {
+        match CompactedCellVec::from_text(text) {
+            Err(Error::TextParseError { line, column, .. }) => {
+                assert_eq!(line, expected_line);
+                assert_eq!(column, expected_column);
+            }
+            other => panic!("expected a TextParseError, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn text_parse_error_positions() {
+        // invalid cell
+        assert_parse_error("res:9 89283080ddbffff xyz\n", 1, 23);
+        // invalid resolution declaration
+        assert_parse_error("res:9 89283080ddbffff\nresolution:8\n", 2, 1);
+        // cell does not match the declared resolution
+        assert_parse_error("res:8 89283080ddbffff\n", 1, 7);
+        // duplicate cell
+        assert_parse_error("res:9 89283080ddbffff 89283080ddbffff\n", 1, 23);
+        // descendant of a cell at a coarser resolution
+        let parent = H3Cell::new(0x89283080ddbffff).get_parent(8).unwrap();
+        assert_parse_error(
+            &format!("res:8 {:x}\nres:9 89283080ddbffff\n", parent.h3index()),
+            2,
+            7,
+        );
+    }
 }
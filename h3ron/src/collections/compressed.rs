@@ -1,6 +1,7 @@
 use std::borrow::Borrow;
 use std::marker::PhantomData;
 use std::mem::size_of;
+use std::ops::Range;
 
 #[cfg(feature = "use-serde")]
 use serde::{Deserialize, Serialize};
@@ -115,6 +116,59 @@ where
         let decompressor = Decompressor::default();
         decompressor.decompress_block_owning(self)
     }
+
+    /// returns the index at position `pos`. `None` when `pos` is out of range.
+    ///
+    /// See [`IndexBlock::iter_uncompressed_range`] for the cost of the decompression.
+    pub fn get(&self, pos: usize) -> Result<Option<T>, Error> {
+        Ok(self
+            .iter_uncompressed_range(pos..pos.saturating_add(1))?
+            .next())
+    }
+
+    /// returns the first index of the block. `None` for empty blocks.
+    pub fn first(&self) -> Result<Option<T>, Error> {
+        self.get(0)
+    }
+
+    /// returns the last index of the block. `None` for empty blocks.
+    pub fn last(&self) -> Result<Option<T>, Error> {
+        match self.num_indexes.checked_sub(1) {
+            Some(pos) => self.get(pos),
+            None => Ok(None),
+        }
+    }
+
+    /// returns an iterator over the indexes at the positions within `range`. Positions
+    /// out of range are skipped.
+    ///
+    /// Only the bytes of the requested indexes get copied out of the run-length-encoded data.
+    /// Other than with [`IndexBlock::iter_uncompressed`], no buffer for the whole block gets
+    /// allocated. The encoded data still needs to be traversed up to the last requested byte. As
+    /// the bytes are grouped by their position in the `u64`, this byte is located in the last
+    /// group, so most of the encoded data gets traversed regardless of the position of `range`.
+    pub fn iter_uncompressed_range(
+        &self,
+        range: Range<usize>,
+    ) -> Result<std::vec::IntoIter<T>, Error> {
+        let end = range.end.min(self.num_indexes);
+        let start = range.start.min(end);
+        let num_requested = end - start;
+        if num_requested == 0 {
+            return Ok(vec![].into_iter());
+        }
+
+        // the bytes of the requested indexes within each group of bytes
+        let byte_ranges: Vec<_> = (0..size_of::<u64>())
+            .map(|byte_i| (byte_i * self.num_indexes + start)..(byte_i * self.num_indexes + end))
+            .collect();
+        let buf = rle_decode_ranges(&self.block_data, &byte_ranges)?;
+
+        Ok((0..num_requested)
+            .map(|pos| T::from_h3index(h3index_from_block_buf(&buf, pos, num_requested)))
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
 }
 
 impl<T> From<&[T]> for IndexBlock<T>
@@ -363,6 +417,46 @@ fn rle_decode(bytes: &[u8], out: &mut Vec<u8>) -> Result<(), Error> {
     })
 }
 
+/// decode the bytes of run-length-encoded `bytes` located within the sorted, non-overlapping
+/// `ranges` of the decoded bytes.
+///
+/// The decoding stops after the end of the last range.
+fn rle_decode_ranges(bytes: &[u8], ranges: &[Range<usize>]) -> Result<Vec<u8>, Error> {
+    let expected_len: usize = ranges.iter().map(|range| range.len()).sum();
+    let mut out = Vec::with_capacity(expected_len);
+    let mut ranges_iter = ranges.iter().filter(|range| !range.is_empty()).peekable();
+
+    // position of the current run within the decoded bytes
+    let mut run_start = 0_usize;
+    rle_decode_step_bytes(bytes, |byte, repetitions| {
+        let run_end = run_start + repetitions as usize;
+        while let Some(range) = ranges_iter.peek() {
+            if range.start >= run_end {
+                break;
+            }
+            let overlap_len = range.end.min(run_end) - range.start.max(run_start);
+            out.extend(std::iter::repeat_n(byte, overlap_len));
+            if range.end <= run_end {
+                ranges_iter.next();
+            } else {
+                break;
+            }
+        }
+        run_start = run_end;
+        ranges_iter.peek().is_some()
+    })?;
+
+    if out.len() != expected_len {
+        Err(Error::DecompressionError(format!(
+            "Expected to decompress {} bytes, but got {} bytes",
+            expected_len,
+            out.len()
+        )))
+    } else {
+        Ok(out)
+    }
+}
+
 /// run-length-encode bytes
 fn rle_encode(bytes: &[u8], out: &mut Vec<u8>) {
     if bytes.is_empty() {
@@ -468,6 +562,52 @@ mod tests {
         assert_eq!(ib, ib_de);
     }
 
+    #[test]
+    fn test_indexblock_random_access() {
+        let disk = make_grid_disk(10);
+        let ib = IndexBlock::from(disk.as_slice());
+
+        for (pos, cell) in disk.iter().enumerate() {
+            assert_eq!(ib.get(pos).unwrap(), Some(*cell));
+        }
+        assert_eq!(ib.get(disk.len()).unwrap(), None);
+        assert_eq!(ib.get(usize::MAX).unwrap(), None);
+        assert_eq!(ib.first().unwrap(), disk.first().copied());
+        assert_eq!(ib.last().unwrap(), disk.last().copied());
+
+        for range in [0..3, 5..5, 10..40, 300..disk.len(), 300..(disk.len() + 10)] {
+            let expected: Vec<_> = disk
+                .iter()
+                .skip(range.start)
+                .take(range.len())
+                .copied()
+                .collect();
+            assert_eq!(
+                ib.iter_uncompressed_range(range)
+                    .unwrap()
+                    .collect::<Vec<_>>(),
+                expected
+            );
+        }
+        assert_eq!(
+            ib.iter_uncompressed_range(0..disk.len())
+                .unwrap()
+                .collect::<Vec<_>>(),
+            disk
+        );
+        assert_eq!(
+            ib.iter_uncompressed_range((disk.len() + 1)..(disk.len() + 5))
+                .unwrap()
+                .count(),
+            0
+        );
+
+        let empty = IndexBlock::<H3Cell>::from(Vec::new());
+        assert_eq!(empty.get(0).unwrap(), None);
+        assert_eq!(empty.first().unwrap(), None);
+        assert_eq!(empty.last().unwrap(), None);
+    }
+
     #[test]
     fn test_indexblock_contains() {
         let cell = H3Cell::try_from(0x89283080ddbffff_u64).unwrap();
@@ -15,7 +15,7 @@ use std::hash::Hash;
 pub use ahash::RandomState;
 pub use hashbrown;
 
-pub use compactedcellvec::CompactedCellVec;
+pub use compactedcellvec::{cells_from_text, cells_to_text, CompactedCellVec};
 pub use compressed::{Decompressor, IndexBlock};
 #[cfg(feature = "roaring")]
 pub use treemap::H3Treemap;
@@ -2,6 +2,8 @@ use std::borrow::Borrow;
 use std::error::Error;
 use std::marker::PhantomData;
 
+#[cfg(feature = "use-rayon")]
+use rayon::prelude::*;
 use roaring::RoaringTreemap;
 
 use crate::collections::ContainsIndex;
@@ -88,6 +90,12 @@ where
         self.treemap.is_disjoint(&rhs.treemap)
     }
 
+    /// number of values contained in both `self` and `rhs`, without building the intersection.
+    #[inline]
+    pub fn intersection_len(&self, rhs: &Self) -> usize {
+        self.treemap.intersection_len(&rhs.treemap) as usize
+    }
+
     #[inline]
     pub fn is_subset(&self, rhs: &Self) -> bool {
         self.treemap.is_subset(&rhs.treemap)
@@ -98,6 +106,24 @@ where
         self.treemap.is_superset(&rhs.treemap)
     }
 
+    /// add all values of `rhs` to `self`.
+    #[inline]
+    pub fn union_with(&mut self, rhs: &Self) {
+        self.treemap |= &rhs.treemap;
+    }
+
+    /// remove all values not contained in `rhs` from `self`.
+    #[inline]
+    pub fn intersect_with(&mut self, rhs: &Self) {
+        self.treemap &= &rhs.treemap;
+    }
+
+    /// remove all values contained in `rhs` from `self`.
+    #[inline]
+    pub fn difference_with(&mut self, rhs: &Self) {
+        self.treemap -= &rhs.treemap;
+    }
+
     pub fn iter(&self) -> Iter<T> {
         Iter {
             inner_iter: self.treemap.iter(),
@@ -120,6 +146,25 @@ where
         }
     }
 
+    /// create this struct from a parallel iterator. The values are collected and sorted
+    /// in parallel before creating the Treemap.
+    ///
+    /// Requires accumulating the whole iterator in memory for a short while.
+    #[cfg(feature = "use-rayon")]
+    pub fn from_iter_parallel<I>(iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        let mut h3indexes: Vec<_> = iter.into_par_iter().map(|c| c.h3index()).collect();
+        h3indexes.par_sort_unstable();
+        h3indexes.dedup();
+
+        Self {
+            treemap: RoaringTreemap::from_sorted_iter(h3indexes.drain(..)).unwrap(),
+            phantom_data: Default::default(),
+        }
+    }
+
     /// create this struct from an iterator over results. The iterator is consumed
     /// and sorted in memory before creating the Treemap - this can greatly
     /// reduce the creation time.
@@ -186,4 +231,49 @@ mod tests {
         }
         assert_eq!(treemap.iter().count(), 7);
     }
+
+    #[test]
+    fn set_operations() {
+        let idx = H3Cell::try_from(0x89283080ddbffff_u64).unwrap();
+        let disk: H3Treemap<H3Cell> = idx.grid_disk(2).unwrap().iter().collect();
+        let ring: H3Treemap<H3Cell> = idx.grid_ring_unsafe(2).unwrap().iter().collect();
+        let center: H3Treemap<H3Cell> = std::iter::once(idx).collect();
+
+        let mut union = center.clone();
+        union.union_with(&ring);
+        assert_eq!(union.len(), 1 + 12);
+        assert!(union.is_subset(&disk));
+
+        let mut intersection = disk.clone();
+        intersection.intersect_with(&ring);
+        assert_eq!(
+            intersection.iter().collect::<Vec<_>>(),
+            ring.iter().collect::<Vec<_>>()
+        );
+
+        let mut difference = disk.clone();
+        difference.difference_with(&ring);
+        assert_eq!(difference.len(), 7);
+        assert!(difference.is_disjoint(&ring));
+        assert!(difference.contains(&idx));
+    }
+
+    #[cfg(feature = "use-rayon")]
+    #[test]
+    fn from_iter_parallel() {
+        use rayon::prelude::*;
+
+        let idx = H3Cell::try_from(0x89283080ddbffff_u64).unwrap();
+        let cells: Vec<_> = idx.grid_disk(20).unwrap().iter().collect();
+        let expected: H3Treemap<H3Cell> = cells.iter().collect();
+
+        // duplicates are removed
+        let treemap =
+            H3Treemap::from_iter_parallel(cells.par_iter().chain(cells.par_iter()).copied());
+        assert_eq!(treemap.len(), cells.len());
+        assert_eq!(
+            treemap.iter().collect::<Vec<_>>(),
+            expected.iter().collect::<Vec<_>>()
+        );
+    }
 }
@@ -0,0 +1,218 @@
+//! Mapping of cells to a one-dimensional key for range-partitioned storage.
+//!
+//! The curve key of a cell consists of the base cell number followed by the digits of the
+//! cell, with the digits beyond the resolution of the cell set to `0`. In contrast to the
+//! raw H3 index, it does neither contain the mode nor the resolution and the unused digits
+//! are not set to `7`. The keys provide the following guarantees:
+//!
+//! * The keys of all descendants of a cell - at any resolution - are located within the
+//!   contiguous range returned by [`curve_range_for_cell`]. The ranges of the cells of a
+//!   resolution are disjoint, and the range of a cell is contained in the range of each of
+//!   its ancestors.
+//! * For cells of the same resolution, the keys are ordered like the raw H3 indexes and their
+//!   differences are equal to the differences of the raw H3 indexes. So the keys do not
+//!   improve the locality of datasets of a single resolution.
+//! * Cells of different resolutions are ordered by their location in the hierarchy instead
+//!   of by their resolution first, as the resolution is not part of the key. With raw H3
+//!   indexes, a difference in the resolution results in a difference of at least `2^52`.
+//! * The keys are smaller than `2^52`.
+//!
+//! A cell and the center child of the cell share the same key, so the resolution is required
+//! to convert a key back to a cell using [`curve_key_to_cell`].
+//!
+//! Spatial neighbors having a common parent only differ in the finer digits and are close to
+//! each other. Neighbors separated by the boundary of a coarse parent - or of a base cell -
+//! may still be far apart, like with the raw H3 indexes.
+use std::ops::RangeInclusive;
+
+use crate::{Error, H3Cell, Index, H3_MAX_RESOLUTION};
+
+/// number of bits of a single digit
+const DIGIT_BITS: u32 = 3;
+
+/// number of bits of the base cell number and all digits
+const KEY_BITS: u32 = 52;
+
+const KEY_MASK: u64 = (1 << KEY_BITS) - 1;
+
+/// bits of the mode of a cell index at its position in the index
+const CELL_MODE_BITS: u64 = 1 << 59;
+
+/// offset of the resolution within an index
+const RESOLUTION_OFFSET: u32 = 52;
+
+/// mask of the bits of the digits finer than `resolution`.
+const fn unused_digits_mask(resolution: u8) -> u64 {
+    (1 << (DIGIT_BITS * (H3_MAX_RESOLUTION - resolution) as u32)) - 1
+}
+
+/// The curve key of `cell`. See the [module documentation](crate::curve) for its guarantees.
+pub fn cell_to_curve_key(cell: H3Cell) -> u64 {
+    cell.h3index() & KEY_MASK & !unused_digits_mask(cell.resolution())
+}
+
+/// The cell of the given `resolution` identified by the curve `key`.
+///
+/// Fails when `key` is not the curve key of a valid cell of `resolution`.
+pub fn curve_key_to_cell(key: u64, resolution: u8) -> Result<H3Cell, Error> {
+    if resolution > H3_MAX_RESOLUTION {
+        return Err(Error::ResDomain);
+    }
+    let unused_mask = unused_digits_mask(resolution);
+    if key & !KEY_MASK != 0 || key & unused_mask != 0 {
+        return Err(Error::Domain);
+    }
+    H3Cell::try_from(
+        CELL_MODE_BITS | (u64::from(resolution) << RESOLUTION_OFFSET) | key | unused_mask,
+    )
+}
+
+/// The range of the curve keys of `cell` and all of its descendants.
+pub fn curve_range_for_cell(cell: H3Cell) -> RangeInclusive<u64> {
+    let key = cell_to_curve_key(cell);
+    key..=(key | unused_digits_mask(cell.resolution()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve::{cell_to_curve_key, curve_key_to_cell, curve_range_for_cell};
+    use crate::{res0_cells, Error, H3Cell, Index, H3_MAX_RESOLUTION};
+
+    /// xorshift pseudo-random numbers for reproducible samples
+    struct Rng(u64);
+
+    impl Rng {
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn next_f64(&mut self) -> f64 {
+            (self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64
+        }
+
+        fn cell(&mut self, resolution: u8) -> H3Cell {
+            let coordinate = (
+                self.next_f64() * 360.0 - 180.0,
+                self.next_f64() * 170.0 - 85.0,
+            );
+            H3Cell::from_coordinate(coordinate.into(), resolution).unwrap()
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for resolution in 0..=H3_MAX_RESOLUTION {
+            for _ in 0..100 {
+                let cell = rng.cell(resolution);
+                let key = cell_to_curve_key(cell);
+                assert!(key < (1 << 52));
+                assert_eq!(curve_key_to_cell(key, resolution).unwrap(), cell);
+            }
+        }
+
+        // pentagons and their children
+        for base_cell in res0_cells().iter() {
+            for cell in [base_cell, base_cell.center_child(5).unwrap()] {
+                let key = cell_to_curve_key(cell);
+                assert_eq!(curve_key_to_cell(key, cell.resolution()).unwrap(), cell);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_keys() {
+        let cell = H3Cell::from_coordinate((12.3, 45.4).into(), 6).unwrap();
+        let key = cell_to_curve_key(cell);
+
+        assert!(matches!(curve_key_to_cell(key, 16), Err(Error::ResDomain)));
+        // digits beyond the resolution are set
+        assert!(matches!(curve_key_to_cell(key | 1, 6), Err(Error::Domain)));
+        assert!(matches!(
+            curve_key_to_cell(key | (1 << 52), 6),
+            Err(Error::Domain)
+        ));
+        // base cell 127 does not exist
+        assert!(curve_key_to_cell(127 << 45, 0).is_err());
+        // the key of the cell is also the key of its center child
+        assert_eq!(
+            curve_key_to_cell(key, 8).unwrap(),
+            cell.center_child(8).unwrap()
+        );
+    }
+
+    #[test]
+    fn descendants_within_range() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for resolution in 0..=12 {
+            for _ in 0..5 {
+                let cell = rng.cell(resolution);
+                let range = curve_range_for_cell(cell);
+
+                for child_resolution in (resolution + 1)..=(resolution + 3) {
+                    for child in cell.get_children(child_resolution).unwrap().iter() {
+                        assert!(range.contains(&cell_to_curve_key(child)));
+                        let child_range = curve_range_for_cell(child);
+                        assert!(range.contains(child_range.start()));
+                        assert!(range.contains(child_range.end()));
+                    }
+                }
+
+                for neighbor in cell.grid_disk(1).unwrap().iter() {
+                    if neighbor != cell {
+                        assert!(!range.contains(&cell_to_curve_key(neighbor)));
+                    }
+                }
+            }
+        }
+
+        // the ranges of the base cells are disjoint
+        let mut ranges: Vec<_> = res0_cells().iter().map(curve_range_for_cell).collect();
+        ranges.sort_unstable_by_key(|range| *range.start());
+        for window in ranges.windows(2) {
+            assert!(window[0].end() < window[1].start());
+        }
+    }
+
+    #[test]
+    fn neighbor_key_distances() {
+        let mut rng = Rng(0xd1b5_4a32_d192_ed03);
+        let mut num_pairs = 0;
+        let mut same_resolution_key_sum = 0.0;
+        let mut same_resolution_raw_sum = 0.0;
+        let mut mixed_resolution_key_sum = 0.0;
+        let mut mixed_resolution_raw_sum = 0.0;
+
+        for _ in 0..500 {
+            let resolution = (rng.next_u64() % 10) as u8 + 2;
+            let cell = rng.cell(resolution);
+            for neighbor in cell.grid_disk(1).unwrap().iter() {
+                if neighbor == cell {
+                    continue;
+                }
+                num_pairs += 1;
+
+                let key_distance = cell_to_curve_key(cell).abs_diff(cell_to_curve_key(neighbor));
+                let raw_distance = cell.h3index().abs_diff(neighbor.h3index());
+                assert_eq!(key_distance, raw_distance);
+                same_resolution_key_sum += key_distance as f64;
+                same_resolution_raw_sum += raw_distance as f64;
+
+                // the neighbor at a finer resolution, like in datasets of mixed resolutions
+                let child = neighbor.center_child(resolution + 2).unwrap();
+                mixed_resolution_key_sum +=
+                    cell_to_curve_key(cell).abs_diff(cell_to_curve_key(child)) as f64;
+                mixed_resolution_raw_sum += cell.h3index().abs_diff(child.h3index()) as f64;
+            }
+        }
+        assert!(num_pairs > 2500);
+
+        let mean = |sum: f64| sum / num_pairs as f64;
+        assert!(mean(same_resolution_key_sum) <= mean(same_resolution_raw_sum));
+        assert!(mean(mixed_resolution_raw_sum) >= (1_u64 << 52) as f64);
+        assert!(mean(mixed_resolution_key_sum) * 4.0 < mean(mixed_resolution_raw_sum));
+    }
+}
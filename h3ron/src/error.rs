@@ -77,6 +77,18 @@ pub enum Error {
 
     #[error("decompression error")]
     DecompressionError(String),
+
+    /// Parsing the text representation of cells failed. `line` and `column` are 1-based.
+    #[error("text parse error at line {line}, column {column}: {message}")]
+    TextParseError {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    /// The binary representation of cells could not be read.
+    #[error("invalid binary representation: {0}")]
+    BinaryFormatError(String),
 }
 
 impl Error {
@@ -4,7 +4,7 @@
 //! # Features
 //!
 //! * **use-serde**: serde serialization/deserialization for most types of this crate.
-//! * **use-rayon**
+//! * **use-rayon**: Parallel construction of `collections::H3Treemap` using `H3Treemap::from_iter_parallel`.
 //! * **roaring**: Enables `collections::H3Treemap` based on the `roaring` crate.
 //! * **parse**: Parse [`H3Cell`] from different string representations using `H3Cell::from_str`.
 //!
@@ -29,6 +29,7 @@ use crate::collections::indexvec::IndexVec;
 pub mod algorithm;
 mod cell;
 pub mod collections;
+pub mod curve;
 mod directed_edge;
 mod direction;
 pub mod error;
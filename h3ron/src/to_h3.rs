@@ -4,8 +4,10 @@ use geo_types::{
 };
 
 use crate::collections::indexvec::IndexVec;
+use crate::collections::{H3CellMap, H3CellSet};
 use crate::error::check_valid_h3_resolution;
-use crate::{line, Error, H3Cell, Index};
+use crate::{line, Error, H3Cell, H3DirectedEdge, Index, ToPolygon};
+use geo::Intersects;
 use h3ron_h3_sys::{GeoLoop, GeoPolygon, LatLng};
 use std::os::raw::c_int;
 
@@ -192,3 +194,259 @@ pub fn polygon_to_cells(poly: &Polygon<f64>, h3_resolution: u8) -> Result<IndexV
         }
     })
 }
+
+/// The cells considered to be covered by a polygon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ContainmentMode {
+    /// Cells with their centroid located within the polygon. This is the polyfill
+    /// of [`polygon_to_cells`].
+    #[default]
+    ContainsCentroid,
+
+    /// Cells intersecting the polygon, including the cells only touching its boundary.
+    IntersectsBoundary,
+
+    /// Cells located completely within the polygon. Cells touching the boundary of the
+    /// polygon are not contained.
+    ContainsBoundary,
+}
+
+/// approximate length of one degree of latitude in meters
+const METERS_PER_DEGREE: f64 = 111_195.0;
+
+/// The cells covering `poly` at `h3_resolution` according to the `containment_mode`.
+///
+/// Starts with the cells of [`polygon_to_cells`] and refines the cells located along the
+/// boundary and the holes of the polygon by intersecting them with the nearby segments of the
+/// rings.
+///
+/// # Example
+///
+/// ```
+/// use geo_types::Rect;
+/// use h3ron::to_h3::{polygon_to_cells, polygon_to_cells_with_mode, ContainmentMode};
+///
+/// let poly = Rect::new((10.0, 20.0), (10.5, 20.5)).to_polygon();
+/// let count = |mode| polygon_to_cells_with_mode(&poly, 6, mode).unwrap().count();
+///
+/// let num_centroid_cells = polygon_to_cells(&poly, 6).unwrap().count();
+/// assert_eq!(count(ContainmentMode::ContainsCentroid), num_centroid_cells);
+/// assert!(count(ContainmentMode::IntersectsBoundary) > num_centroid_cells);
+/// assert!(count(ContainmentMode::ContainsBoundary) < num_centroid_cells);
+/// ```
+pub fn polygon_to_cells_with_mode(
+    poly: &Polygon<f64>,
+    h3_resolution: u8,
+    containment_mode: ContainmentMode,
+) -> Result<IndexVec<H3Cell>, Error> {
+    check_valid_h3_resolution(h3_resolution)?;
+    let centroid_cells = polygon_to_cells(poly, h3_resolution)?;
+    if containment_mode == ContainmentMode::ContainsCentroid {
+        return Ok(centroid_cells);
+    }
+
+    let segments_by_cell = ring_segments_by_cell(poly, h3_resolution)?;
+    let mut cells = IndexVec::new();
+    match containment_mode {
+        ContainmentMode::ContainsCentroid => unreachable!(),
+        ContainmentMode::IntersectsBoundary => {
+            let centroid_cells: H3CellSet = centroid_cells.iter().collect();
+            for cell in centroid_cells.iter() {
+                cells.push(*cell);
+            }
+            // a cell with the centroid outside the polygon can only intersect the polygon
+            // when the boundary of the polygon passes the cell.
+            for (cell, segments) in segments_by_cell.iter() {
+                if centroid_cells.contains(cell) {
+                    continue;
+                }
+                let cell_polygon = cell.to_polygon()?;
+                if segments
+                    .iter()
+                    .any(|segment| cell_polygon.intersects(segment))
+                {
+                    cells.push(*cell);
+                }
+            }
+        }
+        ContainmentMode::ContainsBoundary => {
+            for cell in centroid_cells.iter() {
+                if let Some(segments) = segments_by_cell.get(&cell) {
+                    let cell_polygon = cell.to_polygon()?;
+                    if segments
+                        .iter()
+                        .any(|segment| cell_polygon.intersects(segment))
+                    {
+                        continue;
+                    }
+                }
+                cells.push(cell);
+            }
+        }
+    }
+    cells.sort_unstable();
+    Ok(cells)
+}
+
+/// The cells covering any of the polygons of `mpoly` according to the `containment_mode`.
+///
+/// In contrast to [`ToH3Cells`] cells covered by multiple polygons are only contained once.
+pub fn multipolygon_to_cells_with_mode(
+    mpoly: &MultiPolygon<f64>,
+    h3_resolution: u8,
+    containment_mode: ContainmentMode,
+) -> Result<IndexVec<H3Cell>, Error> {
+    let mut cells = IndexVec::new();
+    for poly in &mpoly.0 {
+        let mut poly_cells = polygon_to_cells_with_mode(poly, h3_resolution, containment_mode)?;
+        cells.append(&mut poly_cells);
+    }
+    cells.sort_unstable();
+    cells.dedup();
+    Ok(cells)
+}
+
+/// The cells passed by the segments of the rings of `poly` together with the passing segments.
+///
+/// The segments are sampled in steps of half the average edge length. Every cell intersecting
+/// a segment is a neighbor of the cell of one of the samples.
+fn ring_segments_by_cell(
+    poly: &Polygon<f64>,
+    h3_resolution: u8,
+) -> Result<H3CellMap<Vec<Line<f64>>>, Error> {
+    let step = 0.5 * H3DirectedEdge::edge_length_avg_m(h3_resolution)? / METERS_PER_DEGREE;
+    let mut segments_by_cell: H3CellMap<Vec<Line<f64>>> = H3CellMap::default();
+    for ring in std::iter::once(poly.exterior()).chain(poly.interiors().iter()) {
+        for segment in ring.lines() {
+            let num_steps = (segment.dx().hypot(segment.dy()) / step).ceil().max(1.0) as usize;
+            let mut segment_cells = H3CellSet::default();
+            for i in 0..=num_steps {
+                let fraction = i as f64 / num_steps as f64;
+                let coordinate = Coordinate {
+                    x: segment.start.x + fraction * segment.dx(),
+                    y: segment.start.y + fraction * segment.dy(),
+                };
+                segment_cells.extend(
+                    H3Cell::from_coordinate(coordinate, h3_resolution)?
+                        .grid_disk(1)?
+                        .iter(),
+                );
+            }
+            for cell in segment_cells {
+                segments_by_cell.entry(cell).or_default().push(segment);
+            }
+        }
+    }
+    Ok(segments_by_cell)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{Contains, Intersects, Relate};
+    use geo_types::{LineString, Polygon};
+
+    use crate::collections::H3CellSet;
+    use crate::to_h3::{polygon_to_cells, polygon_to_cells_with_mode, ContainmentMode};
+    use crate::{H3Cell, ToCoordinate, ToPolygon};
+
+    fn square(min: f64, max: f64) -> LineString<f64> {
+        LineString::from(vec![
+            (min, min),
+            (max, min),
+            (max, max),
+            (min, max),
+            (min, min),
+        ])
+    }
+
+    fn cell_set(poly: &Polygon<f64>, h3_resolution: u8, mode: ContainmentMode) -> H3CellSet {
+        polygon_to_cells_with_mode(poly, h3_resolution, mode)
+            .unwrap()
+            .iter()
+            .collect()
+    }
+
+    #[test]
+    fn containment_modes_with_hole() {
+        let poly = Polygon::new(square(0.0, 1.0), vec![square(0.3, 0.7)]);
+        let h3_resolution = 6;
+        let centroid = cell_set(&poly, h3_resolution, ContainmentMode::ContainsCentroid);
+        let intersecting = cell_set(&poly, h3_resolution, ContainmentMode::IntersectsBoundary);
+        let contained = cell_set(&poly, h3_resolution, ContainmentMode::ContainsBoundary);
+
+        assert_eq!(
+            centroid,
+            polygon_to_cells(&poly, h3_resolution)
+                .unwrap()
+                .iter()
+                .collect::<H3CellSet>()
+        );
+        assert!(contained.is_subset(&centroid));
+        assert!(centroid.is_subset(&intersecting));
+        assert!(contained.len() < centroid.len());
+        assert!(centroid.len() < intersecting.len());
+
+        // compare against the predicates applied to all cells in the vicinity
+        let vicinity: H3CellSet =
+            polygon_to_cells(&Polygon::new(square(-0.2, 1.2), vec![]), h3_resolution)
+                .unwrap()
+                .iter()
+                .collect();
+        for cell in vicinity {
+            let cell_polygon = cell.to_polygon().unwrap();
+            assert_eq!(
+                centroid.contains(&cell),
+                poly.contains(&cell.to_coordinate().unwrap())
+            );
+            assert_eq!(
+                intersecting.contains(&cell),
+                poly.intersects(&cell_polygon),
+                "{:?}",
+                cell
+            );
+            assert_eq!(
+                contained.contains(&cell),
+                poly.relate(&cell_polygon).is_contains()
+                    && !poly.exterior().intersects(&cell_polygon)
+                    && !poly
+                        .interiors()
+                        .iter()
+                        .any(|ring| ring.intersects(&cell_polygon)),
+                "{:?}",
+                cell
+            );
+        }
+    }
+
+    #[test]
+    fn polygon_smaller_than_cell() {
+        let poly = Polygon::new(square(0.501, 0.502), vec![]);
+        let cell = H3Cell::from_coordinate((0.5015, 0.5015).into(), 5).unwrap();
+
+        assert!(cell_set(&poly, 5, ContainmentMode::ContainsCentroid).is_empty());
+        assert!(cell_set(&poly, 5, ContainmentMode::ContainsBoundary).is_empty());
+        let intersecting = cell_set(&poly, 5, ContainmentMode::IntersectsBoundary);
+        assert_eq!(intersecting.len(), 1);
+        assert!(intersecting.contains(&cell));
+    }
+
+    /// the boundary of the polygon passes exactly through the centroid of a cell
+    #[test]
+    fn boundary_through_centroid() {
+        let cell = H3Cell::from_coordinate((10.3, 20.6).into(), 7).unwrap();
+        let centroid = cell.to_coordinate().unwrap();
+        let poly = Polygon::new(
+            LineString::from(vec![
+                (centroid.x, centroid.y - 0.2),
+                (centroid.x + 0.2, centroid.y - 0.2),
+                (centroid.x + 0.2, centroid.y + 0.2),
+                (centroid.x, centroid.y + 0.2),
+                (centroid.x, centroid.y - 0.2),
+            ]),
+            vec![],
+        );
+
+        assert!(cell_set(&poly, 7, ContainmentMode::IntersectsBoundary).contains(&cell));
+        assert!(!cell_set(&poly, 7, ContainmentMode::ContainsBoundary).contains(&cell));
+    }
+}